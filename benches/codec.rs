@@ -0,0 +1,116 @@
+// Encoding and decoding the pool messages. The fixtures are the ones of the golden-vector tests, built
+// from the genesis block, so this runs without a network or GPU.
+
+use std::io::Cursor;
+
+use aleoxminer::{
+    message::{ProverCodec, ProverMessage, MSGPACK_FLAG},
+    testing::{notify, submit, submit_result},
+};
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn frame(message: ProverMessage) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    ProverCodec::default().encode(message, &mut bytes).unwrap();
+    bytes
+}
+
+fn formats(c: &mut Criterion) {
+    let messages: [(&str, fn() -> ProverMessage); 3] =
+        [("notify", notify), ("submit", submit), ("submit_result", submit_result)];
+    for (name, message) in messages {
+        let message = message();
+        let mut binary = Vec::new();
+        let mut json = Vec::new();
+        let mut msgpack = Vec::new();
+        message.serialize_into(&mut binary).unwrap();
+        message.serialize_into_json(&mut json).unwrap();
+        message.serialize_into_msgpack(&mut msgpack).unwrap();
+        let id = message.id();
+        println!(
+            "{}: {} bytes binary, {} bytes json, {} bytes msgpack",
+            name,
+            binary.len(),
+            json.len(),
+            msgpack.len()
+        );
+
+        let mut group = c.benchmark_group(name);
+        group.bench_function("encode_binary", |b| {
+            b.iter(|| {
+                let mut bytes = Vec::with_capacity(binary.len());
+                black_box(&message).serialize_into(&mut bytes).unwrap();
+                bytes
+            })
+        });
+        group.bench_function("encode_json", |b| {
+            b.iter(|| {
+                let mut bytes = Vec::with_capacity(json.len());
+                black_box(&message).serialize_into_json(&mut bytes).unwrap();
+                bytes
+            })
+        });
+        group.bench_function("encode_msgpack", |b| {
+            b.iter(|| {
+                let mut bytes = Vec::with_capacity(msgpack.len());
+                black_box(&message).serialize_into_msgpack(&mut bytes).unwrap();
+                bytes
+            })
+        });
+        // The deserializers read the id first.
+        let binary: Vec<u8> = std::iter::once(id).chain(binary).collect();
+        let json: Vec<u8> = std::iter::once(id).chain(json).collect();
+        let msgpack: Vec<u8> = std::iter::once(id | MSGPACK_FLAG).chain(msgpack).collect();
+        group.throughput(Throughput::Bytes(binary.len() as u64));
+        group.bench_function("decode_binary", |b| {
+            b.iter(|| ProverMessage::deserialize(&mut Cursor::new(black_box(&binary[..]))).unwrap())
+        });
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_function("decode_json", |b| {
+            b.iter(|| ProverMessage::deserialize_json(&mut Cursor::new(black_box(&json[..]))).unwrap())
+        });
+        group.throughput(Throughput::Bytes(msgpack.len() as u64));
+        group.bench_function("decode_msgpack", |b| {
+            b.iter(|| ProverMessage::deserialize_msgpack(&mut Cursor::new(black_box(&msgpack[..]))).unwrap())
+        });
+        group.finish();
+    }
+}
+
+fn stream(c: &mut Criterion) {
+    // What a pool sends: mostly results and canaries, a new template now and then.
+    let frames = [frame(notify()), frame(submit_result()), frame(ProverMessage::Canary)];
+    let mut stream = BytesMut::new();
+    for index in 0..10_000 {
+        let frame = match index % 100 {
+            0 => &frames[0],
+            n if n % 2 == 0 => &frames[1],
+            _ => &frames[2],
+        };
+        stream.extend_from_slice(frame);
+    }
+
+    let mut group = c.benchmark_group("stream");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.bench_function("decode_10k_frames", |b| {
+        b.iter_batched(
+            || stream.clone(),
+            |mut stream| {
+                let mut codec = ProverCodec::default();
+                let mut count = 0;
+                while let Some(message) = codec.decode(&mut stream).unwrap() {
+                    black_box(message);
+                    count += 1;
+                }
+                assert_eq!(count, 10_000);
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, formats, stream);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+// Attempts per second on the fixture template: the former dispatch spawning a task for every attempt
+// against the current one, long-lived workers looping on a dedicated pool. Loads the proving parameters.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use aleoxminer::{
+    backend::{CpuBackend, ProvingBackend},
+    testing,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rand::thread_rng;
+use rayon::ThreadPoolBuilder;
+
+fn threads() -> usize {
+    num_cpus::get().clamp(1, 16)
+}
+
+fn proving(c: &mut Criterion) {
+    let template = testing::fixed_template();
+    let terminator = AtomicBool::new(false);
+    let pool = ThreadPoolBuilder::new().num_threads(threads()).build().unwrap();
+    // The first proof loads the parameters, keep it out of the measurements.
+    CpuBackend.prove(&template, &terminator, &mut thread_rng()).unwrap();
+
+    let mut group = c.benchmark_group("proving");
+    group.sample_size(10).measurement_time(Duration::from_secs(60)).throughput(Throughput::Elements(1));
+    group.bench_function("spawn_per_attempt", |b| {
+        b.iter_custom(|attempts| {
+            let started = Instant::now();
+            pool.scope(|scope| {
+                for _ in 0..attempts {
+                    scope.spawn(|_| {
+                        CpuBackend.prove(&template, &terminator, &mut thread_rng()).unwrap();
+                    });
+                }
+            });
+            started.elapsed()
+        })
+    });
+    group.bench_function("worker_loop", |b| {
+        b.iter_custom(|attempts| {
+            let next = AtomicU64::new(0);
+            let started = Instant::now();
+            pool.scope(|scope| {
+                for _ in 0..threads() {
+                    scope.spawn(|_| {
+                        let mut rng = thread_rng();
+                        while next.fetch_add(1, Ordering::SeqCst) < attempts {
+                            CpuBackend.prove(&template, &terminator, &mut rng).unwrap();
+                        }
+                    });
+                }
+            });
+            started.elapsed()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, proving);
+criterion_main!(benches);
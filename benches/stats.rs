@@ -0,0 +1,67 @@
+// The per-proof bookkeeping: share counters and the latency histogram, contended by 8 provers.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+#[allow(dead_code)]
+#[path = "../src/histogram.rs"]
+mod histogram;
+
+use histogram::LatencyHistogram;
+
+const THREADS: usize = 8;
+const UPDATES: u64 = 10_000;
+
+#[derive(Default)]
+struct Counters {
+    proofs: AtomicU32,
+    valid_shares: AtomicU32,
+    latency: LatencyHistogram,
+}
+
+fn record(counters: &Counters, index: u64) {
+    counters.proofs.fetch_add(1, Ordering::SeqCst);
+    if index % 64 == 0 {
+        counters.valid_shares.fetch_add(1, Ordering::SeqCst);
+    }
+    counters.latency.record(Duration::from_micros(500 + index % 5000));
+}
+
+fn contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stats");
+    group.throughput(Throughput::Elements(UPDATES));
+    group.bench_function("record_single_thread", |b| {
+        let counters = Counters::default();
+        b.iter(|| (0..UPDATES).for_each(|index| record(&counters, index)))
+    });
+    group.throughput(Throughput::Elements(UPDATES * THREADS as u64));
+    group.bench_function("record_8_threads", |b| {
+        let counters = Arc::new(Counters::default());
+        b.iter(|| {
+            let threads: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let counters = counters.clone();
+                    thread::spawn(move || (0..UPDATES).for_each(|index| record(&counters, index)))
+                })
+                .collect();
+            threads.into_iter().for_each(|thread| thread.join().unwrap());
+        })
+    });
+    group.bench_function("snapshot", |b| {
+        let counters = Counters::default();
+        (0..UPDATES).for_each(|index| record(&counters, index));
+        b.iter(|| counters.latency.snapshot())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, contention);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+// A pool that accepts every miner and every share without checking them, for trying miners and
+// proxies locally. It sends one job built from the genesis block, and resumes the sessions it
+// started since it was launched.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+//
+//   cargo run --example echo_pool -- 127.0.0.1:4040
+
+use aleoxminer::{
+    message::{Code, ProverCodec},
+    server::{Action, PoolSession},
+};
+use futures_util::sink::SinkExt;
+use snarkvm::dpc::{testnet2::Testnet2, BlockTemplate};
+use snarkvm::traits::Network;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+static SESSIONS: AtomicU64 = AtomicU64::new(0);
+
+// The requested session if this pool started it, a new one otherwise.
+fn session_token(requested: Option<&str>) -> String {
+    let started = SESSIONS.load(Ordering::SeqCst);
+    match requested.and_then(|token| token.strip_prefix("echo-")?.parse::<u64>().ok()) {
+        Some(session) if session < started => format!("echo-{}", session),
+        _ => format!("echo-{}", SESSIONS.fetch_add(1, Ordering::SeqCst)),
+    }
+}
+
+fn template() -> BlockTemplate<Testnet2> {
+    let genesis = Testnet2::genesis_block();
+    let coinbase = genesis
+        .to_coinbase_transaction()
+        .expect("genesis has a coinbase transaction")
+        .to_records()
+        .next()
+        .expect("coinbase has a record");
+    // The miners drop jobs with a timestamp far off their clock.
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    BlockTemplate::new(
+        genesis.previous_block_hash(),
+        genesis.height() + 1,
+        now,
+        genesis.difficulty_target(),
+        genesis.cumulative_weight(),
+        genesis.previous_ledger_root(),
+        genesis.transactions().clone(),
+        coinbase,
+    )
+}
+
+async fn serve(socket: TcpStream, peer: String) -> anyhow::Result<()> {
+    let mut framed = Framed::new(socket, ProverCodec::default());
+    let mut session = PoolSession::new();
+    while let Some(message) = framed.next().await {
+        match session.receive(message?) {
+            Action::Authorize { account, worker, .. } => {
+                // Honors a d= difficulty hint in the password.
+                let target = session.difficulty_hint().map_or(u64::MAX / 1000, |difficulty| u64::MAX / difficulty);
+                println!("{}: authorized {}.{} at difficulty {}", peer, account, worker, u64::MAX / target);
+                if session.msgpack() {
+                    framed.codec_mut().use_msgpack();
+                }
+                let token = session_token(session.session_token());
+                if session.resumes_sessions() {
+                    println!("{}: session {}", peer, token);
+                }
+                framed.send(session.authorized_session(&token)).await?;
+                if let Some(notify) = session.notify(template(), target) {
+                    framed.send(notify).await?;
+                }
+                // Nothing is ever paid out here.
+                if let Some(info) = session.pool_info(BTreeMap::from([("balance".to_string(), "0".to_string())])) {
+                    framed.send(info).await?;
+                }
+            }
+            Action::ForwardShare { height, nonce, .. } => {
+                println!("{}: share for block {} with nonce {}", peer, height, nonce);
+                framed.send(session.share_result(Code::Success, None)).await?;
+            }
+            Action::Reply(message) => framed.send(message).await?,
+            Action::ProofRate(rate) => println!("{}: {:.2} p/s", peer, rate as f64 / 100.0),
+            Action::JobAck { height, lag, .. } => println!("{}: proving block {}, {} behind", peer, height, lag),
+            Action::ProtocolViolation(reason) => {
+                println!("{}: closing, {}", peer, reason);
+                break;
+            }
+        }
+    }
+    println!("{}: disconnected after {:?}", peer, session.stats());
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let address = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:4040".to_string());
+    let listener = TcpListener::bind(&address).await?;
+    println!("Listening on {}", address);
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = serve(socket, peer.to_string()).await {
+                println!("{}: {}", peer, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,36 @@
+// Connects to a pool with the library's client and prints the height of every new template.
+//
+//   cargo run --example embed_client -- <pool host:port> <aleo address>
+
+use std::str::FromStr;
+
+use aleoxminer::{channel, client, events::EventBus, message::ProtocolLimits, prover::ProverEvent};
+use snarkvm::dpc::{testnet2::Testnet2, Address};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let pool = args.next().ok_or_else(|| anyhow::anyhow!("usage: embed_client <pool> <address>"))?;
+    let address = Address::<Testnet2>::from_str(&args.next().unwrap_or_default())?;
+
+    let client = client::Client::init(
+        None,
+        Some("example".to_string()),
+        Some(address),
+        pool,
+        EventBus::new(),
+        ProtocolLimits::default(),
+        1024,
+    );
+    let (sender, mut receiver) = channel::channel("prover_events", 1024);
+    client::start(sender, client.clone());
+
+    while let Some(event) = receiver.recv().await {
+        match event {
+            ProverEvent::NewWork(_, template, _) => println!("New work for height {}", template.block_height()),
+            ProverEvent::Result { accepted, height, .. } => println!("Share at height {} accepted: {}", height, accepted),
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// Prints the miner's events in colour through `MinerHooks`. Only the pool connection is started
+// here, shares show up once a prover is attached to the client as in the binary.
+//
+//   cargo run --example share_hooks -- <pool host:port> <aleo address>
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use aleoxminer::{channel, client, events::EventBus, hooks::MinerHooks, message::{Code, ProtocolLimits}};
+use ansi_term::Colour::{Cyan, Green, Red, Yellow};
+use snarkvm::dpc::{testnet2::Testnet2, Address};
+
+struct Printer;
+
+impl MinerHooks for Printer {
+    fn on_connected(&self, server: &str) {
+        println!("{}", Green.paint(format!("Connected to {}", server)));
+    }
+
+    fn on_disconnected(&self, server: &str, reason: &str) {
+        println!("{}", Red.paint(format!("Disconnected from {}: {}", server, reason)));
+    }
+
+    fn on_new_job(&self, height: u32, target: u64) {
+        println!("{}", Cyan.paint(format!("New job for block {}, target {}", height, target)));
+    }
+
+    fn on_share_accepted(&self, latency: Option<Duration>) {
+        let latency = latency.map(|latency| format!(" in {} ms", latency.as_millis())).unwrap_or_default();
+        println!("{}", Green.bold().paint(format!("Share accepted{}", latency)));
+    }
+
+    fn on_share_rejected(&self, code: &Code, message: Option<&str>) {
+        println!("{}", Red.bold().paint(format!("Share rejected: {:?} {}", code, message.unwrap_or(""))));
+    }
+
+    fn on_rate(&self, rate: f64) {
+        println!("{}", Yellow.paint(format!("{:.2} proofs per second", rate)));
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let pool = args.next().ok_or_else(|| anyhow::anyhow!("usage: share_hooks <pool> <address>"))?;
+    let address = Address::<Testnet2>::from_str(&args.next().unwrap_or_default())?;
+
+    let client = client::Client::init(
+        None,
+        Some("hooks".to_string()),
+        Some(address),
+        pool,
+        EventBus::new(),
+        ProtocolLimits::default(),
+        1024,
+    );
+    client.set_hooks(Arc::new(Printer));
+    let (sender, mut receiver) = channel::channel("prover_events", 1024);
+    client::start(sender, client.clone());
+    // Nobody proves, the work is dropped.
+    while receiver.recv().await.is_some() {}
+    Ok(())
+}
@@ -0,0 +1,32 @@
+// Feeds arbitrary bytes to the frame decoder in chunks of arbitrary sizes, as they would arrive
+// from the socket. Decoding may fail but must not panic.
+#![no_main]
+
+use aleoxminer::message::{ProtocolLimits, ProverCodec};
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the chunk size, the frame cap is small enough to hit it.
+    let (chunk, data) = match data.split_first() {
+        Some((chunk, data)) => (*chunk as usize + 1, data),
+        None => return,
+    };
+    let mut codec = ProverCodec::new(ProtocolLimits {
+        max_frame_size: 64 * 1024,
+        max_string_length: 256,
+    });
+    let mut buffer = BytesMut::new();
+    for bytes in data.chunks(chunk) {
+        buffer.extend_from_slice(bytes);
+        loop {
+            match codec.decode(&mut buffer) {
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                // The connection is dropped after an error.
+                Err(_) => return,
+            }
+        }
+    }
+});
@@ -0,0 +1,14 @@
+// Feeds arbitrary payloads to every deserializer, the first byte is the message id. They may fail
+// but must not panic.
+#![no_main]
+
+use std::io::Cursor;
+
+use aleoxminer::message::ProverMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ProverMessage::deserialize(&mut Cursor::new(data));
+    let _ = ProverMessage::deserialize_json(&mut Cursor::new(data));
+    let _ = ProverMessage::deserialize_msgpack(&mut Cursor::new(data));
+});
@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use crate::estimate;
+
+/// The alert threshold is at least this many expected share intervals, so slow miners on high
+/// difficulty pools aren't flagged just for being unlucky.
+const EXPECTED_INTERVALS: f64 = 5.0;
+/// The scaled threshold stops here, so a near-zero rate doesn't keep an outage quiet for days.
+const MAX_THRESHOLD: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertChange {
+    /// No share has been accepted for the contained duration
+    Raised(Duration),
+    /// Shares are accepted again after the contained outage
+    Cleared(Duration),
+}
+
+/// Expected time between shares at `rate` proofs per second against `target`.
+pub fn expected_share_interval(rate: f64, target: u64) -> Option<Duration> {
+    if !rate.is_finite() || rate <= 0.0 {
+        return None;
+    }
+    let seconds = estimate::expected_attempts(target) / rate;
+    if !seconds.is_finite() || seconds > u64::MAX as f64 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Alert threshold: the configured minimum, scaled up to the expected share interval but not past
+/// `MAX_THRESHOLD` unless the minimum itself is longer.
+pub fn threshold(base: Duration, expected_interval: Option<Duration>) -> Duration {
+    match expected_interval {
+        Some(interval) => {
+            let scaled = interval.as_secs_f64() * EXPECTED_INTERVALS;
+            base.max(Duration::from_secs_f64(scaled.min(MAX_THRESHOLD.as_secs_f64())))
+        }
+        None => base,
+    }
+}
+
+/// Raises an alert when no share was accepted for longer than a threshold.
+pub struct ShareAlert {
+    base: Duration,
+    last_accepted: Instant,
+    raised: Option<Instant>,
+}
+
+impl ShareAlert {
+    pub fn new(base: Duration, now: Instant) -> Self {
+        Self {
+            base,
+            last_accepted: now,
+            raised: None,
+        }
+    }
+
+    pub fn set_base(&mut self, base: Duration) {
+        self.base = base;
+    }
+
+    /// Clears a raised alert, reporting the time since the previous accepted share as the outage.
+    pub fn accepted(&mut self, now: Instant) -> Option<AlertChange> {
+        let outage = now.saturating_duration_since(self.last_accepted);
+        self.last_accepted = now;
+        self.raised.take().map(|_| AlertChange::Cleared(outage))
+    }
+
+    /// Restarts the clock while proving is paused so the pause itself doesn't raise the alert.
+    pub fn hold(&mut self, now: Instant) {
+        if self.raised.is_none() {
+            self.last_accepted = now;
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant, expected_interval: Option<Duration>) -> Option<AlertChange> {
+        if self.raised.is_some() {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.last_accepted);
+        if elapsed > threshold(self.base, expected_interval) {
+            self.raised = Some(now);
+            return Some(AlertChange::Raised(elapsed));
+        }
+        None
+    }
+
+    /// Time since the last accepted share if the alert is raised.
+    pub fn raised(&self, now: Instant) -> Option<Duration> {
+        self.raised.map(|_| now.saturating_duration_since(self.last_accepted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_secs(15 * 60);
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn threshold_scales_with_the_expected_interval() {
+        assert_eq!(threshold(BASE, None), BASE);
+        assert_eq!(threshold(BASE, Some(minutes(1))), BASE);
+        assert_eq!(threshold(BASE, Some(minutes(10))), minutes(50));
+    }
+
+    #[test]
+    fn threshold_is_capped() {
+        assert_eq!(threshold(BASE, Some(minutes(24 * 60))), MAX_THRESHOLD);
+        assert_eq!(threshold(BASE, Some(Duration::MAX)), MAX_THRESHOLD);
+        assert_eq!(threshold(MAX_THRESHOLD * 2, Some(Duration::MAX)), MAX_THRESHOLD * 2);
+        // Nearly no rate at all still gives an interval, and the alert.
+        assert_eq!(threshold(BASE, expected_share_interval(1e-9, u64::MAX)), MAX_THRESHOLD);
+    }
+
+    #[test]
+    fn expected_interval_needs_a_rate() {
+        assert_eq!(expected_share_interval(0.0, 100), None);
+        assert_eq!(expected_share_interval(f64::NAN, 100), None);
+        assert!(expected_share_interval(10.0, 100).is_some());
+    }
+
+    #[test]
+    fn raises_once_and_clears_with_the_outage() {
+        let start = Instant::now();
+        let mut alert = ShareAlert::new(BASE, start);
+        alert.accepted(start + minutes(5));
+        assert_eq!(alert.poll(start + minutes(19), None), None);
+        assert_eq!(alert.poll(start + minutes(21), None), Some(AlertChange::Raised(minutes(16))));
+        assert_eq!(alert.poll(start + minutes(30), None), None);
+        assert_eq!(alert.raised(start + minutes(30)), Some(minutes(25)));
+        // The outage runs from the last accepted share, not from the alert.
+        assert_eq!(alert.accepted(start + minutes(45)), Some(AlertChange::Cleared(minutes(40))));
+        assert_eq!(alert.raised(start + minutes(46)), None);
+        assert_eq!(alert.accepted(start + minutes(50)), None);
+    }
+
+    #[test]
+    fn pauses_hold_the_clock() {
+        let start = Instant::now();
+        let mut alert = ShareAlert::new(BASE, start);
+        alert.hold(start + minutes(60));
+        assert_eq!(alert.poll(start + minutes(70), None), None);
+        assert!(alert.poll(start + minutes(76), None).is_some());
+        // A raised alert isn't cleared by pausing.
+        alert.hold(start + minutes(80));
+        assert_eq!(alert.raised(start + minutes(80)), Some(minutes(20)));
+    }
+
+    #[test]
+    fn base_changes_apply_to_the_next_poll() {
+        let start = Instant::now();
+        let mut alert = ShareAlert::new(BASE, start);
+        alert.set_base(minutes(5));
+        assert!(alert.poll(start + minutes(6), None).is_some());
+    }
+}
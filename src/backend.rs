@@ -0,0 +1,67 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use anyhow::Result;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use snarkvm::dpc::{testnet2::Testnet2, BlockHeader, BlockTemplate};
+
+#[cfg(feature = "cuda")]
+use crate::gpu::CudaBackend;
+
+/// Computes PoSW proofs for a block template on one device.
+pub trait ProvingBackend: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Proves one random nonce of `template`. Returns early with an error once `terminator` is set.
+    fn prove(
+        &self,
+        template: &BlockTemplate<Testnet2>,
+        terminator: &AtomicBool,
+        rng: &mut dyn RngCore,
+    ) -> Result<BlockHeader<Testnet2>>;
+
+    /// Whether the PoSW proving parameters have to be loaded before the first proof.
+    fn needs_parameters(&self) -> bool {
+        true
+    }
+
+    /// Loads the PoSW proving parameters, called once before the first proof if `needs_parameters`.
+    fn load_parameters(&self) -> Result<()> {
+        Testnet2::posw();
+        Ok(())
+    }
+}
+
+/// Proves on the rayon pool the call is made from.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuBackend;
+
+impl ProvingBackend for CpuBackend {
+    fn name(&self) -> String {
+        "CPU".to_string()
+    }
+
+    fn prove(
+        &self,
+        template: &BlockTemplate<Testnet2>,
+        terminator: &AtomicBool,
+        rng: &mut dyn RngCore,
+    ) -> Result<BlockHeader<Testnet2>> {
+        // snarkVM wants a CryptoRng, seeded from the caller's so a seeded caller stays reproducible.
+        let mut rng = ChaChaRng::from_rng(rng)?;
+        BlockHeader::mine_once_unchecked(template, terminator, &mut rng, -1)
+    }
+}
+
+/// The backend of a device from the `--cuda` list, -1 being the CPU.
+pub fn for_device(device: i16) -> Arc<dyn ProvingBackend> {
+    #[cfg(feature = "cuda")]
+    {
+        if device >= 0 {
+            return Arc::new(CudaBackend::new(device));
+        }
+    }
+    #[cfg(not(feature = "cuda"))]
+    let _ = device;
+    Arc::new(CpuBackend)
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use tokio::task;
+
+use crate::{selftest, units};
+
+/// Proves the self-test fixture `iterations` times on every device and prints the proof time
+/// and rate. Devices are measured one after another, -1 being the CPU.
+pub async fn run(devices: &[i16], threads: usize, iterations: u32) -> Result<()> {
+    for device in devices.iter().copied() {
+        let name = selftest::device_name(device);
+        let mut durations: Vec<Duration> = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations.max(1) {
+            let duration = task::spawn_blocking(move || selftest::prove_fixture(device, threads))
+                .await
+                .map_err(|e| anyhow!("benchmark on {} aborted: {}", name, e))??;
+            durations.push(duration);
+        }
+        durations.sort();
+        let total: Duration = durations.iter().sum();
+        let mean = total / durations.len() as u32;
+        println!(
+            "{}: {} proofs, median {} ms, min {} ms, max {} ms, {}",
+            name,
+            durations.len(),
+            durations[durations.len() / 2].as_millis(),
+            durations[0].as_millis(),
+            durations[durations.len() - 1].as_millis(),
+            units::format_rate(1.0 / mean.as_secs_f64())
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_SHA: &str = env!("ALEOXMINER_GIT_SHA");
+/// UTC, RFC 3339
+pub const BUILD_DATE: &str = env!("ALEOXMINER_BUILD_DATE");
+/// Shown by `--version`.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("ALEOXMINER_GIT_SHA"),
+    ", built ",
+    env!("ALEOXMINER_BUILD_DATE"),
+    ")"
+);
+
+/// Optional features compiled into this binary.
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "cuda") {
+        features.push("cuda");
+    }
+    if cfg!(feature = "nvml") {
+        features.push("nvml");
+    }
+    if cfg!(feature = "tls") {
+        features.push("tls");
+    }
+    if cfg!(feature = "metrics") {
+        features.push("metrics");
+    }
+    if cfg!(feature = "tui") {
+        features.push("tui");
+    }
+    if cfg!(feature = "mqtt") {
+        features.push("mqtt");
+    }
+    features
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub build_date: &'static str,
+    pub features: Vec<&'static str>,
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: VERSION,
+            git_sha: GIT_SHA,
+            build_date: BUILD_DATE,
+            features: features(),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AleoXMiner {} ({}, built {}, {}/{}", self.version, self.git_sha, self.build_date, self.os, self.arch)?;
+        if self.features.is_empty() {
+            write!(f, ")")
+        } else {
+            write!(f, ", features: {})", self.features.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        prover::Prover,
+        status::Status,
+        testing::{self, FakeBackend},
+    };
+
+    #[test]
+    fn describes_the_build() {
+        let build = BuildInfo::current();
+        for value in [build.version, build.git_sha, build.build_date, build.os, build.arch] {
+            assert!(!value.is_empty());
+        }
+        assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+        assert!(chrono::DateTime::parse_from_rfc3339(BUILD_DATE).is_ok(), "{}", BUILD_DATE);
+        assert_eq!(LONG_VERSION, format!("{} ({}, built {})", VERSION, GIT_SHA, BUILD_DATE));
+    }
+
+    #[test]
+    fn lists_the_compiled_features() {
+        let features = features();
+        assert_eq!(features.contains(&"tls"), cfg!(feature = "tls"));
+        assert_eq!(features.contains(&"metrics"), cfg!(feature = "metrics"));
+        assert_eq!(features.contains(&"cuda"), cfg!(feature = "cuda"));
+    }
+
+    #[test]
+    fn formats_the_banner() {
+        let mut build = BuildInfo {
+            version: "1.2.3",
+            git_sha: "0123456789ab",
+            build_date: "2026-01-02T03:04:05Z",
+            features: vec!["tls", "metrics"],
+            os: "linux",
+            arch: "x86_64",
+        };
+        assert_eq!(
+            build.to_string(),
+            "AleoXMiner 1.2.3 (0123456789ab, built 2026-01-02T03:04:05Z, linux/x86_64, features: tls, metrics)"
+        );
+        build.features.clear();
+        assert_eq!(build.to_string(), "AleoXMiner 1.2.3 (0123456789ab, built 2026-01-02T03:04:05Z, linux/x86_64)");
+    }
+
+    #[tokio::test]
+    async fn reaches_the_status() {
+        let client = testing::client("127.0.0.1:1", "build");
+        let prover = Prover::new(testing::prover_config(1, FakeBackend::new(Duration::ZERO)), client.clone()).unwrap();
+        let status = Status::new(&prover.stats(), &client.stats(), "pool", "build");
+        assert_eq!(status.version, VERSION);
+        assert_eq!((status.build.version, status.build.git_sha), (VERSION, GIT_SHA));
+        assert_eq!(status.build.build_date, BUILD_DATE);
+        assert_eq!(status.build.features, features());
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["build"]["git_sha"], GIT_SHA);
+        assert_eq!(json["build"]["os"], std::env::consts::OS);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+use tokio::sync::mpsc::{
+    self,
+    error::{SendError, TrySendError},
+};
+
+/// Depth and backpressure counters of one internal channel. Without the `metrics` feature nothing
+/// is recorded and the counters stay at 0.
+#[derive(Debug)]
+pub struct ChannelMetrics {
+    name: &'static str,
+    capacity: usize,
+    high_watermark: AtomicUsize,
+    sends: AtomicU64,
+    // Nanoseconds senders waited for room.
+    send_wait: AtomicU64,
+    // Sends that found the channel full, or events subscribers missed.
+    overflows: AtomicU64,
+}
+
+/// Snapshot of `ChannelMetrics`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub name: &'static str,
+    pub capacity: usize,
+    /// Most messages queued at once
+    pub high_watermark: usize,
+    pub sends: u64,
+    /// Total time senders waited for room
+    pub send_wait: Duration,
+    pub overflows: u64,
+}
+
+impl ChannelMetrics {
+    pub fn new(name: &'static str, capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            capacity,
+            high_watermark: Default::default(),
+            sends: Default::default(),
+            send_wait: Default::default(),
+            overflows: Default::default(),
+        })
+    }
+
+    #[inline]
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub fn record_depth(&self, depth: usize) {
+        #[cfg(feature = "metrics")]
+        self.high_watermark.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    #[inline]
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub fn record_send(&self, waited: Duration) {
+        #[cfg(feature = "metrics")]
+        {
+            self.sends.fetch_add(1, Ordering::Relaxed);
+            self.send_wait.fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub fn record_overflow(&self, count: u64) {
+        #[cfg(feature = "metrics")]
+        self.overflows.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            name: self.name,
+            capacity: self.capacity,
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            sends: self.sends.load(Ordering::Relaxed),
+            send_wait: Duration::from_nanos(self.send_wait.load(Ordering::Relaxed)),
+            overflows: self.overflows.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Bounded channel whose sender records into `ChannelMetrics` named `name`.
+pub fn channel<T>(name: &'static str, capacity: usize) -> (InstrumentedSender<T>, mpsc::Receiver<T>) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let metrics = ChannelMetrics::new(name, capacity);
+    (InstrumentedSender { sender, metrics }, receiver)
+}
+
+/// `mpsc::Sender` recording the queue depth, the time spent waiting for room and full channels.
+#[derive(Debug)]
+pub struct InstrumentedSender<T> {
+    sender: mpsc::Sender<T>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> Clone for InstrumentedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> InstrumentedSender<T> {
+    pub fn metrics(&self) -> &Arc<ChannelMetrics> {
+        &self.metrics
+    }
+
+    #[inline]
+    fn record_depth(&self) {
+        #[cfg(feature = "metrics")]
+        self.metrics
+            .record_depth(self.metrics.capacity.saturating_sub(self.sender.capacity()));
+    }
+
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+        #[cfg(feature = "metrics")]
+        if self.sender.capacity() == 0 {
+            self.metrics.record_overflow(1);
+        }
+        self.sender.send(value).await?;
+        #[cfg(feature = "metrics")]
+        self.metrics.record_send(started.elapsed());
+        self.record_depth();
+        Ok(())
+    }
+
+    /// `send` for threads outside of the runtime.
+    pub fn blocking_send(&self, value: T) -> Result<(), SendError<T>> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+        #[cfg(feature = "metrics")]
+        if self.sender.capacity() == 0 {
+            self.metrics.record_overflow(1);
+        }
+        self.sender.blocking_send(value)?;
+        #[cfg(feature = "metrics")]
+        self.metrics.record_send(started.elapsed());
+        self.record_depth();
+        Ok(())
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self.sender.try_send(value) {
+            Ok(()) => {
+                self.metrics.record_send(Duration::ZERO);
+                self.record_depth();
+                Ok(())
+            }
+            Err(e) => {
+                if let TrySendError::Full(_) = e {
+                    self.metrics.record_overflow(1);
+                }
+                Err(e)
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+// Fault injection for the pool connection, only built with the `chaos` feature. Faults are placed
+// at byte offsets of the stream, the seed makes garbage and fragment sizes reproducible.
+//
+// Spec: comma separated, e.g. `seed=7,fragment=16,stall@1200:5s,garbage@4000:32,close@90000`
+//   seed=N             seed of the random source
+//   fragment=N         reads return at most a random 1..=N bytes
+//   stall@OFFSET:DUR   reads stop at OFFSET for DUR, then resume
+//   garbage@OFFSET:N   N random bytes appear in the read stream at OFFSET
+//   duplicate@OFFSET:N the last N bytes read (at most 4096) are read again at OFFSET
+//   close@OFFSET       the read stream ends at OFFSET
+//   drop@OFFSET:N      N written bytes starting at OFFSET are discarded but reported as written
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep, Sleep},
+};
+
+use crate::schedule;
+
+const HISTORY: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReadFault {
+    Stall(Duration),
+    Garbage(usize),
+    Duplicate(usize),
+    Close,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChaosConfig {
+    seed: u64,
+    fragment: Option<usize>,
+    // By offset, in order.
+    reads: Vec<(u64, ReadFault)>,
+    drops: Vec<(u64, u64)>,
+}
+
+impl FromStr for ChaosConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let number = |value: &str| value.parse::<u64>().map_err(|_| anyhow!("{}: expected a number", item));
+            if let Some(seed) = item.strip_prefix("seed=") {
+                config.seed = number(seed)?;
+                continue;
+            }
+            if let Some(fragment) = item.strip_prefix("fragment=") {
+                config.fragment = Some(number(fragment)?.max(1) as usize);
+                continue;
+            }
+            let (name, position) = item
+                .split_once('@')
+                .ok_or_else(|| anyhow!("{}: expected name@offset", item))?;
+            let (offset, argument) = match position.split_once(':') {
+                Some((offset, argument)) => (number(offset)?, Some(argument)),
+                None => (number(position)?, None),
+            };
+            let argument = || argument.ok_or_else(|| anyhow!("{}: missing the :argument", item));
+            match name {
+                "stall" => {
+                    let duration = schedule::parse_duration(argument()?).map_err(|e| anyhow!("{}: {}", item, e))?;
+                    config.reads.push((offset, ReadFault::Stall(duration)));
+                }
+                "garbage" => config.reads.push((offset, ReadFault::Garbage(number(argument()?)? as usize))),
+                "duplicate" => config.reads.push((offset, ReadFault::Duplicate(number(argument()?)? as usize))),
+                "close" => config.reads.push((offset, ReadFault::Close)),
+                "drop" => config.drops.push((offset, number(argument()?)?)),
+                _ => return Err(anyhow!("{}: unknown fault {}", item, name)),
+            }
+        }
+        config.reads.sort_by_key(|(offset, _)| *offset);
+        config.drops.sort_by_key(|(offset, _)| *offset);
+        Ok(config)
+    }
+}
+
+/// Stream applying the faults of a `ChaosConfig` to the bytes passing through.
+pub struct ChaosStream<S> {
+    inner: S,
+    rng: ChaChaRng,
+    fragment: Option<usize>,
+    reads: VecDeque<(u64, ReadFault)>,
+    drops: VecDeque<(u64, u64)>,
+    read_offset: u64,
+    write_offset: u64,
+    stall: Option<Pin<Box<Sleep>>>,
+    injected: VecDeque<u8>,
+    history: VecDeque<u8>,
+    closed: bool,
+}
+
+impl<S> ChaosStream<S> {
+    pub fn new(inner: S, config: &ChaosConfig) -> Self {
+        Self {
+            inner,
+            rng: ChaChaRng::seed_from_u64(config.seed),
+            fragment: config.fragment,
+            reads: config.reads.iter().cloned().collect(),
+            drops: config.drops.iter().cloned().collect(),
+            read_offset: 0,
+            write_offset: 0,
+            stall: None,
+            injected: VecDeque::new(),
+            history: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    // Fires the faults due at the current read offset.
+    fn fire(&mut self) {
+        while matches!(self.reads.front(), Some((offset, _)) if *offset <= self.read_offset) {
+            let (_, fault) = self.reads.pop_front().unwrap();
+            match fault {
+                ReadFault::Stall(duration) => self.stall = Some(Box::pin(sleep(duration))),
+                ReadFault::Garbage(length) => {
+                    let mut garbage = vec![0; length];
+                    self.rng.fill_bytes(&mut garbage);
+                    self.injected.extend(garbage);
+                }
+                ReadFault::Duplicate(length) => {
+                    let skip = self.history.len().saturating_sub(length);
+                    let repeated: Vec<u8> = self.history.iter().skip(skip).copied().collect();
+                    self.injected.extend(repeated);
+                }
+                ReadFault::Close => self.closed = true,
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ChaosStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        this.fire();
+        if let Some(stall) = this.stall.as_mut() {
+            if stall.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.stall = None;
+        }
+        if !this.injected.is_empty() {
+            let length = this.injected.len().min(buf.remaining());
+            let bytes: Vec<u8> = this.injected.drain(..length).collect();
+            buf.put_slice(&bytes);
+            return Poll::Ready(Ok(()));
+        }
+        if this.closed {
+            return Poll::Ready(Ok(()));
+        }
+
+        let mut limit = buf.remaining();
+        if let Some((offset, _)) = this.reads.front() {
+            limit = limit.min((offset - this.read_offset).min(usize::MAX as u64) as usize);
+        }
+        if let Some(fragment) = this.fragment {
+            limit = limit.min(this.rng.gen_range(1..=fragment));
+        }
+        let mut limited = buf.take(limit);
+        match Pin::new(&mut this.inner).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+        let read = limited.filled().len();
+        let filled = buf.filled().len();
+        // The bytes were read into the unfilled part of `buf` through `limited`.
+        unsafe { buf.assume_init(read) };
+        buf.advance(read);
+
+        this.history.extend(&buf.filled()[filled..]);
+        let excess = this.history.len().saturating_sub(HISTORY);
+        this.history.drain(..excess);
+        this.read_offset += read as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ChaosStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        while matches!(this.drops.front(), Some((offset, length)) if offset + length <= this.write_offset) {
+            this.drops.pop_front();
+        }
+        let mut length = buf.len();
+        if let Some((offset, dropped)) = this.drops.front().copied() {
+            if offset <= this.write_offset {
+                let discarded = ((offset + dropped - this.write_offset) as usize).min(buf.len());
+                this.write_offset += discarded as u64;
+                return Poll::Ready(Ok(discarded));
+            }
+            length = length.min((offset - this.write_offset).min(usize::MAX as u64) as usize);
+        }
+        let written = match Pin::new(&mut this.inner).poll_write(cx, &buf[..length]) {
+            Poll::Ready(Ok(written)) => written,
+            other => return other,
+        };
+        this.write_offset += written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,1567 @@
+#[cfg(feature = "metrics")]
+use std::{collections::BTreeMap, net::SocketAddr};
+use std::{
+    collections::HashMap,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ansi_term::Colour::Red;
+use snarkvm::dpc::{testnet2::Testnet2, Address};
+use structopt::{clap::Shell, StructOpt};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::{
+    client,
+    cpu::{CpuFeatures, CpuPath},
+    credentials::PasswordSources,
+    events::EventBus,
+    group::WorkerGroup,
+    influx::{InfluxConfig, Tag},
+    logging::{LogConfig, LogFormat, LogRotation},
+    message::{ProtocolLimits, Secret},
+    notify::{EventKind, Notifier},
+    outbox::Outbox,
+    outgoing::MaxAges,
+    estimate::{self, EarningsConfig},
+    exit::{exit, ExitCode},
+    prover::ProverConfig,
+    quality::{self, FailoverPolicy},
+    reload::Reloader,
+    report::{RateDelta, ReportPolicy},
+    run_report::RunReport,
+    schedule::Schedule,
+    session::MiningSession,
+    share_log::ShareLog,
+    stats::{SessionTokens, StatsFile},
+    status::Status,
+    telemetry::TelemetrySampler,
+    threshold::{RateThreshold, Thresholds},
+    throttle::BandwidthLimits,
+    units::RateUnit,
+};
+use crate::{
+    bench, build_info, config, crash, credentials, devices, exit, explain, group, idle, influx, keys, loadtest, logging,
+    notify, prover, proxy, reload, schedule, selftest, shutdown, statsd, systemd, telemetry, test_pool, traffic, units,
+};
+#[cfg(feature = "chaos")]
+use crate::chaos;
+#[cfg(feature = "metrics")]
+use crate::{control, history, history::History, http, metrics, status};
+#[cfg(feature = "mqtt")]
+use crate::{mqtt, mqtt::MqttConfig};
+#[cfg(feature = "tui")]
+use crate::tui;
+#[cfg(all(windows, feature = "windows-service"))]
+use crate::service;
+
+// Keep the exit codes in sync with exit::ExitCode.
+const MINE_HELP: &str = "EXAMPLES:
+    AleoXMiner --address aleo1... --pool pool.example.com:4040 --worker rig1
+    AleoXMiner --config /etc/aleoxminer.toml
+    AleoXMiner --help-config > aleoxminer.toml
+    AleoXMiner --address aleo1... test-pool pool.example.com:4040
+
+EXIT CODES:
+    0   clean shutdown
+    1   other errors
+    2   invalid configuration
+    3   credentials rejected by the pool
+    4   no usable devices or self-test failed
+    10  pool unreachable for longer than --pool-unreachable-exit
+    70  internal error";
+
+// Log lines kept for the dashboard's log pane.
+#[cfg(feature = "tui")]
+const TUI_LOG_LINES: usize = 1000;
+// Warnings and errors kept for the run report.
+const REPORT_LOG_LINES: usize = 50;
+// Log lines kept for a crash report.
+const CRASH_LOG_LINES: usize = 100;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "prover",
+    about = "Standalone prover.",
+    long_version = build_info::LONG_VERSION,
+    after_help = MINE_HELP,
+    setting = structopt::clap::AppSettings::ColoredHelp
+)]
+pub(crate) struct Opt {
+    #[structopt(subcommand)]
+    pub(crate) command: Option<Command>,
+
+    /// Read settings from this TOML file, command line options and ALEOXMINER_* variables override it
+    #[structopt(long = "config", value_name = "FILE")]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Print the effective configuration with secrets redacted and exit
+    #[structopt(long = "print-config")]
+    pub(crate) print_config: bool,
+
+    /// Print an annotated example configuration file with the defaults and exit
+    #[structopt(long = "help-config")]
+    pub(crate) help_config: bool,
+
+    /// Rewrite an older --config file in the current layout, keeping a backup, and exit. Comments are lost
+    #[structopt(long = "write-migrated-config")]
+    pub(crate) write_migrated_config: bool,
+
+    /// Enable debug logging, same as -v
+    #[structopt(short = "d", long = "debug")]
+    pub(crate) debug: bool,
+
+    /// Only log warnings and errors
+    #[structopt(short = "q", long = "quiet", conflicts_with_all = &["verbose", "debug"])]
+    pub(crate) quiet: bool,
+
+    /// Log more: -v for debug, -vv for every message and debug output of snarkvm and tokio
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
+    pub(crate) verbose: u8,
+
+    /// Prover address (aleo1...)
+    #[structopt(
+        short = "a",
+        long = "address",
+        value_name = "ADDRESS",
+        parse(try_from_str = credentials::parse_address)
+    )]
+    pub(crate) address: Option<Address<Testnet2>>,
+
+    /// Impool account
+    #[structopt(long = "account")]
+    pub(crate) account: Option<String>,
+
+    /// Worker name
+    /// Note: The name consists of letters, digits, _ and - and cannot exceed 15 characters in length
+    #[structopt(long = "worker")]
+    pub(crate) worker: Option<String>,
+
+    /// Password sent to the pool when authorizing, visible to other users, prefer --password-file
+    #[structopt(long = "password", parse(from_str))]
+    pub(crate) password: Option<Secret>,
+
+    /// Read the pool password from this file, which other users must not be able to read
+    #[structopt(long = "password-file", value_name = "FILE")]
+    pub(crate) password_file: Option<PathBuf>,
+
+    /// Read the pool password from this environment variable
+    #[structopt(long = "password-env", value_name = "VARIABLE")]
+    pub(crate) password_env: Option<String>,
+
+    /// Read the pool password from this service/user entry of the OS keyring (keyring feature)
+    #[structopt(long = "password-keyring", value_name = "SERVICE/USER")]
+    pub(crate) password_keyring: Option<String>,
+
+    /// Pool server address
+    #[structopt(short = "p", long = "pool", value_name = "HOST:PORT", required_if("new_address", "false"))]
+    pub(crate) pool: Option<String>,
+
+    /// Number of threads
+    #[structopt(short = "t", long = "threads")]
+    pub(crate) threads: Option<u16>,
+
+    /// Output log to file
+    #[structopt(short = "o", long = "log-file", value_name = "FILE", alias = "log")]
+    pub(crate) log: Option<PathBuf>,
+
+    /// When to start a new log file: never, hourly, daily or at a size like 100M
+    #[structopt(long = "log-rotation", value_name = "WHEN", default_value = "daily")]
+    pub(crate) log_rotation: LogRotation,
+
+    /// Number of rotated log files to keep
+    #[structopt(long = "log-keep", default_value = "7")]
+    pub(crate) log_keep: usize,
+
+    /// Log line format: text or json, json includes the debug level spans following each job
+    #[structopt(long = "log-format", value_name = "FORMAT", default_value = "text")]
+    pub(crate) log_format: LogFormat,
+
+    /// Console log level (error, warn, info, debug, trace), overrides -q and -v, RUST_LOG overrides both
+    #[structopt(long = "console-level", value_name = "LEVEL")]
+    pub(crate) console_level: Option<LevelFilter>,
+
+    /// Log file level, defaults to the console level
+    #[structopt(long = "file-level", value_name = "LEVEL")]
+    pub(crate) file_level: Option<LevelFilter>,
+
+    /// Generate a new address, see also the account subcommand
+    #[structopt(long = "new-address")]
+    pub(crate) new_address: bool,
+
+    /// Number of recent shares the reject ratio is computed over
+    #[structopt(long = "reject-window", default_value = "50")]
+    pub(crate) reject_window: usize,
+
+    /// Reject ratio in percent above which proving is paused
+    #[structopt(long = "reject-threshold", default_value = "25")]
+    pub(crate) reject_threshold: f64,
+
+    /// Seconds to stay paused after too many rejects before retrying
+    #[structopt(long = "reject-cooldown", default_value = "300")]
+    pub(crate) reject_cooldown: u64,
+
+    /// Number of shares to evaluate after a cool-down before going back to normal
+    #[structopt(long = "reject-probation", default_value = "5")]
+    pub(crate) reject_probation: usize,
+
+    /// Percentage a worker's median proof latency may drift from the fleet median before warning
+    #[structopt(long = "latency-drift", default_value = "25")]
+    pub(crate) latency_drift: f64,
+
+    /// Maximum number of proofs computed at the same time
+    #[structopt(long = "max-concurrent-proofs")]
+    pub(crate) max_concurrent_proofs: Option<usize>,
+
+    /// Estimated memory used by one proof in MiB, derives --max-concurrent-proofs from the available memory
+    #[structopt(long = "proof-memory")]
+    pub(crate) proof_memory: Option<u64>,
+
+    /// Minimum seconds between proof rate reports to the pool
+    #[structopt(long = "rate-min-interval", default_value = "10")]
+    pub(crate) rate_min_interval: u64,
+
+    /// Maximum seconds between proof rate reports to the pool
+    #[structopt(long = "rate-max-interval", default_value = "30")]
+    pub(crate) rate_max_interval: u64,
+
+    /// Change of the proof rate that triggers a report, absolute (0.5) or relative (5%)
+    #[structopt(long = "rate-delta", default_value = "5%")]
+    pub(crate) rate_delta: RateDelta,
+
+    /// Coins earned per accepted share, used for the estimated daily yield
+    #[structopt(long = "reward-per-share")]
+    pub(crate) reward_per_share: Option<f64>,
+
+    /// Coins earned per day for each p/s of effective proof rate, used for the estimated daily yield
+    #[structopt(long = "reward-per-pps-day")]
+    pub(crate) reward_per_pps_day: Option<f64>,
+
+    /// Write a JSON report about the run to this file on exit
+    #[structopt(long = "run-report", value_name = "FILE")]
+    pub(crate) run_report: Option<PathBuf>,
+
+    /// Append every share result to this file, CSV or JSON lines depending on the extension (.csv, .jsonl)
+    #[structopt(long = "share-log", value_name = "FILE")]
+    pub(crate) share_log: Option<PathBuf>,
+
+    /// Start a new share log once it reaches this size, e.g. 100M
+    #[structopt(long = "share-log-max-size", value_name = "SIZE", parse(try_from_str = logging::parse_size))]
+    pub(crate) share_log_max_size: Option<u64>,
+
+    /// Keep cumulative statistics across restarts in this file, and the pool session tokens and the
+    /// shares waiting for a result next to it
+    #[structopt(long = "stats-file", value_name = "FILE")]
+    pub(crate) stats_file: Option<String>,
+
+    /// Skip the proving self-test at startup
+    #[structopt(long = "no-self-test")]
+    pub(crate) no_self_test: bool,
+
+    /// Only connect and report the proof rates given to the control API, without loading the proving
+    /// parameters, e.g. on a controller proxying the stats of other machines
+    #[structopt(long = "no-prover")]
+    pub(crate) no_prover: bool,
+
+    /// Keep mining on the remaining GPUs if some fail the self-test
+    #[structopt(long = "skip-failed-devices")]
+    pub(crate) skip_failed_devices: bool,
+
+    /// Minutes without an accepted share before alerting, raised automatically for low proof rates
+    #[structopt(long = "share-alert", default_value = "15")]
+    pub(crate) share_alert: u64,
+
+    /// Alert when the 15 minute proof rate drops below this, in p/s or as a percentage of the session average, e.g. 80%
+    #[structopt(long = "alert-min-hashrate")]
+    pub(crate) alert_min_hashrate: Option<RateThreshold>,
+
+    /// Alert when more than this percentage of the shares of the last hour are rejected
+    #[structopt(long = "alert-max-reject")]
+    pub(crate) alert_max_reject: Option<f64>,
+
+    /// Alert when disconnected from the pool longer than this in the last hour, e.g. 5m
+    #[structopt(
+        long = "alert-max-disconnected",
+        value_name = "DURATION",
+        parse(try_from_str = schedule::parse_duration)
+    )]
+    pub(crate) alert_max_disconnected: Option<Duration>,
+
+    #[cfg(feature = "metrics")]
+    /// Serve Prometheus metrics on this address, e.g. 0.0.0.0:9090
+    #[structopt(long = "metrics-bind", value_name = "ADDRESS:PORT")]
+    pub(crate) metrics_bind: Option<SocketAddr>,
+
+    #[cfg(feature = "metrics")]
+    /// Serve the JSON status on this address, at /status and /health
+    #[structopt(long = "status-bind", value_name = "ADDRESS:PORT")]
+    pub(crate) status_bind: Option<SocketAddr>,
+
+    /// Push metrics to this InfluxDB server, e.g. http://localhost:8086
+    #[structopt(long = "influx-url", value_name = "URL")]
+    pub(crate) influx_url: Option<String>,
+
+    /// InfluxDB bucket, or database for InfluxDB 1.x
+    #[structopt(long = "influx-bucket", default_value = "aleoxminer")]
+    pub(crate) influx_bucket: String,
+
+    /// InfluxDB organization (2.x only)
+    #[structopt(long = "influx-org")]
+    pub(crate) influx_org: Option<String>,
+
+    /// InfluxDB API token, uses the 2.x write API when set
+    #[structopt(long = "influx-token")]
+    pub(crate) influx_token: Option<String>,
+
+    /// Seconds between InfluxDB pushes
+    #[structopt(long = "influx-interval", default_value = "10")]
+    pub(crate) influx_interval: u64,
+
+    /// Extra tag for the InfluxDB metrics, e.g. location=basement (repeatable)
+    #[structopt(long = "influx-tag", value_name = "KEY=VALUE")]
+    pub(crate) influx_tags: Vec<Tag>,
+
+    #[cfg(feature = "mqtt")]
+    /// Publish status and events to this MQTT broker (host:port) and accept commands on aleoxminer/<worker>/cmd
+    #[structopt(long = "mqtt-broker", value_name = "HOST:PORT")]
+    pub(crate) mqtt_broker: Option<String>,
+
+    #[cfg(feature = "mqtt")]
+    /// MQTT username
+    #[structopt(long = "mqtt-username")]
+    pub(crate) mqtt_username: Option<String>,
+
+    #[cfg(feature = "mqtt")]
+    /// MQTT password
+    #[structopt(long = "mqtt-password")]
+    pub(crate) mqtt_password: Option<String>,
+
+    #[cfg(all(feature = "mqtt", feature = "tls"))]
+    /// Connect to the MQTT broker over TLS
+    #[structopt(long = "mqtt-tls")]
+    pub(crate) mqtt_tls: bool,
+
+    #[cfg(all(feature = "mqtt", feature = "tls"))]
+    /// CA certificate for the MQTT broker, implies --mqtt-tls
+    #[structopt(long = "mqtt-ca", value_name = "FILE")]
+    pub(crate) mqtt_ca: Option<String>,
+
+    #[cfg(feature = "mqtt")]
+    /// Seconds between MQTT status messages
+    #[structopt(long = "mqtt-interval", default_value = "30")]
+    pub(crate) mqtt_interval: u64,
+
+    /// Send metrics to this statsd server over UDP, e.g. localhost:8125
+    #[structopt(long = "statsd", value_name = "HOST:PORT")]
+    pub(crate) statsd: Option<String>,
+
+    /// Prefix of the statsd metric names
+    #[structopt(long = "statsd-prefix", default_value = "aleoxminer")]
+    pub(crate) statsd_prefix: String,
+
+    #[cfg(feature = "metrics")]
+    /// How long to keep the hashrate history served at /history, e.g. 4h
+    #[structopt(
+        long = "history-retention",
+        value_name = "DURATION",
+        default_value = "4h",
+        parse(try_from_str = schedule::parse_duration)
+    )]
+    pub(crate) history_retention: Duration,
+
+    /// Seconds between keepalive messages used to measure the pool round trip time, 0 to disable
+    #[structopt(long = "keepalive", default_value = "0")]
+    pub(crate) keepalive: u64,
+
+    /// Warn when the pool round trip time exceeds this many milliseconds
+    #[structopt(long = "rtt-warning", default_value = "300")]
+    pub(crate) rtt_warning: u64,
+
+    /// Largest message accepted from the pool, 1 KiB to 1 GiB
+    #[structopt(long = "max-frame-size", value_name = "BYTES", default_value = "134217728")]
+    pub(crate) max_frame_size: usize,
+
+    /// Longest string field accepted from the pool, e.g. a rejection reason, 16 bytes to 1 MiB
+    #[structopt(long = "max-string-length", value_name = "BYTES", default_value = "4096")]
+    pub(crate) max_string_length: usize,
+
+    /// Messages queued for the pool, mostly shares, before proving waits for the connection
+    #[structopt(long = "submit-queue", value_name = "MESSAGES", default_value = "1024")]
+    pub(crate) submit_queue: usize,
+
+    /// Drop queued shares for an earlier job once they waited this long, 0 to always send them
+    #[structopt(long = "max-submit-age", value_name = "SECONDS", default_value = "60")]
+    pub(crate) max_submit_age: u64,
+
+    /// Drop queued proof rate reports once they waited this long, 0 to always send them
+    #[structopt(long = "max-rate-age", value_name = "SECONDS", default_value = "30")]
+    pub(crate) max_rate_age: u64,
+
+    /// Cap the bytes read from the pool at this many KB per second, for metered links
+    #[structopt(long = "read-limit", value_name = "KB/S")]
+    pub(crate) read_limit: Option<u64>,
+
+    /// Cap the bytes written to the pool at this many KB per second, proof rate reports are skipped
+    /// before shares wait
+    #[structopt(long = "write-limit", value_name = "KB/S")]
+    pub(crate) write_limit: Option<u64>,
+
+    /// Offer MessagePack frames to the pool, smaller than JSON, used only if the pool supports them
+    #[structopt(long = "msgpack")]
+    pub(crate) msgpack: bool,
+
+    /// Tell the pool which job is being proven with each proof rate report, only for pools supporting it
+    #[structopt(long = "job-ack")]
+    pub(crate) job_ack: bool,
+
+    /// Take speculative jobs for the next height ahead of time, only for pools supporting them
+    #[structopt(long = "speculative")]
+    pub(crate) speculative: bool,
+
+    /// Ask the pool for balance and payout info, only for pools supporting it
+    #[structopt(long = "pool-info")]
+    pub(crate) pool_info: bool,
+
+    /// Drop jobs for a block this many blocks below an earlier job, unless the pool flags a reorg
+    #[structopt(long = "reorg-tolerance", value_name = "BLOCKS", default_value = "2")]
+    pub(crate) reorg_tolerance: u32,
+
+    /// Pass every job to the prover, even with a zero or implausible target, an empty transactions
+    /// root or a timestamp far off the local clock
+    #[structopt(long = "no-template-validation")]
+    pub(crate) no_template_validation: bool,
+
+    /// Drop jobs whose timestamp is more than this many seconds off the local clock
+    #[structopt(long = "template-max-skew", value_name = "SECONDS", default_value = "600")]
+    pub(crate) template_max_skew: u64,
+
+    /// Ask the pool for this share difficulty with a d= password field, pools may ignore it, 0 to
+    /// leave the password as is
+    #[structopt(long = "difficulty", default_value = "0")]
+    pub(crate) difficulty: u64,
+
+    /// Switch to another configured pool once it scores this many quality points above the active
+    /// one (out of 100, see the quality_score in the status), 0 to stay on the active pool
+    #[structopt(long = "failover-margin", value_name = "POINTS", default_value = "0")]
+    pub(crate) failover_margin: f64,
+
+    /// Seconds the active pool has to stay below the failover margin before switching
+    #[structopt(long = "failover-after", value_name = "SECONDS", default_value = "300")]
+    pub(crate) failover_after: u64,
+
+    /// Seconds between quality probes of the other configured pools, with --failover-margin
+    #[structopt(long = "probe-interval", value_name = "SECONDS", default_value = "60")]
+    pub(crate) probe_interval: u64,
+
+    /// Split the proving time between the configured pools in priority order by these weights, e.g.
+    /// 70,30. All of them stay connected and each gets the shares of its own jobs
+    #[structopt(long = "split", value_name = "WEIGHTS", use_delimiter = true)]
+    pub(crate) split: Option<Vec<u32>>,
+
+    #[cfg(feature = "metrics")]
+    /// Bearer token enabling the control API (/control/...) on the status address
+    #[structopt(long = "control-token")]
+    pub(crate) control_token: Option<String>,
+
+    /// Post notifications about problems and restarts to this webhook URL (Discord and Slack compatible)
+    #[structopt(long = "webhook-url", value_name = "URL")]
+    pub(crate) webhook_url: Option<String>,
+
+    /// Only notify about these events: start, stop, disconnect, reject-rate, no-share, threshold (default all)
+    #[structopt(long = "webhook-event")]
+    pub(crate) webhook_events: Vec<EventKind>,
+
+    /// Seconds the pool connection has to be down before notifying
+    #[structopt(long = "webhook-disconnect-after", default_value = "60")]
+    pub(crate) webhook_disconnect_after: u64,
+
+    /// Exit with code 10 once no pool connection was authorized for this long, e.g. 30m (default: retry forever)
+    #[structopt(
+        long = "pool-unreachable-exit",
+        value_name = "DURATION",
+        parse(try_from_str = schedule::parse_duration)
+    )]
+    pub(crate) pool_unreachable_exit: Option<Duration>,
+
+    /// Unit proof rates are shown in: p/s, kp/s or auto
+    #[structopt(long = "rate-unit", default_value = "auto")]
+    pub(crate) rate_unit: RateUnit,
+
+    #[cfg(feature = "tui")]
+    /// Show a terminal dashboard instead of the log output
+    #[structopt(long = "tui")]
+    pub(crate) tui: bool,
+
+    /// Only mine during these local time ranges, e.g. "22:00-06:00,12:00-14:00"
+    #[structopt(long = "schedule", value_name = "RANGES")]
+    pub(crate) schedule: Option<Schedule>,
+
+    /// Also disconnect from the pool outside the scheduled windows
+    #[structopt(long = "schedule-disconnect")]
+    pub(crate) schedule_disconnect: bool,
+
+    /// Shut down after running this long, e.g. 90m or 4h
+    #[structopt(long = "max-runtime", value_name = "DURATION", parse(try_from_str = schedule::parse_duration))]
+    pub(crate) max_runtime: Option<Duration>,
+
+    /// Flag proof attempts running longer than this multiple of the median proof latency
+    #[structopt(long = "watchdog-multiple", default_value = "10")]
+    pub(crate) watchdog_multiple: f64,
+
+    /// Run the proving threads at the lowest priority
+    #[structopt(long = "nice")]
+    pub(crate) nice: bool,
+
+    /// Only mine while other programs leave the CPU idle (Linux and Windows)
+    #[structopt(long = "idle-only")]
+    pub(crate) idle_only: bool,
+
+    /// CPU usage of other programs in percent above which idle-only mining pauses
+    #[structopt(long = "idle-threshold", default_value = "20")]
+    pub(crate) idle_threshold: f64,
+
+    /// Seconds the system has to stay quiet before idle-only mining resumes
+    #[structopt(long = "idle-quiet", default_value = "60")]
+    pub(crate) idle_quiet: u64,
+
+    /// Inject network faults into the pool connection, e.g. seed=7,fragment=16,stall@1200:5s (see chaos.rs)
+    #[cfg(feature = "chaos")]
+    #[structopt(long = "chaos", value_name = "SPEC")]
+    pub(crate) chaos: Option<chaos::ChaosConfig>,
+
+    /// Write every frame sent to and received from the pool to a new file in this directory per connection,
+    /// the password is scrubbed
+    #[structopt(long = "record-traffic", value_name = "DIR")]
+    pub(crate) record_traffic: Option<PathBuf>,
+
+    /// Connect and prove as usual but only count and log shares instead of submitting them
+    #[structopt(long = "dry-run")]
+    pub(crate) dry_run: bool,
+
+    /// With --dry-run, report a proof rate of 0 to the pool instead of none
+    #[structopt(long = "dry-run-zero-rate", requires = "dry-run")]
+    pub(crate) dry_run_zero_rate: bool,
+
+    /// Seed the nonce generator to get a reproducible nonce sequence (testing only)
+    #[structopt(long = "deterministic-seed", hidden = true)]
+    pub(crate) deterministic_seed: Option<u64>,
+
+    #[cfg(feature = "cuda")]
+    #[structopt(verbatim_doc_comment)]
+    /// Indexes of GPUs to use (starts from 0)
+    /// Specify multiple times to use multiple GPUs
+    /// Example: -g 0 -g 1 -g 2
+    /// Note: Pure CPU proving will be disabled as each GPU job requires one CPU thread as well
+    #[structopt(short = "g", long = "cuda")]
+    pub(crate) cuda: Option<Vec<i16>>,
+
+    #[cfg(feature = "cuda")]
+    #[structopt(verbatim_doc_comment)]
+    /// Parallel jobs per GPU, defaults to 1
+    /// Example: -g 0 -g 1 -j 4
+    /// The above example will result in 8 jobs in total
+    #[structopt(short = "j", long = "cuda-jobs")]
+    pub(crate) jobs: Option<u8>,
+
+    #[cfg(feature = "cuda")]
+    #[structopt(verbatim_doc_comment)]
+    /// Submit the shares of some GPUs under another worker name, using a separate pool connection
+    /// Example: --worker-group rig1_a=0,1,2,3 --worker-group rig1_b=4,5,6,7
+    #[structopt(long = "worker-group")]
+    pub(crate) worker_groups: Vec<WorkerGroup>,
+
+    // Per pool account, worker and password from the `pools` configuration, by pool address.
+    #[structopt(skip)]
+    pub(crate) pool_credentials: HashMap<String, client::PoolCredentials>,
+
+    // Addresses of the `pools` configuration by priority.
+    #[structopt(skip)]
+    pub(crate) pools: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Mine with the options given before the subcommand (default)
+    Mine,
+    /// Measure the proof time on the selected devices without connecting to a pool
+    #[structopt(after_help = "EXAMPLES:\n    AleoXMiner bench --iterations 10\n    AleoXMiner -g 0 -g 1 bench")]
+    Bench {
+        /// Proofs per device
+        #[structopt(long = "iterations", default_value = "5")]
+        iterations: u32,
+    },
+    /// Check that a pool is reachable, accepts the credentials and sends work, then exit
+    #[structopt(
+        after_help = "EXAMPLES:\n    AleoXMiner --address aleo1... test-pool pool.example.com:4040\n    \
+                      AleoXMiner --config miner.toml test-pool tcp://pool.example.com:4040 --timeout 30"
+    )]
+    TestPool {
+        /// Pool address, host:port or tcp://host:port
+        #[structopt(value_name = "URL")]
+        url: String,
+        /// Seconds each stage may take
+        #[structopt(long = "timeout", value_name = "SECONDS", default_value = "10")]
+        timeout: u64,
+    },
+    /// Simulate many miners against a pool and report connect, authorization and share latencies
+    #[structopt(
+        after_help = "EXAMPLES:\n    AleoXMiner --address aleo1... loadtest pool.example.com:4040 --miners 500 --ramp-up 1m\n    \
+                      AleoXMiner --address aleo1... loadtest localhost:4040 --fake-proofs --submit-rate 30 --churn 5"
+    )]
+    Loadtest(loadtest::LoadTest),
+    /// Explain hex or raw protocol frames, e.g. from a pool log or --record-traffic
+    #[structopt(after_help = "EXAMPLES:\n    echo 0100000005 | AleoXMiner decode-frame\n    AleoXMiner decode-frame frames.bin --raw")]
+    DecodeFrame(explain::DecodeFrame),
+    /// Connect once to the pool and let the rigs of this network mine through that connection
+    #[structopt(
+        after_help = "EXAMPLES:\n    AleoXMiner --address aleo1... -p pool.example.com:4040 --worker farm proxy\n    \
+                      AleoXMiner --config farm.toml proxy --listen 192.168.1.10:4040 --rig-password s3cret\n\n\
+                      The rigs connect to the proxy as to a pool, their shares are submitted under the proxy's\n\
+                      worker, or with --multiplex under the worker of each rig for pools supporting it."
+    )]
+    Proxy(proxy::ProxyOptions),
+    /// List the CPU and the GPUs usable for proving
+    ListDevices,
+    /// Print the frames of a --record-traffic recording
+    ReplayTraffic {
+        #[structopt(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Create a new address, or show the address of a private key
+    #[structopt(
+        after_help = "EXAMPLES:\n    AleoXMiner account new --out account.json\n    AleoXMiner account address --key account.json"
+    )]
+    Account(keys::AccountCommand),
+    /// Show the version and build information
+    Version,
+    /// Print a shell completion script
+    #[structopt(after_help = "EXAMPLES:\n    AleoXMiner completions bash > /etc/bash_completion.d/AleoXMiner\n    \
+                              AleoXMiner completions powershell >> $PROFILE")]
+    Completions {
+        /// bash, zsh, fish, powershell or elvish
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
+    /// Install, remove or run the Windows service
+    #[cfg(all(windows, feature = "windows-service"))]
+    Service(service::ServiceAction),
+}
+
+impl Opt {
+    /// (console, file) log levels.
+    pub(crate) fn log_levels(&self) -> (LevelFilter, LevelFilter) {
+        let verbose = self.verbose.max(self.debug as u8);
+        let console_level = self
+            .console_level
+            .unwrap_or_else(|| logging::verbosity_level(self.quiet, verbose));
+        (console_level, self.file_level.unwrap_or(console_level))
+    }
+
+    /// Pool password from whichever source was given.
+    pub(crate) fn pool_password(&self) -> anyhow::Result<Option<Secret>> {
+        let password = PasswordSources {
+            password: self.password.as_ref().map(Secret::expose),
+            file: self.password_file.as_deref(),
+            env: self.password_env.as_deref(),
+            keyring: self.password_keyring.as_deref(),
+        }
+        .resolve()?;
+        Ok(password.map(Secret::from))
+    }
+
+    pub(crate) fn max_ages(&self) -> MaxAges {
+        let max_age = |seconds| Some(Duration::from_secs(seconds)).filter(|age| !age.is_zero());
+        MaxAges {
+            submit: max_age(self.max_submit_age),
+            proof_rate: max_age(self.max_rate_age),
+        }
+    }
+
+    pub(crate) fn bandwidth_limits(&self) -> BandwidthLimits {
+        let limit = |kbps: Option<u64>| kbps.filter(|kbps| *kbps > 0).map(|kbps| kbps.saturating_mul(1024));
+        BandwidthLimits {
+            read: limit(self.read_limit),
+            write: limit(self.write_limit),
+        }
+    }
+
+    pub(crate) fn rate_report(&self) -> ReportPolicy {
+        ReportPolicy {
+            min_interval: Duration::from_secs(self.rate_min_interval),
+            max_interval: Duration::from_secs(self.rate_max_interval.max(self.rate_min_interval)),
+            delta: self.rate_delta,
+        }
+    }
+
+    pub(crate) fn failover_policy(&self) -> FailoverPolicy {
+        FailoverPolicy {
+            margin: self.failover_margin,
+            sustain: Duration::from_secs(self.failover_after),
+            probe_interval: Duration::from_secs(self.probe_interval.max(1)),
+        }
+    }
+
+    pub(crate) fn protocol_limits(&self) -> ProtocolLimits {
+        ProtocolLimits {
+            max_frame_size: self.max_frame_size,
+            max_string_length: self.max_string_length,
+        }
+    }
+
+    pub(crate) fn share_alert_duration(&self) -> Duration {
+        Duration::from_secs(self.share_alert * 60)
+    }
+
+    pub(crate) fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            min_rate: self.alert_min_hashrate,
+            max_reject_percent: self.alert_max_reject,
+            max_disconnected: self.alert_max_disconnected,
+        }
+    }
+}
+
+/// Parses the command line and runs the selected command, exits the process on errors.
+pub async fn run() {
+    #[cfg(windows)]
+    let _ = ansi_term::enable_ansi_support();
+
+    exit::set_panic_hook();
+
+    let matches = Opt::clap().get_matches();
+    let mut opt = Opt::from_clap(&matches);
+    if opt.help_config {
+        match config::example() {
+            Ok(example) => print!("{}", example),
+            Err(e) => exit(ExitCode::Error, format!("Unable to generate the example configuration: {:#}", e)),
+        }
+        return;
+    }
+    let settings = match config::load(opt.config.as_deref(), &matches) {
+        Ok(settings) => settings,
+        Err(e) => exit(ExitCode::Config, format!("{:#}", e)),
+    };
+    if opt.write_migrated_config {
+        let path = match opt.config.as_deref() {
+            Some(path) => path,
+            None => exit(ExitCode::Config, "--write-migrated-config needs --config"),
+        };
+        match config::write_migrated(path) {
+            Ok(Some(backup)) => println!(
+                "Migrated {} to schema version {}, the original was saved as {}",
+                path.display(),
+                config::SCHEMA_VERSION,
+                backup.display()
+            ),
+            Ok(None) => println!("{} is already at schema version {}", path.display(), config::SCHEMA_VERSION),
+            Err(e) => exit(ExitCode::Config, format!("{:#}", e)),
+        }
+        return;
+    }
+    if opt.print_config {
+        match settings.redacted().to_toml() {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => exit(ExitCode::Error, format!("Unable to print the configuration: {:#}", e)),
+        }
+        return;
+    }
+    settings.apply(&mut opt);
+    let password = match opt.pool_password() {
+        Ok(password) => password.unwrap_or_default(),
+        Err(e) => exit(ExitCode::Config, format!("{:#}", e)),
+    };
+    #[cfg(all(windows, feature = "windows-service"))]
+    let mut windows_service = None;
+    let mut proxy_options = None;
+    match opt.command.take() {
+        None | Some(Command::Mine) => {}
+        // Started once logging is set up.
+        Some(Command::Proxy(options)) => proxy_options = Some(options),
+        #[cfg(all(windows, feature = "windows-service"))]
+        Some(Command::Service(action)) => {
+            let result = match action {
+                service::ServiceAction::Install => {
+                    // Services start in the system directory.
+                    if opt.config.as_ref().map_or(false, |path| path.is_relative())
+                        || opt.log.as_ref().map_or(false, |path| path.is_relative())
+                    {
+                        exit(
+                            ExitCode::Config,
+                            "Use absolute paths for --config and --log, the service does not start in this directory",
+                        );
+                    }
+                    let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
+                    service::install(service::launch_arguments(&args)).map(|()| println!("Service installed"))
+                }
+                service::ServiceAction::Uninstall => service::uninstall().map(|()| println!("Service removed")),
+                service::ServiceAction::Run => match service::start() {
+                    Ok(service) => {
+                        windows_service = Some(service);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            if let Err(e) = result {
+                exit(ExitCode::Error, format!("{:#}", e));
+            }
+            if windows_service.is_none() {
+                return;
+            }
+        }
+        Some(Command::Version) => {
+            println!("{}", build_info::BuildInfo::current());
+            return;
+        }
+        Some(Command::Completions { shell }) => {
+            Opt::clap().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut std::io::stdout());
+            return;
+        }
+        Some(Command::Account(command)) => {
+            if let Err(e) = keys::run(command) {
+                exit(ExitCode::Error, format!("{:#}", e));
+            }
+            return;
+        }
+        Some(Command::ListDevices) => {
+            devices::list();
+            return;
+        }
+        Some(Command::DecodeFrame(options)) => {
+            if let Err(e) = explain::run(options) {
+                exit(ExitCode::Error, format!("{:#}", e));
+            }
+            return;
+        }
+        Some(Command::Loadtest(options)) => {
+            if opt.address.is_none() && opt.account.is_none() {
+                exit(ExitCode::Config, "loadtest needs --address or --account to authorize with");
+            }
+            let limits = opt.protocol_limits();
+            if let Err(e) = loadtest::run(options, opt.account.as_deref(), opt.address.as_ref(), password, limits).await {
+                exit(ExitCode::Error, format!("{:#}", e));
+            }
+            return;
+        }
+        Some(Command::ReplayTraffic { file }) => {
+            if let Err(e) = traffic::replay(&file, opt.protocol_limits()) {
+                exit(ExitCode::Error, format!("{:#}", e));
+            }
+            return;
+        }
+        Some(Command::Bench { iterations }) => {
+            #[allow(unused_mut)]
+            let mut devices = vec![-1];
+            #[cfg(feature = "cuda")]
+            if let Some(cuda) = opt.cuda.clone() {
+                devices = cuda;
+            }
+            let threads = opt.threads.unwrap_or(num_cpus::get() as u16).max(1) as usize;
+            if let Err(e) = bench::run(&devices, threads, iterations).await {
+                exit(ExitCode::Devices, format!("{:#}", e));
+            }
+            return;
+        }
+        Some(Command::TestPool { url, timeout }) => {
+            if opt.address.is_none() && opt.account.is_none() {
+                exit(ExitCode::Config, "test-pool needs --address or --account to authorize with");
+            }
+            let authorization = client::authorization(
+                opt.account.as_deref(),
+                opt.address.as_ref(),
+                opt.worker.as_deref().unwrap_or_default(),
+                password,
+            );
+            // The stages are printed as they run, only the code is left to report.
+            let limit = Duration::from_secs(timeout.max(1));
+            let code = match test_pool::run(&url, authorization, opt.protocol_limits(), limit).await {
+                Ok(()) => ExitCode::Success,
+                Err(test_pool::Stage::Authorize) => ExitCode::Auth,
+                Err(_) => ExitCode::PoolUnreachable,
+            };
+            std::process::exit(code.code());
+        }
+    }
+    if opt.new_address {
+        keys::new_account();
+        return;
+    }
+
+    let (console_level, file_level) = opt.log_levels();
+
+    // The dashboard takes over the terminal, log lines go to its log pane instead.
+    #[cfg(feature = "tui")]
+    let tui_logs = if opt.tui { Some(logging::LogBuffer::new(TUI_LOG_LINES)) } else { None };
+    #[cfg(not(feature = "tui"))]
+    let tui_logs: Option<logging::LogBuffer> = None;
+    let recent_logs = logging::LogBuffer::new(CRASH_LOG_LINES);
+    let log_config = LogConfig {
+        console_level,
+        file_level,
+        format: opt.log_format,
+        file: opt.log.clone(),
+        rotation: opt.log_rotation,
+        keep: opt.log_keep,
+        tui: tui_logs.clone(),
+        recent: Some(recent_logs.clone()),
+    };
+    units::set_rate_unit(opt.rate_unit);
+    let (_log_guard, log_levels) = match logging::init(log_config) {
+        Ok(logging) => logging,
+        Err(e) => exit(ExitCode::Config, format!("Unable to set up logging: {:#}", e)),
+    };
+    let crash_dir = opt
+        .log
+        .as_deref()
+        .or_else(|| opt.stats_file.as_deref().map(Path::new))
+        .map(crash::dir_of)
+        .unwrap_or_else(|| PathBuf::from("."));
+    crash::install(crash_dir, Some(recent_logs.clone()));
+    info!("{}", build_info::BuildInfo::current());
+    for warning in settings.warnings() {
+        warn!("{}", warning);
+    }
+    for migration in settings.migrations() {
+        info!("Migrated the configuration file to {}", migration);
+    }
+    if !settings.migrations().is_empty() {
+        info!("Run with --write-migrated-config to save the migrated configuration file");
+    }
+    info!("Console log filter: {}", logging::effective_filter(console_level));
+    info!(
+        "Protocol: max frame size {} bytes, max string length {} bytes, submit queue {} messages",
+        opt.max_frame_size, opt.max_string_length, opt.submit_queue
+    );
+    if let Some(log) = opt.log.as_ref() {
+        info!(
+            "Logging to {} with filter {}, rotation {:?}, keeping {} files",
+            log.display(),
+            logging::effective_filter(file_level),
+            opt.log_rotation,
+            opt.log_keep
+        );
+    }
+
+    let mut address = None;
+    let mut account = None;
+    if opt.address.is_some() {
+        address = opt.address;
+    } else if opt.account.is_some() {
+        account = opt.account;
+    } else {
+        exit(ExitCode::Config, "Please enter address or account!");
+    }
+
+    // Validated with the configuration.
+    let worker = Some(opt.worker.clone().unwrap_or_default());
+
+    let thresholds = opt.thresholds();
+    if let Err(e) = thresholds.validate() {
+        exit(ExitCode::Config, format!("Invalid alert threshold: {}", e));
+    }
+
+    let pool = match opt.pool {
+        Some(pool) => pool,
+        None => exit(ExitCode::Config, "Pool address is required!"),
+    };
+    if let Err(e) = pool.to_socket_addrs() {
+        exit(ExitCode::Config, format!("Invalid pool address {}: {}", pool, e));
+    }
+
+    if let Some(options) = proxy_options {
+        let upstream = client::Client::init(
+            account,
+            worker,
+            address,
+            pool,
+            EventBus::new(),
+            opt.protocol_limits(),
+            opt.submit_queue,
+        );
+        upstream.set_password(password);
+        upstream.set_max_ages(opt.max_ages());
+        upstream.set_bandwidth_limits(opt.bandwidth_limits());
+        upstream.set_msgpack(opt.msgpack);
+        upstream.set_reorg_tolerance(opt.reorg_tolerance);
+        upstream.set_template_validation(!opt.no_template_validation, Duration::from_secs(opt.template_max_skew));
+        upstream.set_difficulty(opt.difficulty);
+        if let Err(e) = proxy::run(options, upstream, opt.protocol_limits()).await {
+            exit(ExitCode::Error, format!("{:#}", e));
+        }
+        return;
+    }
+
+    // The main pool comes first, then the other configured pools by priority.
+    let split: Vec<(String, u32)> = match opt.split.as_ref() {
+        Some(weights) if weights.len() > 1 => {
+            let others = opt.pools.iter().filter(|other| **other != pool).cloned();
+            let split: Vec<(String, u32)> =
+                std::iter::once(pool.clone()).chain(others).zip(weights.iter().copied()).collect();
+            if split.len() < weights.len() {
+                exit(
+                    ExitCode::Config,
+                    format!("--split gives {} weights but only {} pools are configured", weights.len(), split.len()),
+                );
+            }
+            split
+        }
+        _ => Vec::new(),
+    };
+
+    let threads = opt.threads.unwrap_or(num_cpus::get() as u16);
+
+    let mut cuda: Option<Vec<i16>>;
+    let cuda_jobs: Option<u8>;
+    let worker_groups: Vec<WorkerGroup>;
+    #[cfg(feature = "cuda")]
+    {
+        cuda = opt.cuda;
+        cuda_jobs = opt.jobs;
+        worker_groups = opt.worker_groups;
+    }
+    #[cfg(not(feature = "cuda"))]
+    {
+        cuda = None;
+        cuda_jobs = None;
+        worker_groups = Vec::new();
+    }
+    if let Some(cuda) = cuda.clone() {
+        if cuda.is_empty() {
+            exit(ExitCode::Config, "No GPUs specified. Use -g 0 if there is only one GPU.");
+        }
+    }
+    if let Err(e) = group::validate(&worker_groups, cuda.as_deref().unwrap_or_default()) {
+        exit(ExitCode::Config, e);
+    }
+
+    let cpu_features = CpuFeatures::detect();
+    info!("CPU features: {}", cpu_features);
+    let missing = cpu_features.missing(&CpuFeatures::compiled());
+    if !missing.is_empty() && !opt.no_prover {
+        exit(
+            ExitCode::Devices,
+            format!(
+                "This binary was built for CPUs with {} which this CPU lacks, please use a generic build",
+                missing.join(", ")
+            ),
+        );
+    }
+    let cpu_path = CpuPath::select(&cpu_features);
+    info!("Selected the {} proving path", cpu_path.name());
+
+    if opt.no_prover {
+        info!("Not proving, proof rates are reported as given to POST /control/proof-rate");
+    } else if !opt.no_self_test {
+        info!("Running proving self-test");
+        let devices = cuda.clone().unwrap_or_else(|| vec![-1]);
+        let mut failed = Vec::new();
+        for (device, result) in selftest::run(&devices, threads.max(1) as usize).await {
+            match result {
+                Ok(latency) => info!(
+                    "Self-test passed on {}, single proof took {}ms",
+                    selftest::device_name(device),
+                    latency.as_millis()
+                ),
+                Err(e) => {
+                    error!("Self-test failed on {}: {}", selftest::device_name(device), e);
+                    if device >= 0 {
+                        error!("Check that the NVIDIA driver matches the CUDA version this miner was built with");
+                    } else {
+                        error!("The proving parameters may be corrupted, try removing them to download them again");
+                    }
+                    failed.push(device);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            let remaining: Vec<i16> = devices.into_iter().filter(|device| !failed.contains(device)).collect();
+            if opt.skip_failed_devices && cuda.is_some() && !remaining.is_empty() {
+                warn!("Skipping failed devices, mining on {} GPUs", remaining.len());
+                cuda = Some(remaining);
+            } else {
+                exit(ExitCode::Devices, "Self-test failed, use --no-self-test to start anyway");
+            }
+        }
+    }
+
+    info!("Starting prover");
+    if opt.dry_run {
+        warn!(
+            "{}",
+            Red.bold()
+                .paint("DRY RUN: shares are counted and logged but never submitted, nothing is earned")
+        );
+    }
+    if let Some(seed) = opt.deterministic_seed {
+        warn!("Deterministic nonce generation enabled with seed {}, do not use this for real mining", seed);
+    }
+    // if opt.old_protocol {
+    //     info!("Using old protocol");
+    //     let node = Node::init(address, pool);
+    //     debug!("Node initialized");
+    // }
+
+    let pool_name = pool.clone();
+    let notifier = match opt.webhook_url {
+        Some(url) => {
+            let name = match worker.as_deref() {
+                Some(worker) if !worker.is_empty() => worker.to_string(),
+                _ => pool_name.clone(),
+            };
+            match Notifier::new(url, name, &opt.webhook_events) {
+                Ok(notifier) => Some(Arc::new(notifier)),
+                Err(e) => exit(ExitCode::Config, format!("Unable to set up the webhook: {}", e)),
+            }
+        }
+        None => None,
+    };
+    let max_concurrent_proofs = match (opt.max_concurrent_proofs, opt.proof_memory) {
+        (Some(max), _) => Some(max),
+        (None, Some(proof_memory)) => match prover::available_memory() {
+            Some(available) => Some((available / (proof_memory.max(1) * 1024 * 1024)).max(1) as usize),
+            None => {
+                warn!("Unable to determine the available memory, not limiting concurrent proofs");
+                None
+            }
+        },
+        (None, None) => None,
+    };
+
+    #[allow(unused_mut)]
+    let mut gpu_sampler: Option<Arc<dyn TelemetrySampler>> = None;
+    #[cfg(feature = "nvml")]
+    {
+        if cuda.is_some() {
+            gpu_sampler = telemetry::NvmlSampler::init().map(|sampler| Arc::new(sampler) as Arc<dyn TelemetrySampler>);
+        }
+    }
+
+    let config = ProverConfig {
+        threads,
+        cuda,
+        cuda_jobs,
+        reject_window: opt.reject_window,
+        reject_threshold: opt.reject_threshold,
+        reject_cooldown: Duration::from_secs(opt.reject_cooldown),
+        reject_probation: opt.reject_probation,
+        latency_drift: opt.latency_drift,
+        deterministic_seed: opt.deterministic_seed,
+        max_concurrent_proofs,
+        rate_report: opt.rate_report(),
+        earnings: EarningsConfig {
+            reward_per_share: opt.reward_per_share,
+            reward_per_pps_day: opt.reward_per_pps_day,
+        },
+        nice: opt.nice,
+        share_alert: opt.share_alert_duration(),
+        watchdog_multiple: opt.watchdog_multiple,
+        cpu_path,
+        groups: Vec::new(),
+        notifier: notifier.clone(),
+        telemetry: gpu_sampler,
+        thresholds,
+        proving: !opt.no_prover,
+        backend: None,
+    };
+    let mut builder = MiningSession::builder()
+        .pool(pool)
+        .account(account)
+        .address(address)
+        .worker(worker)
+        .password(password.clone())
+        .pool_credentials(opt.pool_credentials.clone())
+        .protocol_limits(opt.protocol_limits())
+        .submit_queue(opt.submit_queue)
+        .max_ages(opt.max_ages())
+        .bandwidth(opt.bandwidth_limits())
+        .msgpack(opt.msgpack)
+        .job_ack(opt.job_ack)
+        .speculative(opt.speculative)
+        .pool_info(opt.pool_info)
+        .reorg_tolerance(opt.reorg_tolerance)
+        .template_validation(!opt.no_template_validation, Duration::from_secs(opt.template_max_skew))
+        .difficulty(opt.difficulty)
+        .groups(worker_groups)
+        .split(split)
+        .prover(config)
+        .keepalive(
+            Some(Duration::from_secs(opt.keepalive)).filter(|keepalive| !keepalive.is_zero()),
+            Duration::from_millis(opt.rtt_warning),
+        )
+        .unreachable_limit(opt.pool_unreachable_exit)
+        .record_traffic(opt.record_traffic.clone())
+        .session_tokens(opt.stats_file.as_ref().map(|path| Arc::new(SessionTokens::load(path))))
+        .outbox(opt.stats_file.as_ref().map(|path| Arc::new(Outbox::load(path))));
+    #[cfg(feature = "chaos")]
+    {
+        builder = builder.chaos(opt.chaos.clone());
+    }
+    if opt.dry_run {
+        builder = builder.dry_run(opt.dry_run_zero_rate);
+    }
+    let session = match builder.build() {
+        Ok(session) => session,
+        Err(e) => exit(ExitCode::Devices, format!("Unable to initialize prover: {}", e)),
+    };
+    debug!("Prover initialized");
+    let client = session.client().clone();
+    let groups = session.groups().to_vec();
+    let prover = session.prover().clone();
+
+    let share_log = match opt.share_log.as_ref() {
+        Some(path) => match ShareLog::open(path, opt.share_log_max_size, session.events()) {
+            Ok(share_log) => Some(share_log),
+            Err(e) => exit(ExitCode::Config, format!("Unable to open the share log: {:#}", e)),
+        },
+        None => None,
+    };
+    if let Err(e) = session.start().await {
+        exit(ExitCode::Devices, format!("Unable to start prover: {}", e));
+    }
+    if let Some(notifier) = notifier.as_ref() {
+        notifier.notify(EventKind::Start, format!("Mining on {}", pool_name));
+        notify::watch_connection(
+            notifier.clone(),
+            client.clone(),
+            Duration::from_secs(opt.webhook_disconnect_after),
+        );
+    }
+    if opt.idle_only {
+        idle::spawn(prover.clone(), opt.idle_threshold, Duration::from_secs(opt.idle_quiet));
+    }
+
+    if opt.failover_margin > 0.0 && !session.split().is_empty() {
+        warn!("Not failing over, the proving time is split between the pools");
+    } else if opt.failover_margin > 0.0 && opt.pools.len() > 1 {
+        quality::spawn(client.clone(), opt.pools.clone(), opt.failover_policy());
+    }
+
+    // Snapshot for a crash report, taking one in the panic hook could deadlock.
+    {
+        let prover = prover.clone();
+        let client = client.clone();
+        tokio::spawn(async move {
+            loop {
+                let status = Status::new(&prover.stats(), &client.stats(), &client.server(), &client.worker());
+                match serde_json::to_string(&status) {
+                    Ok(status) => crash::record_status(status),
+                    Err(e) => debug!("Unable to serialize the status for a crash report: {}", e),
+                }
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+        });
+    }
+
+    let started = Instant::now();
+    let stats_file = opt.stats_file.map(|path| Arc::new(StatsFile::load(path)));
+    if let Some(stats_file) = stats_file.clone() {
+        let prover = prover.clone();
+        let pool = pool_name.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                let stats = stats_file.previous().merge(&prover.stats(), started.elapsed(), &pool);
+                if let Err(e) = stats_file.save(&stats) {
+                    warn!("Unable to save stats: {}", e);
+                }
+            }
+        });
+    }
+
+    let (schedule_sender, schedule) = watch::channel(opt.schedule.clone());
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    let reloader = opt.config.clone().map(|path| {
+        let clients = std::iter::once(client.clone())
+            .chain(groups.iter().map(|(_, group_client)| group_client.clone()))
+            .collect();
+        let reloader = Arc::new(Reloader::new(
+            path,
+            matches.clone(),
+            settings.clone(),
+            log_levels,
+            prover.clone(),
+            clients,
+            schedule_sender.clone(),
+        ));
+        reload::spawn(reloader.clone());
+        reloader
+    });
+
+    #[cfg(feature = "metrics")]
+    {
+        // Endpoints configured on the same address share one listener.
+        let mut endpoints: Vec<(SocketAddr, http::Handler)> = Vec::new();
+        if let Some(bind) = opt.metrics_bind {
+            endpoints.push((bind, metrics::handler(prover.clone(), client.clone())));
+        }
+        if let Some(bind) = opt.status_bind {
+            let history = Arc::new(History::new(opt.history_retention));
+            history::spawn(history.clone(), prover.clone());
+            endpoints.push((bind, status::handler(prover.clone(), client.clone(), history.clone())));
+            endpoints.push((bind, history::handler(history)));
+            if let Some(token) = opt.control_token.clone() {
+                endpoints.push((bind, control::handler(prover.clone(), client.clone(), token, reloader.clone())));
+            }
+        } else if opt.control_token.is_some() {
+            warn!("--control-token has no effect without --status-bind");
+        }
+        let mut listeners: BTreeMap<SocketAddr, Vec<http::Handler>> = BTreeMap::new();
+        for (bind, handler) in endpoints {
+            listeners.entry(bind).or_default().push(handler);
+        }
+        for (bind, handlers) in listeners {
+            http::serve(bind, handlers);
+        }
+    }
+    if let Some(url) = opt.influx_url {
+        let config = InfluxConfig {
+            url,
+            bucket: opt.influx_bucket,
+            org: opt.influx_org,
+            token: opt.influx_token,
+            interval: Duration::from_secs(opt.influx_interval.max(1)),
+            tags: opt.influx_tags,
+        };
+        if let Err(e) = influx::spawn(config, prover.clone(), client.clone()) {
+            exit(ExitCode::Config, format!("Unable to set up the InfluxDB push: {}", e));
+        }
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = opt.mqtt_broker {
+        let config = MqttConfig {
+            broker,
+            username: opt.mqtt_username,
+            password: opt.mqtt_password,
+            #[cfg(feature = "tls")]
+            tls: opt.mqtt_tls || opt.mqtt_ca.is_some(),
+            #[cfg(feature = "tls")]
+            ca: opt.mqtt_ca,
+            interval: Duration::from_secs(opt.mqtt_interval.max(1)),
+        };
+        if let Err(e) = mqtt::spawn(config, prover.clone(), client.clone()) {
+            exit(ExitCode::Config, format!("Unable to set up MQTT: {}", e));
+        }
+    }
+    if let Some(server) = opt.statsd.as_ref() {
+        if let Err(e) = statsd::spawn(server, opt.statsd_prefix.clone(), prover.clone(), client.clone()) {
+            exit(ExitCode::Config, format!("Unable to set up statsd: {}", e));
+        }
+    }
+    systemd::spawn(prover.clone(), client.clone());
+    #[cfg(all(windows, feature = "windows-service"))]
+    if let Some(service) = windows_service.as_ref() {
+        service.running();
+    }
+    schedule::spawn(prover.clone(), client.clone(), schedule, opt.schedule_disconnect);
+
+    let service_stop = async {
+        #[cfg(all(windows, feature = "windows-service"))]
+        if let Some(service) = windows_service.as_mut() {
+            service.stop_requested().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    };
+    let max_runtime = async {
+        match opt.max_runtime {
+            Some(max_runtime) => tokio::time::sleep(max_runtime).await,
+            None => std::future::pending().await,
+        }
+    };
+    let dashboard = async {
+        #[cfg(feature = "tui")]
+        if let Some(logs) = tui_logs {
+            match tui::run(prover.clone(), client.clone(), logs).await {
+                Ok(()) => return,
+                Err(e) => error!("Dashboard failed: {}", e),
+            }
+        }
+        std::future::pending::<()>().await
+    };
+    let connection_failed = futures::future::select_all(
+        std::iter::once(&client)
+            .chain(groups.iter().map(|(_, group_client)| group_client))
+            .map(|connection| Box::pin(connection.failed())),
+    );
+    let (exit_code, exit_reason) = tokio::select! {
+        _ = shutdown::signal() => (ExitCode::Success, None),
+        _ = service_stop => {
+            info!("Stop requested by the service manager");
+            (ExitCode::Success, None)
+        }
+        _ = max_runtime => {
+            info!("Maximum runtime reached");
+            (ExitCode::Success, None)
+        }
+        _ = dashboard => (ExitCode::Success, None),
+        (error, ..) = connection_failed => (error.exit_code(), Some(error.to_string())),
+    };
+    match exit_reason.as_ref() {
+        Some(reason) => warn!("Shutting down: {}", reason),
+        None => info!("Shutting down"),
+    }
+    systemd::stopping();
+    #[cfg(all(windows, feature = "windows-service"))]
+    if let Some(service) = windows_service.as_ref() {
+        service.stopping();
+    }
+    session.shutdown().await;
+
+    if let Some(share_log) = share_log.as_ref() {
+        share_log.flush();
+    }
+
+    let session = prover.stats();
+    let uptime = started.elapsed();
+    let average_rate = if uptime.as_secs_f64() > 0.0 {
+        session.total_proofs as f64 / uptime.as_secs_f64()
+    } else {
+        0.0
+    };
+    info!(
+        "Session: {} proofs ({} average), {} / {} shares accepted, uptime {}s",
+        session.total_proofs,
+        units::format_rate(average_rate),
+        session.valid_shares,
+        session.valid_shares + session.invalid_shares,
+        uptime.as_secs()
+    );
+    if session.best_difficulty > 0 {
+        info!(
+            "Best share: {}",
+            estimate::best_share(session.best_difficulty, session.network_target)
+        );
+    }
+    if let Some(path) = opt.run_report.as_ref() {
+        let warnings = recent_logs.warnings(REPORT_LOG_LINES);
+        let report = RunReport::new(&session, &client.stats(), &client.server(), &client.worker(), uptime, warnings);
+        match report.save(path) {
+            Ok(()) => info!("Run report written to {}", path.display()),
+            Err(e) => error!("Unable to write the run report: {:#}", e),
+        }
+    }
+    if let Some(notifier) = notifier.as_ref() {
+        let details = format!(
+            "Stopped after {}s with {} / {} shares accepted",
+            uptime.as_secs(),
+            session.valid_shares,
+            session.valid_shares + session.invalid_shares
+        );
+        notifier.notify_and_wait(EventKind::Stop, details).await;
+    }
+    if let Some(stats_file) = stats_file {
+        let lifetime = stats_file.previous().merge(&session, uptime, &pool_name);
+        if let Err(e) = stats_file.save(&lifetime) {
+            error!("Unable to save stats: {}", e);
+        }
+        info!(
+            "Lifetime: {} proofs, {} / {} shares accepted, uptime {}s",
+            lifetime.total_proofs,
+            lifetime.valid_shares,
+            lifetime.valid_shares + lifetime.invalid_shares,
+            lifetime.uptime_secs
+        );
+    }
+    #[cfg(all(windows, feature = "windows-service"))]
+    if let Some(service) = windows_service.as_ref() {
+        service.stopped(exit_code.code() as u32);
+    }
+    if exit_code != ExitCode::Success {
+        exit(exit_code, exit_reason.unwrap_or_default());
+    }
+}
+
+#[cfg(vanity)]
+async fn vanity() {
+    let count: Arc<RwLock<u32>> = Arc::new(RwLock::new(0u32));
+    let end: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
+    let mut threads = vec![];
+    for _ in 0..num_cpus::get() {
+        let count = count.clone();
+        let end = end.clone();
+        threads.push(task::spawn(async move {
+            loop {
+                for _ in 0..100 {
+                    let account = snarkvm::dpc::Account::<Testnet2>::new(&mut rand::thread_rng());
+                    if format!("{}", account.address()).starts_with("aleo1haruka") {
+                        println!("{}", account.private_key());
+                        println!("{}", account.view_key());
+                        println!("{}", account.address());
+                        *end.write().unwrap() = true;
+                    }
+                }
+                *count.write().unwrap() += 100;
+                let c = *count.read().unwrap();
+                println!("{}", c);
+                if *end.read().unwrap() {
+                    break;
+                }
+            }
+        }));
+    }
+    for t in threads {
+        // Wait for the thread to finish. Returns a result.
+        t.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opt(args: &[&str]) -> Result<Opt, structopt::clap::Error> {
+        Opt::from_iter_safe(std::iter::once("prover").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn maps_the_verbosity_flags_to_filters() {
+        let cases: [(&[&str], LevelFilter, &str); 8] = [
+            (&[], LevelFilter::INFO, "warn,aleoxminer=info"),
+            (&["-q"], LevelFilter::WARN, "warn"),
+            (&["-v"], LevelFilter::DEBUG, "warn,aleoxminer=debug"),
+            (&["-d"], LevelFilter::DEBUG, "warn,aleoxminer=debug"),
+            (&["-vv"], LevelFilter::TRACE, "warn,snarkvm=debug,tokio=debug,aleoxminer=trace"),
+            (&["-v", "-v", "-v"], LevelFilter::TRACE, "warn,snarkvm=debug,tokio=debug,aleoxminer=trace"),
+            (&["-vv", "--console-level", "error"], LevelFilter::ERROR, "error"),
+            (&["-q", "--console-level", "debug"], LevelFilter::DEBUG, "warn,aleoxminer=debug"),
+        ];
+        for (args, level, directives) in cases {
+            let (console, file) = opt(args).unwrap().log_levels();
+            assert_eq!(console, level, "{:?}", args);
+            assert_eq!(file, level, "{:?}", args);
+            assert_eq!(logging::filter_directives(console), directives, "{:?}", args);
+        }
+
+        let (console, file) = opt(&["-q", "--file-level", "trace"]).unwrap().log_levels();
+        assert_eq!((console, file), (LevelFilter::WARN, LevelFilter::TRACE));
+        assert!(opt(&["-q", "-v"]).is_err());
+        assert!(opt(&["-q", "-d"]).is_err());
+    }
+
+    #[cfg(all(windows, feature = "windows-service"))]
+    #[test]
+    fn the_installed_service_runs_with_the_install_options() {
+        let install = opt(&["--worker", "rig1", "--threads", "8", "service", "install"]).unwrap();
+        assert!(matches!(install.command, Some(Command::Service(service::ServiceAction::Install))));
+        assert!(matches!(opt(&["service", "uninstall"]).unwrap().command, Some(Command::Service(..))));
+
+        let args: Vec<std::ffi::OsString> =
+            ["--worker", "rig1", "--threads", "8", "service", "install"].iter().map(Into::into).collect();
+        let launched = service::launch_arguments(&args);
+        let run = Opt::from_iter_safe(std::iter::once("prover".into()).chain(launched)).unwrap();
+        assert!(matches!(run.command, Some(Command::Service(service::ServiceAction::Run))));
+        assert_eq!(run.worker.as_deref(), Some("rig1"));
+        assert_eq!(run.threads, Some(8));
+    }
+
+    #[cfg(not(all(windows, feature = "windows-service")))]
+    #[test]
+    fn has_no_service_command_without_the_feature() {
+        assert!(opt(&["service", "install"]).is_err());
+        assert!(matches!(opt(&["mine"]).unwrap().command, Some(Command::Mine)));
+    }
+}
@@ -1,155 +1,1716 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+        PoisonError,
+        RwLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use futures_util::sink::SinkExt;
-use snarkvm::dpc::{testnet2::Testnet2, Address};
+use snarkvm::{
+    dpc::{testnet2::Testnet2, Address, BlockTemplate, PoSWProof},
+    traits::Network,
+};
 use tokio::{
-    net::TcpStream,
     sync::{
-        mpsc,
-        mpsc::{Receiver, Sender},
+        mpsc::Receiver,
+        watch,
         Mutex,
+        Notify,
     },
     task,
-    time::{sleep, timeout},
+    time::{interval, sleep, timeout},
 };
+use serde::Serialize;
+use thiserror::Error;
 use tokio_stream::StreamExt;
 use tokio_util::codec::Framed;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::{message::{Code, ProverMessage}, prover::ProverEvent};
-use bytes::{BytesMut, BufMut};
-use std::io::{Write, Read};
+use crate::{
+    channel::{self, ChannelStats, InstrumentedSender},
+    estimate,
+    exit::ExitCode,
+    histogram::{HistogramSnapshot, LatencyHistogram},
+    hooks::{self, MinerHooks},
+    message::{
+        capability_names,
+        difficulty_hint,
+        Code,
+        CodecStats,
+        MessageTraffic,
+        ProtocolError,
+        ProtocolLimits,
+        ProverCodec,
+        ProverMessage,
+        Secret,
+        AUTHORIZATION_REVOKED,
+        JOB_ACK_CAPABILITY,
+        MSGPACK_CAPABILITY,
+        MULTIPLEX_CAPABILITY,
+        POOL_INFO_CAPABILITY,
+        SESSION_CAPABILITY,
+        SESSION_TOKEN_PREFIX,
+        SPECULATIVE_CAPABILITY,
+    },
+    job_trace::JobTrace,
+    outbox::{Outbox, OutboxEntry},
+    outgoing::{MaxAges, Outgoing, OutgoingSender},
+    events::{EventBus, MinerEvent, ShareResult},
+    prover::ProverEvent,
+    reject::RejectBreakdown,
+    quality::{self, QualityInputs, QualityTracker},
+    rtt::{RttEstimator, RttStats},
+    stats::SessionTokens,
+    template::{self, TemplateError},
+    throttle::{Bandwidth, BandwidthLimits, BandwidthStats},
+    traffic::{Recorder, RecordingCodec},
+    transport::{Connector, TcpConnector},
+};
+#[cfg(feature = "chaos")]
+use crate::chaos::{ChaosConfig, ChaosStream};
+use snarkvm::utilities::ToBytes;
 
+/// Connection to the pool, reconnecting until the process exits. Start it with `start`.
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// use std::time::Duration;
+///
+/// use aleoxminer::{
+///     client::{self, Client},
+///     events::EventBus,
+///     message::ProtocolLimits,
+///     prover::Prover,
+///     testing::{self, FakeBackend, MockPool},
+/// };
+///
+/// let pool = MockPool::start().await?;
+/// let client = Client::init(
+///     None,
+///     Some("embedded".to_string()),
+///     Some(testing::address()),
+///     pool.address(),
+///     EventBus::new(),
+///     ProtocolLimits::default(),
+///     1024,
+/// );
+/// let backend = FakeBackend::new(Duration::from_millis(5));
+/// let prover = Prover::new(testing::prover_config(16, backend), client.clone())?;
+/// client::start(prover.event_sender(), client.clone());
+///
+/// let received = pool.wait_for(Duration::from_secs(10), |received| !received.authorizations.is_empty()).await?;
+/// assert_eq!(received.authorizations[0].1, "embedded");
+/// # Ok(())
+/// # }
+/// ```
 pub struct Client {
     account: Option<String>,
     worker: Option<String>,
     address: Option<Address<Testnet2>>,
-    server: String,
-    sender: Arc<Sender<ProverMessage>>,
-    receiver: Arc<Mutex<Receiver<ProverMessage>>>,
+    server: RwLock<String>,
+    password: RwLock<Secret>,
+    // Credentials overridden per pool address.
+    pool_credentials: RwLock<HashMap<String, PoolCredentials>>,
+    // Signalled when the server changes, the current connection is dropped.
+    reconnect: Notify,
+    sender: OutgoingSender,
+    receiver: Arc<Mutex<Receiver<Outgoing>>>,
+    // Counts the jobs notified by the pool, queued messages are stamped with it.
+    job_epoch: Arc<AtomicU64>,
+    max_ages: RwLock<MaxAges>,
+    // Only the latest proof rate matters, unsent older reports are superseded.
+    proof_rate: watch::Sender<Option<u64>>,
+    proof_rate_receiver: watch::Receiver<Option<u64>>,
+    // Whether the client should stay connected to the pool.
+    online: watch::Sender<bool>,
+    online_receiver: watch::Receiver<bool>,
+    // Height of the latest work notified by the pool, shares for older heights are stale.
+    latest_height: AtomicU32,
+    local_stale: AtomicU32,
+    // Set once shutdown begins, nothing is forwarded to the prover after that.
+    closing: AtomicBool,
+    // Worker group connections only submit shares, work comes from the main connection.
+    forward_work: AtomicBool,
+    // Pool the proving time is split with, work is forwarded tagged with this connection.
+    split: AtomicBool,
+    // Latest work from the pool, cleared on disconnect.
+    work: std::sync::Mutex<Option<(u64, BlockTemplate<Testnet2>)>>,
+    // Send a JobAck of the (height, pool target) the prover works on with each proof rate report.
+    job_ack: AtomicBool,
+    // Offer to take speculative jobs and to show PoolInfo messages.
+    speculative: AtomicBool,
+    accept_pool_info: AtomicBool,
+    // Name the worker of each share queued after a Worker message.
+    multiplex: AtomicBool,
+    working: std::sync::Mutex<Option<(u32, u64)>>,
+    // Latest PoolInfo and the pool that sent it.
+    pool_info: std::sync::Mutex<Option<(String, BTreeMap<String, String>)>>,
+    connected: AtomicBool,
+    authorized: AtomicBool,
+    connections: AtomicU32,
+    // Time spent connected to the pool before the current connection, and when that started.
+    connected_time: std::sync::Mutex<(Duration, Option<Instant>)>,
+    pending_submits: AtomicUsize,
+    // Time from sending a share to receiving its result.
+    submit_latency: LatencyHistogram,
+    // Time messages spent queued before being sent.
+    queue_wait: LatencyHistogram,
+    rejections: std::sync::Mutex<RejectBreakdown>,
+    events: EventBus,
+    rtt: std::sync::Mutex<RttEstimator>,
+    bandwidth: Arc<Bandwidth>,
+    limits: ProtocolLimits,
+    #[cfg(feature = "chaos")]
+    chaos: RwLock<Option<ChaosConfig>>,
+    // Directory each connection's traffic is recorded to.
+    record_traffic: RwLock<Option<PathBuf>>,
+    connector: RwLock<Arc<dyn Connector>>,
+    // Offer MessagePack frames when authorizing.
+    msgpack: AtomicBool,
+    // Blocks a job may go back without the reorg flag before it is dropped.
+    reorg_tolerance: AtomicU32,
+    dropped_jobs: AtomicU64,
+    // Check jobs with `template::validate`, allowing this much clock skew in seconds.
+    template_validation: AtomicBool,
+    template_max_skew: AtomicU64,
+    invalid_templates: AtomicU64,
+    // Difficulty hint added to the password, 0 for none.
+    difficulty: AtomicU64,
+    // Share results received while no share was waiting for one.
+    unmatched_results: AtomicU64,
+    proxy_exceptions: AtomicU64,
+    // Speculative jobs switched to, and the ones superseded or left when disconnecting.
+    speculative_activated: AtomicU64,
+    speculative_expired: AtomicU64,
+    // Shares queued again after a ProxyException.
+    proxy_retries: std::sync::Mutex<HashSet<<Testnet2 as Network>::PoSWNonce>>,
+    // Frames to and from every pool connection.
+    codec_stats: Arc<CodecStats>,
+    // Quality measurements of the current pool.
+    quality: std::sync::Mutex<QualityTracker>,
+    // Tokens of the pool sessions to resume, sessions aren't resumed without.
+    session_tokens: RwLock<Option<Arc<SessionTokens>>>,
+    // Shares waiting for a result, kept across restarts.
+    outbox: RwLock<Option<Arc<Outbox>>>,
+    // What the current connection negotiated, set once authorized.
+    connection: std::sync::Mutex<Option<ConnectionInfo>>,
+    // Shares are counted and logged instead of sent.
+    dry_run: AtomicBool,
+    // Report a proof rate of 0 in a dry run instead of none.
+    dry_run_zero_rate: AtomicBool,
+    would_submit: AtomicU64,
+    // Milliseconds between keepalive Canary messages, 0 to disable.
+    keepalive: AtomicU64,
+    // Round trip time in milliseconds above which stales become likely.
+    rtt_warning: AtomicU64,
+    rtt_warned: AtomicBool,
+    // Milliseconds without an authorized connection before giving up, 0 to retry forever.
+    unreachable_limit: AtomicU64,
+    // Set when the connection gives up for good.
+    failure: watch::Sender<Option<Arc<ClientError>>>,
+    failure_receiver: watch::Receiver<Option<Arc<ClientError>>>,
+}
+
+/// Why the connection to the pool failed.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Failed to connect to {server}: {source}")]
+    Connect {
+        server: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Timed out connecting to {server}")]
+    Timeout { server: String },
+    #[error(
+        "The pool rejected the credentials {attempts} times in a row, authorization failed{}",
+        .message.as_ref().map(|message| format!(": {}", message)).unwrap_or_default()
+    )]
+    AuthRejected { message: Option<String>, attempts: u32 },
+    #[error("Unable to mine on {server} for {}s, giving up", .after.as_secs())]
+    Unreachable { server: String, after: Duration },
+    #[error("Unreadable message from the pool: {0}")]
+    ProtocolMismatch(#[from] ProtocolError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl ClientError {
+    /// Exit code of the binary when a connection gives up with this error.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::AuthRejected { .. } => ExitCode::Auth,
+            Self::Unreachable { .. } => ExitCode::PoolUnreachable,
+            _ => ExitCode::Error,
+        }
+    }
+}
+
+/// Credentials of one pool entry, unset ones fall back to the top-level values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolCredentials {
+    pub account: Option<String>,
+    pub worker: Option<String>,
+    pub password: Option<Secret>,
+}
+
+// Keepalives without an answer after which the pool is assumed not to echo them.
+const MAX_UNANSWERED_CANARIES: usize = 3;
+// Shares awaiting a result on one connection, the oldest is given up beyond this.
+const MAX_PENDING_SUBMITS: usize = 1024;
+// Authorization rejections in a row after which the credentials are considered wrong.
+const MAX_AUTH_REJECTIONS: u32 = 3;
+// A job sent before the authorization result is dropped if the result takes longer than this.
+const EARLY_NOTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+// Time a probe of another pool gets to connect, and then to get a job.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+// Delay before sending a share again after the proxy reported an exception for it.
+const PROXY_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// A share sent on this connection, waiting for its result.
+struct PendingSubmit {
+    height: u32,
+    nonce: <Testnet2 as Network>::PoSWNonce,
+    proof: PoSWProof<Testnet2>,
+    sent: Instant,
+    // Sent again after a ProxyException, it isn't retried twice.
+    retry: bool,
+}
+
+/// What a connection to the pool negotiated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionInfo {
+    /// Protocol version sent with Authorize, without the capability bits
+    pub protocol_version: u16,
+    /// `json` or `msgpack`, with `json` SubmitResult and ProofRate are binary
+    pub wire_format: &'static str,
+    /// Capabilities offered to the pool and used by it
+    pub capabilities: Vec<&'static str>,
+    /// Seconds between keepalives, absent when disabled
+    pub keepalive_secs: Option<u64>,
+    /// Share difficulty asked for with a `d=` password field
+    pub requested_difficulty: Option<u64>,
+    /// Share difficulty of the latest job's target, absent until the first job
+    pub difficulty: Option<u64>,
+    /// TLS protocol version and cipher suite, absent on a plain connection
+    pub tls: Option<String>,
+    /// Compression of the byte stream, absent when uncompressed
+    pub compression: Option<&'static str>,
+    /// Pool software version from the `server_version` PoolInfo field, absent until the pool sends it
+    pub server_version: Option<String>,
+}
+
+impl std::fmt::Display for ConnectionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "protocol version {}, {} frames, capabilities: {}, keepalive: {}",
+            self.protocol_version,
+            self.wire_format,
+            match self.capabilities.is_empty() {
+                true => "none".to_string(),
+                false => self.capabilities.join(", "),
+            },
+            match self.keepalive_secs {
+                Some(secs) => format!("every {}s", secs),
+                None => "off".to_string(),
+            }
+        )?;
+        if let Some(requested) = self.requested_difficulty {
+            write!(f, ", requested difficulty: {}", estimate::format_difficulty(requested))?;
+        }
+        if let Some(tls) = self.tls.as_ref() {
+            write!(f, ", tls: {}", tls)?;
+        }
+        if let Some(compression) = self.compression {
+            write!(f, ", compression: {}", compression)?;
+        }
+        if let Some(version) = self.server_version.as_ref() {
+            write!(f, ", server version: {}", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Connection state for monitoring.
+#[derive(Debug, Clone)]
+pub struct ClientStats {
+    pub connected: bool,
+    pub authorized: bool,
+    pub reconnects: u32,
+    /// Total time connected to the pool
+    pub connected_time: Duration,
+    /// Shares sent that didn't get a result yet
+    pub pending_submits: usize,
+    pub submit_latency: HistogramSnapshot,
+    /// Time from queueing a message to sending it
+    pub queue_wait: HistogramSnapshot,
+    /// Jobs dropped for going back more blocks than the reorg tolerance
+    pub dropped_jobs: u64,
+    /// Jobs dropped for a template failing `template::validate`
+    pub invalid_templates: u64,
+    /// Share results received while no share was waiting for one, or late for a share given up on
+    pub unmatched_results: u64,
+    /// ProxyException results, the share is retried once and counted as a proxy rejection only if
+    /// it is lost
+    pub proxy_exceptions: u64,
+    /// Speculative jobs the pool activated
+    pub speculative_activated: u64,
+    /// Speculative jobs never activated
+    pub speculative_expired: u64,
+    /// Quality score of the current pool from 100 down to 0, see `quality::score`
+    pub quality_score: f64,
+    /// Frames and bytes exchanged with the pools by message type
+    pub traffic: Vec<MessageTraffic>,
+    /// Negotiated parameters of the authorized connection
+    pub connection: Option<ConnectionInfo>,
+    /// Outgoing queue and event bus
+    pub channels: Vec<ChannelStats>,
+    /// Rejected shares on this connection by result code
+    pub rejections: RejectBreakdown,
+    pub rtt: RttStats,
+    /// Bytes per second to and from the pool against the caps
+    pub bandwidth: BandwidthStats,
+    /// What the current pool last told about the account, e.g. the unpaid balance
+    pub pool_info: Option<BTreeMap<String, String>>,
+    pub dry_run: bool,
+    /// Shares a dry run would have submitted
+    pub would_submit: u64,
 }
 
 impl Client {
-    pub fn init(account: Option<String>, worker: Option<String>, address: Option<Address<Testnet2>>, server: String) -> Arc<Self> {
-        let (sender, receiver) = mpsc::channel(1024);
+    /// New connection to `server` (`host:port`), authorizing as `account` or `address`. Messages wait
+    /// in a queue of `submit_queue` until the connection is up.
+    pub fn init(
+        account: Option<String>,
+        worker: Option<String>,
+        address: Option<Address<Testnet2>>,
+        server: String,
+        events: EventBus,
+        limits: ProtocolLimits,
+        submit_queue: usize,
+    ) -> Arc<Self> {
+        let (sender, receiver) = channel::channel("outgoing", submit_queue.max(1));
+        let (proof_rate, proof_rate_receiver) = watch::channel(None);
+        let (online, online_receiver) = watch::channel(true);
+        let (failure, failure_receiver) = watch::channel(None);
+        let job_epoch = Arc::new(AtomicU64::default());
         Arc::new(Self {
             account,
             worker,
             address,
-            server,
-            sender: Arc::new(sender),
+            server: RwLock::new(server),
+            password: Default::default(),
+            pool_credentials: Default::default(),
+            reconnect: Notify::new(),
+            sender: OutgoingSender::with_epoch(sender, job_epoch.clone()),
             receiver: Arc::new(Mutex::new(receiver)),
+            job_epoch,
+            max_ages: RwLock::new(MaxAges::default()),
+            proof_rate,
+            proof_rate_receiver,
+            online,
+            online_receiver,
+            latest_height: Default::default(),
+            local_stale: Default::default(),
+            closing: Default::default(),
+            forward_work: AtomicBool::new(true),
+            split: Default::default(),
+            work: Default::default(),
+            job_ack: Default::default(),
+            speculative: Default::default(),
+            accept_pool_info: Default::default(),
+            multiplex: Default::default(),
+            working: Default::default(),
+            pool_info: Default::default(),
+            connected: Default::default(),
+            authorized: Default::default(),
+            connections: Default::default(),
+            connected_time: Default::default(),
+            pending_submits: Default::default(),
+            submit_latency: Default::default(),
+            queue_wait: Default::default(),
+            rejections: Default::default(),
+            events,
+            rtt: Default::default(),
+            bandwidth: Default::default(),
+            limits,
+            #[cfg(feature = "chaos")]
+            chaos: Default::default(),
+            record_traffic: Default::default(),
+            connector: RwLock::new(Arc::new(TcpConnector::default())),
+            msgpack: Default::default(),
+            reorg_tolerance: AtomicU32::new(2),
+            dropped_jobs: Default::default(),
+            template_validation: AtomicBool::new(true),
+            template_max_skew: AtomicU64::new(600),
+            invalid_templates: Default::default(),
+            difficulty: Default::default(),
+            unmatched_results: Default::default(),
+            proxy_exceptions: Default::default(),
+            speculative_activated: Default::default(),
+            speculative_expired: Default::default(),
+            proxy_retries: Default::default(),
+            codec_stats: Default::default(),
+            session_tokens: Default::default(),
+            outbox: Default::default(),
+            quality: Default::default(),
+            connection: Default::default(),
+            dry_run: Default::default(),
+            dry_run_zero_rate: Default::default(),
+            would_submit: Default::default(),
+            keepalive: Default::default(),
+            rtt_warning: AtomicU64::new(u64::MAX),
+            rtt_warned: Default::default(),
+            unreachable_limit: Default::default(),
+            failure,
+            failure_receiver,
         })
     }
 
-    pub fn sender(&self) -> Arc<Sender<ProverMessage>> {
+    /// Queue of messages to the pool.
+    pub fn sender(&self) -> OutgoingSender {
         self.sender.clone()
     }
 
-    pub fn receiver(&self) -> Arc<Mutex<Receiver<ProverMessage>>> {
+    /// Receiving end of the queue, used by the connection task.
+    pub fn receiver(&self) -> Arc<Mutex<Receiver<Outgoing>>> {
         self.receiver.clone()
     }
+
+    /// Queued messages older than these are dropped instead of sent.
+    pub fn set_max_ages(&self, max_ages: MaxAges) {
+        *self.max_ages.write().unwrap_or_else(PoisonError::into_inner) = max_ages;
+    }
+
+    /// Caps the bytes per second read from and written to the pool from the next connection on.
+    pub fn set_bandwidth_limits(&self, limits: BandwidthLimits) {
+        self.bandwidth.set_limits(limits);
+    }
+
+    /// Queues a proof rate report (p/s * 100), replacing any report not sent yet.
+    pub fn report_proof_rate(&self, rate: u64) {
+        let _ = self.proof_rate.send(Some(rate));
+    }
+
+    /// Height of the latest template from the pool.
+    pub fn latest_height(&self) -> u32 {
+        self.latest_height.load(Ordering::SeqCst)
+    }
+
+    /// Number of shares dropped because the pool had already moved on to a newer height.
+    pub fn local_stale(&self) -> u32 {
+        self.local_stale.load(Ordering::SeqCst)
+    }
+
+    /// Current pool address.
+    pub fn server(&self) -> String {
+        self.server.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Switches to another pool, reconnecting right away. Does nothing if already on `server`.
+    pub fn set_server(&self, server: String) {
+        let mut current = self.server.write().unwrap_or_else(PoisonError::into_inner);
+        if *current == server {
+            return;
+        }
+        *current = server;
+        drop(current);
+        *self.quality.lock().unwrap_or_else(PoisonError::into_inner) = QualityTracker::default();
+        self.reconnect.notify_one();
+    }
+
+    // Sends a share again after the proxy reported an exception for it, the pool drops duplicates.
+    // It goes through the queue like any share, and is lost if a new job arrives meanwhile.
+    fn retry_submit(
+        self: &Arc<Self>,
+        height: u32,
+        nonce: <Testnet2 as Network>::PoSWNonce,
+        proof: PoSWProof<Testnet2>,
+    ) {
+        debug!(
+            "Proxy exception for the share for block {}, retrying in {}s",
+            height,
+            PROXY_RETRY_DELAY.as_secs()
+        );
+        self.proxy_retries.lock().unwrap_or_else(PoisonError::into_inner).insert(nonce);
+        let client = self.clone();
+        task::spawn(async move {
+            sleep(PROXY_RETRY_DELAY).await;
+            let moved_on = client.latest_height() > height;
+            if moved_on || client.sender.send(ProverMessage::Submit(height, nonce, proof)).await.is_err() {
+                client.proxy_retries.lock().unwrap_or_else(PoisonError::into_inner).remove(&nonce);
+                client.forget_submit(&nonce);
+                client.proxy_share_lost(height);
+            }
+        });
+    }
+
+    // Takes `nonce` out of the retried shares, returns whether it was one.
+    fn take_retry(&self, nonce: &<Testnet2 as Network>::PoSWNonce) -> bool {
+        self.proxy_retries.lock().unwrap_or_else(PoisonError::into_inner).remove(nonce)
+    }
+
+    fn proxy_share_lost(&self, height: u32) {
+        warn!("The share for block {} is lost to a proxy exception", height);
+        self.rejections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(&Code::ProxyException);
+    }
+
+    /// Quality measurements of the current pool.
+    pub fn quality(&self) -> QualityInputs {
+        self.quality.lock().unwrap_or_else(PoisonError::into_inner).inputs()
+    }
+
+    fn track_quality(&self, record: impl FnOnce(&mut QualityTracker)) {
+        record(&mut self.quality.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Connects to `server` aside from the current connection, authorizes and waits for the first
+    /// job. The connect time stands for the round trip time, `None` if the pool fails any step.
+    /// The probe resumes no session and offers no capabilities, MessagePack included.
+    pub async fn probe(&self, server: &str) -> Option<QualityInputs> {
+        let started = Instant::now();
+        let socket = timeout(PROBE_TIMEOUT, self.connector().connect(server)).await.ok()?.ok()?;
+        let rtt = started.elapsed();
+        let mut framed = Framed::new(socket, ProverCodec::new(self.limits));
+        framed.send(self.plain_authorization(server)).await.ok()?;
+        let first_notify = timeout(PROBE_TIMEOUT, async {
+            while let Some(message) = framed.next().await {
+                match message.ok()? {
+                    ProverMessage::Notify(..) => return Some(started.elapsed()),
+                    ProverMessage::AuthorizeResult(false, _) => return None,
+                    _ => {}
+                }
+            }
+            None
+        })
+        .await
+        .ok()??;
+        Some(QualityInputs {
+            rtt: Some(rtt),
+            first_notify: Some(first_notify),
+            ..Default::default()
+        })
+    }
+
+    /// Password sent with the next authorization.
+    pub fn set_password(&self, password: Secret) {
+        *self.password.write().unwrap_or_else(PoisonError::into_inner) = password;
+    }
+
+    /// Per pool overrides, by pool address. Used from the next authorization on.
+    pub fn set_pool_credentials(&self, credentials: HashMap<String, PoolCredentials>) {
+        *self.pool_credentials.write().unwrap_or_else(PoisonError::into_inner) = credentials;
+    }
+
+    /// `credentials` for a worker group connection, which keeps its own worker name.
+    pub fn group_credentials(credentials: &HashMap<String, PoolCredentials>) -> HashMap<String, PoolCredentials> {
+        credentials
+            .iter()
+            .map(|(server, credentials)| {
+                let credentials = PoolCredentials {
+                    worker: None,
+                    ..credentials.clone()
+                };
+                (server.clone(), credentials)
+            })
+            .collect()
+    }
+
+    fn overrides(&self, server: &str) -> PoolCredentials {
+        self.pool_credentials
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(server)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Effective (account, worker, password) for `server`.
+    fn credentials(&self, server: &str) -> (Option<String>, String, Secret) {
+        let overrides = self.overrides(server);
+        (
+            overrides.account.or_else(|| self.account.clone()),
+            overrides
+                .worker
+                .unwrap_or_else(|| self.worker.clone().unwrap_or_default()),
+            overrides
+                .password
+                .unwrap_or_else(|| self.password.read().unwrap_or_else(PoisonError::into_inner).clone()),
+        )
+    }
+
+    // Authorize message for `server` with the credentials only, no session to resume and no capabilities.
+    fn plain_authorization(&self, server: &str) -> ProverMessage {
+        let (account, worker, mut password) = self.credentials(server);
+        let difficulty = self.difficulty.load(Ordering::SeqCst);
+        if difficulty > 0 {
+            password = password.with_difficulty(difficulty);
+        }
+        authorization(account.as_deref(), self.address.as_ref(), &worker, password)
+    }
+
+    // Authorize message for `server`, offering the enabled capabilities.
+    fn authorization(&self, server: &str) -> ProverMessage {
+        let resume_sessions = self.session_tokens.read().unwrap_or_else(PoisonError::into_inner).is_some();
+        let mut authorization = self.plain_authorization(server);
+        if let ProverMessage::Authorize(_, _, password, version) = &mut authorization {
+            if let Some(token) = self.session_token(server) {
+                *password = password.with_field("session", token);
+            }
+            if self.msgpack.load(Ordering::SeqCst) {
+                *version |= MSGPACK_CAPABILITY;
+            }
+            if resume_sessions {
+                *version |= SESSION_CAPABILITY;
+            }
+            if self.speculative.load(Ordering::SeqCst) {
+                *version |= SPECULATIVE_CAPABILITY;
+            }
+            if self.accept_pool_info.load(Ordering::SeqCst) {
+                *version |= POOL_INFO_CAPABILITY;
+            }
+            if self.job_ack.load(Ordering::SeqCst) {
+                *version |= JOB_ACK_CAPABILITY;
+            }
+            if self.multiplex.load(Ordering::SeqCst) {
+                *version |= MULTIPLEX_CAPABILITY;
+            }
+        }
+        authorization
+    }
+
+    // Key of this connection's session token for `server`.
+    fn session_key(&self, server: &str) -> String {
+        format!("{}/{}", server, self.credentials(server).1)
+    }
+
+    // Token of the session to resume on `server`.
+    fn session_token(&self, server: &str) -> Option<String> {
+        let tokens = self.session_tokens.read().unwrap_or_else(PoisonError::into_inner);
+        tokens.as_ref()?.get(&self.session_key(server))
+    }
+
+    // Keeps the session token of a successful AuthorizeResult, returns whether the pool gave one. A
+    // token different from the one sent means the pool started a new session.
+    fn keep_session_token(&self, server: &str, message: Option<&str>) -> bool {
+        let tokens = match self.session_tokens.read().unwrap_or_else(PoisonError::into_inner).clone() {
+            Some(tokens) => tokens,
+            None => return false,
+        };
+        let token = match message.and_then(|message| message.strip_prefix(SESSION_TOKEN_PREFIX)) {
+            Some(token) => token,
+            None => return false,
+        };
+        let key = self.session_key(server);
+        match tokens.get(&key) {
+            Some(previous) if previous == token => debug!("Resumed pool session {}", token),
+            Some(_) => debug!("The pool started a new session {}", token),
+            None => debug!("Pool session {}", token),
+        }
+        if let Err(e) = tokens.set(&key, Some(token)) {
+            warn!("Unable to save the pool session token: {}", e);
+        }
+        true
+    }
+
+    // Forgets the session token of `server` after a failed authorization, the next one starts anew.
+    fn forget_session_token(&self, server: &str) {
+        if let Some(tokens) = self.session_tokens.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+            if let Err(e) = tokens.set(&self.session_key(server), None) {
+                warn!("Unable to save the pool session tokens: {}", e);
+            }
+        }
+    }
+
+    /// Keeps a share in the outbox until the pool answers it, called before queueing the share.
+    pub fn keep_submit(&self, height: u32, nonce: <Testnet2 as Network>::PoSWNonce, proof: &PoSWProof<Testnet2>) {
+        if self.dry_run() {
+            return;
+        }
+        if let Some(outbox) = self.outbox.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+            let entry = OutboxEntry {
+                key: self.session_key(&self.server()),
+                height,
+                nonce,
+                proof: proof.clone(),
+            };
+            if let Err(e) = outbox.add(entry) {
+                warn!("Unable to save the share to the outbox: {}", e);
+            }
+        }
+    }
+
+    // Takes a share out of the outbox once it got a result or is lost.
+    fn forget_submit(&self, nonce: &<Testnet2 as Network>::PoSWNonce) {
+        if let Some(outbox) = self.outbox.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+            if let Err(e) = outbox.remove(nonce) {
+                warn!("Unable to update the outbox: {}", e);
+            }
+        }
+    }
+
+    // Queues the shares of the previous run that are still fresh at `height`, once per run.
+    async fn resubmit_restored(&self, height: u32) {
+        let outbox = match self.outbox.read().unwrap_or_else(PoisonError::into_inner).clone() {
+            Some(outbox) => outbox,
+            None => return,
+        };
+        for entry in outbox.take_restored(&self.session_key(&self.server()), height) {
+            info!("Submitting the share for block {} found before the restart", entry.height);
+            if self
+                .sender
+                .send(ProverMessage::Submit(entry.height, entry.nonce, entry.proof))
+                .await
+                .is_err()
+            {
+                warn!("Unable to queue the share for block {}", entry.height);
+            }
+        }
+    }
+
+    // Share difficulty asked of `server`.
+    fn requested_difficulty(&self, server: &str) -> Option<u64> {
+        match self.difficulty.load(Ordering::SeqCst) {
+            0 => difficulty_hint(self.credentials(server).2.expose()),
+            difficulty => Some(difficulty),
+        }
+    }
+
+    // Records the pool software version from a PoolInfo, logging when it is new.
+    fn reported_server_version(&self, version: Option<&String>) {
+        let mut connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+        let (info, version) = match (connection.as_mut(), version) {
+            (Some(info), Some(version)) => (info, version),
+            _ => return,
+        };
+        if info.server_version.as_ref() != Some(version) {
+            info!("The pool runs {}", version);
+            info.server_version = Some(version.clone());
+        }
+    }
+
+    // Records the difficulty of a new job's target, logging when the pool changes it.
+    fn assigned_target(&self, target: u64) {
+        let difficulty = estimate::difficulty(target);
+        let mut connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+        let info = match connection.as_mut() {
+            Some(info) => info,
+            None => return,
+        };
+        if info.difficulty.replace(difficulty) == Some(difficulty) {
+            return;
+        }
+        let formatted = estimate::format_difficulty(difficulty);
+        match info.requested_difficulty {
+            Some(requested) if requested != difficulty => warn!(
+                "The pool assigned share difficulty {} instead of the requested {}",
+                formatted,
+                estimate::format_difficulty(requested)
+            ),
+            Some(_) => info!("The pool assigned the requested share difficulty {}", formatted),
+            None => debug!("The pool assigned share difficulty {}", formatted),
+        }
+    }
+
+    /// Worker name on the current pool.
+    pub fn worker(&self) -> String {
+        self.credentials(&self.server()).1
+    }
+
+    /// Snapshot of the counters.
+    pub fn stats(&self) -> ClientStats {
+        ClientStats {
+            connected: self.connected.load(Ordering::SeqCst),
+            authorized: self.authorized.load(Ordering::SeqCst),
+            reconnects: self.connections.load(Ordering::SeqCst).saturating_sub(1),
+            connected_time: {
+                let (total, since) = *self.connected_time.lock().unwrap_or_else(PoisonError::into_inner);
+                total + since.map(|since| since.elapsed()).unwrap_or_default()
+            },
+            pending_submits: self.pending_submits.load(Ordering::SeqCst),
+            submit_latency: self.submit_latency.snapshot(),
+            queue_wait: self.queue_wait.snapshot(),
+            dropped_jobs: self.dropped_jobs.load(Ordering::SeqCst),
+            invalid_templates: self.invalid_templates.load(Ordering::SeqCst),
+            unmatched_results: self.unmatched_results.load(Ordering::SeqCst),
+            proxy_exceptions: self.proxy_exceptions.load(Ordering::SeqCst),
+            speculative_activated: self.speculative_activated.load(Ordering::SeqCst),
+            speculative_expired: self.speculative_expired.load(Ordering::SeqCst),
+            traffic: self.codec_stats.snapshot(),
+            quality_score: quality::score(&self.quality()),
+            connection: self.connection.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            channels: vec![self.sender.metrics().stats(), self.events.channel_stats()],
+            rejections: *self.rejections.lock().unwrap_or_else(PoisonError::into_inner),
+            rtt: self.rtt.lock().unwrap_or_else(PoisonError::into_inner).stats(),
+            bandwidth: self.bandwidth.stats(),
+            pool_info: self.pool_info(),
+            dry_run: self.dry_run(),
+            would_submit: self.would_submit.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Counts and logs shares instead of submitting them, the proof rate is reported as 0 with
+    /// `zero_rate` and not at all otherwise.
+    pub fn set_dry_run(&self, zero_rate: bool) {
+        self.dry_run_zero_rate.store(zero_rate, Ordering::SeqCst);
+        self.dry_run.store(true, Ordering::SeqCst);
+    }
+
+    /// Offers MessagePack frames to the pool from the next authorization on, they are used if the
+    /// pool supports them too.
+    pub fn set_msgpack(&self, msgpack: bool) {
+        self.msgpack.store(msgpack, Ordering::SeqCst);
+    }
+
+    /// Sends the pool a JobAck with each proof rate report from the next authorization on. Only for
+    /// pools supporting it, see `JOB_ACK_CAPABILITY`.
+    pub fn set_job_ack(&self, job_ack: bool) {
+        self.job_ack.store(job_ack, Ordering::SeqCst);
+    }
+
+    /// Takes speculative jobs for the next height from the next authorization on, see
+    /// `SPECULATIVE_CAPABILITY`. Pools not knowing the capability may reject the authorization.
+    pub fn set_speculative(&self, speculative: bool) {
+        self.speculative.store(speculative, Ordering::SeqCst);
+    }
+
+    /// Asks the pool for PoolInfo messages from the next authorization on, see `POOL_INFO_CAPABILITY`.
+    pub fn set_accept_pool_info(&self, accept: bool) {
+        self.accept_pool_info.store(accept, Ordering::SeqCst);
+    }
+
+    /// Submits the shares of several workers from the next authorization on, see `MULTIPLEX_CAPABILITY`.
+    /// A Worker message queued right before a Submit is sent along with it, and dropped with it.
+    pub fn set_multiplex(&self, multiplex: bool) {
+        self.multiplex.store(multiplex, Ordering::SeqCst);
+    }
+
+    /// Fields of the latest PoolInfo from the current pool, `None` until it sends one.
+    pub fn pool_info(&self) -> Option<BTreeMap<String, String>> {
+        let pool_info = self.pool_info.lock().unwrap_or_else(PoisonError::into_inner);
+        let server = self.server();
+        pool_info
+            .as_ref()
+            .filter(|(from, _)| *from == server)
+            .map(|(_, fields)| fields.clone())
+    }
+
+    /// Records the job the prover works on for this pool, acknowledged with the next proof rate report.
+    pub fn working_on(&self, height: u32, pool_target: u64) {
+        *self.working.lock().unwrap_or_else(PoisonError::into_inner) = Some((height, pool_target));
+    }
+
+    /// Asks the pools for this share difficulty with a `d=` password field, replacing any in the
+    /// password, 0 to send the password unchanged. Pools may ignore it, the target of their jobs is
+    /// used either way.
+    pub fn set_difficulty(&self, difficulty: u64) {
+        self.difficulty.store(difficulty, Ordering::SeqCst);
+    }
+
+    /// Jobs for a block more than `blocks` below the highest one of the connection are dropped,
+    /// unless the pool flags them as a reorg.
+    pub fn set_reorg_tolerance(&self, blocks: u32) {
+        self.reorg_tolerance.store(blocks, Ordering::SeqCst);
+    }
+
+    /// Drops jobs whose template fails `template::validate` with a timestamp at most `max_skew` off
+    /// the local clock. Disabled, every job goes to the prover.
+    pub fn set_template_validation(&self, enabled: bool, max_skew: Duration) {
+        self.template_validation.store(enabled, Ordering::SeqCst);
+        self.template_max_skew.store(max_skew.as_secs(), Ordering::SeqCst);
+    }
+
+    // Checks a job against the previous one from the pool, see `set_template_validation`.
+    fn check_template(&self, template: &BlockTemplate<Testnet2>) -> Result<(), TemplateError> {
+        if !self.template_validation.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let previous = self
+            .work()
+            .map(|(_, previous)| (previous.block_height(), previous.difficulty_target()));
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let max_skew = Duration::from_secs(self.template_max_skew.load(Ordering::SeqCst));
+        template::validate(template, previous, now, max_skew).map_err(|e| {
+            self.invalid_templates.fetch_add(1, Ordering::SeqCst);
+            e
+        })
+    }
+
+    /// Injects faults into every following connection.
+    #[cfg(feature = "chaos")]
+    pub fn set_chaos(&self, chaos: Option<ChaosConfig>) {
+        *self.chaos.write().unwrap_or_else(PoisonError::into_inner) = chaos;
+    }
+
+    /// Resumes the pool sessions of `tokens` and keeps the tokens of new ones, from the next
+    /// authorization on.
+    pub fn set_session_tokens(&self, tokens: Option<Arc<SessionTokens>>) {
+        *self.session_tokens.write().unwrap_or_else(PoisonError::into_inner) = tokens;
+    }
+
+    /// Keeps the shares waiting for a result in `outbox`, see `keep_submit`. The shares it has from
+    /// the previous run are submitted once the pool sends work for their height.
+    pub fn set_outbox(&self, outbox: Option<Arc<Outbox>>) {
+        *self.outbox.write().unwrap_or_else(PoisonError::into_inner) = outbox;
+    }
+
+    /// Records the traffic of every following connection to a new file in `dir`.
+    pub fn set_record_traffic(&self, dir: Option<PathBuf>) {
+        *self.record_traffic.write().unwrap_or_else(PoisonError::into_inner) = dir;
+    }
+
+    /// Calls `hooks` on the events of this connection and its prover, from a task of its own.
+    pub fn set_hooks(&self, hooks: Arc<dyn MinerHooks>) {
+        hooks::spawn(hooks, &self.events);
+    }
+
+    /// Opens the following connections with `connector` instead of plain TCP.
+    pub fn set_connector(&self, connector: Arc<dyn Connector>) {
+        *self.connector.write().unwrap_or_else(PoisonError::into_inner) = connector;
+    }
+
+    fn connector(&self) -> Arc<dyn Connector> {
+        self.connector.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Whether shares are only counted, see `set_dry_run`.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::SeqCst)
+    }
+
+    /// Whether work notified on this connection is passed on to the prover.
+    pub fn set_forward_work(&self, forward_work: bool) {
+        self.forward_work.store(forward_work, Ordering::SeqCst);
+    }
+
+    /// Marks the connection as one of the pools the proving time is split between, see `split::spawn`.
+    /// Its work reaches the prover as `ProverEvent::PoolWork` so the shares are submitted here.
+    pub fn set_split(&self, split: bool) {
+        self.split.store(split, Ordering::SeqCst);
+    }
+
+    /// Latest (pool target, template) from the pool, `None` until the pool sends work or while
+    /// disconnected.
+    pub fn work(&self) -> Option<(u64, BlockTemplate<Testnet2>)> {
+        self.work.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Whether the connection is authorized and has work.
+    pub fn ready(&self) -> bool {
+        self.connected.load(Ordering::SeqCst) && self.authorized.load(Ordering::SeqCst) && self.work().is_some()
+    }
+
+    /// Passes the latest work on to the prover, tagged with this connection.
+    pub async fn forward_latest(self: &Arc<Self>, prover_sender: &InstrumentedSender<ProverEvent>) {
+        if let Some((pool_target, block_template)) = self.work() {
+            let trace = JobTrace::new(
+                block_template.block_height(),
+                self.job_epoch.load(Ordering::SeqCst),
+                &self.server(),
+            );
+            if let Err(e) = prover_sender
+                .send(ProverEvent::PoolWork(self.clone(), pool_target, block_template, trace))
+                .await
+            {
+                error!("Error sending work to prover: {}", e);
+            }
+        }
+    }
+
+    /// Sends a Canary every `interval` to measure the round trip time, warning when it exceeds `warning`.
+    pub fn set_keepalive(&self, interval: Option<Duration>, warning: Duration) {
+        self.keepalive
+            .store(interval.map_or(0, |interval| interval.as_millis() as u64), Ordering::SeqCst);
+        self.rtt_warning.store(warning.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    fn record_rtt(&self, sample: Duration) {
+        let average = {
+            let mut rtt = self.rtt.lock().unwrap_or_else(PoisonError::into_inner);
+            rtt.record(sample);
+            rtt.stats().average.unwrap_or(sample)
+        };
+        let warning = self.rtt_warning.load(Ordering::SeqCst);
+        if average.as_millis() as u64 > warning {
+            if !self.rtt_warned.swap(true, Ordering::SeqCst) {
+                warn!(
+                    "Round trip time to the pool is {}ms (over {}ms), shares are more likely to go stale",
+                    average.as_millis(),
+                    warning
+                );
+            }
+        } else {
+            self.rtt_warned.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Bus the connection and share events are published on.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Stops forwarding work and share results to the prover.
+    pub fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
+    /// Disconnects from the pool until set back online.
+    pub fn set_online(&self, online: bool) {
+        let _ = self.online.send(online);
+    }
+
+    /// Gives up once the pool couldn't be mined on for `limit`, time spent offline on purpose excluded.
+    pub fn set_unreachable_limit(&self, limit: Option<Duration>) {
+        self.unreachable_limit
+            .store(limit.map_or(0, |limit| limit.as_millis() as u64), Ordering::SeqCst);
+    }
+
+    /// Resolves once the connection gave up for good, with the reason.
+    pub async fn failed(&self) -> Arc<ClientError> {
+        let mut failure = self.failure_receiver.clone();
+        loop {
+            if let Some(failure) = failure.borrow().clone() {
+                return failure;
+            }
+            if failure.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    fn fail(&self, error: ClientError) {
+        let _ = self.failure.send(Some(Arc::new(error)));
+    }
 }
 
-pub fn start(prover_sender: Arc<Sender<ProverEvent>>, client: Arc<Client>) {
+/// Authorize message for an Impool account, or for an address if there is no account.
+pub fn authorization(
+    account: Option<&str>,
+    address: Option<&Address<Testnet2>>,
+    worker: &str,
+    password: Secret,
+) -> ProverMessage {
+    let name = match (account, address) {
+        (Some(account), _) => account.to_string(),
+        (None, Some(address)) => address.to_string(),
+        (None, None) => String::new(),
+    };
+    ProverMessage::Authorize(name, worker.to_string(), password, *ProverMessage::version())
+}
+
+// Version of an Authorize message without the protocol version, only its capability bits.
+fn offered_capabilities(authorization: &ProverMessage) -> u16 {
+    match authorization {
+        ProverMessage::Authorize(_, _, _, version) => version & !*ProverMessage::version(),
+        _ => 0,
+    }
+}
+
+// The job sent before the authorization result, unless the result took too long.
+fn fresh_job(early_notify: Option<(BlockTemplate<Testnet2>, u64, Instant)>) -> Option<(BlockTemplate<Testnet2>, u64)> {
+    let (template, target, received) = early_notify?;
+    if received.elapsed() > EARLY_NOTIFY_TIMEOUT {
+        debug!(
+            "Discarding the job for block {} sent {}s before the authorization",
+            template.block_height(),
+            received.elapsed().as_secs()
+        );
+        return None;
+    }
+    Some((template, target))
+}
+
+// Records new work from the pool and hands it to the prover, unless the connection only submits.
+async fn new_work(
+    client: &Arc<Client>,
+    prover_sender: &InstrumentedSender<ProverEvent>,
+    block_template: BlockTemplate<Testnet2>,
+    pool_target: u64,
+) {
+    let height = block_template.block_height();
+    client.latest_height.store(height, Ordering::SeqCst);
+    let job_epoch = client.job_epoch.fetch_add(1, Ordering::SeqCst) + 1;
+    let trace = JobTrace::new(height, job_epoch, &client.server());
+    client.assigned_target(pool_target);
+    *client.work.lock().unwrap_or_else(PoisonError::into_inner) = Some((pool_target, block_template.clone()));
+    client.resubmit_restored(height).await;
+    if !client.forward_work.load(Ordering::SeqCst) {
+        return;
+    }
+    client.events.publish(MinerEvent::NewJob {
+        height,
+        target: pool_target,
+    });
+    let event = if client.split.load(Ordering::SeqCst) {
+        ProverEvent::PoolWork(client.clone(), pool_target, block_template, trace)
+    } else {
+        ProverEvent::NewWork(pool_target, block_template, trace)
+    };
+    if let Err(e) = prover_sender.send(event).await {
+        error!("Error sending work to prover: {}", e);
+    } else {
+        trace!("Sent work to prover");
+    }
+}
+
+/// Runs the connection in a task, new work and share results are sent to `prover_sender`.
+pub fn start(prover_sender: InstrumentedSender<ProverEvent>, client: Arc<Client>) {
     task::spawn(async move {
         let receiver = client.receiver();
+        let mut online = client.online_receiver.clone();
+        let mut unreachable_since = Instant::now();
+        let mut auth_rejections = 0;
         loop {
+            if !*online.borrow() {
+                while !*online.borrow() {
+                    if online.changed().await.is_err() {
+                        return;
+                    }
+                }
+                // Time offline on purpose doesn't count as unreachable.
+                unreachable_since = Instant::now();
+            }
             info!("Connecting to server...");
-            match timeout(Duration::from_secs(5), TcpStream::connect(&client.server)).await {
-                Ok(socket) => match socket {
-                    Ok(socket) => {
-                        info!("Connected to {}", client.server);
-                        let mut framed = Framed::new(socket, ProverMessage::Canary);
-
-                        let worker = client.worker.as_ref().unwrap().clone();
-                        let authorization = match &client.account {
-                            Some(account) => ProverMessage::Authorize(account.clone(), worker, String::new(), *ProverMessage::version()),
-                            None => ProverMessage::Authorize(client.address.as_ref().unwrap().to_string(), worker, String::new(), *ProverMessage::version())
-                        };
-
-                        if let Err(e) = framed.send(authorization).await {
-                            error!("Error sending authorization: {}", e);
-                        } else {
-                            debug!("Sent authorization");
-                        }
-                        let receiver = &mut *receiver.lock().await;
-                        while receiver.try_recv().is_ok() {}
-                        loop {
-                            tokio::select! {
-                                Some(message) = receiver.recv() => {
-                                    // let message = message.clone();
-                                    let name = message.name();
-                                    debug!("Sending {} to server", name);
-                                    if let Err(e) = framed.send(message).await {
-                                        error!("Error sending {}: {:?}", name, e);
+            let server = client.server();
+            let connecting = Instant::now();
+            match client.connector().connect(&server).await {
+                Ok(socket) => {
+                    info!("Connected to {}", server);
+                    // Connecting takes at least one round trip.
+                    client.record_rtt(connecting.elapsed());
+                    client.events.publish(MinerEvent::Connected {
+                        server: server.clone(),
+                        worker: client.worker(),
+                    });
+                    client.connected.store(true, Ordering::SeqCst);
+                    client.connected_time.lock().unwrap_or_else(PoisonError::into_inner).1 = Some(Instant::now());
+                    client.connections.fetch_add(1, Ordering::SeqCst);
+                    let record_traffic = client.record_traffic.read().unwrap_or_else(PoisonError::into_inner).clone();
+                    let recorder = record_traffic.and_then(|dir| {
+                        Recorder::create(&dir, &server)
+                            .map_err(|e| warn!("Unable to record the pool traffic: {:#}", e))
+                            .ok()
+                    });
+                    #[cfg(feature = "chaos")]
+                    let socket = {
+                        let chaos = client.chaos.read().unwrap_or_else(PoisonError::into_inner).clone();
+                        let chaos = chaos.unwrap_or_default();
+                        ChaosStream::new(socket, &chaos)
+                    };
+                    let socket = client.bandwidth.wrap(socket);
+                    let mut codec = ProverCodec::with_stats(client.limits, client.codec_stats.clone());
+                    // Same as the offer in the authorization below.
+                    if client.msgpack.load(Ordering::SeqCst) {
+                        codec.offer_msgpack();
+                    }
+                    let mut framed = Framed::new(socket, RecordingCodec::new(codec, recorder));
+
+                    let authorization = client.authorization(&server);
+                    // Capability bits of the latest authorization, the pool takes those it doesn't refuse.
+                    let mut offered = offered_capabilities(&authorization);
+                    if let Err(e) = framed.send(authorization).await {
+                        error!("Error sending authorization: {}", e);
+                    } else {
+                        debug!("Sent authorization");
+                    }
+                    let receiver = &mut *receiver.lock().await;
+                    while receiver.try_recv().is_ok() {}
+                    // The pool answers submits in order, so results are matched to the oldest pending submit.
+                    let mut pending: VecDeque<PendingSubmit> = VecDeque::new();
+                    // Shares given up while waiting, the next results answer them and are skipped.
+                    let mut given_up = 0;
+                    let mut current_height = 0;
+                    let mut current_target = None;
+                    // Some pools send the job before the authorization result, it waits for the result.
+                    let mut early_notify: Option<(BlockTemplate<Testnet2>, u64, Instant)> = None;
+                    // Job for the next height held until the pool activates it, never mined before.
+                    let mut speculative: Option<(BlockTemplate<Testnet2>, u64)> = None;
+                    // Highest block this connection, jobs for much lower ones are replays unless flagged as a reorg.
+                    let mut highest_height = 0;
+                    let mut awaiting_job = true;
+                    let mut proof_rate = client.proof_rate_receiver.clone();
+                    let keepalive_interval = client.keepalive.load(Ordering::SeqCst);
+                    // As offered in the authorization.
+                    let job_ack = client.job_ack.load(Ordering::SeqCst);
+                    let mut keepalive = interval(Duration::from_millis(keepalive_interval.max(1)));
+                    let mut canaries: VecDeque<Instant> = VecDeque::new();
+                    // Worker naming the next share, held until the share is sent.
+                    let mut share_worker: Option<ProverMessage> = None;
+                    let reason = loop {
+                        tokio::select! {
+                            Ok(()) = online.changed() => {
+                                if !*online.borrow() {
+                                    info!("Disconnecting from server");
+                                    break "disconnect requested".to_string();
+                                }
+                            }
+                            _ = keepalive.tick(), if keepalive_interval > 0 => {
+                                if canaries.len() >= MAX_UNANSWERED_CANARIES {
+                                    continue;
+                                }
+                                canaries.push_back(Instant::now());
+                                if let Err(e) = framed.send(ProverMessage::Canary).await {
+                                    error!("Error sending Canary: {:?}", e);
+                                }
+                            }
+                            _ = client.reconnect.notified() => {
+                                info!("Switching to {}", client.server());
+                                break format!("switching to {}", client.server());
+                            }
+                            Ok(()) = proof_rate.changed() => {
+                                // Shares go first on a throttled connection, the next report replaces this one.
+                                if client.bandwidth.write_exhausted() {
+                                    debug!("Skipping a proof rate report, the write limit is reached");
+                                    continue;
+                                }
+                                let mut rate = *proof_rate.borrow();
+                                if client.dry_run() {
+                                    let zero_rate = client.dry_run_zero_rate.load(Ordering::SeqCst);
+                                    rate = rate.filter(|_| zero_rate).map(|_| 0);
+                                }
+                                if let Some(rate) = rate {
+                                    trace!("Sending ProofRate to server");
+                                    if let Err(e) = framed.send(ProverMessage::ProofRate(rate)).await {
+                                        error!("Error sending ProofRate: {:?}", e);
                                     }
                                 }
-                                result = framed.next() => match result {
-                                    Some(Ok(message)) => {
-                                        debug!("Received {} from server", message.name());
-                                        match message {
-                                            ProverMessage::AuthorizeResult(result, message) => {
-                                                if result {
-                                                    debug!("Authorized");
-                                                } else if let Some(message) = message {
-                                                    error!("Authorization failed: {}", message);
-                                                    sleep(Duration::from_secs(5)).await;
-                                                    break;
+                                let working = *client.working.lock().unwrap_or_else(PoisonError::into_inner);
+                                if let Some((height, pool_target)) = working.filter(|_| job_ack) {
+                                    trace!("Sending JobAck for block {} to server", height);
+                                    if let Err(e) = framed.send(ProverMessage::JobAck(height, pool_target)).await {
+                                        error!("Error sending JobAck: {:?}", e);
+                                    }
+                                }
+                            }
+                            Some(outgoing) = receiver.recv() => {
+                                let max_ages = *client.max_ages.read().unwrap_or_else(PoisonError::into_inner);
+                                if max_ages.expired(&outgoing, client.job_epoch.load(Ordering::SeqCst)) {
+                                    if let ProverMessage::Submit(_, nonce, _) = &outgoing.message {
+                                        client.forget_submit(nonce);
+                                        share_worker = None;
+                                    }
+                                    debug!(
+                                        "Dropping {} queued {}s ago",
+                                        outgoing.message.name(),
+                                        outgoing.age().as_secs()
+                                    );
+                                    continue;
+                                }
+                                client.queue_wait.record(outgoing.age());
+                                let message = outgoing.message;
+                                if let ProverMessage::Worker(..) = message {
+                                    share_worker = Some(message);
+                                    continue;
+                                }
+                                if let ProverMessage::Submit(height, nonce, proof) = &message {
+                                    let worker = share_worker.take();
+                                    let latest_height = client.latest_height();
+                                    let retry = client.take_retry(nonce);
+                                    if *height < latest_height {
+                                        if retry {
+                                            client.proxy_share_lost(*height);
+                                        }
+                                        client.local_stale.fetch_add(1, Ordering::SeqCst);
+                                        client.forget_submit(nonce);
+                                        warn!("Dropping stale share for block {} (latest {})", height, latest_height);
+                                        continue;
+                                    }
+                                    if client.dry_run() {
+                                        client.would_submit.fetch_add(1, Ordering::SeqCst);
+                                        info!(
+                                            "Dry run, not submitting the share for block {} with nonce {} at difficulty {}",
+                                            height,
+                                            nonce,
+                                            current_target.map(estimate::difficulty).unwrap_or_default()
+                                        );
+                                        continue;
+                                    }
+                                    if pending.len() >= MAX_PENDING_SUBMITS {
+                                        // A pool that never answers would otherwise grow this for the whole connection.
+                                        if let Some(oldest) = pending.pop_front() {
+                                            given_up += 1;
+                                            client.forget_submit(&oldest.nonce);
+                                            warn!(
+                                                "No result for the share for block {} after {}s, no longer waiting",
+                                                oldest.height,
+                                                oldest.sent.elapsed().as_secs()
+                                            );
+                                        }
+                                    }
+                                    pending.push_back(PendingSubmit {
+                                        height: *height,
+                                        nonce: *nonce,
+                                        proof: proof.clone(),
+                                        sent: Instant::now(),
+                                        retry,
+                                    });
+                                    client.pending_submits.store(pending.len(), Ordering::SeqCst);
+                                    client.events.publish(MinerEvent::ShareSubmitted {
+                                        worker: client.worker(),
+                                        height: *height,
+                                    });
+                                    if let Some(worker) = worker {
+                                        if let Err(e) = framed.send(worker).await {
+                                            error!("Error sending Worker: {:?}", e);
+                                        }
+                                    }
+                                }
+                                let name = message.name();
+                                trace!("Sending {} to server", name);
+                                if let Err(e) = framed.send(message).await {
+                                    error!("Error sending {}: {:?}", name, e);
+                                }
+                            }
+                            result = framed.next() => match result {
+                                Some(Ok(message)) => {
+                                    trace!("Received {} from server", message.name());
+                                    if client.closing.load(Ordering::SeqCst) {
+                                        continue;
+                                    }
+                                    match message {
+                                        // Only the first result counts, proxies sometimes repeat it.
+                                        ProverMessage::AuthorizeResult(result, message)
+                                            if client.authorized.load(Ordering::SeqCst) =>
+                                        {
+                                            if result || message.as_deref() != Some(AUTHORIZATION_REVOKED) {
+                                                debug!("Ignoring a repeated authorization result ({})", result);
+                                                continue;
+                                            }
+                                            warn!("The pool revoked the authorization, authorizing again");
+                                            client.authorized.store(false, Ordering::SeqCst);
+                                            *client.connection.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                                            let authorization = client.authorization(&server);
+                                            offered = offered_capabilities(&authorization);
+                                            if let Err(e) = framed.send(authorization).await {
+                                                error!("Error sending authorization: {}", e);
+                                            }
+                                        }
+                                        ProverMessage::AuthorizeResult(result, message) => {
+                                            if result {
+                                                auth_rejections = 0;
+                                                client.authorized.store(true, Ordering::SeqCst);
+                                                debug!("Authorized");
+                                                let msgpack = framed.codec().msgpack();
+                                                let session = client.keep_session_token(&server, message.as_deref());
+                                                // MessagePack and sessions are the only ones the pool answers,
+                                                // the others it took by accepting the authorization.
+                                                let refused = (!msgpack as u16 * MSGPACK_CAPABILITY)
+                                                    | (!session as u16 * SESSION_CAPABILITY);
+                                                let connector = client.connector();
+                                                let info = ConnectionInfo {
+                                                    protocol_version: *ProverMessage::version(),
+                                                    wire_format: if msgpack { "msgpack" } else { "json" },
+                                                    capabilities: capability_names(offered & !refused),
+                                                    keepalive_secs: Some(keepalive_interval / 1000)
+                                                        .filter(|_| keepalive_interval > 0),
+                                                    requested_difficulty: client.requested_difficulty(&server),
+                                                    difficulty: None,
+                                                    tls: connector.tls(),
+                                                    compression: connector.compression(),
+                                                    server_version: None,
+                                                };
+                                                info!("Connection to {}: {}", server, info);
+                                                *client.connection.lock().unwrap_or_else(PoisonError::into_inner) =
+                                                    Some(info);
+                                                client.events.publish(MinerEvent::Authorized {
+                                                    worker: client.worker(),
+                                                });
+                                                if let Some((template, target)) = fresh_job(early_notify.take()) {
+                                                    current_height = template.block_height();
+                                                    current_target = Some(target);
+                                                    new_work(&client, &prover_sender, template, target).await;
+                                                }
+                                            } else {
+                                                if let Some((template, ..)) = early_notify.take() {
+                                                    debug!(
+                                                        "Discarding the job for block {}, authorization failed",
+                                                        template.block_height()
+                                                    );
+                                                }
+                                                client.forget_session_token(&server);
+                                                let reason = match message.as_ref() {
+                                                    Some(message) => {
+                                                        error!("Authorization failed: {}", message);
+                                                        format!("authorization failed: {}", message)
+                                                    }
+                                                    None => {
+                                                        error!("Authorization failed");
+                                                        "authorization failed".to_string()
+                                                    }
+                                                };
+                                                auth_rejections += 1;
+                                                if auth_rejections >= MAX_AUTH_REJECTIONS {
+                                                    client.fail(ClientError::AuthRejected {
+                                                        message,
+                                                        attempts: auth_rejections,
+                                                    });
                                                 } else {
-                                                    error!("Authorization failed");
                                                     sleep(Duration::from_secs(5)).await;
-                                                    break;
                                                 }
+                                                break reason;
+                                            }
+                                        }
+                                        ProverMessage::Notify(block_template, pool_target, _, true) => {
+                                            let height = block_template.block_height();
+                                            if height <= current_height {
+                                                debug!("Ignoring a speculative job for the past block {}", height);
+                                                continue;
+                                            }
+                                            if let Err(e) = client.check_template(&block_template) {
+                                                warn!("Dropping the speculative job for block {}: {}", height, e);
+                                                continue;
+                                            }
+                                            debug!("Holding the speculative job for block {}", height);
+                                            if speculative.replace((block_template, pool_target)).is_some() {
+                                                client.speculative_expired.fetch_add(1, Ordering::SeqCst);
+                                            }
+                                        }
+                                        ProverMessage::ActivateJob(height) => {
+                                            let (block_template, pool_target) = match speculative.take() {
+                                                Some(job) if job.0.block_height() == height => job,
+                                                held => {
+                                                    speculative = held;
+                                                    debug!("Ignoring the activation of unknown job {}", height);
+                                                    continue;
+                                                }
+                                            };
+                                            client.speculative_activated.fetch_add(1, Ordering::SeqCst);
+                                            debug!("Activated the speculative job for block {}", height);
+                                            highest_height = highest_height.max(height);
+                                            if !client.authorized.load(Ordering::SeqCst) {
+                                                early_notify = Some((block_template, pool_target, Instant::now()));
+                                                continue;
                                             }
-                                            ProverMessage::Notify(block_template, pool_target) => {
-                                                if let Err(e) = prover_sender.send(ProverEvent::NewWork(pool_target, block_template)).await {
-                                                    error!("Error sending work to prover: {}", e);
+                                            current_height = height;
+                                            current_target = Some(pool_target);
+                                            new_work(&client, &prover_sender, block_template, pool_target).await;
+                                        }
+                                        ProverMessage::Notify(block_template, pool_target, reorg, false) => {
+                                            let height = block_template.block_height();
+                                            // A regular job for the held template activates it, any other for
+                                            // its height or above supersedes it.
+                                            if let Some((held, held_target)) = speculative.take() {
+                                                if held == block_template {
+                                                    client.speculative_activated.fetch_add(1, Ordering::SeqCst);
+                                                    debug!("Activated the speculative job for block {}", height);
+                                                } else if held.block_height() <= height {
+                                                    client.speculative_expired.fetch_add(1, Ordering::SeqCst);
+                                                    let held_height = held.block_height();
+                                                    debug!("Dropping the speculative job for block {}", held_height);
                                                 } else {
-                                                    debug!("Sent work to prover");
+                                                    speculative = Some((held, held_target));
                                                 }
                                             }
-                                            ProverMessage::SubmitResult(code, message) => {
-                                                match code {
-                                                    Code::ProxyException => {
-                                                        warn!("Proxy has an exception, skip statistics");
-                                                    }
-                                                    _ => {
-                                                        if let Err(e) = prover_sender.send(ProverEvent::Result(Code::Success == code, message)).await {
-                                                            error!("Error sending share result to prover: {}", e);
-                                                        } else {
-                                                            debug!("Sent share result to prover");
-                                                        }
+                                            let tolerance = client.reorg_tolerance.load(Ordering::SeqCst);
+                                            if !reorg && height.saturating_add(tolerance) < highest_height {
+                                                client.dropped_jobs.fetch_add(1, Ordering::SeqCst);
+                                                warn!(
+                                                    "Dropping the job for block {}, the pool already sent block {}",
+                                                    height, highest_height
+                                                );
+                                                continue;
+                                            }
+                                            if let Err(e) = client.check_template(&block_template) {
+                                                warn!("Dropping the job for block {}: {}", height, e);
+                                                continue;
+                                            }
+                                            highest_height = highest_height.max(height);
+                                            if awaiting_job {
+                                                awaiting_job = false;
+                                                client.track_quality(|quality| {
+                                                    quality.record_first_notify(connecting.elapsed())
+                                                });
+                                            }
+                                            if !client.authorized.load(Ordering::SeqCst) {
+                                                debug!(
+                                                    "Holding the job for block {} until the pool authorizes",
+                                                    block_template.block_height()
+                                                );
+                                                early_notify = Some((block_template, pool_target, Instant::now()));
+                                                continue;
+                                            }
+                                            current_height = block_template.block_height();
+                                            current_target = Some(pool_target);
+                                            new_work(&client, &prover_sender, block_template, pool_target).await;
+                                        }
+                                        ProverMessage::SubmitResult(code, message) => {
+                                            if given_up > 0 {
+                                                given_up -= 1;
+                                                client.unmatched_results.fetch_add(1, Ordering::SeqCst);
+                                                debug!("Ignoring a late {:?} result for a share given up on", code);
+                                                continue;
+                                            }
+                                            if pending.is_empty() {
+                                                client.unmatched_results.fetch_add(1, Ordering::SeqCst);
+                                                debug!("Ignoring a {:?} result, no share is waiting for one", code);
+                                                continue;
+                                            }
+                                            let submitted = pending.pop_front();
+                                            client.pending_submits.store(pending.len(), Ordering::SeqCst);
+                                            if let Some(submitted) = submitted.as_ref() {
+                                                let sent = submitted.sent;
+                                                client.submit_latency.record(sent.elapsed());
+                                                client.track_quality(|quality| {
+                                                    quality.record_result(sent.elapsed(), code == Code::Stale)
+                                                });
+                                            }
+                                            if code == Code::ProxyException {
+                                                client.proxy_exceptions.fetch_add(1, Ordering::SeqCst);
+                                                if let Some(submitted) = submitted.as_ref().filter(|submitted| {
+                                                    !submitted.retry && submitted.height >= client.latest_height()
+                                                }) {
+                                                    client.retry_submit(
+                                                        submitted.height,
+                                                        submitted.nonce,
+                                                        submitted.proof.clone(),
+                                                    );
+                                                    continue;
+                                                }
+                                            }
+                                            if let Some(submitted) = submitted.as_ref() {
+                                                client.forget_submit(&submitted.nonce);
+                                            }
+                                            let submitted = submitted
+                                                .map(|submitted| (submitted.height, submitted.nonce, submitted.sent));
+                                            client
+                                                .rejections
+                                                .lock()
+                                                .unwrap_or_else(PoisonError::into_inner)
+                                                .record(&code);
+                                            let result = ShareResult {
+                                                pool: server.clone(),
+                                                worker: client.worker(),
+                                                height: submitted.map(|(height, _, _)| height).unwrap_or(current_height),
+                                                nonce: submitted.and_then(|(_, nonce, _)| nonce.to_bytes_le().ok()).map(|bytes| {
+                                                    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+                                                }),
+                                                code: code.clone(),
+                                                message: message.clone(),
+                                                latency: submitted.map(|(_, _, sent)| sent.elapsed()),
+                                                difficulty: current_target.map(estimate::difficulty),
+                                            };
+                                            client.events.publish(if code == Code::Success {
+                                                MinerEvent::ShareAccepted(result)
+                                            } else {
+                                                MinerEvent::ShareRejected(result)
+                                            });
+                                            match code {
+                                                Code::ProxyException => {
+                                                    warn!("Share lost to a proxy exception, skip statistics");
+                                                }
+                                                _ => {
+                                                    let event = ProverEvent::Result {
+                                                        code,
+                                                        message,
+                                                        height: submitted.map(|(height, _, _)| height).unwrap_or(current_height),
+                                                        nonce: submitted.map(|(_, nonce, _)| nonce),
+                                                        device: None,
+                                                    };
+                                                    if let Err(e) = prover_sender.send(event).await {
+                                                        error!("Error sending share result to prover: {}", e);
+                                                    } else {
+                                                        trace!("Sent share result to prover");
                                                     }
                                                 }
                                             }
-                                            _ => {
-                                                debug!("Unhandled message: {}", message.name());
+                                        }
+                                        ProverMessage::PoolInfo(fields) => {
+                                            debug!("Pool info: {:?}", fields);
+                                            client.reported_server_version(fields.get("server_version"));
+                                            *client.pool_info.lock().unwrap_or_else(PoisonError::into_inner) =
+                                                Some((server.clone(), fields.clone()));
+                                            client.events.publish(MinerEvent::PoolInfo {
+                                                server: server.clone(),
+                                                fields,
+                                            });
+                                        }
+                                        ProverMessage::Canary => {
+                                            if let Some(sent) = canaries.pop_front() {
+                                                client.record_rtt(sent.elapsed());
                                             }
                                         }
+                                        _ => {
+                                            debug!("Unhandled message: {}", message.name());
+                                        }
                                     }
-                                    Some(Err(e)) => {
-                                        warn!("Failed to read the message: {:?}", e);
-                                    }
-                                    None => {
-                                        error!("Disconnected from server");
-                                        sleep(Duration::from_secs(5)).await;
-                                        break;
-                                    }
+                                }
+                                Some(Err(e)) => {
+                                    warn!("{}", ClientError::ProtocolMismatch(e));
+                                }
+                                None => {
+                                    error!("Disconnected from server");
+                                    client.track_quality(QualityTracker::record_disconnect);
+                                    sleep(Duration::from_secs(5)).await;
+                                    break "connection closed by the server".to_string();
                                 }
                             }
                         }
+                    };
+                    if speculative.is_some() {
+                        client.speculative_expired.fetch_add(1, Ordering::SeqCst);
                     }
-                    Err(e) => {
-                        error!("Failed to connect to operator: {}", e);
-                        sleep(Duration::from_secs(5)).await;
+                    client.connected.store(false, Ordering::SeqCst);
+                    client.events.publish(MinerEvent::Disconnected {
+                        server,
+                        worker: client.worker(),
+                        reason,
+                    });
+                    {
+                        let mut connected_time = client.connected_time.lock().unwrap_or_else(PoisonError::into_inner);
+                        if let Some(since) = connected_time.1.take() {
+                            connected_time.0 += since.elapsed();
+                        }
+                    }
+                    *client.connection.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                    *client.work.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                    if client.authorized.swap(false, Ordering::SeqCst) {
+                        unreachable_since = Instant::now();
+                    }
+                    client.pending_submits.store(0, Ordering::SeqCst);
+                    if auth_rejections >= MAX_AUTH_REJECTIONS {
+                        return;
                     }
-                },
-                Err(_) => {
-                    error!("Failed to connect to operator: Timed out");
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    client.track_quality(QualityTracker::record_disconnect);
                     sleep(Duration::from_secs(5)).await;
                 }
             }
+            let limit = Duration::from_millis(client.unreachable_limit.load(Ordering::SeqCst));
+            if !limit.is_zero() && unreachable_since.elapsed() >= limit {
+                client.fail(ClientError::Unreachable {
+                    server: client.server(),
+                    after: limit,
+                });
+                return;
+            }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn drops_an_early_job_held_past_the_timeout() {
+        let held = |age| Some((testing::template(2), 1000, Instant::now().checked_sub(age).unwrap()));
+        let job = fresh_job(held(EARLY_NOTIFY_TIMEOUT - Duration::from_secs(1)));
+        assert_eq!(job.map(|(template, target)| (template.block_height(), target)), Some((2, 1000)));
+        assert!(fresh_job(held(EARLY_NOTIFY_TIMEOUT + Duration::from_secs(1))).is_none());
+        assert!(fresh_job(None).is_none());
+    }
+}
@@ -0,0 +1,1538 @@
+use std::{
+    env,
+    fmt::Display,
+    fs,
+    net::SocketAddr,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use structopt::{clap::ArgMatches, StructOpt};
+use tracing_subscriber::filter::LevelFilter;
+
+use crate::{
+    cli::Opt,
+    client::PoolCredentials,
+    credentials,
+    logging::{LogFormat, LogRotation},
+    message::{Secret, FRAME_SIZE_RANGE, STRING_LENGTH_RANGE},
+    report::RateDelta,
+    schedule::{self, Schedule},
+    threshold::RateThreshold,
+    units::RateUnit,
+};
+
+// Prefix of the environment variables overriding the configuration.
+const ENV_PREFIX: &str = "ALEOXMINER_";
+// Separates the section from the field in variable names, e.g. ALEOXMINER_PROVER__THREADS.
+const ENV_SEPARATOR: &str = "__";
+const REDACTED: &str = "<redacted>";
+const MAX_KEEPALIVE: u64 = 3600;
+const SUBMIT_QUEUE_RANGE: RangeInclusive<usize> = 1..=65536;
+/// Layout version of the configuration file written by this build, files without one are version 1.
+pub const SCHEMA_VERSION: u32 = 2;
+
+type Migration = fn(&mut toml::value::Table) -> Result<()>;
+
+/// Steps upgrading older files, by the version they produce, applied in order.
+const MIGRATIONS: [(u32, &str, Migration); 1] = [(2, "moved the single server into the pools list", server_to_pools)];
+
+/// Version 1 files could name one pool as `server` or `pool`.
+fn server_to_pools(table: &mut toml::value::Table) -> Result<()> {
+    for key in ["server", "pool"] {
+        let server = match table.remove(key) {
+            Some(toml::Value::String(server)) => server,
+            Some(_) => bail!("{} must be a string", key),
+            None => continue,
+        };
+        ensure!(!table.contains_key("pools"), "both {} and pools are set", key);
+        let mut pool = toml::value::Table::new();
+        pool.insert("url".to_string(), toml::Value::String(server));
+        pool.insert("priority".to_string(), toml::Value::Integer(0));
+        table.insert("pools".to_string(), toml::Value::Array(vec![toml::Value::Table(pool)]));
+    }
+    Ok(())
+}
+
+/// Removes and checks `schema_version`.
+fn take_schema_version(table: &mut toml::value::Table) -> Result<u32> {
+    let version = match table.remove("schema_version") {
+        None => 1,
+        Some(toml::Value::Integer(version)) if version >= 1 => u32::try_from(version).unwrap_or(u32::MAX),
+        Some(_) => bail!("schema_version must be a positive integer"),
+    };
+    ensure!(
+        version <= SCHEMA_VERSION,
+        "config is newer than this binary: schema version {}, this build reads up to {}, please upgrade",
+        version,
+        SCHEMA_VERSION
+    );
+    Ok(version)
+}
+
+/// Applies the migrations past `version` and returns their descriptions.
+fn migrate(table: &mut toml::value::Table, version: u32) -> Result<Vec<String>> {
+    let mut applied = Vec::new();
+    for (to, description, migration) in MIGRATIONS.iter() {
+        if *to > version {
+            migration(table).with_context(|| format!("unable to migrate to schema version {}", to))?;
+            applied.push(format!("schema version {}: {}", to, description));
+        }
+    }
+    Ok(applied)
+}
+
+/// How an environment variable is parsed.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Integer,
+    Float,
+    Bool,
+    /// Comma separated integers
+    Integers,
+    /// Comma separated pool URLs in priority order
+    Pools,
+}
+
+/// Every configuration field by path with its description, settable as `ALEOXMINER_<PATH>` with
+/// `.` written as `__`.
+const FIELDS: [(&str, Kind, &str); 66] = [
+    ("address", Kind::Text, "Prover address (aleo1...)"),
+    ("account", Kind::Text, "Pool account, instead of an address"),
+    ("worker", Kind::Text, "Worker name, letters, digits, _ and -, at most 15 characters"),
+    ("password", Kind::Text, "Pool password, prefer password_file so this file holds no secret"),
+    ("password_file", Kind::Text, "Read the pool password from this file"),
+    ("password_env", Kind::Text, "Read the pool password from this environment variable"),
+    ("password_keyring", Kind::Text, "Read the pool password from this service/user entry of the OS keyring"),
+    ("schedule", Kind::Text, "Only mine during these local time ranges, e.g. \"22:00-06:00,12:00-14:00\""),
+    ("rate_unit", Kind::Text, "Unit proof rates are shown in: p/s, kp/s or auto"),
+    (
+        "pools",
+        Kind::Pools,
+        "Pools as host:port or tcp://host:port by priority, each may set account, worker and password",
+    ),
+    ("prover.threads", Kind::Integer, "Number of proving threads, defaults to the number of CPUs"),
+    ("prover.gpus", Kind::Integers, "Indexes of the GPUs to use (cuda feature)"),
+    ("prover.cuda_jobs", Kind::Integer, "Parallel jobs per GPU (cuda feature)"),
+    ("prover.max_concurrent_proofs", Kind::Integer, "Maximum number of proofs computed at the same time"),
+    ("prover.nice", Kind::Bool, "Run the proving threads at the lowest priority"),
+    ("logging.level", Kind::Text, "Console log level: error, warn, info, debug or trace"),
+    ("logging.file_level", Kind::Text, "Log file level, defaults to the console level"),
+    ("logging.file", Kind::Text, "Write the log to this file"),
+    ("logging.rotation", Kind::Text, "When to start a new log file: never, hourly, daily or at a size like 100M"),
+    ("logging.keep", Kind::Integer, "Number of rotated log files to keep"),
+    ("logging.format", Kind::Text, "Log line format: text or json"),
+    ("report.min_interval", Kind::Integer, "Minimum seconds between proof rate reports to the pool"),
+    ("report.max_interval", Kind::Integer, "Maximum seconds between proof rate reports to the pool"),
+    ("report.delta", Kind::Text, "Change of the proof rate that triggers a report, absolute (0.5) or relative (5%)"),
+    ("alerts.no_share", Kind::Integer, "Minutes without an accepted share before alerting"),
+    ("alerts.min_hashrate", Kind::Text, "Alert below this 15 minute proof rate, in p/s or percent of the average"),
+    ("alerts.max_reject_percent", Kind::Float, "Alert above this percentage of rejected shares in the last hour"),
+    ("alerts.max_disconnected", Kind::Text, "Alert when disconnected longer than this in the last hour, e.g. 5m"),
+    ("telemetry.status_bind", Kind::Text, "Serve the JSON status on this address, e.g. 127.0.0.1:8080"),
+    ("telemetry.metrics_bind", Kind::Text, "Serve Prometheus metrics on this address, e.g. 0.0.0.0:9090"),
+    ("telemetry.control_token", Kind::Text, "Bearer token enabling the control API on the status address"),
+    ("telemetry.influx_url", Kind::Text, "Push metrics to this InfluxDB server, e.g. http://localhost:8086"),
+    ("telemetry.influx_bucket", Kind::Text, "InfluxDB bucket, or database for InfluxDB 1.x"),
+    ("telemetry.influx_org", Kind::Text, "InfluxDB organization (2.x only)"),
+    ("telemetry.influx_token", Kind::Text, "InfluxDB API token, uses the 2.x write API when set"),
+    ("telemetry.influx_interval", Kind::Integer, "Seconds between InfluxDB pushes"),
+    ("telemetry.mqtt_broker", Kind::Text, "Publish status and events to this MQTT broker (host:port)"),
+    ("telemetry.mqtt_username", Kind::Text, "MQTT username"),
+    ("telemetry.mqtt_password", Kind::Text, "MQTT password"),
+    ("telemetry.mqtt_interval", Kind::Integer, "Seconds between MQTT status messages"),
+    ("telemetry.statsd", Kind::Text, "Send metrics to this statsd server over UDP, e.g. localhost:8125"),
+    ("telemetry.statsd_prefix", Kind::Text, "Prefix of the statsd metric names"),
+    ("telemetry.webhook_url", Kind::Text, "Post notifications to this webhook URL (Discord and Slack compatible)"),
+    ("timeouts.keepalive", Kind::Integer, "Seconds between keepalive messages, 0 to disable"),
+    ("timeouts.rtt_warning", Kind::Integer, "Warn when the pool round trip time exceeds this many milliseconds"),
+    ("timeouts.webhook_disconnect_after", Kind::Integer, "Seconds the pool connection has to be down before notifying"),
+    ("timeouts.pool_unreachable", Kind::Text, "Exit with code 10 once no pool connection was authorized for this long"),
+    ("protocol.max_frame_size", Kind::Integer, "Largest message accepted from the pool in bytes, 1 KiB to 1 GiB"),
+    ("protocol.max_string_length", Kind::Integer, "Longest string field accepted from the pool in bytes, 16 to 1 MiB"),
+    ("protocol.submit_queue", Kind::Integer, "Messages queued for the pool before proving waits for the connection"),
+    ("protocol.max_submit_age", Kind::Integer, "Seconds before queued shares for an earlier job are dropped, 0 never"),
+    ("protocol.max_rate_age", Kind::Integer, "Seconds before queued proof rate reports are dropped, 0 never"),
+    ("protocol.read_limit", Kind::Integer, "KB per second read from the pool at most, 0 no limit"),
+    ("protocol.write_limit", Kind::Integer, "KB per second written to the pool at most, 0 no limit"),
+    ("protocol.msgpack", Kind::Bool, "Offer MessagePack frames to the pool, used if it supports them"),
+    ("protocol.job_ack", Kind::Bool, "Tell the pool which job is being proven, only for pools supporting it"),
+    ("protocol.speculative", Kind::Bool, "Take speculative jobs ahead of time, only for pools supporting them"),
+    ("protocol.pool_info", Kind::Bool, "Ask the pool for balance and payout info, only for pools supporting it"),
+    ("protocol.reorg_tolerance", Kind::Integer, "Blocks a job may go back without being flagged as a reorg"),
+    ("protocol.no_template_validation", Kind::Bool, "Pass every job to the prover without checking its template"),
+    ("protocol.template_max_skew", Kind::Integer, "Seconds a job's timestamp may be off the local clock"),
+    ("protocol.difficulty", Kind::Integer, "Share difficulty asked of the pool with a d= password field"),
+    ("protocol.failover_margin", Kind::Float, "Quality points another pool must score above the active one, 0 never"),
+    ("protocol.failover_after", Kind::Integer, "Seconds the active pool must stay below the margin before switching"),
+    ("protocol.probe_interval", Kind::Integer, "Seconds between quality probes of the other pools"),
+    ("protocol.split", Kind::Integers, "Weights splitting the proving time between the pools by priority"),
+];
+
+fn env_name(path: &str) -> String {
+    format!("{}{}", ENV_PREFIX, path.replace('.', ENV_SEPARATOR).to_ascii_uppercase())
+}
+
+fn parse_env(kind: Kind, value: &str) -> Result<Value, String> {
+    let items = || value.split(',').map(str::trim).filter(|item| !item.is_empty());
+    match kind {
+        Kind::Text => Ok(Value::from(value)),
+        Kind::Integer => value
+            .trim()
+            .parse::<u64>()
+            .map(Value::from)
+            .map_err(|_| "expected a non-negative integer".to_string()),
+        Kind::Float => match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Value::from(number)),
+            _ => Err("expected a number".to_string()),
+        },
+        Kind::Bool => match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(Value::Bool(true)),
+            "false" | "0" | "no" | "off" => Ok(Value::Bool(false)),
+            _ => Err("expected true or false".to_string()),
+        },
+        Kind::Integers => items()
+            .map(|item| item.parse::<i64>().map(Value::from))
+            .collect::<Result<Vec<Value>, _>>()
+            .map(Value::Array)
+            .map_err(|_| "expected comma separated integers".to_string()),
+        Kind::Pools => Ok(Value::Array(
+            items()
+                .enumerate()
+                .map(|(priority, url)| serde_json::json!({ "url": url, "priority": priority }))
+                .collect(),
+        )),
+    }
+}
+
+// Commented out value shown in the example for fields without a default.
+fn placeholder(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Text => "\"\"",
+        Kind::Integer => "0",
+        Kind::Float => "0.0",
+        Kind::Bool => "false",
+        Kind::Integers => "[0]",
+        Kind::Pools => "[{ url = \"host:port\", priority = 0 }]",
+    }
+}
+
+fn toml_literal(value: &Value) -> String {
+    match value {
+        Value::String(text) => toml::Value::String(text.clone()).to_string(),
+        Value::Array(items) => format!("[{}]", items.iter().map(toml_literal).collect::<Vec<_>>().join(", ")),
+        Value::Object(fields) => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, toml_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        other => other.to_string(),
+    }
+}
+
+fn field_position(path: &str) -> usize {
+    FIELDS.iter().position(|(field, ..)| *field == path).unwrap_or(FIELDS.len())
+}
+
+/// Pool entry, pools are tried in ascending priority.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    /// `host:port` or `tcp://host:port`
+    pub url: String,
+    #[serde(default)]
+    pub priority: u32,
+    /// Override the top-level values on this pool
+    pub account: Option<String>,
+    pub worker: Option<String>,
+    pub password: Option<Secret>,
+}
+
+/// `[prover]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProverSection {
+    pub threads: Option<u16>,
+    pub gpus: Option<Vec<i16>>,
+    pub cuda_jobs: Option<u8>,
+    pub max_concurrent_proofs: Option<usize>,
+    pub nice: Option<bool>,
+}
+
+/// `[logging]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSection {
+    pub level: Option<String>,
+    pub file_level: Option<String>,
+    pub file: Option<PathBuf>,
+    pub rotation: Option<String>,
+    pub keep: Option<usize>,
+    pub format: Option<String>,
+}
+
+/// When to report the proof rate to the pool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportSection {
+    pub min_interval: Option<u64>,
+    pub max_interval: Option<u64>,
+    pub delta: Option<String>,
+}
+
+/// `[alerts]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsSection {
+    /// Minutes without an accepted share
+    pub no_share: Option<u64>,
+    pub min_hashrate: Option<String>,
+    pub max_reject_percent: Option<f64>,
+    pub max_disconnected: Option<String>,
+}
+
+/// `[telemetry]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetrySection {
+    pub status_bind: Option<String>,
+    pub metrics_bind: Option<String>,
+    pub control_token: Option<String>,
+    pub influx_url: Option<String>,
+    pub influx_bucket: Option<String>,
+    pub influx_org: Option<String>,
+    pub influx_token: Option<String>,
+    pub influx_interval: Option<u64>,
+    pub mqtt_broker: Option<String>,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<String>,
+    pub mqtt_interval: Option<u64>,
+    pub statsd: Option<String>,
+    pub statsd_prefix: Option<String>,
+    pub webhook_url: Option<String>,
+}
+
+/// `[timeouts]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsSection {
+    /// Seconds between keepalive messages, 0 to disable
+    pub keepalive: Option<u64>,
+    /// Milliseconds
+    pub rtt_warning: Option<u64>,
+    /// Seconds
+    pub webhook_disconnect_after: Option<u64>,
+    /// Duration, e.g. 30m
+    pub pool_unreachable: Option<String>,
+}
+
+/// `[protocol]`, limits on what the pool may send
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtocolSection {
+    /// Bytes
+    pub max_frame_size: Option<u64>,
+    /// Bytes
+    pub max_string_length: Option<u64>,
+    pub submit_queue: Option<u64>,
+    /// Seconds
+    pub max_submit_age: Option<u64>,
+    /// Seconds
+    pub max_rate_age: Option<u64>,
+    /// KB per second
+    pub read_limit: Option<u64>,
+    /// KB per second
+    pub write_limit: Option<u64>,
+    pub msgpack: Option<bool>,
+    pub job_ack: Option<bool>,
+    pub speculative: Option<bool>,
+    pub pool_info: Option<bool>,
+    /// Blocks
+    pub reorg_tolerance: Option<u32>,
+    pub no_template_validation: Option<bool>,
+    /// Seconds
+    pub template_max_skew: Option<u64>,
+    pub difficulty: Option<u64>,
+    /// Quality points
+    pub failover_margin: Option<f64>,
+    /// Seconds
+    pub failover_after: Option<u64>,
+    /// Seconds
+    pub probe_interval: Option<u64>,
+    /// Weights of the pools by priority
+    pub split: Option<Vec<u32>>,
+}
+
+/// Settings from the configuration file, the command line and the environment. Everything is
+/// optional, unset values keep the command line defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub address: Option<String>,
+    pub account: Option<String>,
+    pub worker: Option<String>,
+    /// Prefer `password_file`, so the configuration file holds no secret
+    pub password: Option<Secret>,
+    pub password_file: Option<PathBuf>,
+    pub password_env: Option<String>,
+    /// `service/user` entry of the OS keyring
+    pub password_keyring: Option<String>,
+    pub schedule: Option<String>,
+    pub rate_unit: Option<String>,
+    pub pools: Option<Vec<PoolConfig>>,
+    pub prover: ProverSection,
+    pub logging: LoggingSection,
+    pub report: ReportSection,
+    pub alerts: AlertsSection,
+    pub telemetry: TelemetrySection,
+    pub timeouts: TimeoutsSection,
+    pub protocol: ProtocolSection,
+    /// Migrations applied when reading the file
+    #[serde(skip)]
+    migrations: Vec<String>,
+}
+
+/// Collects validation errors prefixed with the field path.
+struct Errors(Vec<String>);
+
+impl Errors {
+    fn check<T, E: Display>(&mut self, path: &str, value: Option<&String>, parse: impl Fn(&str) -> Result<T, E>) {
+        if let Some(value) = value {
+            if let Err(e) = parse(value) {
+                self.0.push(format!("{}: {}", path, e));
+            }
+        }
+    }
+
+    fn push(&mut self, path: &str, message: impl Display) {
+        self.0.push(format!("{}: {}", path, message));
+    }
+
+    // Settings of a cargo feature this build was made without.
+    fn feature<T>(&mut self, path: &str, value: Option<&T>, feature: &str, enabled: bool) {
+        if value.is_some() && !enabled {
+            self.push(path, format!("needs the `{}` feature, build with `--features {}`", feature, feature));
+        }
+    }
+}
+
+fn cli_value(matches: &ArgMatches, explicit: bool, name: &str) -> Option<String> {
+    if (matches.occurrences_of(name) > 0) != explicit {
+        return None;
+    }
+    matches.value_of(name).map(str::to_string)
+}
+
+fn cli_number<T: FromStr>(matches: &ArgMatches, explicit: bool, name: &str) -> Option<T> {
+    cli_value(matches, explicit, name).and_then(|value| value.parse().ok())
+}
+
+/// Strips the optional `tcp://` scheme from a pool URL.
+pub fn pool_address(url: &str) -> Result<&str> {
+    let address = match url.split_once("://") {
+        Some(("tcp", address)) => address,
+        Some(_) => return Err(anyhow!("invalid scheme, expected tcp://")),
+        None => url,
+    };
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(address),
+        _ => Err(anyhow!("expected host:port")),
+    }
+}
+
+impl Config {
+    /// Reads and migrates a configuration file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("unable to parse {}", path.display()))
+    }
+
+    /// Parses a file of any supported schema version, migrating older ones.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let mut table: toml::value::Table = toml::from_str(text)?;
+        let version = take_schema_version(&mut table)?;
+        let migrations = migrate(&mut table, version)?;
+        let mut config: Self = toml::Value::Table(table).try_into()?;
+        config.migrations = migrations;
+        Ok(config)
+    }
+
+    /// Values from the command line: given explicitly, or only the defaults of the options not given.
+    pub fn from_matches(matches: &ArgMatches, explicit: bool) -> Self {
+        let value = |name: &str| cli_value(matches, explicit, name);
+        let flag = |name: &str| Some(true).filter(|_| explicit && matches.is_present(name));
+        Self {
+            address: value("address"),
+            account: value("account"),
+            worker: value("worker"),
+            password: value("password").map(Secret::from),
+            password_file: value("password_file").map(PathBuf::from),
+            password_env: value("password_env"),
+            password_keyring: value("password_keyring"),
+            schedule: value("schedule"),
+            rate_unit: value("rate_unit"),
+            pools: value("pool").map(|url| {
+                vec![PoolConfig {
+                    url,
+                    ..Default::default()
+                }]
+            }),
+            prover: ProverSection {
+                threads: cli_number(matches, explicit, "threads"),
+                gpus: match matches.values_of("cuda") {
+                    Some(gpus) if explicit => Some(gpus.filter_map(|gpu| gpu.parse().ok()).collect()),
+                    _ => None,
+                },
+                cuda_jobs: cli_number(matches, explicit, "jobs"),
+                max_concurrent_proofs: cli_number(matches, explicit, "max_concurrent_proofs"),
+                nice: flag("nice"),
+            },
+            logging: LoggingSection {
+                level: value("console_level"),
+                file_level: value("file_level"),
+                file: value("log").map(PathBuf::from),
+                rotation: value("log_rotation"),
+                keep: cli_number(matches, explicit, "log_keep"),
+                format: value("log_format"),
+            },
+            report: ReportSection {
+                min_interval: cli_number(matches, explicit, "rate_min_interval"),
+                max_interval: cli_number(matches, explicit, "rate_max_interval"),
+                delta: value("rate_delta"),
+            },
+            alerts: AlertsSection {
+                no_share: cli_number(matches, explicit, "share_alert"),
+                min_hashrate: value("alert_min_hashrate"),
+                max_reject_percent: cli_number(matches, explicit, "alert_max_reject"),
+                max_disconnected: value("alert_max_disconnected"),
+            },
+            telemetry: TelemetrySection {
+                status_bind: value("status_bind"),
+                metrics_bind: value("metrics_bind"),
+                control_token: value("control_token"),
+                influx_url: value("influx_url"),
+                influx_bucket: value("influx_bucket"),
+                influx_org: value("influx_org"),
+                influx_token: value("influx_token"),
+                influx_interval: cli_number(matches, explicit, "influx_interval"),
+                mqtt_broker: value("mqtt_broker"),
+                mqtt_username: value("mqtt_username"),
+                mqtt_password: value("mqtt_password"),
+                mqtt_interval: cli_number(matches, explicit, "mqtt_interval"),
+                statsd: value("statsd"),
+                statsd_prefix: value("statsd_prefix"),
+                webhook_url: value("webhook_url"),
+            },
+            timeouts: TimeoutsSection {
+                keepalive: cli_number(matches, explicit, "keepalive"),
+                rtt_warning: cli_number(matches, explicit, "rtt_warning"),
+                webhook_disconnect_after: cli_number(matches, explicit, "webhook_disconnect_after"),
+                pool_unreachable: value("pool_unreachable_exit"),
+            },
+            protocol: ProtocolSection {
+                max_frame_size: cli_number(matches, explicit, "max_frame_size"),
+                max_string_length: cli_number(matches, explicit, "max_string_length"),
+                submit_queue: cli_number(matches, explicit, "submit_queue"),
+                max_submit_age: cli_number(matches, explicit, "max_submit_age"),
+                max_rate_age: cli_number(matches, explicit, "max_rate_age"),
+                read_limit: cli_number(matches, explicit, "read_limit"),
+                write_limit: cli_number(matches, explicit, "write_limit"),
+                msgpack: flag("msgpack"),
+                job_ack: flag("job_ack"),
+                speculative: flag("speculative"),
+                pool_info: flag("pool_info"),
+                reorg_tolerance: cli_number(matches, explicit, "reorg_tolerance"),
+                no_template_validation: flag("no_template_validation"),
+                template_max_skew: cli_number(matches, explicit, "template_max_skew"),
+                difficulty: cli_number(matches, explicit, "difficulty"),
+                failover_margin: cli_number(matches, explicit, "failover_margin"),
+                failover_after: cli_number(matches, explicit, "failover_after"),
+                probe_interval: cli_number(matches, explicit, "probe_interval"),
+                split: match matches.values_of("split") {
+                    Some(weights) if explicit => Some(weights.filter_map(|weight| weight.parse().ok()).collect()),
+                    _ => None,
+                },
+            },
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Values from `ALEOXMINER_*` environment variables.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(env::vars())
+    }
+
+    /// Values from `ALEOXMINER_*` variables, empty ones count as unset. `ALEOXMINER_POOL` is
+    /// accepted for a single pool.
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        let mut config = serde_json::Map::new();
+        let mut errors = Errors(Vec::new());
+        for (name, value) in vars {
+            if !name.starts_with(ENV_PREFIX) || value.is_empty() {
+                continue;
+            }
+            let alias = format!("{}POOL", ENV_PREFIX);
+            let field = FIELDS
+                .iter()
+                .find(|(path, ..)| env_name(path) == name || (*path == "pools" && name == alias));
+            let (path, kind) = match field {
+                Some((path, kind, _)) => (*path, *kind),
+                None => {
+                    errors.push(&name, "unknown setting");
+                    continue;
+                }
+            };
+            let value = match parse_env(kind, &value) {
+                Ok(value) => value,
+                Err(e) => {
+                    errors.push(&name, e);
+                    continue;
+                }
+            };
+            match path.split_once('.') {
+                Some((section, field)) => {
+                    let section = config
+                        .entry(section.to_string())
+                        .or_insert_with(|| Value::Object(Default::default()));
+                    if let Value::Object(section) = section {
+                        section.insert(field.to_string(), value);
+                    }
+                }
+                None => {
+                    config.insert(path.to_string(), value);
+                }
+            }
+        }
+        if !errors.0.is_empty() {
+            return Err(anyhow!("invalid environment:\n  {}", errors.0.join("\n  ")));
+        }
+        Ok(serde_json::from_value(Value::Object(config))?)
+    }
+
+    /// Overlays `other`, whose values win. Lists are replaced, not appended to.
+    pub fn merge(mut self, other: Self) -> Result<Self> {
+        fn overlay(base: &mut Value, other: Value) {
+            match (base, other) {
+                (Value::Object(base), Value::Object(other)) => {
+                    for (key, value) in other {
+                        match base.get_mut(&key) {
+                            Some(existing) => overlay(existing, value),
+                            None => {
+                                base.insert(key, value);
+                            }
+                        }
+                    }
+                }
+                (_, Value::Null) => {}
+                (base, other) => *base = other,
+            }
+        }
+        // A password source replaces the ones given at a lower level instead of conflicting with them.
+        if other.password.is_some()
+            || other.password_file.is_some()
+            || other.password_env.is_some()
+            || other.password_keyring.is_some()
+        {
+            self.password = None;
+            self.password_file = None;
+            self.password_env = None;
+            self.password_keyring = None;
+        }
+        let mut base = serde_json::to_value(self)?;
+        overlay(&mut base, serde_json::to_value(other)?);
+        Ok(serde_json::from_value(base)?)
+    }
+
+    /// Checks every value, the errors name the offending field, e.g. `pools[1].url: invalid scheme`.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Errors(Vec::new());
+        errors.check("address", self.address.as_ref(), credentials::parse_address);
+        errors.check("account", self.account.as_ref(), credentials::validate_account);
+        errors.check("worker", self.worker.as_ref(), credentials::validate_worker);
+        errors.check("schedule", self.schedule.as_ref(), |s| s.parse::<Schedule>());
+        errors.check("rate_unit", self.rate_unit.as_ref(), |s| s.parse::<RateUnit>());
+        for (index, pool) in self.pools.iter().flatten().enumerate() {
+            if let Err(e) = pool_address(&pool.url) {
+                errors.push(&format!("pools[{}].url", index), e);
+            }
+            errors.check(&format!("pools[{}].account", index), pool.account.as_ref(), credentials::validate_account);
+            errors.check(&format!("pools[{}].worker", index), pool.worker.as_ref(), credentials::validate_worker);
+            if pool.account.is_none() && self.account.is_none() && self.address.is_none() {
+                errors.push(&format!("pools[{}]", index), "no account or address, set one here or at the top level");
+            }
+        }
+        if self.prover.threads == Some(0) {
+            errors.push("prover.threads", "must be at least 1");
+        }
+        if matches!(self.prover.gpus.as_ref(), Some(gpus) if gpus.is_empty()) {
+            errors.push("prover.gpus", "must list at least one GPU");
+        }
+        errors.check("logging.level", self.logging.level.as_ref(), |s| s.parse::<LevelFilter>());
+        errors.check("logging.file_level", self.logging.file_level.as_ref(), |s| s.parse::<LevelFilter>());
+        errors.check("logging.rotation", self.logging.rotation.as_ref(), |s| s.parse::<LogRotation>());
+        errors.check("logging.format", self.logging.format.as_ref(), |s| s.parse::<LogFormat>());
+        errors.check("report.delta", self.report.delta.as_ref(), |s| s.parse::<RateDelta>());
+        errors.check("alerts.min_hashrate", self.alerts.min_hashrate.as_ref(), |s| s.parse::<RateThreshold>());
+        errors.check("alerts.max_disconnected", self.alerts.max_disconnected.as_ref(), schedule::parse_duration);
+        errors.check("timeouts.pool_unreachable", self.timeouts.pool_unreachable.as_ref(), schedule::parse_duration);
+        if matches!(self.timeouts.keepalive, Some(keepalive) if keepalive > MAX_KEEPALIVE) {
+            errors.push("timeouts.keepalive", format!("must be at most {} seconds", MAX_KEEPALIVE));
+        }
+        let in_range = |value: Option<u64>, range: RangeInclusive<usize>| {
+            value.map_or(true, |value| range.contains(&(value.min(usize::MAX as u64) as usize)))
+        };
+        if !in_range(self.protocol.max_frame_size, FRAME_SIZE_RANGE) {
+            errors.push(
+                "protocol.max_frame_size",
+                format!("must be {} to {} bytes", FRAME_SIZE_RANGE.start(), FRAME_SIZE_RANGE.end()),
+            );
+        }
+        if !in_range(self.protocol.max_string_length, STRING_LENGTH_RANGE) {
+            errors.push(
+                "protocol.max_string_length",
+                format!("must be {} to {} bytes", STRING_LENGTH_RANGE.start(), STRING_LENGTH_RANGE.end()),
+            );
+        }
+        if !in_range(self.protocol.submit_queue, SUBMIT_QUEUE_RANGE) {
+            errors.push(
+                "protocol.submit_queue",
+                format!("must be {} to {} messages", SUBMIT_QUEUE_RANGE.start(), SUBMIT_QUEUE_RANGE.end()),
+            );
+        }
+        if let Some(percent) = self.alerts.max_reject_percent {
+            if !(0.0..100.0).contains(&percent) {
+                errors.push("alerts.max_reject_percent", "must be at least 0 and below 100");
+            }
+        }
+        if let Some(margin) = self.protocol.failover_margin {
+            if !(0.0..=100.0).contains(&margin) {
+                errors.push("protocol.failover_margin", "must be 0 to 100 points");
+            }
+        }
+        if matches!(self.protocol.split.as_ref(), Some(weights) if weights.iter().all(|weight| *weight == 0)) {
+            errors.push("protocol.split", "must give at least one pool a weight");
+        }
+        errors.check("telemetry.status_bind", self.telemetry.status_bind.as_ref(), |s| s.parse::<SocketAddr>());
+        errors.check("telemetry.metrics_bind", self.telemetry.metrics_bind.as_ref(), |s| s.parse::<SocketAddr>());
+
+        let telemetry = &self.telemetry;
+        errors.feature("prover.gpus", self.prover.gpus.as_ref(), "cuda", cfg!(feature = "cuda"));
+        errors.feature("prover.cuda_jobs", self.prover.cuda_jobs.as_ref(), "cuda", cfg!(feature = "cuda"));
+        let metrics = cfg!(feature = "metrics");
+        errors.feature("telemetry.status_bind", telemetry.status_bind.as_ref(), "metrics", metrics);
+        errors.feature("telemetry.metrics_bind", telemetry.metrics_bind.as_ref(), "metrics", metrics);
+        errors.feature("telemetry.control_token", telemetry.control_token.as_ref(), "metrics", metrics);
+        let mqtt = cfg!(feature = "mqtt");
+        errors.feature("telemetry.mqtt_broker", telemetry.mqtt_broker.as_ref(), "mqtt", mqtt);
+        errors.feature("telemetry.mqtt_username", telemetry.mqtt_username.as_ref(), "mqtt", mqtt);
+        errors.feature("telemetry.mqtt_password", telemetry.mqtt_password.as_ref(), "mqtt", mqtt);
+        errors.feature("telemetry.mqtt_interval", telemetry.mqtt_interval.as_ref(), "mqtt", mqtt);
+        let https = |url: &Option<String>| url.as_ref().filter(|url| url.starts_with("https://"));
+        errors.feature("telemetry.influx_url", https(&telemetry.influx_url), "tls", cfg!(feature = "tls"));
+        errors.feature("telemetry.webhook_url", https(&telemetry.webhook_url), "tls", cfg!(feature = "tls"));
+        if errors.0.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("invalid configuration:\n  {}", errors.0.join("\n  ")))
+        }
+    }
+
+    /// Applies the set values to the options, call `validate` first.
+    pub(crate) fn apply(&self, opt: &mut Opt) {
+        fn set<T>(target: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        fn parse<T: FromStr>(value: Option<&String>) -> Option<T> {
+            value.and_then(|value| value.parse().ok())
+        }
+
+        set(
+            &mut opt.address,
+            self.address.as_deref().and_then(|s| credentials::parse_address(s).ok()).map(Some),
+        );
+        set(&mut opt.account, self.account.clone().map(Some));
+        set(&mut opt.worker, self.worker.clone().map(Some));
+        set(&mut opt.password, self.password.clone().map(Some));
+        set(&mut opt.password_file, self.password_file.clone().map(Some));
+        set(&mut opt.password_env, self.password_env.clone().map(Some));
+        set(&mut opt.password_keyring, self.password_keyring.clone().map(Some));
+        set(&mut opt.schedule, parse(self.schedule.as_ref()).map(Some));
+        set(&mut opt.rate_unit, parse(self.rate_unit.as_ref()));
+        if let Some(pools) = self.pools.as_ref() {
+            let mut pools = pools.clone();
+            pools.sort_by_key(|pool| pool.priority);
+            opt.pools = pools
+                .iter()
+                .filter_map(|pool| pool_address(&pool.url).ok())
+                .map(|address| address.to_string())
+                .collect();
+            set(
+                &mut opt.pool,
+                pools
+                    .first()
+                    .and_then(|pool| pool_address(&pool.url).ok())
+                    .map(|address| Some(address.to_string())),
+            );
+            opt.pool_credentials = pools
+                .iter()
+                .filter_map(|pool| {
+                    let credentials = PoolCredentials {
+                        account: pool.account.clone(),
+                        worker: pool.worker.clone(),
+                        password: pool.password.clone(),
+                    };
+                    let address = pool_address(&pool.url).ok()?;
+                    Some((address.to_string(), credentials)).filter(|_| credentials != PoolCredentials::default())
+                })
+                .collect();
+        }
+
+        set(&mut opt.threads, self.prover.threads.map(Some));
+        #[cfg(feature = "cuda")]
+        {
+            set(&mut opt.cuda, self.prover.gpus.clone().map(Some));
+            set(&mut opt.jobs, self.prover.cuda_jobs.map(Some));
+        }
+        set(&mut opt.max_concurrent_proofs, self.prover.max_concurrent_proofs.map(Some));
+        set(&mut opt.nice, self.prover.nice);
+
+        set(&mut opt.console_level, parse(self.logging.level.as_ref()).map(Some));
+        set(&mut opt.file_level, parse(self.logging.file_level.as_ref()).map(Some));
+        set(&mut opt.log, self.logging.file.clone().map(Some));
+        set(&mut opt.log_rotation, parse(self.logging.rotation.as_ref()));
+        set(&mut opt.log_keep, self.logging.keep);
+        set(&mut opt.log_format, parse(self.logging.format.as_ref()));
+
+        set(&mut opt.rate_min_interval, self.report.min_interval);
+        set(&mut opt.rate_max_interval, self.report.max_interval);
+        set(&mut opt.rate_delta, parse(self.report.delta.as_ref()));
+
+        set(&mut opt.share_alert, self.alerts.no_share);
+        set(&mut opt.alert_min_hashrate, parse(self.alerts.min_hashrate.as_ref()).map(Some));
+        set(&mut opt.alert_max_reject, self.alerts.max_reject_percent.map(Some));
+        set(
+            &mut opt.alert_max_disconnected,
+            self.alerts
+                .max_disconnected
+                .as_deref()
+                .and_then(|s| schedule::parse_duration(s).ok())
+                .map(Some),
+        );
+
+        let telemetry = &self.telemetry;
+        #[cfg(feature = "metrics")]
+        {
+            set(&mut opt.status_bind, parse(telemetry.status_bind.as_ref()).map(Some));
+            set(&mut opt.metrics_bind, parse(telemetry.metrics_bind.as_ref()).map(Some));
+            set(&mut opt.control_token, telemetry.control_token.clone().map(Some));
+        }
+        set(&mut opt.influx_url, telemetry.influx_url.clone().map(Some));
+        set(&mut opt.influx_bucket, telemetry.influx_bucket.clone());
+        set(&mut opt.influx_org, telemetry.influx_org.clone().map(Some));
+        set(&mut opt.influx_token, telemetry.influx_token.clone().map(Some));
+        set(&mut opt.influx_interval, telemetry.influx_interval);
+        #[cfg(feature = "mqtt")]
+        {
+            set(&mut opt.mqtt_broker, telemetry.mqtt_broker.clone().map(Some));
+            set(&mut opt.mqtt_username, telemetry.mqtt_username.clone().map(Some));
+            set(&mut opt.mqtt_password, telemetry.mqtt_password.clone().map(Some));
+            set(&mut opt.mqtt_interval, telemetry.mqtt_interval);
+        }
+        set(&mut opt.statsd, telemetry.statsd.clone().map(Some));
+        set(&mut opt.statsd_prefix, telemetry.statsd_prefix.clone());
+        set(&mut opt.webhook_url, telemetry.webhook_url.clone().map(Some));
+
+        set(&mut opt.max_frame_size, self.protocol.max_frame_size.map(|size| size as usize));
+        set(&mut opt.max_string_length, self.protocol.max_string_length.map(|length| length as usize));
+        set(&mut opt.submit_queue, self.protocol.submit_queue.map(|size| size as usize));
+        set(&mut opt.max_submit_age, self.protocol.max_submit_age);
+        set(&mut opt.max_rate_age, self.protocol.max_rate_age);
+        set(&mut opt.read_limit, self.protocol.read_limit.map(Some));
+        set(&mut opt.write_limit, self.protocol.write_limit.map(Some));
+        set(&mut opt.msgpack, self.protocol.msgpack);
+        set(&mut opt.job_ack, self.protocol.job_ack);
+        set(&mut opt.speculative, self.protocol.speculative);
+        set(&mut opt.pool_info, self.protocol.pool_info);
+        set(&mut opt.reorg_tolerance, self.protocol.reorg_tolerance);
+        set(&mut opt.no_template_validation, self.protocol.no_template_validation);
+        set(&mut opt.template_max_skew, self.protocol.template_max_skew);
+        set(&mut opt.difficulty, self.protocol.difficulty);
+        set(&mut opt.failover_margin, self.protocol.failover_margin);
+        set(&mut opt.failover_after, self.protocol.failover_after);
+        set(&mut opt.probe_interval, self.protocol.probe_interval);
+        set(&mut opt.split, self.protocol.split.clone().map(Some));
+
+        set(&mut opt.keepalive, self.timeouts.keepalive);
+        set(&mut opt.rtt_warning, self.timeouts.rtt_warning);
+        set(&mut opt.webhook_disconnect_after, self.timeouts.webhook_disconnect_after);
+        set(
+            &mut opt.pool_unreachable_exit,
+            self.timeouts
+                .pool_unreachable
+                .as_deref()
+                .and_then(|s| schedule::parse_duration(s).ok())
+                .map(Some),
+        );
+    }
+
+    /// Migrations applied to the configuration file, oldest first.
+    pub fn migrations(&self) -> &[String] {
+        &self.migrations
+    }
+
+    /// Problems that don't prevent mining but are likely mistakes.
+    pub fn warnings(&self) -> Vec<String> {
+        self.account.as_deref().and_then(credentials::account_warning).into_iter().collect()
+    }
+
+    /// Copy with the credentials and tokens replaced, for printing.
+    pub fn redacted(&self) -> Self {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| REDACTED.to_string());
+        let redact_secret = |value: &Option<Secret>| value.as_ref().map(|_| Secret::from(REDACTED));
+        let mut config = self.clone();
+        config.account = redact(&self.account);
+        config.password = redact_secret(&self.password);
+        for pool in config.pools.iter_mut().flatten() {
+            pool.account = redact(&pool.account);
+            pool.password = redact_secret(&pool.password);
+        }
+        config.telemetry.control_token = redact(&self.telemetry.control_token);
+        config.telemetry.influx_token = redact(&self.telemetry.influx_token);
+        config.telemetry.mqtt_password = redact(&self.telemetry.mqtt_password);
+        config.telemetry.webhook_url = redact(&self.telemetry.webhook_url);
+        config
+    }
+
+    /// The configuration in the current file layout.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(format!("schema_version = {}\n\n{}", SCHEMA_VERSION, toml::to_string_pretty(self)?))
+    }
+}
+
+/// Annotated example file with every field set to its default or commented out. The fields come
+/// from `Config` and the defaults from the command line options, so it can't drift from either.
+pub fn example() -> Result<String> {
+    let matches = Opt::clap().get_matches_from_safe(["prover"])?;
+    let defaults = serde_json::to_value(Config::from_matches(&matches, false))?;
+    let fields = match serde_json::to_value(Config::default())? {
+        Value::Object(fields) => fields,
+        _ => return Err(anyhow!("configuration is not a table")),
+    };
+
+    // Top level fields have to come before the first section.
+    let mut top = Vec::new();
+    let mut sections = Vec::new();
+    for (key, value) in fields {
+        match value {
+            Value::Object(section) => {
+                let mut paths: Vec<String> = section.keys().map(|field| format!("{}.{}", key, field)).collect();
+                paths.sort_by_key(|path| field_position(path));
+                sections.push((key, paths));
+            }
+            _ => top.push(key),
+        }
+    }
+    top.sort_by_key(|path| field_position(path));
+    sections.sort_by_key(|(_, paths)| paths.first().map_or(FIELDS.len(), |path| field_position(path)));
+
+    let entry = |text: &mut String, path: &str| {
+        let key = path.rsplit('.').next().unwrap_or(path);
+        let (kind, description) = FIELDS
+            .iter()
+            .find(|(field, ..)| *field == path)
+            .map_or((Kind::Text, ""), |(_, kind, description)| (*kind, *description));
+        if !description.is_empty() {
+            text.push_str(&format!("# {}\n", description));
+        }
+        text.push_str(&format!("# Environment: {}\n", env_name(path)));
+        match defaults.pointer(&format!("/{}", path.replace('.', "/"))) {
+            Some(value) if !value.is_null() => text.push_str(&format!("{} = {}\n\n", key, toml_literal(value))),
+            _ => text.push_str(&format!("# {} = {}\n\n", key, placeholder(kind))),
+        }
+    };
+    let mut text = format!(
+        "# AleoXMiner configuration, values shown are the defaults.\n\
+         # Command line options and ALEOXMINER_* variables override this file.\n\n\
+         # Layout version of this file, older files are migrated when read\n\
+         schema_version = {}\n\n",
+        SCHEMA_VERSION
+    );
+    for path in top.iter() {
+        entry(&mut text, path);
+    }
+    for (section, paths) in sections.iter() {
+        text.push_str(&format!("[{}]\n\n", section));
+        for path in paths.iter() {
+            entry(&mut text, path);
+        }
+    }
+    Ok(text)
+}
+
+/// Effective configuration: command line defaults, then the file, the command line and the
+/// environment, each overriding the previous ones.
+pub fn load(path: Option<&Path>, matches: &ArgMatches) -> Result<Config> {
+    load_with(path, matches, env::vars())
+}
+
+// `load` with the environment given as `vars`.
+fn load_with(
+    path: Option<&Path>,
+    matches: &ArgMatches,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Config> {
+    let file = match path {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    let migrations = file.migrations.clone();
+    let mut config = Config::from_matches(matches, false)
+        .merge(file)?
+        .merge(Config::from_matches(matches, true))?
+        .merge(Config::from_vars(vars)?)?;
+    config.validate()?;
+    config.migrations = migrations;
+    Ok(config)
+}
+
+/// Rewrites an older file in the current schema, keeping the original as `<path>.v<version>.bak`.
+/// Returns the backup path, or None if the file is already current. Comments are not kept.
+pub fn write_migrated(path: &Path) -> Result<Option<PathBuf>> {
+    let text = fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))?;
+    let mut table: toml::value::Table =
+        toml::from_str(&text).with_context(|| format!("unable to parse {}", path.display()))?;
+    let version = take_schema_version(&mut table)?;
+    if version == SCHEMA_VERSION {
+        return Ok(None);
+    }
+    let config = Config::from_toml(&text).with_context(|| format!("unable to parse {}", path.display()))?;
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    let backup = PathBuf::from(backup);
+    ensure!(!backup.exists(), "{} already exists", backup.display());
+    fs::copy(path, &backup).with_context(|| format!("unable to back up {}", path.display()))?;
+    fs::write(path, config.to_toml()?).with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(Some(backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // Writes `text` as the configuration file of `test`.
+    fn file(test: &str, text: &str) -> PathBuf {
+        let path = testing::scratch("config", test).join("miner.toml");
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    // The command line `prover <args>`.
+    fn matches(args: &[&str]) -> ArgMatches<'static> {
+        Opt::clap().get_matches_from_safe(std::iter::once("prover").chain(args.iter().copied())).unwrap()
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    // Every error line of a failed validation.
+    fn errors(config: &Config) -> Vec<String> {
+        let error = config.validate().unwrap_err().to_string();
+        error.lines().skip(1).map(|line| line.trim().to_string()).collect()
+    }
+
+    const FILE: &str = r#"
+        worker = "file"
+
+        [prover]
+        threads = 4
+
+        [logging]
+        keep = 3
+
+        [report]
+        min_interval = 20
+    "#;
+
+    #[test]
+    fn environment_overrides_command_line_overrides_file_overrides_defaults() {
+        let path = file("precedence", FILE);
+        let config = load_with(
+            Some(&path),
+            &matches(&["--worker", "cli", "--threads", "8"]),
+            vars(&[("ALEOXMINER_PROVER__THREADS", "12")]),
+        )
+        .unwrap();
+        assert_eq!(config.worker.as_deref(), Some("cli"));
+        assert_eq!(config.prover.threads, Some(12));
+        assert_eq!(config.logging.keep, Some(3));
+        assert_eq!(config.report.min_interval, Some(20));
+        // Neither set anywhere, the command line defaults.
+        assert_eq!(config.report.max_interval, Some(30));
+        assert_eq!(config.logging.rotation.as_deref(), Some("daily"));
+    }
+
+    #[test]
+    fn the_file_overrides_only_the_defaults() {
+        let path = file("file-only", FILE);
+        let config = load_with(Some(&path), &matches(&["--log-keep", "9"]), Vec::new()).unwrap();
+        assert_eq!(config.worker.as_deref(), Some("file"));
+        assert_eq!(config.prover.threads, Some(4));
+        assert_eq!(config.logging.keep, Some(9));
+
+        let config = load_with(None, &matches(&[]), Vec::new()).unwrap();
+        assert_eq!(config.worker, None);
+        assert_eq!(config.logging.keep, Some(7));
+    }
+
+    #[test]
+    fn a_password_source_replaces_the_lower_ones() {
+        let path = file("password", "password_file = \"/etc/aleoxminer/password\"\n");
+        let config = load_with(Some(&path), &matches(&["--password", "cli"]), Vec::new()).unwrap();
+        assert_eq!(config.password, Some(Secret::from("cli")));
+        assert_eq!(config.password_file, None);
+    }
+
+    #[test]
+    fn merging_replaces_lists() {
+        let base = Config::from_toml("[prover]\ngpus = [0, 1, 2]\nnice = true\n").unwrap();
+        let other = Config::from_toml("[prover]\ngpus = [3]\n").unwrap();
+        let merged = base.merge(other).unwrap();
+        assert_eq!(merged.prover.gpus, Some(vec![3]));
+        assert_eq!(merged.prover.nice, Some(true));
+    }
+
+    #[test]
+    fn parses_environment_variables_by_kind() {
+        let config = Config::from_vars(vars(&[
+            ("ALEOXMINER_WORKER", "rig1"),
+            ("ALEOXMINER_PROVER__THREADS", " 12 "),
+            ("ALEOXMINER_PROVER__GPUS", "0, 2,,3,"),
+            ("ALEOXMINER_PROVER__NICE", "Yes"),
+            ("ALEOXMINER_PROTOCOL__MSGPACK", "off"),
+            ("ALEOXMINER_PROTOCOL__JOB_ACK", "1"),
+            ("ALEOXMINER_ALERTS__MAX_REJECT_PERCENT", "12.5"),
+            ("ALEOXMINER_POOLS", "a.example.com:4040, tcp://b.example.com:4040"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+        assert_eq!(config.worker.as_deref(), Some("rig1"));
+        assert_eq!(config.prover.threads, Some(12));
+        assert_eq!(config.prover.gpus, Some(vec![0, 2, 3]));
+        assert_eq!(config.prover.nice, Some(true));
+        assert_eq!(config.protocol.msgpack, Some(false));
+        assert_eq!(config.protocol.job_ack, Some(true));
+        assert_eq!(config.alerts.max_reject_percent, Some(12.5));
+        let pools = config.pools.unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!((pools[0].url.as_str(), pools[0].priority), ("a.example.com:4040", 0));
+        assert_eq!((pools[1].url.as_str(), pools[1].priority), ("tcp://b.example.com:4040", 1));
+    }
+
+    #[test]
+    fn parses_environment_edge_cases() {
+        // Empty values count as unset, an empty list is an empty list.
+        let config = Config::from_vars(vars(&[("ALEOXMINER_WORKER", ""), ("ALEOXMINER_PROVER__GPUS", " , ")])).unwrap();
+        assert_eq!(config.worker, None);
+        assert_eq!(config.prover.gpus, Some(Vec::new()));
+        assert!(errors(&config)[0].starts_with("prover.gpus: "));
+
+        // The single pool alias.
+        let config = Config::from_vars(vars(&[("ALEOXMINER_POOL", "a.example.com:4040")])).unwrap();
+        assert_eq!(config.pools.unwrap()[0].url, "a.example.com:4040");
+
+        for value in ["TRUE", "on", "1", "yes"] {
+            let config = Config::from_vars(vars(&[("ALEOXMINER_PROVER__NICE", value)])).unwrap();
+            assert_eq!(config.prover.nice, Some(true), "{}", value);
+        }
+        for value in ["False", "off", "0", "no"] {
+            let config = Config::from_vars(vars(&[("ALEOXMINER_PROVER__NICE", value)])).unwrap();
+            assert_eq!(config.prover.nice, Some(false), "{}", value);
+        }
+    }
+
+    #[test]
+    fn names_every_invalid_environment_variable() {
+        let error = Config::from_vars(vars(&[
+            ("ALEOXMINER_PROVER__NICE", "maybe"),
+            ("ALEOXMINER_PROVER__THREADS", "-1"),
+            ("ALEOXMINER_PROVER__GPUS", "0,one"),
+            ("ALEOXMINER_ALERTS__MAX_REJECT_PERCENT", "NaN"),
+            ("ALEOXMINER_THREADS", "4"),
+        ]))
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with("invalid environment:"), "{}", error);
+        for expected in [
+            "ALEOXMINER_PROVER__NICE: expected true or false",
+            "ALEOXMINER_PROVER__THREADS: expected a non-negative integer",
+            "ALEOXMINER_PROVER__GPUS: expected comma separated integers",
+            "ALEOXMINER_ALERTS__MAX_REJECT_PERCENT: expected a number",
+            "ALEOXMINER_THREADS: unknown setting",
+        ] {
+            assert!(error.contains(expected), "{} not in {}", expected, error);
+        }
+    }
+
+    #[test]
+    fn every_field_has_an_environment_variable() {
+        let fields = match serde_json::to_value(Config::default()).unwrap() {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        };
+        for (key, value) in fields {
+            let paths = match value {
+                Value::Object(section) => section.keys().map(|field| format!("{}.{}", key, field)).collect(),
+                _ => vec![key],
+            };
+            for path in paths {
+                assert!(FIELDS.iter().any(|(field, ..)| *field == path), "{} has no variable", path);
+            }
+        }
+    }
+
+    // A fixture of this crate's tests directory.
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config").join(name)
+    }
+
+    #[test]
+    fn the_example_parses_back_into_the_defaults() {
+        let example = Config::from_toml(&example().unwrap()).unwrap();
+        assert_eq!(example, load_with(None, &matches(&[]), Vec::new()).unwrap());
+        assert!(example.migrations().is_empty());
+        assert!(example.validate().is_ok());
+    }
+
+    // The snapshot is of a build without the mqtt feature, which adds the default of mqtt_interval.
+    #[cfg(not(feature = "mqtt"))]
+    #[test]
+    fn the_example_matches_its_snapshot() {
+        let snapshot = fs::read_to_string(fixture("example.toml")).unwrap();
+        assert!(
+            example().unwrap() == snapshot,
+            "--help-config changed, update tests/fixtures/config/example.toml with `AleoXMiner --help-config`"
+        );
+    }
+
+    // Every schema version's fixture, what it loads to and the migrations applied.
+    fn versions() -> Vec<(&'static str, Config, Vec<String>)> {
+        let base = Config {
+            account: Some("miner-account".to_string()),
+            worker: Some("rig1".to_string()),
+            prover: ProverSection { threads: Some(8), ..Default::default() },
+            logging: LoggingSection { keep: Some(3), ..Default::default() },
+            ..Default::default()
+        };
+        let pool = PoolConfig { url: "pool.example.com:4040".to_string(), ..Default::default() };
+        let backup = PoolConfig {
+            url: "tcp://backup.example.com:4040".to_string(),
+            priority: 1,
+            worker: Some("rig1-backup".to_string()),
+            ..Default::default()
+        };
+        vec![
+            (
+                "v1.toml",
+                Config { pools: Some(vec![pool.clone()]), ..base.clone() },
+                vec!["schema version 2: moved the single server into the pools list".to_string()],
+            ),
+            ("v2.toml", Config { pools: Some(vec![pool, backup]), ..base }, Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn loads_every_schema_version() {
+        assert_eq!(versions().len() as u32, SCHEMA_VERSION);
+        for (name, mut expected, migrations) in versions() {
+            let config = Config::from_file(&fixture(name)).unwrap();
+            assert_eq!(config.migrations(), migrations, "{}", name);
+            expected.migrations = migrations;
+            assert_eq!(config, expected, "{}", name);
+            assert!(config.validate().is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn migrates_the_single_pool_of_version_1() {
+        let config = Config::from_toml("pool = \"pool.example.com:4040\"\naccount = \"a\"\n").unwrap();
+        assert_eq!(config.pools.unwrap()[0].url, "pool.example.com:4040");
+        let error = Config::from_toml("server = \"a.example.com:4040\"\npools = [{ url = \"b.example.com:4040\" }]\n")
+            .unwrap_err();
+        assert_eq!(format!("{:#}", error), "unable to migrate to schema version 2: both server and pools are set");
+        let error = Config::from_toml("server = 4040\n").unwrap_err();
+        assert_eq!(format!("{:#}", error), "unable to migrate to schema version 2: server must be a string");
+    }
+
+    #[test]
+    fn refuses_newer_and_invalid_versions() {
+        let error = Config::from_toml(&format!("schema_version = {}\n", SCHEMA_VERSION + 1)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "config is newer than this binary: schema version {}, this build reads up to {}, please upgrade",
+                SCHEMA_VERSION + 1,
+                SCHEMA_VERSION
+            )
+        );
+        for version in ["0", "-1", "\"2\"", "1.5"] {
+            let error = Config::from_toml(&format!("schema_version = {}\n", version)).unwrap_err();
+            assert_eq!(error.to_string(), "schema_version must be a positive integer", "{}", version);
+        }
+    }
+
+    #[test]
+    fn writes_migrated_files_keeping_a_backup() {
+        let path = testing::scratch("config", "write-migrated").join("miner.toml");
+        let original = fs::read_to_string(fixture("v1.toml")).unwrap();
+        fs::write(&path, &original).unwrap();
+
+        let backup = write_migrated(&path).unwrap().unwrap();
+        assert_eq!(backup, path.with_file_name("miner.toml.v1.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+        let (_, mut expected, _) = versions().remove(0);
+        expected.migrations = Vec::new();
+        assert_eq!(Config::from_file(&path).unwrap(), expected);
+        // Current files are left alone.
+        assert_eq!(write_migrated(&path).unwrap(), None);
+
+        // An earlier backup is never overwritten.
+        fs::write(&path, &original).unwrap();
+        assert!(write_migrated(&path).unwrap_err().to_string().ends_with("already exists"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    const POOLS: &str = r#"
+account = "top"
+worker = "rig1"
+password = "top-secret"
+pools = [
+    { url = "c.example.com:4040", priority = 2, account = "c-account" },
+    { url = "tcp://a.example.com:4040" },
+    { url = "b.example.com:4040", priority = 1, worker = "rig1-b", password = "b-secret" },
+]
+"#;
+
+    #[test]
+    fn pools_override_the_top_level_credentials() {
+        let mut opt = Opt::from_iter_safe(["prover"]).unwrap();
+        Config::from_toml(POOLS).unwrap().apply(&mut opt);
+        assert_eq!(opt.pools, ["a.example.com:4040", "b.example.com:4040", "c.example.com:4040"]);
+        assert_eq!(opt.pool.as_deref(), Some("a.example.com:4040"));
+        assert_eq!((opt.account.as_deref(), opt.worker.as_deref()), (Some("top"), Some("rig1")));
+        // Pools without overrides use the top-level values, unset overrides fall back one by one.
+        let b = PoolCredentials {
+            worker: Some("rig1-b".to_string()),
+            password: Some(Secret::from("b-secret")),
+            ..Default::default()
+        };
+        let c = PoolCredentials { account: Some("c-account".to_string()), ..Default::default() };
+        let expected = std::collections::HashMap::from([
+            ("b.example.com:4040".to_string(), b),
+            ("c.example.com:4040".to_string(), c),
+        ]);
+        assert_eq!(opt.pool_credentials, expected);
+    }
+
+    #[test]
+    fn a_pool_from_a_higher_source_replaces_the_pools_and_their_overrides() {
+        let path = file("pool-overrides", POOLS);
+        let config = load_with(Some(&path), &matches(&["--pool", "d.example.com:4040"]), Vec::new()).unwrap();
+        let pool = |url: &str| PoolConfig { url: url.to_string(), ..Default::default() };
+        assert_eq!(config.pools, Some(vec![pool("d.example.com:4040")]));
+        assert_eq!(config.worker.as_deref(), Some("rig1"));
+        let environment = vars(&[("ALEOXMINER_POOL", "e.example.com:4040")]);
+        let config = load_with(Some(&path), &matches(&[]), environment).unwrap();
+        assert_eq!(config.pools, Some(vec![pool("e.example.com:4040")]));
+        let mut opt = Opt::from_iter_safe(["prover"]).unwrap();
+        load_with(Some(&path), &matches(&[]), Vec::new()).unwrap().apply(&mut opt);
+        assert_eq!(opt.pool_credentials.len(), 2);
+    }
+
+    #[test]
+    fn every_pool_needs_an_account_or_address() {
+        let config = Config::from_toml(
+            "pools = [{ url = \"a.example.com:4040\", account = \"a\" }, { url = \"b.example.com:4040\" }]\n",
+        )
+        .unwrap();
+        assert_eq!(errors(&config), ["pools[1]: no account or address, set one here or at the top level"]);
+        let config = Config { account: Some("top".to_string()), ..config };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn the_codec_enforces_the_configured_limits() {
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        use crate::message::{Code, ProtocolError, ProverCodec, ProverMessage};
+
+        let config = Config::from_toml("[protocol]\nmax_frame_size = 1024\nmax_string_length = 16\n").unwrap();
+        assert!(config.validate().is_ok());
+        let mut opt = Opt::from_iter_safe(["prover"]).unwrap();
+        config.apply(&mut opt);
+        let mut codec = ProverCodec::new(opt.protocol_limits());
+
+        // A frame at the cap waits for its body, one byte over is refused from the length prefix alone.
+        let mut frame = BytesMut::from(&1024u32.to_le_bytes()[..]);
+        assert!(matches!(codec.decode(&mut frame), Ok(None)));
+        let mut frame = BytesMut::from(&1025u32.to_le_bytes()[..]);
+        assert!(matches!(
+            codec.decode(&mut frame),
+            Err(ProtocolError::FrameTooLarge { length: 1025, limit: 1024 })
+        ));
+
+        let mut frame = BytesMut::new();
+        let result = ProverMessage::SubmitResult(Code::Success, Some("a".repeat(17)));
+        codec.encode(result, &mut frame).unwrap();
+        assert!(matches!(
+            codec.decode(&mut frame),
+            Err(ProtocolError::StringTooLong { message: "SubmitResult", length: 17, limit: 16 })
+        ));
+
+        let config = Config::from_toml("[protocol]\nmax_frame_size = 1023\nmax_string_length = 15\n").unwrap();
+        assert_eq!(
+            errors(&config),
+            [
+                "protocol.max_frame_size: must be 1024 to 1073741824 bytes",
+                "protocol.max_string_length: must be 16 to 1048576 bytes"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_the_feature_a_setting_needs() {
+        let (cuda, metrics, mqtt, tls) =
+            (cfg!(feature = "cuda"), cfg!(feature = "metrics"), cfg!(feature = "mqtt"), cfg!(feature = "tls"));
+        let cases = [
+            ("prover", "gpus = [0]", "cuda", cuda),
+            ("prover", "cuda_jobs = 2", "cuda", cuda),
+            ("telemetry", "status_bind = \"127.0.0.1:8080\"", "metrics", metrics),
+            ("telemetry", "metrics_bind = \"127.0.0.1:9090\"", "metrics", metrics),
+            ("telemetry", "control_token = \"token\"", "metrics", metrics),
+            ("telemetry", "mqtt_broker = \"localhost:1883\"", "mqtt", mqtt),
+            ("telemetry", "mqtt_username = \"miner\"", "mqtt", mqtt),
+            ("telemetry", "mqtt_password = \"secret\"", "mqtt", mqtt),
+            ("telemetry", "mqtt_interval = 30", "mqtt", mqtt),
+            ("telemetry", "influx_url = \"https://localhost:8086\"", "tls", tls),
+            ("telemetry", "webhook_url = \"https://hooks.example.com/1\"", "tls", tls),
+        ];
+        for (section, setting, feature, enabled) in cases {
+            let config = Config::from_toml(&format!("[{}]\n{}\n", section, setting)).unwrap();
+            let path = format!("{}.{}", section, setting.split(' ').next().unwrap());
+            if enabled {
+                assert!(config.validate().is_ok(), "{}", path);
+            } else {
+                let needs = format!("needs the `{}` feature, build with `--features {}`", feature, feature);
+                assert_eq!(errors(&config), [format!("{}: {}", path, needs)]);
+            }
+        }
+        // Plain HTTP needs no TLS.
+        let config = Config::from_toml("[telemetry]\ninflux_url = \"http://localhost:8086\"\n").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn names_the_field_of_every_error() {
+        let config = Config::from_toml(
+            r#"
+            account = "pool-account"
+            pools = [{ url = "tcp://a.example.com:4040" }, { url = "http://b.example.com:4040" }]
+
+            [prover]
+            threads = 0
+
+            [logging]
+            level = "loud"
+            "#,
+        )
+        .unwrap();
+        let errors = errors(&config);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(errors[0], "pools[1].url: invalid scheme, expected tcp://");
+        assert_eq!(errors[1], "prover.threads: must be at least 1");
+        assert!(errors[2].starts_with("logging.level: "), "{}", errors[2]);
+    }
+
+    #[test]
+    fn validates_the_credentials() {
+        let address = crate::testing::address().to_string();
+        let valid = Config { address: Some(address.clone()), worker: Some("rig1".to_string()), ..Default::default() };
+        assert!(valid.validate().is_ok());
+
+        let invalid = Config {
+            address: Some(address[..50].to_string()),
+            account: Some("pool account".to_string()),
+            worker: Some("rig.1".to_string()),
+            pools: Some(vec![PoolConfig {
+                url: "a.example.com:4040".to_string(),
+                account: Some(address.replacen("aleo1", "aleo2", 1)),
+                worker: Some("rig 1".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let errors = errors(&invalid);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors[0].starts_with("address: the address is 50 characters long"), "{}", errors[0]);
+        assert!(errors[1].starts_with("account: the account name contains ' '"), "{}", errors[1]);
+        assert!(errors[2].starts_with("worker: the worker name contains '.'"), "{}", errors[2]);
+        assert!(errors[3].starts_with("pools[0].worker: the worker name contains ' '"), "{}", errors[3]);
+
+        // An address given as the account is valid but likely a mistake.
+        let account = Config { account: Some(address[..50].to_string()), ..Default::default() };
+        assert!(account.validate().is_ok());
+        assert_eq!(account.warnings().len(), 1);
+        assert!(account.warnings()[0].contains("looks like an address but isn't a valid one"));
+    }
+
+    #[test]
+    fn refuses_unknown_fields_and_wrong_types() {
+        assert!(Config::from_toml("threads = 4\n").is_err());
+        assert!(Config::from_toml("[prover]\nthreads = \"four\"\n").is_err());
+        assert!(Config::from_toml("[prover]\ncores = 4\n").is_err());
+    }
+
+    #[test]
+    fn loading_fails_on_an_invalid_file() {
+        let path = file("invalid", "[alerts]\nmax_reject_percent = 100.0\n");
+        let error = load_with(Some(&path), &matches(&[]), Vec::new()).unwrap_err().to_string();
+        assert!(error.contains("alerts.max_reject_percent: must be at least 0 and below 100"), "{}", error);
+    }
+
+    #[test]
+    fn printing_redacts_the_secrets() {
+        let config = Config::from_toml(
+            r#"
+            account = "pool-account"
+            password = "hunter2"
+            pools = [{ url = "a.example.com:4040", account = "other-account", password = "s3cret", worker = "rig1" }]
+
+            [telemetry]
+            control_token = "c0ntrol"
+            influx_token = "infl0x"
+            mqtt_password = "mqtt-s3cret"
+            webhook_url = "https://hooks.example.com/secret"
+            "#,
+        )
+        .unwrap();
+        let printed = config.redacted().to_toml().unwrap();
+        let secrets = ["pool-account", "other-account", "hunter2", "s3cret", "c0ntrol", "infl0x", "hooks.example.com"];
+        for secret in secrets {
+            assert!(!printed.contains(secret), "{} in {}", secret, printed);
+        }
+        assert!(printed.contains("rig1"));
+        assert!(printed.starts_with(&format!("schema_version = {}", SCHEMA_VERSION)));
+    }
+}
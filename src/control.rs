@@ -0,0 +1,316 @@
+use std::sync::Arc;
+
+use futures::future::FutureExt;
+use hyper::{
+    header::{HeaderValue, AUTHORIZATION},
+    Body,
+    Method,
+    Response,
+    StatusCode,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    client::Client,
+    http::{self, Handler},
+    prover::{PauseReason, Prover},
+    reload::Reloader,
+    status::{json, Status},
+};
+
+#[derive(Debug, Deserialize)]
+struct SetServer {
+    server: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetThreads {
+    threads: u16,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProofRate {
+    /// Proofs per second
+    rate: f64,
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "error": message }).to_string();
+    json(status, body)
+}
+
+fn status(prover: &Prover, client: &Client) -> Response<Body> {
+    let status = Status::new(&prover.stats(), &client.stats(), &client.server(), &client.worker());
+    json(StatusCode::OK, serde_json::to_string(&status).unwrap_or_default())
+}
+
+/// Compares without stopping at the first mismatch so the token can't be guessed byte by byte.
+fn authorized(header: Option<&HeaderValue>, token: &str) -> bool {
+    let given = match header.and_then(|value| value.to_str().ok()?.strip_prefix("Bearer ")) {
+        Some(given) => given.as_bytes(),
+        None => return false,
+    };
+    let expected = token.as_bytes();
+    given.len() == expected.len()
+        && given.iter().zip(expected.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Serves `POST /control/{pause,resume,clear-rejects,set-server,set-threads,reload-config,proof-rate}`
+/// for requests bearing `token`. Every action can be repeated safely, the response is the status after the action.
+/// `resume` lifts a pause from `pause` only, `clear-rejects` one for too many rejects.
+/// `set-threads` scales the CPU workers and fails when proving with GPUs.
+/// `reload-config` needs a configuration file to re-read, `proof-rate` a miner run with --no-prover.
+pub fn handler(prover: Arc<Prover>, client: Arc<Client>, token: String, reloader: Option<Arc<Reloader>>) -> Handler {
+    Box::new(move |request| {
+        let action = request.uri().path().strip_prefix("/control/")?;
+        if request.method() != Method::POST {
+            return Some(http::ready(error(StatusCode::METHOD_NOT_ALLOWED, "use POST")));
+        }
+        if !authorized(request.headers().get(AUTHORIZATION), &token) {
+            return Some(http::ready(error(StatusCode::UNAUTHORIZED, "invalid or missing bearer token")));
+        }
+        let prover = prover.clone();
+        let client = client.clone();
+        match action {
+            "pause" => Some(
+                async move {
+                    info!("Pausing on control API request");
+                    prover.pause(PauseReason::Manual).await;
+                    status(&prover, &client)
+                }
+                .boxed(),
+            ),
+            "resume" => Some(
+                async move {
+                    info!("Resuming on control API request");
+                    prover.resume(PauseReason::Manual).await;
+                    status(&prover, &client)
+                }
+                .boxed(),
+            ),
+            "clear-rejects" => Some(
+                async move {
+                    info!("Clearing the reject guard on control API request");
+                    prover.clear_rejects().await;
+                    status(&prover, &client)
+                }
+                .boxed(),
+            ),
+            "set-server" => {
+                let response = match serde_json::from_slice::<SetServer>(request.body()) {
+                    Ok(SetServer { server }) if !server.trim().is_empty() => {
+                        info!("Switching to {} on control API request", server.trim());
+                        client.set_server(server.trim().to_string());
+                        status(&prover, &client)
+                    }
+                    Ok(_) => error(StatusCode::BAD_REQUEST, "server must not be empty"),
+                    Err(e) => error(StatusCode::BAD_REQUEST, &format!("invalid body: {}", e)),
+                };
+                Some(http::ready(response))
+            }
+            "set-threads" => match serde_json::from_slice::<SetThreads>(request.body()) {
+                Ok(SetThreads { threads }) if threads > 0 => Some(
+                    async move {
+                        info!("Changing to {} threads on control API request", threads);
+                        match prover.set_threads(threads).await {
+                            Ok(_) => status(&prover, &client),
+                            Err(e) => error(StatusCode::CONFLICT, &format!("{:#}", e)),
+                        }
+                    }
+                    .boxed(),
+                ),
+                Ok(_) => Some(http::ready(error(StatusCode::BAD_REQUEST, "threads must be at least 1"))),
+                Err(e) => Some(http::ready(error(StatusCode::BAD_REQUEST, &format!("invalid body: {}", e)))),
+            },
+            "proof-rate" => {
+                let response = match serde_json::from_slice::<ProofRate>(request.body()) {
+                    Ok(ProofRate { rate }) if rate.is_finite() && rate >= 0.0 => {
+                        if prover.report_external_rate(rate) {
+                            status(&prover, &client)
+                        } else {
+                            error(StatusCode::CONFLICT, "the miner reports its own proof rate")
+                        }
+                    }
+                    Ok(_) => error(StatusCode::BAD_REQUEST, "rate must be a positive number"),
+                    Err(e) => error(StatusCode::BAD_REQUEST, &format!("invalid body: {}", e)),
+                };
+                Some(http::ready(response))
+            }
+            "reload-config" => {
+                let response = match reloader.as_ref() {
+                    Some(reloader) => {
+                        info!("Reloading the configuration on control API request");
+                        match reloader.reload() {
+                            Ok(_) => status(&prover, &client),
+                            Err(e) => {
+                                error!("Configuration reload failed, keeping the running configuration: {:#}", e);
+                                error(StatusCode::UNPROCESSABLE_ENTITY, &format!("{:#}", e))
+                            }
+                        }
+                    }
+                    None => error(StatusCode::NOT_IMPLEMENTED, "no configuration file to reload"),
+                };
+                Some(http::ready(response))
+            }
+            _ => Some(http::ready(error(StatusCode::NOT_FOUND, "unknown action"))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        history::History,
+        status,
+        testing::{self, FakeBackend},
+    };
+
+    const TOKEN: &str = "secret";
+
+    fn free_address() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    // Serves the status and, with a token, the control API on one listener like the miner does.
+    fn serve(token: Option<&str>) -> (SocketAddr, Arc<Prover>, Arc<Client>) {
+        let client = testing::client("127.0.0.1:1", "control");
+        let prover = Prover::new(testing::prover_config(2, FakeBackend::new(Duration::ZERO)), client.clone()).unwrap();
+        let history = Arc::new(History::new(Duration::from_secs(3600)));
+        let mut handlers = vec![status::handler(prover.clone(), client.clone(), history)];
+        if let Some(token) = token {
+            handlers.push(handler(prover.clone(), client.clone(), token.to_string(), None));
+        }
+        let address = free_address();
+        http::serve(address, handlers);
+        (address, prover, client)
+    }
+
+    async fn post(address: SocketAddr, action: &str, token: Option<&str>, body: &str) -> (StatusCode, Value) {
+        let requests = reqwest::Client::new();
+        for _ in 0..100 {
+            let mut request = requests.post(format!("http://{}/control/{}", address, action)).body(body.to_string());
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            if let Ok(response) = request.send().await {
+                let status = StatusCode::from_u16(response.status().as_u16()).unwrap();
+                let body = response.text().await.unwrap();
+                return (status, serde_json::from_str(&body).unwrap_or(Value::Null));
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the control API isn't listening on {}", address);
+    }
+
+    #[test]
+    fn checks_the_bearer_token() {
+        let header = |value: &str| Some(HeaderValue::from_str(value).unwrap());
+        assert!(authorized(header("Bearer secret").as_ref(), TOKEN));
+        assert!(!authorized(header("Bearer secreT").as_ref(), TOKEN));
+        assert!(!authorized(header("Bearer secret2").as_ref(), TOKEN));
+        assert!(!authorized(header("Bearer ").as_ref(), TOKEN));
+        assert!(!authorized(header("secret").as_ref(), TOKEN));
+        assert!(!authorized(None, TOKEN));
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_without_the_token() {
+        let (address, prover, _) = serve(Some(TOKEN));
+
+        for token in [None, Some("wrong")] {
+            let (status, body) = post(address, "pause", token, "").await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["error"], "invalid or missing bearer token");
+        }
+        assert!(!prover.stats().paused);
+
+        let response = reqwest::get(format!("http://{}/control/pause", address)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 405);
+        assert_eq!(post(address, "restart", Some(TOKEN), "").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn pauses_and_resumes() {
+        let (address, prover, _) = serve(Some(TOKEN));
+
+        for _ in 0..2 {
+            let (status, body) = post(address, "pause", Some(TOKEN), "").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["paused"], true);
+            assert_eq!(body["pause_reasons"], serde_json::json!(["manual"]));
+        }
+        assert!(prover.stats().paused);
+
+        let (status, body) = post(address, "resume", Some(TOKEN), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["paused"], false);
+        assert!(!prover.stats().paused);
+    }
+
+    #[tokio::test]
+    async fn clears_only_the_reject_pause() {
+        let (address, prover, _) = serve(Some(TOKEN));
+        prover.pause(PauseReason::Rejects).await;
+        prover.pause(PauseReason::Manual).await;
+
+        let (status, body) = post(address, "clear-rejects", Some(TOKEN), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pause_reasons"], serde_json::json!(["manual"]));
+        assert_eq!(prover.stats().pause_reasons, vec![PauseReason::Manual]);
+    }
+
+    #[tokio::test]
+    async fn switches_the_server() {
+        let (address, _, client) = serve(Some(TOKEN));
+
+        let (status, body) = post(address, "set-server", Some(TOKEN), r#"{"server": " 127.0.0.2:4040 "}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pool"], "127.0.0.2:4040");
+        assert_eq!(client.server(), "127.0.0.2:4040");
+
+        for invalid in [r#"{"server": " "}"#, r#"{"host": "127.0.0.3:4040"}"#, "127.0.0.3:4040"] {
+            assert_eq!(post(address, "set-server", Some(TOKEN), invalid).await.0, StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(client.server(), "127.0.0.2:4040");
+    }
+
+    #[tokio::test]
+    async fn sets_the_threads() {
+        let (address, _, _) = serve(Some(TOKEN));
+
+        assert_eq!(post(address, "set-threads", Some(TOKEN), r#"{"threads": 1}"#).await.0, StatusCode::OK);
+        assert_eq!(post(address, "set-threads", Some(TOKEN), r#"{"threads": 0}"#).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(post(address, "set-threads", Some(TOKEN), "{}").await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn refuses_what_this_miner_cannot_do() {
+        let (address, _, _) = serve(Some(TOKEN));
+
+        let (status, body) = post(address, "reload-config", Some(TOKEN), "").await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(body["error"], "no configuration file to reload");
+        let (status, body) = post(address, "proof-rate", Some(TOKEN), r#"{"rate": 12.5}"#).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "the miner reports its own proof rate");
+        assert_eq!(post(address, "proof-rate", Some(TOKEN), r#"{"rate": -1}"#).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_no_control_routes_without_a_token() {
+        let (address, prover, _) = serve(None);
+
+        for token in [None, Some(TOKEN)] {
+            assert_eq!(post(address, "pause", token, "").await.0, StatusCode::NOT_FOUND);
+        }
+        assert!(!prover.stats().paused);
+        let response = reqwest::get(format!("http://{}/status", address)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
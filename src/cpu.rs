@@ -0,0 +1,167 @@
+use std::fmt::{Display, Formatter};
+
+/// Instruction set extensions relevant to the proving stack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    pub sse42: bool,
+    pub avx: bool,
+    pub avx2: bool,
+    pub bmi2: bool,
+    pub adx: bool,
+    pub avx512f: bool,
+}
+
+impl CpuFeatures {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub fn detect() -> Self {
+        Self {
+            sse42: is_x86_feature_detected!("sse4.2"),
+            avx: is_x86_feature_detected!("avx"),
+            avx2: is_x86_feature_detected!("avx2"),
+            bmi2: is_x86_feature_detected!("bmi2"),
+            adx: is_x86_feature_detected!("adx"),
+            avx512f: is_x86_feature_detected!("avx512f"),
+        }
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    pub fn detect() -> Self {
+        Self::default()
+    }
+
+    /// Features the binary was compiled to require, e.g. through `-C target-cpu`.
+    pub fn compiled() -> Self {
+        Self {
+            sse42: cfg!(target_feature = "sse4.2"),
+            avx: cfg!(target_feature = "avx"),
+            avx2: cfg!(target_feature = "avx2"),
+            bmi2: cfg!(target_feature = "bmi2"),
+            adx: cfg!(target_feature = "adx"),
+            avx512f: cfg!(target_feature = "avx512f"),
+        }
+    }
+
+    fn list(&self) -> Vec<&'static str> {
+        [
+            (self.sse42, "sse4.2"),
+            (self.avx, "avx"),
+            (self.avx2, "avx2"),
+            (self.bmi2, "bmi2"),
+            (self.adx, "adx"),
+            (self.avx512f, "avx512f"),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+
+    /// Features in `required` this CPU lacks.
+    pub fn missing(&self, required: &CpuFeatures) -> Vec<&'static str> {
+        let available = self.list();
+        required
+            .list()
+            .into_iter()
+            .filter(|feature| !available.contains(feature))
+            .collect()
+    }
+}
+
+impl Display for CpuFeatures {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let features = self.list();
+        if features.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", features.join(" "))
+        }
+    }
+}
+
+/// CPU proving code path, from the most to the least capable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuPath {
+    Avx512,
+    Avx2,
+    Generic,
+}
+
+impl CpuPath {
+    /// Picks the best path both the CPU and the binary support.
+    pub fn select(available: &CpuFeatures) -> Self {
+        if available.avx512f && available.avx2 && available.bmi2 && available.adx {
+            Self::Avx512
+        } else if available.avx2 && available.bmi2 && available.adx {
+            Self::Avx2
+        } else {
+            Self::Generic
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Avx512 => "cpu-avx512",
+            Self::Avx2 => "cpu-avx2",
+            Self::Generic => "cpu-generic",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AVX2: CpuFeatures = CpuFeatures {
+        sse42: true,
+        avx: true,
+        avx2: true,
+        bmi2: true,
+        adx: true,
+        avx512f: false,
+    };
+
+    #[test]
+    fn selects_the_best_supported_path() {
+        let avx512 = CpuFeatures { avx512f: true, ..AVX2 };
+        assert_eq!(CpuPath::select(&avx512), CpuPath::Avx512);
+        assert_eq!(CpuPath::select(&AVX2), CpuPath::Avx2);
+        assert_eq!(CpuPath::select(&CpuFeatures::default()), CpuPath::Generic);
+    }
+
+    #[test]
+    fn falls_back_when_a_companion_feature_is_missing() {
+        assert_eq!(CpuPath::select(&CpuFeatures { adx: false, ..AVX2 }), CpuPath::Generic);
+        assert_eq!(CpuPath::select(&CpuFeatures { bmi2: false, ..AVX2 }), CpuPath::Generic);
+        let without_adx = CpuFeatures {
+            avx512f: true,
+            adx: false,
+            ..AVX2
+        };
+        assert_eq!(CpuPath::select(&without_adx), CpuPath::Generic);
+        let without_avx2 = CpuFeatures {
+            avx512f: true,
+            avx2: false,
+            ..AVX2
+        };
+        assert_eq!(CpuPath::select(&without_avx2), CpuPath::Generic);
+    }
+
+    #[test]
+    fn names_the_missing_features() {
+        let required = CpuFeatures { avx512f: true, ..AVX2 };
+        assert_eq!(AVX2.missing(&required), vec!["avx512f"]);
+        assert_eq!(
+            CpuFeatures::default().missing(&AVX2),
+            vec!["sse4.2", "avx", "avx2", "bmi2", "adx"]
+        );
+        assert!(required.missing(&AVX2).is_empty());
+        assert!(AVX2.missing(&CpuFeatures::default()).is_empty());
+    }
+
+    #[test]
+    fn lists_the_features() {
+        assert_eq!(AVX2.to_string(), "sse4.2 avx avx2 bmi2 adx");
+        assert_eq!(CpuFeatures::default().to_string(), "none");
+        assert_eq!(CpuPath::select(&AVX2).name(), "cpu-avx2");
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+use tracing::error;
+
+use crate::{build_info::BuildInfo, logging::LogBuffer};
+
+/// Where crash reports go and what they include besides the panic.
+struct CrashReporter {
+    dir: PathBuf,
+    logs: Option<LogBuffer>,
+    started: Instant,
+    // Latest status as JSON and when it was taken, the panicking thread may hold the locks needed
+    // to take a new one.
+    status: Option<(Instant, String)>,
+}
+
+static REPORTER: Mutex<Option<CrashReporter>> = Mutex::new(None);
+
+/// Writes a crash report to `dir` when a thread panics, see `exit::set_panic_hook`. `logs` are the
+/// recent log lines shared with the run report.
+pub fn install(dir: PathBuf, logs: Option<LogBuffer>) {
+    *REPORTER.lock().unwrap_or_else(PoisonError::into_inner) = Some(CrashReporter {
+        dir,
+        logs,
+        started: Instant::now(),
+        status: None,
+    });
+}
+
+/// Keeps `status` for the next crash report.
+pub fn record_status(status: String) {
+    if let Some(reporter) = REPORTER.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+        reporter.status = Some((Instant::now(), status));
+    }
+}
+
+/// Directory of `file`, the current directory for a bare file name.
+pub fn dir_of(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Writes the report of a panic at `location` if a reporter is installed, returns its path.
+pub fn report(location: Option<String>, message: &str) -> Option<PathBuf> {
+    let reporter = REPORTER.lock().unwrap_or_else(PoisonError::into_inner);
+    let reporter = reporter.as_ref()?;
+    let now = chrono::Local::now();
+    let path = reporter
+        .dir
+        .join(format!("aleoxminer-crash-{}.txt", now.format("%Y%m%d-%H%M%S")));
+    let mut text = String::new();
+    let _ = writeln!(text, "AleoXMiner crash report, {}", now.to_rfc3339());
+    let _ = writeln!(text, "\n== Panic");
+    let _ = writeln!(text, "thread: {}", std::thread::current().name().unwrap_or("<unnamed>"));
+    let _ = writeln!(text, "location: {}", location.as_deref().unwrap_or("<unknown>"));
+    let _ = writeln!(text, "message: {}", message);
+    let _ = writeln!(text, "\n== Backtrace\n{}", Backtrace::force_capture());
+    let _ = writeln!(text, "== Build\n{}", BuildInfo::current());
+    let _ = writeln!(text, "\n== Uptime\n{}s", reporter.started.elapsed().as_secs());
+    let _ = writeln!(text, "\n== Recent log");
+    for line in reporter.logs.as_ref().map(LogBuffer::lines).unwrap_or_default() {
+        let _ = writeln!(text, "{}", line);
+    }
+    let _ = writeln!(text, "\n== Status");
+    match reporter.status.as_ref() {
+        Some((taken, status)) => {
+            let _ = writeln!(text, "taken {}s before the crash\n{}", taken.elapsed().as_secs(), status);
+        }
+        None => {
+            let _ = writeln!(text, "none taken yet");
+        }
+    }
+    match fs::write(&path, text) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            error!("Unable to write the crash report {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::warn;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{logging::LogLayer, testing};
+
+    #[test]
+    fn writes_the_panic_the_recent_log_and_the_last_status() {
+        assert_eq!(report(None, "no reporter"), None);
+        let dir = testing::scratch("crash", "report");
+        let logs = LogBuffer::new(16);
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(LogLayer::new(logs.clone())), || {
+            warn!("Reject ratio 30.00% of CPU exceeds the threshold");
+        });
+        install(dir.clone(), Some(logs));
+
+        let path = report(Some("src/prover.rs:10:5".to_string()), "worker failed").unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("aleoxminer-crash-") && name.ends_with(".txt"), "{}", name);
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("AleoXMiner crash report, "), "{}", text);
+        let build = format!("\n== Build\n{}\n", BuildInfo::current());
+        for expected in [
+            "\n== Panic\n",
+            "\nlocation: src/prover.rs:10:5\n",
+            "\nmessage: worker failed\n",
+            "\n== Backtrace\n",
+            build.as_str(),
+            "\n== Uptime\n",
+            " WARN Reject ratio 30.00% of CPU exceeds the threshold\n",
+            "\n== Status\nnone taken yet\n",
+        ] {
+            assert!(text.contains(expected), "no {:?} in {}", expected, text);
+        }
+
+        record_status(r#"{"connected":true}"#.to_string());
+        let text = fs::read_to_string(report(None, "worker failed").unwrap()).unwrap();
+        assert!(text.contains("\nlocation: <unknown>\n"), "{}", text);
+        assert!(text.ends_with("\n== Status\ntaken 0s before the crash\n{\"connected\":true}\n"), "{}", text);
+
+        *REPORTER.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn puts_the_reports_next_to_the_given_file() {
+        assert_eq!(dir_of(Path::new("miner.log")), PathBuf::from("."));
+        assert_eq!(dir_of(Path::new("logs/miner.log")), PathBuf::from("logs"));
+        assert_eq!(dir_of(Path::new("/var/log/miner.log")), PathBuf::from("/var/log"));
+    }
+}
@@ -0,0 +1,220 @@
+use std::{env, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use snarkvm::dpc::{testnet2::Testnet2, Address};
+
+const ADDRESS_PREFIX: &str = "aleo1";
+const ADDRESS_LENGTH: usize = 63;
+const MAX_ACCOUNT_LENGTH: usize = 64;
+const MAX_WORKER_LENGTH: usize = 15;
+
+/// Parses an Aleo address, explaining what is wrong with it if it doesn't parse.
+pub fn parse_address(s: &str) -> Result<Address<Testnet2>> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(anyhow!("the address is empty"));
+    }
+    if s.starts_with("APrivateKey1") || s.starts_with("AViewKey1") {
+        return Err(anyhow!(
+            "this is a private or view key, not an address. Never share it, mine to the address derived from it"
+        ));
+    }
+    if !s.starts_with(ADDRESS_PREFIX) {
+        return Err(anyhow!("an Aleo address starts with {}", ADDRESS_PREFIX));
+    }
+    if s.len() != ADDRESS_LENGTH {
+        return Err(anyhow!(
+            "the address is {} characters long instead of {}, check that it was copied completely",
+            s.len(),
+            ADDRESS_LENGTH
+        ));
+    }
+    Address::<Testnet2>::from_str(s).map_err(|e| anyhow!("the address doesn't match its checksum, check for typos: {}", e))
+}
+
+/// Account names are letters, digits, `_`, `-` and `.`.
+pub fn validate_account(s: &str) -> Result<()> {
+    if s.is_empty() {
+        return Err(anyhow!("the account name is empty"));
+    }
+    if s.len() > MAX_ACCOUNT_LENGTH {
+        return Err(anyhow!("the account name cannot exceed {} characters", MAX_ACCOUNT_LENGTH));
+    }
+    match s.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))) {
+        Some(c) => Err(anyhow!(
+            "the account name contains {:?}, only letters, digits, _, - and . are allowed",
+            c
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Account names that are valid but probably not what was meant.
+pub fn account_warning(s: &str) -> Option<String> {
+    if s.starts_with(ADDRESS_PREFIX) {
+        return Some(match parse_address(s) {
+            Ok(_) => format!("account {} is an address, pass it with --address to mine to it", s),
+            Err(e) => format!("account {} looks like an address but isn't a valid one: {}", s, e),
+        });
+    }
+    None
+}
+
+/// Worker names are up to 15 letters, digits, `_` and `-`.
+pub fn validate_worker(s: &str) -> Result<()> {
+    if s.len() > MAX_WORKER_LENGTH {
+        return Err(anyhow!("the worker name cannot exceed {} characters", MAX_WORKER_LENGTH));
+    }
+    match s.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))) {
+        Some(c) => Err(anyhow!(
+            "the worker name contains {:?}, only letters, digits, _ and - are allowed",
+            c
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reads a password file, dropping the trailing newline. Files other users can read are refused.
+pub fn read_password_file(path: &Path) -> Result<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(path)
+            .with_context(|| format!("unable to read password file {}", path.display()))?
+            .permissions()
+            .mode();
+        if mode & 0o004 != 0 {
+            return Err(anyhow!(
+                "password file {} is readable by every user, restrict it with chmod 600",
+                path.display()
+            ));
+        }
+    }
+    let password = fs::read_to_string(path).with_context(|| format!("unable to read password file {}", path.display()))?;
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+#[cfg(feature = "keyring")]
+fn read_keyring(entry: &str) -> Result<String> {
+    let (service, user) = entry
+        .split_once('/')
+        .ok_or_else(|| anyhow!("invalid keyring entry {}, expected service/user", entry))?;
+    keyring::Entry::new(service, user)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| anyhow!("unable to read {} from the keyring: {}", entry, e))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(entry: &str) -> Result<String> {
+    Err(anyhow!(
+        "unable to read {} from the keyring: this build has no keyring support",
+        entry
+    ))
+}
+
+/// Where the pool password comes from, at most one source may be given.
+#[derive(Debug, Clone, Default)]
+pub struct PasswordSources<'a> {
+    pub password: Option<&'a str>,
+    pub file: Option<&'a Path>,
+    /// Name of the environment variable holding it
+    pub env: Option<&'a str>,
+    /// `service/user` entry of the OS keyring
+    pub keyring: Option<&'a str>,
+}
+
+impl PasswordSources<'_> {
+    pub fn resolve(&self) -> Result<Option<String>> {
+        let given = [
+            self.password.is_some(),
+            self.file.is_some(),
+            self.env.is_some(),
+            self.keyring.is_some(),
+        ];
+        if given.iter().filter(|given| **given).count() > 1 {
+            return Err(anyhow!(
+                "use only one of --password, --password-file, --password-env and --password-keyring"
+            ));
+        }
+        if let Some(password) = self.password {
+            return Ok(Some(password.to_string()));
+        }
+        if let Some(path) = self.file {
+            return read_password_file(path).map(Some);
+        }
+        if let Some(name) = self.env {
+            return env::var(name)
+                .map(Some)
+                .map_err(|_| anyhow!("environment variable {} with the password is not set", name));
+        }
+        if let Some(entry) = self.keyring {
+            return read_keyring(entry).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn error(result: Result<impl std::fmt::Debug>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_valid_addresses() {
+        let address = testing::address().to_string();
+        assert_eq!(parse_address(&address).unwrap(), testing::address());
+        assert_eq!(parse_address(&format!(" {}\n", address)).unwrap(), testing::address());
+    }
+
+    #[test]
+    fn explains_invalid_addresses() {
+        let address = testing::address().to_string();
+        assert_eq!(error(parse_address("")), "the address is empty");
+        assert!(error(parse_address(&address[..40])).contains("is 40 characters long instead of 63"));
+        assert!(error(parse_address(&format!("{}q", address))).contains("is 64 characters long instead of 63"));
+        for wrong_prefix in [address.replacen("aleo1", "aleo2", 1), address[5..].to_string()] {
+            assert_eq!(error(parse_address(&wrong_prefix)), "an Aleo address starts with aleo1");
+        }
+        for key in ["APrivateKey1zkp8cC4jgHEBnbtu3xxs1Ndja2EMizcvTRDq5", "AViewKey1iAf6a7fv6ELA4ECwAth1hDNUJJ"] {
+            assert!(error(parse_address(key)).starts_with("this is a private or view key"), "{}", key);
+        }
+
+        let last = if address.ends_with('q') { 'p' } else { 'q' };
+        let typo = format!("{}{}", &address[..address.len() - 1], last);
+        assert!(error(parse_address(&typo)).starts_with("the address doesn't match its checksum"));
+    }
+
+    #[test]
+    fn validates_account_names() {
+        for account in ["pool-account", "miner_1", "user.rig", "a".repeat(64).as_str()] {
+            assert!(validate_account(account).is_ok(), "{}", account);
+        }
+        assert_eq!(error(validate_account("")), "the account name is empty");
+        assert_eq!(error(validate_account(&"a".repeat(65))), "the account name cannot exceed 64 characters");
+        assert!(error(validate_account("pool account")).contains("contains ' '"));
+        assert!(error(validate_account("user@pool")).contains("contains '@'"));
+    }
+
+    #[test]
+    fn warns_about_accounts_looking_like_addresses() {
+        let address = testing::address().to_string();
+        assert!(account_warning(&address).unwrap().contains("pass it with --address"));
+        assert!(account_warning(&address[..40]).unwrap().contains("isn't a valid one"));
+        assert_eq!(account_warning("pool-account"), None);
+    }
+
+    #[test]
+    fn validates_worker_names() {
+        for worker in ["", "rig1", "rig_1-a", "a23456789012345"] {
+            assert!(validate_worker(worker).is_ok(), "{}", worker);
+        }
+        assert_eq!(error(validate_worker("a234567890123456")), "the worker name cannot exceed 15 characters");
+        assert!(error(validate_worker("rig.1")).contains("contains '.'"));
+        assert!(error(validate_worker("rig 1")).contains("contains ' '"));
+    }
+}
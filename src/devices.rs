@@ -0,0 +1,29 @@
+use crate::cpu::{CpuFeatures, CpuPath};
+
+/// Prints the CPU and the GPUs with the indexes taken by `-g`.
+pub fn list() {
+    let features = CpuFeatures::detect();
+    println!(
+        "CPU: {} threads, features {}, code path {}",
+        num_cpus::get(),
+        features,
+        CpuPath::select(&features).name()
+    );
+    #[cfg(feature = "nvml")]
+    match crate::telemetry::NvmlSampler::init() {
+        Some(nvml) => {
+            let gpus = nvml.devices();
+            if gpus.is_empty() {
+                println!("No NVIDIA GPUs found");
+            }
+            for (index, name) in gpus {
+                println!("GPU {}: {} (-g {})", index, name, index);
+            }
+        }
+        None => println!("No NVIDIA driver found"),
+    }
+    #[cfg(all(feature = "cuda", not(feature = "nvml")))]
+    println!("GPUs can only be listed in builds with the nvml feature, select them by CUDA index with -g");
+    #[cfg(not(feature = "cuda"))]
+    println!("GPU proving is not available in this build");
+}
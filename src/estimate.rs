@@ -0,0 +1,198 @@
+use std::{collections::VecDeque, time::Duration};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// User supplied figures to turn share statistics into an estimated yield. No network lookups.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EarningsConfig {
+    /// Coins earned per accepted share
+    pub reward_per_share: Option<f64>,
+    /// Coins earned per day for each proof per second of effective rate
+    pub reward_per_pps_day: Option<f64>,
+}
+
+/// Share difficulty for a pool target, i.e. the expected number of proofs per share.
+pub fn difficulty(target: u64) -> u64 {
+    u64::MAX / target.max(1)
+}
+
+/// Formats a difficulty with an SI suffix, e.g. `412.3 G`.
+pub fn format_difficulty(difficulty: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    let mut value = difficulty as f64;
+    if value < 1000.0 {
+        return difficulty.to_string();
+    }
+    let mut unit = 0;
+    value /= 1000.0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Best share so far formatted with its distance to the network target,
+/// e.g. `412.3 G (0.8% of a block)`.
+pub fn best_share(best_difficulty: u64, network_target: u64) -> String {
+    let block = difficulty(network_target);
+    if block == 0 {
+        return format_difficulty(best_difficulty);
+    }
+    format!(
+        "{} ({:.1}% of a block)",
+        format_difficulty(best_difficulty),
+        best_difficulty as f64 / block as f64 * 100.0
+    )
+}
+
+/// Effective proof rate as credited by the pool: accepted share difficulty per second.
+pub fn effective_rate(accepted_difficulty: u64, elapsed: Duration) -> Option<f64> {
+    if elapsed.is_zero() {
+        return None;
+    }
+    Some(accepted_difficulty as f64 / elapsed.as_secs_f64())
+}
+
+/// Estimated coins per day, preferring the per-share reward when both figures are configured.
+pub fn daily_yield(
+    config: &EarningsConfig,
+    accepted_shares: u32,
+    accepted_difficulty: u64,
+    elapsed: Duration,
+) -> Option<f64> {
+    if elapsed.is_zero() {
+        return None;
+    }
+    if let Some(reward) = config.reward_per_share {
+        let shares_per_day = accepted_shares as f64 / elapsed.as_secs_f64() * SECONDS_PER_DAY;
+        return Some(shares_per_day * reward);
+    }
+    let reward = config.reward_per_pps_day?;
+    Some(effective_rate(accepted_difficulty, elapsed)? * reward)
+}
+
+/// Expected number of proofs until one meets `target`, proof difficulties being uniformly distributed.
+pub fn expected_attempts(target: u64) -> f64 {
+    u64::MAX as f64 / target.max(1) as f64
+}
+
+/// Keeps the effort (attempts relative to the statistical expectation) of the last accepted shares.
+pub struct EffortTracker {
+    history: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl EffortTracker {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            history: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Records an accepted share found after `attempts` proofs and returns its effort (1.0 = 100%).
+    pub fn record(&mut self, attempts: u64, target: u64) -> f64 {
+        let effort = attempts as f64 / expected_attempts(target);
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(effort);
+        effort
+    }
+
+    pub fn last(&self) -> Option<f64> {
+        self.history.back().copied()
+    }
+
+    pub fn average(&self) -> Option<f64> {
+        if self.history.is_empty() {
+            return None;
+        }
+        Some(self.history.iter().sum::<f64>() / self.history.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn difficulties() {
+        assert_eq!(difficulty(u64::MAX), 1);
+        assert_eq!(difficulty(u64::MAX / 1000), 1000);
+        assert_eq!(difficulty(0), u64::MAX);
+    }
+
+    #[test]
+    fn formats_difficulties() {
+        assert_eq!(format_difficulty(999), "999");
+        assert_eq!(format_difficulty(1000), "1.0 K");
+        assert_eq!(format_difficulty(412_300_000_000), "412.3 G");
+        assert_eq!(format_difficulty(u64::MAX), "18.4 E");
+        assert_eq!(best_share(412_300_000_000, u64::MAX / 50_000_000_000_000), "412.3 G (0.8% of a block)");
+    }
+
+    #[test]
+    fn effective_rates() {
+        assert_close(effective_rate(600, Duration::from_secs(60)).unwrap(), 10.0);
+        assert_eq!(effective_rate(600, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn daily_yields() {
+        let hour = Duration::from_secs(3600);
+        let per_share = EarningsConfig {
+            reward_per_share: Some(0.5),
+            reward_per_pps_day: None,
+        };
+        let per_pps = EarningsConfig {
+            reward_per_share: None,
+            reward_per_pps_day: Some(2.0),
+        };
+        // 10 shares an hour are 240 a day, 3600 difficulty an hour is 1 proof per second.
+        assert_close(daily_yield(&per_share, 10, 3600, hour).unwrap(), 120.0);
+        assert_close(daily_yield(&per_pps, 10, 3600, hour).unwrap(), 2.0);
+        let both = EarningsConfig {
+            reward_per_share: Some(0.5),
+            reward_per_pps_day: Some(2.0),
+        };
+        assert_close(daily_yield(&both, 10, 3600, hour).unwrap(), 120.0);
+        assert_eq!(daily_yield(&EarningsConfig::default(), 10, 3600, hour), None);
+        assert_eq!(daily_yield(&per_share, 10, 3600, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn expected_attempts_of_targets() {
+        // A target of 2^62 is met by a quarter of the proofs, 2^57 by one in 128.
+        assert_close(expected_attempts(1 << 62), 4.0);
+        assert_close(expected_attempts(1 << 57), 128.0);
+        assert_close(expected_attempts(u64::MAX), 1.0);
+        assert_close(expected_attempts(0), u64::MAX as f64);
+    }
+
+    #[test]
+    fn tracks_the_effort_of_the_last_shares() {
+        let mut effort = EffortTracker::new(2);
+        assert_eq!((effort.last(), effort.average()), (None, None));
+        assert_close(effort.record(64, 1 << 57), 0.5);
+        assert_close(effort.record(192, 1 << 57), 1.5);
+        assert_close(effort.average().unwrap(), 1.0);
+        // The oldest effort leaves the history.
+        assert_close(effort.record(4, 1 << 62), 1.0);
+        assert_close(effort.last().unwrap(), 1.0);
+        assert_close(effort.average().unwrap(), 1.25);
+    }
+
+    #[test]
+    fn keeps_at_least_one_effort() {
+        let mut effort = EffortTracker::new(0);
+        effort.record(64, 1 << 57);
+        effort.record(256, 1 << 57);
+        assert_close(effort.average().unwrap(), 2.0);
+    }
+}
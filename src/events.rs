@@ -0,0 +1,88 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    channel::{ChannelMetrics, ChannelStats},
+    message::Code,
+};
+
+// Subscribers falling further behind than this miss the oldest events.
+const CAPACITY: usize = 1024;
+
+/// Result of a submitted share.
+#[derive(Debug, Clone)]
+pub struct ShareResult {
+    pub pool: String,
+    pub worker: String,
+    pub height: u32,
+    /// Little endian nonce bytes in hex
+    pub nonce: Option<String>,
+    pub code: Code,
+    /// Reason given by the pool
+    pub message: Option<String>,
+    /// Time from submitting to receiving the result
+    pub latency: Option<Duration>,
+    /// Share difficulty from the pool target
+    pub difficulty: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MinerEvent {
+    Connected { server: String, worker: String },
+    Disconnected { server: String, worker: String, reason: String },
+    Authorized { worker: String },
+    NewJob { height: u32, target: u64 },
+    ShareSubmitted { worker: String, height: u32 },
+    ShareAccepted(ShareResult),
+    /// Rejected, stale or failed at the proxy
+    ShareRejected(ShareResult),
+    /// (window in minutes, proofs per second), published with the periodic stats
+    RateSample { proof_rates: Vec<(u32, Option<f64>)> },
+    DeviceError { device: String, error: String, disabled: bool },
+    /// A user defined alert threshold was breached or is back to normal
+    Threshold { alert: &'static str, details: String, raised: bool },
+    /// The pool told the miner about its account, e.g. the unpaid balance
+    PoolInfo { server: String, fields: BTreeMap<String, String> },
+}
+
+/// Broadcasts miner events to any number of subscribers. Publishing never waits,
+/// subscribers that can't keep up lag behind and skip events instead.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<MinerEvent>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let metrics = ChannelMetrics::new("events", CAPACITY);
+        Self { sender, metrics }
+    }
+
+    pub fn publish(&self, event: MinerEvent) {
+        self.metrics.record_send(Duration::ZERO);
+        // Failing only means nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+
+    /// Counts the events a lagging subscriber missed as overflows.
+    pub fn record_lag(&self, missed: u64) {
+        self.metrics.record_overflow(missed);
+    }
+
+    pub fn channel_stats(&self) -> ChannelStats {
+        self.metrics.stats()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MinerEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    cell::Cell,
+    fmt::{self, Display},
+};
+
+use tracing::error;
+
+use crate::crash;
+
+/// Process exit codes, kept stable for scripts and service managers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Clean shutdown
+    Success = 0,
+    /// Anything not covered below
+    Error = 1,
+    /// Invalid configuration, options or environment
+    Config = 2,
+    /// The pool keeps rejecting the credentials
+    Auth = 3,
+    /// No usable devices, or the self-test failed
+    Devices = 4,
+    /// The pool stayed unreachable longer than allowed
+    PoolUnreachable = 10,
+    /// Internal error (panic)
+    Internal = 70,
+}
+
+impl ExitCode {
+    pub fn code(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Success => "success",
+            Self::Error => "error",
+            Self::Config => "configuration error",
+            Self::Auth => "authorization rejected",
+            Self::Devices => "no usable devices",
+            Self::PoolUnreachable => "pool unreachable",
+            Self::Internal => "internal error",
+        };
+        write!(f, "{} ({})", self.code(), name)
+    }
+}
+
+/// Logs why the process stops and exits with `code`. Every early exit goes through here,
+/// before logging is set up the reason goes to stderr.
+pub fn exit(code: ExitCode, reason: impl Display) -> ! {
+    if tracing::dispatcher::has_been_set() {
+        error!("{}", reason);
+        error!("Exiting with code {}", code);
+    } else {
+        eprintln!("{}", reason);
+    }
+    std::process::exit(code.code())
+}
+
+thread_local! {
+    static SUPERVISED: Cell<bool> = Cell::new(false);
+}
+
+/// Runs `f` as a supervised worker: a panic inside unwinds to the supervisor, which restarts it,
+/// instead of exiting the process.
+pub fn supervised<T>(f: impl FnOnce() -> T) -> T {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SUPERVISED.with(|supervised| supervised.set(self.0));
+        }
+    }
+    let _restore = Restore(SUPERVISED.with(|supervised| supervised.replace(true)));
+    f()
+}
+
+/// Whether a panic on the current thread ends the process: only on the main thread, which sets up
+/// the runtime and runs the command, and outside `supervised`. Tasks on the runtime's worker threads
+/// are restarted or logged by whoever spawned them.
+fn fatal() -> bool {
+    std::thread::current().name() == Some("main") && !SUPERVISED.with(Cell::get)
+}
+
+/// Exits with the internal error code when the main thread panics, a process that can't run its
+/// command is worse than a restart. Every panic gets a crash report once `crash::install` was called.
+pub fn set_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "<no message>".to_string());
+        if let Some(path) = crash::report(info.location().map(|location| location.to_string()), &message) {
+            error!("Crash report written to {}", path.display());
+        }
+        if fatal() {
+            std::process::exit(ExitCode::Internal.code());
+        }
+        let thread = std::thread::current();
+        error!("Thread {} panicked: {}", thread.name().unwrap_or("<unnamed>"), message);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, thread};
+
+    use super::*;
+
+    #[test]
+    fn supervised_marks_only_its_scope() {
+        assert!(!SUPERVISED.with(Cell::get));
+        assert!(supervised(|| SUPERVISED.with(Cell::get)));
+        assert!(supervised(|| supervised(|| true) && SUPERVISED.with(Cell::get)));
+        assert!(!SUPERVISED.with(Cell::get));
+    }
+
+    #[test]
+    fn supervised_unmarks_after_a_panic() {
+        let result = panic::catch_unwind(|| supervised(|| panic!("worker failed")));
+        assert!(result.is_err());
+        assert!(!SUPERVISED.with(Cell::get));
+    }
+
+    #[test]
+    fn only_the_main_thread_is_fatal() {
+        let named = |name: &str| thread::Builder::new().name(name.to_string());
+        assert!(named("main").spawn(fatal).unwrap().join().unwrap());
+        assert!(!named("main").spawn(|| supervised(fatal)).unwrap().join().unwrap());
+        assert!(!named("tokio-runtime-worker").spawn(fatal).unwrap().join().unwrap());
+        assert!(!thread::spawn(fatal).join().unwrap());
+    }
+
+    #[test]
+    fn codes_are_stable() {
+        assert_eq!(ExitCode::Config.code(), 2);
+        assert_eq!(ExitCode::PoolUnreachable.code(), 10);
+        assert_eq!(ExitCode::Internal.to_string(), "70 (internal error)");
+    }
+}
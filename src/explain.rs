@@ -0,0 +1,350 @@
+use std::{
+    io::{self, Cursor, Read},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use crate::message::{ProtocolError, ProverMessage, MSGPACK_FLAG};
+
+// Bytes shown before and after the offset where decoding stopped.
+const CONTEXT: usize = 32;
+
+#[derive(Debug, StructOpt)]
+pub struct DecodeFrame {
+    /// File with one or more frames, hex or raw bytes, - for stdin
+    #[structopt(value_name = "FILE", default_value = "-")]
+    input: PathBuf,
+    /// Treat the input as raw bytes even if it looks like hex
+    #[structopt(long = "raw")]
+    raw: bool,
+}
+
+pub fn run(options: DecodeFrame) -> Result<()> {
+    let mut input = Vec::new();
+    if options.input.as_os_str() == "-" {
+        io::stdin().read_to_end(&mut input)?;
+    } else {
+        input = std::fs::read(&options.input).with_context(|| format!("unable to read {}", options.input.display()))?;
+    }
+    let bytes = if options.raw { input } else { from_hex(&input).unwrap_or(input) };
+    print!("{}", explain(&bytes));
+    Ok(())
+}
+
+/// Decodes hex, ignoring whitespace, an optional 0x prefix and `:`/`-` separators. `None` if this
+/// isn't hex.
+fn from_hex(input: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(input).ok()?.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace() && *byte != b':' && *byte != b'-')
+        .collect();
+    if digits.is_empty() || digits.len() % 2 != 0 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn fields(message: &ProverMessage) -> Vec<(&'static str, String)> {
+    match message {
+        ProverMessage::Authorize(account, worker, password, version) => vec![
+            ("account", account.clone()),
+            ("worker", worker.clone()),
+            ("password", format!("<{} bytes, masked>", password.expose().len())),
+            ("version", version.to_string()),
+        ],
+        ProverMessage::AuthorizeResult(result, message) => {
+            vec![("result", result.to_string()), ("message", format!("{:?}", message))]
+        }
+        ProverMessage::Notify(template, target, reorg, speculative) => vec![
+            ("height", template.block_height().to_string()),
+            ("previous block", template.previous_block_hash().to_string()),
+            ("timestamp", template.block_timestamp().to_string()),
+            ("difficulty", template.difficulty_target().to_string()),
+            ("pool target", target.to_string()),
+            ("reorg", reorg.to_string()),
+            ("speculative", speculative.to_string()),
+        ],
+        ProverMessage::Submit(height, nonce, _) => {
+            vec![("height", height.to_string()), ("nonce", nonce.to_string())]
+        }
+        ProverMessage::SubmitResult(code, message) => {
+            vec![("code", format!("{:?}", code)), ("message", format!("{:?}", message))]
+        }
+        ProverMessage::ProofRate(rate) => vec![("proof rate", format!("{} ({:.2} p/s)", rate, *rate as f64 / 100.0))],
+        ProverMessage::ActivateJob(height) => vec![("height", height.to_string())],
+        ProverMessage::JobAck(height, target) => {
+            vec![("height", height.to_string()), ("pool target", target.to_string())]
+        }
+        ProverMessage::PoolInfo(fields) => fields
+            .iter()
+            .map(|(key, value)| ("field", format!("{} = {}", key, value)))
+            .collect(),
+        ProverMessage::Worker(worker) => vec![("worker", worker.clone())],
+        ProverMessage::Canary => vec![],
+    }
+}
+
+/// 16 bytes per line with the offset and the printable characters, `mark` is bracketed.
+fn hexdump(bytes: &[u8], start: usize, mark: Option<usize>) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let offset = start + line * 16;
+        let hex: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| match mark {
+                Some(mark) if mark == offset + index => format!("[{:02x}]", byte),
+                _ => format!(" {:02x} ", byte),
+            })
+            .collect();
+        let text: String = chunk
+            .iter()
+            .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+            .collect();
+        out.push_str(&format!("    {:08x} {:<64} |{}|\n", offset, hex.join(""), text));
+    }
+    out
+}
+
+fn attempt(
+    out: &mut String,
+    format: &str,
+    payload: &[u8],
+    parse: fn(&mut Cursor<&[u8]>) -> Result<ProverMessage, ProtocolError>,
+) {
+    let mut cursor = Cursor::new(payload);
+    match parse(&mut cursor) {
+        Ok(message) => {
+            out.push_str(&format!("  {}: {}\n", format, message.name()));
+            for (name, value) in fields(&message) {
+                out.push_str(&format!("    {:<15} {}\n", name, value));
+            }
+            let rest = payload.len() as u64 - cursor.position().min(payload.len() as u64);
+            if rest > 0 {
+                out.push_str(&format!("    ({} trailing bytes not read)\n", rest));
+            }
+        }
+        Err(e) => {
+            // The cursor stops where the deserializer gave up.
+            let stopped = (cursor.position() as usize).min(payload.len());
+            out.push_str(&format!("  {}: failed at payload offset {}: {}\n", format, stopped, e));
+            let from = stopped.saturating_sub(CONTEXT) & !15;
+            let to = (stopped + CONTEXT).min(payload.len());
+            out.push_str(&hexdump(&payload[from..to], from, Some(stopped.min(payload.len().saturating_sub(1)))));
+        }
+    }
+}
+
+/// Explains every frame in `bytes` using the decoder's own deserializers.
+pub fn explain(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+    let mut index = 0;
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        if rest.len() < 4 {
+            out.push_str(&format!(
+                "Frame {} at byte {}: {} bytes left, too short for a length prefix\n",
+                index,
+                offset,
+                rest.len()
+            ));
+            out.push_str(&hexdump(rest, offset, None));
+            break;
+        }
+        let length = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if length == 0 {
+            out.push_str(&format!("Frame {} at byte {}: empty frame, the decoder rejects it\n", index, offset));
+            offset += 4;
+            index += 1;
+            continue;
+        }
+        let available = rest.len() - 4;
+        let payload = &rest[4..4 + length.min(available)];
+        let id = match payload.first() {
+            Some(id) => *id,
+            None => {
+                out.push_str(&format!("Frame {} at byte {}: {} bytes announced, none present\n", index, offset, length));
+                break;
+            }
+        };
+        // Which deserializer the decoder uses for this id.
+        let msgpack = id & MSGPACK_FLAG != 0;
+        let used = match id {
+            _ if msgpack => "msgpack",
+            4 | 6 => "binary",
+            _ => "json",
+        };
+        out.push_str(&format!(
+            "Frame {} at byte {}: {} bytes, id {} ({}), decoded as {}\n",
+            index,
+            offset,
+            length,
+            id,
+            ProverMessage::name_of(id & !MSGPACK_FLAG),
+            used
+        ));
+        if available < length {
+            out.push_str(&format!("  truncated: only {} of {} payload bytes present\n", available, length));
+        }
+        if msgpack {
+            attempt(&mut out, "msgpack", payload, |cursor| ProverMessage::deserialize_msgpack(cursor));
+        } else {
+            attempt(&mut out, "binary", payload, |cursor| ProverMessage::deserialize(cursor));
+            attempt(&mut out, "json", payload, |cursor| ProverMessage::deserialize_json(cursor));
+        }
+        if available < length {
+            break;
+        }
+        offset += 4 + length;
+        index += 1;
+    }
+    if bytes.is_empty() {
+        out.push_str("No input\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use super::*;
+    use crate::testing;
+
+    // (name, format, id and fields) of the golden vectors.
+    fn vectors() -> Vec<(String, String, Vec<u8>)> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden.txt");
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.split(' ').collect::<Vec<_>>()[..] {
+                [name, format, hex] => (name.to_string(), format.to_string(), from_hex(hex.as_bytes()).unwrap()),
+                _ => panic!("invalid golden vector {}", line),
+            })
+            .collect()
+    }
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    // The format the decoder reads a non-MessagePack `id` in.
+    fn decoded_as(id: u8) -> &'static str {
+        if matches!(id, 4 | 6) {
+            "binary"
+        } else {
+            "json"
+        }
+    }
+
+    #[test]
+    fn explains_every_golden_vector() {
+        let messages: HashMap<_, _> = testing::messages().into_iter().collect();
+        let vectors = vectors();
+        assert!(!vectors.is_empty());
+        for (name, format, payload) in vectors {
+            let message = &messages[name.as_str()];
+            let id = payload[0];
+            let used = if id & MSGPACK_FLAG != 0 { "msgpack" } else { decoded_as(id) };
+            let out = explain(&frame(&payload));
+            let header = format!(
+                "Frame 0 at byte 0: {} bytes, id {} ({}), decoded as {}\n",
+                payload.len(),
+                id,
+                message.name(),
+                used
+            );
+            assert!(out.starts_with(&header), "{} {}:\n{}", name, format, out);
+            let decoded = format!("\n  {}: {}\n", format, message.name());
+            assert!(out.contains(&decoded), "{} {}:\n{}", name, format, out);
+            for (field, value) in fields(message) {
+                assert!(out.contains(&format!("    {:<15} {}\n", field, value)), "{} {}: no {}", name, format, field);
+            }
+        }
+    }
+
+    #[test]
+    fn masks_the_password() {
+        let vectors = vectors();
+        let (_, _, payload) = vectors.iter().find(|(name, format, _)| name == "authorize" && format == "json").unwrap();
+        let out = explain(&frame(payload));
+        assert!(out.contains("    password        <6 bytes, masked>\n"), "{}", out);
+        assert!(!out.contains("x;d=64"), "{}", out);
+    }
+
+    #[test]
+    fn explains_concatenated_frames_in_hex() {
+        let vectors = vectors();
+        let used: Vec<_> = vectors
+            .iter()
+            .filter(|(_, format, payload)| format == decoded_as(payload[0]))
+            .map(|(_, _, payload)| frame(payload))
+            .collect();
+        let hex: Vec<String> = used.concat().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let input = format!("0x{}\n", hex.join(" "));
+        let out = explain(&from_hex(input.as_bytes()).unwrap());
+        let mut offset = 0;
+        for (index, frame) in used.iter().enumerate() {
+            assert!(out.contains(&format!("Frame {} at byte {}: {} bytes", index, offset, frame.len() - 4)), "{}", out);
+            offset += frame.len();
+        }
+        assert!(!out.contains("failed"), "{}", out);
+    }
+
+    #[test]
+    fn points_at_where_a_corrupted_frame_stopped() {
+        let vectors = vectors();
+        let payload = |wanted: &str| {
+            let (_, _, payload) = vectors.iter().find(|(name, format, _)| name == wanted && format == "json").unwrap();
+            payload.clone()
+        };
+        // `["account";` instead of `["account",`.
+        let mut corrupted = payload("authorize");
+        assert_eq!(corrupted[11], b',');
+        corrupted[11] = b';';
+        let input = [frame(&payload("authorize_result")), frame(&corrupted), frame(&payload("canary"))].concat();
+        let out = explain(&input);
+
+        let frames: Vec<&str> = out.split("Frame ").skip(1).collect();
+        assert_eq!(frames.len(), 3, "{}", out);
+        assert!(frames[0].contains("\n  json: AuthorizeResult\n"), "{}", out);
+        assert!(frames[2].contains("\n  json: Canary\n"), "{}", out);
+
+        let failure = frames[1].lines().find(|line| line.starts_with("  json: failed at payload offset ")).unwrap();
+        let stopped = failure.trim_start_matches("  json: failed at payload offset ").split(':').next().unwrap();
+        let stopped: usize = stopped.parse().unwrap();
+        assert!((11..=12).contains(&stopped), "{}", failure);
+        // The hexdump around it, with the stopping byte bracketed.
+        let dump: Vec<&str> = frames[1].lines().filter(|line| line.starts_with("    0000")).collect();
+        assert!(dump.iter().any(|line| line.contains(" 3b ") || line.contains("[3b]")), "{}", frames[1]);
+        assert!(dump.iter().any(|line| line.contains(&format!("[{:02x}]", corrupted[stopped]))), "{}", frames[1]);
+    }
+
+    #[test]
+    fn reports_truncated_and_empty_frames() {
+        let mut input = vec![0, 0, 0, 0];
+        input.extend_from_slice(&frame(&[5]));
+        input.extend_from_slice(&[10, 0, 0, 0, 7, 7]);
+        let out = explain(&input);
+        assert!(out.contains("Frame 0 at byte 0: empty frame, the decoder rejects it\n"), "{}", out);
+        assert!(out.contains("Frame 1 at byte 4: 1 bytes, id 5 (Canary), decoded as json\n"), "{}", out);
+        assert!(out.contains("Frame 2 at byte 9: 10 bytes, id 7 (ActivateJob), decoded as json\n"), "{}", out);
+        assert!(out.contains("  truncated: only 2 of 10 payload bytes present\n"), "{}", out);
+        let out = explain(&[1, 0]);
+        let short = "Frame 0 at byte 0: 2 bytes left, too short for a length prefix\n";
+        assert!(out.starts_with(&format!("{}    00000000  01  00 ", short)), "{}", out);
+        assert!(out.ends_with(" |..|\n"), "{}", out);
+        assert_eq!(explain(&[]), "No input\n");
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::AtomicBool;
+
+use anyhow::Result;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use snarkvm::dpc::{testnet2::Testnet2, BlockHeader, BlockTemplate};
+
+use crate::backend::ProvingBackend;
+
+/// Offloads the MSM and FFT parts of the proof to a CUDA device through snarkVM's CUDA kernels.
+#[derive(Debug, Clone, Copy)]
+pub struct CudaBackend {
+    device: i16,
+}
+
+impl CudaBackend {
+    pub fn new(device: i16) -> Self {
+        Self { device }
+    }
+}
+
+impl ProvingBackend for CudaBackend {
+    fn name(&self) -> String {
+        format!("GPU {}", self.device)
+    }
+
+    fn prove(
+        &self,
+        template: &BlockTemplate<Testnet2>,
+        terminator: &AtomicBool,
+        rng: &mut dyn RngCore,
+    ) -> Result<BlockHeader<Testnet2>> {
+        let mut rng = ChaChaRng::from_rng(rng)?;
+        BlockHeader::mine_once_unchecked(template, terminator, &mut rng, self.device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selftest;
+
+    // GPUs the NVIDIA driver lists.
+    fn devices() -> Vec<i16> {
+        std::fs::read_dir("/proc/driver/nvidia/gpus")
+            .map(|entries| (0..entries.count() as i16).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    #[ignore = "needs an NVIDIA GPU and loads the proving parameters"]
+    fn proves_the_fixture_on_every_gpu() {
+        let devices = devices();
+        assert!(!devices.is_empty(), "no GPU found");
+        for device in devices {
+            selftest::prove_fixture(device, 2).unwrap_or_else(|e| panic!("GPU {}: {:#}", device, e));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+
+/// A pool worker name and the GPUs whose shares are submitted under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerGroup {
+    pub name: String,
+    pub devices: Vec<i16>,
+}
+
+impl FromStr for WorkerGroup {
+    type Err = Error;
+
+    /// Parses `rig1_a=0,1,2,3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, devices) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid worker group {}: expected NAME=GPU,GPU,...", s))?;
+        let name = name.trim().to_string();
+        if name.is_empty() || name.len() > 15 {
+            return Err(anyhow!("invalid worker group {}: the name must be 1 to 15 characters", s));
+        }
+        let devices = devices
+            .split(',')
+            .map(|device| {
+                device
+                    .trim()
+                    .parse::<i16>()
+                    .ok()
+                    .filter(|device| *device >= 0)
+                    .ok_or_else(|| anyhow!("invalid worker group {}: bad GPU index {}", s, device))
+            })
+            .collect::<Result<Vec<i16>>>()?;
+        Ok(Self { name, devices })
+    }
+}
+
+/// Checks that every grouped device is in use and that no device belongs to two groups.
+pub fn validate(groups: &[WorkerGroup], devices: &[i16]) -> Result<()> {
+    let mut assigned = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        if groups[..index].iter().any(|other| other.name == group.name) {
+            return Err(anyhow!("Worker group {} is defined twice", group.name));
+        }
+        for device in group.devices.iter() {
+            if !devices.contains(device) {
+                return Err(anyhow!("GPU {} of worker group {} is not in use", device, group.name));
+            }
+            if let Some(other) = assigned.insert(*device, &group.name) {
+                return Err(anyhow!(
+                    "GPU {} is assigned to both worker groups {} and {}",
+                    device,
+                    other,
+                    group.name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(name: &str, devices: &[i16]) -> WorkerGroup {
+        WorkerGroup {
+            name: name.to_string(),
+            devices: devices.to_vec(),
+        }
+    }
+
+    #[test]
+    fn parses_groups() {
+        assert_eq!("rig1_a=0,1, 2".parse::<WorkerGroup>().unwrap(), group("rig1_a", &[0, 1, 2]));
+        assert!("rig1_a".parse::<WorkerGroup>().is_err());
+        assert!("=0".parse::<WorkerGroup>().is_err());
+        assert!("a_very_long_worker=0".parse::<WorkerGroup>().is_err());
+        assert!("rig=0,-1".parse::<WorkerGroup>().is_err());
+        assert!("rig=0,gpu".parse::<WorkerGroup>().is_err());
+    }
+
+    #[test]
+    fn accepts_disjoint_groups() {
+        let groups = [group("a", &[0, 1]), group("b", &[2])];
+        assert!(validate(&groups, &[0, 1, 2, 3]).is_ok());
+        assert!(validate(&[], &[]).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_device_in_two_groups() {
+        let error = validate(&[group("a", &[0, 1]), group("b", &[1, 2])], &[0, 1, 2]).unwrap_err();
+        assert_eq!(error.to_string(), "GPU 1 is assigned to both worker groups a and b");
+    }
+
+    #[test]
+    fn rejects_unused_devices_and_duplicate_names() {
+        assert!(validate(&[group("a", &[4])], &[0, 1]).is_err());
+        assert!(validate(&[group("a", &[0]), group("a", &[1])], &[0, 1]).is_err());
+    }
+}
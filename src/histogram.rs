@@ -0,0 +1,271 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+// Log-linear buckets: 16 linear sub-buckets per power of two, about 6% relative precision.
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+fn index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let magnitude = 63 - value.leading_zeros();
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub = (value >> shift) as usize & (SUB_BUCKETS - 1);
+    (shift as usize + 1) * SUB_BUCKETS + sub
+}
+
+fn lower_bound(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    let sub = index % SUB_BUCKETS;
+    ((SUB_BUCKETS + sub) as u64) << shift
+}
+
+/// Lock-free latency histogram with microsecond resolution.
+pub struct LatencyHistogram {
+    counts: Vec<AtomicU64>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        self.counts[index(micros)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            counts: self.counts.iter().map(|count| count.load(Ordering::Relaxed)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistogramSnapshot {
+    counts: Vec<u64>,
+}
+
+impl Default for HistogramSnapshot {
+    fn default() -> Self {
+        Self { counts: vec![0; BUCKETS] }
+    }
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn merge(&mut self, other: &HistogramSnapshot) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+
+    /// Samples recorded since `earlier` was taken from the same histogram.
+    pub fn delta(&self, earlier: &HistogramSnapshot) -> HistogramSnapshot {
+        HistogramSnapshot {
+            counts: self
+                .counts
+                .iter()
+                .zip(earlier.counts.iter())
+                .map(|(now, past)| now.saturating_sub(*past))
+                .collect(),
+        }
+    }
+
+    /// Returns the latency below which `quantile` (0.0 - 1.0) of the samples fall.
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let target = ((quantile.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(Duration::from_micros(lower_bound(index)));
+            }
+        }
+        None
+    }
+
+    /// Number of samples at or below `bound`, within the bucket precision.
+    pub fn count_below(&self, bound: Duration) -> u64 {
+        let bound = bound.as_micros().min(u64::MAX as u128) as u64;
+        self.counts
+            .iter()
+            .enumerate()
+            .take_while(|(index, _)| lower_bound(*index) <= bound)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Approximate sum of all samples, taking the lower bound of each bucket.
+    pub fn sum(&self) -> Duration {
+        let micros = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| lower_bound(index).saturating_mul(*count))
+            .fold(0u64, |sum, value| sum.saturating_add(value));
+        Duration::from_micros(micros)
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count(),
+            p50: self.percentile(0.5),
+            p90: self.percentile(0.9),
+            p99: self.percentile(0.99),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50: Option<Duration>,
+    pub p90: Option<Duration>,
+    pub p99: Option<Duration>,
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn ms(duration: Option<Duration>) -> String {
+            match duration {
+                Some(duration) => format!("{}ms", duration.as_millis()),
+                None => "---".to_string(),
+            }
+        }
+        write!(f, "p50: {}, p90: {}, p99: {}", ms(self.p50), ms(self.p90), ms(self.p99))
+    }
+}
+
+/// Returns the workers whose median latency drifts more than `threshold` percent from the fleet median,
+/// together with their drift in percent.
+pub fn drifting_workers(workers: &[HistogramSnapshot], threshold: f64) -> Vec<(usize, Duration, f64)> {
+    let mut fleet = HistogramSnapshot::default();
+    for worker in workers {
+        fleet.merge(worker);
+    }
+    let fleet_median = match fleet.percentile(0.5) {
+        Some(median) if !median.is_zero() => median,
+        _ => return vec![],
+    };
+    workers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, worker)| {
+            let median = worker.percentile(0.5)?;
+            let drift = (median.as_secs_f64() - fleet_median.as_secs_f64()).abs() / fleet_median.as_secs_f64() * 100.0;
+            if drift > threshold {
+                Some((index, median, drift))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn histogram(samples: impl IntoIterator<Item = Duration>) -> HistogramSnapshot {
+        let histogram = LatencyHistogram::default();
+        for sample in samples {
+            histogram.record(sample);
+        }
+        histogram.snapshot()
+    }
+
+    #[test]
+    fn buckets_keep_the_precision() {
+        for value in (0..16).chain((16..1 << 20).step_by(997)).chain([u64::MAX / 3, u64::MAX]) {
+            let bound = lower_bound(index(value));
+            assert!(bound <= value, "{} in a bucket from {}", value, bound);
+            assert!(value - bound <= value / SUB_BUCKETS as u64, "{} in a bucket from {}", value, bound);
+        }
+        assert!(index(u64::MAX) < BUCKETS);
+    }
+
+    #[test]
+    fn small_values_are_exact() {
+        let snapshot = histogram((1..=10).map(Duration::from_micros));
+        assert_eq!(snapshot.count(), 10);
+        assert_eq!(snapshot.percentile(0.0), Some(Duration::from_micros(1)));
+        assert_eq!(snapshot.percentile(0.5), Some(Duration::from_micros(5)));
+        assert_eq!(snapshot.percentile(0.51), Some(Duration::from_micros(6)));
+        assert_eq!(snapshot.percentile(1.0), Some(Duration::from_micros(10)));
+        assert_eq!(snapshot.sum(), Duration::from_micros(55));
+    }
+
+    #[test]
+    fn percentiles_of_a_spread() {
+        let snapshot = histogram((1..=1000).map(ms));
+        for (quantile, expected) in [(0.5, 500.0), (0.9, 900.0), (0.99, 990.0)] {
+            let percentile = snapshot.percentile(quantile).unwrap().as_secs_f64() * 1000.0;
+            assert!(percentile <= expected && percentile > expected * 0.93, "p{} {}ms", quantile * 100.0, percentile);
+        }
+        assert_eq!(snapshot.count_below(Duration::ZERO), 0);
+        assert_eq!(snapshot.count_below(ms(2000)), 1000);
+    }
+
+    #[test]
+    fn empty_histograms_have_no_percentiles() {
+        let snapshot = HistogramSnapshot::default();
+        assert_eq!(snapshot.percentile(0.5), None);
+        assert_eq!(snapshot.summary().to_string(), "p50: ---, p90: ---, p99: ---");
+    }
+
+    #[test]
+    fn deltas_hold_the_samples_since_the_snapshot() {
+        let histogram = LatencyHistogram::default();
+        histogram.record(ms(10));
+        let earlier = histogram.snapshot();
+        histogram.record(ms(20));
+        histogram.record(ms(20));
+        let delta = histogram.snapshot().delta(&earlier);
+        assert_eq!(delta.count(), 2);
+        assert_eq!(delta.percentile(0.0), delta.percentile(1.0));
+
+        let mut merged = earlier.clone();
+        merged.merge(&delta);
+        assert_eq!(merged.count(), 3);
+    }
+
+    #[test]
+    fn finds_the_drifting_worker() {
+        let steady = || histogram((0..100).map(|_| ms(10)));
+        let slow = histogram((0..100).map(|_| ms(20)));
+        let drifting = drifting_workers(&[steady(), steady(), steady(), slow], 25.0);
+        assert_eq!(drifting.len(), 1);
+        let (worker, median, drift) = drifting[0];
+        assert_eq!(worker, 3);
+        assert!(median > ms(18) && median <= ms(20));
+        assert!((drift - 100.0).abs() < 7.0, "{}", drift);
+
+        assert!(drifting_workers(&[steady(), steady()], 25.0).is_empty());
+        assert!(drifting_workers(&[steady(), histogram([ms(11)])], 25.0).is_empty());
+        assert!(drifting_workers(&[], 25.0).is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{Method, StatusCode};
+use serde::Serialize;
+use tokio::{task, time::sleep};
+
+use crate::{
+    http::{self, Handler},
+    prover::Prover,
+    schedule,
+    status::json,
+};
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+// Rates are averaged over this many sample intervals (one minute).
+const RATE_SAMPLES: usize = 4;
+/// Points in the sparkline included in the status.
+pub const SPARKLINE_POINTS: usize = 60;
+const SPARKLINE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// Unix timestamp
+    pub timestamp: u64,
+    /// Proofs per second over the last minute
+    pub rate: Option<f64>,
+    pub device_rates: Vec<Option<f64>>,
+}
+
+/// Hashrate samples for the retention period. Memory use is bounded by the number of samples
+/// (retention / 15s, 960 for the default 4h) times about 8 bytes per device plus 32 bytes.
+pub struct History {
+    samples: Mutex<VecDeque<Sample>>,
+    capacity: usize,
+    devices: Mutex<Vec<String>>,
+}
+
+/// Response of `GET /history`, one array entry per sample.
+#[derive(Debug, Serialize)]
+pub struct HistoryResponse {
+    pub interval: u64,
+    pub timestamps: Vec<u64>,
+    pub rate: Vec<Option<f64>>,
+    pub devices: Vec<DeviceHistory>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceHistory {
+    pub name: String,
+    pub rate: Vec<Option<f64>>,
+}
+
+impl History {
+    pub fn new(retention: Duration) -> Self {
+        let capacity = ((retention.as_secs() / SAMPLE_INTERVAL.as_secs()) as usize).max(1);
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            devices: Default::default(),
+        }
+    }
+
+    pub fn push(&self, sample: Sample) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Samples taken in the last `window`.
+    pub fn window(&self, window: Duration) -> Vec<Sample> {
+        let count = (window.as_secs() / SAMPLE_INTERVAL.as_secs()) as usize;
+        let samples = self.samples.lock().unwrap();
+        samples.iter().skip(samples.len().saturating_sub(count)).cloned().collect()
+    }
+
+    pub fn query(&self, window: Duration) -> HistoryResponse {
+        let samples = self.window(window);
+        let devices = self.devices.lock().unwrap();
+        HistoryResponse {
+            interval: SAMPLE_INTERVAL.as_secs(),
+            timestamps: samples.iter().map(|sample| sample.timestamp).collect(),
+            rate: samples.iter().map(|sample| sample.rate).collect(),
+            devices: devices
+                .iter()
+                .enumerate()
+                .map(|(index, name)| DeviceHistory {
+                    name: name.clone(),
+                    rate: samples
+                        .iter()
+                        .map(|sample| sample.device_rates.get(index).copied().flatten())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Rate over the last hour averaged down to at most `SPARKLINE_POINTS` points.
+    pub fn sparkline(&self) -> Vec<Option<f64>> {
+        let rates: Vec<Option<f64>> = self.window(SPARKLINE_WINDOW).iter().map(|sample| sample.rate).collect();
+        downsample(&rates, SPARKLINE_POINTS)
+    }
+}
+
+/// Averages consecutive values into `points` buckets, skipping missing values.
+pub fn downsample(values: &[Option<f64>], points: usize) -> Vec<Option<f64>> {
+    if values.len() <= points || points == 0 {
+        return values.to_vec();
+    }
+    (0..points)
+        .map(|bucket| {
+            let start = bucket * values.len() / points;
+            let end = (bucket + 1) * values.len() / points;
+            let present: Vec<f64> = values[start..end].iter().flatten().copied().collect();
+            if present.is_empty() {
+                None
+            } else {
+                Some(present.iter().sum::<f64>() / present.len() as f64)
+            }
+        })
+        .collect()
+}
+
+fn rate(now: u64, past: u64, elapsed: Duration) -> Option<f64> {
+    if past == 0 || now < past || elapsed.is_zero() {
+        return None;
+    }
+    Some((now - past) as f64 / elapsed.as_secs_f64())
+}
+
+/// Samples the prover's proof counters every 15 seconds.
+pub fn spawn(history: Arc<History>, prover: Arc<Prover>) {
+    task::spawn(async move {
+        // (time, total proofs, proofs per device) of the last minute of samples
+        let mut counters: VecDeque<(Instant, u64, Vec<u64>)> = VecDeque::with_capacity(RATE_SAMPLES + 1);
+        loop {
+            sleep(SAMPLE_INTERVAL).await;
+            let stats = prover.stats();
+            *history.devices.lock().unwrap() = stats.devices.iter().map(|device| device.name.clone()).collect();
+            let device_counts: Vec<u64> = stats.worker_latency.iter().map(|latency| latency.count).collect();
+            let now = Instant::now();
+            counters.push_back((now, stats.total_proofs as u64, device_counts.clone()));
+            if counters.len() > RATE_SAMPLES + 1 {
+                counters.pop_front();
+            }
+            let (then, past, past_devices) = &counters[0];
+            let elapsed = now.duration_since(*then);
+            history.push(Sample {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs())
+                    .unwrap_or_default(),
+                rate: rate(stats.total_proofs as u64, *past, elapsed),
+                device_rates: device_counts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, count)| rate(*count, past_devices.get(index).copied().unwrap_or_default(), elapsed))
+                    .collect(),
+            });
+        }
+    });
+}
+
+/// Serves `GET /history?window=1h`, the window defaulting to the whole retention period.
+pub fn handler(history: Arc<History>) -> Handler {
+    Box::new(move |request| {
+        if request.method() != Method::GET || request.uri().path() != "/history" {
+            return None;
+        }
+        let window = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("window="));
+        let window = match window.map(schedule::parse_duration) {
+            Some(Ok(window)) => window,
+            Some(Err(e)) => {
+                let body = serde_json::json!({ "error": e.to_string() }).to_string();
+                return Some(http::ready(json(StatusCode::BAD_REQUEST, body)));
+            }
+            None => Duration::MAX,
+        };
+        let body = serde_json::to_string(&history.query(window)).unwrap_or_default();
+        Some(http::ready(json(StatusCode::OK, body)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use hyper::Request;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn sample(timestamp: u64) -> Sample {
+        Sample { timestamp, rate: Some(timestamp as f64), device_rates: vec![Some(1.0), None] }
+    }
+
+    fn timestamps(samples: &[Sample]) -> Vec<u64> {
+        samples.iter().map(|sample| sample.timestamp).collect()
+    }
+
+    // History of `retention` with one sample per timestamp in `range`.
+    fn filled(retention: Duration, range: std::ops::Range<u64>) -> History {
+        let history = History::new(retention);
+        range.for_each(|timestamp| history.push(sample(timestamp)));
+        history
+    }
+
+    #[test]
+    fn keeps_one_sample_per_interval_of_the_retention() {
+        assert_eq!(History::new(Duration::from_secs(4 * 3600)).capacity, 960);
+        assert_eq!(History::new(Duration::from_secs(60)).capacity, 4);
+        assert_eq!(History::new(Duration::from_secs(1)).capacity, 1);
+    }
+
+    #[test]
+    fn drops_the_oldest_samples_when_full() {
+        let history = filled(Duration::from_secs(60), 0..3);
+        assert_eq!(timestamps(&history.window(Duration::MAX)), [0, 1, 2]);
+
+        let history = filled(Duration::from_secs(60), 0..10);
+        assert_eq!(timestamps(&history.window(Duration::MAX)), [6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn queries_the_last_samples_of_the_window() {
+        let history = filled(Duration::from_secs(3600), 0..100);
+        assert_eq!(timestamps(&history.window(Duration::from_secs(60))), [96, 97, 98, 99]);
+        assert_eq!(timestamps(&history.window(Duration::from_secs(74))), [96, 97, 98, 99]);
+        assert_eq!(timestamps(&history.window(Duration::from_secs(75))), [95, 96, 97, 98, 99]);
+        assert!(history.window(Duration::from_secs(14)).is_empty());
+        assert_eq!(history.window(Duration::MAX).len(), 100);
+    }
+
+    #[test]
+    fn answers_a_column_per_device() {
+        let history = filled(Duration::from_secs(3600), 0..3);
+        *history.devices.lock().unwrap() = vec!["cpu".to_string(), "gpu0".to_string(), "gpu1".to_string()];
+        let response = history.query(Duration::from_secs(30));
+        assert_eq!(response.interval, 15);
+        assert_eq!(response.timestamps, [1, 2]);
+        assert_eq!(response.rate, [Some(1.0), Some(2.0)]);
+        let columns: Vec<(&str, &[Option<f64>])> =
+            response.devices.iter().map(|device| (device.name.as_str(), device.rate.as_slice())).collect();
+        assert_eq!(
+            columns,
+            [
+                ("cpu", &[Some(1.0), Some(1.0)][..]),
+                ("gpu0", &[None, None][..]),
+                ("gpu1", &[None, None][..])
+            ]
+        );
+    }
+
+    #[test]
+    fn downsamples_into_averaged_buckets() {
+        let values: Vec<Option<f64>> = (0..6).map(|value| Some(value as f64)).collect();
+        assert_eq!(downsample(&values, 6), values);
+        assert_eq!(downsample(&values, 10), values);
+        assert_eq!(downsample(&values, 0), values);
+        assert_eq!(downsample(&values, 3), [Some(0.5), Some(2.5), Some(4.5)]);
+        assert_eq!(downsample(&values, 4), [Some(0.0), Some(1.5), Some(3.0), Some(4.5)]);
+
+        let gaps = [Some(1.0), None, None, None, Some(4.0), Some(6.0)];
+        assert_eq!(downsample(&gaps, 3), [Some(1.0), None, Some(5.0)]);
+    }
+
+    #[test]
+    fn draws_the_sparkline_of_the_last_hour() {
+        let history = filled(Duration::from_secs(4 * 3600), 0..500);
+        let sparkline = history.sparkline();
+        assert_eq!(sparkline.len(), SPARKLINE_POINTS);
+        // The last hour is samples 260..500, four per point.
+        assert_eq!(sparkline[0], Some(261.5));
+        assert_eq!(sparkline[59], Some(497.5));
+
+        let history = filled(Duration::from_secs(3600), 0..3);
+        assert_eq!(history.sparkline(), [Some(0.0), Some(1.0), Some(2.0)]);
+    }
+
+    #[test]
+    fn computes_rates_from_counters() {
+        assert_eq!(rate(160, 100, Duration::from_secs(60)), Some(1.0));
+        assert_eq!(rate(100, 0, Duration::from_secs(60)), None);
+        assert_eq!(rate(50, 100, Duration::from_secs(60)), None);
+        assert_eq!(rate(160, 100, Duration::ZERO), None);
+    }
+
+    #[tokio::test]
+    async fn serves_the_window_asked_for() {
+        let handler = handler(Arc::new(filled(Duration::from_secs(3600), 0..10)));
+        let get = |path: &str| handler(&Request::get(path).body(Bytes::new()).unwrap());
+        assert!(get("/status").is_none());
+        assert!(handler(&Request::post("/history").body(Bytes::new()).unwrap()).is_none());
+
+        for (path, expected, samples) in [
+            ("/history", StatusCode::OK, 10),
+            ("/history?window=1m", StatusCode::OK, 4),
+            ("/history?pretty&window=30s", StatusCode::OK, 2),
+            ("/history?window=soon", StatusCode::BAD_REQUEST, 0),
+        ] {
+            let response = get(path).expect("history not served").await;
+            assert_eq!(response.status(), expected, "{}", path);
+            let body: Value = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap())
+                .unwrap();
+            assert_eq!(body["timestamps"].as_array().map_or(0, Vec::len), samples, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::broadcast::error::RecvError, task};
+
+use crate::{
+    events::{EventBus, MinerEvent},
+    message::Code,
+};
+
+/// Callbacks for embedders that don't want to consume the `EventBus`. They are called in order from
+/// one task, a slow callback delays the following ones but never the miner.
+#[allow(unused_variables)]
+pub trait MinerHooks: Send + Sync {
+    fn on_connected(&self, server: &str) {}
+    fn on_disconnected(&self, server: &str, reason: &str) {}
+    /// New work from the pool, `target` is the share target
+    fn on_new_job(&self, height: u32, target: u64) {}
+    /// `latency` is the time from submitting to the result, if the share was matched to one
+    fn on_share_accepted(&self, latency: Option<Duration>) {}
+    fn on_share_rejected(&self, code: &Code, message: Option<&str>) {}
+    /// Proofs per second over the last minute, every minute
+    fn on_rate(&self, rate: f64) {}
+}
+
+/// Hooks doing nothing.
+pub struct NoHooks;
+
+impl MinerHooks for NoHooks {}
+
+/// Calls `hooks` for the events published on `events` until the bus is dropped.
+pub fn spawn(hooks: Arc<dyn MinerHooks>, events: &EventBus) {
+    let bus = events.clone();
+    let mut events = events.subscribe();
+    task::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => dispatch(&*hooks, event),
+                Err(RecvError::Lagged(missed)) => bus.record_lag(missed),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+fn dispatch(hooks: &dyn MinerHooks, event: MinerEvent) {
+    match event {
+        MinerEvent::Connected { server, .. } => hooks.on_connected(&server),
+        MinerEvent::Disconnected { server, reason, .. } => hooks.on_disconnected(&server, &reason),
+        MinerEvent::NewJob { height, target } => hooks.on_new_job(height, target),
+        MinerEvent::ShareAccepted(result) => hooks.on_share_accepted(result.latency),
+        MinerEvent::ShareRejected(result) => hooks.on_share_rejected(&result.code, result.message.as_deref()),
+        MinerEvent::RateSample { proof_rates } => {
+            if let Some((_, Some(rate))) = proof_rates.first() {
+                hooks.on_rate(*rate);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,80 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use futures::future::{self, BoxFuture, FutureExt};
+use hyper::{
+    header::CONTENT_LENGTH,
+    service::{make_service_fn, service_fn},
+    Body,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+};
+use tokio::task;
+use tracing::{error, info};
+
+// Request bodies are small JSON documents.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Answers the requests it is responsible for and returns `None` for the others.
+pub type Handler = Box<dyn Fn(&Request<Bytes>) -> Option<BoxFuture<'static, Response<Body>>> + Send + Sync>;
+
+/// Wraps a response that is ready right away.
+pub fn ready(response: Response<Body>) -> BoxFuture<'static, Response<Body>> {
+    future::ready(response).boxed()
+}
+
+fn reply(status: StatusCode, body: &'static str) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+async fn respond(handlers: Arc<Vec<Handler>>, request: Request<Body>) -> Response<Body> {
+    let too_large = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<u64>().ok())
+        .map_or(false, |length| length > MAX_BODY);
+    if too_large {
+        return reply(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+    }
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return reply(StatusCode::BAD_REQUEST, "Unable to read the request body"),
+    };
+    let request = Request::from_parts(parts, body);
+    match handlers.iter().find_map(|handler| handler(&request)) {
+        Some(response) => response.await,
+        None => reply(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Serves the handlers on `bind` until the process exits, the first handler answering a request wins.
+pub fn serve(bind: SocketAddr, handlers: Vec<Handler>) {
+    let handlers = Arc::new(handlers);
+    task::spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let handlers = handlers.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let handlers = handlers.clone();
+                    async move { Ok::<_, Infallible>(respond(handlers, request).await) }
+                }))
+            }
+        });
+        let server = match Server::try_bind(&bind) {
+            Ok(builder) => builder.serve(make_service),
+            Err(e) => {
+                error!("Unable to listen on {}: {}", bind, e);
+                return;
+            }
+        };
+        info!("Listening for HTTP requests on {}", bind);
+        if let Err(e) = server.await {
+            error!("HTTP server on {} failed: {}", bind, e);
+        }
+    });
+}
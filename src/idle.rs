@@ -0,0 +1,282 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::task;
+use tracing::{debug, info, warn};
+
+use crate::prover::{PauseReason, Prover};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Lowers the priority of the calling thread as far as the platform allows.
+pub fn set_lowest_priority() {
+    #[cfg(unix)]
+    {
+        // On Linux this only affects the calling thread.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+            debug!("Unable to lower the thread priority");
+        }
+    }
+    #[cfg(windows)]
+    {
+        use winapi::um::{
+            processthreadsapi::{GetCurrentThread, SetThreadPriority},
+            winbase::THREAD_PRIORITY_IDLE,
+        };
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_IDLE as i32) } == 0 {
+            debug!("Unable to lower the thread priority");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    Pause,
+    Resume,
+}
+
+/// Pauses proving while other programs use the CPU and resumes after a quiet period.
+pub struct IdleController {
+    threshold: f64,
+    quiet_period: Duration,
+    quiet_since: Option<Instant>,
+    paused: bool,
+}
+
+impl IdleController {
+    /// `threshold` is the CPU usage of other programs in percent above which proving is paused.
+    pub fn new(threshold: f64, quiet_period: Duration) -> Self {
+        Self {
+            threshold,
+            quiet_period,
+            quiet_since: None,
+            paused: false,
+        }
+    }
+
+    pub fn update(&mut self, usage: f64, now: Instant) -> Option<IdleAction> {
+        if usage > self.threshold {
+            self.quiet_since = None;
+            if !self.paused {
+                self.paused = true;
+                return Some(IdleAction::Pause);
+            }
+            return None;
+        }
+        if self.paused {
+            let quiet_since = *self.quiet_since.get_or_insert(now);
+            if now.duration_since(quiet_since) >= self.quiet_period {
+                self.paused = false;
+                self.quiet_since = None;
+                return Some(IdleAction::Resume);
+            }
+        }
+        None
+    }
+}
+
+/// CPU time counters, in platform specific units.
+#[derive(Debug, Clone, Copy)]
+struct CpuTimes {
+    total: u64,
+    busy: u64,
+    own: u64,
+}
+
+impl CpuTimes {
+    /// CPU usage of everything but this process in percent since `earlier`.
+    fn other_usage(&self, earlier: &CpuTimes) -> Option<f64> {
+        let total = self.total.checked_sub(earlier.total)?;
+        if total == 0 {
+            return None;
+        }
+        let busy = self.busy.saturating_sub(earlier.busy);
+        let own = self.own.saturating_sub(earlier.own);
+        Some(busy.saturating_sub(own) as f64 / total as f64 * 100.0)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sample() -> Option<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let values: Vec<u64> = stat
+        .lines()
+        .next()?
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    if values.len() < 8 {
+        return None;
+    }
+    let total: u64 = values.iter().sum();
+    let idle = values[3] + values[4];
+
+    // Fields after the command name, which may contain spaces; utime and stime are the 14th and 15th field.
+    let own = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let fields: Vec<&str> = own.get(own.rfind(')')? + 1..)?.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+
+    Some(CpuTimes {
+        total,
+        busy: total - idle,
+        own: utime + stime,
+    })
+}
+
+#[cfg(windows)]
+fn sample() -> Option<CpuTimes> {
+    use winapi::{
+        shared::minwindef::FILETIME,
+        um::processthreadsapi::{GetCurrentProcess, GetProcessTimes, GetSystemTimes},
+    };
+    fn ticks(time: &FILETIME) -> u64 {
+        ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64
+    }
+    let mut idle = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut own_kernel = FILETIME::default();
+    let mut own_user = FILETIME::default();
+    unsafe {
+        if GetSystemTimes(&mut idle, &mut kernel, &mut user) == 0 {
+            return None;
+        }
+        if GetProcessTimes(
+            GetCurrentProcess(),
+            &mut creation,
+            &mut exit,
+            &mut own_kernel,
+            &mut own_user,
+        ) == 0
+        {
+            return None;
+        }
+    }
+    // Kernel time includes idle time.
+    let total = ticks(&kernel) + ticks(&user);
+    Some(CpuTimes {
+        total,
+        busy: total - ticks(&idle),
+        own: ticks(&own_kernel) + ticks(&own_user),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn sample() -> Option<CpuTimes> {
+    None
+}
+
+/// Samples the system CPU usage and pauses the prover while other programs are busy.
+pub fn spawn(prover: Arc<Prover>, threshold: f64, quiet_period: Duration) {
+    let mut last = match sample() {
+        Some(times) => times,
+        None => {
+            warn!("Idle-only mining is not supported on this platform, mining continuously");
+            return;
+        }
+    };
+    info!(
+        "Idle-only mining enabled, pausing when other programs use more than {}% CPU",
+        threshold
+    );
+    task::spawn(async move {
+        let mut controller = IdleController::new(threshold, quiet_period);
+        loop {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+            let times = match sample() {
+                Some(times) => times,
+                None => continue,
+            };
+            let usage = times.other_usage(&last);
+            last = times;
+            let usage = match usage {
+                Some(usage) => usage,
+                None => continue,
+            };
+            match controller.update(usage, Instant::now()) {
+                Some(IdleAction::Pause) => {
+                    info!("Other programs are using {:.0}% CPU, pausing", usage);
+                    prover.pause(PauseReason::Idle).await;
+                }
+                Some(IdleAction::Resume) => {
+                    info!("System is idle again, resuming");
+                    prover.resume(PauseReason::Idle).await;
+                }
+                None => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: Duration = Duration::from_secs(30);
+
+    // Feeds one usage sample every 5 seconds, returning the actions taken.
+    fn simulate(controller: &mut IdleController, usage: &[f64]) -> Vec<(usize, IdleAction)> {
+        let start = Instant::now();
+        usage
+            .iter()
+            .enumerate()
+            .filter_map(|(index, usage)| {
+                let now = start + SAMPLE_INTERVAL * index as u32;
+                controller.update(*usage, now).map(|action| (index, action))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_mining_below_the_threshold() {
+        let mut controller = IdleController::new(20.0, QUIET);
+        assert!(simulate(&mut controller, &[0.0, 5.0, 19.9, 20.0, 10.0]).is_empty());
+    }
+
+    #[test]
+    fn pauses_once_and_resumes_after_the_quiet_period() {
+        let mut controller = IdleController::new(20.0, QUIET);
+        // Busy for three samples, then quiet: resumed 30 seconds after the first quiet sample.
+        let usage = [0.0, 50.0, 80.0, 60.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0];
+        assert_eq!(
+            simulate(&mut controller, &usage),
+            vec![(1, IdleAction::Pause), (10, IdleAction::Resume)]
+        );
+    }
+
+    #[test]
+    fn activity_during_the_quiet_period_restarts_it() {
+        let mut controller = IdleController::new(20.0, QUIET);
+        let mut usage = vec![90.0, 0.0, 0.0, 0.0, 0.0, 90.0];
+        usage.extend([0.0; 7]);
+        assert_eq!(
+            simulate(&mut controller, &usage),
+            vec![(0, IdleAction::Pause), (12, IdleAction::Resume)]
+        );
+    }
+
+    #[test]
+    fn excludes_the_miner_from_the_usage() {
+        let earlier = CpuTimes {
+            total: 1000,
+            busy: 100,
+            own: 50,
+        };
+        // 800 of 1000 ticks busy, 700 of them the miner's own.
+        let now = CpuTimes {
+            total: 2000,
+            busy: 900,
+            own: 750,
+        };
+        assert_eq!(now.other_usage(&earlier), Some(10.0));
+        assert_eq!(earlier.other_usage(&earlier), None);
+        assert_eq!(earlier.other_usage(&now), None);
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Error, Result};
+use tokio::{task, time::sleep};
+use tracing::{debug, info, warn};
+
+use crate::{
+    client::{Client, ClientStats},
+    prover::{Prover, ProverStats},
+};
+
+const MEASUREMENT: &str = "aleoxminer";
+const TIMEOUT: Duration = Duration::from_secs(5);
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+// Intervals kept while the server is unreachable, the oldest are dropped first.
+const MAX_BUFFERED: usize = 360;
+
+/// Extra tag added to every line, e.g. `rig=rig1`.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid tag {}, expected key=value", s))?;
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() || value.is_empty() {
+            return Err(anyhow!("invalid tag {}, expected key=value", s));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    /// Server URL, e.g. `http://localhost:8086`
+    pub url: String,
+    /// Bucket, or database for InfluxDB 1.x
+    pub bucket: String,
+    /// Organization, InfluxDB 2.x only
+    pub org: Option<String>,
+    /// API token, selects the InfluxDB 2.x write API
+    pub token: Option<String>,
+    pub interval: Duration,
+    pub tags: Vec<Tag>,
+}
+
+impl InfluxConfig {
+    fn write_url(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        match (&self.token, &self.org) {
+            (Some(_), Some(org)) => format!("{}/api/v2/write?bucket={}&org={}&precision=s", url, self.bucket, org),
+            (Some(_), None) => format!("{}/api/v2/write?bucket={}&precision=s", url, self.bucket),
+            (None, _) => format!("{}/write?db={}&precision=s", url, self.bucket),
+        }
+    }
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+fn tag_set(tags: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    for (key, value) in tags {
+        if !value.is_empty() {
+            let _ = write!(out, ",{}={}", escape_tag(key), escape_tag(value));
+        }
+    }
+    out
+}
+
+fn seconds(duration: Option<Duration>) -> Option<f64> {
+    duration.map(|duration| duration.as_secs_f64())
+}
+
+/// Formats the stats as InfluxDB line protocol: one line for the miner and one per device.
+pub fn render(
+    stats: &ProverStats,
+    client: &ClientStats,
+    worker: &str,
+    pool: &str,
+    extra: &[Tag],
+    timestamp: u64,
+) -> String {
+    let mut tags: Vec<(&str, &str)> = vec![("worker", worker), ("pool", pool)];
+    tags.extend(extra.iter().map(|tag| (tag.key.as_str(), tag.value.as_str())));
+
+    let mut fields = vec![
+        format!("uptime={}i", stats.uptime.as_secs()),
+        format!("paused={}", stats.paused),
+        format!("connected={}", client.connected),
+        format!("proofs={}i", stats.total_proofs),
+        format!("proofs_below_target={}i", stats.below_target),
+        format!("proofs_in_flight={}i", stats.in_flight),
+        format!("shares_accepted={}i", stats.valid_shares),
+        format!("shares_rejected={}i", stats.invalid_shares),
+        format!("shares_stale={}i", stats.local_stale),
+        format!("reconnects={}i", client.reconnects),
+        format!("pending_submits={}i", client.pending_submits),
+        format!("block_height={}i", stats.current_block),
+    ];
+    for (window, rate) in stats.proof_rates.iter() {
+        if let Some(rate) = rate {
+            fields.push(format!("proof_rate_{}m={}", window, rate));
+        }
+    }
+    if let Some(rate) = stats.effective_rate {
+        fields.push(format!("effective_rate={}", rate));
+    }
+    let latency = client.submit_latency.summary();
+    for (name, value) in [("p50", latency.p50), ("p90", latency.p90), ("p99", latency.p99)] {
+        if let Some(value) = seconds(value) {
+            fields.push(format!("submit_latency_{}={}", name, value));
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}{} {} {}", MEASUREMENT, tag_set(&tags), fields.join(","), timestamp);
+
+    for (device, latency) in stats.devices.iter().zip(stats.worker_latency.iter()) {
+        let mut device_tags = tags.clone();
+        device_tags.push(("device", device.name.as_str()));
+        let mut fields = vec![
+            format!("proofs={}i", latency.count),
+            format!("failures={}i", device.failures),
+        ];
+        if let Some(p50) = seconds(latency.p50) {
+            fields.push(format!("proof_latency_p50={}", p50));
+        }
+        let _ = writeln!(out, "{}{} {} {}", MEASUREMENT, tag_set(&device_tags), fields.join(","), timestamp);
+    }
+
+    for gpu in stats.gpu_telemetry.iter() {
+        let index = gpu.index.to_string();
+        let mut gpu_tags = tags.clone();
+        gpu_tags.push(("gpu", index.as_str()));
+        let mut fields = Vec::new();
+        if let Some(temperature) = gpu.temperature {
+            fields.push(format!("temperature={}i", temperature));
+        }
+        if let Some(power) = gpu.power {
+            fields.push(format!("power={}", power));
+        }
+        if let Some(fan) = gpu.fan {
+            fields.push(format!("fan={}i", fan));
+        }
+        if let Some(clock) = gpu.sm_clock {
+            fields.push(format!("sm_clock={}i", clock));
+        }
+        if let Some(clock) = gpu.memory_clock {
+            fields.push(format!("memory_clock={}i", clock));
+        }
+        if !fields.is_empty() {
+            let _ = writeln!(out, "{}{} {} {}", MEASUREMENT, tag_set(&gpu_tags), fields.join(","), timestamp);
+        }
+    }
+    out
+}
+
+/// Pushes the stats to InfluxDB every interval. Failed intervals are retried with the next push.
+pub fn spawn(config: InfluxConfig, prover: Arc<Prover>, client: Arc<Client>) -> Result<()> {
+    let http = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+    let url = config.write_url();
+    info!("Pushing metrics to {} every {}s", config.url, config.interval.as_secs());
+    task::spawn(async move {
+        let mut buffer: VecDeque<String> = VecDeque::new();
+        let mut failures = 0u64;
+        let mut last_warning: Option<Instant> = None;
+        loop {
+            sleep(config.interval).await;
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default();
+            if buffer.len() == MAX_BUFFERED {
+                buffer.pop_front();
+            }
+            buffer.push_back(render(
+                &prover.stats(),
+                &client.stats(),
+                &client.worker(),
+                &client.server(),
+                &config.tags,
+                timestamp,
+            ));
+
+            let body: String = buffer.iter().map(String::as_str).collect();
+            let mut request = http.post(&url).body(body);
+            if let Some(token) = &config.token {
+                request = request.header("Authorization", format!("Token {}", token));
+            }
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Pushed {} intervals to InfluxDB", buffer.len());
+                    buffer.clear();
+                    continue;
+                }
+                Ok(response) => format!("server returned {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+            failures += 1;
+            if last_warning.map_or(true, |last| last.elapsed() >= WARN_INTERVAL) {
+                warn!(
+                    "Unable to push metrics to InfluxDB: {} ({} failures, {} intervals buffered)",
+                    error,
+                    failures,
+                    buffer.len()
+                );
+                last_warning = Some(Instant::now());
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        histogram::LatencySummary,
+        pipeline::{DeviceStatus, PipelineState},
+        telemetry::GpuTelemetry,
+        testing::{self, FakeBackend, HttpSink},
+    };
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    fn config(url: String, token: Option<&str>, org: Option<&str>) -> InfluxConfig {
+        InfluxConfig {
+            url,
+            bucket: "miners".to_string(),
+            org: org.map(str::to_string),
+            token: token.map(str::to_string),
+            interval: Duration::from_millis(100),
+            tags: Vec::new(),
+        }
+    }
+
+    fn prover(client: &Arc<Client>) -> Arc<Prover> {
+        Prover::new(testing::prover_config(16, FakeBackend::new(Duration::ZERO)), client.clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn formats_line_protocol() {
+        let client = testing::client("127.0.0.1:1", "rig1");
+        let mut stats = prover(&client).stats();
+        stats.uptime = Duration::from_secs(120);
+        stats.paused = false;
+        stats.total_proofs = 1000;
+        stats.below_target = 990;
+        stats.in_flight = 2;
+        stats.valid_shares = 9;
+        stats.invalid_shares = 1;
+        stats.local_stale = 3;
+        stats.current_block = 2;
+        stats.proof_rates = vec![(1, Some(8.5)), (15, None)];
+        stats.effective_rate = Some(7.25);
+        stats.devices = vec![DeviceStatus {
+            name: "GPU 0".to_string(),
+            state: PipelineState::Mining,
+            failures: 1,
+        }];
+        stats.worker_latency = vec![LatencySummary {
+            count: 500,
+            p50: Some(Duration::from_millis(250)),
+            ..Default::default()
+        }];
+        stats.gpu_telemetry = vec![GpuTelemetry {
+            index: 0,
+            temperature: Some(65),
+            power: Some(180.5),
+            fan: None,
+            sm_clock: Some(1800),
+            memory_clock: None,
+        }];
+        let tags = ["rig=a,b".parse().unwrap()];
+
+        let lines = render(&stats, &client.stats(), "rig 1", "pool.example:4040", &tags, 1_650_000_000);
+        let tags = "worker=rig\\ 1,pool=pool.example:4040,rig=a\\,b";
+        assert_eq!(
+            lines,
+            format!(
+                "aleoxminer,{tags} uptime=120i,paused=false,connected=false,proofs=1000i,proofs_below_target=990i,\
+                 proofs_in_flight=2i,shares_accepted=9i,shares_rejected=1i,shares_stale=3i,reconnects=0i,\
+                 pending_submits=0i,block_height=2i,proof_rate_1m=8.5,effective_rate=7.25 1650000000\n\
+                 aleoxminer,{tags},device=GPU\\ 0 proofs=500i,failures=1i,proof_latency_p50=0.25 1650000000\n\
+                 aleoxminer,{tags},gpu=0 temperature=65i,power=180.5,sm_clock=1800i 1650000000\n",
+                tags = tags
+            )
+        );
+    }
+
+    #[test]
+    fn parses_tags() {
+        let tag: Tag = " rig = rig1 ".parse().unwrap();
+        assert_eq!((tag.key.as_str(), tag.value.as_str()), ("rig", "rig1"));
+        assert!("rig".parse::<Tag>().is_err());
+        assert!("rig=".parse::<Tag>().is_err());
+        assert_eq!(tag_set(&[("a b", "c=d"), ("empty", "")]), ",a\\ b=c\\=d");
+    }
+
+    #[test]
+    fn writes_to_the_api_of_the_version() {
+        let url = "http://influx:8086/".to_string();
+        assert_eq!(config(url.clone(), None, None).write_url(), "http://influx:8086/write?db=miners&precision=s");
+        assert_eq!(
+            config(url.clone(), Some("token"), None).write_url(),
+            "http://influx:8086/api/v2/write?bucket=miners&precision=s"
+        );
+        assert_eq!(
+            config(url, Some("token"), Some("farm")).write_url(),
+            "http://influx:8086/api/v2/write?bucket=miners&org=farm&precision=s"
+        );
+    }
+
+    // Lines of the miner measurement in a request body, one per pushed interval.
+    fn intervals(body: &[u8]) -> usize {
+        String::from_utf8_lossy(body)
+            .lines()
+            .filter(|line| line.starts_with("aleoxminer,worker=rig1,pool=127.0.0.1:1 "))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn pushes_every_interval_and_resends_after_failures() {
+        let sink = HttpSink::start().await.unwrap();
+        sink.fail(2);
+        let client = testing::client("127.0.0.1:1", "rig1");
+        spawn(config(sink.url(""), None, None), prover(&client), client).unwrap();
+
+        let requests = sink.wait_for(LIMIT, 4).await.unwrap();
+        assert!(requests.iter().all(|request| request.method == "POST"));
+        assert_eq!(requests[0].path, "/write?db=miners&precision=s");
+        // The two failed intervals go again with the third, then the buffer is empty.
+        let pushed: Vec<usize> = requests[..4].iter().map(|request| intervals(&request.body)).collect();
+        assert_eq!(pushed, vec![1, 2, 3, 1]);
+    }
+}
@@ -0,0 +1,68 @@
+use tracing::{debug, debug_span, Span};
+
+/// Spans following one job from the pool's Notify to the first attempt of each device, at debug level.
+/// With `--log-format json` they show where the time between a Notify and the first proof went.
+///
+/// The `job` span has the fields `height`, `job_epoch` and `pool`. Its children are `channel_wait`,
+/// from the Notify until the prover picks the job up, `prover_activation`, until the workers are
+/// started, and one `first_attempt` per device. Cancellation and each submit are events of the `job`
+/// span.
+#[derive(Debug, Clone)]
+pub struct JobTrace {
+    span: Span,
+    channel_wait: Option<Span>,
+}
+
+impl JobTrace {
+    /// Trace of a job the pool just notified, waiting for the prover.
+    pub fn new(height: u32, job_epoch: u64, pool: &str) -> Self {
+        let span = debug_span!("job", height, job_epoch, pool);
+        let channel_wait = debug_span!(parent: &span, "channel_wait");
+        Self {
+            span,
+            channel_wait: Some(channel_wait),
+        }
+    }
+
+    /// Trace that records nothing, for work that didn't come from a Notify. A child of a disabled
+    /// span would be a new root, so none are made.
+    pub fn none() -> Self {
+        Self {
+            span: Span::none(),
+            channel_wait: None,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Ends `channel_wait`, the prover picked the job up.
+    pub fn received(&mut self) {
+        self.channel_wait = None;
+    }
+
+    /// Span the prover spends starting the workers in.
+    pub fn activation(&self) -> Span {
+        if self.span.is_none() {
+            return Span::none();
+        }
+        debug_span!(parent: &self.span, "prover_activation")
+    }
+
+    /// Span of the first proof attempt of `device` on this job.
+    pub fn first_attempt(&self, device: &str) -> Span {
+        if self.span.is_none() {
+            return Span::none();
+        }
+        debug_span!(parent: &self.span, "first_attempt", device)
+    }
+
+    /// Records that the job was dropped for `reason` before its workers finished.
+    pub fn cancelled(&self, reason: &str) {
+        if self.span.is_none() {
+            return;
+        }
+        debug!(parent: &self.span, reason, "Job cancelled");
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ansi_term::Colour::Red;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use snarkvm::dpc::{testnet2::Testnet2, Account, Address, PrivateKey};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum AccountCommand {
+    /// Generate a new private key, view key and address
+    New {
+        /// Also write the keys to this JSON file, readable by the owner only, an existing file is never overwritten
+        #[structopt(long = "out", value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+    /// Print the address of a stored private key
+    Address {
+        /// File with the private key, either a file written by `account new` or the bare key, - for stdin
+        #[structopt(long = "key", value_name = "FILE")]
+        key: String,
+    },
+}
+
+/// Keys file written by `account new`.
+#[derive(Serialize, Deserialize)]
+struct KeysFile {
+    private_key: String,
+    view_key: String,
+    address: String,
+}
+
+pub fn run(command: AccountCommand) -> Result<()> {
+    match command {
+        AccountCommand::New { out } => {
+            let account = Account::<Testnet2>::new(&mut rand::thread_rng());
+            let keys = KeysFile {
+                private_key: account.private_key().to_string(),
+                view_key: account.view_key().to_string(),
+                address: account.address().to_string(),
+            };
+            if let Some(path) = out.as_ref() {
+                write_keys(path, &keys)?;
+            }
+            print_keys(&keys);
+            if let Some(path) = out {
+                println!("The keys were written to {}", path.display());
+                println!();
+            }
+            Ok(())
+        }
+        AccountCommand::Address { key } => {
+            let text = if key == "-" {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                std::fs::read_to_string(&key).with_context(|| format!("unable to read {}", key))?
+            };
+            println!("{}", address(&text)?);
+            Ok(())
+        }
+    }
+}
+
+/// Prints freshly generated keys with the backup warning.
+pub fn new_account() {
+    let account = Account::<Testnet2>::new(&mut rand::thread_rng());
+    print_keys(&KeysFile {
+        private_key: account.private_key().to_string(),
+        view_key: account.view_key().to_string(),
+        address: account.address().to_string(),
+    });
+}
+
+fn print_keys(keys: &KeysFile) {
+    println!();
+    println!("Private key: {}", keys.private_key);
+    println!("   View key: {}", keys.view_key);
+    println!("    Address: {}", keys.address);
+    println!();
+    for line in [
+        "WARNING: Make sure you have a backup of both private key and view key!",
+        "         Nobody can help you recover those keys if you lose them!",
+        "         Anyone who has the private key can spend what the address earns.",
+    ] {
+        println!("{}", Red.bold().paint(line));
+    }
+    println!();
+}
+
+/// Creates `path` readable by the owner only, failing if it exists.
+fn write_keys(path: &Path, keys: &KeysFile) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => anyhow!("{} already exists, refusing to overwrite it", path.display()),
+        _ => anyhow!("unable to create {}: {}", path.display(), e),
+    })?;
+    let json = serde_json::to_string_pretty(keys)?;
+    file.write_all(json.as_bytes())
+        .and_then(|()| file.write_all(b"\n"))
+        .with_context(|| format!("unable to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Address of the private key in `text`, a keys file or the bare key. Errors never include the key.
+pub fn address(text: &str) -> Result<String> {
+    let text = text.trim();
+    let private_key = if text.starts_with('{') {
+        let keys: KeysFile = serde_json::from_str(text).map_err(|_| anyhow!("not a valid keys file"))?;
+        keys.private_key
+    } else {
+        text.to_string()
+    };
+    let private_key =
+        PrivateKey::<Testnet2>::from_str(private_key.trim()).map_err(|_| anyhow!("not a valid private key"))?;
+    Ok(Address::from_private_key(&private_key).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    use super::*;
+    use crate::testing;
+
+    // Example account of the snarkOS documentation.
+    const PRIVATE_KEY: &str = "APrivateKey1zkp8cC4jgHEBnbtu3xxs1Ndja2EMizcvTRDq5Nikdkukg1p";
+    const VIEW_KEY: &str = "AViewKey1iAf6a7fv6ELA4ECwAth1hDNUJJNNoWNThmREjpybqder";
+    const ADDRESS: &str = "aleo1d5hg2z3ma00382pngntdp68e74zv54jdxy249qhaujhks9c72yrs33ddah";
+
+    fn keys() -> KeysFile {
+        KeysFile {
+            private_key: PRIVATE_KEY.to_string(),
+            view_key: VIEW_KEY.to_string(),
+            address: ADDRESS.to_string(),
+        }
+    }
+
+    #[test]
+    fn derives_the_address_of_a_known_key() {
+        assert_eq!(address(PRIVATE_KEY).unwrap(), ADDRESS);
+        assert_eq!(address(&format!("  {}\r\n", PRIVATE_KEY)).unwrap(), ADDRESS);
+        assert_eq!(address(&serde_json::to_string_pretty(&keys()).unwrap()).unwrap(), ADDRESS);
+    }
+
+    #[test]
+    fn derives_the_address_of_generated_keys() {
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        for _ in 0..3 {
+            let account = Account::<Testnet2>::new(&mut rng);
+            assert_eq!(address(&account.private_key().to_string()).unwrap(), account.address().to_string());
+        }
+    }
+
+    #[test]
+    fn errors_never_show_the_key() {
+        let truncated = &PRIVATE_KEY[..40];
+        let error = address(truncated).unwrap_err().to_string();
+        assert_eq!(error, "not a valid private key");
+        let error = address(&format!("{{\"private_key\": \"{}\"}}", truncated)).unwrap_err().to_string();
+        assert_eq!(error, "not a valid keys file");
+        assert!(address(ADDRESS).is_err());
+    }
+
+    #[test]
+    fn writes_keys_readable_by_the_owner_only() {
+        let path = testing::scratch("keys", "mode").join("account.json");
+        write_keys(&path, &keys()).unwrap();
+        assert_eq!(address(&fs::read_to_string(&path).unwrap()).unwrap(), ADDRESS);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn refuses_to_overwrite_a_file() {
+        let path = testing::scratch("keys", "overwrite").join("account.json");
+        fs::write(&path, "keep me").unwrap();
+        let error = write_keys(&path, &keys()).unwrap_err().to_string();
+        assert!(error.ends_with("already exists, refusing to overwrite it"), "{}", error);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+    }
+}
@@ -0,0 +1,84 @@
+// The miner as a library: the pool protocol (`message`), the pool connection (`client`), the
+// prover, the configuration, the statistics and the event bus. The `cli` module is the command line binary.
+
+mod alert;
+pub mod backend;
+mod bench;
+mod build_info;
+#[cfg(feature = "chaos")]
+mod chaos;
+pub mod channel;
+pub mod cli;
+#[forbid(unsafe_code)]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+pub mod client;
+pub mod config;
+#[cfg(feature = "metrics")]
+mod control;
+mod cpu;
+mod crash;
+mod credentials;
+mod devices;
+mod estimate;
+pub mod events;
+mod exit;
+mod explain;
+#[cfg(feature = "cuda")]
+mod gpu;
+mod group;
+mod histogram;
+#[cfg(feature = "metrics")]
+mod history;
+pub mod hooks;
+#[cfg(feature = "metrics")]
+mod http;
+mod idle;
+mod influx;
+pub mod job_trace;
+mod keys;
+mod limit;
+mod loadtest;
+mod logging;
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+pub mod message;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notify;
+pub mod outbox;
+pub mod outgoing;
+mod pipeline;
+pub mod prover;
+pub mod proxy;
+pub mod quality;
+mod reject;
+mod reload;
+mod rtt;
+mod run_report;
+mod report;
+mod schedule;
+mod selftest;
+pub mod session;
+pub mod server;
+#[cfg(all(windows, feature = "windows-service"))]
+mod service;
+mod share_log;
+mod shutdown;
+pub mod split;
+pub mod stats;
+mod statsd;
+mod systemd;
+mod telemetry;
+pub mod template;
+mod test_pool;
+pub mod testing;
+mod threshold;
+pub mod throttle;
+mod traffic;
+pub mod transport;
+#[cfg(feature = "tui")]
+mod tui;
+mod status;
+mod units;
+mod watchdog;
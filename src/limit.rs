@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar,
+        Mutex,
+        PoisonError,
+    },
+    time::Duration,
+};
+
+// Waiting workers look at the stop flag this often.
+const STOP_CHECK: Duration = Duration::from_millis(100);
+
+/// Caps the proofs computed at once. Proving threads block on it, so it doesn't need an async runtime.
+pub struct ProofLimit {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Allows one proof, returned to the limit on drop.
+pub struct ProofPermit<'a> {
+    limit: &'a ProofLimit,
+}
+
+impl ProofLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            available: Mutex::new(max.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until a proof may start, or returns `None` once `stop` is set.
+    pub fn acquire(&self, stop: &AtomicBool) -> Option<ProofPermit<'_>> {
+        let mut available = self.available.lock().unwrap_or_else(PoisonError::into_inner);
+        while *available == 0 {
+            if stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            available = self
+                .released
+                .wait_timeout(available, STOP_CHECK)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        *available -= 1;
+        Some(ProofPermit { limit: self })
+    }
+
+    #[cfg(test)]
+    fn available(&self) -> usize {
+        *self.available.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for ProofPermit<'_> {
+    fn drop(&mut self) {
+        *self.limit.available.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        self.limit.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Instant,
+    };
+
+    use super::*;
+
+    #[test]
+    fn caps_concurrent_holders() {
+        let limit = Arc::new(ProofLimit::new(2));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (holding, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let threads: Vec<_> = (0..6)
+            .map(|_| {
+                let (limit, stop, holding, most) = (limit.clone(), stop.clone(), holding.clone(), most.clone());
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        let _permit = limit.acquire(&stop).unwrap();
+                        let now = holding.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(1));
+                        holding.fetch_sub(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|thread| thread.join().unwrap());
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(limit.available(), 2);
+    }
+
+    #[test]
+    fn stop_releases_waiters() {
+        let limit = Arc::new(ProofLimit::new(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let held = limit.acquire(&stop).unwrap();
+        let waiter = {
+            let (limit, stop) = (limit.clone(), stop.clone());
+            thread::spawn(move || limit.acquire(&stop).is_none())
+        };
+        thread::sleep(Duration::from_millis(50));
+        let stopped = Instant::now();
+        stop.store(true, Ordering::SeqCst);
+        assert!(waiter.join().unwrap());
+        assert!(stopped.elapsed() < STOP_CHECK * 3);
+        drop(held);
+        assert_eq!(limit.available(), 1);
+    }
+
+    #[test]
+    fn zero_allows_one() {
+        let limit = ProofLimit::new(0);
+        let permit = limit.acquire(&AtomicBool::new(false));
+        assert!(permit.is_some());
+        assert_eq!(limit.available(), 0);
+    }
+}
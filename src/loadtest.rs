@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use futures_util::sink::SinkExt;
+use rand::Rng;
+use snarkvm::{
+    dpc::{testnet2::Testnet2, Address, PoSWProof},
+    traits::Network,
+    utilities::UniformRand,
+};
+use structopt::StructOpt;
+use tokio::{
+    net::TcpStream,
+    time::{interval, sleep, sleep_until, timeout},
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+use crate::{
+    client, config,
+    histogram::LatencyHistogram,
+    message::{ProtocolLimits, ProverCodec, ProverMessage, Secret},
+    schedule,
+};
+
+#[derive(Debug, StructOpt)]
+pub struct LoadTest {
+    /// Pool to test, host:port or tcp://host:port
+    #[structopt(value_name = "POOL")]
+    url: String,
+    /// Simulated miners
+    #[structopt(long = "miners", value_name = "COUNT", default_value = "10")]
+    miners: u32,
+    /// Spread the miner starts evenly over this time, e.g. 30s
+    #[structopt(
+        long = "ramp-up",
+        value_name = "DURATION",
+        default_value = "0s",
+        parse(try_from_str = schedule::parse_duration)
+    )]
+    ramp_up: Duration,
+    /// How long to run after the ramp-up, e.g. 10m
+    #[structopt(
+        long = "duration",
+        value_name = "DURATION",
+        default_value = "1m",
+        parse(try_from_str = schedule::parse_duration)
+    )]
+    duration: Duration,
+    /// Shares per miner per minute, only with --fake-proofs
+    #[structopt(long = "submit-rate", value_name = "SHARES", default_value = "6")]
+    submit_rate: f64,
+    /// Submit the genesis proof with random nonces, the pool rejects them but has to verify them first.
+    /// Without it the miners only authorize and receive work
+    #[structopt(long = "fake-proofs")]
+    fake_proofs: bool,
+    /// Percentage of the miners dropping and reopening their connection per minute
+    #[structopt(long = "churn", value_name = "PERCENT", default_value = "0")]
+    churn: f64,
+    /// Worker names are this prefix followed by the miner number
+    #[structopt(long = "worker-prefix", value_name = "PREFIX", default_value = "load")]
+    worker_prefix: String,
+}
+
+#[derive(Default)]
+struct Totals {
+    connect_attempts: u64,
+    connects: u64,
+    authorized: u64,
+    auth_rejected: u64,
+    notifies: u64,
+    submits: u64,
+    results: BTreeMap<String, u64>,
+    churned: u64,
+    errors: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+struct Stats {
+    totals: Mutex<Totals>,
+    connect_latency: LatencyHistogram,
+    auth_latency: LatencyHistogram,
+    submit_latency: LatencyHistogram,
+}
+
+impl Stats {
+    fn update(&self, update: impl FnOnce(&mut Totals)) {
+        update(&mut self.totals.lock().unwrap());
+    }
+
+    fn error(&self, error: impl ToString) {
+        self.update(|totals| *totals.errors.entry(error.to_string()).or_default() += 1);
+    }
+
+    fn print(&self, elapsed: Duration) {
+        let totals = self.totals.lock().unwrap();
+        let percent = |part: u64, total: u64| if total > 0 { part as f64 * 100.0 / total as f64 } else { 0.0 };
+        println!("After {}s:", elapsed.as_secs());
+        println!(
+            "  connects   {}/{} ({:.1}%), latency {}",
+            totals.connects,
+            totals.connect_attempts,
+            percent(totals.connects, totals.connect_attempts),
+            self.connect_latency.snapshot().summary()
+        );
+        println!(
+            "  authorized {}, rejected {}, latency {}",
+            totals.authorized,
+            totals.auth_rejected,
+            self.auth_latency.snapshot().summary()
+        );
+        println!("  notifies   {}, churned connections {}", totals.notifies, totals.churned);
+        let results: Vec<String> = totals.results.iter().map(|(code, count)| format!("{} {}", code, count)).collect();
+        println!(
+            "  submits    {}, results [{}], latency {}",
+            totals.submits,
+            results.join(", "),
+            self.submit_latency.snapshot().summary()
+        );
+        for (error, count) in totals.errors.iter() {
+            println!("  error      {}x {}", count, error);
+        }
+    }
+}
+
+/// What a simulated miner submits, the proof is shared, the nonces are random.
+struct FakeShare {
+    proof: PoSWProof<Testnet2>,
+}
+
+impl FakeShare {
+    fn new() -> Self {
+        Self {
+            proof: Testnet2::genesis_block().header().proof().clone(),
+        }
+    }
+
+    fn submit(&self, height: u32) -> ProverMessage {
+        let nonce = <Testnet2 as Network>::PoSWNonce::rand(&mut rand::thread_rng());
+        ProverMessage::Submit(height, nonce, self.proof.clone())
+    }
+}
+
+struct Miner {
+    url: String,
+    account: Option<String>,
+    address: Option<Address<Testnet2>>,
+    worker: String,
+    password: Secret,
+    limits: ProtocolLimits,
+    submit_every: Option<Duration>,
+    // Chance per second to drop the connection.
+    churn: f64,
+    share: Option<Arc<FakeShare>>,
+    stats: Arc<Stats>,
+}
+
+enum Session {
+    Finished,
+    Churned,
+}
+
+impl Miner {
+    async fn run(self, until: Instant) {
+        while Instant::now() < until {
+            match self.session(until).await {
+                Ok(Session::Finished) => return,
+                Ok(Session::Churned) => self.stats.update(|totals| totals.churned += 1),
+                Err(e) => {
+                    self.stats.error(e);
+                    // Don't hammer a pool that refuses connections.
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    async fn session(&self, until: Instant) -> Result<Session> {
+        self.stats.update(|totals| totals.connect_attempts += 1);
+        let started = Instant::now();
+        let socket = match timeout(Duration::from_secs(10), TcpStream::connect(&self.url)).await {
+            Ok(socket) => socket.map_err(|e| anyhow!("connect: {}", e))?,
+            Err(_) => return Err(anyhow!("connect: timed out")),
+        };
+        self.stats.connect_latency.record(started.elapsed());
+        self.stats.update(|totals| totals.connects += 1);
+        let mut framed = Framed::new(socket, ProverCodec::new(self.limits));
+
+        let authorizing = Instant::now();
+        framed
+            .send(client::authorization(
+                self.account.as_deref(),
+                self.address.as_ref(),
+                &self.worker,
+                self.password.clone(),
+            ))
+            .await?;
+        let mut authorized = false;
+        let mut height = None;
+        let mut pending: VecDeque<Instant> = VecDeque::new();
+        let mut submit = interval(self.submit_every.unwrap_or(Duration::from_secs(3600)));
+        let mut churn = interval(Duration::from_secs(1));
+        // Both tick immediately.
+        submit.tick().await;
+        churn.tick().await;
+        loop {
+            tokio::select! {
+                _ = sleep_until(until.into()) => return Ok(Session::Finished),
+                _ = churn.tick() => {
+                    if rand::thread_rng().gen_bool(self.churn) {
+                        return Ok(Session::Churned);
+                    }
+                }
+                _ = submit.tick(), if authorized && self.submit_every.is_some() => {
+                    if let (Some(share), Some(height)) = (self.share.as_ref(), height) {
+                        framed.send(share.submit(height)).await?;
+                        pending.push_back(Instant::now());
+                        self.stats.update(|totals| totals.submits += 1);
+                    }
+                }
+                message = framed.next() => match message {
+                    Some(Ok(ProverMessage::AuthorizeResult(result, message))) => {
+                        self.stats.auth_latency.record(authorizing.elapsed());
+                        if !result {
+                            self.stats.update(|totals| totals.auth_rejected += 1);
+                            return Err(anyhow!("authorization rejected: {}", message.unwrap_or_default()));
+                        }
+                        authorized = true;
+                        self.stats.update(|totals| totals.authorized += 1);
+                    }
+                    Some(Ok(ProverMessage::Notify(template, ..))) => {
+                        height = Some(template.block_height());
+                        self.stats.update(|totals| totals.notifies += 1);
+                    }
+                    Some(Ok(ProverMessage::SubmitResult(code, _))) => {
+                        if let Some(sent) = pending.pop_front() {
+                            self.stats.submit_latency.record(sent.elapsed());
+                        }
+                        self.stats.update(|totals| *totals.results.entry(format!("{:?}", code)).or_default() += 1);
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(anyhow!("decode: {}", e)),
+                    None => return Err(anyhow!("the pool closed the connection")),
+                }
+            }
+        }
+    }
+}
+
+/// Runs the simulated miners and prints the totals every 10 seconds and at the end.
+pub async fn run(
+    options: LoadTest,
+    account: Option<&str>,
+    address: Option<&Address<Testnet2>>,
+    password: Secret,
+    limits: ProtocolLimits,
+) -> Result<()> {
+    let url = config::pool_address(&options.url)?.to_string();
+    if options.miners == 0 {
+        return Err(anyhow!("--miners must be at least 1"));
+    }
+    if !(0.0..=100.0).contains(&options.churn) {
+        return Err(anyhow!("--churn must be 0 to 100"));
+    }
+    let submit_every = Some(options.submit_rate)
+        .filter(|rate| options.fake_proofs && *rate > 0.0)
+        .map(|rate| Duration::from_secs_f64(60.0 / rate));
+    let share = if options.fake_proofs { Some(Arc::new(FakeShare::new())) } else { None };
+    let stats = Arc::new(Stats::default());
+    let started = Instant::now();
+    let until = started + options.ramp_up + options.duration;
+    println!(
+        "Starting {} miners against {} over {}s, running {}s{}",
+        options.miners,
+        url,
+        options.ramp_up.as_secs(),
+        options.duration.as_secs(),
+        if options.fake_proofs { "" } else { ", not submitting (no --fake-proofs)" }
+    );
+
+    let mut miners = Vec::new();
+    for index in 0..options.miners {
+        let miner = Miner {
+            url: url.clone(),
+            account: account.map(str::to_string),
+            address: address.copied(),
+            worker: format!("{}{}", options.worker_prefix, index),
+            password: password.clone(),
+            limits,
+            submit_every,
+            churn: options.churn / 100.0 / 60.0,
+            share: share.clone(),
+            stats: stats.clone(),
+        };
+        let start = started + options.ramp_up.mul_f64(index as f64 / options.miners as f64);
+        miners.push(tokio::spawn(async move {
+            sleep_until(start.into()).await;
+            miner.run(until).await;
+        }));
+    }
+
+    let mut report = interval(Duration::from_secs(10));
+    report.tick().await;
+    let finished = futures::future::join_all(miners);
+    tokio::pin!(finished);
+    loop {
+        tokio::select! {
+            _ = &mut finished => break,
+            _ = report.tick() => stats.print(started.elapsed()),
+        }
+    }
+    println!();
+    stats.print(started.elapsed());
+    Ok(())
+}
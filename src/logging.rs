@@ -0,0 +1,572 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Error, Result};
+use tracing::{
+    field::{Field, Visit},
+    Event as TracingEvent,
+    Subscriber,
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::{EnvFilter, LevelFilter},
+    fmt::{
+        self,
+        format::{Format, FmtSpan, Json, JsonFields},
+    },
+    layer::{Context as LayerContext, SubscriberExt},
+    registry::LookupSpan,
+    reload,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
+    /// Rotate once the file exceeds this many bytes
+    Size(u64),
+}
+
+impl FromStr for LogRotation {
+    type Err = Error;
+
+    /// Parses `never`, `hourly`, `daily` or a size like `100M`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "never" => Ok(Self::Never),
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            size => parse_size(size)
+                .map(Self::Size)
+                .map_err(|_| anyhow!("invalid log rotation {}: expected never, hourly, daily or a size", s)),
+        }
+    }
+}
+
+/// How the console and the log file lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with the current span and spans opening and closing, see `JobTrace`
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("invalid log format {}: expected text or json", s)),
+        }
+    }
+}
+
+/// Parses a size in bytes with an optional K, M or G suffix, e.g. `100M`.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let size = s.trim().to_ascii_lowercase();
+    let (value, multiplier) = match size.char_indices().last() {
+        Some((index, 'k')) => (&size[..index], 1024),
+        Some((index, 'm')) => (&size[..index], 1024 * 1024),
+        Some((index, 'g')) => (&size[..index], 1024 * 1024 * 1024),
+        _ => (size.as_str(), 1),
+    };
+    let value: u64 = value.trim().parse().map_err(|_| anyhow!("invalid size {}", s))?;
+    if value == 0 {
+        return Err(anyhow!("invalid size {}: must not be zero", s));
+    }
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("invalid size {}: too large", s))
+}
+
+// Overrides the filters built from the log levels when set.
+const FILTER_VARIABLE: &str = "RUST_LOG";
+
+/// Console level for `-q` (warnings only), the default, `-v` and `-vv`.
+pub fn verbosity_level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+/// Filter directives for a level: the level applies to the miner, dependencies only log warnings
+/// unless tracing, when snarkvm and tokio log at debug.
+pub fn filter_directives(level: LevelFilter) -> String {
+    let own = module_path!().split("::").next().unwrap_or_default();
+    if level <= LevelFilter::WARN {
+        level.to_string().to_ascii_lowercase()
+    } else if level == LevelFilter::TRACE {
+        format!("warn,snarkvm=debug,tokio=debug,{}=trace", own)
+    } else {
+        format!("warn,{}={}", own, level.to_string().to_ascii_lowercase())
+    }
+}
+
+/// Directives in effect for a level, `RUST_LOG` wins if set.
+pub fn effective_filter(level: LevelFilter) -> String {
+    match std::env::var(FILTER_VARIABLE) {
+        Ok(directives) if !directives.trim().is_empty() => directives,
+        _ => filter_directives(level),
+    }
+}
+
+fn env_filter(level: LevelFilter) -> Result<EnvFilter> {
+    let directives = effective_filter(level);
+    EnvFilter::try_new(&directives).map_err(|e| anyhow!("invalid log filter {}: {}", directives, e))
+}
+
+pub struct LogConfig {
+    pub console_level: LevelFilter,
+    pub file_level: LevelFilter,
+    pub format: LogFormat,
+    pub file: Option<PathBuf>,
+    pub rotation: LogRotation,
+    /// Number of rotated files to keep
+    pub keep: usize,
+    /// Log pane of the dashboard, replaces the console output
+    pub tui: Option<LogBuffer>,
+    /// Keeps the last lines at info level and above, for the run and crash reports
+    pub recent: Option<LogBuffer>,
+}
+
+/// Log file rotated by renaming it to `<name>.1`, `<name>.2`, ... once it exceeds the size limit.
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingFile {
+    fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated(self.keep));
+            for index in (1..self.keep).rev() {
+                let _ = fs::rename(self.rotated(index), self.rotated(index + 1));
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = File::create(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Shared handle so the file can be rotated between writes.
+#[derive(Clone)]
+struct SizeRotatingWriter(Arc<Mutex<SizeRotatingFile>>);
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file = self.0.lock().unwrap();
+        if file.size > 0 && file.size + buf.len() as u64 > file.max_size {
+            file.rotate()?;
+        }
+        let written = file.file.write(buf)?;
+        file.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().file.flush()
+    }
+}
+
+fn file_writer(path: &Path, rotation: LogRotation, keep: usize) -> Result<Box<dyn Write + Send>> {
+    if let LogRotation::Size(max_size) = rotation {
+        let file = SizeRotatingFile::open(path, max_size, keep)
+            .with_context(|| format!("unable to open log file {}", path.display()))?;
+        return Ok(Box::new(SizeRotatingWriter(Arc::new(Mutex::new(file)))));
+    }
+    let rotation = match rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        _ => Rotation::NEVER,
+    };
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = path
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid log file {}", path.display()))?;
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix.to_string_lossy())
+        .max_log_files(keep.max(1))
+        .build(directory)
+        .with_context(|| format!("unable to open log file {}", path.display()))?;
+    Ok(Box::new(appender))
+}
+
+type SetLevel = Box<dyn Fn(LevelFilter) -> Result<()> + Send + Sync>;
+
+/// Changes the log levels of the installed subscriber.
+pub struct LogLevels {
+    console: Vec<SetLevel>,
+    file: Option<SetLevel>,
+}
+
+impl LogLevels {
+    pub fn set(&self, console_level: LevelFilter, file_level: LevelFilter) -> Result<()> {
+        for set in self.console.iter() {
+            set(console_level)?;
+        }
+        if let Some(set) = self.file.as_ref() {
+            set(file_level)?;
+        }
+        Ok(())
+    }
+
+    /// Levels appending every console level set to `levels` instead of changing a subscriber.
+    #[cfg(test)]
+    pub(crate) fn recording(levels: Arc<Mutex<Vec<LevelFilter>>>) -> Self {
+        let set: SetLevel = Box::new(move |level| {
+            levels.lock().unwrap().push(level);
+            Ok(())
+        });
+        Self { console: vec![set], file: None }
+    }
+}
+
+fn reloadable<S: 'static>(level: LevelFilter) -> Result<(reload::Layer<EnvFilter, S>, SetLevel)> {
+    let (filter, handle) = reload::Layer::new(env_filter(level)?);
+    let set: SetLevel = Box::new(move |level| {
+        handle
+            .reload(env_filter(level)?)
+            .map_err(|e| anyhow!("unable to change the log level: {}", e))
+    });
+    Ok((filter, set))
+}
+
+/// Installs the global subscriber. The returned guard flushes the log file when dropped.
+pub fn init(config: LogConfig) -> Result<(Option<WorkerGuard>, LogLevels)> {
+    let mut levels = LogLevels {
+        console: Vec::new(),
+        file: None,
+    };
+    let json = config.format == LogFormat::Json;
+    let console = match config.tui {
+        Some(_) => None,
+        None if json => None,
+        None => {
+            let (filter, set) = reloadable(config.console_level)?;
+            levels.console.push(set);
+            Some(tracing_subscriber::fmt::layer().with_filter(filter))
+        }
+    };
+    let console_json = match config.tui {
+        None if json => {
+            let (filter, set) = reloadable(config.console_level)?;
+            levels.console.push(set);
+            Some(json_layer().with_filter(filter))
+        }
+        _ => None,
+    };
+    let dashboard = match config.tui {
+        Some(logs) => {
+            let (filter, set) = reloadable(config.console_level)?;
+            levels.console.push(set);
+            Some(LogLayer::new(logs).with_filter(filter))
+        }
+        None => None,
+    };
+    let recent = config
+        .recent
+        .map(|buffer| LogLayer::new(buffer).with_filter(LevelFilter::INFO));
+    let (file, file_json, guard) = match config.file.as_ref() {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(file_writer(path, config.rotation, config.keep)?);
+            let (filter, set) = reloadable(config.file_level)?;
+            levels.file = Some(set);
+            if json {
+                let layer = json_layer().with_writer(writer).with_filter(filter);
+                (None, Some(layer), Some(guard))
+            } else {
+                let layer = tracing_subscriber::fmt::layer()
+                    .with_writer(writer)
+                    .with_ansi(false)
+                    .with_filter(filter);
+                (Some(layer), None, Some(guard))
+            }
+        }
+        None => (None, None, None),
+    };
+    tracing_subscriber::registry()
+        .with(console)
+        .with(console_json)
+        .with(dashboard)
+        .with(recent)
+        .with(file)
+        .with(file_json)
+        .try_init()
+        .map_err(|e| anyhow!("unable to set global default subscriber: {}", e))?;
+    Ok((guard, levels))
+}
+
+// JSON lines with the spans an event is in, plus a line when a span opens and when it closes with
+// its busy and idle time.
+fn json_layer<S>() -> fmt::Layer<S, JsonFields, Format<Json>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+}
+
+/// Bounded buffer of formatted log lines, shown in the dashboard and the run and crash reports.
+#[derive(Clone)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The last `count` warnings and errors.
+    pub fn warnings(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let mut warnings: Vec<String> = lines
+            .iter()
+            .rev()
+            .filter(|line| matches!(line.split_whitespace().nth(1), Some("WARN") | Some("ERROR")))
+            .take(count)
+            .cloned()
+            .collect();
+        warnings.reverse();
+        warnings
+    }
+}
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Tracing layer writing log lines into a `LogBuffer` instead of stdout.
+pub struct LogLayer {
+    buffer: LogBuffer,
+}
+
+impl LogLayer {
+    pub fn new(buffer: LogBuffer) -> Self {
+        Self { buffer }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &TracingEvent<'_>, _ctx: LayerContext<'_, S>) {
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        // Log lines may contain ANSI colours meant for the console.
+        let message = strip_ansi(&visitor.0);
+        let time = chrono::Local::now().format("%H:%M:%S");
+        self.buffer.push(format!("{} {:>5} {}", time, event.metadata().level(), message));
+    }
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{debug, info, trace, warn};
+
+    use super::*;
+    use crate::testing;
+
+    fn rotated(path: &Path, index: usize) -> PathBuf {
+        let mut path = path.to_path_buf().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    // Logs a line at every level through a file layer filtered like `init` does, returns the file.
+    fn log_at(path: &Path, level: LevelFilter) -> String {
+        let writer = Mutex::new(file_writer(path, LogRotation::Size(1024 * 1024), 1).unwrap());
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_filter(EnvFilter::new(filter_directives(level)));
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            trace!("trace line");
+            debug!("debug line");
+            info!("info line");
+            warn!("warn line");
+        });
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn parses_sizes_and_rotations() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("100M").unwrap(), 100 * 1024 * 1024);
+        assert_eq!(parse_size(" 2 k").unwrap(), 2048);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("99999999999G").is_err());
+        assert_eq!("Daily".parse::<LogRotation>().unwrap(), LogRotation::Daily);
+        assert_eq!("1g".parse::<LogRotation>().unwrap(), LogRotation::Size(1024 * 1024 * 1024));
+        assert!("weekly".parse::<LogRotation>().is_err());
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    }
+
+    #[test]
+    fn filters_by_level() {
+        assert_eq!(filter_directives(LevelFilter::WARN), "warn");
+        assert_eq!(filter_directives(LevelFilter::DEBUG), "warn,aleoxminer=debug");
+        assert_eq!(filter_directives(LevelFilter::TRACE), "warn,snarkvm=debug,tokio=debug,aleoxminer=trace");
+        assert_eq!(verbosity_level(true, 2), LevelFilter::WARN);
+        assert_eq!(verbosity_level(false, 1), LevelFilter::DEBUG);
+    }
+
+    // The only test reading or writing RUST_LOG.
+    #[test]
+    fn rust_log_wins_over_the_level() {
+        std::env::set_var(FILTER_VARIABLE, "aleoxminer::client=trace");
+        assert_eq!(effective_filter(LevelFilter::WARN), "aleoxminer::client=trace");
+        std::env::set_var(FILTER_VARIABLE, " ");
+        assert_eq!(effective_filter(LevelFilter::WARN), "warn");
+        std::env::remove_var(FILTER_VARIABLE);
+        assert_eq!(effective_filter(LevelFilter::INFO), "warn,aleoxminer=info");
+    }
+
+    #[test]
+    fn writes_the_file_at_its_own_level() {
+        let dir = testing::scratch("logging", "levels");
+        let debug = log_at(&dir.join("debug.log"), LevelFilter::DEBUG);
+        assert!(debug.contains("debug line") && debug.contains("warn line"));
+        assert!(!debug.contains("trace line"));
+        let warn = log_at(&dir.join("warn.log"), LevelFilter::WARN);
+        assert!(warn.contains("warn line"));
+        assert!(!warn.contains("info line"));
+    }
+
+    #[test]
+    fn rotates_by_size_keeping_some_files() {
+        let path = testing::scratch("logging", "rotation").join("miner.log");
+        let mut writer = file_writer(&path, LogRotation::Size(100), 2).unwrap();
+        for line in 0..5 {
+            writer.write_all(format!("{:059}\n", line).as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        // Every line fills more than half a file, the newest is in the log file itself.
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{:059}\n", 4));
+        assert_eq!(fs::read_to_string(rotated(&path, 1)).unwrap(), format!("{:059}\n", 3));
+        assert_eq!(fs::read_to_string(rotated(&path, 2)).unwrap(), format!("{:059}\n", 2));
+        assert!(!rotated(&path, 3).exists());
+    }
+
+    #[test]
+    fn truncates_without_keeping_files() {
+        let path = testing::scratch("logging", "truncate").join("miner.log");
+        let mut writer = file_writer(&path, LogRotation::Size(100), 0).unwrap();
+        for line in 0..3 {
+            writer.write_all(format!("{:059}\n", line).as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{:059}\n", 2));
+        assert!(!rotated(&path, 1).exists());
+    }
+
+    #[test]
+    fn fails_on_a_path_it_cannot_open() {
+        let path = testing::scratch("logging", "missing").join("no such directory").join("miner.log");
+        let error = file_writer(&path, LogRotation::Size(100), 1).err().unwrap();
+        assert!(error.to_string().starts_with("unable to open log file"));
+    }
+
+    #[test]
+    fn keeps_the_last_lines_and_the_warnings() {
+        let buffer = LogBuffer::new(3);
+        for line in ["10:00:00  INFO a", "10:00:01  WARN b", "10:00:02 ERROR c", "10:00:03  INFO d"] {
+            buffer.push(line.to_string());
+        }
+        assert_eq!(buffer.lines(), vec!["10:00:01  WARN b", "10:00:02 ERROR c", "10:00:03  INFO d"]);
+        assert_eq!(buffer.warnings(1), vec!["10:00:02 ERROR c"]);
+        assert_eq!(strip_ansi("\u{1b}[1;32mShare found\u{1b}[0m"), "Share found");
+    }
+}
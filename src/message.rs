@@ -1,39 +1,201 @@
 use std::{
-    io::{Read, Cursor, Seek, Write},
-    marker::PhantomData,
-    net::SocketAddr,
-    str::FromStr
+    collections::BTreeMap,
+    io::{self, Read, Cursor, Seek, Write},
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
-use anyhow::{anyhow, Result};
+use bincode::Options;
 use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::{Buf, BufMut, BytesMut};
 use snarkvm::{
-    dpc::{testnet2::Testnet2, Address, BlockTemplate, PoSWProof},
+    dpc::{testnet2::Testnet2, BlockTemplate, PoSWProof},
     traits::Network,
     utilities::{FromBytes, ToBytes},
 };
 use tokio_util::codec::{Decoder, Encoder};
 use serde_json;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::trace;
 
+/// Why a message couldn't be encoded or decoded.
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error("Empty frame")]
+    EmptyFrame,
+    #[error("Frame of {length} bytes exceeds the {limit} byte limit")]
+    FrameTooLarge { length: usize, limit: usize },
+    #[error("{message} string field of {length} bytes exceeds the {limit} byte limit")]
+    StringTooLong {
+        message: &'static str,
+        length: usize,
+        limit: usize,
+    },
+    #[error("Unknown message id: {0}")]
+    UnknownMessage(u8),
+    #[error("Invalid protocol version: {0:?}")]
+    InvalidVersion(String),
+    #[error("{message} followed by {length} unexpected bytes")]
+    TrailingBytes { message: &'static str, length: usize },
+    #[error("Invalid binary field in {message}: {source}")]
+    Binary {
+        message: &'static str,
+        #[source]
+        source: bincode::Error,
+    },
+    #[error("Invalid JSON in {message}: {source}")]
+    Json {
+        message: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Unable to encode {message} as MessagePack: {source}")]
+    MessagePackEncode {
+        message: &'static str,
+        #[source]
+        source: rmp_serde::encode::Error,
+    },
+    #[error("Invalid MessagePack in {message}: {source}")]
+    MessagePackDecode {
+        message: &'static str,
+        #[source]
+        source: rmp_serde::decode::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Result of encoding or decoding a message.
+pub type Result<T, E = ProtocolError> = std::result::Result<T, E>;
+
+// Attaches the message name to bincode, serde_json and rmp_serde errors.
+trait Context<T> {
+    fn context(self, message: &'static str) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, bincode::Error> {
+    fn context(self, message: &'static str) -> Result<T> {
+        self.map_err(|source| ProtocolError::Binary { message, source })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, serde_json::Error> {
+    fn context(self, message: &'static str) -> Result<T> {
+        self.map_err(|source| ProtocolError::Json { message, source })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, rmp_serde::encode::Error> {
+    fn context(self, message: &'static str) -> Result<T> {
+        self.map_err(|source| ProtocolError::MessagePackEncode { message, source })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, rmp_serde::decode::Error> {
+    fn context(self, message: &'static str) -> Result<T> {
+        self.map_err(|source| ProtocolError::MessagePackDecode { message, source })
+    }
+}
+
+/// Result code of a submitted share.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Code {
     Success = 0,
     InvalidProof,
     Stale,
-    ProxyException
+    ProxyException,
+    /// Codes added to the protocol after this version
+    #[serde(other)]
+    Other,
+}
+
+/// A password, printed as `***` so it stays out of logs and `Debug` output.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// The actual value, only to be used when it is sent or stored.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// The password with its `key=` field set to `value`, other comma separated fields are kept,
+    /// e.g. `x` becomes `x,d=5000`.
+    pub fn with_field(&self, key: &str, value: impl std::fmt::Display) -> Self {
+        let prefix = format!("{}=", key);
+        let mut fields: Vec<String> = self
+            .0
+            .split(',')
+            .filter(|field| !field.is_empty() && !field.trim().starts_with(&prefix))
+            .map(str::to_string)
+            .collect();
+        fields.push(format!("{}{}", prefix, value));
+        Self(fields.join(","))
+    }
+
+    /// The password with its `d=` difficulty hint set to `difficulty`.
+    pub fn with_difficulty(&self, difficulty: u64) -> Self {
+        self.with_field("d", difficulty)
+    }
 }
 
+/// Value of the `key=` field of a comma separated password.
+pub fn password_field<'a>(password: &'a str, key: &str) -> Option<&'a str> {
+    password
+        .split(',')
+        .find_map(|field| field.trim().strip_prefix(key)?.strip_prefix('='))
+}
+
+/// Share difficulty asked for with a `d=NNNN` field of a comma separated password, as pools of other
+/// coins do.
+pub fn difficulty_hint(password: &str) -> Option<u64> {
+    password_field(password, "d")
+        .and_then(|difficulty| difficulty.parse().ok())
+        .filter(|&difficulty| difficulty > 0)
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+/// Message between the prover and the pool.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum ProverMessage {
     // as in stratum, with an additional protocol version field
     /// Authorize := (account, worker, password, version)
-    Authorize(String, String, String, u16),
+    Authorize(String, String, Secret, u16),
     AuthorizeResult(bool, Option<String>),
     // combine notify and pool_target to be consistent
-    Notify(BlockTemplate<Testnet2>, u64),
+    /// Notify := (template, pool_target, reorg, speculative), the flags are left out on the wire unless
+    /// set. A speculative job is for the next height and only mined once activated
+    Notify(BlockTemplate<Testnet2>, u64, bool, bool),
     // include block height to detect stales faster
     Submit(u32, <Testnet2 as Network>::PoSWNonce, PoSWProof<Testnet2>),
     // miners might want to know the stale rate, optionally provide a message
@@ -41,19 +203,170 @@ pub enum ProverMessage {
     SubmitResult(Code, Option<String>),
     /// ProofRate := (p/s * 100)
     ProofRate(u64),
+    /// ActivateJob := (height), switches to the speculative job for that height. Jobs have no other id
+    ActivateJob(u32),
+    /// JobAck := (height, pool_target) of the job the miner is proving, sent with each proof rate report
+    JobAck(u32, u64),
+    /// PoolInfo := (fields), what the pool tells the miner about its account, e.g. `balance`,
+    /// `last_payout` and `next_payout_estimate`. Every field is kept, known or not
+    PoolInfo(BTreeMap<String, String>),
+    /// Worker := (worker) the next Submit is a share of, for miners submitting the shares of several
+    /// workers, e.g. a proxy. A Submit without one is the authorized worker's
+    Worker(String),
 
     Canary,
 }
 
-#[allow(dead_code)]
+// Proofs have no PartialEq, they compare by their encoding.
+impl PartialEq for ProverMessage {
+    fn eq(&self, other: &Self) -> bool {
+        fn bytes<T: ToBytes>(value: &T) -> Option<Vec<u8>> {
+            value.to_bytes_le().ok()
+        }
+        match (self, other) {
+            (Self::Authorize(a1, w1, p1, v1), Self::Authorize(a2, w2, p2, v2)) => {
+                a1 == a2 && w1 == w2 && p1 == p2 && v1 == v2
+            }
+            (Self::AuthorizeResult(r1, m1), Self::AuthorizeResult(r2, m2)) => r1 == r2 && m1 == m2,
+            (Self::Notify(t1, p1, r1, s1), Self::Notify(t2, p2, r2, s2)) => {
+                p1 == p2 && r1 == r2 && s1 == s2 && t1 == t2
+            }
+            (Self::Submit(h1, n1, p1), Self::Submit(h2, n2, p2)) => h1 == h2 && n1 == n2 && bytes(p1) == bytes(p2),
+            (Self::SubmitResult(c1, m1), Self::SubmitResult(c2, m2)) => c1 == c2 && m1 == m2,
+            (Self::ProofRate(r1), Self::ProofRate(r2)) => r1 == r2,
+            (Self::ActivateJob(h1), Self::ActivateJob(h2)) => h1 == h2,
+            (Self::JobAck(h1, p1), Self::JobAck(h2, p2)) => h1 == h2 && p1 == p2,
+            (Self::PoolInfo(f1), Self::PoolInfo(f2)) => f1 == f2,
+            (Self::Worker(w1), Self::Worker(w2)) => w1 == w2,
+            (Self::Canary, Self::Canary) => true,
+            _ => false,
+        }
+    }
+}
+
 static VERSION: u16 = 1;
 
+/// Set in the Authorize version by provers reading MessagePack frames. The pool answers with
+/// MessagePack frames if it does too, from then on both sides use it.
+pub const MSGPACK_CAPABILITY: u16 = 0x100;
+/// Set in the id of MessagePack frames.
+pub const MSGPACK_FLAG: u8 = 0x80;
+
+/// Reason of an AuthorizeResult revoking an earlier authorization, the prover authorizes again.
+pub const AUTHORIZATION_REVOKED: &str = "revoked";
+
+/// Offered in the Authorize version by provers resuming pool sessions. The pool answers such an
+/// Authorize with `session=TOKEN` as the AuthorizeResult message, and the prover sends the token
+/// back in a `session=TOKEN` password field when it authorizes again, even after a restart. A pool
+/// not recognizing the token starts a new session and answers with a new token.
+pub const SESSION_CAPABILITY: u16 = 0x200;
+
+/// Prefix of the session token in an AuthorizeResult message, and the password field holding it.
+pub const SESSION_TOKEN_PREFIX: &str = "session=";
+
+/// Offered in the Authorize version by provers taking speculative jobs. Only such provers get a
+/// speculative Notify for the next height ahead of time, and the ActivateJob switching to it.
+pub const SPECULATIVE_CAPABILITY: u16 = 0x400;
+
+/// Offered in the Authorize version by provers sending a JobAck with each ProofRate, so the pool can
+/// tell a miner still proving an earlier job without waiting for its next share. Only sent when
+/// enabled, pools not knowing the message would close the connection.
+pub const JOB_ACK_CAPABILITY: u16 = 0x800;
+
+/// Offered in the Authorize version by provers showing a PoolInfo, only they are sent one.
+pub const POOL_INFO_CAPABILITY: u16 = 0x1000;
+
+/// Offered in the Authorize version by miners naming the worker of a share with a Worker before the
+/// Submit. Only sent when enabled, pools not knowing the message would close the connection.
+pub const MULTIPLEX_CAPABILITY: u16 = 0x2000;
+
+/// PoolInfo fields shown in the stats line, in this order before any others.
+pub const POOL_INFO_FIELDS: [&str; 3] = ["balance", "last_payout", "next_payout_estimate"];
+
+/// PoolInfo fields for a log line, the `POOL_INFO_FIELDS` first.
+pub fn pool_info_line(fields: &BTreeMap<String, String>) -> String {
+    let known = POOL_INFO_FIELDS
+        .iter()
+        .filter_map(|key| fields.get_key_value(*key));
+    let others = fields
+        .iter()
+        .filter(|(key, _)| !POOL_INFO_FIELDS.contains(&key.as_str()));
+    known
+        .chain(others)
+        .map(|(key, value)| format!("{} {}", key.replace('_', " "), value))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Bits of the flags byte ending a binary Notify.
+const NOTIFY_REORG: u8 = 1;
+const NOTIFY_SPECULATIVE: u8 = 2;
+
+/// Names of the capability bits set in an Authorize version.
+pub fn capability_names(version: u16) -> Vec<&'static str> {
+    [
+        (MSGPACK_CAPABILITY, "msgpack"),
+        (SESSION_CAPABILITY, "session"),
+        (SPECULATIVE_CAPABILITY, "speculative"),
+        (JOB_ACK_CAPABILITY, "job_ack"),
+        (POOL_INFO_CAPABILITY, "pool_info"),
+        (MULTIPLEX_CAPABILITY, "multiplex"),
+    ]
+        .iter()
+        .filter(|(capability, _)| version & capability != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+impl Code {
+    fn from_u8(code: u8) -> Self {
+        match code {
+            0 => Code::Success,
+            1 => Code::InvalidProof,
+            2 => Code::Stale,
+            3 => Code::ProxyException,
+            _ => Code::Other,
+        }
+    }
+}
+
+// Matches `bincode::serialize_into`, with a length limit so a bogus length prefix can't allocate
+// more than any field may hold.
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(*STRING_LENGTH_RANGE.end() as u64)
+}
+
+// Notify read from an array of two to four fields.
+#[derive(Deserialize)]
+struct NotifyFields<T> {
+    template: T,
+    pool_target: u64,
+    #[serde(default)]
+    reorg: bool,
+    #[serde(default)]
+    speculative: bool,
+}
+
+fn le_bytes<T: ToBytes>(value: &T) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    value.write_le(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn parse_version(version: &str) -> Result<u16> {
+    u16::from_str(version).map_err(|_| ProtocolError::InvalidVersion(version.to_string()))
+}
+
 impl ProverMessage {
-    #[allow(dead_code)]
+    /// Protocol version sent with Authorize.
     pub fn version() -> &'static u16 {
         &VERSION
     }
 
+    /// Id of the message on the wire.
     pub fn id(&self) -> u8 {
         match self {
             ProverMessage::Authorize(..) => 0,
@@ -62,11 +375,34 @@ impl ProverMessage {
             ProverMessage::Submit(..) => 3,
             ProverMessage::SubmitResult(..) => 4,
             ProverMessage::ProofRate(..) => 6,
+            ProverMessage::ActivateJob(..) => 7,
+            ProverMessage::JobAck(..) => 8,
+            ProverMessage::PoolInfo(..) => 9,
+            ProverMessage::Worker(..) => 10,
 
             ProverMessage::Canary => 5,
         }
     }
 
+    /// Name of the message with wire id `id`, without `MSGPACK_FLAG`.
+    pub fn name_of(id: u8) -> &'static str {
+        match id {
+            0 => "Authorize",
+            1 => "AuthorizeResult",
+            2 => "Notify",
+            3 => "Submit",
+            4 => "SubmitResult",
+            5 => "Canary",
+            6 => "ProofRate",
+            7 => "ActivateJob",
+            8 => "JobAck",
+            9 => "PoolInfo",
+            10 => "Worker",
+            _ => "unknown",
+        }
+    }
+
+    /// Name for logs.
     pub fn name(&self) -> &'static str {
         match self {
             ProverMessage::Authorize(..) => "Authorize",
@@ -75,20 +411,25 @@ impl ProverMessage {
             ProverMessage::Submit(..) => "Submit",
             ProverMessage::SubmitResult(..) => "SubmitResult",
             ProverMessage::ProofRate(..) => "ProofRate",
+            ProverMessage::ActivateJob(..) => "ActivateJob",
+            ProverMessage::JobAck(..) => "JobAck",
+            ProverMessage::PoolInfo(..) => "PoolInfo",
+            ProverMessage::Worker(..) => "Worker",
 
             ProverMessage::Canary => "Canary",
         }
     }
 
+    /// Writes the binary encoding of the fields, without the id.
     #[inline]
     pub fn serialize_into<W: Write>(&self, writer: &mut W) -> Result<()> {
         match self {
             Self::Authorize(account, worker, password, version) => {
-                bincode::serialize_into(&mut *writer, &account)?;
-                bincode::serialize_into(&mut *writer, &worker)?;
-                bincode::serialize_into(&mut *writer, &password)?;
+                bincode::serialize_into(&mut *writer, &account).context("Authorize")?;
+                bincode::serialize_into(&mut *writer, &worker).context("Authorize")?;
+                bincode::serialize_into(&mut *writer, password.expose()).context("Authorize")?;
                 let version = version.to_string();
-                bincode::serialize_into(&mut *writer, &version)?;
+                bincode::serialize_into(&mut *writer, &version).context("Authorize")?;
                 Ok(())
             }
             Self::AuthorizeResult(result, message) => {
@@ -98,15 +439,19 @@ impl ProverMessage {
                 }])?;
                 if let Some(message) = message {
                     writer.write_all(&[1])?;
-                    bincode::serialize_into(&mut *writer, &message)?;
+                    bincode::serialize_into(&mut *writer, &message).context("AuthorizeResult")?;
                 } else {
                     writer.write_all(&[0])?;
                 }
                 Ok(())
             }
-            Self::Notify(template, pool_target) => {
+            Self::Notify(template, pool_target, reorg, speculative) => {
                 template.write_le(&mut *writer)?;
                 writer.write_all(&pool_target.to_le_bytes())?;
+                let flags = if *reorg { NOTIFY_REORG } else { 0 } | if *speculative { NOTIFY_SPECULATIVE } else { 0 };
+                if flags != 0 {
+                    writer.write_all(&[flags])?;
+                }
                 Ok(())
             }
             Self::Submit(height, nonce, proof) => {
@@ -119,11 +464,28 @@ impl ProverMessage {
                 writer.write_all(&proof_rate.to_le_bytes())?;
                 Ok(())
             }
+            Self::ActivateJob(height) => {
+                writer.write_all(&height.to_le_bytes())?;
+                Ok(())
+            }
+            Self::JobAck(height, pool_target) => {
+                writer.write_all(&height.to_le_bytes())?;
+                writer.write_all(&pool_target.to_le_bytes())?;
+                Ok(())
+            }
+            Self::PoolInfo(fields) => {
+                bincode::serialize_into(&mut *writer, fields).context("PoolInfo")?;
+                Ok(())
+            }
+            Self::Worker(worker) => {
+                bincode::serialize_into(&mut *writer, worker).context("Worker")?;
+                Ok(())
+            }
             Self::SubmitResult(code, message) => {
-                bincode::serialize_into(&mut *writer, &code)?;
+                bincode::serialize_into(&mut *writer, &code).context("SubmitResult")?;
                 if let Some(message) = message {
                     writer.write_all(&[1])?;
-                    bincode::serialize_into(&mut *writer, &message)?;
+                    bincode::serialize_into(&mut *writer, &message).context("SubmitResult")?;
                 } else {
                     writer.write_all(&[0])?;
                 }
@@ -133,12 +495,13 @@ impl ProverMessage {
         }
     }
 
+    /// Writes the JSON encoding of the fields, without the id.
     #[inline]
     pub fn serialize_into_json<W: Write>(&self, writer: &mut W) -> Result<()> {
         match self {
             Self::Authorize(account, worker, password, version) => {
                 let version = version.to_string();
-                serde_json::to_writer(writer, &(account, worker, password, version))?;
+                serde_json::to_writer(writer, &(account, worker, password.expose(), version)).context("Authorize")?;
                 Ok(())
             }
             Self::AuthorizeResult(result, message) => {
@@ -148,29 +511,53 @@ impl ProverMessage {
                 }])?;
                 if let Some(message) = message {
                     writer.write_all(&[1])?;
-                    serde_json::to_writer(&mut *writer, message)?;
+                    serde_json::to_writer(&mut *writer, message).context("AuthorizeResult")?;
                 } else {
                     writer.write_all(&[0])?;
                 }
                 Ok(())
             }
-            Self::Notify(template, pool_target) => {
-                serde_json::to_writer(&mut *writer, &(template, pool_target))?;
+            Self::Notify(template, pool_target, false, false) => {
+                serde_json::to_writer(&mut *writer, &(template, pool_target)).context("Notify")?;
+                Ok(())
+            }
+            Self::Notify(template, pool_target, reorg, false) => {
+                serde_json::to_writer(&mut *writer, &(template, pool_target, reorg)).context("Notify")?;
+                Ok(())
+            }
+            Self::Notify(template, pool_target, reorg, true) => {
+                serde_json::to_writer(&mut *writer, &(template, pool_target, reorg, true)).context("Notify")?;
                 Ok(())
             }
             Self::Submit(height, nonce, proof) => {
-                serde_json::to_writer(&mut *writer, &(height, nonce, proof))?;
+                serde_json::to_writer(&mut *writer, &(height, nonce, proof)).context("Submit")?;
                 Ok(())
             }
             Self::ProofRate(proof_rate) => {
-                serde_json::to_writer(&mut *writer, &proof_rate)?;
+                serde_json::to_writer(&mut *writer, &proof_rate).context("ProofRate")?;
+                Ok(())
+            }
+            Self::ActivateJob(height) => {
+                serde_json::to_writer(&mut *writer, &height).context("ActivateJob")?;
+                Ok(())
+            }
+            Self::JobAck(height, pool_target) => {
+                serde_json::to_writer(&mut *writer, &(height, pool_target)).context("JobAck")?;
+                Ok(())
+            }
+            Self::PoolInfo(fields) => {
+                serde_json::to_writer(&mut *writer, fields).context("PoolInfo")?;
+                Ok(())
+            }
+            Self::Worker(worker) => {
+                serde_json::to_writer(&mut *writer, worker).context("Worker")?;
                 Ok(())
             }
             Self::SubmitResult(code, message) => {
-                serde_json::to_writer(&mut *writer, code)?;
+                serde_json::to_writer(&mut *writer, code).context("SubmitResult")?;
                 if let Some(message) = message {
                     writer.write_all(&[1])?;
-                    serde_json::to_writer(&mut *writer, message)?;
+                    serde_json::to_writer(&mut *writer, message).context("SubmitResult")?;
                 } else {
                     writer.write_all(&[0])?;
                 }
@@ -180,22 +567,23 @@ impl ProverMessage {
         }
     }
 
+    /// Reads the id and the binary encoding of the fields.
     #[inline]
     pub fn deserialize<R: Read + Seek>(reader: &mut R) -> Result<Self> {
         let msg_id = reader.read_u8()?;
 
         let message = match msg_id {
             0 => {
-                let account = bincode::deserialize_from(&mut *reader)?;
-                let worker = bincode::deserialize_from(&mut *reader)?;
-                let password = bincode::deserialize_from(&mut *reader)?;
-                let version: String = bincode::deserialize_from(&mut *reader)?;
-                Self::Authorize(account, worker, password, version.parse::<u16>().unwrap())
+                let account = bincode_options().deserialize_from(&mut *reader).context("Authorize")?;
+                let worker = bincode_options().deserialize_from(&mut *reader).context("Authorize")?;
+                let password = bincode_options().deserialize_from(&mut *reader).context("Authorize")?;
+                let version: String = bincode_options().deserialize_from(&mut *reader).context("Authorize")?;
+                Self::Authorize(account, worker, password, parse_version(&version)?)
             }
             1 => {
                 let result = reader.read_u8()? == 1;
                 let message = if reader.read_u8()? == 1 {
-                    Some(bincode::deserialize_from(reader)?)
+                    Some(bincode_options().deserialize_from(reader).context("AuthorizeResult")?)
                 } else {
                     None
                 };
@@ -204,7 +592,26 @@ impl ProverMessage {
             2 => {
                 let template = BlockTemplate::<Testnet2>::read_le(&mut *reader)?;
                 let pool_target = reader.read_u64::<LittleEndian>()?;
-                Self::Notify(template, pool_target)
+                let flags = match reader.read_u8() {
+                    Ok(flags) => flags,
+                    // Pools predating the flags end the message here.
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+                    Err(e) => return Err(e.into()),
+                };
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest)?;
+                if !rest.is_empty() {
+                    return Err(ProtocolError::TrailingBytes {
+                        message: "Notify",
+                        length: rest.len(),
+                    });
+                }
+                Self::Notify(
+                    template,
+                    pool_target,
+                    flags & NOTIFY_REORG != 0,
+                    flags & NOTIFY_SPECULATIVE != 0,
+                )
             }
             3 => {
                 let height = reader.read_u32::<LittleEndian>()?;
@@ -213,60 +620,198 @@ impl ProverMessage {
                 Self::Submit(height, nonce, proof)
             }
             4 => {
-                let code = bincode::deserialize_from(&mut *reader)?;
+                let code = bincode_options().deserialize_from(&mut *reader).context("SubmitResult")?;
                 let message = if reader.read_u8()? == 1 {
-                    Some(bincode::deserialize_from(reader)?)
+                    Some(bincode_options().deserialize_from(reader).context("SubmitResult")?)
                 } else {
                     None
                 };
                 Self::SubmitResult(code, message)
             }
+            5 => Self::Canary,
+            6 => Self::ProofRate(reader.read_u64::<LittleEndian>()?),
+            7 => Self::ActivateJob(reader.read_u32::<LittleEndian>()?),
+            8 => {
+                let height = reader.read_u32::<LittleEndian>()?;
+                let pool_target = reader.read_u64::<LittleEndian>()?;
+                Self::JobAck(height, pool_target)
+            }
+            9 => Self::PoolInfo(bincode_options().deserialize_from(&mut *reader).context("PoolInfo")?),
+            10 => Self::Worker(bincode_options().deserialize_from(&mut *reader).context("Worker")?),
             _ => {
-                return Err(anyhow!("Unknown message id: {}", msg_id));
+                return Err(ProtocolError::UnknownMessage(msg_id));
             }
         };
 
         Ok(message)
     }
 
+    /// Reads the id and the JSON encoding of the fields.
     #[inline]
     pub fn deserialize_json<R: Read + Seek>(reader: &mut R) -> Result<Self> {
         let msg_id = reader.read_u8()?;
 
         let message = match msg_id {
             0 => {
-                let (account, worker, password, version): (String, String, String, String) = serde_json::from_reader(&mut *reader)?;
-                let version = u16::from_str(&version).unwrap();
-                Self::Authorize(account, worker, password, version)
+                let (account, worker, password, version): (String, String, String, String) =
+                    serde_json::from_reader(&mut *reader).context("Authorize")?;
+                let version = parse_version(&version)?;
+                Self::Authorize(account, worker, Secret::from(password), version)
             }
             1 => {
                 let result = reader.read_u8()? == 1;
                 let message = if reader.read_u8()? == 1 {
-                    Some(serde_json::from_reader(&mut *reader)?)
+                    Some(serde_json::from_reader(&mut *reader).context("AuthorizeResult")?)
                 } else {
                     None
                 };
                 Self::AuthorizeResult(result, message)
             }
             2 => {
-                let (template, pool_target) = serde_json::from_reader(&mut *reader)?;
-                Self::Notify(template, pool_target)
+                let notify: NotifyFields<BlockTemplate<Testnet2>> =
+                    serde_json::from_reader(&mut *reader).context("Notify")?;
+                Self::Notify(notify.template, notify.pool_target, notify.reorg, notify.speculative)
             }
             3 => {
-                let (height, nonce, proof) = serde_json::from_reader(&mut *reader)?;
+                let (height, nonce, proof) = serde_json::from_reader(&mut *reader).context("Submit")?;
                 Self::Submit(height, nonce, proof)
             }
             4 => {
-                let code = serde_json::from_reader(&mut *reader)?;
+                let code = serde_json::from_reader(&mut *reader).context("SubmitResult")?;
                 let message = if reader.read_u8()? == 1 {
-                    Some(serde_json::from_reader(&mut *reader)?)
+                    Some(serde_json::from_reader(&mut *reader).context("SubmitResult")?)
                 } else {
                     None
                 };
                 Self::SubmitResult(code, message)
             }
+            5 => Self::Canary,
+            6 => Self::ProofRate(serde_json::from_reader(&mut *reader).context("ProofRate")?),
+            7 => Self::ActivateJob(serde_json::from_reader(&mut *reader).context("ActivateJob")?),
+            8 => {
+                let (height, pool_target) = serde_json::from_reader(&mut *reader).context("JobAck")?;
+                Self::JobAck(height, pool_target)
+            }
+            9 => Self::PoolInfo(serde_json::from_reader(&mut *reader).context("PoolInfo")?),
+            10 => Self::Worker(serde_json::from_reader(&mut *reader).context("Worker")?),
+            _ => {
+                return Err(ProtocolError::UnknownMessage(msg_id));
+            }
+        };
+
+        Ok(message)
+    }
+
+    /// Writes the MessagePack encoding of the fields, without the id. The fields are one array in
+    /// the order of the message, a template or proof is the binary encoding and a code its number:
+    ///
+    /// Authorize := [account, worker, password, version]
+    /// AuthorizeResult := [result, message or nil]
+    /// Notify := [template, pool_target, reorg, speculative], the flags only up to the last one set
+    /// Submit := [height, nonce, proof]
+    /// SubmitResult := [code, message or nil]
+    /// ProofRate := [rate]
+    /// ActivateJob := [height]
+    /// JobAck := [height, pool_target]
+    /// PoolInfo := [fields], the fields a map of strings
+    /// Worker := [worker]
+    /// Canary has no fields
+    #[inline]
+    pub fn serialize_into_msgpack<W: Write>(&self, writer: &mut W) -> Result<()> {
+        match self {
+            Self::Authorize(account, worker, password, version) => {
+                rmp_serde::encode::write(writer, &(account, worker, password.expose(), version)).context("Authorize")
+            }
+            Self::AuthorizeResult(result, message) => {
+                rmp_serde::encode::write(writer, &(result, message)).context("AuthorizeResult")
+            }
+            Self::Notify(template, pool_target, reorg, speculative) => {
+                let template = le_bytes(template)?;
+                let template = serde_bytes::Bytes::new(&template);
+                match (reorg, speculative) {
+                    (false, false) => rmp_serde::encode::write(writer, &(template, pool_target)),
+                    (reorg, false) => rmp_serde::encode::write(writer, &(template, pool_target, reorg)),
+                    (reorg, true) => rmp_serde::encode::write(writer, &(template, pool_target, reorg, true)),
+                }
+                .context("Notify")
+            }
+            Self::Submit(height, nonce, proof) => {
+                let (nonce, proof) = (le_bytes(nonce)?, le_bytes(proof)?);
+                let fields = (height, serde_bytes::Bytes::new(&nonce), serde_bytes::Bytes::new(&proof));
+                rmp_serde::encode::write(writer, &fields).context("Submit")
+            }
+            Self::SubmitResult(code, message) => {
+                rmp_serde::encode::write(writer, &(code.clone() as u8, message)).context("SubmitResult")
+            }
+            Self::ProofRate(proof_rate) => rmp_serde::encode::write(writer, &(proof_rate,)).context("ProofRate"),
+            Self::ActivateJob(height) => rmp_serde::encode::write(writer, &(height,)).context("ActivateJob"),
+            Self::JobAck(height, pool_target) => {
+                rmp_serde::encode::write(writer, &(height, pool_target)).context("JobAck")
+            }
+            Self::PoolInfo(fields) => rmp_serde::encode::write(writer, &(fields,)).context("PoolInfo"),
+            Self::Worker(worker) => rmp_serde::encode::write(writer, &(worker,)).context("Worker"),
+            Self::Canary => Ok(()),
+        }
+    }
+
+    /// Reads the id, with or without `MSGPACK_FLAG`, and the MessagePack encoding of the fields.
+    #[inline]
+    pub fn deserialize_msgpack<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let msg_id = reader.read_u8()? & !MSGPACK_FLAG;
+
+        let message = match msg_id {
+            0 => {
+                let (account, worker, password, version): (String, String, String, u16) =
+                    rmp_serde::decode::from_read(&mut *reader).context("Authorize")?;
+                Self::Authorize(account, worker, Secret::from(password), version)
+            }
+            1 => {
+                let (result, message) = rmp_serde::decode::from_read(&mut *reader).context("AuthorizeResult")?;
+                Self::AuthorizeResult(result, message)
+            }
+            2 => {
+                let notify: NotifyFields<serde_bytes::ByteBuf> =
+                    rmp_serde::decode::from_read(&mut *reader).context("Notify")?;
+                Self::Notify(
+                    BlockTemplate::read_le(&notify.template[..])?,
+                    notify.pool_target,
+                    notify.reorg,
+                    notify.speculative,
+                )
+            }
+            3 => {
+                let (height, nonce, proof): (u32, serde_bytes::ByteBuf, serde_bytes::ByteBuf) =
+                    rmp_serde::decode::from_read(&mut *reader).context("Submit")?;
+                Self::Submit(height, FromBytes::read_le(&nonce[..])?, PoSWProof::read_le(&proof[..])?)
+            }
+            4 => {
+                let (code, message): (u8, Option<String>) =
+                    rmp_serde::decode::from_read(&mut *reader).context("SubmitResult")?;
+                Self::SubmitResult(Code::from_u8(code), message)
+            }
+            5 => Self::Canary,
+            6 => {
+                let (proof_rate,) = rmp_serde::decode::from_read(&mut *reader).context("ProofRate")?;
+                Self::ProofRate(proof_rate)
+            }
+            7 => {
+                let (height,) = rmp_serde::decode::from_read(&mut *reader).context("ActivateJob")?;
+                Self::ActivateJob(height)
+            }
+            8 => {
+                let (height, pool_target) = rmp_serde::decode::from_read(&mut *reader).context("JobAck")?;
+                Self::JobAck(height, pool_target)
+            }
+            9 => {
+                let (fields,) = rmp_serde::decode::from_read(&mut *reader).context("PoolInfo")?;
+                Self::PoolInfo(fields)
+            }
+            10 => {
+                let (worker,) = rmp_serde::decode::from_read(&mut *reader).context("Worker")?;
+                Self::Worker(worker)
+            }
             _ => {
-                return Err(anyhow!("Unknown message id: {}", msg_id));
+                return Err(ProtocolError::UnknownMessage(msg_id));
             }
         };
 
@@ -274,60 +819,227 @@ impl ProverMessage {
     }
 }
 
-impl Encoder<ProverMessage> for ProverMessage {
-    type Error = anyhow::Error;
+/// Bounds on what the pool may send, a frame or string above them closes the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// Bytes of one message, without the length prefix
+    pub max_frame_size: usize,
+    /// Bytes of a string field, e.g. the reason of a SubmitResult
+    pub max_string_length: usize,
+}
+
+/// Accepted values of `ProtocolLimits::max_frame_size`.
+pub const FRAME_SIZE_RANGE: RangeInclusive<usize> = 1024..=1024 * 1024 * 1024;
+/// Accepted values of `ProtocolLimits::max_string_length`.
+pub const STRING_LENGTH_RANGE: RangeInclusive<usize> = 16..=1024 * 1024;
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            max_frame_size: 128 * 1024 * 1024,
+            max_string_length: 4096,
+        }
+    }
+}
+
+impl ProtocolLimits {
+    fn check_strings(&self, message: &ProverMessage) -> Result<()> {
+        let strings: Vec<&str> = match message {
+            ProverMessage::Authorize(account, worker, password, _) => vec![account, worker, password.expose()],
+            ProverMessage::AuthorizeResult(_, message) | ProverMessage::SubmitResult(_, message) => {
+                message.as_deref().into_iter().collect()
+            }
+            ProverMessage::PoolInfo(fields) => fields
+                .iter()
+                .flat_map(|(key, value)| [key.as_str(), value.as_str()])
+                .collect(),
+            ProverMessage::Worker(worker) => vec![worker],
+            _ => vec![],
+        };
+        match strings.iter().find(|string| string.len() > self.max_string_length) {
+            Some(string) => Err(ProtocolError::StringTooLong {
+                message: message.name(),
+                length: string.len(),
+                limit: self.max_string_length,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+// Message ids 0 to 10, and one slot for unknown ids.
+const COUNTED_IDS: usize = 12;
+
+#[derive(Debug, Default)]
+struct FrameCounter {
+    frames: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Frames and bytes through a codec per message type, the length prefix included. Shared by the
+/// codecs of successive connections.
+#[derive(Debug, Default)]
+pub struct CodecStats {
+    encoded: [FrameCounter; COUNTED_IDS],
+    decoded: [FrameCounter; COUNTED_IDS],
+}
+
+/// Traffic of one message type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MessageTraffic {
+    pub message: &'static str,
+    pub encoded_frames: u64,
+    pub encoded_bytes: u64,
+    pub decoded_frames: u64,
+    pub decoded_bytes: u64,
+}
+
+impl CodecStats {
+    #[inline]
+    fn record(counters: &[FrameCounter; COUNTED_IDS], id: u8, bytes: usize) {
+        let counter = &counters[((id & !MSGPACK_FLAG) as usize).min(COUNTED_IDS - 1)];
+        counter.frames.fetch_add(1, Ordering::Relaxed);
+        counter.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Totals of the message types seen in either direction, by id.
+    pub fn snapshot(&self) -> Vec<MessageTraffic> {
+        (0..COUNTED_IDS)
+            .map(|id| MessageTraffic {
+                message: ProverMessage::name_of(id as u8),
+                encoded_frames: self.encoded[id].frames.load(Ordering::Relaxed),
+                encoded_bytes: self.encoded[id].bytes.load(Ordering::Relaxed),
+                decoded_frames: self.decoded[id].frames.load(Ordering::Relaxed),
+                decoded_bytes: self.decoded[id].bytes.load(Ordering::Relaxed),
+            })
+            .filter(|traffic| traffic.encoded_frames + traffic.decoded_frames > 0)
+            .collect()
+    }
+}
+
+/// Length prefixed framing of `ProverMessage`s.
+#[derive(Debug, Clone, Default)]
+pub struct ProverCodec {
+    limits: ProtocolLimits,
+    // Set once the peer is known to read MessagePack, every frame but Authorize is sent as such.
+    msgpack: bool,
+    // Whether this side offered `MSGPACK_CAPABILITY`, only then does a MessagePack frame switch formats.
+    offered: bool,
+    stats: Option<Arc<CodecStats>>,
+}
+
+impl ProverCodec {
+    /// Codec enforcing `limits`.
+    pub fn new(limits: ProtocolLimits) -> Self {
+        Self {
+            limits,
+            msgpack: false,
+            offered: false,
+            stats: None,
+        }
+    }
+
+    /// Codec enforcing `limits` and counting its frames into `stats`.
+    pub fn with_stats(limits: ProtocolLimits, stats: Arc<CodecStats>) -> Self {
+        Self {
+            stats: Some(stats),
+            ..Self::new(limits)
+        }
+    }
+
+    /// Sends MessagePack frames from now on, for a pool answering a prover with `MSGPACK_CAPABILITY`.
+    pub fn use_msgpack(&mut self) {
+        self.msgpack = true;
+    }
+
+    /// For a prover offering `MSGPACK_CAPABILITY`: the first MessagePack frame received is the pool
+    /// accepting the offer and switches the frames sent to MessagePack. Without an offer the frames
+    /// sent stay as they are whatever arrives.
+    pub fn offer_msgpack(&mut self) {
+        self.offered = true;
+    }
+
+    pub fn msgpack(&self) -> bool {
+        self.msgpack
+    }
+}
+
+impl Encoder<ProverMessage> for ProverCodec {
+    type Error = ProtocolError;
 
     fn encode(&mut self, item: ProverMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
         dst.extend_from_slice(&0u32.to_le_bytes());
 
         let mut writer = dst.writer();
-        writer.write_all(&[item.id()])?;
-
-        match item {
-            ProverMessage::ProofRate(..) => item.serialize_into(&mut writer)?,
-            _ => item.serialize_into_json(&mut writer)?
+        // Authorize offers MessagePack, so it is always JSON.
+        let msgpack = self.msgpack && !matches!(item, ProverMessage::Authorize(..));
+        if msgpack {
+            writer.write_all(&[item.id() | MSGPACK_FLAG])?;
+            item.serialize_into_msgpack(&mut writer)?;
+        } else {
+            writer.write_all(&[item.id()])?;
+            // The binary messages have to match the ids the decoder reads with `deserialize`.
+            match item {
+                ProverMessage::SubmitResult(..) | ProverMessage::ProofRate(..) => item.serialize_into(&mut writer)?,
+                _ => item.serialize_into_json(&mut writer)?
+            }
         }
 
         let msg_len = dst.len() - 4;
         dst[..4].copy_from_slice(&(msg_len as u32).to_le_bytes());
+        if let Some(stats) = &self.stats {
+            CodecStats::record(&stats.encoded, item.id(), 4 + msg_len);
+        }
 
-        #[cfg(debug_assertions)]
-        println!("Encode {}: {:?}", item.name(), dst);
+        // Only the size, the Authorize frame holds the password.
+        trace!("Encoded {}: {} bytes", item.name(), msg_len);
 
         Ok(())
     }
 }
 
-impl Decoder for ProverMessage {
-    type Error = anyhow::Error;
+impl Decoder for ProverCodec {
+    type Error = ProtocolError;
     type Item = ProverMessage;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if src.len() < 4 {
             return Ok(None);
         }
-        let length = u32::from_le_bytes(src[..4].try_into().unwrap()) as usize;
-        if length > 128 * 1024 * 1024 { // 128 Mib
-            return Err(anyhow!("Message too long"));
+        let length = u32::from_le_bytes([src[0], src[1], src[2], src[3]]) as usize;
+        if length == 0 {
+            return Err(ProtocolError::EmptyFrame);
+        }
+        if length > self.limits.max_frame_size {
+            return Err(ProtocolError::FrameTooLarge {
+                length,
+                limit: self.limits.max_frame_size,
+            });
         }
         if src.len() < 4 + length {
             return Ok(None);
         }
 
-        let msg_id = u8::from_le_bytes(src[4..5].try_into().unwrap()) as usize;
+        let msg_id = src[4];
+        if let Some(stats) = &self.stats {
+            CodecStats::record(&stats.decoded, msg_id, 4 + length);
+        }
         let msg = match msg_id {
-            4 => match ProverMessage::deserialize(&mut Cursor::new(&src[4..][..length])) {
-                Ok(msg) => Ok(Some(msg)),
-                Err(error) => Err(anyhow!(error)),
-            }
-            _ => match ProverMessage::deserialize_json(&mut Cursor::new(&src[4..][..length])) {
-                Ok(msg) => Ok(Some(msg)),
-                Err(error) => Err(anyhow!(error)),
+            _ if msg_id & MSGPACK_FLAG != 0 => {
+                let msg = ProverMessage::deserialize_msgpack(&mut Cursor::new(&src[4..][..length]));
+                // The pool accepted the offer, it reads MessagePack as well.
+                self.msgpack |= self.offered && msg.is_ok();
+                msg.map(Some)
             }
+            4 | 6 => ProverMessage::deserialize(&mut Cursor::new(&src[4..][..length])).map(Some),
+            _ => ProverMessage::deserialize_json(&mut Cursor::new(&src[4..][..length])).map(Some),
         };
 
         src.advance(4 + length);
-        
+
+        if let Ok(Some(message)) = &msg {
+            self.limits.check_strings(message)?;
+        }
         msg
     }
 }
@@ -0,0 +1,294 @@
+use std::{fmt::Write, sync::Arc, time::Duration};
+
+use hyper::{header::CONTENT_TYPE, Body, Method, Response};
+
+use crate::{
+    build_info::BuildInfo,
+    channel::ChannelStats,
+    client::{Client, ClientStats},
+    histogram::HistogramSnapshot,
+    http::{self, Handler},
+    prover::{Prover, ProverStats},
+};
+
+// Upper bounds of the submit latency buckets in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP aleoxminer_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE aleoxminer_{} {}", name, kind);
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, latency: &HistogramSnapshot) {
+    metric(out, name, "histogram", help);
+    for bound in LATENCY_BUCKETS.iter() {
+        let count = latency.count_below(Duration::from_secs_f64(*bound));
+        let _ = writeln!(out, "aleoxminer_{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+    }
+    let _ = writeln!(out, "aleoxminer_{}_bucket{{le=\"+Inf\"}} {}", name, latency.count());
+    let _ = writeln!(out, "aleoxminer_{}_sum {}", name, latency.sum().as_secs_f64());
+    let _ = writeln!(out, "aleoxminer_{}_count {}", name, latency.count());
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Renders the stats in the Prometheus text exposition format.
+pub fn render(stats: &ProverStats, client: &ClientStats) -> String {
+    let mut out = String::new();
+
+    metric(&mut out, "build_info", "gauge", "Build of the running miner, always 1.");
+    let build = BuildInfo::current();
+    let _ = writeln!(
+        out,
+        "aleoxminer_build_info{{version=\"{}\",git_sha=\"{}\",build_date=\"{}\",features=\"{}\"}} 1",
+        build.version,
+        build.git_sha,
+        build.build_date,
+        build.features.join(",")
+    );
+
+    metric(&mut out, "uptime_seconds", "gauge", "Time since the prover started.");
+    let _ = writeln!(out, "aleoxminer_uptime_seconds {}", stats.uptime.as_secs());
+
+    metric(&mut out, "paused", "gauge", "Whether proving is paused.");
+    let _ = writeln!(out, "aleoxminer_paused {}", stats.paused as u8);
+
+    metric(&mut out, "proofs_total", "counter", "Proofs computed.");
+    let _ = writeln!(out, "aleoxminer_proofs_total {}", stats.total_proofs);
+
+    metric(&mut out, "proofs_below_target_total", "counter", "Proofs not meeting the pool target.");
+    let _ = writeln!(out, "aleoxminer_proofs_below_target_total {}", stats.below_target);
+
+    metric(&mut out, "proof_rate", "gauge", "Proofs per second over a window.");
+    for (window, rate) in stats.proof_rates.iter() {
+        if let Some(rate) = rate {
+            let _ = writeln!(out, "aleoxminer_proof_rate{{window=\"{}m\"}} {}", window, rate);
+        }
+    }
+
+    metric(&mut out, "device_proofs_total", "counter", "Proofs computed per device.");
+    for (device, latency) in stats.devices.iter().zip(stats.worker_latency.iter()) {
+        let _ = writeln!(
+            out,
+            "aleoxminer_device_proofs_total{{device=\"{}\"}} {}",
+            escape(&device.name),
+            latency.count
+        );
+    }
+
+    metric(&mut out, "device_failures_total", "counter", "Pipeline failures per device.");
+    for device in stats.devices.iter() {
+        let _ = writeln!(
+            out,
+            "aleoxminer_device_failures_total{{device=\"{}\"}} {}",
+            escape(&device.name),
+            device.failures
+        );
+    }
+
+    metric(&mut out, "shares_total", "counter", "Shares by result.");
+    let _ = writeln!(out, "aleoxminer_shares_total{{result=\"accepted\"}} {}", stats.valid_shares);
+    let _ = writeln!(out, "aleoxminer_shares_total{{result=\"rejected\"}} {}", stats.invalid_shares);
+    let _ = writeln!(out, "aleoxminer_shares_total{{result=\"stale\"}} {}", stats.local_stale);
+
+    metric(&mut out, "shares_rejected_total", "counter", "Rejected shares by result code.");
+    for (code, count) in stats.rejections.by_label() {
+        let _ = writeln!(out, "aleoxminer_shares_rejected_total{{code=\"{}\"}} {}", code, count);
+    }
+
+    metric(&mut out, "connected", "gauge", "Whether the pool connection is up.");
+    let _ = writeln!(out, "aleoxminer_connected {}", client.connected as u8);
+
+    metric(&mut out, "pool_rtt_seconds", "gauge", "Round trip time to the pool.");
+    for (stat, rtt) in [("average", client.rtt.average), ("min", client.rtt.min), ("max", client.rtt.max)] {
+        if let Some(rtt) = rtt {
+            let _ = writeln!(out, "aleoxminer_pool_rtt_seconds{{stat=\"{}\"}} {}", stat, rtt.as_secs_f64());
+        }
+    }
+
+    metric(&mut out, "reconnects_total", "counter", "Reconnections to the pool.");
+    let _ = writeln!(out, "aleoxminer_reconnects_total {}", client.reconnects);
+
+    metric(&mut out, "dropped_jobs_total", "counter", "Jobs dropped for going back too many blocks.");
+    let _ = writeln!(out, "aleoxminer_dropped_jobs_total {}", client.dropped_jobs);
+    metric(&mut out, "invalid_templates_total", "counter", "Jobs dropped for an implausible template.");
+    let _ = writeln!(out, "aleoxminer_invalid_templates_total {}", client.invalid_templates);
+    metric(&mut out, "unmatched_results_total", "counter", "Share results received with no share waiting.");
+    let _ = writeln!(out, "aleoxminer_unmatched_results_total {}", client.unmatched_results);
+
+    metric(&mut out, "frames_total", "counter", "Frames exchanged with the pool by message type.");
+    for traffic in client.traffic.iter() {
+        for (direction, frames) in [("sent", traffic.encoded_frames), ("received", traffic.decoded_frames)] {
+            let _ = writeln!(
+                out,
+                "aleoxminer_frames_total{{message=\"{}\",direction=\"{}\"}} {}",
+                traffic.message, direction, frames
+            );
+        }
+    }
+    metric(&mut out, "frame_bytes_total", "counter", "Bytes exchanged with the pool by message type.");
+    for traffic in client.traffic.iter() {
+        for (direction, bytes) in [("sent", traffic.encoded_bytes), ("received", traffic.decoded_bytes)] {
+            let _ = writeln!(
+                out,
+                "aleoxminer_frame_bytes_total{{message=\"{}\",direction=\"{}\"}} {}",
+                traffic.message, direction, bytes
+            );
+        }
+    }
+
+    metric(&mut out, "proxy_exceptions_total", "counter", "ProxyException results, including retried shares.");
+    let _ = writeln!(out, "aleoxminer_proxy_exceptions_total {}", client.proxy_exceptions);
+    metric(&mut out, "speculative_jobs_total", "counter", "Speculative jobs by outcome.");
+    let _ = writeln!(
+        out,
+        "aleoxminer_speculative_jobs_total{{outcome=\"activated\"}} {}",
+        client.speculative_activated
+    );
+    let _ = writeln!(
+        out,
+        "aleoxminer_speculative_jobs_total{{outcome=\"expired\"}} {}",
+        client.speculative_expired
+    );
+
+    metric(&mut out, "pool_bandwidth_bytes_per_second", "gauge", "Bytes per second to and from the pool.");
+    let _ = writeln!(
+        out,
+        "aleoxminer_pool_bandwidth_bytes_per_second{{direction=\"read\"}} {}",
+        client.bandwidth.read_rate
+    );
+    let _ = writeln!(
+        out,
+        "aleoxminer_pool_bandwidth_bytes_per_second{{direction=\"write\"}} {}",
+        client.bandwidth.write_rate
+    );
+    let limits = [("read", client.bandwidth.read_limit), ("write", client.bandwidth.write_limit)];
+    if limits.iter().any(|(_, limit)| limit.is_some()) {
+        metric(&mut out, "pool_bandwidth_limit_bytes_per_second", "gauge", "Cap on the bytes per second.");
+        for (direction, limit) in limits {
+            if let Some(limit) = limit {
+                let _ = writeln!(
+                    out,
+                    "aleoxminer_pool_bandwidth_limit_bytes_per_second{{direction=\"{}\"}} {}",
+                    direction, limit
+                );
+            }
+        }
+    }
+
+    metric(&mut out, "pool_quality_score", "gauge", "Quality of the current pool from 100 down to 0.");
+    let _ = writeln!(out, "aleoxminer_pool_quality_score {}", client.quality_score);
+
+    metric(&mut out, "pending_submits", "gauge", "Shares waiting for a result from the pool.");
+    let _ = writeln!(out, "aleoxminer_pending_submits {}", client.pending_submits);
+
+    metric(&mut out, "proofs_in_flight", "gauge", "Proofs currently being computed.");
+    let _ = writeln!(out, "aleoxminer_proofs_in_flight {}", stats.in_flight);
+
+    histogram(
+        &mut out,
+        "submit_latency_seconds",
+        "Time from sending a share to receiving its result.",
+        &client.submit_latency,
+    );
+    histogram(
+        &mut out,
+        "queue_wait_seconds",
+        "Time messages to the pool spent queued before being sent.",
+        &client.queue_wait,
+    );
+
+    let channels: Vec<&ChannelStats> = client.channels.iter().chain(std::iter::once(&stats.channel)).collect();
+    metric(&mut out, "channel_capacity", "gauge", "Capacity of an internal channel.");
+    for channel in channels.iter() {
+        let _ = writeln!(out, "aleoxminer_channel_capacity{{channel=\"{}\"}} {}", channel.name, channel.capacity);
+    }
+    metric(&mut out, "channel_high_watermark", "gauge", "Most messages queued at once in an internal channel.");
+    for channel in channels.iter() {
+        let _ = writeln!(
+            out,
+            "aleoxminer_channel_high_watermark{{channel=\"{}\"}} {}",
+            channel.name, channel.high_watermark
+        );
+    }
+    metric(&mut out, "channel_sends_total", "counter", "Messages sent through an internal channel.");
+    for channel in channels.iter() {
+        let _ = writeln!(out, "aleoxminer_channel_sends_total{{channel=\"{}\"}} {}", channel.name, channel.sends);
+    }
+    metric(
+        &mut out,
+        "channel_send_wait_seconds_total",
+        "counter",
+        "Time senders waited for room in an internal channel.",
+    );
+    for channel in channels.iter() {
+        let _ = writeln!(
+            out,
+            "aleoxminer_channel_send_wait_seconds_total{{channel=\"{}\"}} {}",
+            channel.name,
+            channel.send_wait.as_secs_f64()
+        );
+    }
+    metric(
+        &mut out,
+        "channel_overflows_total",
+        "counter",
+        "Sends that found an internal channel full, or events a subscriber missed.",
+    );
+    for channel in channels.iter() {
+        let _ = writeln!(
+            out,
+            "aleoxminer_channel_overflows_total{{channel=\"{}\"}} {}",
+            channel.name, channel.overflows
+        );
+    }
+
+    if !stats.gpu_telemetry.is_empty() {
+        metric(&mut out, "gpu_temperature_celsius", "gauge", "GPU temperature.");
+        for gpu in stats.gpu_telemetry.iter() {
+            if let Some(temperature) = gpu.temperature {
+                let _ = writeln!(out, "aleoxminer_gpu_temperature_celsius{{gpu=\"{}\"}} {}", gpu.index, temperature);
+            }
+        }
+        metric(&mut out, "gpu_power_watts", "gauge", "GPU power draw.");
+        for gpu in stats.gpu_telemetry.iter() {
+            if let Some(power) = gpu.power {
+                let _ = writeln!(out, "aleoxminer_gpu_power_watts{{gpu=\"{}\"}} {}", gpu.index, power);
+            }
+        }
+        metric(&mut out, "gpu_fan_percent", "gauge", "GPU fan speed.");
+        for gpu in stats.gpu_telemetry.iter() {
+            if let Some(fan) = gpu.fan {
+                let _ = writeln!(out, "aleoxminer_gpu_fan_percent{{gpu=\"{}\"}} {}", gpu.index, fan);
+            }
+        }
+        metric(&mut out, "gpu_clock_mhz", "gauge", "GPU clocks.");
+        for gpu in stats.gpu_telemetry.iter() {
+            if let Some(clock) = gpu.sm_clock {
+                let _ = writeln!(out, "aleoxminer_gpu_clock_mhz{{gpu=\"{}\",clock=\"sm\"}} {}", gpu.index, clock);
+            }
+            if let Some(clock) = gpu.memory_clock {
+                let _ = writeln!(out, "aleoxminer_gpu_clock_mhz{{gpu=\"{}\",clock=\"memory\"}} {}", gpu.index, clock);
+            }
+        }
+    }
+
+    out
+}
+
+/// Serves `GET /metrics`.
+pub fn handler(prover: Arc<Prover>, client: Arc<Client>) -> Handler {
+    Box::new(move |request| {
+        if request.method() != Method::GET || request.uri().path() != "/metrics" {
+            return None;
+        }
+        Some(http::ready(
+            Response::builder()
+                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(Body::from(render(&prover.stats(), &client.stats())))
+                .unwrap_or_default(),
+        ))
+    })
+}
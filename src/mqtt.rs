@@ -0,0 +1,425 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+#[cfg(feature = "tls")]
+use rumqttc::{TlsConfiguration, Transport};
+use serde::Serialize;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    task,
+    time::{interval, sleep},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    client::Client,
+    events::MinerEvent,
+    message::pool_info_line,
+    prover::{PauseReason, Prover},
+    status::Status,
+};
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    /// Broker address, `host:port`
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[cfg(feature = "tls")]
+    pub tls: bool,
+    /// CA certificate file for TLS, the system roots are used otherwise
+    #[cfg(feature = "tls")]
+    pub ca: Option<String>,
+    pub interval: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    ClearRejects,
+    SetServer(String),
+}
+
+impl Command {
+    /// Parses `pause`, `resume`, `clear-rejects` or `set-server <host:port>`.
+    pub fn parse(payload: &str) -> Result<Self> {
+        let mut parts = payload.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("pause"), None, _) => Ok(Self::Pause),
+            (Some("resume"), None, _) => Ok(Self::Resume),
+            (Some("clear-rejects"), None, _) => Ok(Self::ClearRejects),
+            (Some("set-server"), Some(server), None) => Ok(Self::SetServer(server.to_string())),
+            _ => Err(anyhow!("unknown command {:?}", payload)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EventMessage {
+    event: &'static str,
+    details: String,
+}
+
+fn topic(worker: &str, name: &str) -> String {
+    format!("aleoxminer/{}/{}", worker, name)
+}
+
+fn options(config: &MqttConfig, worker: &str) -> Result<MqttOptions> {
+    let (host, port) = config
+        .broker
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("invalid MQTT broker {}, expected host:port", config.broker))?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| anyhow!("invalid MQTT broker port {}", port))?;
+    let mut options = MqttOptions::new(format!("aleoxminer-{}", worker), host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    // The broker drops the status once the miner goes away.
+    options.set_last_will(rumqttc::LastWill::new(
+        topic(worker, "status"),
+        r#"{"online":false}"#,
+        QoS::AtLeastOnce,
+        true,
+    ));
+    #[cfg(feature = "tls")]
+    if config.tls {
+        let transport = match &config.ca {
+            Some(ca) => {
+                let ca = std::fs::read(ca).map_err(|e| anyhow!("unable to read MQTT CA certificate {}: {}", ca, e))?;
+                Transport::Tls(TlsConfiguration::Simple {
+                    ca,
+                    alpn: None,
+                    client_auth: None,
+                })
+            }
+            None => Transport::tls_with_default_config(),
+        };
+        options.set_transport(transport);
+    }
+    Ok(options)
+}
+
+fn handle_command(payload: &[u8], prover: &Arc<Prover>, client: &Arc<Client>) {
+    let payload = String::from_utf8_lossy(payload);
+    match Command::parse(payload.trim()) {
+        Ok(Command::Pause) => {
+            info!("Pausing on MQTT command");
+            let prover = prover.clone();
+            task::spawn(async move { prover.pause(PauseReason::Manual).await });
+        }
+        Ok(Command::Resume) => {
+            info!("Resuming on MQTT command");
+            let prover = prover.clone();
+            task::spawn(async move { prover.resume(PauseReason::Manual).await });
+        }
+        Ok(Command::ClearRejects) => {
+            info!("Clearing the reject guard on MQTT command");
+            let prover = prover.clone();
+            task::spawn(async move { prover.clear_rejects().await });
+        }
+        Ok(Command::SetServer(server)) => {
+            info!("Switching to {} on MQTT command", server);
+            client.set_server(server);
+        }
+        Err(e) => warn!("Ignoring MQTT command: {}", e),
+    }
+}
+
+/// Publishes the status and events to an MQTT broker and listens for commands.
+/// Publishing never waits for the broker, so broker problems can't hold up mining.
+pub fn spawn(config: MqttConfig, prover: Arc<Prover>, client: Arc<Client>) -> Result<()> {
+    let worker = match client.worker().as_str() {
+        "" => "default".to_string(),
+        worker => worker.to_string(),
+    };
+    let (mqtt, mut eventloop) = AsyncClient::new(options(&config, &worker)?, 64);
+    info!("Publishing telemetry to MQTT broker {}", config.broker);
+
+    let commands = topic(&worker, "cmd");
+    {
+        let prover = prover.clone();
+        let client = client.clone();
+        let mqtt = mqtt.clone();
+        task::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        debug!("Connected to MQTT broker");
+                        if let Err(e) = mqtt.try_subscribe(&commands, QoS::AtLeastOnce) {
+                            warn!("Unable to subscribe to {}: {}", commands, e);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == commands => {
+                        handle_command(&publish.payload, &prover, &client);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("MQTT connection error: {}", e);
+                        sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
+    }
+
+    let mut events = client.events().subscribe();
+    task::spawn(async move {
+        let status_topic = topic(&worker, "status");
+        let events_topic = topic(&worker, "events");
+        let publish_event = |event: &'static str, details: String| {
+            let message = EventMessage { event, details };
+            if let Ok(payload) = serde_json::to_vec(&message) {
+                if let Err(e) = mqtt.try_publish(&events_topic, QoS::AtLeastOnce, false, payload) {
+                    debug!("Unable to publish MQTT event: {}", e);
+                }
+            }
+        };
+        let mut status_interval = interval(config.interval);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(MinerEvent::ShareAccepted(result)) => publish_event(
+                        "share-accepted",
+                        format!("Share accepted for block {} ({})", result.height, result.worker),
+                    ),
+                    Ok(MinerEvent::Disconnected { server, reason, .. }) => {
+                        publish_event("disconnect", format!("Disconnected from {}: {}", server, reason))
+                    }
+                    Ok(MinerEvent::Threshold { alert, details, raised: true }) => {
+                        publish_event("threshold", format!("{}: {}", alert, details))
+                    }
+                    Ok(MinerEvent::Threshold { alert, raised: false, .. }) => {
+                        publish_event("threshold-cleared", format!("{} is back to normal", alert))
+                    }
+                    Ok(MinerEvent::PoolInfo { server, fields }) => {
+                        publish_event("pool-info", format!("{}: {}", server, pool_info_line(&fields)))
+                    }
+                    Err(RecvError::Lagged(missed)) => client.events().record_lag(missed),
+                    Ok(_) => {}
+                    Err(RecvError::Closed) => return,
+                },
+                _ = status_interval.tick() => {
+                    let status = Status::new(&prover.stats(), &client.stats(), &client.server(), &client.worker());
+                    if let Ok(payload) = serde_json::to_vec(&status) {
+                        if let Err(e) = mqtt.try_publish(&status_topic, QoS::AtLeastOnce, true, payload) {
+                            debug!("Unable to publish MQTT status: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{tcp::OwnedReadHalf, TcpListener},
+        sync::mpsc,
+    };
+
+    use super::*;
+    use crate::testing::{self, FakeBackend};
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("pause").unwrap(), Command::Pause);
+        assert_eq!(Command::parse(" resume ").unwrap(), Command::Resume);
+        assert_eq!(Command::parse("clear-rejects").unwrap(), Command::ClearRejects);
+        assert_eq!(
+            Command::parse("set-server pool.example:4040").unwrap(),
+            Command::SetServer("pool.example:4040".to_string())
+        );
+        assert!(Command::parse("set-server").is_err());
+        assert!(Command::parse("set-server a b").is_err());
+        assert!(Command::parse("pause now").is_err());
+        assert!(Command::parse("").is_err());
+    }
+
+    #[test]
+    fn rejects_brokers_without_a_port() {
+        let config = MqttConfig {
+            broker: "localhost".to_string(),
+            username: None,
+            password: None,
+            #[cfg(feature = "tls")]
+            tls: false,
+            #[cfg(feature = "tls")]
+            ca: None,
+            interval: Duration::from_secs(1),
+        };
+        assert!(options(&config, "rig1").is_err());
+    }
+
+    // What the mock broker got: the CONNECT flags and every PUBLISH as (topic, payload).
+    #[derive(Default)]
+    struct Received {
+        connect_flags: Option<u8>,
+        subscribed: bool,
+        publishes: Vec<(String, Vec<u8>)>,
+    }
+
+    // One MQTT 3.1.1 control packet: the first header byte and the rest after the length.
+    async fn packet(reader: &mut OwnedReadHalf) -> Option<(u8, Vec<u8>)> {
+        let header = reader.read_u8().await.ok()?;
+        let (mut length, mut shift) = (0usize, 0);
+        loop {
+            let byte = reader.read_u8().await.ok()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    fn string(bytes: &[u8]) -> (String, &[u8]) {
+        let length = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        (String::from_utf8_lossy(&bytes[2..2 + length]).to_string(), &bytes[2 + length..])
+    }
+
+    // Broker for a single client: acknowledges everything it needs to and sends the commands queued
+    // on the returned sender as QoS 0 publishes.
+    async fn broker() -> (String, Arc<Mutex<Received>>, mpsc::UnboundedSender<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Received::default()));
+        let (commands, mut queued) = mpsc::unbounded_channel::<(String, String)>();
+        let state = received.clone();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (mut reader, mut writer) = socket.into_split();
+            let (replies, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+            tokio::spawn(async move {
+                while let Some(bytes) = outgoing.recv().await {
+                    if writer.write_all(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+            });
+            let publisher = replies.clone();
+            tokio::spawn(async move {
+                while let Some((topic, payload)) = queued.recv().await {
+                    let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+                    body.extend_from_slice(topic.as_bytes());
+                    body.extend_from_slice(payload.as_bytes());
+                    let mut bytes = vec![0x30, body.len() as u8];
+                    bytes.extend(body);
+                    let _ = publisher.send(bytes);
+                }
+            });
+            while let Some((header, body)) = packet(&mut reader).await {
+                match header >> 4 {
+                    1 => {
+                        state.lock().unwrap().connect_flags = Some(body[7]);
+                        let _ = replies.send(vec![0x20, 0x02, 0x00, 0x00]);
+                    }
+                    3 => {
+                        let (topic, rest) = string(&body);
+                        let qos = (header >> 1) & 0x03;
+                        let payload = if qos > 0 {
+                            let _ = replies.send(vec![0x40, 0x02, rest[0], rest[1]]);
+                            rest[2..].to_vec()
+                        } else {
+                            rest.to_vec()
+                        };
+                        state.lock().unwrap().publishes.push((topic, payload));
+                    }
+                    8 => {
+                        state.lock().unwrap().subscribed = true;
+                        let _ = replies.send(vec![0x90, 0x03, body[0], body[1], 0x01]);
+                    }
+                    12 => {
+                        let _ = replies.send(vec![0xd0, 0x00]);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        (address, received, commands)
+    }
+
+    async fn until(mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = std::time::Instant::now() + LIMIT;
+        while !condition() {
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        true
+    }
+
+    fn published(received: &Mutex<Received>, topic: &str) -> Vec<Value> {
+        let received = received.lock().unwrap();
+        received
+            .publishes
+            .iter()
+            .filter(|(published, _)| published == topic)
+            .filter_map(|(_, payload)| serde_json::from_slice(payload).ok())
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publishes_and_obeys_commands() {
+        let (broker, received, commands) = broker().await;
+        let client = testing::client("127.0.0.1:1", "rig1");
+        let prover =
+            Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+                .unwrap();
+        let config = MqttConfig {
+            broker,
+            username: Some("miner".to_string()),
+            password: Some("secret".to_string()),
+            #[cfg(feature = "tls")]
+            tls: false,
+            #[cfg(feature = "tls")]
+            ca: None,
+            interval: Duration::from_millis(100),
+        };
+        spawn(config, prover.clone(), client.clone()).unwrap();
+
+        assert!(until(|| received.lock().unwrap().subscribed).await);
+        // Username, password and a retained last will.
+        assert_eq!(received.lock().unwrap().connect_flags.unwrap() & 0xe4, 0xe4);
+        assert!(until(|| !published(&received, "aleoxminer/rig1/status").is_empty()).await);
+        let status = published(&received, "aleoxminer/rig1/status").remove(0);
+        assert_eq!(status["worker"], "rig1");
+        assert_eq!(status["connected"], false);
+
+        client.events().publish(MinerEvent::Disconnected {
+            server: "127.0.0.1:1".to_string(),
+            worker: "rig1".to_string(),
+            reason: "connection refused".to_string(),
+        });
+        assert!(until(|| !published(&received, "aleoxminer/rig1/events").is_empty()).await);
+        let event = published(&received, "aleoxminer/rig1/events").remove(0);
+        assert_eq!(event["event"], "disconnect");
+        assert_eq!(event["details"], "Disconnected from 127.0.0.1:1: connection refused");
+
+        let command = |payload: &str| commands.send(("aleoxminer/rig1/cmd".to_string(), payload.to_string())).unwrap();
+        command("pause");
+        assert!(until(|| prover.stats().paused).await);
+        command("resume");
+        assert!(until(|| !prover.stats().paused).await);
+        command("set-server pool.example:4040");
+        assert!(until(|| client.server() == "pool.example:4040").await);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    task,
+    time::{sleep, sleep_until},
+};
+use tracing::{debug, warn};
+
+use crate::{client::Client, events::MinerEvent};
+
+const ATTEMPTS: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Start,
+    Stop,
+    Disconnect,
+    RejectRate,
+    NoShare,
+    /// A user defined alert threshold was breached
+    Threshold,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 6] = [
+        EventKind::Start,
+        EventKind::Stop,
+        EventKind::Disconnect,
+        EventKind::RejectRate,
+        EventKind::NoShare,
+        EventKind::Threshold,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Disconnect => "disconnect",
+            Self::RejectRate => "reject-rate",
+            Self::NoShare => "no-share",
+            Self::Threshold => "threshold",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == s.trim())
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown event {}, expected one of start, stop, disconnect, reject-rate, no-share, threshold",
+                    s
+                )
+            })
+    }
+}
+
+/// Body of the webhook request, for example:
+///
+/// ```json
+/// {"event":"no-share","worker":"rig1","timestamp":1650000000,"details":"No share accepted for 15 minutes"}
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    pub event: &'static str,
+    pub worker: String,
+    /// Unix timestamp
+    pub timestamp: u64,
+    pub details: String,
+    /// Discord and Slack display this field
+    pub content: String,
+}
+
+impl Payload {
+    pub fn new(kind: EventKind, worker: &str, details: String) -> Self {
+        Self {
+            event: kind.name(),
+            worker: worker.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+            content: format!("[{}] {}: {}", worker, kind.name(), details),
+            details,
+        }
+    }
+}
+
+/// Posts miner events to a webhook. Sending is best-effort and never blocks the caller.
+pub struct Notifier {
+    url: String,
+    worker: String,
+    events: HashSet<EventKind>,
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    /// Notifies about all events if `events` is empty.
+    pub fn new(url: String, worker: String, events: &[EventKind]) -> Result<Self> {
+        let events = if events.is_empty() {
+            EventKind::ALL.iter().copied().collect()
+        } else {
+            events.iter().copied().collect()
+        };
+        Ok(Self {
+            url,
+            worker,
+            events,
+            http: reqwest::Client::builder().timeout(TIMEOUT).build()?,
+        })
+    }
+
+    pub fn notify(self: &Arc<Self>, kind: EventKind, details: String) {
+        if !self.events.contains(&kind) {
+            return;
+        }
+        let notifier = self.clone();
+        task::spawn(async move { notifier.post(kind, details).await });
+    }
+
+    /// Like `notify`, but waits until the notification is sent or given up on.
+    pub async fn notify_and_wait(&self, kind: EventKind, details: String) {
+        if self.events.contains(&kind) {
+            self.post(kind, details).await;
+        }
+    }
+
+    async fn post(&self, kind: EventKind, details: String) {
+        let payload = Payload::new(kind, &self.worker, details);
+        for attempt in 1..=ATTEMPTS {
+            match self.http.post(&self.url).json(&payload).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Sent {} notification", kind.name());
+                    return;
+                }
+                Ok(response) => warn!("Webhook returned {} for {} notification", response.status(), kind.name()),
+                Err(e) => warn!("Unable to send {} notification: {}", kind.name(), e),
+            }
+            if attempt < ATTEMPTS {
+                sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+        }
+    }
+}
+
+/// Notifies when the pool connection stays down for longer than `threshold`.
+pub fn watch_connection(notifier: Arc<Notifier>, client: Arc<Client>, threshold: Duration) {
+    let mut events = client.events().subscribe();
+    task::spawn(async move {
+        let worker = client.worker();
+        // Down until the first connection, with the reason once known.
+        let mut down: Option<(Instant, String)> = Some((Instant::now(), "not connected yet".to_string()));
+        let mut notified = false;
+        loop {
+            let deadline = match down.as_ref() {
+                Some((since, _)) if !notified => Some(*since + threshold),
+                _ => None,
+            };
+            let expired = async {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(MinerEvent::Connected { worker: connected, .. }) if connected == worker => {
+                        down = None;
+                        notified = false;
+                    }
+                    Ok(MinerEvent::Disconnected { worker: disconnected, reason, .. }) if disconnected == worker => {
+                        down.get_or_insert_with(|| (Instant::now(), reason));
+                    }
+                    Err(RecvError::Lagged(missed)) => client.events().record_lag(missed),
+                    Ok(_) => {}
+                    Err(RecvError::Closed) => return,
+                },
+                _ = expired => {
+                    if let Some((since, reason)) = down.as_ref() {
+                        notified = true;
+                        notifier.notify(
+                            EventKind::Disconnect,
+                            format!(
+                                "Disconnected from {} for {} seconds ({})",
+                                client.server(),
+                                since.elapsed().as_secs(),
+                                reason
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::testing::{self, HttpSink};
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    fn payload(body: &[u8]) -> Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[test]
+    fn parses_event_kinds() {
+        for kind in EventKind::ALL {
+            assert_eq!(kind.name().parse::<EventKind>().unwrap(), kind);
+        }
+        assert_eq!(" no-share ".parse::<EventKind>().unwrap(), EventKind::NoShare);
+        assert!("share".parse::<EventKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn posts_the_payload() {
+        let sink = HttpSink::start().await.unwrap();
+        let notifier = Notifier::new(sink.url("/hook"), "rig1".to_string(), &[]).unwrap();
+        notifier
+            .notify_and_wait(EventKind::NoShare, "No share accepted for 15 minutes".to_string())
+            .await;
+
+        let requests = sink.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!((requests[0].method.as_str(), requests[0].path.as_str()), ("POST", "/hook"));
+        let payload = payload(&requests[0].body);
+        assert_eq!(payload["event"], "no-share");
+        assert_eq!(payload["worker"], "rig1");
+        assert_eq!(payload["details"], "No share accepted for 15 minutes");
+        assert_eq!(payload["content"], "[rig1] no-share: No share accepted for 15 minutes");
+        assert!(payload["timestamp"].as_u64().unwrap() > 1_600_000_000);
+    }
+
+    #[tokio::test]
+    async fn posts_only_the_chosen_events() {
+        let sink = HttpSink::start().await.unwrap();
+        let notifier = Notifier::new(sink.url("/"), "rig1".to_string(), &[EventKind::Disconnect]).unwrap();
+        notifier.notify_and_wait(EventKind::Start, String::new()).await;
+        notifier.notify_and_wait(EventKind::Disconnect, String::new()).await;
+
+        let requests = sink.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(payload(&requests[0].body)["event"], "disconnect");
+    }
+
+    #[tokio::test]
+    async fn retries_after_a_failure() {
+        let sink = HttpSink::start().await.unwrap();
+        sink.fail(1);
+        let notifier = Notifier::new(sink.url("/"), "rig1".to_string(), &[]).unwrap();
+        notifier.notify_and_wait(EventKind::Stop, String::new()).await;
+        assert_eq!(sink.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_without_blocking_the_caller() {
+        let sink = HttpSink::start().await.unwrap();
+        sink.fail(ATTEMPTS as usize);
+        let notifier = Arc::new(Notifier::new(sink.url("/"), "rig1".to_string(), &[]).unwrap());
+        let start = Instant::now();
+        notifier.notify(EventKind::RejectRate, String::new());
+        assert!(start.elapsed() < Duration::from_millis(100));
+        sink.wait_for(LIMIT, ATTEMPTS as usize).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn notifies_a_long_disconnect() {
+        let sink = HttpSink::start().await.unwrap();
+        let notifier = Arc::new(Notifier::new(sink.url("/"), "rig1".to_string(), &[]).unwrap());
+        watch_connection(notifier, testing::client("127.0.0.1:1", "rig1"), Duration::from_millis(100));
+
+        let requests = sink.wait_for(LIMIT, 1).await.unwrap();
+        let payload = payload(&requests[0].body);
+        assert_eq!(payload["event"], "disconnect");
+        assert!(payload["details"].as_str().unwrap().contains("not connected yet"));
+        // Once per outage.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(sink.requests().len(), 1);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use snarkvm::{
+    dpc::{testnet2::Testnet2, PoSWProof},
+    traits::Network,
+};
+use tracing::{debug, info, warn};
+
+// Shares kept at most, the oldest are dropped beyond.
+const MAX_ENTRIES: usize = 64;
+// Records appended before the file is rewritten with only the live entries.
+const COMPACT_AFTER: usize = 1024;
+
+/// A share queued for a pool that didn't get a result yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// Pool and worker, see `Client::session_key`
+    pub key: String,
+    pub height: u32,
+    pub nonce: <Testnet2 as Network>::PoSWNonce,
+    pub proof: PoSWProof<Testnet2>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Add(OutboxEntry),
+    Remove(<Testnet2 as Network>::PoSWNonce),
+}
+
+struct State {
+    file: Option<File>,
+    entries: Vec<OutboxEntry>,
+    // Entries of the previous run, resubmitted once the pool gives the current height.
+    restored: Vec<OutboxEntry>,
+    appended: usize,
+}
+
+/// Shares sent to the pools and not answered yet, kept in an append-only file next to the stats file
+/// so that a share found just before the process is killed is submitted after the restart.
+pub struct Outbox {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Outbox {
+    /// Outbox of the previous runs using `stats_file`. A corrupted file is moved aside and the
+    /// outbox starts empty.
+    pub fn load<P: AsRef<Path>>(stats_file: P) -> Self {
+        let mut path = stats_file.as_ref().to_path_buf().into_os_string();
+        path.push(".outbox");
+        let path = PathBuf::from(path);
+        let restored = match read(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let mut quarantine = path.clone().into_os_string();
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                quarantine.push(format!(".corrupt-{}", now.as_secs()));
+                warn!(
+                    "Outbox {} is corrupted, moving it to {:?}: {:#}",
+                    path.display(),
+                    quarantine,
+                    e
+                );
+                if let Err(e) = fs::rename(&path, &quarantine) {
+                    warn!("Unable to move the corrupted outbox aside: {}", e);
+                }
+                Vec::new()
+            }
+        };
+        if !restored.is_empty() {
+            info!("{} shares of the previous run are waiting to be submitted", restored.len());
+        }
+        let outbox = Self {
+            path,
+            state: Mutex::new(State {
+                file: None,
+                entries: restored.clone(),
+                restored,
+                appended: 0,
+            }),
+        };
+        if outbox.path.exists() {
+            if let Err(e) = outbox.compact(&mut outbox.state.lock().unwrap_or_else(PoisonError::into_inner)) {
+                warn!("Unable to write the outbox {}: {}", outbox.path.display(), e);
+            }
+        }
+        outbox
+    }
+
+    /// Keeps a share until `remove` is called, dropping the shares of `key` for older heights.
+    pub fn add(&self, entry: OutboxEntry) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = state.entries.len();
+        state
+            .entries
+            .retain(|kept| kept.key != entry.key || kept.height >= entry.height);
+        let mut expired = before - state.entries.len();
+        while state.entries.len() >= MAX_ENTRIES {
+            state.entries.remove(0);
+            expired += 1;
+        }
+        state.entries.push(entry.clone());
+        if expired > 0 || state.appended >= COMPACT_AFTER {
+            return self.compact(&mut state);
+        }
+        self.append(&mut state, &Record::Add(entry))
+    }
+
+    /// Forgets a share once the pool answered it or it went stale.
+    pub fn remove(&self, nonce: &<Testnet2 as Network>::PoSWNonce) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = state.entries.len();
+        state.entries.retain(|entry| entry.nonce != *nonce);
+        if state.entries.len() == before {
+            return Ok(());
+        }
+        self.append(&mut state, &Record::Remove(*nonce))
+    }
+
+    /// Shares of the previous run for `key` that are still fresh at `height`, each returned once.
+    /// The stale ones are dropped.
+    pub fn take_restored(&self, key: &str, height: u32) -> Vec<OutboxEntry> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (taken, kept): (Vec<OutboxEntry>, Vec<OutboxEntry>) =
+            state.restored.drain(..).partition(|entry| entry.key == key);
+        state.restored = kept;
+        let (fresh, stale): (Vec<OutboxEntry>, Vec<OutboxEntry>) =
+            taken.into_iter().partition(|entry| entry.height >= height);
+        if !stale.is_empty() {
+            debug!("Discarding {} shares of the previous run below block {}", stale.len(), height);
+            state
+                .entries
+                .retain(|entry| !stale.iter().any(|stale| stale.nonce == entry.nonce));
+            if let Err(e) = self.compact(&mut state) {
+                warn!("Unable to write the outbox {}: {}", self.path.display(), e);
+            }
+        }
+        fresh
+    }
+
+    fn append(&self, state: &mut State, record: &Record) -> Result<()> {
+        if state.file.is_none() {
+            state.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        if let Some(file) = state.file.as_mut() {
+            file.write_all(&line)?;
+            file.sync_data()?;
+        }
+        state.appended += 1;
+        Ok(())
+    }
+
+    // Rewrites the file with only the live entries.
+    fn compact(&self, state: &mut State) -> Result<()> {
+        state.file = None;
+        state.appended = 0;
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let mut file = File::create(&temp)?;
+        for entry in state.entries.iter() {
+            let mut line = serde_json::to_vec(&Record::Add(entry.clone()))?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+// Replays the records of the file. A last line cut short by a kill mid-write is ignored, any other
+// unreadable line makes the file corrupted.
+fn read(path: &Path) -> Result<Vec<OutboxEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries: Vec<OutboxEntry> = Vec::new();
+    let mut lines = BufReader::new(file).lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = line?;
+        let record = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) if lines.peek().is_none() && e.is_eof() => {
+                debug!("Ignoring the incomplete last record of the outbox");
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match record {
+            Record::Add(entry) => entries.push(entry),
+            Record::Remove(nonce) => entries.retain(|entry| entry.nonce != nonce),
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::error::SendError;
+
+use crate::{
+    channel::{ChannelMetrics, InstrumentedSender},
+    message::ProverMessage,
+};
+
+/// A message queued for the pool, with when it was queued and for which job.
+#[derive(Debug)]
+pub struct Outgoing {
+    pub message: ProverMessage,
+    pub enqueued_at: Instant,
+    /// Jobs notified by the pool before this message was queued
+    pub job_epoch: u64,
+}
+
+impl Outgoing {
+    pub fn new(message: ProverMessage, job_epoch: u64) -> Self {
+        Self {
+            message,
+            enqueued_at: Instant::now(),
+            job_epoch,
+        }
+    }
+
+    /// Time spent in the queue so far.
+    pub fn age(&self) -> Duration {
+        self.enqueued_at.elapsed()
+    }
+}
+
+/// How long queued messages are still worth sending, `None` to always send them. Authorize and
+/// Canary are never dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxAges {
+    /// Shares for an earlier job than the current one
+    pub submit: Option<Duration>,
+    pub proof_rate: Option<Duration>,
+}
+
+impl Default for MaxAges {
+    fn default() -> Self {
+        Self {
+            submit: Some(Duration::from_secs(60)),
+            proof_rate: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+impl MaxAges {
+    /// Whether `outgoing` is too old to send while the pool is on job `epoch`.
+    pub fn expired(&self, outgoing: &Outgoing, epoch: u64) -> bool {
+        let max_age = match outgoing.message {
+            ProverMessage::Submit(..) if outgoing.job_epoch < epoch => self.submit,
+            ProverMessage::ProofRate(..) => self.proof_rate,
+            _ => None,
+        };
+        max_age.map_or(false, |max_age| outgoing.age() > max_age)
+    }
+}
+
+/// Queues messages to the pool, stamped with the time and the current job.
+#[derive(Debug, Clone)]
+pub struct OutgoingSender {
+    sender: InstrumentedSender<Outgoing>,
+    epoch: Arc<AtomicU64>,
+}
+
+impl OutgoingSender {
+    /// Sender for a queue without jobs, every message is stamped with epoch 0.
+    pub fn new(sender: InstrumentedSender<Outgoing>) -> Self {
+        Self::with_epoch(sender, Default::default())
+    }
+
+    /// Sender stamping messages with the value of `epoch` when they are queued.
+    pub fn with_epoch(sender: InstrumentedSender<Outgoing>, epoch: Arc<AtomicU64>) -> Self {
+        Self { sender, epoch }
+    }
+
+    pub fn metrics(&self) -> &Arc<ChannelMetrics> {
+        self.sender.metrics()
+    }
+
+    fn wrap(&self, message: ProverMessage) -> Outgoing {
+        Outgoing::new(message, self.epoch.load(Ordering::SeqCst))
+    }
+
+    pub async fn send(&self, message: ProverMessage) -> Result<(), SendError<ProverMessage>> {
+        self.sender
+            .send(self.wrap(message))
+            .await
+            .map_err(|SendError(outgoing)| SendError(outgoing.message))
+    }
+
+    /// Waits for room in the queue outside of the runtime, as the proving threads do.
+    pub fn blocking_send(&self, message: ProverMessage) -> Result<(), SendError<ProverMessage>> {
+        self.sender
+            .blocking_send(self.wrap(message))
+            .map_err(|SendError(outgoing)| SendError(outgoing.message))
+    }
+}
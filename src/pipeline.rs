@@ -0,0 +1,94 @@
+use std::{
+    fmt::{Display, Formatter},
+    sync::atomic::{AtomicU32, AtomicU8, Ordering},
+    time::Duration,
+};
+
+/// Failures after which a device is disabled for the rest of the session.
+pub const MAX_FAILURES: u32 = 5;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineState {
+    Idle,
+    Mining,
+    Restarting,
+    Disabled,
+    /// Paused by the reject guard until its cool-down elapses
+    Paused,
+}
+
+impl Display for PipelineState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            Self::Idle => "idle",
+            Self::Mining => "mining",
+            Self::Restarting => "restarting",
+            Self::Disabled => "disabled",
+            Self::Paused => "paused",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Supervision state of one worker and the device it proves on.
+#[derive(Default)]
+pub struct Pipeline {
+    state: AtomicU8,
+    failures: AtomicU32,
+}
+
+impl Pipeline {
+    pub fn state(&self) -> PipelineState {
+        match self.state.load(Ordering::SeqCst) {
+            1 => PipelineState::Mining,
+            2 => PipelineState::Restarting,
+            3 => PipelineState::Disabled,
+            4 => PipelineState::Paused,
+            _ => PipelineState::Idle,
+        }
+    }
+
+    pub fn set_state(&self, state: PipelineState) {
+        let value = match state {
+            PipelineState::Idle => 0,
+            PipelineState::Mining => 1,
+            PipelineState::Restarting => 2,
+            PipelineState::Disabled => 3,
+            PipelineState::Paused => 4,
+        };
+        self.state.store(value, Ordering::SeqCst);
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures.load(Ordering::SeqCst)
+    }
+
+    /// Counts a failure and returns the total number of failures.
+    pub fn record_failure(&self) -> u32 {
+        self.failures.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// Delay before restarting after the given number of failures, doubling up to a minute.
+pub fn backoff(failures: u32) -> Duration {
+    Duration::from_secs(1u64 << failures.min(6)).min(MAX_BACKOFF)
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceStatus {
+    pub name: String,
+    pub state: PipelineState,
+    pub failures: u32,
+}
+
+impl Display for DeviceStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.failures > 0 {
+            write!(f, "{}: {} ({} failures)", self.name, self.state, self.failures)
+        } else {
+            write!(f, "{}: {}", self.name, self.state)
+        }
+    }
+}
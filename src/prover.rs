@@ -1,48 +1,341 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    panic::AssertUnwindSafe,
     sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
         Arc,
+        RwLock,
     },
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
 use ansi_term::Colour::{Cyan, Green, Red};
-use anyhow::Result;
-use rand::thread_rng;
+use anyhow::{anyhow, Result};
+use futures::FutureExt;
+use rand::{thread_rng, SeedableRng};
+use rand_chacha::ChaChaRng;
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use snarkvm::dpc::{testnet2::Testnet2, BlockHeader, BlockTemplate};
-use tokio::{sync::mpsc, task};
-use tracing::{debug, error, info};
+use snarkvm::{
+    dpc::{testnet2::Testnet2, BlockTemplate},
+    traits::Network,
+};
+use tokio::{
+    sync::{mpsc, oneshot, Mutex, OnceCell},
+    task,
+    task::JoinHandle,
+    time::timeout,
+};
+use tracing::{debug, error, info, warn, Instrument, Span};
+
+use crate::{
+    alert::{self, AlertChange, ShareAlert},
+    backend::{self, ProvingBackend},
+    channel::{self, ChannelStats, InstrumentedSender},
+    client::Client,
+    cpu::CpuPath,
+    estimate::{self, EarningsConfig, EffortTracker},
+    events::MinerEvent,
+    exit,
+    group::WorkerGroup,
+    histogram::{drifting_workers, HistogramSnapshot, LatencyHistogram, LatencySummary},
+    idle,
+    job_trace::JobTrace,
+    limit::ProofLimit,
+    message::{pool_info_line, Code, ProverMessage},
+    notify::{EventKind, Notifier},
+    pipeline::{self, DeviceStatus, Pipeline, PipelineState},
+    reject::{GuardAction, RejectBreakdown, RejectGuard},
+    report::ReportPolicy,
+    split::SplitShare,
+    telemetry::{GpuTelemetry, TelemetrySampler},
+    threshold::{ThresholdChange, ThresholdMonitor, Thresholds},
+    units,
+    watchdog::{Heartbeats, Watchdog},
+};
+
+/// Settings of a `Prover`.
+pub struct ProverConfig {
+    /// Number of CPU threads used for proving
+    pub threads: u16,
+    /// Indexes of GPUs to use, pure CPU proving if `None`
+    pub cuda: Option<Vec<i16>>,
+    /// Parallel jobs per GPU
+    pub cuda_jobs: Option<u8>,
+    /// Number of recent share results the reject ratio is computed over
+    pub reject_window: usize,
+    /// Reject ratio in percent above which proving is paused
+    pub reject_threshold: f64,
+    /// Time to stay paused before retrying
+    pub reject_cooldown: Duration,
+    /// Number of shares to evaluate after a cool-down before going back to normal
+    pub reject_probation: usize,
+    /// Percentage a worker's median proof latency may drift from the fleet median before warning
+    pub latency_drift: f64,
+    /// Seeds the nonce generator to get a reproducible nonce sequence, for testing only
+    pub deterministic_seed: Option<u64>,
+    /// Maximum number of proofs computed at the same time, unlimited if `None`
+    pub max_concurrent_proofs: Option<usize>,
+    /// When to report the proof rate to the pool
+    pub rate_report: ReportPolicy,
+    /// Figures for the estimated yield
+    pub earnings: EarningsConfig,
+    /// Run the proving threads at the lowest scheduling priority
+    pub nice: bool,
+    /// Minimum time without an accepted share before alerting
+    pub share_alert: Duration,
+    /// Multiple of the median proof latency after which an attempt is considered stuck
+    pub watchdog_multiple: f64,
+    /// CPU code path selected from the detected CPU features
+    pub cpu_path: CpuPath,
+    /// GPUs submitting under their own worker name, with the connection used for that name
+    pub groups: Vec<(WorkerGroup, Arc<Client>)>,
+    /// Webhook for reject rate and missing share alerts
+    pub notifier: Option<Arc<Notifier>>,
+    /// GPU sensor readings, sampled with the periodic stats
+    pub telemetry: Option<Arc<dyn TelemetrySampler>>,
+    /// User defined alert thresholds, evaluated with the periodic stats
+    pub thresholds: Thresholds,
+    /// Whether to compute proofs, if not only the work and the proof rates given to
+    /// `report_external_rate` are passed on and the proving parameters are never loaded
+    pub proving: bool,
+    /// Proves with this backend on every worker instead of the CPU or the GPUs, e.g. a fake in tests
+    pub backend: Option<Arc<dyn ProvingBackend>>,
+}
+
+/// Why proving is paused. Every reason is lifted on its own, proving resumes once none is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// The control API, MQTT or the dashboard
+    Manual,
+    /// Outside the mining schedule
+    Schedule,
+    /// Other programs are using the CPU, see `--idle-only`
+    Idle,
+    /// The reject ratio exceeded the threshold, lifted after the cool-down
+    Rejects,
+}
+
+impl PauseReason {
+    pub const ALL: [Self; 4] = [Self::Manual, Self::Schedule, Self::Idle, Self::Rejects];
 
-use crate::{message::ProverMessage, Client};
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Schedule => "schedule",
+            Self::Idle => "idle",
+            Self::Rejects => "rejects",
+        }
+    }
+
+    /// Reasons set in `bits`.
+    fn from_bits(bits: u8) -> Vec<Self> {
+        Self::ALL.into_iter().filter(|reason| bits & reason.bit() != 0).collect()
+    }
+}
+
+/// Snapshot of the prover counters.
+#[derive(Debug, Clone)]
+pub struct ProverStats {
+    pub running: bool,
+    pub paused: bool,
+    /// Why proving is paused, empty while it isn't
+    pub pause_reasons: Vec<PauseReason>,
+    pub current_block: u32,
+    pub total_proofs: u32,
+    /// Proofs that didn't meet the pool target, these are expected and not submitted
+    pub below_target: u32,
+    pub valid_shares: u32,
+    pub invalid_shares: u32,
+    /// Sum of the difficulty of all accepted shares
+    pub accepted_difficulty: u64,
+    pub uptime: Duration,
+    /// Proof rate as credited by the pool (estimate)
+    pub effective_rate: Option<f64>,
+    /// Coins per day, only if reward figures are configured (estimate)
+    pub estimated_daily_yield: Option<f64>,
+    /// Proofs computed for the current job
+    pub job_attempts: u64,
+    /// Effort of the last accepted share, 1.0 being exactly the expected number of attempts
+    pub last_effort: Option<f64>,
+    pub average_effort: Option<f64>,
+    /// Number of CPU proving threads, or CUDA jobs when using GPUs
+    pub threads: usize,
+    /// Number of proofs currently being computed
+    pub in_flight: usize,
+    pub latency: LatencySummary,
+    pub worker_latency: Vec<LatencySummary>,
+    /// (valid, invalid) shares per worker
+    pub worker_shares: Vec<(u32, u32)>,
+    /// Time since the last accepted share, only while the no-share alert is raised
+    pub no_share_alert: Option<Duration>,
+    /// Descriptions of the breached alert thresholds
+    pub threshold_breaches: Vec<String>,
+    /// Number of attempts the watchdog flagged as stuck
+    pub watchdog_interventions: u32,
+    /// Supervision state of every worker
+    pub devices: Vec<DeviceStatus>,
+    /// Shares dropped before submitting because the pool had moved on to a newer height
+    pub local_stale: u32,
+    /// Name of the CPU code path in use
+    pub cpu_path: &'static str,
+    /// Highest difficulty of any proof this session, whether it met the pool target or not
+    pub best_difficulty: u64,
+    /// Difficulty target of the current block
+    pub network_target: u64,
+    /// (name, valid, invalid) shares per worker group
+    pub group_shares: Vec<(String, u32, u32)>,
+    /// (window in minutes, proofs per second) as of the last periodic stats
+    pub proof_rates: Vec<(u32, Option<f64>)>,
+    /// When the last share was accepted
+    pub last_share: Option<SystemTime>,
+    /// GPU sensor readings as of the last periodic stats
+    pub gpu_telemetry: Vec<GpuTelemetry>,
+    /// Rejected shares by result code over all pool connections
+    pub rejections: RejectBreakdown,
+    /// Channel from the pool connections to the prover
+    pub channel: ChannelStats,
+    /// Target and realized share of the proving time per pool, when split between pools
+    pub split: Vec<SplitShare>,
+}
 
+/// Proves the templates from the pool on the CPU or the GPUs and submits the shares.
 pub struct Prover {
-    thread_pools: Arc<Vec<Arc<ThreadPool>>>,
+    thread_pools: RwLock<Vec<Arc<ThreadPool>>>,
+    pool_threads: u16,
+    nice: bool,
+    active_workers: AtomicUsize,
+    busy: Vec<AtomicBool>,
     cuda: Option<Vec<i16>>,
     cuda_jobs: Option<u8>,
-    sender: Arc<mpsc::Sender<ProverEvent>>,
+    sender: InstrumentedSender<ProverEvent>,
+    receiver: Mutex<Option<mpsc::Receiver<ProverEvent>>>,
     client: Arc<Client>,
+    groups: Vec<(WorkerGroup, Arc<Client>)>,
+    /// Connection each worker submits its shares on
+    worker_clients: Vec<Arc<Client>>,
+    running: Arc<AtomicBool>,
+    /// `PauseReason` bits, proving only while none is set
+    paused: AtomicU8,
     terminator: Arc<AtomicBool>,
     current_block: Arc<AtomicU32>,
+    /// Incremented on every dispatched job, workers of older epochs exit
+    epoch: AtomicU64,
+    pool_target: AtomicU64,
+    network_target: AtomicU64,
+    best_difficulty: AtomicU64,
+    current_work: Mutex<Option<(u64, BlockTemplate<Testnet2>)>>,
+    /// Reject guard for the share results that can't be attributed to a device, pausing all of them
+    reject_guard: Mutex<RejectGuard>,
+    /// Reject guard of every device, pausing only that device
+    device_guards: Vec<std::sync::Mutex<RejectGuard>>,
+    /// Workers of the devices the reject guard paused
+    device_paused: Vec<AtomicBool>,
+    job: Mutex<Vec<JoinHandle<()>>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
     total_proofs: Arc<AtomicU32>,
+    below_target: AtomicU32,
     valid_shares: Arc<AtomicU32>,
     invalid_shares: Arc<AtomicU32>,
+    latencies: Arc<Vec<LatencyHistogram>>,
+    latency_drift: f64,
+    deterministic_seed: Option<u64>,
+    // Worker and pool target of each share awaiting its result.
+    submitted: Mutex<HashMap<<Testnet2 as Network>::PoSWNonce, (usize, u64, Instant)>>,
+    worker_shares: Arc<Vec<(AtomicU32, AtomicU32)>>,
+    proof_limit: Option<ProofLimit>,
+    in_flight: AtomicUsize,
+    rate_report: std::sync::Mutex<ReportPolicy>,
+    earnings: EarningsConfig,
+    accepted_difficulty: AtomicU64,
+    started: RwLock<Option<Instant>>,
+    job_attempts: AtomicU64,
+    attempts_since_accept: AtomicU64,
+    effort: std::sync::Mutex<EffortTracker>,
+    share_alert: std::sync::Mutex<ShareAlert>,
+    thresholds: std::sync::Mutex<ThresholdMonitor>,
+    heartbeats: Heartbeats,
+    pipelines: Vec<Pipeline>,
+    cpu_path: CpuPath,
+    proof_rates: std::sync::Mutex<Vec<(u32, Option<f64>)>>,
+    last_share: std::sync::Mutex<Option<SystemTime>>,
+    notifier: Option<Arc<Notifier>>,
+    watchdog_multiple: f64,
+    watchdog_interventions: AtomicU32,
+    telemetry: Option<Arc<dyn TelemetrySampler>>,
+    gpu_telemetry: std::sync::Mutex<Vec<GpuTelemetry>>,
+    // Pool the current job came from when splitting, its shares go there instead of the worker's
+    // connection.
+    job_client: RwLock<Option<Arc<Client>>>,
+    // Spans of the current job, workers started for it record their first attempt there.
+    job_trace: RwLock<Option<JobTrace>>,
+    split: std::sync::Mutex<Vec<SplitShare>>,
+    proving: bool,
+    backend: Option<Arc<dyn ProvingBackend>>,
+    // Set once the proving parameters are loaded, to the time loading took.
+    parameters: OnceCell<Duration>,
 }
 
+// Number of accepted shares the average effort is computed over.
+const EFFORT_HISTORY: usize = 20;
+
+/// What the pool connection tells the prover.
 #[allow(clippy::large_enum_variant)]
 pub enum ProverEvent {
-    NewWork(u64, BlockTemplate<Testnet2>),
-    Result(bool, Option<String>),
+    NewWork(u64, BlockTemplate<Testnet2>, JobTrace),
+    /// Work from one of the pools the proving time is split between, its shares are submitted there
+    PoolWork(Arc<Client>, u64, BlockTemplate<Testnet2>, JobTrace),
+    Result {
+        code: Code,
+        message: Option<String>,
+        height: u32,
+        nonce: Option<<Testnet2 as Network>::PoSWNonce>,
+        device: Option<usize>,
+    },
+    /// Stops all workers and background tasks, then acknowledges on the sender
+    Exit(oneshot::Sender<()>),
 }
 
+// Submits older than this are unlikely to ever get a result.
+const SUBMITTED_TTL: Duration = Duration::from_secs(600);
+
+// Consecutive backend errors after which a worker is restarted.
+const MAX_BACKEND_ERRORS: u32 = 5;
+
+// Time workers get to finish their current attempt on exit.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl Prover {
-    pub async fn init(
-        threads: u16,
-        client: Arc<Client>,
-        cuda: Option<Vec<i16>>,
-        cuda_jobs: Option<u8>,
-    ) -> Result<Arc<Self>> {
+    /// Starts the proving threads, work comes through `event_sender`.
+    pub fn new(config: ProverConfig, client: Arc<Client>) -> Result<Arc<Self>> {
+        let ProverConfig {
+            threads,
+            cuda,
+            cuda_jobs,
+            reject_window,
+            reject_threshold,
+            reject_cooldown,
+            reject_probation,
+            latency_drift,
+            deterministic_seed,
+            max_concurrent_proofs,
+            rate_report,
+            earnings,
+            nice,
+            share_alert,
+            watchdog_multiple,
+            cpu_path,
+            groups,
+            notifier,
+            telemetry,
+            thresholds,
+            proving,
+            backend,
+        } = config;
         let mut thread_pools: Vec<Arc<ThreadPool>> = Vec::new();
         let pool_count;
         let pool_threads;
@@ -59,20 +352,21 @@ impl Prover {
             pool_count = threads / 6;
             pool_threads = 6;
         }
-        if cuda.is_none() {
+        let max_workers;
+        if !proving {
+            info!("Not proving, the proof rate is reported as given");
+            max_workers = 0;
+        } else if cuda.is_none() {
             for index in 0..pool_count {
-                let pool = ThreadPoolBuilder::new()
-                    .stack_size(16 * 1024 * 1024)
-                    .num_threads(pool_threads as usize)
-                    .thread_name(move |idx| format!("ap-cpu-{}-{}", index, idx))
-                    .build()?;
-                thread_pools.push(Arc::new(pool));
+                thread_pools.push(Arc::new(Self::cpu_pool(index as usize, pool_threads, nice)?));
             }
             info!(
                 "Created {} prover thread pools with {} threads each",
                 thread_pools.len(),
                 pool_threads
             );
+            // Room to scale up to one thread per logical CPU at runtime.
+            max_workers = thread_pools.len().max(num_cpus::get() / pool_threads as usize);
         } else {
             let total_jobs = cuda_jobs.unwrap_or(1) * cuda.clone().unwrap().len() as u8;
             for index in 0..total_jobs {
@@ -80,114 +374,921 @@ impl Prover {
                     .stack_size(8 * 1024 * 1024)
                     .num_threads(2)
                     .thread_name(move |idx| format!("ap-cuda-{}-{}", index, idx))
+                    .start_handler(move |_| {
+                        if nice {
+                            idle::set_lowest_priority();
+                        }
+                    })
                     .build()?;
                 thread_pools.push(Arc::new(pool));
             }
             info!("Created {} prover thread pools with 2 threads each", thread_pools.len(),);
+            max_workers = thread_pools.len();
         }
 
-        let (sender, mut receiver) = mpsc::channel(1024);
-        let terminator = Arc::new(AtomicBool::new(false));
-        let prover = Arc::new(Self {
-            thread_pools: Arc::new(thread_pools),
+        if let Some(max) = max_concurrent_proofs {
+            info!("Limiting concurrent proofs to {}", max);
+        }
+
+        let worker_clients = (0..max_workers)
+            .map(|worker| {
+                let device = cuda
+                    .as_ref()
+                    .and_then(|cuda| cuda.get(worker / cuda_jobs.unwrap_or(1) as usize).copied());
+                device
+                    .and_then(|device| groups.iter().find(|(group, _)| group.devices.contains(&device)))
+                    .map(|(_, group_client)| group_client.clone())
+                    .unwrap_or_else(|| client.clone())
+            })
+            .collect();
+        for (group, _) in groups.iter() {
+            info!("GPUs {:?} submit as worker {}", group.devices, group.name);
+        }
+
+        let latencies = (0..max_workers).map(|_| LatencyHistogram::default()).collect();
+        let devices = cuda.as_ref().map_or(max_workers, |cuda| cuda.len());
+        let device_guards = (0..devices)
+            .map(|_| {
+                std::sync::Mutex::new(RejectGuard::new(
+                    reject_window,
+                    reject_threshold,
+                    reject_cooldown,
+                    reject_probation,
+                ))
+            })
+            .collect();
+        let worker_shares = (0..max_workers).map(|_| Default::default()).collect();
+        let (sender, receiver) = channel::channel("prover_events", 1024);
+        Ok(Arc::new(Self {
+            active_workers: AtomicUsize::new(thread_pools.len()),
+            thread_pools: RwLock::new(thread_pools),
+            pool_threads,
+            nice,
+            busy: (0..max_workers).map(|_| Default::default()).collect(),
             cuda,
             cuda_jobs,
-            sender: Arc::new(sender),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
             client,
-            terminator,
+            groups,
+            worker_clients,
+            running: Default::default(),
+            paused: Default::default(),
+            terminator: Default::default(),
             current_block: Default::default(),
+            epoch: Default::default(),
+            pool_target: AtomicU64::new(u64::MAX),
+            network_target: AtomicU64::new(u64::MAX),
+            best_difficulty: Default::default(),
+            current_work: Default::default(),
+            reject_guard: Mutex::new(RejectGuard::new(
+                reject_window,
+                reject_threshold,
+                reject_cooldown,
+                reject_probation,
+            )),
+            device_guards,
+            device_paused: (0..max_workers).map(|_| Default::default()).collect(),
+            job: Default::default(),
+            tasks: Default::default(),
             total_proofs: Default::default(),
+            below_target: Default::default(),
             valid_shares: Default::default(),
             invalid_shares: Default::default(),
-        });
+            latencies: Arc::new(latencies),
+            latency_drift,
+            deterministic_seed,
+            submitted: Default::default(),
+            worker_shares: Arc::new(worker_shares),
+            proof_limit: max_concurrent_proofs.map(ProofLimit::new),
+            in_flight: Default::default(),
+            rate_report: std::sync::Mutex::new(rate_report),
+            earnings,
+            accepted_difficulty: Default::default(),
+            started: Default::default(),
+            job_attempts: Default::default(),
+            attempts_since_accept: Default::default(),
+            effort: std::sync::Mutex::new(EffortTracker::new(EFFORT_HISTORY)),
+            share_alert: std::sync::Mutex::new(ShareAlert::new(share_alert, Instant::now())),
+            thresholds: std::sync::Mutex::new(ThresholdMonitor::new(thresholds)),
+            heartbeats: Heartbeats::new(max_workers),
+            pipelines: (0..max_workers).map(|_| Default::default()).collect(),
+            cpu_path,
+            proof_rates: Default::default(),
+            last_share: Default::default(),
+            notifier,
+            watchdog_multiple,
+            watchdog_interventions: Default::default(),
+            telemetry,
+            gpu_telemetry: Default::default(),
+            job_client: Default::default(),
+            job_trace: Default::default(),
+            split: Default::default(),
+            proving,
+            backend,
+            parameters: OnceCell::new(),
+        }))
+    }
 
-        let p = prover.clone();
-        let _ = task::spawn(async move {
+    fn cpu_pool(index: usize, pool_threads: u16, nice: bool) -> Result<ThreadPool> {
+        Ok(ThreadPoolBuilder::new()
+            .stack_size(16 * 1024 * 1024)
+            .num_threads(pool_threads as usize)
+            .thread_name(move |idx| format!("ap-cpu-{}-{}", index, idx))
+            .start_handler(move |_| {
+                if nice {
+                    idle::set_lowest_priority();
+                }
+            })
+            .build()?)
+    }
+
+    /// Starts handling prover events. Work received before this is queued in the event channel.
+    pub async fn start(self: &Arc<Self>) -> Result<()> {
+        let mut receiver = match self.receiver.lock().await.take() {
+            Some(receiver) => receiver,
+            None => return Err(anyhow!("Prover has already been started")),
+        };
+        self.running.store(true, Ordering::SeqCst);
+        *self.started.write().unwrap() = Some(Instant::now());
+        let mut tasks = self.tasks.lock().await;
+
+        // Not part of `tasks` as it has to outlive them to handle `Exit`.
+        let p = self.clone();
+        task::spawn(async move {
             while let Some(msg) = receiver.recv().await {
                 match msg {
-                    ProverEvent::NewWork(pool_target, block_template) => {
-                        p.new_work(pool_target, block_template).await;
+                    ProverEvent::NewWork(pool_target, block_template, trace) => {
+                        p.new_work(None, pool_target, block_template, trace).await;
+                    }
+                    ProverEvent::PoolWork(client, pool_target, block_template, trace) => {
+                        p.new_work(Some(client), pool_target, block_template, trace).await;
                     }
-                    ProverEvent::Result(success, error) => {
-                        p.result(success, error).await;
+                    ProverEvent::Result {
+                        code,
+                        message,
+                        height,
+                        nonce,
+                        device,
+                    } => {
+                        p.result(code == Code::Success, message, height, nonce, device).await;
+                    }
+                    ProverEvent::Exit(done) => {
+                        p.teardown().await;
+                        let _ = done.send(());
+                        break;
                     }
                 }
             }
         });
         debug!("Created prover message handler");
 
-        let terminator = prover.terminator.clone();
+        let p = self.clone();
+        tasks.push(spawn_helper("proof rate calculator", move || {
+            let p = p.clone();
+            let total_proofs = p.total_proofs.clone();
+            let latencies = p.latencies.clone();
+            let latency_drift = p.latency_drift;
+            async move {
+                fn calculate(now: u32, past: u32, interval: u32) -> f64 {
+                    (now - past) as f64 / (interval * 60) as f64
+                }
+                fn proof_rate(now: u32, past: u32, interval: u32) -> Option<f64> {
+                    if interval < 1 || now <= past || past == 0 {
+                        return None;
+                    }
+                    Some(calculate(now, past, interval))
+                }
+                fn calculate_proof_rate(now: u32, past: u32, interval: u32) -> String {
+                    units::format_optional_rate(proof_rate(now, past, interval))
+                }
+                let mut log = VecDeque::<u32>::from(vec![0; 60]);
+                let mut last_latencies = vec![HistogramSnapshot::default(); latencies.len()];
+                loop {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    let proofs = total_proofs.load(Ordering::SeqCst);
+                    log.push_back(proofs);
+                    let m1 = *log.get(59).unwrap_or(&0);
+                    let m5 = *log.get(55).unwrap_or(&0);
+                    let m15 = *log.get(45).unwrap_or(&0);
+                    let m30 = *log.get(30).unwrap_or(&0);
+                    let m60 = log.pop_front().unwrap_or_default();
+                    let proof_rates = vec![
+                        (1, proof_rate(proofs, m1, 1)),
+                        (5, proof_rate(proofs, m5, 5)),
+                        (15, proof_rate(proofs, m15, 15)),
+                        (30, proof_rate(proofs, m30, 30)),
+                        (60, proof_rate(proofs, m60, 60)),
+                    ];
+                    let uptime = p.started.read().unwrap().map(|started| started.elapsed().as_secs_f64());
+                    let changes = p.thresholds.lock().unwrap().update(
+                        Instant::now(),
+                        p.client.stats().connected_time,
+                        p.valid_shares.load(Ordering::SeqCst),
+                        p.invalid_shares.load(Ordering::SeqCst),
+                        proof_rate(proofs, m15, 15),
+                        uptime.filter(|uptime| *uptime > 0.0).map(|uptime| proofs as f64 / uptime),
+                    );
+                    for change in changes {
+                        p.threshold_changed(change);
+                    }
+                    *p.proof_rates.lock().unwrap() = proof_rates.clone();
+                    p.client.events().publish(MinerEvent::RateSample { proof_rates });
+                    info!(
+                        "{}",
+                        Cyan.normal().paint(format!(
+                            "Total proofs: {} (1m: {}, 5m: {}, 15m: {}, 30m: {}, 60m: {})",
+                            proofs,
+                            calculate_proof_rate(proofs, m1, 1),
+                            calculate_proof_rate(proofs, m5, 5),
+                            calculate_proof_rate(proofs, m15, 15),
+                            calculate_proof_rate(proofs, m30, 30),
+                            calculate_proof_rate(proofs, m60, 60),
+                        ))
+                    );
 
-        task::spawn(async move {
-            let mut counter = false;
-            loop {
-                if terminator.load(Ordering::SeqCst) {
-                    if counter {
-                        debug!("Long terminator detected, resetting");
-                        terminator.store(false, Ordering::SeqCst);
-                        counter = false;
-                    } else {
-                        counter = true;
+                    let snapshots: Vec<HistogramSnapshot> = latencies.iter().map(|h| h.snapshot()).collect();
+                    let mut overall = HistogramSnapshot::default();
+                    for snapshot in snapshots.iter() {
+                        overall.merge(snapshot);
                     }
-                } else {
-                    counter = false;
+                    info!("{}", Cyan.normal().paint(format!("Proof latency: {}", overall.summary())));
+                    if let (Some(telemetry), Some(cuda)) = (p.telemetry.as_ref(), p.cuda.as_ref()) {
+                        let readings: Vec<GpuTelemetry> =
+                            cuda.iter().filter_map(|device| telemetry.sample(*device)).collect();
+                        if !readings.is_empty() {
+                            let gpus: Vec<String> = readings.iter().map(|reading| reading.to_string()).collect();
+                            info!("{}", Cyan.normal().paint(gpus.join(", ")));
+                        }
+                        *p.gpu_telemetry.lock().unwrap() = readings;
+                    }
+                    let stats = p.stats();
+                    if let Some(rate) = stats.effective_rate {
+                        let estimate = match stats.estimated_daily_yield {
+                            Some(daily_yield) => format!(
+                                "Effective proof rate (estimate): {}, daily yield (estimate): {:.4}",
+                                units::format_rate(rate),
+                                daily_yield
+                            ),
+                            None => format!("Effective proof rate (estimate): {}", units::format_rate(rate)),
+                        };
+                        info!("{}", Cyan.normal().paint(estimate));
+                    }
+                    if stats.devices.iter().any(|device| device.failures > 0) {
+                        let devices: Vec<String> = stats.devices.iter().map(|device| device.to_string()).collect();
+                        info!("{}", Cyan.normal().paint(format!("Devices: {}", devices.join(", "))));
+                    }
+                    let client_stats = p.client.stats();
+                    info!("{}", Cyan.normal().paint(format!("Pool round trip time: {}", client_stats.rtt)));
+                    if client_stats.dry_run {
+                        let message = format!("DRY RUN: {} shares found and not submitted", client_stats.would_submit);
+                        info!("{}", Red.bold().paint(message));
+                    }
+                    if stats.rejections.total() > 0 {
+                        info!("{}", Cyan.normal().paint(format!("Rejected: {}", stats.rejections)));
+                    }
+                    if !stats.split.is_empty() {
+                        let split: Vec<String> = stats.split.iter().map(|share| share.to_string()).collect();
+                        info!("{}", Cyan.normal().paint(format!("Split: {}", split.join(", "))));
+                    }
+                    if let Some(pool_info) = client_stats.pool_info.as_ref().filter(|fields| !fields.is_empty()) {
+                        info!("{}", Cyan.normal().paint(format!("Pool: {}", pool_info_line(pool_info))));
+                    }
+                    for (name, valid, invalid) in stats.group_shares.iter() {
+                        info!(
+                            "{}",
+                            Cyan.normal()
+                                .paint(format!("Worker {}: {} / {} shares accepted", name, valid, valid + invalid))
+                        );
+                    }
+                    if stats.best_difficulty > 0 {
+                        info!(
+                            "{}",
+                            Cyan.normal().paint(format!(
+                                "Best share: {}",
+                                estimate::best_share(stats.best_difficulty, stats.network_target)
+                            ))
+                        );
+                    }
+                    if let (Some(last), Some(average)) = (stats.last_effort, stats.average_effort) {
+                        info!(
+                            "{}",
+                            Cyan.normal().paint(format!(
+                                "Effort: last {:.0}%, avg({}) {:.0}%",
+                                last * 100.0,
+                                EFFORT_HISTORY,
+                                average * 100.0
+                            ))
+                        );
+                    }
+                    let interval: Vec<HistogramSnapshot> = snapshots
+                        .iter()
+                        .zip(last_latencies.iter())
+                        .map(|(now, past)| now.delta(past))
+                        .collect();
+                    for (worker, median, drift) in drifting_workers(&interval, latency_drift) {
+                        warn!(
+                            "Worker {} median proof latency {}ms drifts {:.0}% from the fleet median",
+                            worker,
+                            median.as_millis(),
+                            drift
+                        );
+                    }
+                    last_latencies = snapshots;
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
             }
-        });
-        debug!("Created prover terminator guard");
+        }));
+        debug!("Created proof rate calculator");
 
-        let client = prover.client.clone();
-        let total_proofs = prover.total_proofs.clone();
-        task::spawn(async move {
-            fn calculate(now: u32, past: u32, interval: u32) -> f64 {
-                (now - past) as f64 / (interval * 60) as f64
+        let p = self.clone();
+        tasks.push(spawn_helper("proof rate reporter", move || {
+            let p = p.clone();
+            let total_proofs = p.total_proofs.clone();
+            async move {
+                if !p.proving {
+                    return;
+                }
+                let mut samples = VecDeque::<(Instant, u32)>::new();
+                let mut last: Option<(f64, Instant)> = None;
+                loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    let now = Instant::now();
+                    let proofs = total_proofs.load(Ordering::SeqCst);
+                    samples.push_back((now, proofs));
+                    // One minute of samples for the 1m rate.
+                    while samples.len() > 61 {
+                        samples.pop_front();
+                    }
+                    let (oldest_time, oldest_proofs) = samples[0];
+                    let elapsed = now.duration_since(oldest_time).as_secs_f64();
+                    if elapsed < 1.0 {
+                        continue;
+                    }
+                    let rate = proofs.saturating_sub(oldest_proofs) as f64 / elapsed;
+                    let policy = *p.rate_report.lock().unwrap();
+                    if policy.should_report(rate, last.map(|(rate, time)| (rate, now.duration_since(time)))) {
+                        // Each worker group reports the part of the rate its devices contribute. Without
+                        // workers, e.g. fewer threads than one CPU pool takes, there is nothing to split.
+                        let workers = p.active_workers.load(Ordering::SeqCst).min(p.worker_clients.len());
+                        if workers == 0 {
+                            p.client.report_proof_rate((rate * 100.0) as u64);
+                        } else {
+                            let clients = std::iter::once(&p.client).chain(p.groups.iter().map(|(_, client)| client));
+                            for client in clients {
+                                let share = p.worker_clients[..workers]
+                                    .iter()
+                                    .filter(|worker_client| Arc::ptr_eq(worker_client, client))
+                                    .count();
+                                client.report_proof_rate((rate * share as f64 / workers as f64 * 100.0) as u64);
+                            }
+                        }
+                        last = Some((rate, now));
+                    }
+                }
             }
-            fn calculate_proof_rate(now: u32, past: u32, interval: u32) -> Box<str> {
-                if interval < 1 {
-                    return Box::from("---");
+        }));
+        debug!("Created proof rate reporter");
+
+        let p = self.clone();
+        tasks.push(spawn_helper("reject guard", move || {
+            let p = p.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    let now = Instant::now();
+                    let action = p.reject_guard.lock().await.poll(now);
+                    if action == GuardAction::Resume {
+                        warn!("Reject cool-down elapsed, resuming on probation");
+                        p.resume(PauseReason::Rejects).await;
+                    }
+                    for device in 0..p.device_guards.len() {
+                        let action = p.device_guards[device].lock().unwrap().poll(now);
+                        if action == GuardAction::Resume {
+                            warn!("Reject cool-down of {} elapsed, resuming it on probation", p.device_name(device));
+                            p.resume_device(device).await;
+                        }
+                    }
                 }
-                if now <= past || past == 0 {
-                    return Box::from("---");
+            }
+        }));
+        debug!("Created reject guard");
+
+        let p = self.clone();
+        tasks.push(spawn_helper("no-share alert", move || {
+            let p = p.clone();
+            async move {
+                let mut samples = VecDeque::<(Instant, u32)>::new();
+                loop {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    let now = Instant::now();
+                    let proofs = p.total_proofs.load(Ordering::SeqCst);
+                    samples.push_back((now, proofs));
+                    // Five minutes of samples to estimate the expected share interval.
+                    while samples.len() > 31 {
+                        samples.pop_front();
+                    }
+                    let mut alert = p.share_alert.lock().unwrap();
+                    if p.is_paused() {
+                        alert.hold(now);
+                        continue;
+                    }
+                    let (oldest_time, oldest_proofs) = samples[0];
+                    let elapsed = now.duration_since(oldest_time).as_secs_f64();
+                    let expected_interval = if elapsed > 0.0 {
+                        let rate = proofs.saturating_sub(oldest_proofs) as f64 / elapsed;
+                        alert::expected_share_interval(rate, p.pool_target.load(Ordering::SeqCst))
+                    } else {
+                        None
+                    };
+                    if let Some(AlertChange::Raised(elapsed)) = alert.poll(now, expected_interval) {
+                        let message = format!(
+                            "No share has been accepted for {} minutes, check the miner and the pool",
+                            elapsed.as_secs() / 60
+                        );
+                        error!("{}", Red.normal().paint(&message));
+                        if let Some(notifier) = p.notifier.as_ref() {
+                            notifier.notify(EventKind::NoShare, message);
+                        }
+                    }
                 }
-                let rate = calculate(now, past, interval);
-                Box::from(format!("{:.2}", rate))
-            }
-            let mut log = VecDeque::<u32>::from(vec![0; 60]);
-            loop {
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                let proofs = total_proofs.load(Ordering::SeqCst);
-                log.push_back(proofs);
-                let m1 = *log.get(59).unwrap_or(&0);
-                let m5 = *log.get(55).unwrap_or(&0);
-                let m15 = *log.get(45).unwrap_or(&0);
-                let m30 = *log.get(30).unwrap_or(&0);
-                let m60 = log.pop_front().unwrap_or_default();
-                info!(
-                    "{}",
-                    Cyan.normal().paint(format!(
-                        "Total proofs: {} (1m: {} p/s, 5m: {} p/s, 15m: {} p/s, 30m: {} p/s, 60m: {} p/s)",
-                        proofs,
-                        calculate_proof_rate(proofs, m1, 1),
-                        calculate_proof_rate(proofs, m5, 5),
-                        calculate_proof_rate(proofs, m15, 15),
-                        calculate_proof_rate(proofs, m30, 30),
-                        calculate_proof_rate(proofs, m60, 60),
-                    ))
-                );
             }
-        });
-        debug!("Created proof rate calculator");
+        }));
+        debug!("Created no-share alert");
 
-        Ok(prover)
+        let p = self.clone();
+        tasks.push(spawn_helper("watchdog", move || {
+            let p = p.clone();
+            async move {
+                let mut watchdog = Watchdog::new(p.busy.len(), p.watchdog_multiple);
+                let mut baseline = HistogramSnapshot::default();
+                let mut baseline_time = Instant::now();
+                loop {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    let mut overall = HistogramSnapshot::default();
+                    for histogram in p.latencies.iter() {
+                        overall.merge(&histogram.snapshot());
+                    }
+                    // Median of the last minutes, or of the whole session while there are too few samples.
+                    let window = overall.delta(&baseline);
+                    let median = if window.count() >= 10 {
+                        window.percentile(0.5)
+                    } else {
+                        overall.percentile(0.5)
+                    };
+                    if baseline_time.elapsed() > Duration::from_secs(300) {
+                        baseline = overall;
+                        baseline_time = Instant::now();
+                    }
+                    for (worker, elapsed) in watchdog.check(&p.heartbeats, median) {
+                        p.watchdog_interventions.fetch_add(1, Ordering::SeqCst);
+                        // The terminator is shared by all workers and a hung device call never checks it,
+                        // so neither backend can cancel a single attempt.
+                        error!(
+                            "Worker {} on {} has been stuck in a proof for {}s (median {}ms)",
+                            worker,
+                            p.worker_device(worker),
+                            elapsed.as_secs(),
+                            median.unwrap_or_default().as_millis()
+                        );
+                    }
+                }
+            }
+        }));
+        debug!("Created watchdog");
+
+        info!("Prover started");
+        Ok(())
+    }
+
+    /// Aborts in-flight work and keeps the workers idle until `resume` is called with the same
+    /// reason and with every other reason set since. New work is still tracked while paused.
+    pub async fn pause(&self, reason: PauseReason) {
+        let previous = self.paused.fetch_or(reason.bit(), Ordering::SeqCst);
+        if previous & reason.bit() != 0 {
+            return;
+        }
+        if previous != 0 {
+            debug!("Prover paused ({}), already paused", reason.name());
+            return;
+        }
+        let mut job = self.job.lock().await;
+        self.halt(&mut job).await;
+        info!("Prover paused ({})", reason.name());
     }
 
-    pub fn sender(&self) -> Arc<mpsc::Sender<ProverEvent>> {
+    /// Lifts a pause for `reason` and resumes proving on the latest work received unless the prover
+    /// is paused for another reason. Doesn't reset the reject guard, see `clear_rejects`.
+    pub async fn resume(self: &Arc<Self>, reason: PauseReason) {
+        let previous = self.paused.fetch_and(!reason.bit(), Ordering::SeqCst);
+        if previous & reason.bit() == 0 {
+            return;
+        }
+        let remaining = PauseReason::from_bits(previous & !reason.bit());
+        if !remaining.is_empty() {
+            let remaining: Vec<&str> = remaining.iter().map(PauseReason::name).collect();
+            info!("{} pause lifted, still paused ({})", reason.name(), remaining.join(", "));
+            return;
+        }
+        info!("Prover resumed");
+        let work = self.current_work.lock().await.clone();
+        if let Some((pool_target, block_template)) = work {
+            // Work held since before the first load.
+            if let Err(e) = self.warmup().await {
+                error!("Loading the proving parameters failed: {}", e);
+                return;
+            }
+            self.dispatch(pool_target, block_template).await;
+        }
+    }
+
+    /// Forgets the share results the reject guard has seen and lifts a pause for rejects, when the
+    /// operator knows the cause is gone.
+    pub async fn clear_rejects(self: &Arc<Self>) {
+        self.reject_guard.lock().await.reset();
+        for device in 0..self.device_guards.len() {
+            self.device_guards[device].lock().unwrap().reset();
+            self.resume_device(device).await;
+        }
+        self.resume(PauseReason::Rejects).await;
+    }
+
+    /// Stops the workers of one device after their current attempt, the others keep proving.
+    async fn pause_device(&self, device: usize) {
+        for worker in self.device_workers(device) {
+            self.device_paused[worker].store(true, Ordering::SeqCst);
+        }
+        info!("{} paused (rejects)", self.device_name(device));
+    }
+
+    /// Lifts the pause of one device and puts its workers back on the current job, if any.
+    async fn resume_device(self: &Arc<Self>, device: usize) {
+        let workers = self.device_workers(device);
+        let mut resumed = false;
+        for worker in workers.clone() {
+            resumed |= self.device_paused[worker].swap(false, Ordering::SeqCst);
+        }
+        if !resumed {
+            return;
+        }
+        info!("{} resumed", self.device_name(device));
+        let work = self.current_work.lock().await.clone();
+        if let Some((_, block_template)) = work {
+            let mut job = self.job.lock().await;
+            // Nothing is dispatched while the whole prover is paused or stopped.
+            if job.is_empty() || self.is_paused() {
+                return;
+            }
+            let thread_pools = self.thread_pools.read().unwrap().clone();
+            let active = self.active_workers.load(Ordering::SeqCst);
+            for worker in workers.filter(|worker| *worker < active.min(thread_pools.len())) {
+                let backend = self.worker_backend(worker);
+                self.spawn_worker(&mut job, worker, thread_pools[worker].clone(), backend, &block_template);
+            }
+        }
+    }
+
+    // Whether the device of `worker` is paused for rejects.
+    fn device_paused(&self, worker: usize) -> bool {
+        self.device_paused[worker].load(Ordering::SeqCst)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst) != 0
+    }
+
+    /// Aborts in-flight work, waits for all workers to exit and stops handling events.
+    pub async fn stop(&self) {
+        if !self.running.load(Ordering::SeqCst) {
+            return;
+        }
+        let (done, exited) = oneshot::channel();
+        if self.sender.send(ProverEvent::Exit(done)).await.is_err() {
+            self.teardown().await;
+            return;
+        }
+        if timeout(EXIT_TIMEOUT * 2, exited).await.is_err() {
+            warn!("Prover did not stop in time");
+        }
+    }
+
+    /// Cancels the current job, waits for the workers to finish and stops the background tasks.
+    async fn teardown(&self) {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let mut job = self.job.lock().await;
+        if timeout(EXIT_TIMEOUT, self.halt(&mut job)).await.is_err() {
+            warn!("Some prover workers did not finish within {}s", EXIT_TIMEOUT.as_secs());
+        }
+        for task in self.tasks.lock().await.drain(..) {
+            task.abort();
+        }
+        info!(
+            "Prover stopped after {} proofs, {} valid and {} invalid shares",
+            self.total_proofs.load(Ordering::SeqCst),
+            self.valid_shares.load(Ordering::SeqCst),
+            self.invalid_shares.load(Ordering::SeqCst)
+        );
+    }
+
+    /// Changes the number of CPU proving threads without interrupting the current work.
+    /// Threads are added and removed in units of one thread pool; removed workers finish their
+    /// current attempt before exiting. Returns the effective thread count.
+    pub async fn set_threads(self: &Arc<Self>, threads: u16) -> Result<usize> {
+        if self.cuda.is_some() {
+            return Err(anyhow!("Thread count can't be changed when proving with GPUs"));
+        }
+        let mut workers = (threads / self.pool_threads).max(1) as usize;
+        if workers > self.busy.len() {
+            warn!(
+                "Requested {} threads but at most {} are supported",
+                threads,
+                self.busy.len() * self.pool_threads as usize
+            );
+            workers = self.busy.len();
+        }
+        {
+            let mut thread_pools = self.thread_pools.write().unwrap();
+            while thread_pools.len() < workers {
+                let index = thread_pools.len();
+                thread_pools.push(Arc::new(Self::cpu_pool(index, self.pool_threads, self.nice)?));
+            }
+        }
+        let previous = self.active_workers.swap(workers, Ordering::SeqCst);
+        info!(
+            "Prover threads changed from {} to {}",
+            previous * self.pool_threads as usize,
+            workers * self.pool_threads as usize
+        );
+
+        if workers > previous {
+            let work = self.current_work.lock().await.clone();
+            if let Some((_, block_template)) = work {
+                let mut job = self.job.lock().await;
+                if !job.is_empty() {
+                    let thread_pools = self.thread_pools.read().unwrap().clone();
+                    for worker in previous..workers {
+                        self.spawn_worker(
+                            &mut job,
+                            worker,
+                            thread_pools[worker].clone(),
+                            self.backend(-1),
+                            &block_template,
+                        );
+                    }
+                }
+            }
+        }
+        Ok(workers * self.pool_threads as usize)
+    }
+
+    /// Replaces the settings that can change while running.
+    pub fn reconfigure(&self, rate_report: ReportPolicy, share_alert: Duration, thresholds: Thresholds) {
+        *self.rate_report.lock().unwrap() = rate_report;
+        self.share_alert.lock().unwrap().set_base(share_alert);
+        self.thresholds.lock().unwrap().set_thresholds(thresholds);
+    }
+
+    fn threshold_changed(&self, change: ThresholdChange) {
+        let (alert, details, raised) = match change {
+            ThresholdChange::Raised(breach, details) => {
+                error!("{}", Red.normal().paint(format!("Alert {}: {}", breach.name(), details)));
+                (breach.name(), details, true)
+            }
+            ThresholdChange::Cleared(breach) => {
+                info!("{}", Green.normal().paint(format!("Alert {} cleared", breach.name())));
+                (breach.name(), format!("{} is back to normal", breach.name()), false)
+            }
+        };
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(EventKind::Threshold, details.clone());
+        }
+        self.client.events().publish(MinerEvent::Threshold { alert, details, raised });
+    }
+
+    /// Snapshot of the counters.
+    pub fn stats(&self) -> ProverStats {
+        let snapshots: Vec<HistogramSnapshot> = self.latencies.iter().map(|h| h.snapshot()).collect();
+        let mut overall = HistogramSnapshot::default();
+        for snapshot in snapshots.iter() {
+            overall.merge(snapshot);
+        }
+        let uptime = self
+            .started
+            .read()
+            .unwrap()
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        let valid_shares = self.valid_shares.load(Ordering::SeqCst);
+        let accepted_difficulty = self.accepted_difficulty.load(Ordering::SeqCst);
+        let (last_effort, average_effort) = {
+            let effort = self.effort.lock().unwrap();
+            (effort.last(), effort.average())
+        };
+        ProverStats {
+            running: self.running.load(Ordering::SeqCst),
+            paused: self.is_paused(),
+            pause_reasons: PauseReason::from_bits(self.paused.load(Ordering::SeqCst)),
+            current_block: self.current_block.load(Ordering::SeqCst),
+            total_proofs: self.total_proofs.load(Ordering::SeqCst),
+            below_target: self.below_target.load(Ordering::SeqCst),
+            valid_shares,
+            invalid_shares: self.invalid_shares.load(Ordering::SeqCst),
+            accepted_difficulty,
+            uptime,
+            effective_rate: estimate::effective_rate(accepted_difficulty, uptime),
+            estimated_daily_yield: estimate::daily_yield(&self.earnings, valid_shares, accepted_difficulty, uptime),
+            job_attempts: self.job_attempts.load(Ordering::SeqCst),
+            last_effort,
+            average_effort,
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+            threads: match self.cuda {
+                Some(_) => self.active_workers.load(Ordering::SeqCst),
+                None => self.active_workers.load(Ordering::SeqCst) * self.pool_threads as usize,
+            },
+            latency: overall.summary(),
+            worker_latency: snapshots.iter().map(|s| s.summary()).collect(),
+            worker_shares: self
+                .worker_shares
+                .iter()
+                .map(|(valid, invalid)| (valid.load(Ordering::SeqCst), invalid.load(Ordering::SeqCst)))
+                .collect(),
+            no_share_alert: self.share_alert.lock().unwrap().raised(Instant::now()),
+            threshold_breaches: self.thresholds.lock().unwrap().breaches(),
+            watchdog_interventions: self.watchdog_interventions.load(Ordering::SeqCst),
+            devices: self
+                .pipelines
+                .iter()
+                .enumerate()
+                .take(self.active_workers.load(Ordering::SeqCst))
+                .map(|(worker, pipeline)| DeviceStatus {
+                    name: self.worker_device(worker),
+                    state: match pipeline.state() {
+                        PipelineState::Idle if self.device_paused(worker) => PipelineState::Paused,
+                        state => state,
+                    },
+                    failures: pipeline.failures(),
+                })
+                .collect(),
+            local_stale: self.client.local_stale()
+                + self.groups.iter().map(|(_, client)| client.local_stale()).sum::<u32>(),
+            cpu_path: self.cpu_path.name(),
+            best_difficulty: self.best_difficulty.load(Ordering::SeqCst),
+            network_target: self.network_target.load(Ordering::SeqCst),
+            group_shares: self
+                .groups
+                .iter()
+                .map(|(group, client)| {
+                    let (valid, invalid) = self
+                        .worker_clients
+                        .iter()
+                        .zip(self.worker_shares.iter())
+                        .filter(|(worker_client, _)| Arc::ptr_eq(worker_client, client))
+                        .fold((0, 0), |(valid, invalid), (_, (v, i))| {
+                            (valid + v.load(Ordering::SeqCst), invalid + i.load(Ordering::SeqCst))
+                        });
+                    (group.name.clone(), valid, invalid)
+                })
+                .collect(),
+            proof_rates: self.proof_rates.lock().unwrap().clone(),
+            last_share: *self.last_share.lock().unwrap(),
+            gpu_telemetry: self.gpu_telemetry.lock().unwrap().clone(),
+            rejections: self.groups.iter().fold(self.client.stats().rejections, |mut total, (_, client)| {
+                total += client.stats().rejections;
+                total
+            }),
+            channel: self.sender.metrics().stats(),
+            split: self.split.lock().unwrap().clone(),
+        }
+    }
+
+    /// Device and thread pool a worker proves on.
+    fn worker_device(&self, worker: usize) -> String {
+        match &self.cuda {
+            Some(cuda) => {
+                let jobs = self.cuda_jobs.unwrap_or(1) as usize;
+                let device = cuda.get(worker / jobs).copied().unwrap_or(-1);
+                format!("{} job {}", self.backend(device).name(), worker % jobs)
+            }
+            None => format!("CPU pool {} ({} threads)", worker, self.pool_threads),
+        }
+    }
+
+    // Index of the device `worker` proves on: its GPU in the `--cuda` list, or its own CPU pool.
+    fn device_of(&self, worker: usize) -> usize {
+        match &self.cuda {
+            Some(_) => worker / self.cuda_jobs.unwrap_or(1) as usize,
+            None => worker,
+        }
+    }
+
+    // Workers proving on a device, see `device_of`.
+    fn device_workers(&self, device: usize) -> std::ops::Range<usize> {
+        let jobs = match &self.cuda {
+            Some(_) => self.cuda_jobs.unwrap_or(1) as usize,
+            None => 1,
+        };
+        device * jobs..((device + 1) * jobs).min(self.device_paused.len())
+    }
+
+    fn device_name(&self, device: usize) -> String {
+        match &self.cuda {
+            Some(cuda) => format!("GPU {}", cuda.get(device).copied().unwrap_or(-1)),
+            None => format!("CPU pool {}", device),
+        }
+    }
+
+    fn worker_backend(&self, worker: usize) -> Arc<dyn ProvingBackend> {
+        match &self.cuda {
+            Some(cuda) => self.backend(cuda.get(self.device_of(worker)).copied().unwrap_or(-1)),
+            None => self.backend(-1),
+        }
+    }
+
+    // Backend of a device from the `--cuda` list, -1 being the CPU, unless one was given in the config.
+    fn backend(&self, device: i16) -> Arc<dyn ProvingBackend> {
+        match self.backend.as_ref() {
+            Some(backend) => backend.clone(),
+            None => backend::for_device(device),
+        }
+    }
+
+    /// Updates the split shown in the stats, see `split::spawn`.
+    pub fn set_split(&self, split: Vec<SplitShare>) {
+        *self.split.lock().unwrap() = split;
+    }
+
+    // Tells the connections the workers submit to which job they prove, for their JobAck.
+    fn ack_job(&self, height: u32, pool_target: u64) {
+        match self.job_client.read().unwrap().as_ref() {
+            Some(client) => client.working_on(height, pool_target),
+            None => self
+                .worker_clients
+                .iter()
+                .for_each(|client| client.working_on(height, pool_target)),
+        }
+    }
+
+    // Connection the shares of a worker go to: the pool of the job when splitting, the worker's own
+    // otherwise.
+    fn submit_client(&self, worker: usize) -> Arc<Client> {
+        self.job_client
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| self.worker_clients[worker].clone())
+    }
+
+    /// Sender to pass to `client::start`.
+    pub fn event_sender(&self) -> InstrumentedSender<ProverEvent> {
         self.sender.clone()
     }
 
-    async fn result(&self, success: bool, msg: Option<String>) {
+    async fn result(
+        &self,
+        success: bool,
+        msg: Option<String>,
+        height: u32,
+        nonce: Option<<Testnet2 as Network>::PoSWNonce>,
+        device: Option<usize>,
+    ) {
+        let (worker, share_target) = {
+            let mut submitted = self.submitted.lock().await;
+            submitted.retain(|_, (_, _, time)| time.elapsed() < SUBMITTED_TTL);
+            let share = nonce.and_then(|nonce| submitted.remove(&nonce));
+            (device.or(share.map(|(worker, ..)| worker)), share.map(|(_, target, _)| target))
+        };
+        let worker = worker.filter(|worker| *worker < self.worker_shares.len());
+        match worker.map(|worker| (worker, &self.worker_shares[worker])) {
+            Some((worker, (valid, invalid))) => {
+                if success {
+                    valid.fetch_add(1, Ordering::SeqCst);
+                } else {
+                    invalid.fetch_add(1, Ordering::SeqCst);
+                    debug!("Rejected share for block {} was produced by worker {}", height, worker);
+                }
+            }
+            None => debug!("Unable to attribute the share result for block {} to a worker", height),
+        }
+
         if success {
+            // The target the share was found at, the pool may have changed it since.
+            let pool_target = share_target.unwrap_or_else(|| self.pool_target.load(Ordering::SeqCst));
+            self.accepted_difficulty
+                .fetch_add(estimate::difficulty(pool_target), Ordering::SeqCst);
+            let attempts = self.attempts_since_accept.swap(0, Ordering::SeqCst);
+            let effort = self.effort.lock().unwrap().record(attempts, pool_target);
+            debug!("Share effort: {:.0}% ({} attempts)", effort * 100.0, attempts);
+            if let Some(AlertChange::Cleared(outage)) = self.share_alert.lock().unwrap().accepted(Instant::now()) {
+                info!(
+                    "{}",
+                    Green.normal().paint(format!(
+                        "Shares are accepted again, the alert was raised for {} minutes",
+                        outage.as_secs() / 60
+                    ))
+                );
+            }
+            *self.last_share.lock().unwrap() = Some(SystemTime::now());
             let valid_minus_1 = self.valid_shares.fetch_add(1, Ordering::SeqCst);
             let valid = valid_minus_1 + 1;
             let invalid = self.invalid_shares.load(Ordering::SeqCst);
@@ -240,10 +1341,79 @@ impl Prover {
                 );
             }
         }
+
+        // A share of a known worker counts against its device only, the others against all devices.
+        if let Some(device) = worker.map(|worker| self.device_of(worker)) {
+            let message = {
+                let mut guard = self.device_guards[device].lock().unwrap();
+                if guard.record(success, Instant::now()) != GuardAction::Pause {
+                    return;
+                }
+                format!(
+                    "Reject ratio {:.2}% of {} exceeds the threshold, pausing it for {} seconds",
+                    guard.reject_ratio() * 100.0,
+                    self.device_name(device),
+                    guard.cooldown().as_secs()
+                )
+            };
+            error!("{}", message);
+            if let Some(notifier) = self.notifier.as_ref() {
+                notifier.notify(EventKind::RejectRate, message);
+            }
+            self.pause_device(device).await;
+            return;
+        }
+        let mut guard = self.reject_guard.lock().await;
+        if guard.record(success, Instant::now()) == GuardAction::Pause {
+            let message = format!(
+                "Reject ratio {:.2}% exceeds the threshold, pausing all devices for {} seconds",
+                guard.reject_ratio() * 100.0,
+                guard.cooldown().as_secs()
+            );
+            error!("{}", message);
+            if let Some(notifier) = self.notifier.as_ref() {
+                notifier.notify(EventKind::RejectRate, message);
+            }
+            drop(guard);
+            self.pause(PauseReason::Rejects).await;
+        }
     }
 
-    async fn new_work(&self, pool_target: u64, block_template: BlockTemplate<Testnet2>) {
+    async fn new_work(
+        self: &Arc<Self>,
+        source: Option<Arc<Client>>,
+        pool_target: u64,
+        block_template: BlockTemplate<Testnet2>,
+        mut trace: JobTrace,
+    ) {
         let block_height = block_template.block_height();
+        trace.received();
+        {
+            let mut current_work = self.current_work.lock().await;
+            // The same template from another split pool is a new job, shares are never submitted to
+            // a pool other than the one that sent the job.
+            let same_source = match (self.job_client.read().unwrap().as_ref(), source.as_ref()) {
+                (Some(current), Some(source)) => Arc::ptr_eq(current, source),
+                (current, source) => current.is_none() && source.is_none(),
+            };
+            // Only the target changed, keep the workers going and apply it to the next checks.
+            if let Some((current_target, current_template)) = current_work.as_mut() {
+                if *current_template == block_template && same_source {
+                    if *current_target != pool_target {
+                        *current_target = pool_target;
+                        self.pool_target.store(pool_target, Ordering::SeqCst);
+                        self.ack_job(block_height, pool_target);
+                        info!("Pool difficulty changed to {}", u64::MAX / pool_target);
+                    }
+                    return;
+                }
+            }
+            *current_work = Some((pool_target, block_template.clone()));
+            *self.job_client.write().unwrap() = source;
+            if let Some(previous) = self.job_trace.write().unwrap().replace(trace.clone()) {
+                previous.cancelled("new work");
+            }
+        }
         self.current_block.store(block_height, Ordering::SeqCst);
         info!(
             "Received new work: block {}, pool difficulty {}",
@@ -251,168 +1421,395 @@ impl Prover {
             u64::MAX / pool_target
         );
 
-        let current_block = self.current_block.clone();
-        let terminator = self.terminator.clone();
-        let client = self.client.clone();
-        let thread_pools = self.thread_pools.clone();
-        let total_proofs = self.total_proofs.clone();
-        let cuda = self.cuda.clone();
-        let cuda_jobs = self.cuda_jobs;
+        if !self.proving {
+            return;
+        }
+        if self.is_paused() {
+            debug!("Prover is paused, holding work for block {}", block_height);
+            return;
+        }
+        async {
+            // The next work tries again.
+            if let Err(e) = self.warmup().await {
+                error!("Loading the proving parameters failed: {}", e);
+                return;
+            }
+            self.dispatch(pool_target, block_template).await;
+        }
+        .instrument(trace.activation())
+        .await;
+    }
 
-        task::spawn(async move {
-            terminator.store(true, Ordering::SeqCst);
-            while terminator.load(Ordering::SeqCst) {
-                // Wait until the prover terminator is set to false.
-                tokio::time::sleep(Duration::from_millis(50)).await;
-            }
-
-            let _ = task::spawn(async move {
-                let mut joins = Vec::new();
-                if let Some(cuda) = cuda {
-                    for gpu_index in cuda {
-                        for job_index in 0..cuda_jobs.unwrap_or(1) {
-                            let tp = thread_pools
-                                .get(gpu_index as usize * cuda_jobs.unwrap_or(1) as usize + job_index as usize)
-                                .unwrap();
-                            debug!("Spawning CUDA thread on GPU {} job {}", gpu_index, job_index,);
-
-                            let current_block = current_block.clone();
-                            let terminator = terminator.clone();
-                            let client = client.clone();
-                            let block_template = block_template.clone();
-                            let total_proofs = total_proofs.clone();
-                            let tp = tp.clone();
-                            joins.push(task::spawn(async move {
-                                while !terminator.load(Ordering::SeqCst) {
-                                    let terminator = terminator.clone();
-                                    let block_template = block_template.clone();
-                                    let block_height = block_template.block_height();
-                                    let tp = tp.clone();
-                                    if block_height != current_block.load(Ordering::SeqCst) {
-                                        debug!(
-                                            "Terminating stale work: current {} latest {}",
-                                            block_height,
-                                            current_block.load(Ordering::SeqCst)
-                                        );
-                                        break;
-                                    }
-                                    if let Ok(Ok(block_header)) = task::spawn_blocking(move || {
-                                        tp.install(|| {
-                                            BlockHeader::mine_once_unchecked(
-                                                &block_template,
-                                                &terminator,
-                                                &mut thread_rng(),
-                                                gpu_index,
-                                            )
-                                        })
-                                    })
-                                    .await
-                                    {
-                                        if block_height != current_block.load(Ordering::SeqCst) {
-                                            debug!(
-                                                "Terminating stale work: current {} latest {}",
-                                                block_height,
-                                                current_block.load(Ordering::SeqCst)
-                                            );
-                                            break;
-                                        }
-                                        // Ensure the share difficulty target is met.
-                                        let nonce = block_header.nonce();
-                                        let proof = block_header.proof().clone();
-                                        let proof_target = proof.to_proof_difficulty().unwrap_or(u64::MAX);
-                                        if proof_target > pool_target {
-                                            debug!(
-                                                "Share difficulty target not met: {} > {}",
-                                                proof_target, pool_target
-                                            );
-                                            total_proofs.fetch_add(1, Ordering::SeqCst);
-                                            continue;
-                                        }
-
-                                        info!("Share found for block {} ({})", block_height, nonce);
-
-                                        // Send a `Submit` to the proxy.
-                                        let message = ProverMessage::Submit(block_height, nonce, proof);
-                                        if let Err(error) = client.sender().send(message).await {
-                                            error!("Failed to send Submit: {}", error);
-                                        }
-                                        total_proofs.fetch_add(1, Ordering::SeqCst);
-                                    }
-                                }
-                            }));
+    /// Loads the proving parameters unless they already are, concurrent callers wait for the same
+    /// load. The first work does this too. Returns how long loading took, zero if the prover doesn't
+    /// compute proofs or its backend needs no parameters. A failed load is tried again by the next call.
+    pub async fn warmup(&self) -> Result<Duration> {
+        let took = self
+            .parameters
+            .get_or_try_init(|| async {
+                if !self.proving || !self.backend.as_ref().map_or(true, |backend| backend.needs_parameters()) {
+                    return Ok(Duration::ZERO);
+                }
+                info!("Loading the proving parameters");
+                let started = Instant::now();
+                let configured = self.backend.clone();
+                task::spawn_blocking(move || match configured {
+                    Some(configured) => configured.load_parameters(),
+                    None => backend::CpuBackend.load_parameters(),
+                })
+                .await??;
+                let took = started.elapsed();
+                info!("Loaded the proving parameters in {:.1}s", took.as_secs_f64());
+                Ok::<_, anyhow::Error>(took)
+            })
+            .await?;
+        Ok(*took)
+    }
+
+    /// Whether proofs are computed, see `ProverConfig::proving`.
+    pub fn proving(&self) -> bool {
+        self.proving
+    }
+
+    /// Reports `rate` proofs per second to the pool, for a prover that doesn't compute proofs
+    /// itself. Returns false and reports nothing otherwise.
+    pub fn report_external_rate(&self, rate: f64) -> bool {
+        if self.proving {
+            return false;
+        }
+        self.client.report_proof_rate((rate.max(0.0) * 100.0) as u64);
+        true
+    }
+
+    /// Terminates the running job, if any, and waits until all of its workers exited.
+    async fn halt(&self, job: &mut Vec<JoinHandle<()>>) {
+        self.terminator.store(true, Ordering::SeqCst);
+        for result in futures::future::join_all(job.drain(..)).await {
+            if let Err(e) = result {
+                error!("Prover worker failed: {}", e);
+            }
+        }
+        self.terminator.store(false, Ordering::SeqCst);
+    }
+
+    /// Spawns a worker unless it is still running, e.g. because it is about to retire after a scale down.
+    fn spawn_worker(
+        self: &Arc<Self>,
+        job: &mut Vec<JoinHandle<()>>,
+        worker: usize,
+        tp: Arc<ThreadPool>,
+        backend: Arc<dyn ProvingBackend>,
+        block_template: &BlockTemplate<Testnet2>,
+    ) {
+        if self.pipelines[worker].state() == PipelineState::Disabled || self.device_paused(worker) {
+            return;
+        }
+        if self.busy[worker]
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        let prover = self.clone();
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let block_template = block_template.clone();
+        let trace = self.job_trace.read().unwrap().clone().unwrap_or_else(JobTrace::none);
+        job.push(task::spawn(prover.supervise(worker, tp, backend, epoch, block_template, trace)));
+    }
+
+    /// Runs a worker and restarts it with backoff when it panics or its backend keeps failing,
+    /// so one bad device doesn't take the others down. Devices failing too often are disabled.
+    async fn supervise(
+        self: Arc<Self>,
+        worker: usize,
+        tp: Arc<ThreadPool>,
+        backend: Arc<dyn ProvingBackend>,
+        epoch: u64,
+        block_template: BlockTemplate<Testnet2>,
+        trace: JobTrace,
+    ) {
+        let pipeline = &self.pipelines[worker];
+        loop {
+            pipeline.set_state(PipelineState::Mining);
+            let prover = self.clone();
+            let tp = tp.clone();
+            let template = block_template.clone();
+            let trace = trace.clone();
+            let backend = backend.clone();
+            let result = task::spawn_blocking(move || {
+                tp.install(move || exit::supervised(|| prover.mine(worker, backend, epoch, template, trace)))
+            })
+            .await;
+            let error = match result {
+                Ok(Ok(())) => {
+                    pipeline.set_state(PipelineState::Idle);
+                    return;
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(e) if e.is_panic() => format!("panicked: {}", e),
+                Err(_) => return,
+            };
+            // The failed attempt never got to clean up after itself.
+            if self.heartbeats.running(worker) {
+                self.heartbeats.finished(worker);
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            }
+
+            let failures = pipeline.record_failure();
+            self.client.events().publish(MinerEvent::DeviceError {
+                device: self.worker_device(worker),
+                error: error.clone(),
+                disabled: failures >= pipeline::MAX_FAILURES,
+            });
+            if failures >= pipeline::MAX_FAILURES {
+                pipeline.set_state(PipelineState::Disabled);
+                self.busy[worker].store(false, Ordering::SeqCst);
+                error!(
+                    "{}",
+                    Red.normal().paint(format!(
+                        "{} failed {} times and has been disabled: {}",
+                        self.worker_device(worker),
+                        failures,
+                        error
+                    ))
+                );
+                return;
+            }
+            pipeline.set_state(PipelineState::Restarting);
+            let delay = pipeline::backoff(failures);
+            warn!(
+                "{} failed: {}, restarting in {}s",
+                self.worker_device(worker),
+                error,
+                delay.as_secs()
+            );
+            let deadline = Instant::now() + delay;
+            while Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                if self.terminator.load(Ordering::SeqCst) || epoch != self.epoch.load(Ordering::SeqCst) {
+                    pipeline.set_state(PipelineState::Idle);
+                    self.busy[worker].store(false, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn dispatch(self: &Arc<Self>, pool_target: u64, block_template: BlockTemplate<Testnet2>) {
+        let mut job = self.job.lock().await;
+        self.halt(&mut job).await;
+        if !self.running.load(Ordering::SeqCst) || self.is_paused() {
+            return;
+        }
+        self.pool_target.store(pool_target, Ordering::SeqCst);
+        self.network_target
+            .store(block_template.difficulty_target(), Ordering::SeqCst);
+        let attempts = self.job_attempts.swap(0, Ordering::SeqCst);
+        if attempts > 0 {
+            debug!("Previous job finished after {} attempts", attempts);
+        }
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        self.ack_job(block_template.block_height(), pool_target);
+        let thread_pools = self.thread_pools.read().unwrap().clone();
+        if let Some(cuda) = self.cuda.clone() {
+            let cuda_jobs = self.cuda_jobs.unwrap_or(1);
+            for (position, gpu_index) in cuda.into_iter().enumerate() {
+                for job_index in 0..cuda_jobs {
+                    let worker = position * cuda_jobs as usize + job_index as usize;
+                    debug!("Spawning CUDA thread on GPU {} job {}", gpu_index, job_index,);
+                    let backend = self.backend(gpu_index);
+                    self.spawn_worker(&mut job, worker, thread_pools[worker].clone(), backend, &block_template);
+                }
+            }
+        } else {
+            let workers = self.active_workers.load(Ordering::SeqCst);
+            for (worker, tp) in thread_pools.into_iter().enumerate().take(workers) {
+                self.spawn_worker(&mut job, worker, tp, self.backend(-1), &block_template);
+            }
+        }
+    }
+
+    /// Proving loop of one worker, running on the worker's own thread pool for the whole job so
+    /// snarkVM's internal parallelism stays inside that pool.
+    fn mine(
+        self: Arc<Self>,
+        worker: usize,
+        backend: Arc<dyn ProvingBackend>,
+        epoch: u64,
+        block_template: BlockTemplate<Testnet2>,
+        trace: JobTrace,
+    ) -> Result<()> {
+        let block_height = block_template.block_height();
+        // Events of this worker belong to the job, e.g. terminating stale work or submitting a share.
+        let _job = trace.span().enter();
+        let mut first_attempt = Some(trace.first_attempt(&self.worker_device(worker)));
+        // Each worker gets its own stream so the nonce sequence doesn't depend on scheduling.
+        let mut rng = self.deterministic_seed.map(|seed| {
+            let mut rng = ChaChaRng::seed_from_u64(seed);
+            rng.set_stream(worker as u64);
+            rng
+        });
+        let submit_client = self.submit_client(worker);
+        let mut errors = 0;
+        'work: loop {
+            while !self.terminator.load(Ordering::SeqCst) {
+                if worker >= self.active_workers.load(Ordering::SeqCst) {
+                    debug!("Retiring worker {}", worker);
+                    break;
+                }
+                if self.device_paused(worker) {
+                    debug!("Pausing worker {}, its device is paused", worker);
+                    break;
+                }
+                if epoch != self.epoch.load(Ordering::SeqCst) {
+                    debug!(
+                        "Terminating stale work: current {} latest {}",
+                        block_height,
+                        self.current_block.load(Ordering::SeqCst)
+                    );
+                    break 'work;
+                }
+                // The pool may have notified a new height that hasn't been dispatched yet.
+                let latest_height = submit_client.latest_height();
+                if block_height < latest_height {
+                    debug!("Terminating stale work: current {} latest {}", block_height, latest_height);
+                    break 'work;
+                }
+                // Workers over the limit wait here instead of allocating another proof.
+                let permit = match &self.proof_limit {
+                    Some(limit) => match limit.acquire(&self.terminator) {
+                        Some(permit) => Some(permit),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                self.in_flight.fetch_add(1, Ordering::SeqCst);
+                let started = Instant::now();
+                self.heartbeats.started(worker);
+                let attempt = first_attempt.take().unwrap_or_else(Span::none);
+                let entered = attempt.enter();
+                let result = match rng.as_mut() {
+                    Some(rng) => backend.prove(&block_template, &self.terminator, rng),
+                    None => backend.prove(&block_template, &self.terminator, &mut thread_rng()),
+                };
+                drop(entered);
+                self.heartbeats.finished(worker);
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                drop(permit);
+                let block_header = match result {
+                    Ok(block_header) => {
+                        errors = 0;
+                        block_header
+                    }
+                    Err(e) => {
+                        // Attempts aborted by the terminator or a new job end with an error as well.
+                        if self.terminator.load(Ordering::SeqCst) || epoch != self.epoch.load(Ordering::SeqCst) {
+                            continue;
+                        }
+                        errors += 1;
+                        if errors >= MAX_BACKEND_ERRORS {
+                            return Err(anyhow!("{} consecutive proving errors, last: {}", errors, e));
                         }
+                        debug!("Proving error on worker {}: {}", worker, e);
+                        continue;
                     }
+                };
+                self.latencies[worker].record(started.elapsed());
+                self.job_attempts.fetch_add(1, Ordering::SeqCst);
+                self.attempts_since_accept.fetch_add(1, Ordering::SeqCst);
+                if epoch != self.epoch.load(Ordering::SeqCst) {
+                    debug!(
+                        "Terminating stale work: current {} latest {}",
+                        block_height,
+                        self.current_block.load(Ordering::SeqCst)
+                    );
+                    break 'work;
+                }
+                // Ensure the share difficulty target is met, the target may have changed since the job started.
+                let nonce = block_header.nonce();
+                let proof = block_header.proof().clone();
+                let proof_target = proof.to_proof_difficulty().unwrap_or(u64::MAX);
+                self.best_difficulty
+                    .fetch_max(estimate::difficulty(proof_target), Ordering::SeqCst);
+                if proof_target <= block_template.difficulty_target() {
+                    warn!(
+                        "{}",
+                        Green.bold().paint(format!(
+                            "Proof for block {} ({}) meets the network target!",
+                            block_height, nonce
+                        ))
+                    );
+                }
+                let pool_target = self.pool_target.load(Ordering::SeqCst);
+                if proof_target > pool_target {
+                    debug!(
+                        "Share difficulty target not met: {} > {}",
+                        proof_target, pool_target
+                    );
+                    self.below_target.fetch_add(1, Ordering::SeqCst);
+                    self.total_proofs.fetch_add(1, Ordering::SeqCst);
+                    continue;
+                }
+
+                info!("Share found for block {} ({})", block_height, nonce);
+
+                self.submitted.blocking_lock().insert(nonce, (worker, pool_target, Instant::now()));
+
+                // Kept on disk first, a restart right after finding the share doesn't lose it.
+                submit_client.keep_submit(block_height, nonce, &proof);
+
+                // Send a `Submit` to the proxy.
+                let message = ProverMessage::Submit(block_height, nonce, proof);
+                if let Err(error) = submit_client.sender().blocking_send(message) {
+                    error!("Failed to send Submit: {}", error);
                 } else {
-                    for tp in thread_pools.iter() {
-                        let current_block = current_block.clone();
-                        let terminator = terminator.clone();
-                        let client = client.clone();
-                        let block_template = block_template.clone();
-                        let total_proofs = total_proofs.clone();
-                        let tp = tp.clone();
-                        joins.push(task::spawn(async move {
-                            while !terminator.load(Ordering::SeqCst) {
-                                let terminator = terminator.clone();
-                                let block_template = block_template.clone();
-                                let tp = tp.clone();
-                                let block_height = block_template.block_height();
-                                if block_height != current_block.load(Ordering::SeqCst) {
-                                    debug!(
-                                        "Terminating stale work: current {} latest {}",
-                                        block_height,
-                                        current_block.load(Ordering::SeqCst)
-                                    );
-                                    break;
-                                }
-                                if let Ok(Ok(block_header)) = task::spawn_blocking(move || {
-                                    tp.install(|| {
-                                        BlockHeader::mine_once_unchecked(
-                                            &block_template,
-                                            &terminator,
-                                            &mut thread_rng(),
-                                            -1,
-                                        )
-                                    })
-                                })
-                                .await
-                                {
-                                    if block_height != current_block.load(Ordering::SeqCst) {
-                                        debug!(
-                                            "Terminating stale work: current {} latest {}",
-                                            block_height,
-                                            current_block.load(Ordering::SeqCst)
-                                        );
-                                        break;
-                                    }
-                                    // Ensure the share difficulty target is met.
-                                    let nonce = block_header.nonce();
-                                    let proof = block_header.proof().clone();
-                                    let proof_target = proof.to_proof_difficulty().unwrap_or(u64::MAX);
-                                    if proof_target > pool_target {
-                                        debug!(
-                                            "Share difficulty target not met: {} > {}",
-                                            proof_target, pool_target
-                                        );
-                                        total_proofs.fetch_add(1, Ordering::SeqCst);
-                                        continue;
-                                    }
-
-                                    info!("Share found for block {} ({})", block_height, nonce);
-
-                                    // Send a `Submit` to the proxy.
-                                    let message = ProverMessage::Submit(block_height, nonce, proof);
-                                    if let Err(error) = client.sender().send(message).await {
-                                        error!("Failed to send Submit: {}", error);
-                                    }
-                                    total_proofs.fetch_add(1, Ordering::SeqCst);
-                                }
-                            }
-                        }));
-                    }
+                    debug!(height = block_height, %nonce, "Share submitted");
                 }
-                futures::future::join_all(joins).await;
-                terminator.store(false, Ordering::SeqCst);
-            });
-        });
+                self.total_proofs.fetch_add(1, Ordering::SeqCst);
+            }
+            self.busy[worker].store(false, Ordering::SeqCst);
+            // A scale up may have happened right after this worker decided to retire.
+            if self.terminator.load(Ordering::SeqCst)
+                || worker >= self.active_workers.load(Ordering::SeqCst)
+                || self.device_paused(worker)
+                || self.busy[worker]
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+            {
+                return Ok(());
+            }
+        }
+        self.busy[worker].store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// Spawns the background task `make` builds and builds it again after a panic, the panic hook
+// already wrote the crash report. Mining goes on meanwhile, the task only misses a second.
+fn spawn_helper<F, T>(name: &'static str, make: F) -> JoinHandle<()>
+where
+    F: Fn() -> T + Send + 'static,
+    T: Future<Output = ()> + Send + 'static,
+{
+    task::spawn(async move {
+        while AssertUnwindSafe(make()).catch_unwind().await.is_err() {
+            error!("The {} panicked, restarting it", name);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    })
+}
+
+/// Returns the memory available for new allocations in bytes, if it can be determined on this platform.
+pub fn available_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+        let kib = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+        Some(kib * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
     }
 }
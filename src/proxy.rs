@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use futures_util::sink::SinkExt;
+use snarkvm::{
+    dpc::{testnet2::Testnet2, BlockTemplate},
+    traits::Network,
+};
+use structopt::StructOpt;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, watch, Mutex as AsyncMutex},
+    time::interval,
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+use tracing::{debug, info, warn};
+
+use crate::{
+    channel,
+    client::{self, Client},
+    message::{Code, ProtocolLimits, ProverCodec, ProverMessage, Secret},
+    prover::ProverEvent,
+    server::{Action, PoolSession, SessionStats},
+};
+
+// Results waiting to be sent to one rig.
+const RESULT_QUEUE: usize = 64;
+
+#[derive(Debug, StructOpt)]
+pub struct ProxyOptions {
+    /// Address the rigs connect to, e.g. 0.0.0.0:4040 for the rigs of other machines along with
+    /// --rig-password
+    #[structopt(long = "listen", value_name = "HOST:PORT", default_value = "127.0.0.1:4040")]
+    pub listen: SocketAddr,
+    /// Password the rigs have to authorize with, one of the comma separated fields of theirs
+    #[structopt(long = "rig-password", parse(from_str))]
+    pub rig_password: Option<Secret>,
+    /// Submit each share under the worker of its rig, only for pools supporting it
+    #[structopt(long = "multiplex")]
+    pub multiplex: bool,
+    /// Seconds between the statistics of each rig, 0 never
+    #[structopt(long = "stats-interval", value_name = "SECONDS", default_value = "60")]
+    pub stats_interval: u64,
+}
+
+/// What the proxy knows about one connected rig.
+#[derive(Debug, Clone)]
+struct Rig {
+    peer: SocketAddr,
+    worker: Option<String>,
+    /// p/s * 100
+    rate: u64,
+    stats: SessionStats,
+    results: mpsc::Sender<(Code, Option<String>)>,
+}
+
+struct Proxy {
+    upstream: Arc<Client>,
+    limits: ProtocolLimits,
+    rig_password: Option<Secret>,
+    multiplex: bool,
+    // Held while queueing a Worker and its Submit, so the shares of other rigs can't come between.
+    submitting: AsyncMutex<()>,
+    next_rig: AtomicU64,
+    rigs: Mutex<HashMap<u64, Rig>>,
+    // Rig each share waiting for a result upstream came from.
+    shares: Mutex<HashMap<<Testnet2 as Network>::PoSWNonce, u64>>,
+    // Latest (pool target, template) from upstream.
+    work: watch::Receiver<Option<(u64, BlockTemplate<Testnet2>)>>,
+}
+
+impl Proxy {
+    fn rigs(&self) -> MutexGuard<'_, HashMap<u64, Rig>> {
+        self.rigs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn shares(&self) -> MutexGuard<'_, HashMap<<Testnet2 as Network>::PoSWNonce, u64>> {
+        self.shares.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Reports the sum of the rigs' proof rates upstream.
+    fn report_proof_rate(&self) {
+        let rate = self.rigs().values().map(|rig| rig.rate).sum();
+        self.upstream.report_proof_rate(rate);
+    }
+
+    // Whether a rig authorizing with `password` may mine through the proxy.
+    fn admits(&self, password: &Secret) -> bool {
+        match &self.rig_password {
+            Some(rig_password) => password.expose().split(',').any(|field| field.trim() == rig_password.expose()),
+            None => true,
+        }
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut Rig)) {
+        if let Some(rig) = self.rigs().get_mut(&id) {
+            update(rig);
+        }
+    }
+
+    fn log_stats(&self) {
+        let upstream = self.upstream.stats();
+        let mut rigs: Vec<Rig> = self.rigs().values().cloned().collect();
+        rigs.sort_by_key(|rig| rig.peer);
+        let rate: u64 = rigs.iter().map(|rig| rig.rate).sum();
+        info!(
+            "Proxy: {} rigs at {:.2} p/s, upstream {}, {} shares waiting for a result",
+            rigs.len(),
+            rate as f64 / 100.0,
+            if upstream.connected { "connected" } else { "disconnected" },
+            upstream.pending_submits
+        );
+        for rig in rigs {
+            info!(
+                "  {} ({}): {:.2} p/s, {} shares, {} accepted, {} rejected, {} stale",
+                rig.worker.as_deref().unwrap_or("<unauthorized>"),
+                rig.peer,
+                rig.rate as f64 / 100.0,
+                rig.stats.shares,
+                rig.stats.accepted,
+                rig.stats.rejected,
+                rig.stats.stale
+            );
+        }
+    }
+}
+
+/// Serves the rigs connecting to `options.listen` through the one `upstream` connection. Every rig
+/// gets the upstream work and each result goes back to the rig that found the share. With
+/// `options.multiplex` the shares are submitted under the rig's worker, see `MULTIPLEX_CAPABILITY`,
+/// otherwise under the proxy's and which rig found which share is only known to the proxy.
+pub async fn run(options: ProxyOptions, upstream: Arc<Client>, limits: ProtocolLimits) -> Result<()> {
+    let listener = TcpListener::bind(options.listen)
+        .await
+        .with_context(|| format!("Unable to listen on {}", options.listen))?;
+    run_on(listener, options, upstream, limits).await
+}
+
+/// `run` with the rigs connecting to `listener` instead of `options.listen`.
+pub async fn run_on(
+    listener: TcpListener,
+    options: ProxyOptions,
+    upstream: Arc<Client>,
+    limits: ProtocolLimits,
+) -> Result<()> {
+    let listen = listener.local_addr()?;
+    info!("Proxying the rigs connecting to {} to {}", listen, upstream.server());
+    if options.rig_password.is_none() && !listen.ip().is_loopback() {
+        warn!("Any rig reaching {} mines for this account, consider --rig-password", listen);
+    }
+    upstream.set_multiplex(options.multiplex);
+
+    let (prover_sender, mut upstream_events) = channel::channel("prover_events", 1024);
+    let (work_sender, work) = watch::channel(None);
+    let proxy = Arc::new(Proxy {
+        upstream: upstream.clone(),
+        limits,
+        rig_password: options.rig_password.clone(),
+        multiplex: options.multiplex,
+        submitting: AsyncMutex::new(()),
+        next_rig: AtomicU64::new(0),
+        rigs: Default::default(),
+        shares: Default::default(),
+        work,
+    });
+    client::start(prover_sender, upstream);
+
+    let events = proxy.clone();
+    tokio::spawn(async move {
+        while let Some(event) = upstream_events.recv().await {
+            match event {
+                ProverEvent::NewWork(target, template, _) => {
+                    debug!("Passing on the job for block {}", template.block_height());
+                    let _ = work_sender.send(Some((target, template)));
+                }
+                ProverEvent::Result {
+                    code,
+                    message,
+                    nonce: Some(nonce),
+                    ..
+                } => {
+                    let rig = events.shares().remove(&nonce);
+                    let results = rig.and_then(|rig| events.rigs().get(&rig).map(|rig| rig.results.clone()));
+                    match results {
+                        // A rig not reading its results must not hold up the others'.
+                        Some(results) => {
+                            if let Err(e) = results.try_send((code, message)) {
+                                warn!("Dropping the result of share {}: {}", nonce, e);
+                            }
+                        }
+                        None => debug!("Dropping the result of share {}, its rig is gone", nonce),
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    if options.stats_interval > 0 {
+        let stats = proxy.clone();
+        tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(options.stats_interval));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                stats.log_stats();
+            }
+        });
+    }
+
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Unable to accept a rig: {}", e);
+                continue;
+            }
+        };
+        let proxy = proxy.clone();
+        tokio::spawn(async move {
+            let id = proxy.next_rig.fetch_add(1, Ordering::SeqCst);
+            if let Err(e) = serve(&proxy, id, socket, peer).await {
+                debug!("Rig {}: {:#}", peer, e);
+            }
+            let rig = proxy.rigs().remove(&id);
+            proxy.shares().retain(|_, rig| *rig != id);
+            proxy.report_proof_rate();
+            if let Some(rig) = rig {
+                info!(
+                    "Rig {} ({}) disconnected after {} shares",
+                    rig.worker.as_deref().unwrap_or("<unauthorized>"),
+                    peer,
+                    rig.stats.shares
+                );
+            }
+        });
+    }
+}
+
+// Speaks the pool side of the protocol with one rig until it disconnects.
+async fn serve(proxy: &Proxy, id: u64, socket: TcpStream, peer: SocketAddr) -> Result<()> {
+    let mut framed = Framed::new(socket, ProverCodec::new(proxy.limits));
+    let mut session = PoolSession::new();
+    let mut work = proxy.work.clone();
+    let (results, mut result_receiver) = mpsc::channel(RESULT_QUEUE);
+    proxy.rigs().insert(
+        id,
+        Rig {
+            peer,
+            worker: None,
+            rate: 0,
+            stats: SessionStats::default(),
+            results,
+        },
+    );
+    loop {
+        tokio::select! {
+            message = framed.next() => {
+                let message = match message {
+                    Some(message) => message?,
+                    None => return Ok(()),
+                };
+                match session.receive(message) {
+                    Action::Authorize { worker, password, .. } => {
+                        if !proxy.admits(&password) {
+                            warn!("Rejecting rig {} as {}, wrong password", peer, worker);
+                            framed.send(session.authorized(false, Some("wrong password".to_string()))).await?;
+                            return Ok(());
+                        }
+                        info!("Rig {} connected as {}", peer, worker);
+                        proxy.update(id, |rig| rig.worker = Some(worker));
+                        if session.msgpack() {
+                            framed.codec_mut().use_msgpack();
+                        }
+                        framed.send(session.authorized(true, None)).await?;
+                        let latest = work.borrow_and_update().clone();
+                        if let Some(notify) = latest.and_then(|(target, template)| session.notify(template, target)) {
+                            framed.send(notify).await?;
+                        }
+                    }
+                    Action::ForwardShare { height, nonce, proof, .. } => {
+                        debug!("Rig {} found share {} for block {}", peer, nonce, height);
+                        proxy.shares().insert(nonce, id);
+                        let sender = proxy.upstream.sender();
+                        let _submitting = proxy.submitting.lock().await;
+                        if let Some((_, worker)) = session.identity().filter(|_| proxy.multiplex) {
+                            sender
+                                .send(ProverMessage::Worker(worker.to_string()))
+                                .await
+                                .context("Upstream connection closed")?;
+                        }
+                        sender
+                            .send(ProverMessage::Submit(height, nonce, proof))
+                            .await
+                            .context("Upstream connection closed")?;
+                    }
+                    Action::Reply(message) => framed.send(message).await?,
+                    Action::ProofRate(rate) => {
+                        proxy.update(id, |rig| rig.rate = rate);
+                        proxy.report_proof_rate();
+                    }
+                    Action::JobAck { .. } | Action::None => {}
+                    Action::ProtocolViolation(reason) => {
+                        warn!("Closing the connection of rig {}: {}", peer, reason);
+                        return Ok(());
+                    }
+                }
+            }
+            changed = work.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+                let latest = work.borrow_and_update().clone();
+                if let Some(notify) = latest.and_then(|(target, template)| session.notify(template, target)) {
+                    framed.send(notify).await?;
+                }
+            }
+            Some((code, message)) = result_receiver.recv() => {
+                framed.send(session.share_result(code, message)).await?;
+            }
+        }
+        let stats = session.stats().clone();
+        proxy.update(id, |rig| rig.stats = stats);
+    }
+}
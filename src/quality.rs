@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::{task, time::sleep};
+use tracing::{debug, warn};
+
+use crate::client::Client;
+
+// Weight of a new sample in the rolling averages.
+const EWMA_WEIGHT: f64 = 0.1;
+// Disconnects are counted over the last hour.
+const DISCONNECT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// What the quality score of a pool is computed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QualityInputs {
+    /// Time from sending a share to its result, or the connect time for a probed pool
+    pub rtt: Option<Duration>,
+    /// Fraction of the share results that were stale, 0 to 1
+    pub stale_ratio: f64,
+    /// Disconnects and failed connections in the last hour
+    pub disconnects: u32,
+    /// Time from connecting to the first job
+    pub first_notify: Option<Duration>,
+}
+
+/// Quality of a pool from 100 down to 0. Each input takes off up to a fixed amount: the round trip
+/// time up to 40 (1 per 15 ms), stales up to 40 (4 per percent), disconnects up to 20 (5 each) and
+/// the time to the first job up to 10 (1 per second). Missing inputs cost nothing.
+pub fn score(inputs: &QualityInputs) -> f64 {
+    let rtt = inputs.rtt.map_or(0.0, |rtt| rtt.as_secs_f64() * 1000.0 / 15.0).min(40.0);
+    let stales = (inputs.stale_ratio * 400.0).min(40.0);
+    let disconnects = (inputs.disconnects as f64 * 5.0).min(20.0);
+    let first_notify = inputs.first_notify.map_or(0.0, |time| time.as_secs_f64()).min(10.0);
+    (100.0 - rtt - stales - disconnects - first_notify).max(0.0)
+}
+
+fn ewma(average: Option<f64>, sample: f64) -> Option<f64> {
+    Some(average.map_or(sample, |average| average + EWMA_WEIGHT * (sample - average)))
+}
+
+/// Rolling quality measurements of the active pool.
+#[derive(Debug, Default)]
+pub struct QualityTracker {
+    // Seconds.
+    submit_rtt: Option<f64>,
+    stale_ratio: Option<f64>,
+    first_notify: Option<f64>,
+    disconnects: VecDeque<Instant>,
+}
+
+impl QualityTracker {
+    pub fn record_result(&mut self, latency: Duration, stale: bool) {
+        self.submit_rtt = ewma(self.submit_rtt, latency.as_secs_f64());
+        self.stale_ratio = ewma(self.stale_ratio, stale as u8 as f64);
+    }
+
+    pub fn record_first_notify(&mut self, time: Duration) {
+        self.first_notify = ewma(self.first_notify, time.as_secs_f64());
+    }
+
+    pub fn record_disconnect(&mut self) {
+        self.disconnects.push_back(Instant::now());
+    }
+
+    pub fn inputs(&mut self) -> QualityInputs {
+        while let Some(disconnect) = self.disconnects.front() {
+            if disconnect.elapsed() <= DISCONNECT_WINDOW {
+                break;
+            }
+            self.disconnects.pop_front();
+        }
+        QualityInputs {
+            rtt: self.submit_rtt.map(Duration::from_secs_f64),
+            stale_ratio: self.stale_ratio.unwrap_or_default(),
+            disconnects: self.disconnects.len() as u32,
+            first_notify: self.first_notify.map(Duration::from_secs_f64),
+        }
+    }
+}
+
+/// When to leave the active pool for a better one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailoverPolicy {
+    /// Points the best alternative has to score above the active pool
+    pub margin: f64,
+    /// How long the active pool has to stay that far below before switching
+    pub sustain: Duration,
+    /// Time between probes of the other pools
+    pub probe_interval: Duration,
+}
+
+/// Hysteresis of the switch: the active pool has to stay below the best alternative by the margin
+/// for the whole sustain period, a single bad probe or a short spike doesn't make it flap.
+#[derive(Debug, Default)]
+pub struct Failover {
+    below_since: Option<Instant>,
+}
+
+impl Failover {
+    /// Pool to switch to given the active pool's score and the best alternative, `None` to stay.
+    pub fn decide(
+        &mut self,
+        policy: &FailoverPolicy,
+        active: f64,
+        best: Option<(&str, f64)>,
+        now: Instant,
+    ) -> Option<String> {
+        let server = match best {
+            Some((server, score)) if active + policy.margin < score => server,
+            _ => {
+                self.below_since = None;
+                return None;
+            }
+        };
+        let since = *self.below_since.get_or_insert(now);
+        if now.duration_since(since) < policy.sustain {
+            return None;
+        }
+        self.below_since = None;
+        Some(server.to_string())
+    }
+}
+
+/// Probes the pools other than the active one every `probe_interval` and switches `client` to the
+/// best one when the policy says so.
+pub fn spawn(client: Arc<Client>, pools: Vec<String>, policy: FailoverPolicy) {
+    task::spawn(async move {
+        let mut failover = Failover::default();
+        loop {
+            sleep(policy.probe_interval).await;
+            let active = client.server();
+            let current = score(&client.quality());
+            let mut best: Option<(String, f64)> = None;
+            for server in pools.iter().filter(|server| **server != active) {
+                let probed = match client.probe(server).await {
+                    Some(inputs) => score(&inputs),
+                    None => {
+                        debug!("Probing {} failed", server);
+                        continue;
+                    }
+                };
+                debug!("Probed {}, quality {:.0}", server, probed);
+                if best.as_ref().map_or(true, |(_, score)| probed > *score) {
+                    best = Some((server.clone(), probed));
+                }
+            }
+            let best = best.as_ref().map(|(server, score)| (server.as_str(), *score));
+            if let Some(server) = failover.decide(&policy, current, best, Instant::now()) {
+                warn!(
+                    "Switching to {}, the quality of {} stayed at {:.0} against {:.0}",
+                    server,
+                    active,
+                    current,
+                    best.map(|(_, score)| score).unwrap_or_default()
+                );
+                client.set_server(server);
+            }
+        }
+    });
+}
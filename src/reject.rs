@@ -0,0 +1,303 @@
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+    ops::AddAssign,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::message::Code;
+
+/// Rejected shares by result code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RejectBreakdown {
+    pub stale: u32,
+    pub invalid: u32,
+    /// Shares lost to proxy exceptions, after their retry
+    pub proxy: u32,
+    /// Codes this version doesn't know about
+    pub other: u32,
+}
+
+impl RejectBreakdown {
+    pub fn record(&mut self, code: &Code) {
+        match code {
+            Code::Success => {}
+            Code::Stale => self.stale += 1,
+            Code::InvalidProof => self.invalid += 1,
+            Code::ProxyException => self.proxy += 1,
+            Code::Other => self.other += 1,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.stale + self.invalid + self.proxy + self.other
+    }
+
+    /// (label, count) pairs as used in the exports.
+    pub fn by_label(&self) -> [(&'static str, u32); 4] {
+        [
+            ("stale", self.stale),
+            ("invalid", self.invalid),
+            ("proxy", self.proxy),
+            ("other", self.other),
+        ]
+    }
+}
+
+impl AddAssign for RejectBreakdown {
+    fn add_assign(&mut self, other: Self) {
+        self.stale += other.stale;
+        self.invalid += other.invalid;
+        self.proxy += other.proxy;
+        self.other += other.other;
+    }
+}
+
+impl Display for RejectBreakdown {
+    /// E.g. `8 stale, 3 invalid`, leaving out codes that didn't occur.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self
+            .by_label()
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(label, count)| format!("{} {}", count, label))
+            .collect();
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// Tracks the rolling reject ratio of submitted shares and decides when proving should be
+/// paused because the rig is most likely producing garbage.
+pub struct RejectGuard {
+    window: usize,
+    threshold: f64,
+    cooldown: Duration,
+    probation: usize,
+    results: VecDeque<bool>,
+    state: GuardState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardState {
+    Normal,
+    /// Paused until the cool-down elapses
+    Tripped(Instant),
+    /// Resumed after a cool-down, the next few shares decide whether we stay resumed
+    Probation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardAction {
+    None,
+    Pause,
+    Resume,
+}
+
+impl RejectGuard {
+    /// `threshold` is the maximum tolerated reject ratio in percent.
+    pub fn new(window: usize, threshold: f64, cooldown: Duration, probation: usize) -> Self {
+        Self {
+            window: window.max(1),
+            threshold: threshold / 100.0,
+            cooldown,
+            probation: probation.max(1),
+            results: VecDeque::new(),
+            state: GuardState::Normal,
+        }
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    pub fn state(&self) -> GuardState {
+        self.state
+    }
+
+    pub fn reject_ratio(&self) -> f64 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        self.results.iter().filter(|accepted| !**accepted).count() as f64 / self.results.len() as f64
+    }
+
+    /// Records a share result and returns whether proving should be paused.
+    pub fn record(&mut self, accepted: bool, now: Instant) -> GuardAction {
+        match self.state {
+            GuardState::Normal => {
+                self.results.push_back(accepted);
+                while self.results.len() > self.window {
+                    self.results.pop_front();
+                }
+                if self.results.len() >= self.window && self.reject_ratio() > self.threshold {
+                    return self.trip(now);
+                }
+                GuardAction::None
+            }
+            GuardState::Probation => {
+                self.results.push_back(accepted);
+                if self.results.len() >= self.probation {
+                    if self.reject_ratio() > self.threshold {
+                        return self.trip(now);
+                    }
+                    self.state = GuardState::Normal;
+                }
+                GuardAction::None
+            }
+            // Results of shares submitted right before the pause.
+            GuardState::Tripped(_) => GuardAction::None,
+        }
+    }
+
+    /// Returns `Resume` once the cool-down of a tripped guard has elapsed.
+    pub fn poll(&mut self, now: Instant) -> GuardAction {
+        match self.state {
+            GuardState::Tripped(until) if now >= until => {
+                self.results.clear();
+                self.state = GuardState::Probation;
+                GuardAction::Resume
+            }
+            _ => GuardAction::None,
+        }
+    }
+
+    /// Forgets the share history, used when the operator clears a pause for rejects.
+    pub fn reset(&mut self) {
+        self.results.clear();
+        self.state = GuardState::Normal;
+    }
+
+    fn trip(&mut self, now: Instant) -> GuardAction {
+        self.state = GuardState::Tripped(now + self.cooldown);
+        GuardAction::Pause
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(300);
+
+    // Window of 4 shares, pausing above 50% rejects, 2 shares of probation.
+    fn guard() -> RejectGuard {
+        RejectGuard::new(4, 50.0, COOLDOWN, 2)
+    }
+
+    // Records `results` ('a' accepted, 'r' rejected), returning the last action.
+    fn script(guard: &mut RejectGuard, results: &str, now: Instant) -> GuardAction {
+        results
+            .chars()
+            .map(|result| guard.record(result == 'a', now))
+            .last()
+            .unwrap_or(GuardAction::None)
+    }
+
+    #[test]
+    fn waits_for_a_full_window() {
+        let mut guard = guard();
+        assert_eq!(script(&mut guard, "rrr", Instant::now()), GuardAction::None);
+        assert_eq!(guard.state(), GuardState::Normal);
+        assert_eq!(guard.record(false, Instant::now()), GuardAction::Pause);
+    }
+
+    #[test]
+    fn tolerates_the_threshold() {
+        let mut guard = guard();
+        assert_eq!(script(&mut guard, "aarr", Instant::now()), GuardAction::None);
+        assert_eq!(script(&mut guard, "aa", Instant::now()), GuardAction::None);
+        assert_eq!(guard.state(), GuardState::Normal);
+    }
+
+    #[test]
+    fn ratio_includes_the_result_that_trips() {
+        let mut guard = guard();
+        script(&mut guard, "aarr", Instant::now());
+        assert_eq!(guard.reject_ratio(), 0.5);
+        assert_eq!(guard.record(false, Instant::now()), GuardAction::Pause);
+        assert_eq!(guard.reject_ratio(), 0.75);
+    }
+
+    #[test]
+    fn only_the_window_counts() {
+        let mut guard = guard();
+        // Old rejects slide out of the window.
+        assert_eq!(script(&mut guard, "rraaaa", Instant::now()), GuardAction::None);
+        assert_eq!(guard.reject_ratio(), 0.0);
+        assert_eq!(script(&mut guard, "rr", Instant::now()), GuardAction::None);
+        assert_eq!(guard.reject_ratio(), 0.5);
+    }
+
+    #[test]
+    fn resumes_on_probation_after_the_cooldown() {
+        let mut guard = guard();
+        let start = Instant::now();
+        assert_eq!(script(&mut guard, "rrrr", start), GuardAction::Pause);
+        assert_eq!(guard.state(), GuardState::Tripped(start + COOLDOWN));
+        // Late results of shares submitted before the pause change nothing.
+        assert_eq!(script(&mut guard, "aaaa", start), GuardAction::None);
+        assert_eq!(guard.poll(start + COOLDOWN - Duration::from_secs(1)), GuardAction::None);
+        assert_eq!(guard.poll(start + COOLDOWN), GuardAction::Resume);
+        assert_eq!(guard.state(), GuardState::Probation);
+        assert_eq!(guard.poll(start + COOLDOWN * 2), GuardAction::None);
+    }
+
+    #[test]
+    fn passing_probation_goes_back_to_normal() {
+        let mut guard = guard();
+        let start = Instant::now();
+        script(&mut guard, "rrrr", start);
+        guard.poll(start + COOLDOWN);
+        assert_eq!(script(&mut guard, "a", start + COOLDOWN), GuardAction::None);
+        assert_eq!(guard.state(), GuardState::Probation);
+        assert_eq!(script(&mut guard, "a", start + COOLDOWN), GuardAction::None);
+        assert_eq!(guard.state(), GuardState::Normal);
+    }
+
+    #[test]
+    fn failing_probation_pauses_again() {
+        let mut guard = guard();
+        let start = Instant::now();
+        script(&mut guard, "rrrr", start);
+        guard.poll(start + COOLDOWN);
+        let now = start + COOLDOWN;
+        assert_eq!(script(&mut guard, "rr", now), GuardAction::Pause);
+        assert_eq!(guard.state(), GuardState::Tripped(now + COOLDOWN));
+    }
+
+    #[test]
+    fn reset_clears_a_tripped_guard() {
+        let mut guard = guard();
+        script(&mut guard, "rrrr", Instant::now());
+        guard.reset();
+        assert_eq!(guard.state(), GuardState::Normal);
+        assert_eq!(guard.reject_ratio(), 0.0);
+        assert_eq!(script(&mut guard, "rrr", Instant::now()), GuardAction::None);
+    }
+
+    #[test]
+    fn counts_rejections_by_code() {
+        let mut rejections = RejectBreakdown::default();
+        let codes = [Code::Stale, Code::Success, Code::Stale, Code::InvalidProof, Code::ProxyException, Code::Other];
+        codes.iter().for_each(|code| rejections.record(code));
+        assert_eq!(rejections, RejectBreakdown { stale: 2, invalid: 1, proxy: 1, other: 1 });
+        assert_eq!(rejections.total(), 5);
+        assert_eq!(rejections.by_label(), [("stale", 2), ("invalid", 1), ("proxy", 1), ("other", 1)]);
+
+        rejections += RejectBreakdown { stale: 1, ..RejectBreakdown::default() };
+        assert_eq!(rejections.stale, 3);
+    }
+
+    #[test]
+    fn formats_only_the_codes_that_occurred() {
+        assert_eq!(RejectBreakdown::default().to_string(), "none");
+        let rejections = RejectBreakdown { stale: 8, invalid: 3, proxy: 0, other: 1 };
+        assert_eq!(rejections.to_string(), "8 stale, 3 invalid, 1 other");
+    }
+}
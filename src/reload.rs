@@ -0,0 +1,243 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use serde_json::Value;
+use structopt::{clap::ArgMatches, StructOpt};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::{
+    cli::Opt,
+    client::Client,
+    config::{self, Config},
+    logging::LogLevels,
+    prover::Prover,
+    schedule::Schedule,
+};
+
+// Settings applied while running, by path prefix. Pool and password changes reconnect.
+const HOT_RELOADABLE: [&str; 8] = [
+    "logging.level",
+    "logging.file_level",
+    "report.",
+    "alerts.",
+    "schedule",
+    "pools",
+    "password",
+    "prover.threads",
+];
+
+/// Re-reads the configuration file and applies the settings that can change while running.
+pub struct Reloader {
+    path: PathBuf,
+    matches: ArgMatches<'static>,
+    current: Mutex<Config>,
+    levels: LogLevels,
+    prover: Arc<Prover>,
+    clients: Vec<Arc<Client>>,
+    schedule: watch::Sender<Option<Schedule>>,
+}
+
+/// Dotted paths of the values that differ, e.g. `alerts.max_reject_percent`.
+fn changed_fields(old: &Config, new: &Config) -> Vec<String> {
+    fn diff(path: String, old: &Value, new: &Value, changed: &mut Vec<String>) {
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => {
+                let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    diff(path, old.get(key).unwrap_or(&Value::Null), new.get(key).unwrap_or(&Value::Null), changed);
+                }
+            }
+            (old, new) if old != new => changed.push(path),
+            _ => {}
+        }
+    }
+    let mut changed = Vec::new();
+    if let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) {
+        diff(String::new(), &old, &new, &mut changed);
+    }
+    changed
+}
+
+impl Reloader {
+    /// `clients` is the main connection followed by the worker group connections.
+    pub(crate) fn new(
+        path: PathBuf,
+        matches: ArgMatches<'static>,
+        config: Config,
+        levels: LogLevels,
+        prover: Arc<Prover>,
+        clients: Vec<Arc<Client>>,
+        schedule: watch::Sender<Option<Schedule>>,
+    ) -> Self {
+        Self {
+            path,
+            matches,
+            current: Mutex::new(config),
+            levels,
+            prover,
+            clients,
+            schedule,
+        }
+    }
+
+    /// Applies the changed settings and returns their paths. Nothing is applied if the file is invalid.
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let config = config::load(Some(&self.path), &self.matches)?;
+        let mut current = self.current.lock().unwrap();
+        let changed = changed_fields(&current, &config);
+        if changed.is_empty() {
+            info!("Configuration reloaded, nothing changed");
+            return Ok(changed);
+        }
+        let (applied, restart): (Vec<&String>, Vec<&String>) = changed
+            .iter()
+            .partition(|field| HOT_RELOADABLE.iter().any(|prefix| field.starts_with(prefix)));
+
+        let mut opt = Opt::from_clap(&self.matches);
+        config.apply(&mut opt);
+        let password = opt.pool_password()?.unwrap_or_default();
+        let (console_level, file_level) = opt.log_levels();
+        self.levels.set(console_level, file_level)?;
+        self.prover
+            .reconfigure(opt.rate_report(), opt.share_alert_duration(), opt.thresholds());
+        let _ = self.schedule.send(opt.schedule.clone());
+        if changed.iter().any(|field| field == "prover.threads") {
+            let threads = opt.threads.unwrap_or(num_cpus::get() as u16).max(1);
+            let prover = self.prover.clone();
+            tokio::spawn(async move {
+                if let Err(e) = prover.set_threads(threads).await {
+                    error!("Unable to change the prover threads: {:#}", e);
+                }
+            });
+        }
+        for (index, client) in self.clients.iter().enumerate() {
+            client.set_password(password.clone());
+            if index == 0 {
+                client.set_pool_credentials(opt.pool_credentials.clone());
+            } else {
+                client.set_pool_credentials(Client::group_credentials(&opt.pool_credentials));
+            }
+            if let Some(pool) = opt.pool.as_ref() {
+                client.set_server(pool.clone());
+            }
+        }
+        *current = config;
+
+        if !applied.is_empty() {
+            let applied: Vec<&str> = applied.iter().map(|field| field.as_str()).collect();
+            info!("Configuration reloaded, applied {}", applied.join(", "));
+        }
+        if !restart.is_empty() {
+            let restart: Vec<&str> = restart.iter().map(|field| field.as_str()).collect();
+            warn!("Changes to {} take effect after a restart", restart.join(", "));
+        }
+        Ok(changed)
+    }
+}
+
+/// Reloads the configuration on SIGHUP.
+pub fn spawn(reloader: Arc<Reloader>) {
+    #[cfg(unix)]
+    tokio::task::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Unable to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading the configuration");
+            if let Err(e) = reloader.reload() {
+                error!("Configuration reload failed, keeping the running configuration: {:#}", e);
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    let _ = reloader;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, time::Duration};
+
+    use tokio::time::{sleep, timeout};
+    use tracing_subscriber::filter::LevelFilter;
+
+    use super::*;
+    use crate::testing::{self, FakeBackend};
+
+    fn file(extra: &str, pool: &str, threads: u16, level: &str) -> String {
+        format!(
+            "{}account = \"reload\"\npools = [{{ url = \"{}\" }}]\n\n[prover]\nthreads = {}\n\n\
+             [logging]\nlevel = \"{}\"\n",
+            extra, pool, threads, level
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn applies_changes_and_keeps_the_running_config_on_errors() {
+        let dir = env::temp_dir().join(format!("aleoxminer-reload-{}-apply", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("miner.toml");
+        fs::write(&path, file("", "a.example.com:4040", 16, "info")).unwrap();
+
+        let matches = Opt::clap().get_matches_from_safe(["prover"]).unwrap();
+        let config = config::load(Some(&path), &matches).unwrap();
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let client = testing::client("a.example.com:4040", "reload");
+        let backend = FakeBackend::new(Duration::from_millis(5));
+        let prover = Prover::new(testing::prover_config(16, backend), client.clone()).unwrap();
+        let (schedule, mut schedules) = watch::channel(None);
+        let reloader = Reloader::new(
+            path.clone(),
+            matches,
+            config,
+            LogLevels::recording(levels.clone()),
+            prover.clone(),
+            vec![client.clone()],
+            schedule,
+        );
+        assert!(reloader.reload().unwrap().is_empty());
+        assert!(levels.lock().unwrap().is_empty());
+
+        let changed_file = file("schedule = \"22:00-06:00\"\n", "b.example.com:4040", 8, "debug");
+        fs::write(&path, &changed_file).unwrap();
+        let changed = reloader.reload().unwrap();
+        assert_eq!(changed, ["logging.level", "pools", "prover.threads", "schedule"]);
+        assert_eq!(*levels.lock().unwrap(), [LevelFilter::DEBUG]);
+        assert_eq!(client.server(), "b.example.com:4040");
+        assert_eq!(*schedules.borrow_and_update(), Some("22:00-06:00".parse().unwrap()));
+        timeout(Duration::from_secs(10), async {
+            while prover.stats().threads != 8 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the prover threads didn't change");
+
+        // Nothing of an invalid file is applied.
+        fs::write(&path, file("", "c.example.com:4040", 16, "loud")).unwrap();
+        let error = reloader.reload().unwrap_err().to_string();
+        assert!(error.contains("logging.level"), "{}", error);
+        assert_eq!(*levels.lock().unwrap(), [LevelFilter::DEBUG]);
+        assert_eq!(client.server(), "b.example.com:4040");
+        assert!(!schedules.has_changed().unwrap());
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(prover.stats().threads, 8);
+        // The running configuration is still the last valid one.
+        fs::write(&path, &changed_file).unwrap();
+        assert!(reloader.reload().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
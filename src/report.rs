@@ -0,0 +1,133 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Error};
+
+/// Minimum change of the proof rate that is worth reporting to the pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateDelta {
+    /// In proofs per second
+    Absolute(f64),
+    /// In percent of the last reported rate
+    Percent(f64),
+}
+
+impl FromStr for RateDelta {
+    type Err = Error;
+
+    /// Parses `5%` as a relative and `0.5` as an absolute delta.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (value, percent) = match s.strip_suffix('%') {
+            Some(value) => (value, true),
+            None => (s, false),
+        };
+        let value = value
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| anyhow!("invalid rate delta {}: {}", s, e))?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(anyhow!("invalid rate delta {}: must be a positive number", s));
+        }
+        Ok(if percent { Self::Percent(value) } else { Self::Absolute(value) })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReportPolicy {
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    pub delta: RateDelta,
+}
+
+impl ReportPolicy {
+    /// Decides whether `rate` should be sent, given the last reported rate and the time since it was sent.
+    pub fn should_report(&self, rate: f64, last: Option<(f64, Duration)>) -> bool {
+        let (last_rate, elapsed) = match last {
+            Some(last) => last,
+            None => return true,
+        };
+        if elapsed < self.min_interval {
+            return false;
+        }
+        if elapsed >= self.max_interval {
+            return true;
+        }
+        let change = (rate - last_rate).abs();
+        match self.delta {
+            RateDelta::Absolute(delta) => change > delta,
+            RateDelta::Percent(percent) => {
+                if last_rate == 0.0 {
+                    change > 0.0
+                } else {
+                    change / last_rate * 100.0 > percent
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(delta: RateDelta) -> ReportPolicy {
+        ReportPolicy {
+            min_interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(30),
+            delta,
+        }
+    }
+
+    // Seconds at which the rates, sampled every 5 seconds, get reported.
+    fn reports(policy: &ReportPolicy, rates: &[f64]) -> Vec<u64> {
+        let mut last: Option<(f64, u64)> = None;
+        let mut sent = Vec::new();
+        for (i, &rate) in rates.iter().enumerate() {
+            let now = i as u64 * 5;
+            if policy.should_report(rate, last.map(|(rate, at)| (rate, Duration::from_secs(now - at)))) {
+                last = Some((rate, now));
+                sent.push(now);
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn parses_deltas() {
+        assert_eq!("5%".parse::<RateDelta>().unwrap(), RateDelta::Percent(5.0));
+        assert_eq!(" 2.5 % ".parse::<RateDelta>().unwrap(), RateDelta::Percent(2.5));
+        assert_eq!("0.5".parse::<RateDelta>().unwrap(), RateDelta::Absolute(0.5));
+        assert!("-1".parse::<RateDelta>().is_err());
+        assert!("fast".parse::<RateDelta>().is_err());
+        assert!("inf%".parse::<RateDelta>().is_err());
+    }
+
+    #[test]
+    fn reports_the_first_rate() {
+        assert!(policy(RateDelta::Absolute(1.0)).should_report(50.0, None));
+    }
+
+    #[test]
+    fn steady_rates_are_reported_at_the_max_interval() {
+        assert_eq!(reports(&policy(RateDelta::Percent(5.0)), &[50.0; 14]), vec![0, 30, 60]);
+    }
+
+    #[test]
+    fn changes_wait_for_the_min_interval() {
+        let rates = [50.0, 80.0, 20.0, 90.0, 90.0, 90.0];
+        assert_eq!(reports(&policy(RateDelta::Absolute(1.0)), &rates), vec![0, 10, 20]);
+    }
+
+    #[test]
+    fn small_changes_are_not_reported() {
+        let rates = [50.0, 50.4, 50.8, 51.2, 52.0, 52.4, 52.5];
+        assert_eq!(reports(&policy(RateDelta::Absolute(1.0)), &rates), vec![0, 15, 25]);
+        assert_eq!(reports(&policy(RateDelta::Percent(5.0)), &rates), vec![0, 30]);
+    }
+
+    #[test]
+    fn any_rise_from_zero_is_a_change() {
+        let rates = [0.0, 0.0, 0.0, 0.1];
+        assert_eq!(reports(&policy(RateDelta::Percent(50.0)), &rates), vec![0, 15]);
+    }
+}
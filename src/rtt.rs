@@ -0,0 +1,103 @@
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+// Weight of a new sample in the moving average.
+const ALPHA: f64 = 0.2;
+
+/// Rolling round trip time to the pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RttStats {
+    /// Exponentially weighted moving average
+    pub average: Option<Duration>,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    pub samples: u64,
+}
+
+impl Display for RttStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.average, self.min, self.max) {
+            (Some(average), Some(min), Some(max)) => write!(
+                f,
+                "{}ms (min {}ms, max {}ms)",
+                average.as_millis(),
+                min.as_millis(),
+                max.as_millis()
+            ),
+            _ => write!(f, "n/a"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RttEstimator {
+    stats: RttStats,
+}
+
+impl RttEstimator {
+    pub fn record(&mut self, sample: Duration) {
+        let stats = &mut self.stats;
+        stats.average = Some(match stats.average {
+            Some(average) => average.mul_f64(1.0 - ALPHA) + sample.mul_f64(ALPHA),
+            None => sample,
+        });
+        stats.min = Some(stats.min.map_or(sample, |min| min.min(sample)));
+        stats.max = Some(stats.max.map_or(sample, |max| max.max(sample)));
+        stats.samples += 1;
+    }
+
+    pub fn stats(&self) -> RttStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn estimate(samples: &[u64]) -> RttStats {
+        let mut estimator = RttEstimator::default();
+        samples.iter().for_each(|sample| estimator.record(ms(*sample)));
+        estimator.stats()
+    }
+
+    #[test]
+    fn starts_without_an_estimate() {
+        let stats = estimate(&[]);
+        assert_eq!(stats, RttStats::default());
+        assert_eq!(stats.to_string(), "n/a");
+    }
+
+    #[test]
+    fn starts_the_average_at_the_first_sample() {
+        let stats = estimate(&[40]);
+        assert_eq!((stats.average, stats.min, stats.max, stats.samples), (Some(ms(40)), Some(ms(40)), Some(ms(40)), 1));
+        assert_eq!(stats.to_string(), "40ms (min 40ms, max 40ms)");
+    }
+
+    #[test]
+    fn weighs_new_samples_by_a_fifth() {
+        // 100 * 0.8 + 200 * 0.2 = 120, then 120 * 0.8 + 20 * 0.2 = 100
+        let stats = estimate(&[100, 200, 20]);
+        let average = stats.average.unwrap().as_secs_f64() * 1000.0;
+        assert!((average - 100.0).abs() < 0.001, "{}", average);
+        assert_eq!((stats.min, stats.max, stats.samples), (Some(ms(20)), Some(ms(200)), 3));
+        assert_eq!(stats.to_string(), "100ms (min 20ms, max 200ms)");
+    }
+
+    #[test]
+    fn converges_on_a_steady_round_trip() {
+        let mut samples = vec![500];
+        samples.extend([50; 40]);
+        let stats = estimate(&samples);
+        let average = stats.average.unwrap().as_secs_f64() * 1000.0;
+        assert!((average - 50.0).abs() < 0.1, "{}", average);
+        assert_eq!(stats.max, Some(ms(500)));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    build_info::BuildInfo,
+    client::ClientStats,
+    histogram::LatencySummary,
+    message::MessageTraffic,
+    prover::ProverStats,
+};
+
+/// Options whose values must not end up in the report.
+const SECRET_OPTIONS: [&str; 6] = [
+    "--control-token",
+    "--password",
+    "--influx-token",
+    "--mqtt-password",
+    "--webhook-url",
+    "--account",
+];
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Serialize)]
+pub struct Connection {
+    pub connected_seconds: u64,
+    /// Fraction of the runtime connected to the pool
+    pub availability: f64,
+    pub reconnects: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolShares {
+    pub server: String,
+    pub worker: String,
+    pub accepted: u32,
+    pub rejected: u32,
+    pub stale: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Latency {
+    pub count: u64,
+    pub p50_ms: Option<u128>,
+    pub p90_ms: Option<u128>,
+    pub p99_ms: Option<u128>,
+}
+
+impl From<&LatencySummary> for Latency {
+    fn from(summary: &LatencySummary) -> Self {
+        Self {
+            count: summary.count,
+            p50_ms: summary.p50.map(|p| p.as_millis()),
+            p90_ms: summary.p90.map(|p| p.as_millis()),
+            p99_ms: summary.p99.map(|p| p.as_millis()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceReport {
+    pub name: String,
+    pub state: String,
+    pub failures: u32,
+    pub accepted: u32,
+    pub rejected: u32,
+    pub proof_latency: Latency,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hashrate {
+    /// Proofs per second over the whole run
+    pub average: Option<f64>,
+    /// Proofs per second by window as of the last periodic stats, e.g. `15m`
+    pub windows: Vec<(String, Option<f64>)>,
+}
+
+/// Machine readable description of a run, written on exit with `--run-report`.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub build: BuildInfo,
+    /// Command line with secrets redacted
+    pub arguments: Vec<String>,
+    /// Unix timestamps
+    pub started: u64,
+    pub stopped: u64,
+    pub runtime_seconds: u64,
+    pub connection: Connection,
+    pub pools: Vec<PoolShares>,
+    pub devices: Vec<DeviceReport>,
+    pub hashrate: Hashrate,
+    pub proofs: u32,
+    pub best_difficulty: u64,
+    pub submit_latency: Latency,
+    /// Frames and bytes exchanged with the pools by message type
+    pub traffic: Vec<MessageTraffic>,
+    /// Last warnings and errors logged
+    pub recent_warnings: Vec<String>,
+}
+
+/// Replaces the values of secret options, given either as `--option value` or `--option=value`.
+pub fn redact_arguments(arguments: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut redacted = Vec::new();
+    let mut secret_next = false;
+    for argument in arguments {
+        if secret_next {
+            secret_next = false;
+            redacted.push(REDACTED.to_string());
+            continue;
+        }
+        match argument.split_once('=') {
+            Some((option, _)) if SECRET_OPTIONS.contains(&option) => {
+                redacted.push(format!("{}={}", option, REDACTED));
+            }
+            _ => {
+                secret_next = SECRET_OPTIONS.contains(&argument.as_str());
+                redacted.push(argument);
+            }
+        }
+    }
+    redacted
+}
+
+fn unix(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default()
+}
+
+impl RunReport {
+    pub fn new(
+        stats: &ProverStats,
+        client: &ClientStats,
+        server: &str,
+        worker: &str,
+        runtime: Duration,
+        recent_warnings: Vec<String>,
+    ) -> Self {
+        let now = SystemTime::now();
+        let mut pools = vec![PoolShares {
+            server: server.to_string(),
+            worker: worker.to_string(),
+            accepted: stats.valid_shares,
+            rejected: stats.invalid_shares,
+            stale: stats.local_stale,
+        }];
+        // Worker group shares are included in the totals above.
+        pools.extend(stats.group_shares.iter().map(|(name, valid, invalid)| PoolShares {
+            server: server.to_string(),
+            worker: name.clone(),
+            accepted: *valid,
+            rejected: *invalid,
+            stale: 0,
+        }));
+        let devices = stats
+            .devices
+            .iter()
+            .zip(stats.worker_latency.iter())
+            .zip(stats.worker_shares.iter())
+            .map(|((device, latency), (accepted, rejected))| DeviceReport {
+                name: device.name.clone(),
+                state: device.state.to_string(),
+                failures: device.failures,
+                accepted: *accepted,
+                rejected: *rejected,
+                proof_latency: latency.into(),
+            })
+            .collect();
+        let runtime_secs = runtime.as_secs_f64();
+        Self {
+            build: BuildInfo::current(),
+            arguments: redact_arguments(std::env::args()),
+            started: unix(now - runtime),
+            stopped: unix(now),
+            runtime_seconds: runtime.as_secs(),
+            connection: Connection {
+                connected_seconds: client.connected_time.as_secs(),
+                availability: if runtime_secs > 0.0 {
+                    (client.connected_time.as_secs_f64() / runtime_secs).min(1.0)
+                } else {
+                    0.0
+                },
+                reconnects: client.reconnects,
+            },
+            pools,
+            devices,
+            hashrate: Hashrate {
+                average: if runtime_secs > 0.0 {
+                    Some(stats.total_proofs as f64 / runtime_secs)
+                } else {
+                    None
+                },
+                windows: stats
+                    .proof_rates
+                    .iter()
+                    .map(|(window, rate)| (format!("{}m", window), *rate))
+                    .collect(),
+            },
+            proofs: stats.total_proofs,
+            best_difficulty: stats.best_difficulty,
+            submit_latency: (&client.submit_latency.summary()).into(),
+            traffic: client.traffic.clone(),
+            recent_warnings,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).with_context(|| format!("unable to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        client,
+        prover::Prover,
+        testing::{self, FakeBackend, MockPool, ALL_SHARES},
+    };
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        redact_arguments(arguments.iter().map(|argument| argument.to_string()))
+    }
+
+    #[test]
+    fn redacts_the_secret_options() {
+        assert_eq!(
+            arguments(&["aleoxminer", "--password", "hunter2", "--pool", "pool:4040", "--control-token=abc"]),
+            ["aleoxminer", "--password", REDACTED, "--pool", "pool:4040", "--control-token=<redacted>"]
+        );
+        for option in SECRET_OPTIONS {
+            let redacted = arguments(&[option, "secret", &format!("{}=secret", option), "--worker", "rig"]);
+            assert!(!redacted.iter().any(|argument| argument.contains("secret")), "{:?}", redacted);
+            assert_eq!(redacted[3..], ["--worker", "rig"]);
+        }
+    }
+
+    #[test]
+    fn keeps_values_of_other_options() {
+        let given = ["aleoxminer", "--worker", "--password-file", "--log=/tmp/a=b", "--password"];
+        assert_eq!(arguments(&given), given);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_a_mock_pool_session() {
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        let client = pool.client("report");
+        let prover =
+            Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+                .unwrap();
+        prover.start().await.unwrap();
+        client::start(prover.event_sender(), client.clone());
+        let deadline = std::time::Instant::now() + LIMIT;
+        while prover.stats().valid_shares < 3 {
+            assert!(std::time::Instant::now() < deadline, "no shares accepted");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let stats = prover.stats();
+        let warnings = vec!["WARN pool is slow".to_string()];
+        let runtime = Duration::from_secs(2);
+        let report = RunReport::new(&stats, &client.stats(), &client.server(), "report", runtime, warnings);
+        let path = std::env::temp_dir().join(format!("aleoxminer-report-{}.json", std::process::id()));
+        report.save(&path).unwrap();
+        let json: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let mut keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        let mut expected = vec![
+            "build",
+            "arguments",
+            "started",
+            "stopped",
+            "runtime_seconds",
+            "connection",
+            "pools",
+            "devices",
+            "hashrate",
+            "proofs",
+            "best_difficulty",
+            "submit_latency",
+            "traffic",
+            "recent_warnings",
+        ];
+        keys.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+
+        assert!(!json["build"]["version"].as_str().unwrap().is_empty());
+        assert!(json["arguments"].as_array().unwrap().iter().all(Value::is_string));
+        assert_eq!(json["runtime_seconds"], 2);
+        assert_eq!(json["stopped"].as_u64().unwrap() - json["started"].as_u64().unwrap(), 2);
+        let availability = json["connection"]["availability"].as_f64().unwrap();
+        assert!(availability > 0.0 && availability <= 1.0);
+        assert_eq!(json["connection"]["reconnects"], 0);
+
+        let pools = json["pools"].as_array().unwrap();
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0]["server"], client.server());
+        assert_eq!(pools[0]["worker"], "report");
+        assert_eq!(pools[0]["accepted"], stats.valid_shares);
+        assert_eq!(pools[0]["rejected"], 0);
+
+        let devices = json["devices"].as_array().unwrap();
+        assert_eq!(devices.len(), stats.devices.len());
+        for device in devices {
+            assert!(device["name"].is_string() && device["state"].is_string());
+            assert!(device["proof_latency"]["count"].is_u64());
+        }
+        let accepted: u64 = devices.iter().map(|device| device["accepted"].as_u64().unwrap()).sum();
+        assert_eq!(accepted, stats.valid_shares as u64);
+
+        assert_eq!(json["proofs"], stats.total_proofs);
+        assert_eq!(json["hashrate"]["average"].as_f64().unwrap(), stats.total_proofs as f64 / 2.0);
+        assert!(json["hashrate"]["windows"].is_array());
+        assert!(json["submit_latency"]["count"].as_u64().unwrap() >= 3);
+        assert!(json["submit_latency"]["p50_ms"].is_u64());
+        assert!(!json["traffic"].as_array().unwrap().is_empty());
+        assert_eq!(json["recent_warnings"], serde_json::json!(["WARN pool is slow"]));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Error, Result};
+use chrono::{Local, Timelike};
+use tokio::{select, sync::watch, task};
+use tracing::info;
+
+use crate::{
+    client::Client,
+    prover::{PauseReason, Prover},
+};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// A daily time range in minutes since local midnight, `end` being exclusive.
+/// Ranges with `end` before `start` cross midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start <= self.end {
+            minute >= self.start && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Result<u32> {
+    let (hours, minutes) = s
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid time {}: expected HH:MM", s))?;
+    let hours: u32 = hours.parse().map_err(|_| anyhow!("invalid time {}", s))?;
+    let minutes: u32 = minutes.parse().map_err(|_| anyhow!("invalid time {}", s))?;
+    // 24:00 is accepted as the end of the day.
+    if minutes >= 60 || hours > 24 || (hours == 24 && minutes != 0) {
+        return Err(anyhow!("invalid time {}", s));
+    }
+    Ok(hours * 60 + minutes)
+}
+
+impl FromStr for Window {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("invalid time range {}: expected HH:MM-HH:MM", s))?;
+        let start = parse_time(start)? % MINUTES_PER_DAY;
+        let end = parse_time(end)?;
+        if start == end {
+            return Err(anyhow!("invalid time range {}: empty", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Daily windows in local time during which mining is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn contains(&self, minute: u32) -> bool {
+        self.windows.iter().any(|window| window.contains(minute))
+    }
+
+    pub fn active_now(&self) -> bool {
+        let now = Local::now();
+        self.contains(now.hour() * 60 + now.minute())
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    /// Parses comma separated ranges like `22:00-06:00,12:00-14:00`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let windows = s
+            .split(',')
+            .filter(|range| !range.trim().is_empty())
+            .map(|range| range.trim().parse())
+            .collect::<Result<Vec<Window>>>()?;
+        if windows.is_empty() {
+            return Err(anyhow!("empty schedule"));
+        }
+        Ok(Self { windows })
+    }
+}
+
+/// Parses durations like `90s`, `30m`, `4h` or `2d`. A bare number is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let value: u64 = value.parse().map_err(|_| anyhow!("invalid duration {}", s))?;
+    let multiplier = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(anyhow!("invalid duration {}: unknown unit {}", s, unit)),
+    };
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow!("invalid duration {}: too large", s))
+}
+
+/// Pauses the prover outside the scheduled windows, optionally disconnecting from the pool.
+/// The local time is evaluated periodically so clock and DST changes are picked up. The schedule
+/// can be replaced while running, mining is unrestricted while it is `None`.
+pub fn spawn(prover: Arc<Prover>, client: Arc<Client>, mut schedule: watch::Receiver<Option<Schedule>>, disconnect: bool) {
+    task::spawn(async move {
+        let mut active = true;
+        loop {
+            let now = schedule.borrow().as_ref().map_or(true, Schedule::active_now);
+            if now != active {
+                active = now;
+                if active {
+                    info!("Entering scheduled mining window");
+                    if disconnect {
+                        client.set_online(true);
+                    }
+                    prover.resume(PauseReason::Schedule).await;
+                } else {
+                    info!("Outside the mining schedule, pausing");
+                    prover.pause(PauseReason::Schedule).await;
+                    if disconnect {
+                        client.set_online(false);
+                    }
+                }
+            }
+            select! {
+                _ = tokio::time::sleep(Duration::from_secs(30)) => {}
+                changed = schedule.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minute(time: &str) -> u32 {
+        parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("00:00").unwrap(), 0);
+        assert_eq!(parse_time(" 6:30 ").unwrap(), 6 * 60 + 30);
+        assert_eq!(parse_time("24:00").unwrap(), MINUTES_PER_DAY);
+        for invalid in ["24:01", "25:00", "12:60", "12", "12:xx", "-1:00", ""] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn window_within_a_day() {
+        let window: Window = "09:00-17:00".parse().unwrap();
+        assert!(!window.contains(minute("08:59")));
+        assert!(window.contains(minute("09:00")));
+        assert!(window.contains(minute("16:59")));
+        assert!(!window.contains(minute("17:00")));
+    }
+
+    #[test]
+    fn window_crossing_midnight() {
+        let window: Window = "22:00-06:00".parse().unwrap();
+        assert!(window.contains(minute("22:00")));
+        assert!(window.contains(minute("23:59")));
+        assert!(window.contains(minute("00:00")));
+        assert!(window.contains(minute("05:59")));
+        assert!(!window.contains(minute("06:00")));
+        assert!(!window.contains(minute("21:59")));
+        assert!(!window.contains(minute("12:00")));
+    }
+
+    #[test]
+    fn window_until_the_end_of_the_day() {
+        let window: Window = "18:00-24:00".parse().unwrap();
+        assert!(window.contains(minute("23:59")));
+        assert!(!window.contains(0));
+        // A start of 24:00 is midnight.
+        let window: Window = "24:00-02:00".parse().unwrap();
+        assert!(window.contains(0));
+        assert!(!window.contains(minute("02:00")));
+    }
+
+    #[test]
+    fn rejects_invalid_windows() {
+        for invalid in ["10:00-10:00", "10:00", "10:00-", "-10:00", "10:00-11:00-12:00", "aa:bb-cc:dd"] {
+            assert!(invalid.parse::<Window>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn schedule_of_several_windows() {
+        let schedule: Schedule = " 22:00-06:00, 12:00-14:00 ,".parse().unwrap();
+        assert!(schedule.contains(minute("23:00")));
+        assert!(schedule.contains(minute("13:00")));
+        assert!(!schedule.contains(minute("14:00")));
+        assert!(!schedule.contains(minute("08:00")));
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        assert!("".parse::<Schedule>().is_err());
+        assert!(" , ".parse::<Schedule>().is_err());
+        assert!("22:00-06:00,bad".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_duration(" 4h ").unwrap(), Duration::from_secs(4 * 60 * 60));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(2 * 24 * 60 * 60));
+        for invalid in ["", "h", "4x", "4.5h", "-1h", "99999999999999999999d"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    sync::atomic::AtomicBool,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use rayon::ThreadPoolBuilder;
+use snarkvm::{
+    dpc::{testnet2::Testnet2, Account, BlockHeader, BlockTemplate, PoSWScheme, Record},
+    traits::Network,
+};
+use tokio::task;
+
+use crate::backend::{self, ProvingBackend};
+
+const FIXTURE_SEED: u64 = 0x5e1f_7e57;
+
+/// Deterministic template on top of the genesis block with the easiest possible difficulty,
+/// so any proof produced for it has to verify.
+pub fn fixture<R: Rng + CryptoRng>(rng: &mut R) -> Result<BlockTemplate<Testnet2>> {
+    let genesis = Testnet2::genesis_block();
+    let account = Account::<Testnet2>::new(rng);
+    let coinbase_record = Record::new_noop(account.address(), rng)?;
+    Ok(BlockTemplate::new(
+        genesis.hash(),
+        genesis.header().height() + 1,
+        genesis.header().timestamp() + 1,
+        u64::MAX,
+        genesis.header().cumulative_weight(),
+        genesis.header().previous_ledger_root(),
+        genesis.transactions().clone(),
+        coinbase_record,
+    ))
+}
+
+/// Proves and verifies one nonce of the fixture on the given device (-1 for CPU),
+/// returning the time the proof took.
+pub fn prove_fixture(device: i16, threads: usize) -> Result<Duration> {
+    check(&*backend::for_device(device), threads, |header| Testnet2::posw().verify_from_block_header(header))
+}
+
+// Proves one nonce of the fixture with `backend` and checks the proof with `verify`.
+fn check(
+    backend: &dyn ProvingBackend,
+    threads: usize,
+    verify: impl FnOnce(&BlockHeader<Testnet2>) -> bool,
+) -> Result<Duration> {
+    let mut rng = ChaChaRng::seed_from_u64(FIXTURE_SEED);
+    let template = fixture(&mut rng)?;
+    let pool = ThreadPoolBuilder::new()
+        .stack_size(16 * 1024 * 1024)
+        .num_threads(threads)
+        .thread_name(move |idx| format!("ap-test-{}", idx))
+        .build()?;
+    let terminator = AtomicBool::new(false);
+    let started = Instant::now();
+    let block_header = pool.install(|| backend.prove(&template, &terminator, &mut rng))?;
+    let elapsed = started.elapsed();
+    if !verify(&block_header) {
+        return Err(anyhow!("proof failed verification"));
+    }
+    Ok(elapsed)
+}
+
+/// Runs the self-test on every device one after another.
+pub async fn run(devices: &[i16], threads: usize) -> Vec<(i16, Result<Duration>)> {
+    let mut results = Vec::new();
+    for device in devices.iter().copied() {
+        let result = task::spawn_blocking(move || prove_fixture(device, threads))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("self-test aborted: {}", e)));
+        results.push((device, result));
+    }
+    results
+}
+
+pub fn device_name(device: i16) -> String {
+    backend::for_device(device).name()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::CpuBackend, testing::FakeBackend};
+
+    const NONCES: usize = 3;
+
+    fn prove_seeded(seed: u64) -> Vec<BlockHeader<Testnet2>> {
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        let template = fixture(&mut rng).unwrap();
+        let terminator = AtomicBool::new(false);
+        (0..NONCES)
+            .map(|_| CpuBackend.prove(&template, &terminator, &mut rng).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn passes_with_a_verifying_proof() {
+        let backend = FakeBackend::new(Duration::from_millis(20));
+        let latency = check(&*backend, 1, |_| true).unwrap();
+        assert!(latency >= Duration::from_millis(20));
+        assert_eq!(backend.attempts(), 1);
+    }
+
+    #[test]
+    fn fails_with_a_proof_not_verifying() {
+        let backend = FakeBackend::new(Duration::ZERO);
+        let error = check(&*backend, 1, |_| false).unwrap_err();
+        assert_eq!(error.to_string(), "proof failed verification");
+    }
+
+    #[test]
+    #[ignore = "loads the proving parameters and proves on the CPU, takes minutes"]
+    fn seeded_proofs_repeat_and_verify() {
+        let headers = prove_seeded(FIXTURE_SEED);
+        let nonces: Vec<_> = headers.iter().map(|header| header.nonce()).collect();
+        assert_eq!(nonces.iter().collect::<std::collections::HashSet<_>>().len(), NONCES);
+        for header in headers.iter() {
+            assert!(Testnet2::posw().verify_from_block_header(header));
+        }
+        let again: Vec<_> = prove_seeded(FIXTURE_SEED).iter().map(|header| header.nonce()).collect();
+        assert_eq!(again, nonces);
+    }
+}
@@ -0,0 +1,331 @@
+// Pool side of the protocol: `PoolSession` tracks one miner connection and says what to do with
+// each message it sends. The caller owns the socket, the authorization decision and the shares.
+
+use std::collections::BTreeMap;
+
+use snarkvm::dpc::{testnet2::Testnet2, BlockTemplate, PoSWProof};
+use snarkvm::traits::Network;
+
+use crate::message::{
+    difficulty_hint,
+    password_field,
+    Code,
+    ProverMessage,
+    Secret,
+    JOB_ACK_CAPABILITY,
+    MSGPACK_CAPABILITY,
+    MULTIPLEX_CAPABILITY,
+    POOL_INFO_CAPABILITY,
+    SESSION_CAPABILITY,
+    SESSION_TOKEN_PREFIX,
+    SPECULATIVE_CAPABILITY,
+};
+
+/// Where a session is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// Waiting for Authorize
+    Connected,
+    /// Authorize received, waiting for `PoolSession::authorized`
+    Authorizing,
+    Authorized,
+    /// Rejected or after a protocol violation, nothing more is accepted
+    Closed,
+}
+
+/// What the pool should do after a message from the miner.
+#[derive(Debug)]
+pub enum Action {
+    /// Check the credentials, then answer with `PoolSession::authorized`
+    Authorize {
+        account: String,
+        worker: String,
+        password: Secret,
+        version: u16,
+    },
+    /// Verify the share and answer with `PoolSession::share_result`
+    ForwardShare {
+        /// Named by a Worker before the share, `None` for the authorized worker
+        worker: Option<String>,
+        height: u32,
+        nonce: <Testnet2 as Network>::PoSWNonce,
+        proof: PoSWProof<Testnet2>,
+    },
+    /// Send this to the miner
+    Reply(ProverMessage),
+    /// The miner's proof rate changed, p/s * 100
+    ProofRate(u64),
+    /// The miner is proving the job for `height` at `pool_target`, `lag` blocks behind the latest Notify
+    JobAck { height: u32, pool_target: u64, lag: u32 },
+    /// Close the connection
+    ProtocolViolation(String),
+    /// Nothing to do
+    None,
+}
+
+/// Per session counters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub messages: u64,
+    pub notifies: u64,
+    pub shares: u64,
+    pub accepted: u64,
+    pub rejected: u64,
+    pub stale: u64,
+}
+
+/// State of one miner connection on the pool side.
+#[derive(Debug)]
+pub struct PoolSession {
+    state: SessionState,
+    account: Option<String>,
+    worker: Option<String>,
+    // The miner offered MessagePack frames when authorizing.
+    msgpack: bool,
+    // Share difficulty asked for in the password.
+    difficulty_hint: Option<u64>,
+    // The miner offered to resume sessions, with the token of its previous one if any.
+    session: bool,
+    session_token: Option<String>,
+    // The miner takes speculative jobs.
+    speculative: bool,
+    // The miner sends a JobAck with each proof rate report, and the latest one.
+    job_ack: bool,
+    acked: Option<(u32, u64)>,
+    // The miner shows a PoolInfo.
+    pool_info: bool,
+    // The miner names the worker of a share with a Worker before it, and the one named for the next.
+    multiplex: bool,
+    share_worker: Option<String>,
+    // Height of the latest Notify, earlier shares are stale.
+    height: Option<u32>,
+    stats: SessionStats,
+}
+
+impl Default for PoolSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PoolSession {
+    pub fn new() -> Self {
+        Self {
+            state: SessionState::Connected,
+            account: None,
+            worker: None,
+            msgpack: false,
+            difficulty_hint: None,
+            session: false,
+            session_token: None,
+            speculative: false,
+            job_ack: false,
+            acked: None,
+            pool_info: false,
+            multiplex: false,
+            share_worker: None,
+            height: None,
+            stats: SessionStats::default(),
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Account and worker of the Authorize message.
+    pub fn identity(&self) -> Option<(&str, &str)> {
+        Some((self.account.as_deref()?, self.worker.as_deref()?))
+    }
+
+    /// Whether the miner reads MessagePack frames, answer with `ProverCodec::use_msgpack` to use them.
+    pub fn msgpack(&self) -> bool {
+        self.msgpack
+    }
+
+    /// Share difficulty the miner asked for with a `d=` password field, honoring it is up to the pool.
+    pub fn difficulty_hint(&self) -> Option<u64> {
+        self.difficulty_hint
+    }
+
+    /// Whether the miner keeps session tokens, see `PoolSession::authorized_session`.
+    pub fn resumes_sessions(&self) -> bool {
+        self.session
+    }
+
+    /// Token of the session the miner asks to resume, the pool decides whether it still knows it.
+    pub fn session_token(&self) -> Option<&str> {
+        self.session_token.as_deref()
+    }
+
+    /// Whether the miner takes speculative jobs, see `PoolSession::speculative_notify`.
+    pub fn takes_speculative(&self) -> bool {
+        self.speculative
+    }
+
+    /// Whether the miner reports the job it is proving, see `PoolSession::lag`.
+    pub fn sends_job_acks(&self) -> bool {
+        self.job_ack
+    }
+
+    /// Height and pool target of the job the miner last said it is proving.
+    pub fn acked(&self) -> Option<(u32, u64)> {
+        self.acked
+    }
+
+    /// Blocks the job the miner last acknowledged is behind the latest Notify, `None` before the
+    /// first JobAck. A miner lagging for longer than its report interval is likely stuck.
+    pub fn lag(&self) -> Option<u32> {
+        let (acked, _) = self.acked?;
+        Some(self.height?.saturating_sub(acked))
+    }
+
+    /// Whether the miner shows a PoolInfo, see `PoolSession::pool_info`.
+    pub fn takes_pool_info(&self) -> bool {
+        self.pool_info
+    }
+
+    /// Whether the miner submits the shares of several workers, see `Action::ForwardShare`.
+    pub fn multiplexes(&self) -> bool {
+        self.multiplex
+    }
+
+    pub fn stats(&self) -> &SessionStats {
+        &self.stats
+    }
+
+    /// What to do with a message from the miner.
+    pub fn receive(&mut self, message: ProverMessage) -> Action {
+        self.stats.messages += 1;
+        match (self.state, message) {
+            (SessionState::Closed, message) => {
+                self.violation(format!("{} after the session was closed", message.name()))
+            }
+            (SessionState::Connected, ProverMessage::Authorize(account, worker, password, version)) => {
+                self.state = SessionState::Authorizing;
+                self.account = Some(account.clone());
+                self.worker = Some(worker.clone());
+                self.msgpack = version & MSGPACK_CAPABILITY != 0;
+                self.difficulty_hint = difficulty_hint(password.expose());
+                self.session = version & SESSION_CAPABILITY != 0;
+                self.session_token = password_field(password.expose(), "session").map(str::to_string);
+                self.speculative = version & SPECULATIVE_CAPABILITY != 0;
+                self.job_ack = version & JOB_ACK_CAPABILITY != 0;
+                self.pool_info = version & POOL_INFO_CAPABILITY != 0;
+                self.multiplex = version & MULTIPLEX_CAPABILITY != 0;
+                Action::Authorize {
+                    account,
+                    worker,
+                    password,
+                    version,
+                }
+            }
+            (SessionState::Connected, message) => self.violation(format!("{} before Authorize", message.name())),
+            (_, ProverMessage::Authorize(..)) => self.violation("Authorize sent twice".to_string()),
+            // Keepalives are echoed in any state.
+            (_, ProverMessage::Canary) => Action::Reply(ProverMessage::Canary),
+            (SessionState::Authorizing, message) => {
+                self.violation(format!("{} before the authorization result", message.name()))
+            }
+            (SessionState::Authorized, ProverMessage::Submit(height, nonce, proof)) => {
+                self.stats.shares += 1;
+                let worker = self.share_worker.take();
+                if self.height.map_or(true, |latest| height < latest) {
+                    self.stats.stale += 1;
+                    return Action::Reply(ProverMessage::SubmitResult(Code::Stale, None));
+                }
+                Action::ForwardShare {
+                    worker,
+                    height,
+                    nonce,
+                    proof,
+                }
+            }
+            (SessionState::Authorized, ProverMessage::Worker(worker)) if self.multiplex => {
+                self.share_worker = Some(worker);
+                Action::None
+            }
+            (SessionState::Authorized, ProverMessage::ProofRate(rate)) => Action::ProofRate(rate),
+            (SessionState::Authorized, ProverMessage::JobAck(height, pool_target)) => {
+                self.acked = Some((height, pool_target));
+                Action::JobAck {
+                    height,
+                    pool_target,
+                    lag: self.lag().unwrap_or_default(),
+                }
+            }
+            (SessionState::Authorized, message) => {
+                self.violation(format!("{} is not sent by miners", message.name()))
+            }
+        }
+    }
+
+    /// AuthorizeResult answering the Authorize, a rejected session is closed.
+    pub fn authorized(&mut self, accepted: bool, message: Option<String>) -> ProverMessage {
+        self.state = if accepted { SessionState::Authorized } else { SessionState::Closed };
+        ProverMessage::AuthorizeResult(accepted, message)
+    }
+
+    /// Successful AuthorizeResult giving the miner `token`, the resumed session's or a new one. Miners
+    /// not resuming sessions get no token.
+    pub fn authorized_session(&mut self, token: &str) -> ProverMessage {
+        let message = Some(format!("{}{}", SESSION_TOKEN_PREFIX, token)).filter(|_| self.session);
+        self.authorized(true, message)
+    }
+
+    /// Notify with new work, `None` before the session is authorized. Work for a lower height than
+    /// the previous one is flagged as a reorg.
+    pub fn notify(&mut self, template: BlockTemplate<Testnet2>, target: u64) -> Option<ProverMessage> {
+        if self.state != SessionState::Authorized {
+            return None;
+        }
+        let reorg = self.height.map_or(false, |height| template.block_height() < height);
+        self.height = Some(template.block_height());
+        self.stats.notifies += 1;
+        Some(ProverMessage::Notify(template, target, reorg, false))
+    }
+
+    /// Speculative Notify for the height after the current one, mined only after `activate` or a
+    /// regular Notify of the same template. `None` unless the miner takes speculative jobs.
+    pub fn speculative_notify(&mut self, template: BlockTemplate<Testnet2>, target: u64) -> Option<ProverMessage> {
+        if self.state != SessionState::Authorized || !self.speculative {
+            return None;
+        }
+        self.stats.notifies += 1;
+        Some(ProverMessage::Notify(template, target, false, true))
+    }
+
+    /// ActivateJob switching the miner to the speculative job for `height`, shares for lower heights
+    /// are stale from now on.
+    pub fn activate(&mut self, height: u32) -> Option<ProverMessage> {
+        if self.state != SessionState::Authorized || !self.speculative {
+            return None;
+        }
+        self.height = Some(height);
+        Some(ProverMessage::ActivateJob(height))
+    }
+
+    /// PoolInfo telling the miner about its account, e.g. `balance`, see `POOL_INFO_FIELDS`. `None`
+    /// unless the miner shows it. Each one replaces the previous one.
+    pub fn pool_info(&self, fields: BTreeMap<String, String>) -> Option<ProverMessage> {
+        if self.state != SessionState::Authorized || !self.pool_info {
+            return None;
+        }
+        Some(ProverMessage::PoolInfo(fields))
+    }
+
+    /// SubmitResult for a forwarded share.
+    pub fn share_result(&mut self, code: Code, message: Option<String>) -> ProverMessage {
+        match code {
+            Code::Success => self.stats.accepted += 1,
+            Code::Stale => self.stats.stale += 1,
+            _ => self.stats.rejected += 1,
+        }
+        ProverMessage::SubmitResult(code, message)
+    }
+
+    fn violation(&mut self, reason: String) -> Action {
+        self.state = SessionState::Closed;
+        Action::ProtocolViolation(reason)
+    }
+}
@@ -0,0 +1,209 @@
+// Manual test, from an elevated prompt: `aleoxminer --config C:\miner\config.toml service install`,
+// `sc start aleoxminer`, `sc query aleoxminer` shows RUNNING once mining started, `sc stop aleoxminer`
+// shows STOP_PENDING while the session summary is written and then STOPPED,
+// `aleoxminer service uninstall` removes it.
+
+use std::{
+    ffi::OsString,
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use structopt::StructOpt;
+use tokio::sync::watch;
+use tracing::warn;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+const SERVICE_NAME: &str = "aleoxminer";
+const DISPLAY_NAME: &str = "AleoXMiner";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+// Loading the parameters and connecting may take a while before mining starts.
+const START_WAIT_HINT: Duration = Duration::from_secs(120);
+const STOP_WAIT_HINT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, StructOpt)]
+pub enum ServiceAction {
+    /// Install the service, it starts with the options given before `service`
+    Install,
+    /// Stop and remove the service
+    Uninstall,
+    /// Mine as the service, only the service manager should start this
+    Run,
+}
+
+/// Arguments the installed service starts with: the options before `service`, then `service run`.
+/// `args` excludes the program name.
+pub fn launch_arguments(args: &[OsString]) -> Vec<OsString> {
+    let end = args.iter().rposition(|arg| arg == "service").unwrap_or(args.len());
+    args[..end]
+        .iter()
+        .cloned()
+        .chain(["service", "run"].iter().map(OsString::from))
+        .collect()
+}
+
+pub fn install(arguments: Vec<OsString>) -> Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .context("Unable to connect to the service manager, run this from an elevated prompt")?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: arguments,
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .with_context(|| format!("Unable to install the {} service", SERVICE_NAME))?;
+    service.set_description("Aleo prover")?;
+    Ok(())
+}
+
+pub fn uninstall() -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .context("Unable to connect to the service manager, run this from an elevated prompt")?;
+    let service = manager
+        .open_service(
+            SERVICE_NAME,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )
+        .with_context(|| format!("Unable to open the {} service", SERVICE_NAME))?;
+    service.delete()?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    Ok(())
+}
+
+/// Handle of the running service, reports its state to the service manager.
+pub struct Service {
+    status: ServiceStatusHandle,
+    stop: watch::Receiver<bool>,
+}
+
+// service_main can't take arguments from us, it hands the registered service back through this.
+static STARTED: Mutex<Option<mpsc::Sender<Result<Service>>>> = Mutex::new(None);
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    let service = register();
+    if let Some(started) = STARTED.lock().unwrap().take() {
+        let _ = started.send(service);
+    }
+}
+
+fn register() -> Result<Service> {
+    let (sender, stop) = watch::channel(false);
+    let status = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = sender.send(true);
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+    let service = Service { status, stop };
+    service.set(ServiceState::StartPending, START_WAIT_HINT, 0);
+    Ok(service)
+}
+
+/// Connects to the service manager, which calls back into `service_main`. Fails when not
+/// started by the service manager.
+pub fn start() -> Result<Service> {
+    let (sender, receiver) = mpsc::channel();
+    *STARTED.lock().unwrap() = Some(sender);
+    std::thread::spawn(|| {
+        // Blocks until the service reports it stopped.
+        if let Err(e) = service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+            if let Some(started) = STARTED.lock().unwrap().take() {
+                let _ = started.send(Err(anyhow!(
+                    "Unable to connect to the service manager, `service run` is started by Windows after `service install`: {}",
+                    e
+                )));
+            }
+        }
+    });
+    receiver
+        .recv()
+        .map_err(|_| anyhow!("The service dispatcher exited"))?
+}
+
+impl Service {
+    fn set(&self, state: ServiceState, wait_hint: Duration, exit_code: u32) {
+        let controls_accepted = match state {
+            ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            _ => ServiceControlAccept::empty(),
+        };
+        let status = ServiceStatus {
+            service_type: SERVICE_TYPE,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint,
+            process_id: None,
+        };
+        if let Err(e) = self.status.set_service_status(status) {
+            warn!("Unable to report the service state {:?}: {}", state, e);
+        }
+    }
+
+    pub fn running(&self) {
+        self.set(ServiceState::Running, Duration::ZERO, 0);
+    }
+
+    pub fn stopping(&self) {
+        self.set(ServiceState::StopPending, STOP_WAIT_HINT, 0);
+    }
+
+    pub fn stopped(&self, exit_code: u32) {
+        self.set(ServiceState::Stopped, Duration::ZERO, exit_code);
+    }
+
+    /// Resolves once the service manager asked the service to stop.
+    pub async fn stop_requested(&mut self) {
+        while !*self.stop.borrow() {
+            if self.stop.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn launches_with_the_options_before_service() {
+        let args = os(&["--config", "C:\\miner\\config.toml", "-v", "service", "install"]);
+        assert_eq!(launch_arguments(&args), os(&["--config", "C:\\miner\\config.toml", "-v", "service", "run"]));
+        // Only the last `service` is the subcommand.
+        let args = os(&["--worker", "service", "service", "install"]);
+        assert_eq!(launch_arguments(&args), os(&["--worker", "service", "service", "run"]));
+        assert_eq!(launch_arguments(&os(&["-v"])), os(&["-v", "service", "run"]));
+    }
+}
@@ -0,0 +1,471 @@
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use snarkvm::dpc::{testnet2::Testnet2, Address};
+
+#[cfg(feature = "chaos")]
+use crate::chaos::ChaosConfig;
+#[cfg(feature = "metrics")]
+use crate::{http, metrics};
+use crate::{
+    client::{self, Client, PoolCredentials},
+    events::EventBus,
+    group::WorkerGroup,
+    hooks::MinerHooks,
+    message::{ProtocolLimits, Secret},
+    outbox::Outbox,
+    outgoing::MaxAges,
+    prover::{Prover, ProverConfig},
+    split,
+    stats::SessionTokens,
+    status::Status,
+    throttle::BandwidthLimits,
+    transport::Connector,
+};
+
+/// Pool connections and the prover of one miner, wired together. Made with `MiningSession::builder`.
+pub struct MiningSession {
+    events: EventBus,
+    client: Arc<Client>,
+    groups: Vec<(WorkerGroup, Arc<Client>)>,
+    // Pools the proving time is split between with their weight, the main connection first.
+    split: Vec<(Arc<Client>, u32)>,
+    prover: Arc<Prover>,
+    #[cfg(feature = "metrics")]
+    metrics_bind: Option<SocketAddr>,
+}
+
+/// Settings of a `MiningSession`, a pool, an account or address and the prover settings are required.
+pub struct MiningSessionBuilder {
+    pool: Option<String>,
+    account: Option<String>,
+    address: Option<Address<Testnet2>>,
+    worker: Option<String>,
+    password: Secret,
+    pool_credentials: HashMap<String, PoolCredentials>,
+    limits: ProtocolLimits,
+    submit_queue: usize,
+    max_ages: MaxAges,
+    bandwidth: BandwidthLimits,
+    msgpack: bool,
+    job_ack: bool,
+    speculative: bool,
+    pool_info: bool,
+    reorg_tolerance: u32,
+    template_validation: bool,
+    template_max_skew: Duration,
+    difficulty: u64,
+    groups: Vec<WorkerGroup>,
+    split: Vec<(String, u32)>,
+    prover: Option<ProverConfig>,
+    hooks: Option<Arc<dyn MinerHooks>>,
+    connector: Option<Arc<dyn Connector>>,
+    #[cfg(feature = "metrics")]
+    metrics_bind: Option<SocketAddr>,
+    dry_run: Option<bool>,
+    keepalive: Option<(Option<Duration>, Duration)>,
+    unreachable_limit: Option<Duration>,
+    record_traffic: Option<PathBuf>,
+    session_tokens: Option<Arc<SessionTokens>>,
+    outbox: Option<Arc<Outbox>>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<ChaosConfig>,
+}
+
+impl MiningSession {
+    pub fn builder() -> MiningSessionBuilder {
+        MiningSessionBuilder {
+            pool: None,
+            account: None,
+            address: None,
+            worker: None,
+            password: Secret::default(),
+            pool_credentials: HashMap::new(),
+            limits: ProtocolLimits::default(),
+            submit_queue: 1024,
+            max_ages: MaxAges::default(),
+            bandwidth: BandwidthLimits::default(),
+            msgpack: false,
+            job_ack: false,
+            speculative: false,
+            pool_info: false,
+            reorg_tolerance: 2,
+            template_validation: true,
+            template_max_skew: Duration::from_secs(600),
+            difficulty: 0,
+            groups: Vec::new(),
+            split: Vec::new(),
+            prover: None,
+            hooks: None,
+            connector: None,
+            #[cfg(feature = "metrics")]
+            metrics_bind: None,
+            dry_run: None,
+            keepalive: None,
+            unreachable_limit: None,
+            record_traffic: None,
+            session_tokens: None,
+            outbox: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        }
+    }
+
+    /// Starts proving and connects to the pool.
+    pub async fn start(&self) -> Result<()> {
+        self.prover.start().await?;
+        client::start(self.prover.event_sender(), self.client.clone());
+        for (_, group_client) in self.groups.iter() {
+            client::start(self.prover.event_sender(), group_client.clone());
+        }
+        for (split_client, _) in self.split.iter().skip(1) {
+            client::start(self.prover.event_sender(), split_client.clone());
+        }
+        if !self.split.is_empty() {
+            split::spawn(self.prover.clone(), self.split.clone());
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(bind) = self.metrics_bind {
+            http::serve(bind, vec![metrics::handler(self.prover.clone(), self.client.clone())]);
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> Status {
+        Status::new(
+            &self.prover.stats(),
+            &self.client.stats(),
+            &self.client.server(),
+            &self.client.worker(),
+        )
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Connection of the main worker, it receives the work.
+    pub fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+
+    /// Connections of the worker groups.
+    pub fn groups(&self) -> &[(WorkerGroup, Arc<Client>)] {
+        &self.groups
+    }
+
+    /// Connections of the pools the proving time is split between with their weight, the main
+    /// connection first. Empty unless splitting.
+    pub fn split(&self) -> &[(Arc<Client>, u32)] {
+        &self.split
+    }
+
+    pub fn prover(&self) -> &Arc<Prover> {
+        &self.prover
+    }
+
+    /// Stops forwarding pool messages and waits for the proving threads to stop.
+    pub async fn shutdown(&self) {
+        for connection in self.connections() {
+            connection.close();
+        }
+        self.prover.stop().await;
+    }
+
+    fn connections(&self) -> impl Iterator<Item = &Arc<Client>> {
+        std::iter::once(&self.client)
+            .chain(self.groups.iter().map(|(_, group_client)| group_client))
+            .chain(self.split.iter().skip(1).map(|(split_client, _)| split_client))
+    }
+}
+
+impl MiningSessionBuilder {
+    /// Pool address, `host:port`.
+    pub fn pool(mut self, pool: String) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn account(mut self, account: Option<String>) -> Self {
+        self.account = account;
+        self
+    }
+
+    pub fn address(mut self, address: Option<Address<Testnet2>>) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn worker(mut self, worker: Option<String>) -> Self {
+        self.worker = worker;
+        self
+    }
+
+    pub fn password(mut self, password: Secret) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// Credentials overridden per pool address.
+    pub fn pool_credentials(mut self, credentials: HashMap<String, PoolCredentials>) -> Self {
+        self.pool_credentials = credentials;
+        self
+    }
+
+    pub fn protocol_limits(mut self, limits: ProtocolLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Messages waiting for the connection, 1024 by default.
+    pub fn submit_queue(mut self, submit_queue: usize) -> Self {
+        self.submit_queue = submit_queue;
+        self
+    }
+
+    /// GPUs submitting under their own worker name, each group gets its own connection.
+    pub fn groups(mut self, groups: Vec<WorkerGroup>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Splits the proving time between pools by weight, `(address, weight)` with the main pool
+    /// first. The shares of each job are submitted to the pool that sent it, see `split::spawn`.
+    pub fn split(mut self, pools: Vec<(String, u32)>) -> Self {
+        self.split = pools;
+        self
+    }
+
+    /// Prover settings, its `groups` are filled in by `build`.
+    pub fn prover(mut self, config: ProverConfig) -> Self {
+        self.prover = Some(config);
+        self
+    }
+
+    pub fn hooks(mut self, hooks: Arc<dyn MinerHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Opens the pool connections with `connector` instead of plain TCP.
+    pub fn connector(mut self, connector: Arc<dyn Connector>) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Serves the Prometheus metrics on `bind` once started.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_bind(mut self, bind: SocketAddr) -> Self {
+        self.metrics_bind = Some(bind);
+        self
+    }
+
+    /// Counts shares instead of submitting them, see `Client::set_dry_run`.
+    pub fn dry_run(mut self, zero_rate: bool) -> Self {
+        self.dry_run = Some(zero_rate);
+        self
+    }
+
+    /// Queued messages older than these are dropped instead of sent.
+    pub fn max_ages(mut self, max_ages: MaxAges) -> Self {
+        self.max_ages = max_ages;
+        self
+    }
+
+    /// See `Client::set_bandwidth_limits`.
+    pub fn bandwidth(mut self, limits: BandwidthLimits) -> Self {
+        self.bandwidth = limits;
+        self
+    }
+
+    /// Offers MessagePack frames to the pool, see `Client::set_msgpack`.
+    pub fn msgpack(mut self, msgpack: bool) -> Self {
+        self.msgpack = msgpack;
+        self
+    }
+
+    /// Acknowledges the job being proven with each proof rate report, see `Client::set_job_ack`.
+    pub fn job_ack(mut self, job_ack: bool) -> Self {
+        self.job_ack = job_ack;
+        self
+    }
+
+    /// Takes speculative jobs, see `Client::set_speculative`.
+    pub fn speculative(mut self, speculative: bool) -> Self {
+        self.speculative = speculative;
+        self
+    }
+
+    /// Asks for PoolInfo messages, see `Client::set_accept_pool_info`.
+    pub fn pool_info(mut self, pool_info: bool) -> Self {
+        self.pool_info = pool_info;
+        self
+    }
+
+    /// See `Client::set_reorg_tolerance`.
+    pub fn reorg_tolerance(mut self, blocks: u32) -> Self {
+        self.reorg_tolerance = blocks;
+        self
+    }
+
+    /// See `Client::set_template_validation`.
+    pub fn template_validation(mut self, enabled: bool, max_skew: Duration) -> Self {
+        self.template_validation = enabled;
+        self.template_max_skew = max_skew;
+        self
+    }
+
+    /// See `Client::set_difficulty`.
+    pub fn difficulty(mut self, difficulty: u64) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    pub fn keepalive(mut self, interval: Option<Duration>, rtt_warning: Duration) -> Self {
+        self.keepalive = Some((interval, rtt_warning));
+        self
+    }
+
+    pub fn unreachable_limit(mut self, limit: Option<Duration>) -> Self {
+        self.unreachable_limit = limit;
+        self
+    }
+
+    pub fn record_traffic(mut self, dir: Option<PathBuf>) -> Self {
+        self.record_traffic = dir;
+        self
+    }
+
+    /// See `Client::set_session_tokens`, the connections to a pool with different workers keep
+    /// tokens of their own.
+    pub fn session_tokens(mut self, tokens: Option<Arc<SessionTokens>>) -> Self {
+        self.session_tokens = tokens;
+        self
+    }
+
+    /// See `Client::set_outbox`, shared by all connections.
+    pub fn outbox(mut self, outbox: Option<Arc<Outbox>>) -> Self {
+        self.outbox = outbox;
+        self
+    }
+
+    #[cfg(feature = "chaos")]
+    pub fn chaos(mut self, chaos: Option<ChaosConfig>) -> Self {
+        self.chaos = chaos;
+        self
+    }
+
+    /// Creates the connections and the prover, nothing runs before `MiningSession::start`. Hooks are
+    /// dispatched from a task, so this has to be called within the Tokio runtime.
+    pub fn build(self) -> Result<MiningSession> {
+        let pool = self.pool.ok_or_else(|| anyhow!("no pool given"))?;
+        if self.account.is_none() && self.address.is_none() {
+            return Err(anyhow!("no account or address given"));
+        }
+        let mut config = self.prover.ok_or_else(|| anyhow!("no prover settings given"))?;
+        if matches!(self.split.first(), Some((first, _)) if *first != pool) {
+            return Err(anyhow!("the split has to start with the main pool"));
+        }
+        if !self.split.is_empty() && !self.groups.is_empty() {
+            return Err(anyhow!("worker groups can't be combined with a split"));
+        }
+        let events = EventBus::new();
+        let group_credentials = Client::group_credentials(&self.pool_credentials);
+        let groups: Vec<(WorkerGroup, Arc<Client>)> = self
+            .groups
+            .into_iter()
+            .map(|group| {
+                let client = Client::init(
+                    self.account.clone(),
+                    Some(group.name.clone()),
+                    self.address,
+                    pool.clone(),
+                    events.clone(),
+                    self.limits,
+                    self.submit_queue,
+                );
+                client.set_forward_work(false);
+                client.set_pool_credentials(group_credentials.clone());
+                (group, client)
+            })
+            .collect();
+        let client = Client::init(
+            self.account.clone(),
+            self.worker.clone(),
+            self.address,
+            pool,
+            events.clone(),
+            self.limits,
+            self.submit_queue,
+        );
+        let split: Vec<(Arc<Client>, u32)> = self
+            .split
+            .iter()
+            .enumerate()
+            .map(|(index, (server, weight))| {
+                if index == 0 {
+                    return (client.clone(), *weight);
+                }
+                let split_client = Client::init(
+                    self.account.clone(),
+                    self.worker.clone(),
+                    self.address,
+                    server.clone(),
+                    events.clone(),
+                    self.limits,
+                    self.submit_queue,
+                );
+                split_client.set_pool_credentials(self.pool_credentials.clone());
+                (split_client, *weight)
+            })
+            .collect();
+        client.set_pool_credentials(self.pool_credentials);
+        let connections = std::iter::once(&client)
+            .chain(groups.iter().map(|(_, group_client)| group_client))
+            .chain(split.iter().skip(1).map(|(split_client, _)| split_client));
+        for connection in connections {
+            connection.set_password(self.password.clone());
+            connection.set_max_ages(self.max_ages);
+            connection.set_bandwidth_limits(self.bandwidth);
+            connection.set_msgpack(self.msgpack);
+            connection.set_job_ack(self.job_ack);
+            connection.set_speculative(self.speculative);
+            connection.set_accept_pool_info(self.pool_info);
+            connection.set_reorg_tolerance(self.reorg_tolerance);
+            connection.set_template_validation(self.template_validation, self.template_max_skew);
+            connection.set_difficulty(self.difficulty);
+            connection.set_record_traffic(self.record_traffic.clone());
+            connection.set_session_tokens(self.session_tokens.clone());
+            connection.set_outbox(self.outbox.clone());
+            #[cfg(feature = "chaos")]
+            connection.set_chaos(self.chaos.clone());
+            if let Some(zero_rate) = self.dry_run {
+                connection.set_dry_run(zero_rate);
+            }
+            if let Some((interval, rtt_warning)) = self.keepalive {
+                connection.set_keepalive(interval, rtt_warning);
+            }
+            connection.set_unreachable_limit(self.unreachable_limit);
+            if let Some(connector) = self.connector.as_ref() {
+                connection.set_connector(connector.clone());
+            }
+        }
+        if let Some(hooks) = self.hooks {
+            client.set_hooks(hooks);
+        }
+
+        config.groups = groups.clone();
+        let prover = Prover::new(config, client.clone())?;
+        Ok(MiningSession {
+            events,
+            client,
+            groups,
+            split,
+            prover,
+            #[cfg(feature = "metrics")]
+            metrics_bind: self.metrics_bind,
+        })
+    }
+}
@@ -0,0 +1,388 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use tokio::{sync::broadcast::error::RecvError, task, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    events::{EventBus, MinerEvent, ShareResult},
+    message::Code,
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+const CSV_HEADER: &str = "timestamp,pool,worker,height,nonce,outcome,latency_ms,difficulty";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Jsonl,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Ok(Self::Csv),
+            Some("jsonl") => Ok(Self::Jsonl),
+            _ => Err(anyhow!("share log {} must end in .csv or .jsonl", path.display())),
+        }
+    }
+}
+
+/// One submit result.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareRecord {
+    /// RFC 3339 local time
+    pub timestamp: String,
+    pub pool: String,
+    pub worker: String,
+    pub height: u32,
+    /// Little endian bytes in hex
+    pub nonce: Option<String>,
+    pub outcome: &'static str,
+    pub latency_ms: Option<u128>,
+    pub difficulty: Option<u64>,
+}
+
+pub fn outcome(code: &Code) -> &'static str {
+    match code {
+        Code::Success => "accepted",
+        Code::InvalidProof => "invalid",
+        Code::Stale => "stale",
+        Code::ProxyException => "proxy-exception",
+        Code::Other => "other",
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl From<&ShareResult> for ShareRecord {
+    fn from(result: &ShareResult) -> Self {
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            pool: result.pool.clone(),
+            worker: result.worker.clone(),
+            height: result.height,
+            nonce: result.nonce.clone(),
+            outcome: outcome(&result.code),
+            latency_ms: result.latency.map(|latency| latency.as_millis()),
+            difficulty: result.difficulty,
+        }
+    }
+}
+
+impl ShareRecord {
+    pub fn csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{}",
+            self.timestamp,
+            csv_field(&self.pool),
+            csv_field(&self.worker),
+            self.height,
+            optional(self.nonce.as_ref()),
+            self.outcome,
+            optional(self.latency_ms),
+            optional(self.difficulty)
+        )
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    format: Format,
+    max_size: Option<u64>,
+    file: BufWriter<File>,
+    size: u64,
+}
+
+impl Writer {
+    fn open(path: &Path, format: Format, max_size: Option<u64>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("unable to open share log {}", path.display()))?;
+        let size = file.metadata()?.len();
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            format,
+            max_size,
+            file: BufWriter::new(file),
+            size,
+        };
+        if size == 0 && format == Format::Csv {
+            writer.write_line(CSV_HEADER)?;
+        }
+        Ok(writer)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    /// Moves the full file aside as `<name>.<time>.<extension>` and starts a new one.
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = self.path.extension().unwrap_or_default().to_string_lossy();
+        let rotated = self.path.with_file_name(format!(
+            "{}.{}.{}",
+            stem,
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            extension
+        ));
+        fs::rename(&self.path, &rotated)?;
+        *self = Self::open(&self.path, self.format, self.max_size)?;
+        Ok(())
+    }
+
+    fn record(&mut self, record: &ShareRecord) -> Result<()> {
+        if let Some(max_size) = self.max_size {
+            if self.size >= max_size {
+                self.rotate()?;
+            }
+        }
+        let line = match self.format {
+            Format::Csv => record.csv(),
+            Format::Jsonl => serde_json::to_string(record)?,
+        };
+        self.write_line(&line)
+    }
+}
+
+/// Appends share results to a CSV or JSONL file. The first write error disables the log,
+/// mining goes on regardless.
+pub struct ShareLog {
+    writer: Mutex<Option<Writer>>,
+}
+
+impl ShareLog {
+    /// Records the share results published on `events`.
+    /// The format is chosen by the extension, `.csv` or `.jsonl`.
+    pub fn open(path: &Path, max_size: Option<u64>, events: &EventBus) -> Result<Arc<Self>> {
+        let writer = Writer::open(path, Format::from_path(path)?, max_size)?;
+        info!("Logging share results to {}", path.display());
+        let log = Arc::new(Self {
+            writer: Mutex::new(Some(writer)),
+        });
+        let flusher = log.clone();
+        task::spawn(async move {
+            loop {
+                sleep(FLUSH_INTERVAL).await;
+                flusher.flush();
+            }
+        });
+        let recorder = log.clone();
+        let bus = events.clone();
+        let mut events = events.subscribe();
+        task::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(MinerEvent::ShareAccepted(result)) | Ok(MinerEvent::ShareRejected(result)) => {
+                        recorder.record(&ShareRecord::from(&result));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        bus.record_lag(missed);
+                        warn!("Share log missed {} share results", missed);
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+        Ok(log)
+    }
+
+    fn apply(&self, action: impl FnOnce(&mut Writer) -> Result<()>) {
+        let mut writer = self.writer.lock().unwrap();
+        if let Some(inner) = writer.as_mut() {
+            if let Err(e) = action(inner) {
+                error!("Unable to write the share log, disabling it: {:#}", e);
+                *writer = None;
+            }
+        }
+    }
+
+    pub fn record(&self, record: &ShareRecord) {
+        self.apply(|writer| writer.record(record));
+    }
+
+    pub fn flush(&self) {
+        self.apply(|writer| Ok(writer.file.flush()?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    fn record(height: u32) -> ShareRecord {
+        ShareRecord {
+            timestamp: "2026-01-02T03:04:05+00:00".to_string(),
+            pool: "pool:4040".to_string(),
+            worker: "rig".to_string(),
+            height,
+            nonce: Some("0a0b0c0d".to_string()),
+            outcome: "accepted",
+            latency_ms: Some(42),
+            difficulty: Some(1000),
+        }
+    }
+
+    fn unknown(record: ShareRecord) -> ShareRecord {
+        ShareRecord { nonce: None, outcome: "stale", latency_ms: None, difficulty: None, ..record }
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn chooses_the_format_by_extension() {
+        assert_eq!(Format::from_path(Path::new("shares.csv")).unwrap(), Format::Csv);
+        assert_eq!(Format::from_path(Path::new("logs/shares.jsonl")).unwrap(), Format::Jsonl);
+        for path in ["shares.json", "shares", "csv"] {
+            assert!(Format::from_path(Path::new(path)).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn names_the_outcomes() {
+        let codes = [Code::Success, Code::InvalidProof, Code::Stale, Code::ProxyException, Code::Other];
+        let outcomes: Vec<&str> = codes.iter().map(outcome).collect();
+        assert_eq!(outcomes, ["accepted", "invalid", "stale", "proxy-exception", "other"]);
+    }
+
+    #[test]
+    fn formats_csv_records() {
+        assert_eq!(record(7).csv(), "2026-01-02T03:04:05+00:00,pool:4040,rig,7,0a0b0c0d,accepted,42,1000");
+        assert_eq!(unknown(record(7)).csv(), "2026-01-02T03:04:05+00:00,pool:4040,rig,7,,stale,,");
+        let quoted = ShareRecord { worker: "rig \"a\",b".to_string(), ..record(7) };
+        assert_eq!(quoted.csv(), "2026-01-02T03:04:05+00:00,pool:4040,\"rig \"\"a\"\",b\",7,0a0b0c0d,accepted,42,1000");
+    }
+
+    #[test]
+    fn formats_jsonl_records() {
+        assert_eq!(
+            serde_json::to_string(&record(7)).unwrap(),
+            r#"{"timestamp":"2026-01-02T03:04:05+00:00","pool":"pool:4040","worker":"rig","height":7,"#.to_string()
+                + r#""nonce":"0a0b0c0d","outcome":"accepted","latency_ms":42,"difficulty":1000}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&unknown(record(7))).unwrap(),
+            r#"{"timestamp":"2026-01-02T03:04:05+00:00","pool":"pool:4040","worker":"rig","height":7,"#.to_string()
+                + r#""nonce":null,"outcome":"stale","latency_ms":null,"difficulty":null}"#
+        );
+    }
+
+    #[test]
+    fn writes_the_csv_header_once() {
+        let path = testing::scratch("share-log", "header").join("shares.csv");
+        let mut writer = Writer::open(&path, Format::Csv, None).unwrap();
+        writer.record(&record(1)).unwrap();
+        writer.file.flush().unwrap();
+        drop(writer);
+        let mut writer = Writer::open(&path, Format::Csv, None).unwrap();
+        writer.record(&record(2)).unwrap();
+        writer.file.flush().unwrap();
+
+        assert_eq!(lines(&path), [CSV_HEADER.to_string(), record(1).csv(), record(2).csv()]);
+        assert_eq!(writer.size, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn rotates_once_the_file_reaches_the_size() {
+        let dir = testing::scratch("share-log", "rotate");
+        let path = dir.join("shares.csv");
+        let max_size = (CSV_HEADER.len() + record(1).csv().len() + 2) as u64;
+        let mut writer = Writer::open(&path, Format::Csv, Some(max_size)).unwrap();
+        writer.record(&record(1)).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        writer.record(&record(2)).unwrap();
+        writer.file.flush().unwrap();
+
+        let rotated: Vec<PathBuf> =
+            fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).filter(|file| file != &path).collect();
+        assert_eq!(rotated.len(), 1);
+        let name = rotated[0].file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("shares.") && name.ends_with(".csv"), "{}", name);
+        assert_eq!(name.len(), "shares.20260102-030405.csv".len());
+        assert_eq!(lines(&rotated[0]), [CSV_HEADER.to_string(), record(1).csv()]);
+        assert_eq!(lines(&path), [CSV_HEADER.to_string(), record(2).csv()]);
+    }
+
+    #[test]
+    fn disables_itself_after_a_write_failure() {
+        let dir = testing::scratch("share-log", "failure");
+        let path = dir.join("shares.jsonl");
+        let log = ShareLog { writer: Mutex::new(Some(Writer::open(&path, Format::Jsonl, Some(1)).unwrap())) };
+        log.record(&record(1));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The next record rotates, which fails without the directory.
+        log.record(&record(2));
+        assert!(log.writer.lock().unwrap().is_none());
+        log.record(&record(3));
+        log.flush();
+    }
+
+    #[tokio::test]
+    async fn records_the_published_share_results() {
+        let path = testing::scratch("share-log", "events").join("shares.jsonl");
+        let events = EventBus::new();
+        let log = ShareLog::open(&path, None, &events).unwrap();
+        let result = |code| ShareResult {
+            pool: "pool:4040".to_string(),
+            worker: "rig".to_string(),
+            height: 9,
+            nonce: Some("ff".to_string()),
+            code,
+            message: None,
+            latency: Some(Duration::from_millis(15)),
+            difficulty: None,
+        };
+        events.publish(MinerEvent::ShareAccepted(result(Code::Success)));
+        events.publish(MinerEvent::ShareRejected(result(Code::Stale)));
+
+        let deadline = std::time::Instant::now() + LIMIT;
+        let records = loop {
+            log.flush();
+            let records = lines(&path);
+            if records.len() == 2 {
+                break records;
+            }
+            assert!(std::time::Instant::now() < deadline, "{:?}", records);
+            sleep(Duration::from_millis(10)).await;
+        };
+        let records: Vec<serde_json::Value> =
+            records.iter().map(|record| serde_json::from_str(record).unwrap()).collect();
+        assert_eq!(records[0]["outcome"], "accepted");
+        assert_eq!(records[1]["outcome"], "stale");
+        for record in records {
+            assert!(chrono::DateTime::parse_from_rfc3339(record["timestamp"].as_str().unwrap()).is_ok());
+            assert_eq!((record["height"].as_u64(), record["latency_ms"].as_u64()), (Some(9), Some(15)));
+            assert_eq!((&record["nonce"], &record["difficulty"]), (&serde_json::json!("ff"), &serde_json::Value::Null));
+        }
+    }
+}
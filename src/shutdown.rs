@@ -0,0 +1,93 @@
+use tracing::{error, info};
+
+async fn ctrl_c() {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Unable to listen for the shutdown signal: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Waits for Ctrl-C, SIGTERM on Unix, or the console window closing or the system shutting
+/// down on Windows.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = ctrl_c() => {}
+                _ = terminate.recv() => info!("Received SIGTERM"),
+            },
+            Err(e) => {
+                error!("Unable to listen for SIGTERM: {}", e);
+                ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(windows)]
+    match console::listen() {
+        Ok(mut events) => tokio::select! {
+            _ = ctrl_c() => {}
+            Some(event) = events.recv() => info!("Received {}", console::name(event)),
+        },
+        Err(e) => {
+            error!("Unable to listen for console events: {}", e);
+            ctrl_c().await;
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    ctrl_c().await;
+}
+
+#[cfg(windows)]
+mod console {
+    use std::sync::Mutex;
+
+    use tokio::sync::mpsc;
+    use winapi::{
+        shared::minwindef::{BOOL, DWORD, FALSE, TRUE},
+        um::{
+            consoleapi::SetConsoleCtrlHandler,
+            wincon::{CTRL_CLOSE_EVENT, CTRL_SHUTDOWN_EVENT},
+        },
+    };
+
+    static EVENTS: Mutex<Option<mpsc::UnboundedSender<DWORD>>> = Mutex::new(None);
+
+    pub fn name(event: DWORD) -> &'static str {
+        match event {
+            CTRL_CLOSE_EVENT => "CTRL_CLOSE",
+            CTRL_SHUTDOWN_EVENT => "CTRL_SHUTDOWN",
+            _ => "console event",
+        }
+    }
+
+    unsafe extern "system" fn handler(event: DWORD) -> BOOL {
+        if event != CTRL_CLOSE_EVENT && event != CTRL_SHUTDOWN_EVENT {
+            // Ctrl-C and Ctrl-Break go on to the tokio handler.
+            return FALSE;
+        }
+        let sent = match EVENTS.lock() {
+            Ok(events) => events.as_ref().map_or(false, |events| events.send(event).is_ok()),
+            Err(_) => false,
+        };
+        if !sent {
+            return FALSE;
+        }
+        // Windows terminates the process as soon as this returns. Block instead so the
+        // shutdown path can finish, the process exits from main or when Windows times out.
+        loop {
+            std::thread::park();
+        }
+    }
+
+    pub fn listen() -> std::io::Result<mpsc::UnboundedReceiver<DWORD>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *EVENTS.lock().unwrap() = Some(sender);
+        if unsafe { SetConsoleCtrlHandler(Some(handler), TRUE) } == FALSE {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(receiver)
+    }
+}
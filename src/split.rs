@@ -0,0 +1,172 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tokio::{task, time::sleep};
+use tracing::{debug, info, warn};
+
+use crate::{client::Client, prover::Prover};
+
+// Proving time given to one pool before the split is reconsidered, unless the pool goes down.
+const SLICE: Duration = Duration::from_secs(30);
+// How often the pools are checked and the proving time is accounted.
+const TICK: Duration = Duration::from_secs(1);
+// The realized split is measured over the last hour.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Share of the proving time one pool is meant to get and got over the last hour.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitShare {
+    pub server: String,
+    /// Fraction of the time, 0 to 1
+    pub target: f64,
+    /// Fraction of the time proving on this pool's work over the last hour, 0 to 1
+    pub realized: f64,
+    pub active: bool,
+}
+
+impl fmt::Display for SplitShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:.0}% of {:.0}%{}",
+            self.server,
+            self.realized * 100.0,
+            self.target * 100.0,
+            if self.active { " (active)" } else { "" }
+        )
+    }
+}
+
+/// Proving time handed out by weight over a rolling window, in ticks of equal length.
+#[derive(Debug)]
+pub struct Split {
+    targets: Vec<f64>,
+    window: Duration,
+    ticks: VecDeque<(Instant, usize)>,
+}
+
+impl Split {
+    pub fn new(weights: &[u32], window: Duration) -> Self {
+        let total = weights.iter().sum::<u32>().max(1) as f64;
+        Self {
+            targets: weights.iter().map(|weight| *weight as f64 / total).collect(),
+            window,
+            ticks: VecDeque::new(),
+        }
+    }
+
+    /// Pool to mine for next: of the `available` ones, the furthest below its target.
+    /// `None` when no pool is available.
+    pub fn next(&self, available: &[bool]) -> Option<usize> {
+        let realized = self.realized();
+        (0..self.targets.len())
+            .filter(|pool| available.get(*pool).copied().unwrap_or_default())
+            .map(|pool| (pool, self.targets[pool] - realized[pool]))
+            .fold(None, |best: Option<(usize, f64)>, (pool, deficit)| match best {
+                Some((_, best_deficit)) if best_deficit >= deficit => best,
+                _ => Some((pool, deficit)),
+            })
+            .map(|(pool, _)| pool)
+    }
+
+    /// Records a tick mined for `pool`.
+    pub fn record(&mut self, pool: usize, now: Instant) {
+        self.ticks.push_back((now, pool));
+        while let Some((at, _)) = self.ticks.front() {
+            if now.duration_since(*at) <= self.window {
+                break;
+            }
+            self.ticks.pop_front();
+        }
+    }
+
+    /// Fraction of the ticks in the window each pool got.
+    pub fn realized(&self) -> Vec<f64> {
+        let mut realized = vec![0.0; self.targets.len()];
+        if self.ticks.is_empty() {
+            return realized;
+        }
+        for (_, pool) in self.ticks.iter() {
+            realized[*pool] += 1.0;
+        }
+        let total = self.ticks.len() as f64;
+        realized.iter_mut().for_each(|share| *share /= total);
+        realized
+    }
+
+    pub fn targets(&self) -> &[f64] {
+        &self.targets
+    }
+}
+
+/// Alternates the pool whose work feeds `prover` between `pools` by their weight. All of them stay
+/// connected and authorized, a pool that is down or has no work is left out and the others share its
+/// time until it is back.
+pub fn spawn(prover: Arc<Prover>, pools: Vec<(Arc<Client>, u32)>) {
+    let (clients, weights): (Vec<Arc<Client>>, Vec<u32>) = pools.into_iter().unzip();
+    for client in clients.iter() {
+        client.set_split(true);
+        client.set_forward_work(false);
+    }
+    task::spawn(async move {
+        let mut split = Split::new(&weights, WINDOW);
+        let sender = prover.event_sender();
+        let mut active: Option<usize> = None;
+        let mut slice_started = Instant::now();
+        // Pools count as down until they first have work.
+        let mut down = vec![true; clients.len()];
+        loop {
+            let available: Vec<bool> = clients.iter().map(|client| client.ready()).collect();
+            for (pool, client) in clients.iter().enumerate() {
+                if down[pool] == available[pool] && weights[pool] > 0 {
+                    down[pool] = !available[pool];
+                    if down[pool] {
+                        warn!("{} is unavailable, the other pools get its share of the split", client.server());
+                    } else {
+                        info!("{} joined the split", client.server());
+                    }
+                }
+            }
+            let active_down = active.map_or(true, |pool| !available[pool]);
+            let next = if active_down || slice_started.elapsed() >= SLICE {
+                slice_started = Instant::now();
+                split.next(&available)
+            } else {
+                active
+            };
+            if next != active {
+                if let Some(previous) = active {
+                    clients[previous].set_forward_work(false);
+                }
+                if let Some(next) = next {
+                    debug!("Mining for {}", clients[next].server());
+                    clients[next].set_forward_work(true);
+                    clients[next].forward_latest(&sender).await;
+                }
+                active = next;
+            }
+            if let Some(active) = active {
+                split.record(active, Instant::now());
+            }
+            let realized = split.realized();
+            prover.set_split(
+                clients
+                    .iter()
+                    .enumerate()
+                    .map(|(pool, client)| SplitShare {
+                        server: client.server(),
+                        target: split.targets()[pool],
+                        realized: realized[pool],
+                        active: active == Some(pool),
+                    })
+                    .collect(),
+            );
+            sleep(TICK).await;
+        }
+    });
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::prover::ProverStats;
+
+const STATS_VERSION: u32 = 1;
+
+/// Lifetime share counters of one pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    pub valid_shares: u64,
+    pub invalid_shares: u64,
+}
+
+/// Counters accumulated over all runs of the miner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifetimeStats {
+    pub version: u32,
+    pub valid_shares: u64,
+    pub invalid_shares: u64,
+    pub total_proofs: u64,
+    pub uptime_secs: u64,
+    pub pools: BTreeMap<String, PoolStats>,
+}
+
+impl Default for LifetimeStats {
+    fn default() -> Self {
+        Self {
+            version: STATS_VERSION,
+            valid_shares: 0,
+            invalid_shares: 0,
+            total_proofs: 0,
+            uptime_secs: 0,
+            pools: BTreeMap::new(),
+        }
+    }
+}
+
+impl LifetimeStats {
+    /// Adds the counters of the current session on top of the previous runs.
+    pub fn merge(&self, session: &ProverStats, uptime: Duration, pool: &str) -> LifetimeStats {
+        let mut merged = self.clone();
+        merged.valid_shares += session.valid_shares as u64;
+        merged.invalid_shares += session.invalid_shares as u64;
+        merged.total_proofs += session.total_proofs as u64;
+        merged.uptime_secs += uptime.as_secs();
+        let pool = merged.pools.entry(pool.to_string()).or_default();
+        pool.valid_shares += session.valid_shares as u64;
+        pool.invalid_shares += session.invalid_shares as u64;
+        merged
+    }
+}
+
+/// Lifetime statistics kept on disk across restarts.
+pub struct StatsFile {
+    path: PathBuf,
+    previous: LifetimeStats,
+}
+
+impl StatsFile {
+    /// Loads the stats of previous runs. Unreadable files are moved aside and the stats start from zero.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let previous = match fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<LifetimeStats>(&bytes) {
+                Ok(stats) if stats.version == STATS_VERSION => stats,
+                Ok(stats) => {
+                    warn!("Stats file {} has unsupported version {}", path.display(), stats.version);
+                    Self::backup(&path);
+                    LifetimeStats::default()
+                }
+                Err(e) => {
+                    warn!("Stats file {} is corrupted: {}", path.display(), e);
+                    Self::backup(&path);
+                    LifetimeStats::default()
+                }
+            },
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Unable to read stats file {}: {}", path.display(), e);
+                }
+                LifetimeStats::default()
+            }
+        };
+        Self { path, previous }
+    }
+
+    /// Statistics of the previous runs.
+    pub fn previous(&self) -> &LifetimeStats {
+        &self.previous
+    }
+
+    /// Writes the stats to a temporary file first so a crash never leaves a truncated file behind.
+    pub fn save(&self, stats: &LifetimeStats) -> Result<()> {
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(stats)?)?;
+        fs::rename(&temp, &self.path)?;
+        debug!("Saved stats to {}", self.path.display());
+        Ok(())
+    }
+
+    fn backup(path: &Path) {
+        let mut backup = path.to_path_buf().into_os_string();
+        backup.push(".bak");
+        match fs::rename(path, &backup) {
+            Ok(_) => warn!("Moved the old stats file to {}", Path::new(&backup).display()),
+            Err(e) => warn!("Unable to back up the old stats file: {}", e),
+        }
+    }
+}
+
+/// Session tokens given by the pools, by pool and worker, kept next to the stats file so a pool can
+/// link the sessions of successive runs.
+#[derive(Debug)]
+pub struct SessionTokens {
+    path: PathBuf,
+    tokens: Mutex<BTreeMap<String, String>>,
+}
+
+impl SessionTokens {
+    /// Tokens of the previous runs using `stats_file`, none if they can't be read.
+    pub fn load<P: AsRef<Path>>(stats_file: P) -> Self {
+        let mut path = stats_file.as_ref().to_path_buf().into_os_string();
+        path.push(".sessions");
+        let path = PathBuf::from(path);
+        let tokens = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Session file {} is corrupted, starting new sessions: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Unable to read session file {}: {}", path.display(), e);
+                }
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path,
+            tokens: Mutex::new(tokens),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.tokens.lock().unwrap_or_else(PoisonError::into_inner).get(key).cloned()
+    }
+
+    /// Sets or removes the token of `key`, saving the file when it changed.
+    pub fn set(&self, key: &str, token: Option<&str>) -> Result<()> {
+        let mut tokens = self.tokens.lock().unwrap_or_else(PoisonError::into_inner);
+        if tokens.get(key).map(String::as_str) == token {
+            return Ok(());
+        }
+        match token {
+            Some(token) => tokens.insert(key.to_string(), token.to_string()),
+            None => tokens.remove(key),
+        };
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(&*tokens)?)?;
+        fs::rename(&temp, &self.path)?;
+        debug!("Saved session tokens to {}", self.path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    net::{ToSocketAddrs, UdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::{task, time::sleep};
+use tracing::{debug, info};
+
+use crate::{
+    client::{Client, ClientStats},
+    histogram::HistogramSnapshot,
+    prover::{Prover, ProverStats},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const TICK: Duration = Duration::from_secs(10);
+// Keeps datagrams below the usual Ethernet MTU.
+const MAX_DATAGRAM: usize = 1400;
+
+/// Counters already sent, so only the increase is reported.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sent {
+    proofs: u32,
+    accepted: u32,
+    rejected: u32,
+    stale: u32,
+    reconnects: u32,
+}
+
+impl Sent {
+    fn of(stats: &ProverStats, client: &ClientStats) -> Self {
+        Self {
+            proofs: stats.total_proofs,
+            accepted: stats.valid_shares,
+            rejected: stats.invalid_shares,
+            stale: stats.local_stale,
+            reconnects: client.reconnects,
+        }
+    }
+}
+
+fn counter(lines: &mut Vec<String>, prefix: &str, name: &str, now: u32, before: u32) {
+    if now > before {
+        lines.push(format!("{}.{}:{}|c", prefix, name, now - before));
+    }
+}
+
+/// Counter increments since `sent`, named after the Prometheus metrics.
+fn counters(prefix: &str, now: &Sent, sent: &Sent) -> Vec<String> {
+    let mut lines = Vec::new();
+    counter(&mut lines, prefix, "proofs_total", now.proofs, sent.proofs);
+    counter(&mut lines, prefix, "shares_total.accepted", now.accepted, sent.accepted);
+    counter(&mut lines, prefix, "shares_total.rejected", now.rejected, sent.rejected);
+    counter(&mut lines, prefix, "shares_total.stale", now.stale, sent.stale);
+    counter(&mut lines, prefix, "reconnects_total", now.reconnects, sent.reconnects);
+    lines
+}
+
+/// Gauges and the median submit latency of the shares answered since the last tick.
+fn gauges(prefix: &str, stats: &ProverStats, client: &ClientStats, latency: &HistogramSnapshot) -> Vec<String> {
+    let mut lines = vec![
+        format!("{}.paused:{}|g", prefix, stats.paused as u8),
+        format!("{}.connected:{}|g", prefix, client.connected as u8),
+        format!("{}.pending_submits:{}|g", prefix, client.pending_submits),
+        format!("{}.proofs_in_flight:{}|g", prefix, stats.in_flight),
+    ];
+    for (window, rate) in stats.proof_rates.iter() {
+        if let Some(rate) = rate {
+            lines.push(format!("{}.proof_rate.{}m:{}|g", prefix, window, rate));
+        }
+    }
+    if let Some(p50) = latency.percentile(0.5) {
+        lines.push(format!("{}.submit_latency:{}|ms", prefix, p50.as_millis()));
+    }
+    lines
+}
+
+/// Fire-and-forget sender, failed sends are only counted.
+struct Emitter {
+    socket: UdpSocket,
+    errors: u64,
+}
+
+impl Emitter {
+    fn send(&mut self, lines: &[String]) {
+        let mut datagram = String::new();
+        for line in lines {
+            if !datagram.is_empty() && datagram.len() + line.len() + 1 > MAX_DATAGRAM {
+                self.flush(&datagram);
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(line);
+        }
+        if !datagram.is_empty() {
+            self.flush(&datagram);
+        }
+    }
+
+    fn flush(&mut self, datagram: &str) {
+        if let Err(e) = self.socket.send(datagram.as_bytes()) {
+            self.errors += 1;
+            debug!("Unable to send statsd metrics ({} errors): {}", self.errors, e);
+        }
+    }
+}
+
+/// Sends metrics to a statsd server: counters as soon as they change, gauges every tick.
+pub fn spawn(server: &str, prefix: String, prover: Arc<Prover>, client: Arc<Client>) -> Result<()> {
+    let address = server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("unable to resolve statsd server {}", server))?;
+    let bind = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(address)?;
+    socket.set_nonblocking(true)?;
+    info!("Sending statsd metrics to {}", address);
+
+    task::spawn(async move {
+        let mut emitter = Emitter { socket, errors: 0 };
+        let mut sent = Sent::default();
+        let mut latency = client.stats().submit_latency;
+        let mut elapsed = Duration::ZERO;
+        loop {
+            sleep(CHECK_INTERVAL).await;
+            elapsed += CHECK_INTERVAL;
+            let stats = prover.stats();
+            let client_stats = client.stats();
+            let now = Sent::of(&stats, &client_stats);
+            let mut lines = counters(&prefix, &now, &sent);
+            sent = now;
+            if elapsed >= TICK {
+                elapsed = Duration::ZERO;
+                let answered = client_stats.submit_latency.delta(&latency);
+                lines.extend(gauges(&prefix, &stats, &client_stats, &answered));
+                latency = client_stats.submit_latency;
+            }
+            emitter.send(&lines);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{histogram::LatencyHistogram, testing};
+
+    fn receiver() -> (UdpSocket, Emitter) {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(receiver.local_addr().unwrap()).unwrap();
+        (receiver, Emitter { socket, errors: 0 })
+    }
+
+    fn datagram(receiver: &UdpSocket) -> String {
+        let mut buffer = [0; 2048];
+        let length = receiver.recv(&mut buffer).unwrap();
+        String::from_utf8_lossy(&buffer[..length]).to_string()
+    }
+
+    #[tokio::test]
+    async fn sends_the_snapshot_in_one_datagram() {
+        let client = testing::client("127.0.0.1:1", "rig1");
+        let prover =
+            Prover::new(testing::prover_config(16, testing::FakeBackend::new(Duration::ZERO)), client.clone())
+                .unwrap();
+        let mut stats = prover.stats();
+        stats.paused = false;
+        stats.in_flight = 2;
+        stats.total_proofs = 1000;
+        stats.valid_shares = 9;
+        stats.invalid_shares = 1;
+        stats.local_stale = 0;
+        stats.proof_rates = vec![(1, Some(8.5)), (15, None)];
+        let client = client.stats();
+        let sent = Sent {
+            proofs: 400,
+            accepted: 7,
+            ..Sent::default()
+        };
+
+        let mut lines = counters("miner", &Sent::of(&stats, &client), &sent);
+        lines.extend(gauges("miner", &stats, &client, &HistogramSnapshot::default()));
+        let (receiver, mut emitter) = receiver();
+        emitter.send(&lines);
+
+        assert_eq!(
+            datagram(&receiver),
+            "miner.proofs_total:600|c\nminer.shares_total.accepted:2|c\nminer.shares_total.rejected:1|c\n\
+             miner.paused:0|g\nminer.connected:0|g\nminer.pending_submits:0|g\nminer.proofs_in_flight:2|g\n\
+             miner.proof_rate.1m:8.5|g"
+        );
+        assert_eq!(emitter.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn reports_the_median_submit_latency() {
+        let client = testing::client("127.0.0.1:1", "rig1");
+        let prover =
+            Prover::new(testing::prover_config(16, testing::FakeBackend::new(Duration::ZERO)), client.clone())
+                .unwrap();
+        let histogram = LatencyHistogram::default();
+        for _ in 0..3 {
+            histogram.record(Duration::from_millis(40));
+        }
+        let lines = gauges("miner", &prover.stats(), &client.stats(), &histogram.snapshot());
+        let latency = lines.iter().find_map(|line| line.strip_prefix("miner.submit_latency:")).unwrap();
+        let millis: u64 = latency.strip_suffix("|ms").unwrap().parse().unwrap();
+        assert!((39..=41).contains(&millis), "{}", latency);
+    }
+
+    #[test]
+    fn splits_datagrams_below_the_mtu() {
+        let (receiver, mut emitter) = receiver();
+        let lines: Vec<String> = (0..100).map(|index| format!("miner.counter_{:03}:1|c", index)).collect();
+        emitter.send(&lines);
+
+        let mut received = Vec::new();
+        while received.len() < lines.len() {
+            let datagram = datagram(&receiver);
+            assert!(datagram.len() <= MAX_DATAGRAM);
+            received.extend(datagram.lines().map(str::to_string));
+        }
+        assert_eq!(received, lines);
+    }
+
+    #[test]
+    fn counts_failed_sends() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        // Never connected, sending has nowhere to go.
+        let mut emitter = Emitter { socket, errors: 0 };
+        emitter.send(&["miner.paused:0|g".to_string()]);
+        assert_eq!(emitter.errors, 1);
+    }
+}
@@ -0,0 +1,346 @@
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::{collections::BTreeMap, time::UNIX_EPOCH};
+
+#[cfg(feature = "metrics")]
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Body,
+    Method,
+    Response,
+    StatusCode,
+};
+use serde::Serialize;
+
+#[cfg(feature = "metrics")]
+use crate::{
+    client::Client,
+    history::History,
+    http::{self, Handler},
+    prover::Prover,
+};
+use crate::{
+    build_info::{self, BuildInfo},
+    client::{ClientStats, ConnectionInfo},
+    prover::{PauseReason, ProverStats},
+    reject::RejectBreakdown,
+    split::SplitShare,
+    telemetry::GpuTelemetry,
+    throttle::BandwidthStats,
+    units,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRate {
+    pub name: String,
+    pub state: String,
+    pub proofs: u64,
+    /// Average proofs per second since start
+    pub rate: f64,
+    /// `rate` in the configured unit, e.g. `1.23 kp/s`
+    pub rate_formatted: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Shares {
+    pub accepted: u32,
+    pub rejected: u32,
+    pub stale: u32,
+    /// Rejected shares by result code
+    pub rejected_by_code: RejectBreakdown,
+    /// Shares found but not submitted in a dry run
+    pub would_submit: u64,
+}
+
+/// Round trip time to the pool in milliseconds, absent until measured.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolRtt {
+    pub average_ms: Option<u128>,
+    pub min_ms: Option<u128>,
+    pub max_ms: Option<u128>,
+}
+
+/// Snapshot served on `GET /status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub version: &'static str,
+    pub build: BuildInfo,
+    pub uptime: u64,
+    pub pool: String,
+    pub worker: String,
+    pub connected: bool,
+    pub authorized: bool,
+    /// What the connection negotiated with the pool, absent until authorized
+    pub connection: Option<ConnectionInfo>,
+    pub pool_rtt: PoolRtt,
+    /// Bytes per second to and from the pool against the caps
+    pub bandwidth: BandwidthStats,
+    /// What the pool last told about the account, e.g. `balance`, only from pools sending it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_info: Option<BTreeMap<String, String>>,
+    pub paused: bool,
+    /// Why proving is paused, e.g. `schedule`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pause_reasons: Vec<&'static str>,
+    /// Shares are not submitted (--dry-run)
+    pub dry_run: bool,
+    /// Proofs per second by window, e.g. `1m`
+    pub hashrate: BTreeMap<String, Option<f64>>,
+    /// `hashrate` in the configured unit, `---` without enough data
+    pub hashrate_formatted: BTreeMap<String, String>,
+    pub devices: Vec<DeviceRate>,
+    pub shares: Shares,
+    /// Unix timestamp of the last accepted share
+    pub last_share: Option<u64>,
+    pub height: u32,
+    /// Whether the miner is connected, authorized and getting shares accepted
+    pub healthy: bool,
+    /// Breached alert thresholds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<String>,
+    /// GPU sensor readings, only with NVML support
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gpus: Vec<GpuTelemetry>,
+    /// Target and realized share of the proving time per pool, only when split between pools
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub split: Vec<SplitShare>,
+    /// Proof rate over the last hour, oldest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sparkline: Vec<Option<f64>>,
+}
+
+impl Status {
+    pub fn new(stats: &ProverStats, client: &ClientStats, pool: &str, worker: &str) -> Self {
+        let uptime = stats.uptime.as_secs_f64();
+        Self {
+            version: build_info::VERSION,
+            build: BuildInfo::current(),
+            uptime: stats.uptime.as_secs(),
+            pool: pool.to_string(),
+            worker: worker.to_string(),
+            connected: client.connected,
+            authorized: client.authorized,
+            connection: client.connection.clone(),
+            pool_rtt: PoolRtt {
+                average_ms: client.rtt.average.map(|rtt| rtt.as_millis()),
+                min_ms: client.rtt.min.map(|rtt| rtt.as_millis()),
+                max_ms: client.rtt.max.map(|rtt| rtt.as_millis()),
+            },
+            bandwidth: client.bandwidth,
+            pool_info: client.pool_info.clone(),
+            paused: stats.paused,
+            pause_reasons: stats.pause_reasons.iter().map(PauseReason::name).collect(),
+            dry_run: client.dry_run,
+            hashrate: stats
+                .proof_rates
+                .iter()
+                .map(|(window, rate)| (format!("{}m", window), *rate))
+                .collect(),
+            hashrate_formatted: stats
+                .proof_rates
+                .iter()
+                .map(|(window, rate)| (format!("{}m", window), units::format_optional_rate(*rate)))
+                .collect(),
+            devices: stats
+                .devices
+                .iter()
+                .zip(stats.worker_latency.iter())
+                .map(|(device, latency)| {
+                    let rate = if uptime > 0.0 { latency.count as f64 / uptime } else { 0.0 };
+                    DeviceRate {
+                        name: device.name.clone(),
+                        state: device.state.to_string(),
+                        proofs: latency.count,
+                        rate,
+                        rate_formatted: units::format_rate(rate),
+                    }
+                })
+                .collect(),
+            shares: Shares {
+                accepted: stats.valid_shares,
+                rejected: stats.invalid_shares,
+                rejected_by_code: stats.rejections,
+                stale: stats.local_stale,
+                would_submit: client.would_submit,
+            },
+            last_share: stats
+                .last_share
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_secs()),
+            height: stats.current_block,
+            healthy: healthy(stats, client),
+            alerts: stats.threshold_breaches.clone(),
+            gpus: stats.gpu_telemetry.clone(),
+            split: stats.split.clone(),
+            sparkline: Vec::new(),
+        }
+    }
+}
+
+/// Healthy means connected and authorized with no alert about missing shares or breached thresholds.
+pub fn healthy(stats: &ProverStats, client: &ClientStats) -> bool {
+    client.connected && client.authorized && stats.no_share_alert.is_none() && stats.threshold_breaches.is_empty()
+}
+
+#[cfg(feature = "metrics")]
+pub fn json(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Serves `GET /status` and `GET /health`.
+#[cfg(feature = "metrics")]
+pub fn handler(prover: Arc<Prover>, client: Arc<Client>, history: Arc<History>) -> Handler {
+    Box::new(move |request| {
+        if request.method() != Method::GET {
+            return None;
+        }
+        match request.uri().path() {
+            "/status" => {
+                let mut status = Status::new(&prover.stats(), &client.stats(), &client.server(), &client.worker());
+                status.sparkline = history.sparkline();
+                let body = serde_json::to_string(&status).unwrap_or_default();
+                Some(http::ready(json(StatusCode::OK, body)))
+            }
+            "/health" => {
+                let stats = prover.stats();
+                let client = client.stats();
+                let (code, body) = if healthy(&stats, &client) {
+                    (StatusCode::OK, r#"{"healthy":true}"#)
+                } else {
+                    (StatusCode::SERVICE_UNAVAILABLE, r#"{"healthy":false}"#)
+                };
+                Some(http::ready(json(code, body.to_string())))
+            }
+            _ => None,
+        }
+    })
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use hyper::Request;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        client,
+        message::Code,
+        testing::{self, FakeBackend, MockPool, ALL_SHARES},
+    };
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    async fn get(handler: &Handler, path: &str) -> (StatusCode, Value) {
+        let request = Request::get(path).body(Bytes::new()).unwrap();
+        let response = handler(&request).expect("path not served").await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn until(limit: Duration, mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = std::time::Instant::now() + limit;
+        while !condition() {
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        true
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serves_the_status_and_the_health_until_the_pool_stops() {
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        let client = pool.client("status");
+        let prover =
+            Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+                .unwrap();
+        prover.start().await.unwrap();
+        client::start(prover.event_sender(), client.clone());
+        let handler = handler(prover.clone(), client.clone(), Arc::new(History::new(Duration::from_secs(3600))));
+        assert!(until(LIMIT, || prover.stats().valid_shares >= 3).await);
+
+        let (code, status) = get(&handler, "/status").await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(status["worker"], "status");
+        assert_eq!(status["connected"], true);
+        assert_eq!(status["height"], 2);
+        assert!(status["shares"]["accepted"].as_u64().unwrap() >= 3);
+        assert!(status["last_share"].is_u64());
+        assert!(status["hashrate"].is_object());
+        assert_eq!(status["healthy"], true);
+        assert_eq!(get(&handler, "/health").await, (StatusCode::OK, serde_json::json!({ "healthy": true })));
+
+        drop(pool);
+        assert!(until(LIMIT, || !client.stats().connected).await);
+        let unhealthy = (StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "healthy": false }));
+        assert_eq!(get(&handler, "/health").await, unhealthy);
+        assert_eq!(get(&handler, "/status").await.1["healthy"], false);
+        assert!(handler(&Request::get("/metrics").body(Bytes::new()).unwrap()).is_none());
+        prover.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shows_the_same_rejections_everywhere() {
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        let client = pool.client("rejects");
+        let mut config = testing::prover_config(16, FakeBackend::new(Duration::from_millis(5)));
+        // Rejecting every share on purpose mustn't pause proving.
+        config.reject_threshold = 100.0;
+        let prover = Prover::new(config, client.clone()).unwrap();
+        prover.start().await.unwrap();
+        client::start(prover.event_sender(), client.clone());
+
+        let counts: [(Code, fn(&RejectBreakdown) -> u32); 4] = [
+            (Code::Stale, |rejections| rejections.stale),
+            (Code::InvalidProof, |rejections| rejections.invalid),
+            (Code::ProxyException, |rejections| rejections.proxy),
+            (Code::Other, |rejections| rejections.other),
+        ];
+        for (code, count) in counts {
+            pool.set_result(code, None);
+            assert!(until(LIMIT, || count(&prover.stats().rejections) >= 2).await);
+        }
+        prover.pause(PauseReason::Manual).await;
+        // Proxy exceptions aren't shares the pool judged, the other rejects count as invalid shares.
+        assert!(
+            until(LIMIT, || {
+                let stats = prover.stats();
+                stats.invalid_shares == stats.rejections.total() - stats.rejections.proxy
+            })
+            .await
+        );
+
+        let stats = prover.stats();
+        let rejections = stats.rejections;
+        assert_eq!(rejections, client.stats().rejections);
+        let line = format!("{}", rejections);
+        let expected = format!(
+            "{} stale, {} invalid, {} proxy, {} other",
+            rejections.stale, rejections.invalid, rejections.proxy, rejections.other
+        );
+        assert_eq!(line, expected);
+
+        let status = serde_json::to_value(Status::new(&stats, &client.stats(), "pool", "rejects")).unwrap();
+        assert_eq!(status["shares"]["rejected_by_code"], serde_json::to_value(rejections).unwrap());
+        assert_eq!(status["shares"]["rejected"], stats.invalid_shares);
+
+        let metrics = crate::metrics::render(&stats, &client.stats());
+        for (code, count) in rejections.by_label() {
+            let sample = format!("aleoxminer_shares_rejected_total{{code=\"{}\"}} {}\n", code, count);
+            assert!(metrics.contains(&sample), "{} missing", sample.trim());
+        }
+        prover.stop().await;
+    }
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use crate::{client::Client, prover::Prover};
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{
+        env,
+        os::unix::net::UnixDatagram,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use tokio::{task, time::sleep};
+    use tracing::{debug, info};
+
+    use crate::{client::Client, prover::Prover, status, units};
+
+    const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+    const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Writes sd_notify messages to the socket systemd passed in `NOTIFY_SOCKET`.
+    pub struct SdNotify {
+        socket: UnixDatagram,
+    }
+
+    impl SdNotify {
+        pub fn from_env() -> Option<Self> {
+            let path = env::var_os("NOTIFY_SOCKET")?;
+            let socket = UnixDatagram::unbound().ok()?;
+            let path = path.to_string_lossy();
+            let connected = match path.strip_prefix('@') {
+                // Abstract namespace socket
+                Some(name) => {
+                    use std::os::linux::net::SocketAddrExt;
+                    let address = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()).ok()?;
+                    socket.connect_addr(&address)
+                }
+                None => socket.connect(path.as_ref()),
+            };
+            if let Err(e) = connected {
+                debug!("Unable to connect to NOTIFY_SOCKET {}: {}", path, e);
+                return None;
+            }
+            Some(Self { socket })
+        }
+
+        pub fn send(&self, message: &str) {
+            if let Err(e) = self.socket.send(message.as_bytes()) {
+                debug!("Unable to notify systemd: {}", e);
+            }
+        }
+    }
+
+    /// Watchdog interval requested by systemd, if it is meant for this process.
+    fn watchdog_interval() -> Option<Duration> {
+        if let Ok(pid) = env::var("WATCHDOG_PID") {
+            if pid.parse::<u32>().ok()? != std::process::id() {
+                return None;
+            }
+        }
+        let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        if usec == 0 {
+            return None;
+        }
+        Some(Duration::from_micros(usec))
+    }
+
+    pub fn spawn(prover: Arc<Prover>, client: Arc<Client>) {
+        let notify = match SdNotify::from_env() {
+            Some(notify) => notify,
+            None => return,
+        };
+        // Pet the watchdog twice per interval, as systemd recommends.
+        let watchdog = watchdog_interval().map(|interval| interval / 2);
+        match watchdog {
+            Some(interval) => info!("Notifying systemd, watchdog every {}ms", interval.as_millis()),
+            None => info!("Notifying systemd"),
+        }
+        task::spawn(async move {
+            let mut ready = false;
+            let mut last_status: Option<Instant> = None;
+            let mut last_watchdog: Option<Instant> = None;
+            loop {
+                let stats = prover.stats();
+                let client_stats = client.stats();
+                if !ready && client_stats.authorized {
+                    ready = true;
+                    notify.send("READY=1");
+                }
+                if last_status.map_or(true, |last| last.elapsed() >= STATUS_INTERVAL) {
+                    last_status = Some(Instant::now());
+                    let rate = stats
+                        .proof_rates
+                        .first()
+                        .and_then(|(_, rate)| *rate);
+                    let rate = units::format_optional_rate(rate);
+                    notify.send(&format!(
+                        "STATUS={}, {} / {} shares accepted, {}",
+                        rate,
+                        stats.valid_shares,
+                        stats.valid_shares + stats.invalid_shares,
+                        if client_stats.connected { "connected" } else { "disconnected" }
+                    ));
+                }
+                // A wedged miner stops petting the watchdog, so systemd restarts it.
+                if let Some(interval) = watchdog {
+                    let due = last_watchdog.map_or(true, |last| last.elapsed() >= interval);
+                    if due && status::healthy(&stats, &client_stats) {
+                        last_watchdog = Some(Instant::now());
+                        notify.send("WATCHDOG=1");
+                    }
+                }
+                sleep(CHECK_INTERVAL.min(watchdog.unwrap_or(CHECK_INTERVAL))).await;
+            }
+        });
+    }
+
+    pub fn stopping() {
+        if let Some(notify) = SdNotify::from_env() {
+            notify.send("STOPPING=1");
+        }
+    }
+}
+
+/// Sends readiness, status and watchdog notifications when started by systemd with `Type=notify`.
+pub fn spawn(prover: Arc<Prover>, client: Arc<Client>) {
+    #[cfg(target_os = "linux")]
+    imp::spawn(prover, client);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (prover, client);
+        if std::env::var_os("NOTIFY_SOCKET").is_some() {
+            tracing::debug!("sd_notify is only supported on Linux");
+        }
+    }
+}
+
+/// Tells systemd the miner is shutting down.
+pub fn stopping() {
+    #[cfg(target_os = "linux")]
+    imp::stopping();
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{env, fs, os::unix::net::UnixDatagram, time::Duration};
+
+    use super::*;
+    use crate::{
+        client,
+        testing::{self, FakeBackend, MockPool, ALL_SHARES},
+    };
+
+    const LIMIT: Duration = Duration::from_secs(10);
+
+    // Every datagram sent to `socket` since the last call.
+    fn received(socket: &UnixDatagram) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut buffer = [0; 1024];
+        while let Ok(length) = socket.recv(&mut buffer) {
+            messages.push(String::from_utf8_lossy(&buffer[..length]).into_owned());
+        }
+        messages
+    }
+
+    // Collects datagrams until `condition` holds on all of them so far.
+    async fn wait_for(socket: &UnixDatagram, messages: &mut Vec<String>, condition: impl Fn(&[String]) -> bool) {
+        let deadline = std::time::Instant::now() + LIMIT;
+        while !condition(messages) {
+            assert!(std::time::Instant::now() < deadline, "unexpected notifications {:?}", messages);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            messages.extend(received(socket));
+        }
+    }
+
+    // The environment is shared by the whole process, so the sequence is checked in one test.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn notifies_ready_status_watchdog_and_stopping() {
+        let dir = env::temp_dir().join(format!("aleoxminer-systemd-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+        env::set_var("WATCHDOG_USEC", "200000");
+        env::set_var("WATCHDOG_PID", std::process::id().to_string());
+
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        let client = pool.client("systemd");
+        let prover =
+            Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+                .unwrap();
+        prover.start().await.unwrap();
+        spawn(prover.clone(), client.clone());
+        let mut messages = Vec::new();
+        wait_for(&socket, &mut messages, |messages| !messages.is_empty()).await;
+        assert_eq!(messages[0], "STATUS=---, 0 / 0 shares accepted, disconnected");
+
+        client::start(prover.event_sender(), client.clone());
+        wait_for(&socket, &mut messages, |messages| {
+            messages.iter().filter(|message| *message == "WATCHDOG=1").count() >= 3
+        })
+        .await;
+        let ready = messages.iter().position(|message| message == "READY=1").expect("never ready");
+        let watchdog = messages.iter().position(|message| message == "WATCHDOG=1").unwrap();
+        assert!(ready < watchdog, "{:?}", messages);
+        assert_eq!(messages.iter().filter(|message| *message == "READY=1").count(), 1);
+        assert!(messages.iter().all(|message| message == "READY=1"
+            || message == "WATCHDOG=1"
+            || message.starts_with("STATUS=")));
+
+        // Once the pool is gone the miner is unhealthy and lets the watchdog expire.
+        drop(pool);
+        let deadline = std::time::Instant::now() + LIMIT;
+        while client.stats().connected {
+            assert!(std::time::Instant::now() < deadline, "still connected");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        received(&socket);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!received(&socket).contains(&"WATCHDOG=1".to_string()));
+
+        stopping();
+        let mut messages = Vec::new();
+        wait_for(&socket, &mut messages, |messages| messages.contains(&"STOPPING=1".to_string())).await;
+
+        env::remove_var("NOTIFY_SOCKET");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
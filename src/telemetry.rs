@@ -0,0 +1,210 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+/// Sensor readings of one GPU, fields the driver doesn't report are left out.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GpuTelemetry {
+    pub index: i16,
+    /// Degrees Celsius
+    pub temperature: Option<u32>,
+    /// Watts
+    pub power: Option<f64>,
+    /// Percent of the maximum fan speed
+    pub fan: Option<u32>,
+    /// MHz
+    pub sm_clock: Option<u32>,
+    /// MHz
+    pub memory_clock: Option<u32>,
+}
+
+impl Display for GpuTelemetry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GPU {}:", self.index)?;
+        if let Some(temperature) = self.temperature {
+            write!(f, " {}°C", temperature)?;
+        }
+        if let Some(power) = self.power {
+            write!(f, " {:.0}W", power)?;
+        }
+        if let Some(fan) = self.fan {
+            write!(f, " fan {}%", fan)?;
+        }
+        if let Some(sm_clock) = self.sm_clock {
+            write!(f, " {}MHz", sm_clock)?;
+        }
+        if let Some(memory_clock) = self.memory_clock {
+            write!(f, " mem {}MHz", memory_clock)?;
+        }
+        Ok(())
+    }
+}
+
+/// Source of GPU sensor readings.
+pub trait TelemetrySampler: Send + Sync {
+    fn sample(&self, device: i16) -> Option<GpuTelemetry>;
+}
+
+#[cfg(feature = "nvml")]
+pub use self::nvml::NvmlSampler;
+
+#[cfg(feature = "nvml")]
+mod nvml {
+    use nvml_wrapper::{
+        enum_wrappers::device::{Clock, TemperatureSensor},
+        Nvml,
+    };
+    use tracing::warn;
+
+    use super::{GpuTelemetry, TelemetrySampler};
+
+    pub struct NvmlSampler {
+        nvml: Nvml,
+    }
+
+    impl NvmlSampler {
+        /// Returns `None` with a warning if the NVIDIA driver isn't available.
+        pub fn init() -> Option<Self> {
+            match Nvml::init() {
+                Ok(nvml) => Some(Self { nvml }),
+                Err(e) => {
+                    warn!("Unable to initialize NVML, GPU telemetry is not available: {}", e);
+                    None
+                }
+            }
+        }
+
+        /// (index, name) of every GPU the driver reports.
+        pub fn devices(&self) -> Vec<(i16, String)> {
+            let count = self.nvml.device_count().unwrap_or_default();
+            (0..count)
+                .filter_map(|index| {
+                    let name = self.nvml.device_by_index(index).ok()?.name().ok()?;
+                    Some((i16::try_from(index).ok()?, name))
+                })
+                .collect()
+        }
+    }
+
+    impl TelemetrySampler for NvmlSampler {
+        fn sample(&self, device: i16) -> Option<GpuTelemetry> {
+            let handle = self.nvml.device_by_index(u32::try_from(device).ok()?).ok()?;
+            Some(GpuTelemetry {
+                index: device,
+                temperature: handle.temperature(TemperatureSensor::Gpu).ok(),
+                // Reported in milliwatts
+                power: handle.power_usage().ok().map(|power| power as f64 / 1000.0),
+                fan: handle.fan_speed(0).ok(),
+                sm_clock: handle.clock_info(Clock::SM).ok(),
+                memory_clock: handle.clock_info(Clock::Memory).ok(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        prover::Prover,
+        status::Status,
+        testing::{self, FakeBackend},
+    };
+
+    // Readings per device, devices without one have no sensors.
+    #[derive(Default)]
+    struct MockSampler {
+        readings: BTreeMap<i16, GpuTelemetry>,
+        sampled: Mutex<Vec<i16>>,
+    }
+
+    impl TelemetrySampler for MockSampler {
+        fn sample(&self, device: i16) -> Option<GpuTelemetry> {
+            self.sampled.lock().unwrap().push(device);
+            self.readings.get(&device).cloned()
+        }
+    }
+
+    fn reading(index: i16) -> GpuTelemetry {
+        GpuTelemetry {
+            index,
+            temperature: Some(70),
+            power: Some(215.4),
+            fan: Some(55),
+            sm_clock: Some(1800),
+            memory_clock: Some(9500),
+        }
+    }
+
+    fn sampler(devices: &[i16]) -> Arc<MockSampler> {
+        Arc::new(MockSampler {
+            readings: devices.iter().map(|device| (*device, reading(*device))).collect(),
+            ..MockSampler::default()
+        })
+    }
+
+    async fn prover(cuda: Option<Vec<i16>>, sampler: Arc<MockSampler>) -> Arc<Prover> {
+        let mut config = testing::prover_config(1, FakeBackend::new(Duration::ZERO));
+        config.cuda = cuda;
+        config.telemetry = Some(sampler);
+        let prover = Prover::new(config, testing::client("127.0.0.1:1", "telemetry")).unwrap();
+        prover.start().await.unwrap();
+        prover
+    }
+
+    #[test]
+    fn formats_the_readings() {
+        assert_eq!(reading(1).to_string(), "GPU 1: 70°C 215W fan 55% 1800MHz mem 9500MHz");
+        let partial = GpuTelemetry { index: 2, temperature: Some(64), ..GpuTelemetry::default() };
+        assert_eq!(partial.to_string(), "GPU 2: 64°C");
+        assert_eq!(GpuTelemetry::default().to_string(), "GPU 0:");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn samples_the_gpus_on_the_stats_interval() {
+        let sampler = sampler(&[0, 2]);
+        let prover = prover(Some(vec![0, 1, 2]), sampler.clone()).await;
+        assert!(prover.stats().gpu_telemetry.is_empty());
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert_eq!(*sampler.sampled.lock().unwrap(), [0, 1, 2]);
+        let stats = prover.stats();
+        let indexes: Vec<i16> = stats.gpu_telemetry.iter().map(|gpu| gpu.index).collect();
+        assert_eq!(indexes, [0, 2]);
+
+        let client = testing::client("127.0.0.1:1", "telemetry");
+        let status = serde_json::to_value(Status::new(&stats, &client.stats(), "pool", "telemetry")).unwrap();
+        assert_eq!(status["gpus"][1], serde_json::to_value(reading(2)).unwrap());
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = crate::metrics::render(&stats, &client.stats());
+            assert!(metrics.contains("aleoxminer_gpu_temperature_celsius{gpu=\"2\"} 70\n"));
+            assert!(metrics.contains("aleoxminer_gpu_clock_mhz{gpu=\"0\",clock=\"memory\"} 9500\n"));
+            assert!(!metrics.contains("gpu=\"1\""));
+        }
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(sampler.sampled.lock().unwrap().len(), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn leaves_the_readings_out_without_gpus() {
+        let sampler = sampler(&[0]);
+        let prover = prover(None, sampler.clone()).await;
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert!(sampler.sampled.lock().unwrap().is_empty());
+        assert!(prover.stats().gpu_telemetry.is_empty());
+        #[cfg(feature = "metrics")]
+        {
+            let client = testing::client("127.0.0.1:1", "telemetry");
+            assert!(!crate::metrics::render(&prover.stats(), &client.stats()).contains("aleoxminer_gpu_"));
+        }
+    }
+}
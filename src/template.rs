@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use snarkvm::{
+    dpc::{testnet2::Testnet2, BlockTemplate},
+    utilities::ToBytes,
+};
+use thiserror::Error;
+
+// The network target of consecutive blocks changes by less than this factor, a larger change over a
+// few blocks means the template was built for another height.
+const MAX_TARGET_CHANGE_PER_BLOCK: f64 = 2.0;
+// Blocks after which the target of the previous template says nothing about the next one.
+const TARGET_HISTORY: u32 = 32;
+
+/// Why a template from the pool is not worth proving.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("difficulty target is zero")]
+    ZeroTarget,
+    #[error("difficulty target {target} is implausible {blocks} blocks after {previous} at block {previous_height}")]
+    ImplausibleTarget {
+        target: u64,
+        previous: u64,
+        previous_height: u32,
+        blocks: u32,
+    },
+    #[error("transactions root is empty")]
+    EmptyTransactionsRoot,
+    #[error("timestamp {timestamp} is {skew}s off the local clock, more than the {max_skew}s allowed")]
+    Timestamp { timestamp: i64, skew: i64, max_skew: u64 },
+}
+
+/// Checks a template before it reaches the prover. `previous` is the (height, network target) of the
+/// previous template from the pool, `now` the local Unix time. A height going back is checked against
+/// the reorg tolerance when the job arrives, see `Client::set_reorg_tolerance`.
+pub fn validate(
+    template: &BlockTemplate<Testnet2>,
+    previous: Option<(u32, u64)>,
+    now: i64,
+    max_skew: Duration,
+) -> Result<(), TemplateError> {
+    let target = template.difficulty_target();
+    if target == 0 {
+        return Err(TemplateError::ZeroTarget);
+    }
+    if let Some((previous_height, previous)) = previous {
+        let blocks = template.block_height().abs_diff(previous_height);
+        if blocks > 0 && blocks <= TARGET_HISTORY && previous > 0 {
+            let change = target as f64 / previous as f64;
+            let max_change = MAX_TARGET_CHANGE_PER_BLOCK.powi(blocks as i32);
+            if change > max_change || change < 1.0 / max_change {
+                return Err(TemplateError::ImplausibleTarget {
+                    target,
+                    previous,
+                    previous_height,
+                    blocks,
+                });
+            }
+        }
+    }
+    let root = template
+        .transactions()
+        .transactions_root()
+        .to_bytes_le()
+        .unwrap_or_default();
+    if empty_root(&root) {
+        return Err(TemplateError::EmptyTransactionsRoot);
+    }
+    let timestamp = template.block_timestamp();
+    let skew = timestamp.saturating_sub(now).saturating_abs();
+    if skew as u64 > max_skew.as_secs() {
+        return Err(TemplateError::Timestamp {
+            timestamp,
+            skew,
+            max_skew: max_skew.as_secs(),
+        });
+    }
+    Ok(())
+}
+
+// Whether `root` is the zero root `transactions_root` falls back to when the tree can't be built, or
+// no root at all.
+fn empty_root(root: &[u8]) -> bool {
+    root.iter().all(|byte| *byte == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The genesis transactions always have a root, the zero one can only be checked on its own.
+    #[test]
+    fn takes_a_zero_root_for_an_empty_one() {
+        assert!(empty_root(&[0; 32]));
+        assert!(empty_root(&[]));
+        let mut root = [0; 32];
+        root[31] = 1;
+        assert!(!empty_root(&root));
+    }
+}
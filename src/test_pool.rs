@@ -0,0 +1,226 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use ansi_term::Colour::{Green, Red};
+use futures_util::sink::SinkExt;
+use tokio::{
+    net::{lookup_host, TcpStream},
+    time::timeout,
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+use crate::{
+    config, estimate,
+    message::{ProtocolLimits, ProverCodec, ProverMessage},
+};
+
+/// Connection stage a pool test failed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Dns,
+    Tcp,
+    Authorize,
+    Notify,
+}
+
+impl Stage {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Dns => "DNS",
+            Self::Tcp => "TCP",
+            Self::Authorize => "Authorization",
+            Self::Notify => "Work",
+        }
+    }
+
+    /// What usually causes a failure at this stage.
+    fn hint(&self) -> &'static str {
+        match self {
+            Self::Dns => "check the host name and the DNS settings of this machine",
+            Self::Tcp => "check the port, that the pool is up and that no firewall blocks the connection",
+            Self::Authorize => "check the address or account, the worker name and the password",
+            Self::Notify => "the pool accepted the connection but sent no work, it may be syncing or overloaded",
+        }
+    }
+}
+
+fn passed(stage: Stage, details: String) {
+    println!("{} {}: {}", Green.paint("[ok]"), stage.name(), details);
+}
+
+fn failed(stage: Stage, details: String) -> Stage {
+    println!("{} {}: {}", Red.paint("[failed]"), stage.name(), details);
+    println!("         {}", stage.hint());
+    stage
+}
+
+/// Connects, authorizes and waits for the first job, printing every stage. Each stage gets
+/// `limit` to complete. Returns the stage that failed, if any.
+pub async fn run(
+    url: &str,
+    authorization: ProverMessage,
+    limits: ProtocolLimits,
+    limit: Duration,
+) -> Result<(), Stage> {
+    let address = config::pool_address(url).map_err(|e| failed(Stage::Dns, format!("{}: {}", url, e)))?;
+
+    let started = Instant::now();
+    let addresses: Vec<SocketAddr> = match timeout(limit, lookup_host(address)).await {
+        Ok(Ok(addresses)) => addresses.collect(),
+        Ok(Err(e)) => return Err(failed(Stage::Dns, format!("unable to resolve {}: {}", address, e))),
+        Err(_) => return Err(failed(Stage::Dns, format!("resolving {} timed out", address))),
+    };
+    if addresses.is_empty() {
+        return Err(failed(Stage::Dns, format!("{} has no addresses", address)));
+    }
+    passed(
+        Stage::Dns,
+        format!("{} resolved to {} in {} ms", address, addresses[0].ip(), started.elapsed().as_millis()),
+    );
+
+    let mut socket = None;
+    let mut errors = Vec::new();
+    for address in addresses.iter() {
+        let started = Instant::now();
+        match timeout(limit, TcpStream::connect(address)).await {
+            Ok(Ok(connected)) => {
+                passed(
+                    Stage::Tcp,
+                    format!("connected to {} in {} ms", address, started.elapsed().as_millis()),
+                );
+                socket = Some(connected);
+                break;
+            }
+            Ok(Err(e)) => errors.push(format!("{}: {}", address, e)),
+            Err(_) => errors.push(format!("{}: timed out", address)),
+        }
+    }
+    let socket = socket.ok_or_else(|| failed(Stage::Tcp, errors.join(", ")))?;
+    let mut framed = Framed::new(socket, ProverCodec::new(limits));
+
+    let started = Instant::now();
+    if let Err(e) = framed.send(authorization).await {
+        return Err(failed(Stage::Authorize, format!("unable to send the authorization: {}", e)));
+    }
+    let mut notify = None;
+    let deadline = started + limit;
+    loop {
+        let message = match timeout(deadline.saturating_duration_since(Instant::now()), framed.next()).await {
+            Ok(Some(Ok(message))) => message,
+            Ok(Some(Err(e))) => return Err(failed(Stage::Authorize, format!("invalid message from the pool: {}", e))),
+            Ok(None) => return Err(failed(Stage::Authorize, "the pool closed the connection".to_string())),
+            Err(_) => return Err(failed(Stage::Authorize, "no answer to the authorization".to_string())),
+        };
+        match message {
+            ProverMessage::AuthorizeResult(true, _) => {
+                passed(
+                    Stage::Authorize,
+                    format!(
+                        "accepted in {} ms, protocol version {}",
+                        started.elapsed().as_millis(),
+                        ProverMessage::version()
+                    ),
+                );
+                break;
+            }
+            ProverMessage::AuthorizeResult(false, message) => {
+                let reason = message.unwrap_or_else(|| "no reason given".to_string());
+                return Err(failed(Stage::Authorize, format!("rejected: {}", reason)));
+            }
+            // Some pools send work before the authorization result.
+            ProverMessage::Notify(template, target, _, _) => notify = Some((template.block_height(), target)),
+            _ => {}
+        }
+    }
+
+    let started = Instant::now();
+    let deadline = started + limit;
+    while notify.is_none() {
+        match timeout(deadline.saturating_duration_since(Instant::now()), framed.next()).await {
+            Ok(Some(Ok(ProverMessage::Notify(template, target, _, _)))) => {
+                notify = Some((template.block_height(), target))
+            }
+            Ok(Some(Ok(_))) => {}
+            Ok(Some(Err(e))) => return Err(failed(Stage::Notify, format!("invalid message from the pool: {}", e))),
+            Ok(None) => return Err(failed(Stage::Notify, "the pool closed the connection".to_string())),
+            Err(_) => return Err(failed(Stage::Notify, format!("no job within {}s", limit.as_secs()))),
+        }
+    }
+    if let Some((height, target)) = notify {
+        passed(
+            Stage::Notify,
+            format!(
+                "job for block {} after {} ms, share difficulty {}",
+                height,
+                started.elapsed().as_millis(),
+                estimate::format_difficulty(estimate::difficulty(target))
+            ),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client,
+        message::Secret,
+        testing::{self, MockPool, ALL_SHARES},
+    };
+
+    const LIMIT: Duration = Duration::from_secs(5);
+
+    fn authorization() -> ProverMessage {
+        client::authorization(None, Some(&testing::address()), "test-pool", Secret::default())
+    }
+
+    #[tokio::test]
+    async fn passes_every_stage_against_a_working_pool() {
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        assert_eq!(run(&pool.address(), authorization(), ProtocolLimits::default(), LIMIT).await, Ok(()));
+        let url = format!("tcp://{}", pool.address());
+        assert_eq!(run(&url, authorization(), ProtocolLimits::default(), LIMIT).await, Ok(()));
+
+        let received = pool.received();
+        assert_eq!(received.authorizations.len(), 2);
+        assert_eq!(received.authorizations[0].0, testing::address().to_string());
+        assert_eq!(received.authorizations[0].1, "test-pool");
+    }
+
+    #[tokio::test]
+    async fn fails_at_the_authorization_when_rejected() {
+        let pool = MockPool::start().await.unwrap();
+        pool.notify(testing::template(2), ALL_SHARES);
+        pool.set_authorize(false);
+        let result = run(&pool.address(), authorization(), ProtocolLimits::default(), LIMIT).await;
+        assert_eq!(result, Err(Stage::Authorize));
+    }
+
+    #[tokio::test]
+    async fn fails_at_the_work_without_a_job() {
+        let pool = MockPool::start().await.unwrap();
+        let limit = Duration::from_millis(200);
+        assert_eq!(run(&pool.address(), authorization(), ProtocolLimits::default(), limit).await, Err(Stage::Notify));
+        assert_eq!(pool.received().authorizations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_at_the_connection_when_nothing_listens() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let result = run(&address.to_string(), authorization(), ProtocolLimits::default(), LIMIT).await;
+        assert_eq!(result, Err(Stage::Tcp));
+    }
+
+    #[tokio::test]
+    async fn fails_at_the_lookup_on_invalid_urls() {
+        for url in ["http://127.0.0.1:4040", "127.0.0.1", "127.0.0.1:port"] {
+            let result = run(url, authorization(), ProtocolLimits::default(), LIMIT).await;
+            assert_eq!(result, Err(Stage::Dns), "{}", url);
+        }
+    }
+}
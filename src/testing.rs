@@ -0,0 +1,835 @@
+// Test doubles shared by the unit tests, the integration tests and the benches: fixtures built from the
+// genesis block, a proving backend that doesn't prove, GPU telemetry that panics, a scriptable pool, a
+// replay of recorded pool traffic and an HTTP server recording requests. With the `chaos` feature, the
+// stream injecting network faults as well. Nothing here needs a network, a GPU or the proving parameters.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use futures_util::sink::SinkExt;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use snarkvm::{
+    dpc::{testnet2::Testnet2, Account, Address, BlockHeader, BlockTemplate},
+    traits::Network,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Decoder, Framed};
+
+use crate::{
+    backend::ProvingBackend,
+    client::Client,
+    cpu::CpuPath,
+    estimate::EarningsConfig,
+    events::EventBus,
+    message::{Code, ProtocolLimits, ProverCodec, ProverMessage, Secret},
+    prover::ProverConfig,
+    report::{RateDelta, ReportPolicy},
+    server::{Action, PoolSession},
+    telemetry::{GpuTelemetry, TelemetrySampler},
+    threshold::Thresholds,
+    traffic::{self, Direction},
+};
+
+#[cfg(feature = "chaos")]
+pub use crate::chaos::{ChaosConfig, ChaosStream};
+
+/// Pool target no proof meets, workers prove without ever submitting.
+pub const NO_SHARES: u64 = 1;
+/// Pool target every proof meets.
+pub const ALL_SHARES: u64 = u64::MAX;
+
+/// Template for `height` on top of the genesis block, stamped `timestamp`.
+pub fn template_at(height: u32, timestamp: i64) -> BlockTemplate<Testnet2> {
+    template_with_target(height, timestamp, Testnet2::genesis_block().difficulty_target())
+}
+
+/// Template for `height` stamped `timestamp`, with `difficulty_target` as the network target.
+pub fn template_with_target(height: u32, timestamp: i64, difficulty_target: u64) -> BlockTemplate<Testnet2> {
+    let genesis = Testnet2::genesis_block();
+    let coinbase = genesis
+        .to_coinbase_transaction()
+        .expect("genesis has a coinbase transaction")
+        .to_records()
+        .next()
+        .expect("coinbase has a record");
+    BlockTemplate::new(
+        genesis.previous_block_hash(),
+        height,
+        timestamp,
+        difficulty_target,
+        genesis.cumulative_weight(),
+        genesis.previous_ledger_root(),
+        genesis.transactions().clone(),
+        coinbase,
+    )
+}
+
+/// Template for `height` stamped with the current time, so the client's template checks pass.
+pub fn template(height: u32) -> BlockTemplate<Testnet2> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    template_at(height, now)
+}
+
+/// Template with fixed contents, for byte exact fixtures and benchmarks.
+pub fn fixed_template() -> BlockTemplate<Testnet2> {
+    let genesis = Testnet2::genesis_block();
+    template_at(genesis.height() + 1, genesis.timestamp())
+}
+
+/// A header with a valid proof, the genesis block's.
+pub fn header() -> BlockHeader<Testnet2> {
+    Testnet2::genesis_block().header().clone()
+}
+
+/// Address of a fixed account.
+pub fn address() -> Address<Testnet2> {
+    let mut rng = ChaChaRng::seed_from_u64(0xa1e0);
+    Account::<Testnet2>::new(&mut rng).address()
+}
+
+pub fn notify() -> ProverMessage {
+    ProverMessage::Notify(fixed_template(), u64::MAX / 1000, false, false)
+}
+
+pub fn submit() -> ProverMessage {
+    let header = header();
+    ProverMessage::Submit(1, header.nonce(), header.proof().clone())
+}
+
+pub fn submit_result() -> ProverMessage {
+    ProverMessage::SubmitResult(Code::Stale, Some("share is stale".to_string()))
+}
+
+/// One message of every type by name, with non-default fields where there are any. The golden vectors
+/// in tests/fixtures/golden.txt are their encodings.
+pub fn messages() -> Vec<(&'static str, ProverMessage)> {
+    vec![
+        (
+            "authorize",
+            ProverMessage::Authorize("account".to_string(), "worker".to_string(), "x;d=64".into(), 0x1f01),
+        ),
+        ("authorize_result", ProverMessage::AuthorizeResult(true, Some("session=abc".to_string()))),
+        ("notify", notify()),
+        ("notify_reorg_speculative", ProverMessage::Notify(fixed_template(), 42, true, true)),
+        ("submit", submit()),
+        ("submit_result", submit_result()),
+        ("submit_result_success", ProverMessage::SubmitResult(Code::Success, None)),
+        ("canary", ProverMessage::Canary),
+        ("proof_rate", ProverMessage::ProofRate(12_345)),
+        ("activate_job", ProverMessage::ActivateJob(7)),
+        ("job_ack", ProverMessage::JobAck(7, 1 << 40)),
+        ("pool_info", ProverMessage::PoolInfo(BTreeMap::from([("balance".to_string(), "1.5".to_string())]))),
+        ("worker", ProverMessage::Worker("rig1".to_string())),
+    ]
+}
+
+/// An empty directory for the files of the unit test `test` in `module`, the unit tests of all
+/// modules share a process.
+#[cfg(test)]
+pub fn scratch(module: &str, test: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("aleoxminer-{}-{}-{}", module, std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Client for `server` authorizing as the fixture address, not started yet.
+pub fn client(server: &str, worker: &str) -> Arc<Client> {
+    Client::init(
+        None,
+        Some(worker.to_string()),
+        Some(address()),
+        server.to_string(),
+        EventBus::new(),
+        ProtocolLimits::default(),
+        1024,
+    )
+}
+
+/// Settings of a prover with the defaults of the command line, proving on `backend` with `threads`
+/// CPU threads and reporting its rate every second.
+pub fn prover_config(threads: u16, backend: Arc<dyn ProvingBackend>) -> ProverConfig {
+    ProverConfig {
+        threads,
+        cuda: None,
+        cuda_jobs: None,
+        reject_window: 50,
+        reject_threshold: 25.0,
+        reject_cooldown: Duration::from_secs(300),
+        reject_probation: 5,
+        latency_drift: 25.0,
+        deterministic_seed: None,
+        max_concurrent_proofs: None,
+        rate_report: ReportPolicy {
+            min_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(1),
+            delta: RateDelta::Percent(0.0),
+        },
+        earnings: EarningsConfig::default(),
+        nice: false,
+        share_alert: Duration::from_secs(15 * 60),
+        watchdog_multiple: 10.0,
+        cpu_path: CpuPath::Generic,
+        groups: Vec::new(),
+        notifier: None,
+        telemetry: None,
+        thresholds: Thresholds::default(),
+        proving: true,
+        backend: Some(backend),
+    }
+}
+
+// Index of the CPU pool the current thread belongs to, from the `ap-cpu-{index}-{thread}` name.
+fn current_worker() -> Option<usize> {
+    thread::current().name()?.strip_prefix("ap-cpu-")?.split('-').next()?.parse().ok()
+}
+
+/// Backend returning the genesis header after `delay`, counting what the prover asks of it.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    delay: Duration,
+    attempts: AtomicU64,
+    proving: AtomicUsize,
+    workers: Mutex<BTreeSet<usize>>,
+    failing: Mutex<BTreeSet<usize>>,
+    // First number each attempt drew from the prover's generator, by CPU pool.
+    draws: Mutex<BTreeMap<usize, Vec<u64>>>,
+    // Time loading the parameters takes, `None` if they aren't needed.
+    load_time: Option<Duration>,
+    loads: AtomicU64,
+    // Loading the parameters fails, panicking if true.
+    load_failure: Mutex<Option<bool>>,
+}
+
+impl FakeBackend {
+    pub fn new(delay: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            delay,
+            ..Default::default()
+        })
+    }
+
+    /// Backend that needs the proving parameters, loading them takes `load_time`.
+    pub fn with_parameters(delay: Duration, load_time: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            delay,
+            load_time: Some(load_time),
+            ..Default::default()
+        })
+    }
+
+    /// Times the parameters were loaded.
+    pub fn loads(&self) -> u64 {
+        self.loads.load(Ordering::SeqCst)
+    }
+
+    /// Makes loading the parameters fail from now on, by panicking if `panic`. `None` lets it succeed again.
+    pub fn fail_loading(&self, failure: Option<bool>) {
+        *self.load_failure.lock().unwrap_or_else(PoisonError::into_inner) = failure;
+    }
+
+    /// Attempts that returned a header.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::SeqCst)
+    }
+
+    /// Attempts in progress.
+    pub fn proving(&self) -> usize {
+        self.proving.load(Ordering::SeqCst)
+    }
+
+    /// CPU pools that made an attempt.
+    pub fn workers(&self) -> BTreeSet<usize> {
+        self.workers.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// First number every attempt of the worker on CPU pool `worker` drew from the nonce generator
+    /// the prover passed, in order.
+    pub fn draws(&self, worker: usize) -> Vec<u64> {
+        let draws = self.draws.lock().unwrap_or_else(PoisonError::into_inner);
+        draws.get(&worker).cloned().unwrap_or_default()
+    }
+
+    /// Makes every attempt of the worker proving on CPU pool `worker` fail.
+    pub fn fail(&self, worker: usize) {
+        self.failing.lock().unwrap_or_else(PoisonError::into_inner).insert(worker);
+    }
+}
+
+impl ProvingBackend for FakeBackend {
+    fn name(&self) -> String {
+        "Fake".to_string()
+    }
+
+    fn prove(
+        &self,
+        _template: &BlockTemplate<Testnet2>,
+        terminator: &AtomicBool,
+        rng: &mut dyn RngCore,
+    ) -> Result<BlockHeader<Testnet2>> {
+        let draw = rng.next_u64();
+        if let Some(worker) = current_worker() {
+            let mut draws = self.draws.lock().unwrap_or_else(PoisonError::into_inner);
+            draws.entry(worker).or_default().push(draw);
+        }
+        self.proving.fetch_add(1, Ordering::SeqCst);
+        let deadline = Instant::now() + self.delay;
+        while !terminator.load(Ordering::SeqCst) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.proving.fetch_sub(1, Ordering::SeqCst);
+        if terminator.load(Ordering::SeqCst) {
+            return Err(anyhow!("terminated"));
+        }
+        if let Some(worker) = current_worker() {
+            self.workers.lock().unwrap_or_else(PoisonError::into_inner).insert(worker);
+            if self.failing.lock().unwrap_or_else(PoisonError::into_inner).contains(&worker) {
+                return Err(anyhow!("worker {} fails", worker));
+            }
+        }
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        Ok(header())
+    }
+
+    fn needs_parameters(&self) -> bool {
+        self.load_time.is_some()
+    }
+
+    fn load_parameters(&self) -> Result<()> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(self.load_time.unwrap_or_default());
+        match *self.load_failure.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(true) => panic!("loading the parameters panicked"),
+            Some(false) => Err(anyhow!("no parameters")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// GPU telemetry panicking on every reading, as a broken driver binding might.
+#[derive(Debug, Default)]
+pub struct PanickingSampler {
+    samples: AtomicU64,
+}
+
+impl PanickingSampler {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Readings asked for, each of them panicked.
+    pub fn samples(&self) -> u64 {
+        self.samples.load(Ordering::SeqCst)
+    }
+}
+
+impl TelemetrySampler for PanickingSampler {
+    fn sample(&self, device: i16) -> Option<GpuTelemetry> {
+        self.samples.fetch_add(1, Ordering::SeqCst);
+        panic!("no reading from GPU {}", device);
+    }
+}
+
+/// What the mock pool received, in order of arrival.
+#[derive(Debug, Clone, Default)]
+pub struct Received {
+    /// Connections accepted
+    pub connections: u64,
+    /// (account, worker, version) of every Authorize
+    pub authorizations: Vec<(String, String, u16)>,
+    /// (height, nonce) of every share, stale ones included
+    pub shares: Vec<(u32, <Testnet2 as Network>::PoSWNonce)>,
+    pub proof_rates: Vec<u64>,
+    /// (height, pool target) of every JobAck
+    pub job_acks: Vec<(u32, u64)>,
+    /// (worker, height) of every share that wasn't stale, the worker a Worker named or the authorized one
+    pub forwarded: Vec<(String, u32)>,
+    /// Name of every message
+    pub messages: Vec<&'static str>,
+    /// Session token every Authorize asked to resume, from miners resuming sessions, and any token
+    /// sent without resuming them
+    pub session_tokens: Vec<Option<String>>,
+}
+
+// What the test tells the sessions to send.
+#[derive(Clone)]
+enum Command {
+    Notify(BlockTemplate<Testnet2>, u64),
+    Speculative(BlockTemplate<Testnet2>, u64),
+    Activate(u32),
+    PoolInfo(BTreeMap<String, String>),
+    Disconnect,
+}
+
+struct PoolState {
+    received: Mutex<Received>,
+    // Latest Notify, sent to every session once authorized.
+    work: Mutex<Option<(BlockTemplate<Testnet2>, u64)>>,
+    result: Mutex<(Code, Option<String>)>,
+    // Results of the shares of some workers instead of `result`.
+    worker_results: Mutex<HashMap<String, (Code, Option<String>)>>,
+    authorize: AtomicBool,
+    // Tokens of the sessions given out and not forgotten.
+    sessions: Mutex<HashSet<String>>,
+    connected: AtomicUsize,
+    commands: broadcast::Sender<Command>,
+}
+
+impl PoolState {
+    fn received(&self) -> MutexGuard<'_, Received> {
+        self.received.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Pool speaking the protocol through `PoolSession` to every miner connecting, scripted by the test.
+/// Authorizes everyone, accepts every share and sends the latest `notify` after authorizing. Miners
+/// asking for a difficulty with `d=` get jobs at that difficulty. Miners resuming sessions get a
+/// session token, asking to resume a session the pool doesn't know fails the authorization.
+pub struct MockPool {
+    address: String,
+    state: Arc<PoolState>,
+    accept: JoinHandle<()>,
+}
+
+impl MockPool {
+    /// Listens on a free port of 127.0.0.1.
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address: SocketAddr = listener.local_addr()?;
+        let state = Self::state();
+        let pool = state.clone();
+        let accept = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(pool.clone(), socket));
+            }
+        });
+        Ok(Self {
+            address: address.to_string(),
+            state,
+            accept,
+        })
+    }
+
+    /// Serves the connections of a `DuplexConnector`.
+    pub fn duplex(mut connections: mpsc::UnboundedReceiver<(String, DuplexStream)>) -> Self {
+        let state = Self::state();
+        let pool = state.clone();
+        let accept = tokio::spawn(async move {
+            while let Some((_, stream)) = connections.recv().await {
+                tokio::spawn(serve(pool.clone(), stream));
+            }
+        });
+        Self {
+            address: "mock.pool:4040".to_string(),
+            state,
+            accept,
+        }
+    }
+
+    fn state() -> Arc<PoolState> {
+        let (commands, _) = broadcast::channel(64);
+        Arc::new(PoolState {
+            received: Default::default(),
+            work: Default::default(),
+            result: Mutex::new((Code::Success, None)),
+            worker_results: Default::default(),
+            authorize: AtomicBool::new(true),
+            sessions: Default::default(),
+            connected: Default::default(),
+            commands,
+        })
+    }
+
+    /// `host:port` to give the client.
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Client for this pool authorizing as the fixture address, not started yet.
+    pub fn client(&self, worker: &str) -> Arc<Client> {
+        client(&self.address, worker)
+    }
+
+    /// Sends new work to every authorized miner and to the ones authorizing later.
+    pub fn notify(&self, template: BlockTemplate<Testnet2>, target: u64) {
+        *self.state.work.lock().unwrap_or_else(PoisonError::into_inner) = Some((template.clone(), target));
+        let _ = self.state.commands.send(Command::Notify(template, target));
+    }
+
+    /// Sends a speculative job to the miners taking them.
+    pub fn speculative(&self, template: BlockTemplate<Testnet2>, target: u64) {
+        let _ = self.state.commands.send(Command::Speculative(template, target));
+    }
+
+    pub fn activate(&self, height: u32) {
+        let _ = self.state.commands.send(Command::Activate(height));
+    }
+
+    pub fn pool_info(&self, fields: BTreeMap<String, String>) {
+        let _ = self.state.commands.send(Command::PoolInfo(fields));
+    }
+
+    /// Closes every connection, the miners are free to reconnect.
+    pub fn disconnect(&self) {
+        let _ = self.state.commands.send(Command::Disconnect);
+    }
+
+    /// Result of the shares from now on.
+    pub fn set_result(&self, code: Code, message: Option<String>) {
+        *self.state.result.lock().unwrap_or_else(PoisonError::into_inner) = (code, message);
+    }
+
+    /// Result of the shares of `worker` from now on, whichever connection they come through.
+    pub fn set_worker_result(&self, worker: &str, code: Code, message: Option<String>) {
+        let mut results = self.state.worker_results.lock().unwrap_or_else(PoisonError::into_inner);
+        results.insert(worker.to_string(), (code, message));
+    }
+
+    /// Whether miners are authorized from now on.
+    pub fn set_authorize(&self, authorize: bool) {
+        self.state.authorize.store(authorize, Ordering::SeqCst);
+    }
+
+    /// Forgets the sessions given out so far, as a restarted pool would.
+    pub fn forget_sessions(&self) {
+        self.state.sessions.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// Open connections.
+    pub fn connected(&self) -> usize {
+        self.state.connected.load(Ordering::SeqCst)
+    }
+
+    pub fn received(&self) -> Received {
+        self.state.received().clone()
+    }
+
+    /// Waits until what the pool received meets `condition`, failing after `limit`.
+    pub async fn wait_for(&self, limit: Duration, condition: impl Fn(&Received) -> bool) -> Result<Received> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let received = self.received();
+            if condition(&received) {
+                return Ok(received);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!("condition not met within {:?}, received {:?}", limit, received));
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for MockPool {
+    fn drop(&mut self) {
+        self.accept.abort();
+        let _ = self.state.commands.send(Command::Disconnect);
+    }
+}
+
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(state: Arc<PoolState>, socket: S) {
+    state.received().connections += 1;
+    state.connected.fetch_add(1, Ordering::SeqCst);
+    let _ = session(&state, socket).await;
+    state.connected.fetch_sub(1, Ordering::SeqCst);
+}
+
+async fn session<S: AsyncRead + AsyncWrite + Unpin>(state: &PoolState, socket: S) -> Result<()> {
+    let mut framed = Framed::new(socket, ProverCodec::default());
+    let mut session = PoolSession::new();
+    let mut commands = state.commands.subscribe();
+    loop {
+        tokio::select! {
+            message = framed.next() => {
+                let message = match message {
+                    Some(message) => message?,
+                    None => return Ok(()),
+                };
+                {
+                    let mut received = state.received();
+                    received.messages.push(message.name());
+                    if let ProverMessage::Submit(height, nonce, _) = &message {
+                        received.shares.push((*height, *nonce));
+                    }
+                }
+                match session.receive(message) {
+                    Action::Authorize { account, worker, version, .. } => {
+                        state.received().authorizations.push((account, worker, version));
+                        if session.msgpack() {
+                            framed.codec_mut().use_msgpack();
+                        }
+                        let authorize = state.authorize.load(Ordering::SeqCst);
+                        framed.send(authorized(state, &mut session, authorize)).await?;
+                        let work = state.work.lock().unwrap_or_else(PoisonError::into_inner).clone();
+                        let work = work.map(|(template, target)| (template, hinted(&session, target)));
+                        if let Some(notify) = work.and_then(|(template, target)| session.notify(template, target)) {
+                            framed.send(notify).await?;
+                        }
+                    }
+                    Action::ForwardShare { worker, height, .. } => {
+                        let worker = worker.or_else(|| Some(session.identity()?.1.to_string())).unwrap_or_default();
+                        let worker_result = {
+                            let results = state.worker_results.lock().unwrap_or_else(PoisonError::into_inner);
+                            results.get(&worker).cloned()
+                        };
+                        let (code, message) = worker_result
+                            .unwrap_or_else(|| state.result.lock().unwrap_or_else(PoisonError::into_inner).clone());
+                        state.received().forwarded.push((worker, height));
+                        framed.send(session.share_result(code, message)).await?;
+                    }
+                    Action::Reply(message) => framed.send(message).await?,
+                    Action::ProofRate(rate) => {
+                        state.received().proof_rates.push(rate);
+                    }
+                    Action::JobAck { height, pool_target, .. } => {
+                        state.received().job_acks.push((height, pool_target));
+                    }
+                    Action::ProtocolViolation(reason) => return Err(anyhow!(reason)),
+                    Action::None => {}
+                }
+            }
+            command = commands.recv() => {
+                let message = match command {
+                    Ok(Command::Notify(template, target)) => {
+                        let target = hinted(&session, target);
+                        session.notify(template, target)
+                    }
+                    Ok(Command::Speculative(template, target)) => {
+                        let target = hinted(&session, target);
+                        session.speculative_notify(template, target)
+                    }
+                    Ok(Command::Activate(height)) => session.activate(height),
+                    Ok(Command::PoolInfo(fields)) => session.pool_info(fields),
+                    Ok(Command::Disconnect) | Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                };
+                if let Some(message) = message {
+                    framed.send(message).await?;
+                }
+            }
+        }
+    }
+}
+
+// The AuthorizeResult for `session`, with a session token for miners resuming sessions.
+fn authorized(state: &PoolState, session: &mut PoolSession, authorize: bool) -> ProverMessage {
+    // A miner not resuming sessions has no token to send, one it sends anyway is recorded too.
+    if let Some(token) = session.session_token().filter(|_| !session.resumes_sessions()) {
+        state.received().session_tokens.push(Some(token.to_string()));
+    }
+    if !authorize || !session.resumes_sessions() {
+        return session.authorized(authorize, None);
+    }
+    let asked = session.session_token().map(str::to_string);
+    state.received().session_tokens.push(asked.clone());
+    let mut sessions = state.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+    let token = match asked {
+        Some(token) if sessions.contains(&token) => token,
+        Some(_) => return session.authorized(false, Some("unknown session".to_string())),
+        None => format!("session-{}", state.received().connections),
+    };
+    sessions.insert(token.clone());
+    session.authorized_session(&token)
+}
+
+// The target of the difficulty the miner asked for, `target` if it didn't.
+fn hinted(session: &PoolSession, target: u64) -> u64 {
+    session.difficulty_hint().map_or(target, |difficulty| u64::MAX / difficulty)
+}
+
+// Keepalives and rate reports are sent on timers, a replay can't expect them at the recorded point.
+fn timer_driven(message: &ProverMessage) -> bool {
+    matches!(message, ProverMessage::Canary | ProverMessage::ProofRate(..) | ProverMessage::JobAck(..))
+}
+
+// Recordings are anonymized, the account and password of an Authorize aren't compared.
+fn anonymized(message: ProverMessage) -> ProverMessage {
+    match message {
+        ProverMessage::Authorize(_, worker, _, version) => {
+            ProverMessage::Authorize(String::new(), worker, Secret::default(), version)
+        }
+        message => message,
+    }
+}
+
+/// Plays the pool's side of a `--record-traffic` recording to the first client connecting through
+/// `connections`: sends the inbound frames as recorded and fails when the client sends something else
+/// than the next outbound frame. Timer driven frames are skipped on both sides, see `timer_driven`.
+pub async fn replay(
+    recording: &Path,
+    mut connections: mpsc::UnboundedReceiver<(String, DuplexStream)>,
+) -> Result<()> {
+    let records = traffic::read(recording)?;
+    let (_, stream) = timeout(Duration::from_secs(10), connections.recv())
+        .await
+        .map_err(|_| anyhow!("the client didn't connect"))?
+        .ok_or_else(|| anyhow!("the client didn't connect"))?;
+    let mut framed = Framed::new(stream, ProverCodec::default());
+    for (index, record) in records.into_iter().enumerate() {
+        let recorded = ProverCodec::default()
+            .decode(&mut BytesMut::from(&record.frame[..]))?
+            .ok_or_else(|| anyhow!("record {} holds an incomplete frame", index))?;
+        if timer_driven(&recorded) {
+            continue;
+        }
+        match record.direction {
+            Direction::Inbound => framed.get_mut().write_all(&record.frame).await?,
+            Direction::Outbound => {
+                let sent = loop {
+                    let sent = timeout(Duration::from_secs(10), framed.next())
+                        .await
+                        .map_err(|_| anyhow!("record {}: no {} from the client", index, recorded.name()))?
+                        .ok_or_else(|| anyhow!("record {}: the client disconnected", index))??;
+                    if !timer_driven(&sent) {
+                        break sent;
+                    }
+                };
+                let (sent, recorded) = (anonymized(sent), anonymized(recorded));
+                if sent != recorded {
+                    return Err(anyhow!("record {}: expected {:?}, the client sent {:?}", index, recorded, sent));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A request an `HttpSink` received.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct SinkState {
+    requests: Mutex<Vec<HttpRequest>>,
+    // Requests still to be answered with a server error.
+    failures: AtomicUsize,
+}
+
+/// HTTP server recording every request, for the webhook and metrics push clients. Answers 204, or 500
+/// for as many requests as `fail` asks.
+pub struct HttpSink {
+    address: SocketAddr,
+    state: Arc<SinkState>,
+    accept: JoinHandle<()>,
+}
+
+impl HttpSink {
+    /// Listens on a free port of 127.0.0.1.
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(SinkState::default());
+        let sink = state.clone();
+        let accept = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let sink = sink.clone();
+                tokio::spawn(async move {
+                    let _ = answer(&sink, socket).await;
+                });
+            }
+        });
+        Ok(Self { address, state, accept })
+    }
+
+    /// URL of `path` on the sink, e.g. `http://127.0.0.1:41234/hook`.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+
+    /// Answers the next `count` requests with a server error.
+    pub fn fail(&self, count: usize) {
+        self.state.failures.store(count, Ordering::SeqCst);
+    }
+
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.state.requests.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Waits for `count` requests, failing after `limit`.
+    pub async fn wait_for(&self, limit: Duration, count: usize) -> Result<Vec<HttpRequest>> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let requests = self.requests();
+            if requests.len() >= count {
+                return Ok(requests);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!("got {} of {} requests within {:?}", requests.len(), count, limit));
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for HttpSink {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+// Answers the requests of one kept alive connection.
+async fn answer(state: &SinkState, mut socket: TcpStream) -> Result<()> {
+    let mut buffer = Vec::new();
+    loop {
+        let head_end = loop {
+            if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break end + 4;
+            }
+            if socket.read_buf(&mut buffer).await? == 0 {
+                return Ok(());
+            }
+        };
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        while buffer.len() < head_end + length {
+            if socket.read_buf(&mut buffer).await? == 0 {
+                return Ok(());
+            }
+        }
+        let body = buffer[head_end..head_end + length].to_vec();
+        buffer.drain(..head_end + length);
+        state.requests.lock().unwrap_or_else(PoisonError::into_inner).push(HttpRequest { method, path, body });
+        let failed = state
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| failures.checked_sub(1))
+            .is_ok();
+        let status = if failed { "500 Internal Server Error" } else { "204 No Content" };
+        socket
+            .write_all(format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status).as_bytes())
+            .await?;
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, ensure, Error, Result};
+
+use crate::units;
+
+// Alerts clear only once the value is back inside the threshold by this fraction, so they don't flap.
+const HYSTERESIS: f64 = 0.1;
+// Shares needed in the window before the reject percentage is judged.
+const MIN_SHARES: u32 = 10;
+// Window the reject percentage and disconnected time are evaluated over.
+const WINDOW: Duration = Duration::from_secs(3600);
+
+/// Minimum proof rate, in proofs per second or as a percentage of the session average.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateThreshold {
+    Absolute(f64),
+    PercentOfAverage(f64),
+}
+
+impl FromStr for RateThreshold {
+    type Err = Error;
+
+    /// Parses `50` (proofs per second) or `80%` (of the session average).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let threshold = match s.strip_suffix('%') {
+            Some(percent) => Self::PercentOfAverage(
+                percent
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("invalid hashrate threshold {}", s))?,
+            ),
+            None => Self::Absolute(s.parse().map_err(|_| anyhow!("invalid hashrate threshold {}", s))?),
+        };
+        threshold.validate()?;
+        Ok(threshold)
+    }
+}
+
+impl RateThreshold {
+    fn validate(&self) -> Result<()> {
+        match *self {
+            Self::Absolute(rate) => ensure!(rate.is_finite() && rate > 0.0, "hashrate threshold must be positive"),
+            Self::PercentOfAverage(percent) => ensure!(
+                percent > 0.0 && percent <= 100.0,
+                "hashrate threshold must be between 0% and 100% of the average"
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// User defined alert thresholds, every one of them optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thresholds {
+    /// Minimum 15 minute proof rate
+    pub min_rate: Option<RateThreshold>,
+    /// Maximum percentage of rejected shares over the last hour
+    pub max_reject_percent: Option<f64>,
+    /// Maximum time disconnected from the pool over the last hour
+    pub max_disconnected: Option<Duration>,
+}
+
+impl Thresholds {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(rate) = self.min_rate.as_ref() {
+            rate.validate()?;
+        }
+        if let Some(percent) = self.max_reject_percent {
+            ensure!(
+                (0.0..100.0).contains(&percent),
+                "reject percentage threshold must be at least 0 and below 100"
+            );
+        }
+        if let Some(disconnected) = self.max_disconnected {
+            ensure!(
+                disconnected > Duration::ZERO && disconnected < WINDOW,
+                "disconnected time threshold must be between 0 and 60 minutes"
+            );
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_rate.is_none() && self.max_reject_percent.is_none() && self.max_disconnected.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breach {
+    LowRate,
+    RejectRate,
+    Disconnected,
+}
+
+impl Breach {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LowRate => "low-hashrate",
+            Self::RejectRate => "reject-rate",
+            Self::Disconnected => "disconnected",
+        }
+    }
+}
+
+/// Values the thresholds are evaluated against.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Proof rate over the last 15 minutes
+    pub rate: Option<f64>,
+    /// Proof rate since start
+    pub average_rate: Option<f64>,
+    /// Shares accepted and rejected in the window
+    pub accepted: u32,
+    pub rejected: u32,
+    /// Time disconnected in the window
+    pub disconnected: Duration,
+}
+
+/// Breaches after evaluating `snapshot`, with a description each. Breaches in `active` stay
+/// raised until the value is back inside the threshold by the hysteresis margin.
+pub fn evaluate(thresholds: &Thresholds, snapshot: &Snapshot, active: &[Breach]) -> Vec<(Breach, String)> {
+    let mut breaches = Vec::new();
+    let is_active = |breach| active.contains(&breach);
+
+    if let Some(threshold) = thresholds.min_rate {
+        let limit = match threshold {
+            RateThreshold::Absolute(rate) => Some(rate),
+            RateThreshold::PercentOfAverage(percent) => snapshot.average_rate.map(|average| average * percent / 100.0),
+        };
+        match (snapshot.rate, limit) {
+            (Some(rate), Some(limit)) => {
+                let clear_at = if is_active(Breach::LowRate) { limit * (1.0 + HYSTERESIS) } else { limit };
+                if rate < clear_at {
+                    breaches.push((
+                        Breach::LowRate,
+                        format!(
+                            "15m proof rate {} is below the threshold of {}",
+                            units::format_rate(rate),
+                            units::format_rate(limit)
+                        ),
+                    ));
+                }
+            }
+            // Keep the alert while there isn't enough data to clear it.
+            _ if is_active(Breach::LowRate) => {
+                breaches.push((Breach::LowRate, "15m proof rate unknown".to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(max) = thresholds.max_reject_percent {
+        let total = snapshot.accepted + snapshot.rejected;
+        if total >= MIN_SHARES {
+            let percent = snapshot.rejected as f64 / total as f64 * 100.0;
+            let clear_at = if is_active(Breach::RejectRate) { max * (1.0 - HYSTERESIS) } else { max };
+            if percent > clear_at {
+                breaches.push((
+                    Breach::RejectRate,
+                    format!(
+                        "{:.1}% of shares rejected in the last hour, the threshold is {:.1}%",
+                        percent, max
+                    ),
+                ));
+            }
+        } else if is_active(Breach::RejectRate) {
+            breaches.push((Breach::RejectRate, "Too few shares to judge the reject rate".to_string()));
+        }
+    }
+
+    if let Some(max) = thresholds.max_disconnected {
+        let clear_at = if is_active(Breach::Disconnected) { max.mul_f64(1.0 - HYSTERESIS) } else { max };
+        if snapshot.disconnected > clear_at {
+            breaches.push((
+                Breach::Disconnected,
+                format!(
+                    "Disconnected for {}s in the last hour, the threshold is {}s",
+                    snapshot.disconnected.as_secs(),
+                    max.as_secs()
+                ),
+            ));
+        }
+    }
+
+    breaches
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThresholdChange {
+    Raised(Breach, String),
+    Cleared(Breach),
+}
+
+/// Keeps the last hour of counters and the raised alerts between evaluations.
+pub struct ThresholdMonitor {
+    thresholds: Thresholds,
+    /// (time, connected time, accepted, rejected)
+    samples: VecDeque<(Instant, Duration, u32, u32)>,
+    active: Vec<(Breach, String)>,
+}
+
+impl ThresholdMonitor {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            samples: VecDeque::new(),
+            active: Vec::new(),
+        }
+    }
+
+    /// Raised alerts that are no longer configured clear on the next evaluation.
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// Evaluates the thresholds on a stats tick and returns the alerts raised or cleared.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        connected: Duration,
+        accepted: u32,
+        rejected: u32,
+        rate: Option<f64>,
+        average_rate: Option<f64>,
+    ) -> Vec<ThresholdChange> {
+        if self.thresholds.is_empty() && self.active.is_empty() {
+            return Vec::new();
+        }
+        self.samples.push_back((now, connected, accepted, rejected));
+        while self
+            .samples
+            .front()
+            .map_or(false, |(time, ..)| now.saturating_duration_since(*time) > WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        let (oldest, oldest_connected, oldest_accepted, oldest_rejected) = self.samples[0];
+        let elapsed = now.saturating_duration_since(oldest);
+        let snapshot = Snapshot {
+            rate,
+            average_rate,
+            accepted: accepted.saturating_sub(oldest_accepted),
+            rejected: rejected.saturating_sub(oldest_rejected),
+            disconnected: elapsed.saturating_sub(connected.saturating_sub(oldest_connected)),
+        };
+        let active: Vec<Breach> = self.active.iter().map(|(breach, _)| *breach).collect();
+        let breaches = evaluate(&self.thresholds, &snapshot, &active);
+
+        let mut changes: Vec<ThresholdChange> = breaches
+            .iter()
+            .filter(|(breach, _)| !active.contains(breach))
+            .map(|(breach, details)| ThresholdChange::Raised(*breach, details.clone()))
+            .collect();
+        changes.extend(
+            active
+                .iter()
+                .filter(|breach| !breaches.iter().any(|(raised, _)| raised == *breach))
+                .map(|breach| ThresholdChange::Cleared(*breach)),
+        );
+        self.active = breaches;
+        changes
+    }
+
+    /// Descriptions of the raised alerts.
+    pub fn breaches(&self) -> Vec<String> {
+        self.active.iter().map(|(_, details)| details.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn min_rate(threshold: RateThreshold) -> Thresholds {
+        Thresholds {
+            min_rate: Some(threshold),
+            ..Thresholds::default()
+        }
+    }
+
+    fn max_rejects(percent: f64) -> Thresholds {
+        Thresholds {
+            max_reject_percent: Some(percent),
+            ..Thresholds::default()
+        }
+    }
+
+    fn max_disconnected(disconnected: Duration) -> Thresholds {
+        Thresholds {
+            max_disconnected: Some(disconnected),
+            ..Thresholds::default()
+        }
+    }
+
+    fn rate(rate: Option<f64>, average_rate: Option<f64>) -> Snapshot {
+        Snapshot {
+            rate,
+            average_rate,
+            ..Snapshot::default()
+        }
+    }
+
+    fn shares(accepted: u32, rejected: u32) -> Snapshot {
+        Snapshot {
+            accepted,
+            rejected,
+            ..Snapshot::default()
+        }
+    }
+
+    fn disconnected(disconnected: Duration) -> Snapshot {
+        Snapshot {
+            disconnected,
+            ..Snapshot::default()
+        }
+    }
+
+    #[test]
+    fn evaluates_each_threshold() {
+        use Breach::*;
+        let absolute = min_rate(RateThreshold::Absolute(50.0));
+        let five_minutes = max_disconnected(5 * MINUTE);
+        let relative = min_rate(RateThreshold::PercentOfAverage(80.0));
+        let all = Thresholds {
+            min_rate: Some(RateThreshold::Absolute(50.0)),
+            max_reject_percent: Some(10.0),
+            max_disconnected: Some(5 * MINUTE),
+        };
+        let everything_wrong = Snapshot {
+            rate: Some(10.0),
+            average_rate: Some(100.0),
+            accepted: 5,
+            rejected: 5,
+            disconnected: 10 * MINUTE,
+        };
+        // (thresholds, snapshot, raised before, raised after)
+        let cases: Vec<(&Thresholds, Snapshot, Vec<Breach>, Vec<Breach>)> = vec![
+            (&Thresholds::default(), everything_wrong.clone(), vec![], vec![]),
+            (&absolute, rate(Some(40.0), None), vec![], vec![LowRate]),
+            (&absolute, rate(Some(50.0), None), vec![], vec![]),
+            // Clearing takes 10% above the threshold.
+            (&absolute, rate(Some(52.0), None), vec![LowRate], vec![LowRate]),
+            (&absolute, rate(Some(56.0), None), vec![LowRate], vec![]),
+            // An unknown rate raises nothing and clears nothing.
+            (&absolute, rate(None, None), vec![], vec![]),
+            (&absolute, rate(None, None), vec![LowRate], vec![LowRate]),
+            (&relative, rate(Some(79.0), Some(100.0)), vec![], vec![LowRate]),
+            (&relative, rate(Some(81.0), Some(100.0)), vec![], vec![]),
+            (&relative, rate(Some(10.0), None), vec![], vec![]),
+            (&max_rejects(10.0), shares(0, 9), vec![], vec![]),
+            (&max_rejects(10.0), shares(9, 1), vec![], vec![]),
+            (&max_rejects(10.0), shares(8, 2), vec![], vec![RejectRate]),
+            (&max_rejects(10.0), shares(90, 10), vec![RejectRate], vec![RejectRate]),
+            (&max_rejects(10.0), shares(95, 5), vec![RejectRate], vec![]),
+            (&max_rejects(10.0), shares(1, 1), vec![RejectRate], vec![RejectRate]),
+            (&five_minutes, disconnected(6 * MINUTE), vec![], vec![Disconnected]),
+            (&five_minutes, disconnected(5 * MINUTE), vec![], vec![]),
+            (&five_minutes, disconnected(Duration::from_secs(280)), vec![Disconnected], vec![Disconnected]),
+            (&five_minutes, disconnected(4 * MINUTE), vec![Disconnected], vec![]),
+            (&all, everything_wrong, vec![], vec![LowRate, RejectRate, Disconnected]),
+        ];
+        for (index, (thresholds, snapshot, active, expected)) in cases.into_iter().enumerate() {
+            let breaches: Vec<Breach> = evaluate(thresholds, &snapshot, &active)
+                .into_iter()
+                .map(|(breach, _)| breach)
+                .collect();
+            assert_eq!(breaches, expected, "case {}", index);
+        }
+    }
+
+    #[test]
+    fn describes_breaches() {
+        let breaches = evaluate(&max_rejects(10.0), &shares(8, 2), &[]);
+        assert_eq!(breaches[0].1, "20.0% of shares rejected in the last hour, the threshold is 10.0%");
+        let breaches = evaluate(&max_disconnected(5 * MINUTE), &disconnected(6 * MINUTE), &[]);
+        assert_eq!(breaches[0].1, "Disconnected for 360s in the last hour, the threshold is 300s");
+    }
+
+    #[test]
+    fn parses_rate_thresholds() {
+        assert_eq!("50".parse::<RateThreshold>().unwrap(), RateThreshold::Absolute(50.0));
+        assert_eq!(" 80 % ".parse::<RateThreshold>().unwrap(), RateThreshold::PercentOfAverage(80.0));
+        for invalid in ["0", "-1", "0%", "150%", "fast", "%"] {
+            assert!(invalid.parse::<RateThreshold>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn validates_thresholds() {
+        assert!(Thresholds::default().validate().is_ok());
+        assert!(max_rejects(0.0).validate().is_ok());
+        assert!(max_rejects(100.0).validate().is_err());
+        assert!(max_rejects(-1.0).validate().is_err());
+        assert!(max_disconnected(59 * MINUTE).validate().is_ok());
+        assert!(max_disconnected(60 * MINUTE).validate().is_err());
+        assert!(max_disconnected(Duration::ZERO).validate().is_err());
+    }
+
+    #[test]
+    fn monitor_raises_and_clears_once() {
+        let mut monitor = ThresholdMonitor::new(max_rejects(10.0));
+        let start = Instant::now();
+        assert!(monitor.update(start, Duration::ZERO, 0, 0, None, None).is_empty());
+        let raised = monitor.update(start + MINUTE, MINUTE, 8, 2, None, None);
+        assert!(matches!(raised.as_slice(), [ThresholdChange::Raised(Breach::RejectRate, _)]));
+        assert_eq!(monitor.breaches().len(), 1);
+        // Still above the clearing point, nothing changes.
+        assert!(monitor.update(start + 2 * MINUTE, 2 * MINUTE, 18, 2, None, None).is_empty());
+        let cleared = monitor.update(start + 3 * MINUTE, 3 * MINUTE, 30, 2, None, None);
+        assert_eq!(cleared, vec![ThresholdChange::Cleared(Breach::RejectRate)]);
+        assert!(monitor.breaches().is_empty());
+    }
+
+    #[test]
+    fn unconfigured_alerts_clear() {
+        let mut monitor = ThresholdMonitor::new(max_rejects(10.0));
+        let start = Instant::now();
+        monitor.update(start, Duration::ZERO, 0, 0, None, None);
+        monitor.update(start + MINUTE, MINUTE, 0, 10, None, None);
+        monitor.set_thresholds(Thresholds::default());
+        let cleared = monitor.update(start + 2 * MINUTE, 2 * MINUTE, 0, 20, None, None);
+        assert_eq!(cleared, vec![ThresholdChange::Cleared(Breach::RejectRate)]);
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep, Instant, Sleep},
+};
+
+// A throttled stream waits until at least this many bytes may pass, or the whole buffer if smaller,
+// instead of waking up for every byte.
+const MIN_CHUNK: usize = 512;
+// Smallest burst of a bucket, a lower rate still lets a small message through at once.
+const MIN_BURST: f64 = 1024.0;
+// The usage is measured over windows of this length.
+const METER_WINDOW: Duration = Duration::from_secs(1);
+
+/// Caps on the bytes per second read from and written to the pool, `None` for no cap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandwidthLimits {
+    pub read: Option<u64>,
+    pub write: Option<u64>,
+}
+
+/// Usage of the pool connection against its caps, in bytes per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct BandwidthStats {
+    pub read_rate: f64,
+    pub write_rate: f64,
+    pub read_limit: Option<u64>,
+    pub write_limit: Option<u64>,
+}
+
+/// Bytes a direction may pass, refilled at `rate` per second up to one second of traffic.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        let capacity = (rate as f64).max(MIN_BURST);
+        Self {
+            rate: rate.max(1) as f64,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    // Bytes of `wanted` that may pass now, or how long until enough do.
+    fn allowance(&mut self, wanted: usize, now: Instant) -> Result<usize, Duration> {
+        self.refill(now);
+        let chunk = wanted.min(MIN_CHUNK).min(self.capacity as usize).max(1) as f64;
+        if self.tokens >= chunk {
+            return Ok(wanted.min(self.tokens as usize));
+        }
+        Err(Duration::from_secs_f64((chunk - self.tokens) / self.rate))
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+/// Bytes per second over the last complete window.
+#[derive(Debug)]
+struct RateMeter {
+    since: Instant,
+    bytes: u64,
+    rate: f64,
+}
+
+impl RateMeter {
+    fn new(now: Instant) -> Self {
+        Self {
+            since: now,
+            bytes: 0,
+            rate: 0.0,
+        }
+    }
+
+    fn record(&mut self, bytes: usize, now: Instant) {
+        self.roll(now);
+        self.bytes += bytes as u64;
+    }
+
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed >= METER_WINDOW {
+            self.rate = self.bytes as f64 / elapsed.as_secs_f64();
+            self.since = now;
+            self.bytes = 0;
+        }
+    }
+
+    fn rate(&mut self, now: Instant) -> f64 {
+        self.roll(now);
+        self.rate
+    }
+}
+
+#[derive(Debug)]
+struct Direction {
+    limit: Option<u64>,
+    bucket: Option<TokenBucket>,
+    meter: RateMeter,
+}
+
+impl Direction {
+    fn new(limit: Option<u64>, now: Instant) -> Self {
+        Self {
+            limit,
+            bucket: limit.map(|rate| TokenBucket::new(rate, now)),
+            meter: RateMeter::new(now),
+        }
+    }
+
+    fn allowance(&mut self, wanted: usize) -> Result<usize, Duration> {
+        match self.bucket.as_mut() {
+            Some(bucket) => bucket.allowance(wanted, Instant::now()),
+            None => Ok(wanted),
+        }
+    }
+
+    fn record(&mut self, bytes: usize) {
+        if let Some(bucket) = self.bucket.as_mut() {
+            bucket.consume(bytes);
+        }
+        self.meter.record(bytes, Instant::now());
+    }
+}
+
+/// Byte rate limits of a `Client`'s connections, and their usage. Each connection starts with full
+/// buckets, the usage carries over.
+#[derive(Debug)]
+pub struct Bandwidth {
+    read: Mutex<Direction>,
+    write: Mutex<Direction>,
+}
+
+impl Default for Bandwidth {
+    fn default() -> Self {
+        Self::new(BandwidthLimits::default())
+    }
+}
+
+impl Bandwidth {
+    pub fn new(limits: BandwidthLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            read: Mutex::new(Direction::new(limits.read, now)),
+            write: Mutex::new(Direction::new(limits.write, now)),
+        }
+    }
+
+    /// Applies to the next connection.
+    pub fn set_limits(&self, limits: BandwidthLimits) {
+        for (direction, limit) in [(&self.read, limits.read), (&self.write, limits.write)] {
+            direction.lock().unwrap_or_else(PoisonError::into_inner).limit = limit;
+        }
+    }
+
+    /// `stream` throttled to the limits, with full buckets.
+    pub fn wrap<S>(self: &Arc<Self>, stream: S) -> ThrottledStream<S> {
+        let now = Instant::now();
+        for direction in [&self.read, &self.write] {
+            let mut direction = direction.lock().unwrap_or_else(PoisonError::into_inner);
+            direction.bucket = direction.limit.map(|rate| TokenBucket::new(rate, now));
+        }
+        ThrottledStream {
+            inner: stream,
+            bandwidth: self.clone(),
+            read_delay: None,
+            write_delay: None,
+        }
+    }
+
+    /// Whether writing has to wait for the budget to refill. Reports are skipped then so shares go
+    /// first.
+    pub fn write_exhausted(&self) -> bool {
+        self.write
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .allowance(1)
+            .is_err()
+    }
+
+    pub fn stats(&self) -> BandwidthStats {
+        let now = Instant::now();
+        let mut read = self.read.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut write = self.write.lock().unwrap_or_else(PoisonError::into_inner);
+        BandwidthStats {
+            read_rate: read.meter.rate(now),
+            write_rate: write.meter.rate(now),
+            read_limit: read.limit,
+            write_limit: write.limit,
+        }
+    }
+}
+
+/// Stream passing at most the bytes per second of its `Bandwidth`, beneath the codec so every
+/// message counts.
+pub struct ThrottledStream<S> {
+    inner: S,
+    bandwidth: Arc<Bandwidth>,
+    read_delay: Option<Pin<Box<Sleep>>>,
+    write_delay: Option<Pin<Box<Sleep>>>,
+}
+
+// Bytes of `wanted` that may pass in `direction`, `Pending` until the budget refilled.
+fn poll_allowance(
+    direction: &Mutex<Direction>,
+    delay: &mut Option<Pin<Box<Sleep>>>,
+    wanted: usize,
+    cx: &mut Context<'_>,
+) -> Poll<usize> {
+    loop {
+        if let Some(sleeping) = delay.as_mut() {
+            if sleeping.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            *delay = None;
+        }
+        match direction.lock().unwrap_or_else(PoisonError::into_inner).allowance(wanted) {
+            Ok(allowed) => return Poll::Ready(allowed),
+            Err(wait) => *delay = Some(Box::pin(sleep(wait))),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ThrottledStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if buf.remaining() == 0 {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let limit = match poll_allowance(&this.bandwidth.read, &mut this.read_delay, buf.remaining(), cx) {
+            Poll::Ready(limit) => limit,
+            Poll::Pending => return Poll::Pending,
+        };
+        let mut limited = buf.take(limit);
+        match Pin::new(&mut this.inner).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+        let read = limited.filled().len();
+        // The bytes were read into the unfilled part of `buf` through `limited`.
+        unsafe { buf.assume_init(read) };
+        buf.advance(read);
+        this.bandwidth
+            .read
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ThrottledStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if buf.is_empty() {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        let limit = match poll_allowance(&this.bandwidth.write, &mut this.write_delay, buf.len(), cx) {
+            Poll::Ready(limit) => limit,
+            Poll::Pending => return Poll::Pending,
+        };
+        let written = match Pin::new(&mut this.inner).poll_write(cx, &buf[..limit]) {
+            Poll::Ready(Ok(written)) => written,
+            other => return other,
+        };
+        this.bandwidth
+            .write
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .record(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::{info, warn};
+
+use crate::message::{ProtocolError, ProtocolLimits, ProverCodec, ProverMessage, Secret};
+
+// File layout: MAGIC, then per frame the direction, milliseconds since the connection was made
+// (u64 LE), the frame length (u32 LE) and the frame including its length prefix.
+const MAGIC: &[u8; 8] = b"AXMREC1\n";
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    fn byte(&self) -> u8 {
+        match self {
+            Self::Inbound => b'<',
+            Self::Outbound => b'>',
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.byte() as char)
+    }
+}
+
+/// One recorded frame.
+pub struct Record {
+    pub direction: Direction,
+    /// Milliseconds since the connection was made
+    pub offset: u64,
+    pub frame: Vec<u8>,
+}
+
+/// Writes the frames of one connection, the Authorize password is scrubbed.
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    failed: bool,
+}
+
+impl Recorder {
+    /// Creates a new recording in `dir` named after the time and `server`.
+    pub fn create(dir: &Path, server: &str) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let name: String = server
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}-{}.axrec", now.as_millis(), name));
+        let file = File::create(&path).with_context(|| format!("unable to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        info!("Recording the pool traffic to {}", path.display());
+        Ok(Self {
+            path,
+            writer,
+            started: Instant::now(),
+            failed: false,
+        })
+    }
+
+    fn record(&mut self, direction: Direction, frame: &[u8]) {
+        if self.failed {
+            return;
+        }
+        let offset = self.started.elapsed().as_millis() as u64;
+        let result = self
+            .writer
+            .write_all(&[direction.byte()])
+            .and_then(|()| self.writer.write_all(&offset.to_le_bytes()))
+            .and_then(|()| self.writer.write_all(&(frame.len() as u32).to_le_bytes()))
+            .and_then(|()| self.writer.write_all(frame))
+            .and_then(|()| self.writer.flush());
+        if let Err(e) = result {
+            warn!("Stopped recording to {}: {}", self.path.display(), e);
+            self.failed = true;
+        }
+    }
+}
+
+/// `ProverCodec` that also writes every frame to a `Recorder` when there is one.
+pub struct RecordingCodec {
+    codec: ProverCodec,
+    recorder: Option<Recorder>,
+}
+
+impl RecordingCodec {
+    pub fn new(codec: ProverCodec, recorder: Option<Recorder>) -> Self {
+        Self { codec, recorder }
+    }
+
+    /// Whether frames are sent as MessagePack, see `ProverCodec::use_msgpack`.
+    pub fn msgpack(&self) -> bool {
+        self.codec.msgpack()
+    }
+}
+
+impl Encoder<ProverMessage> for RecordingCodec {
+    type Error = ProtocolError;
+
+    fn encode(&mut self, item: ProverMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let recorder = match self.recorder.as_mut() {
+            Some(recorder) => recorder,
+            None => return self.codec.encode(item, dst),
+        };
+        if let ProverMessage::Authorize(account, worker, _, version) = &item {
+            let scrubbed = ProverMessage::Authorize(account.clone(), worker.clone(), Secret::from(REDACTED), *version);
+            let mut frame = BytesMut::new();
+            // Not counted, the actual Authorize is encoded below.
+            ProverCodec::default().encode(scrubbed, &mut frame)?;
+            recorder.record(Direction::Outbound, &frame);
+            return self.codec.encode(item, dst);
+        }
+        let start = dst.len();
+        self.codec.encode(item, dst)?;
+        recorder.record(Direction::Outbound, &dst[start..]);
+        Ok(())
+    }
+}
+
+impl Decoder for RecordingCodec {
+    type Error = ProtocolError;
+    type Item = ProverMessage;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if src.len() >= 4 {
+                let length = u32::from_le_bytes([src[0], src[1], src[2], src[3]]) as usize;
+                if src.len() >= 4 + length {
+                    recorder.record(Direction::Inbound, &src[..4 + length]);
+                }
+            }
+        }
+        self.codec.decode(src)
+    }
+}
+
+/// Reads a recording written by `Recorder`.
+pub fn read(path: &Path) -> Result<Vec<Record>> {
+    let file = File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("{} is not a traffic recording", path.display());
+    }
+    let mut records = Vec::new();
+    loop {
+        let mut direction = [0; 1];
+        if reader.read(&mut direction)? == 0 {
+            break;
+        }
+        let direction = match direction[0] {
+            b'<' => Direction::Inbound,
+            b'>' => Direction::Outbound,
+            other => return Err(anyhow!("invalid direction {:#04x} in record {}", other, records.len())),
+        };
+        let mut offset = [0; 8];
+        let mut length = [0; 4];
+        reader.read_exact(&mut offset)?;
+        reader.read_exact(&mut length)?;
+        let mut frame = Vec::new();
+        let length = u32::from_le_bytes(length) as u64;
+        reader.by_ref().take(length).read_to_end(&mut frame)?;
+        if frame.len() as u64 != length {
+            bail!("record {} is truncated", records.len());
+        }
+        records.push(Record {
+            direction,
+            offset: u64::from_le_bytes(offset),
+            frame,
+        });
+    }
+    Ok(records)
+}
+
+/// Decodes every frame of a recording and prints what was sent and received.
+pub fn replay(path: &Path, limits: ProtocolLimits) -> Result<()> {
+    let mut codec = ProverCodec::new(limits);
+    for (index, record) in read(path)?.into_iter().enumerate() {
+        let mut frame = BytesMut::from(&record.frame[..]);
+        let message = match codec.decode(&mut frame) {
+            Ok(Some(message)) => describe(&message),
+            Ok(None) => "incomplete frame".to_string(),
+            Err(e) => format!("undecodable frame: {}", e),
+        };
+        println!(
+            "{:>5} {:>9.3}s {} {:>8} bytes  {}",
+            index,
+            record.offset as f64 / 1000.0,
+            record.direction,
+            record.frame.len(),
+            message
+        );
+    }
+    Ok(())
+}
+
+fn describe(message: &ProverMessage) -> String {
+    match message {
+        ProverMessage::Authorize(account, worker, _, version) => {
+            format!("Authorize account {} worker {} version {}", account, worker, version)
+        }
+        ProverMessage::AuthorizeResult(result, message) => format!("AuthorizeResult {} {:?}", result, message),
+        ProverMessage::Notify(template, target, reorg, speculative) => {
+            let reorg = if *reorg { " reorg" } else { "" };
+            let speculative = if *speculative { " speculative" } else { "" };
+            format!("Notify height {} target {}{}{}", template.block_height(), target, reorg, speculative)
+        }
+        ProverMessage::Submit(height, nonce, _) => format!("Submit height {} nonce {}", height, nonce),
+        ProverMessage::SubmitResult(code, message) => format!("SubmitResult {:?} {:?}", code, message),
+        ProverMessage::ProofRate(rate) => format!("ProofRate {}", rate),
+        ProverMessage::ActivateJob(height) => format!("ActivateJob height {}", height),
+        ProverMessage::JobAck(height, target) => format!("JobAck height {} target {}", height, target),
+        ProverMessage::PoolInfo(fields) => format!("PoolInfo {:?}", fields),
+        ProverMessage::Worker(worker) => format!("Worker {}", worker),
+        ProverMessage::Canary => "Canary".to_string(),
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use futures::future::{BoxFuture, FutureExt};
+use tokio::{
+    io::{duplex, AsyncRead, AsyncWrite, DuplexStream},
+    net::TcpStream,
+    sync::mpsc,
+    time::timeout,
+};
+
+use crate::client::ClientError;
+
+/// Byte stream to the pool.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+/// Opens the connections of a `Client`.
+pub trait Connector: Send + Sync {
+    /// Connects to `server`, the pool address as configured.
+    fn connect<'a>(&'a self, server: &'a str) -> BoxFuture<'a, Result<Box<dyn Transport>, ClientError>>;
+
+    /// TLS protocol version and cipher suite of the connections, `None` for plain ones.
+    fn tls(&self) -> Option<String> {
+        None
+    }
+
+    /// Compression of the byte stream, `None` if it goes uncompressed.
+    fn compression(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Plain TCP, the default.
+pub struct TcpConnector {
+    timeout: Duration,
+}
+
+impl TcpConnector {
+    /// Gives up on connections taking longer than `timeout`, resolving the address included.
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl Default for TcpConnector {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl Connector for TcpConnector {
+    fn connect<'a>(&'a self, server: &'a str) -> BoxFuture<'a, Result<Box<dyn Transport>, ClientError>> {
+        async move {
+            match timeout(self.timeout, TcpStream::connect(server)).await {
+                Ok(Ok(socket)) => Ok(Box::new(socket) as Box<dyn Transport>),
+                Ok(Err(source)) => Err(ClientError::Connect {
+                    server: server.to_string(),
+                    source,
+                }),
+                Err(_) => Err(ClientError::Timeout {
+                    server: server.to_string(),
+                }),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// In-memory connections, the pool side of each one is sent to the receiver returned by `new`.
+pub struct DuplexConnector {
+    pools: mpsc::UnboundedSender<(String, DuplexStream)>,
+    buffer: usize,
+}
+
+impl DuplexConnector {
+    /// `buffer` is the number of bytes each direction holds before writes wait.
+    pub fn new(buffer: usize) -> (Self, mpsc::UnboundedReceiver<(String, DuplexStream)>) {
+        let (pools, receiver) = mpsc::unbounded_channel();
+        (Self { pools, buffer }, receiver)
+    }
+}
+
+impl Connector for DuplexConnector {
+    fn connect<'a>(&'a self, server: &'a str) -> BoxFuture<'a, Result<Box<dyn Transport>, ClientError>> {
+        async move {
+            let (client, pool) = duplex(self.buffer);
+            self.pools.send((server.to_string(), pool)).map_err(|_| ClientError::Connect {
+                server: server.to_string(),
+                source: std::io::ErrorKind::ConnectionRefused.into(),
+            })?;
+            Ok(Box::new(client) as Box<dyn Transport>)
+        }
+        .boxed()
+    }
+}
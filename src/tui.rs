@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Stdout},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Sparkline, Wrap},
+    Frame,
+    Terminal,
+};
+use tokio::{runtime::Handle, sync::broadcast::error::TryRecvError, task};
+
+use crate::{
+    client::Client,
+    events::MinerEvent,
+    logging::LogBuffer,
+    prover::{PauseReason, Prover, ProverStats},
+    status::Status,
+    units,
+};
+
+const HISTORY: usize = 60;
+const SHARE_EVENTS: usize = 8;
+const TICK: Duration = Duration::from_millis(250);
+
+/// Dashboard state derived from successive stats snapshots and miner events.
+struct Dashboard {
+    device_history: Vec<VecDeque<u64>>,
+    last_counts: Vec<u64>,
+    share_events: VecDeque<String>,
+    scroll: u16,
+}
+
+impl Dashboard {
+    fn new() -> Self {
+        Self {
+            device_history: Vec::new(),
+            last_counts: Vec::new(),
+            share_events: VecDeque::new(),
+            scroll: 0,
+        }
+    }
+
+    /// Samples the per-device proof counts, called once per second.
+    fn sample(&mut self, stats: &ProverStats) {
+        let counts: Vec<u64> = stats
+            .worker_latency
+            .iter()
+            .take(stats.devices.len())
+            .map(|latency| latency.count)
+            .collect();
+        self.device_history.resize_with(counts.len(), VecDeque::new);
+        self.last_counts.resize(counts.len(), 0);
+        for (device, count) in counts.iter().enumerate() {
+            let history = &mut self.device_history[device];
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(count.saturating_sub(self.last_counts[device]));
+        }
+        self.last_counts = counts;
+    }
+
+    fn event(&mut self, event: &MinerEvent) {
+        let time = chrono::Local::now().format("%H:%M:%S");
+        let line = match event {
+            MinerEvent::ShareAccepted(result) => format!("{} accepted (block {})", time, result.height),
+            MinerEvent::ShareRejected(result) => {
+                format!("{} rejected (block {}, {:?})", time, result.height, result.code)
+            }
+            _ => return,
+        };
+        if self.share_events.len() == SHARE_EVENTS {
+            self.share_events.pop_front();
+        }
+        self.share_events.push_back(line);
+    }
+}
+
+fn draw(frame: &mut Frame<CrosstermBackend<Stdout>>, dashboard: &Dashboard, status: &Status, logs: &[String]) {
+    let devices = dashboard.device_history.len() as u16;
+    let header_lines = if status.gpus.is_empty() { 1 } else { 2 };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(header_lines + 2),
+            Constraint::Length(devices * 3),
+            Constraint::Length(SHARE_EVENTS as u16 + 3),
+            Constraint::Min(5),
+        ])
+        .split(frame.size());
+
+    let connection = match (status.connected, status.authorized, status.paused) {
+        (_, _, true) => Span::styled("paused", Style::default().fg(Color::Yellow)),
+        (true, true, _) => Span::styled("connected", Style::default().fg(Color::Green)),
+        (true, false, _) => Span::styled("authorizing", Style::default().fg(Color::Yellow)),
+        (false, _, _) => Span::styled("disconnected", Style::default().fg(Color::Red)),
+    };
+    let mut header = vec![Line::from(vec![
+        Span::raw(format!(
+            "{}  worker {}  height {}  uptime {}s  ",
+            status.pool, status.worker, status.height, status.uptime
+        )),
+        connection,
+        Span::styled(
+            if status.dry_run { "  DRY RUN" } else { "" },
+            Style::default().fg(Color::Red),
+        ),
+        Span::raw("   q: quit  p: pause/resume"),
+    ])];
+    if !status.gpus.is_empty() {
+        let gpus: Vec<String> = status.gpus.iter().map(|gpu| gpu.to_string()).collect();
+        header.push(Line::from(gpus.join("  ")));
+    }
+    frame.render_widget(
+        Paragraph::new(header).block(Block::default().borders(Borders::ALL).title(" AleoXMiner ")),
+        rows[0],
+    );
+
+    let device_rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(3); devices as usize])
+        .split(rows[1]);
+    for ((history, device), area) in dashboard
+        .device_history
+        .iter()
+        .zip(status.devices.iter())
+        .zip(device_rows.iter())
+    {
+        let data: Vec<u64> = history.iter().copied().collect();
+        let title = format!(" {} {} {} ", device.name, device.state, units::format_rate(device.rate));
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .data(&data)
+                .style(Style::default().fg(Color::Cyan)),
+            *area,
+        );
+    }
+
+    let total = status.shares.accepted + status.shares.rejected;
+    let percent = if total > 0 {
+        status.shares.accepted as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    };
+    let mut shares = vec![Line::from(format!(
+        "accepted {}  rejected {}  stale {}  ({:.2}%)",
+        status.shares.accepted, status.shares.rejected, status.shares.stale, percent
+    ))];
+    shares.extend(dashboard.share_events.iter().rev().map(|event| Line::from(event.clone())));
+    frame.render_widget(
+        Paragraph::new(shares).block(Block::default().borders(Borders::ALL).title(" Shares ")),
+        rows[2],
+    );
+
+    // Follow the latest lines unless scrolled up.
+    let height = rows[3].height.saturating_sub(2) as usize;
+    let bottom = logs.len().saturating_sub(height) as u16;
+    let offset = bottom.saturating_sub(dashboard.scroll);
+    let lines: Vec<Line> = logs.iter().map(|line| Line::from(line.clone())).collect();
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(" Log (up/down to scroll) "))
+            .wrap(Wrap { trim: false })
+            .scroll((offset, 0)),
+        rows[3],
+    );
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+}
+
+/// Runs the dashboard until `q` is pressed. The terminal is restored on return and on panic.
+pub async fn run(prover: Arc<Prover>, client: Arc<Client>, logs: LogBuffer) -> Result<()> {
+    let handle = Handle::current();
+    let bus = client.events().clone();
+    let mut events = bus.subscribe();
+    task::spawn_blocking(move || -> Result<()> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            default_hook(info);
+        }));
+        let result = (|| -> Result<()> {
+            let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+            let mut dashboard = Dashboard::new();
+            let mut last_sample = Instant::now() - Duration::from_secs(1);
+            loop {
+                loop {
+                    match events.try_recv() {
+                        Ok(event) => dashboard.event(&event),
+                        Err(TryRecvError::Lagged(missed)) => bus.record_lag(missed),
+                        Err(_) => break,
+                    }
+                }
+                let stats = prover.stats();
+                if last_sample.elapsed() >= Duration::from_secs(1) {
+                    dashboard.sample(&stats);
+                    last_sample = Instant::now();
+                }
+                let status = Status::new(&stats, &client.stats(), &client.server(), &client.worker());
+                let lines = logs.lines();
+                terminal.draw(|frame| draw(frame, &dashboard, &status, &lines))?;
+
+                if !event::poll(TICK)? {
+                    continue;
+                }
+                if let Event::Key(key) = event::read()? {
+                    match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        // Raw mode swallows the interrupt signal.
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                        KeyCode::Char('p') => {
+                            if stats.pause_reasons.contains(&PauseReason::Manual) {
+                                handle.block_on(prover.resume(PauseReason::Manual));
+                            } else {
+                                handle.block_on(prover.pause(PauseReason::Manual));
+                            }
+                        }
+                        KeyCode::Up => dashboard.scroll = dashboard.scroll.saturating_add(1),
+                        KeyCode::Down => dashboard.scroll = dashboard.scroll.saturating_sub(1),
+                        KeyCode::PageUp => dashboard.scroll = dashboard.scroll.saturating_add(10),
+                        KeyCode::PageDown => dashboard.scroll = dashboard.scroll.saturating_sub(10),
+                        KeyCode::End => dashboard.scroll = 0,
+                        _ => {}
+                    }
+                }
+            }
+        })();
+        restore_terminal();
+        result
+    })
+    .await?
+}
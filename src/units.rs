@@ -0,0 +1,144 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use anyhow::{anyhow, Error};
+
+/// Unit proof rates are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    PerSecond,
+    KiloPerSecond,
+    /// Largest unit keeping the value at or above 1
+    Auto,
+}
+
+impl FromStr for RateUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "p/s" => Ok(Self::PerSecond),
+            "kp/s" => Ok(Self::KiloPerSecond),
+            "auto" => Ok(Self::Auto),
+            _ => Err(anyhow!("invalid rate unit {}: expected p/s, kp/s or auto", s)),
+        }
+    }
+}
+
+impl Display for RateUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PerSecond => write!(f, "p/s"),
+            Self::KiloPerSecond => write!(f, "kp/s"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+static RATE_UNIT: AtomicU8 = AtomicU8::new(RateUnit::Auto as u8);
+
+/// Sets the unit used by `format_rate`, called once at startup.
+pub fn set_rate_unit(unit: RateUnit) {
+    RATE_UNIT.store(unit as u8, Ordering::Relaxed);
+}
+
+fn rate_unit() -> RateUnit {
+    match RATE_UNIT.load(Ordering::Relaxed) {
+        0 => RateUnit::PerSecond,
+        1 => RateUnit::KiloPerSecond,
+        _ => RateUnit::Auto,
+    }
+}
+
+const PREFIXES: [&str; 4] = ["", "k", "M", "G"];
+
+/// Formats a rate in proofs per second with the configured unit, e.g. `54.21 p/s` or `1.23 kp/s`.
+pub fn format_rate(rate: f64) -> String {
+    format_rate_in(rate, rate_unit())
+}
+
+/// Formats with two decimals and a dot as separator regardless of the locale.
+pub fn format_rate_in(rate: f64, unit: RateUnit) -> String {
+    if !rate.is_finite() {
+        return "---".to_string();
+    }
+    let (value, prefix) = match unit {
+        RateUnit::PerSecond => (rate, 0),
+        RateUnit::KiloPerSecond => (rate / 1000.0, 1),
+        RateUnit::Auto => {
+            let mut value = rate;
+            let mut prefix = 0;
+            // Switch units on the rounded value so 999.999 shows as 1.00 kp/s, not 1000.00 p/s.
+            while prefix < PREFIXES.len() - 1 && (value * 100.0).round().abs() >= 100_000.0 {
+                value /= 1000.0;
+                prefix += 1;
+            }
+            (value, prefix)
+        }
+    };
+    // Avoid printing -0.00 for tiny negative rounding noise.
+    let value = if (value * 100.0).round() == 0.0 { 0.0 } else { value };
+    format!("{:.2} {}p/s", value, PREFIXES[prefix])
+}
+
+/// Formats an optional rate, `---` while there isn't enough data.
+pub fn format_optional_rate(rate: Option<f64>) -> String {
+    rate.map(format_rate).unwrap_or_else(|| "---".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_over_to_the_next_unit_on_the_rounded_value() {
+        let cases = [
+            (0.0, "0.00 p/s"),
+            (54.21, "54.21 p/s"),
+            (999.99, "999.99 p/s"),
+            (999.999, "1.00 kp/s"),
+            (1000.0, "1.00 kp/s"),
+            (1234.5, "1.23 kp/s"),
+            (999_990.0, "999.99 kp/s"),
+            (999_999.0, "1.00 Mp/s"),
+            (999_999_999.0, "1.00 Gp/s"),
+            (1e9, "1.00 Gp/s"),
+            // Nothing above giga, the value grows instead.
+            (1e12, "1000.00 Gp/s"),
+        ];
+        for (rate, expected) in cases {
+            assert_eq!(format_rate_in(rate, RateUnit::Auto), expected, "rate {}", rate);
+        }
+    }
+
+    #[test]
+    fn fixed_units_never_roll_over() {
+        assert_eq!(format_rate_in(0.0, RateUnit::PerSecond), "0.00 p/s");
+        assert_eq!(format_rate_in(1234.5, RateUnit::PerSecond), "1234.50 p/s");
+        assert_eq!(format_rate_in(0.0, RateUnit::KiloPerSecond), "0.00 kp/s");
+        assert_eq!(format_rate_in(54.21, RateUnit::KiloPerSecond), "0.05 kp/s");
+        assert_eq!(format_rate_in(2_500_000.0, RateUnit::KiloPerSecond), "2500.00 kp/s");
+    }
+
+    #[test]
+    fn rounding_noise_and_missing_rates() {
+        assert_eq!(format_rate_in(-0.001, RateUnit::Auto), "0.00 p/s");
+        assert_eq!(format_rate_in(-0.001, RateUnit::KiloPerSecond), "0.00 kp/s");
+        assert_eq!(format_rate_in(f64::NAN, RateUnit::Auto), "---");
+        assert_eq!(format_rate_in(f64::INFINITY, RateUnit::PerSecond), "---");
+        assert_eq!(format_optional_rate(None), "---");
+    }
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(" KP/S ".parse::<RateUnit>().unwrap(), RateUnit::KiloPerSecond);
+        assert_eq!("p/s".parse::<RateUnit>().unwrap(), RateUnit::PerSecond);
+        assert!("mp/s".parse::<RateUnit>().is_err());
+        for unit in [RateUnit::PerSecond, RateUnit::KiloPerSecond, RateUnit::Auto] {
+            assert_eq!(unit.to_string().parse::<RateUnit>().unwrap(), unit);
+        }
+    }
+}
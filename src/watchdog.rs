@@ -0,0 +1,148 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Attempts are never flagged before running this long, whatever the median latency is.
+const MIN_STALL: Duration = Duration::from_secs(30);
+
+/// Per-worker timestamps of the attempt currently being proven.
+pub struct Heartbeats {
+    origin: Instant,
+    /// Microseconds since `origin` plus one, 0 while the worker is between attempts
+    started: Vec<AtomicU64>,
+}
+
+impl Heartbeats {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            origin: Instant::now(),
+            started: (0..workers).map(|_| Default::default()).collect(),
+        }
+    }
+
+    pub fn started(&self, worker: usize) {
+        let stamp = self.origin.elapsed().as_micros() as u64 + 1;
+        self.started[worker].store(stamp, Ordering::SeqCst);
+    }
+
+    pub fn finished(&self, worker: usize) {
+        self.started[worker].store(0, Ordering::SeqCst);
+    }
+
+    pub fn running(&self, worker: usize) -> bool {
+        self.started[worker].load(Ordering::SeqCst) != 0
+    }
+
+    /// Start stamp of the running attempt of every worker.
+    pub fn snapshot(&self) -> Vec<u64> {
+        self.started.iter().map(|s| s.load(Ordering::SeqCst)).collect()
+    }
+
+    pub fn elapsed(&self, stamp: u64) -> Duration {
+        self.origin
+            .elapsed()
+            .saturating_sub(Duration::from_micros(stamp.saturating_sub(1)))
+    }
+}
+
+/// Flags every attempt running longer than `multiple` times the median latency, once per attempt.
+pub struct Watchdog {
+    multiple: f64,
+    flagged: Vec<u64>,
+}
+
+impl Watchdog {
+    pub fn new(workers: usize, multiple: f64) -> Self {
+        Self {
+            multiple,
+            flagged: vec![0; workers],
+        }
+    }
+
+    /// Returns the newly stuck workers with the time their attempt has been running.
+    pub fn check(&mut self, heartbeats: &Heartbeats, median: Option<Duration>) -> Vec<(usize, Duration)> {
+        let limit = match median {
+            Some(median) => median.mul_f64(self.multiple).max(MIN_STALL),
+            None => return Vec::new(),
+        };
+        let mut stuck = Vec::new();
+        for (worker, stamp) in heartbeats.snapshot().into_iter().enumerate() {
+            if stamp == 0 || self.flagged[worker] == stamp {
+                continue;
+            }
+            let elapsed = heartbeats.elapsed(stamp);
+            if elapsed > limit {
+                self.flagged[worker] = stamp;
+                stuck.push((worker, elapsed));
+            }
+        }
+        stuck
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000;
+
+    // Heartbeats of `workers` created two minutes ago.
+    fn heartbeats(workers: usize) -> Heartbeats {
+        Heartbeats {
+            origin: Instant::now() - Duration::from_secs(120),
+            ..Heartbeats::new(workers)
+        }
+    }
+
+    // Marks `worker` as proving for the last `secs` seconds.
+    fn proving_for(heartbeats: &Heartbeats, worker: usize, secs: u64) {
+        heartbeats.started[worker].store((120 - secs) * SECOND + 1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn tracks_running_attempts() {
+        let heartbeats = Heartbeats::new(2);
+        heartbeats.started(1);
+        assert!(!heartbeats.running(0));
+        assert!(heartbeats.running(1));
+        assert!(heartbeats.elapsed(heartbeats.snapshot()[1]) < Duration::from_secs(1));
+        heartbeats.finished(1);
+        assert_eq!(heartbeats.snapshot(), vec![0, 0]);
+    }
+
+    #[test]
+    fn flags_a_hung_attempt_once() {
+        let heartbeats = heartbeats(3);
+        proving_for(&heartbeats, 0, 1);
+        proving_for(&heartbeats, 2, 60);
+        let mut watchdog = Watchdog::new(3, 10.0);
+
+        let stuck = watchdog.check(&heartbeats, Some(Duration::from_millis(500)));
+        assert_eq!(stuck.iter().map(|(worker, _)| *worker).collect::<Vec<_>>(), vec![2]);
+        assert!(stuck[0].1 >= Duration::from_secs(60));
+        assert!(watchdog.check(&heartbeats, Some(Duration::from_millis(500))).is_empty());
+
+        // The next attempt hanging is a new intervention.
+        proving_for(&heartbeats, 2, 45);
+        assert_eq!(watchdog.check(&heartbeats, Some(Duration::from_millis(500))).len(), 1);
+    }
+
+    #[test]
+    fn allows_a_multiple_of_the_median() {
+        let heartbeats = heartbeats(1);
+        proving_for(&heartbeats, 0, 60);
+        let mut watchdog = Watchdog::new(1, 10.0);
+        assert!(watchdog.check(&heartbeats, Some(Duration::from_secs(10))).is_empty());
+        assert_eq!(watchdog.check(&heartbeats, Some(Duration::from_secs(5))).len(), 1);
+    }
+
+    #[test]
+    fn waits_for_a_median_and_the_minimum_stall() {
+        let heartbeats = heartbeats(1);
+        proving_for(&heartbeats, 0, 20);
+        let mut watchdog = Watchdog::new(1, 10.0);
+        assert!(watchdog.check(&heartbeats, None).is_empty());
+        assert!(watchdog.check(&heartbeats, Some(Duration::from_millis(1))).is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+// The counters of an instrumented channel driven past its capacity.
+
+use std::time::Duration;
+
+use aleoxminer::channel;
+use tokio::sync::mpsc::error::TrySendError;
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn counts_overflows_and_the_high_watermark_of_a_saturated_channel() {
+    let (sender, mut receiver) = channel::channel("saturated", 4);
+    for value in 0..4 {
+        sender.try_send(value).unwrap();
+    }
+    assert!(matches!(sender.try_send(4), Err(TrySendError::Full(4))));
+    let stats = sender.metrics().stats();
+    assert_eq!((stats.name, stats.capacity), ("saturated", 4));
+    assert_eq!((stats.high_watermark, stats.sends, stats.overflows), (4, 4, 1));
+
+    // A send into the full channel waits for room.
+    let blocked = sender.clone();
+    let send = tokio::spawn(async move { blocked.send(5).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!send.is_finished());
+    assert_eq!(receiver.recv().await, Some(0));
+    send.await.unwrap().unwrap();
+    let stats = sender.metrics().stats();
+    assert_eq!((stats.high_watermark, stats.sends, stats.overflows), (4, 5, 2));
+    assert!(stats.send_wait >= Duration::from_millis(40), "{:?}", stats.send_wait);
+
+    // Draining keeps the watermark.
+    while receiver.try_recv().is_ok() {}
+    sender.try_send(6).unwrap();
+    assert_eq!(sender.metrics().stats().high_watermark, 4);
+}
+
+#[cfg(not(feature = "metrics"))]
+#[tokio::test]
+async fn records_nothing_without_the_metrics_feature() {
+    let (sender, _receiver) = channel::channel("saturated", 1);
+    sender.try_send(0).unwrap();
+    assert!(matches!(sender.try_send(1), Err(TrySendError::Full(1))));
+    let stats = sender.metrics().stats();
+    assert_eq!(
+        (stats.high_watermark, stats.sends, stats.overflows, stats.send_wait),
+        (0, 0, 0, Duration::ZERO)
+    );
+}
@@ -0,0 +1,144 @@
+// The codec and the client loop behind a stream injecting network faults: fragments, stalls, garbage
+// and early ends. Run with `cargo test --features chaos`.
+#![cfg(feature = "chaos")]
+
+mod common;
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use aleoxminer::{
+    client,
+    message::{ProtocolError, ProtocolLimits, ProverCodec, ProverMessage},
+    prover::Prover,
+    testing::{self, ChaosStream, FakeBackend, MockPool, ALL_SHARES},
+    transport::DuplexConnector,
+};
+use bytes::BytesMut;
+use common::{eventually, LIMIT};
+use tokio::io::AsyncWriteExt;
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Encoder, FramedRead};
+
+// The first `count` fixture messages and their frames back to back.
+fn frames(count: usize) -> (Vec<ProverMessage>, Vec<Vec<u8>>) {
+    let mut codec = ProverCodec::default();
+    let messages: Vec<_> = testing::messages().into_iter().map(|(_, message)| message).take(count).collect();
+    let frames = messages
+        .iter()
+        .map(|message| {
+            let mut frame = BytesMut::new();
+            codec.encode(message.clone(), &mut frame).unwrap();
+            frame.to_vec()
+        })
+        .collect();
+    (messages, frames)
+}
+
+// What a codec enforcing `limits` reads from `bytes` through the faults of `chaos`, until the stream ends.
+async fn read(chaos: &str, bytes: Vec<u8>, limits: ProtocolLimits) -> Vec<Result<ProverMessage, ProtocolError>> {
+    let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+    // The reader stops at the first error, the rest of the bytes may have nowhere to go.
+    tokio::spawn(async move { writer.write_all(&bytes).await });
+    let stream = ChaosStream::new(reader, &chaos.parse().unwrap());
+    FramedRead::new(stream, ProverCodec::new(limits)).collect().await
+}
+
+// Limits no fixture message but Notify and Submit exceeds, random bytes are very unlikely to pass.
+fn small() -> ProtocolLimits {
+    ProtocolLimits {
+        max_frame_size: 1024,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn fragments_and_stalls_deliver_every_frame() {
+    let (messages, frames) = frames(usize::MAX);
+    for seed in 0..8 {
+        let chaos = format!("seed={},fragment=7,stall@50:20ms,stall@3000:20ms", seed);
+        let decoded = read(&chaos, frames.concat(), ProtocolLimits::default()).await;
+        let decoded: Vec<_> = decoded.into_iter().map(|message| message.unwrap()).collect();
+        assert_eq!(decoded, messages, "{}", chaos);
+    }
+}
+
+#[tokio::test]
+async fn garbage_ends_the_stream_without_resynchronizing() {
+    let (messages, frames) = frames(3);
+    assert!(frames.iter().all(|frame| frame.len() <= 1024));
+    for seed in 0..8 {
+        let chaos = format!("seed={},garbage@{}:8", seed, frames[0].len());
+        let decoded = read(&chaos, frames.concat(), small()).await;
+        // The frames after the garbage are never read.
+        assert_eq!(decoded.len(), 2, "{}: {:?}", chaos, decoded);
+        assert_eq!(decoded[0].as_ref().unwrap(), &messages[0]);
+        assert!(decoded[1].is_err(), "{}", chaos);
+    }
+}
+
+#[tokio::test]
+async fn an_end_inside_a_frame_is_an_error() {
+    let (messages, frames) = frames(3);
+    let offset = frames[0].len() + frames[1].len() / 2;
+    let decoded = read(&format!("close@{}", offset), frames.concat(), small()).await;
+    assert_eq!(decoded.len(), 2, "{:?}", decoded);
+    assert_eq!(decoded[0].as_ref().unwrap(), &messages[0]);
+    assert!(matches!(decoded[1], Err(ProtocolError::Io(_))), "{:?}", decoded[1]);
+
+    // At a frame boundary the stream just ends.
+    let decoded = read(&format!("close@{}", frames[0].len()), frames.concat(), small()).await;
+    assert_eq!(decoded.len(), 1);
+}
+
+// A mock pool over a duplex pipe and a client reading it through the faults of `chaos`.
+fn chaotic_pool(chaos: &str) -> (MockPool, Arc<client::Client>) {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let pool = MockPool::duplex(connections);
+    let client = pool.client("chaos");
+    client.set_connector(Arc::new(connector));
+    client.set_chaos(Some(chaos.parse().unwrap()));
+    (pool, client)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnects_with_a_pause_after_every_broken_stream() {
+    // Every connection ends in the length prefix of the AuthorizeResult.
+    let (pool, client) = chaotic_pool("close@2");
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    let started = Instant::now();
+    client::start(prover.event_sender(), client.clone());
+    let received = pool.wait_for(3 * LIMIT, |received| received.connections >= 3).await.unwrap();
+    // 5 seconds between attempts.
+    assert!(started.elapsed() >= Duration::from_secs(10), "{:?}", started.elapsed());
+    assert!(received.authorizations.len() >= 2);
+    assert!(!client.stats().authorized);
+    assert!(eventually(LIMIT, || client.stats().reconnects >= 2).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mines_through_fragments_stalls_and_reconnects_without_sending_a_share_twice() {
+    let (pool, client) = chaotic_pool("seed=11,fragment=9,stall@4000:300ms,stall@9000:300ms");
+    pool.notify(testing::template(2), ALL_SHARES);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    pool.wait_for(LIMIT, |received| received.shares.len() >= 10).await.unwrap();
+    pool.disconnect();
+    let received = pool
+        .wait_for(2 * LIMIT, |received| received.connections >= 2 && received.shares.len() >= 20)
+        .await
+        .unwrap();
+    prover.stop().await;
+
+    let nonces: HashSet<_> = received.shares.iter().map(|(_, nonce)| nonce.to_string()).collect();
+    assert_eq!(nonces.len(), received.shares.len(), "a share was sent twice");
+    assert_eq!(client.stats().unmatched_results, 0);
+    // Only the results in flight when the pool hung up can be missing.
+    assert!(eventually(LIMIT, || prover.stats().valid_shares >= 10).await);
+}
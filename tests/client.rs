@@ -0,0 +1,725 @@
+// The client's connection loop against the mock pool over in-memory pipes, no sockets involved.
+
+mod common;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use aleoxminer::{
+    channel,
+    client::{self, Client, ConnectionInfo, PoolCredentials},
+    message::{
+        Code,
+        ProverCodec,
+        ProverMessage,
+        AUTHORIZATION_REVOKED,
+        JOB_ACK_CAPABILITY,
+        MSGPACK_CAPABILITY,
+        POOL_INFO_CAPABILITY,
+        SPECULATIVE_CAPABILITY,
+    },
+    prover::{Prover, ProverEvent},
+    stats::SessionTokens,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+    transport::DuplexConnector,
+};
+use common::{eventually, scratch, LIMIT};
+use futures_util::SinkExt;
+use tokio::{
+    io::DuplexStream,
+    sync::mpsc::{Receiver, UnboundedReceiver},
+    time::{timeout, timeout_at, Instant},
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+// A mock pool and a client connecting to it through `DuplexConnector`.
+fn duplex_pool(worker: &str) -> (MockPool, Arc<Client>) {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let pool = MockPool::duplex(connections);
+    let client = pool.client(worker);
+    client.set_connector(Arc::new(connector));
+    (pool, client)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mines_over_a_duplex_pipe() {
+    let (pool, client) = duplex_pool("duplex");
+    pool.notify(testing::template(2), ALL_SHARES);
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(16, backend.clone()), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+
+    let received = pool.wait_for(LIMIT, |received| received.shares.len() >= 3).await.unwrap();
+    assert_eq!(received.connections, 1);
+    assert_eq!(received.authorizations.len(), 1);
+    assert_eq!(received.authorizations[0].1, "duplex");
+    assert!(received.shares.iter().all(|(height, _)| *height == 2));
+    assert!(client.stats().connected);
+    assert!(eventually(LIMIT, || prover.stats().valid_shares >= 3).await);
+    prover.stop().await;
+}
+
+// Wire format of the authorized connection and the version the pool received.
+async fn negotiated(msgpack: bool) -> (&'static str, u16) {
+    negotiated_with(msgpack, false).await
+}
+
+async fn negotiated_with(msgpack: bool, extensions: bool) -> (&'static str, u16) {
+    let (pool, client) = duplex_pool("negotiation");
+    client.set_msgpack(msgpack);
+    client.set_speculative(extensions);
+    client.set_accept_pool_info(extensions);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    client::start(prover.event_sender(), client.clone());
+    let received = pool.wait_for(LIMIT, |received| !received.authorizations.is_empty()).await.unwrap();
+    assert!(eventually(LIMIT, || client.stats().connection.is_some()).await);
+    (client.stats().connection.unwrap().wire_format, received.authorizations[0].2)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn negotiates_msgpack_when_offered() {
+    let (format, version) = negotiated(true).await;
+    assert_eq!(format, "msgpack");
+    assert_ne!(version & MSGPACK_CAPABILITY, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stays_on_json_without_an_offer() {
+    let (format, version) = negotiated(false).await;
+    assert_eq!(format, "json");
+    assert_eq!(version & MSGPACK_CAPABILITY, 0);
+}
+
+// Version the pool received and the JobAcks it got while the prover reported two proof rates.
+async fn job_acks(job_ack: bool) -> (u16, Vec<(u32, u64)>) {
+    let (pool, client) = duplex_pool("job-ack");
+    client.set_job_ack(job_ack);
+    pool.notify(testing::template(2), ALL_SHARES);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    let received = pool.wait_for(LIMIT, |received| received.proof_rates.len() >= 2).await.unwrap();
+    prover.stop().await;
+    (received.authorizations[0].2, received.job_acks)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sends_job_acks_only_when_offered() {
+    let (version, acks) = job_acks(true).await;
+    assert_eq!(version & JOB_ACK_CAPABILITY, JOB_ACK_CAPABILITY);
+    assert!(!acks.is_empty());
+    assert!(acks.iter().all(|(height, _)| *height == 2), "{:?}", acks);
+    let (version, acks) = job_acks(false).await;
+    assert_eq!(version & JOB_ACK_CAPABILITY, 0);
+    assert!(acks.is_empty(), "{:?}", acks);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offers_speculative_jobs_and_pool_info_only_when_set() {
+    let (_, version) = negotiated_with(false, false).await;
+    assert_eq!(version & (SPECULATIVE_CAPABILITY | POOL_INFO_CAPABILITY), 0);
+    let (_, version) = negotiated_with(false, true).await;
+    assert_eq!(version & SPECULATIVE_CAPABILITY, SPECULATIVE_CAPABILITY);
+    assert_eq!(version & POOL_INFO_CAPABILITY, POOL_INFO_CAPABILITY);
+}
+
+// A client taking speculative jobs, mining block 2 on a mock pool.
+async fn speculating() -> (MockPool, Arc<Client>, Arc<Prover>) {
+    let (pool, client) = duplex_pool("speculative");
+    client.set_speculative(true);
+    pool.notify(testing::template(2), ALL_SHARES);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    pool.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+    (pool, client, prover)
+}
+
+// Waits for a share for `height` submitted after the call.
+async fn mines(pool: &MockPool, height: u32) {
+    let before = pool.received().shares.len();
+    pool.wait_for(LIMIT, |received| received.shares[before..].iter().any(|(mined, _)| *mined == height))
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn activates_a_speculative_job() {
+    let (pool, client, prover) = speculating().await;
+    pool.speculative(testing::template(3), ALL_SHARES);
+    pool.activate(3);
+    mines(&pool, 3).await;
+    let stats = client.stats();
+    assert_eq!((stats.speculative_activated, stats.speculative_expired), (1, 0));
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_newer_speculative_job_supersedes_the_held_one() {
+    let (pool, client, prover) = speculating().await;
+    pool.speculative(testing::template(3), ALL_SHARES);
+    pool.speculative(testing::template(4), ALL_SHARES);
+    // The held job is gone, activating it does nothing.
+    pool.activate(3);
+    pool.activate(4);
+    mines(&pool, 4).await;
+    let stats = client.stats();
+    assert_eq!((stats.speculative_activated, stats.speculative_expired), (1, 1));
+    assert!(pool.received().shares.iter().all(|(height, _)| *height != 3));
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_never_activated_speculative_job_expires() {
+    let (pool, client, prover) = speculating().await;
+    pool.speculative(testing::template(3), ALL_SHARES);
+    pool.notify(testing::template(4), ALL_SHARES);
+    mines(&pool, 4).await;
+    pool.activate(3);
+    pool.notify(testing::template(5), ALL_SHARES);
+    mines(&pool, 5).await;
+    let stats = client.stats();
+    assert_eq!((stats.speculative_activated, stats.speculative_expired), (0, 1));
+    assert!(pool.received().shares.iter().all(|(height, _)| *height != 3));
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gets_no_speculative_jobs_without_taking_them() {
+    let (pool, client) = duplex_pool("regular");
+    pool.notify(testing::template(2), ALL_SHARES);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    pool.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+    pool.speculative(testing::template(3), ALL_SHARES);
+    pool.activate(3);
+    pool.notify(testing::template(4), ALL_SHARES);
+    mines(&pool, 4).await;
+    let stats = client.stats();
+    assert_eq!((stats.speculative_activated, stats.speculative_expired), (0, 0));
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drops_a_share_queued_for_an_older_height() {
+    let (pool, client) = duplex_pool("stale");
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    client::start(prover.event_sender(), client.clone());
+    pool.notify(testing::template(2), ALL_SHARES);
+    assert!(eventually(LIMIT, || client.latest_height() == 2).await);
+
+    // The pool moves on to 3 while a share for 2 is still queued.
+    pool.notify(testing::template(3), ALL_SHARES);
+    assert!(eventually(LIMIT, || client.latest_height() == 3).await);
+    let header = testing::header();
+    for height in [2, 3] {
+        client.sender().send(ProverMessage::Submit(height, header.nonce(), header.proof().clone())).await.unwrap();
+    }
+
+    let received = pool.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+    assert_eq!(received.shares.iter().map(|(height, _)| *height).collect::<Vec<_>>(), vec![3]);
+    assert_eq!(client.local_stale(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_failover_authorizes_with_the_credentials_of_the_new_pool() {
+    let (primary, backup) = (MockPool::start().await.unwrap(), MockPool::start().await.unwrap());
+    let client = primary.client("rig1");
+    let overrides = PoolCredentials {
+        account: Some("backup-account".to_string()),
+        worker: Some("rig1-backup".to_string()),
+        ..Default::default()
+    };
+    client.set_pool_credentials(HashMap::from([(backup.address(), overrides)]));
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    client::start(prover.event_sender(), client.clone());
+    let address = testing::address().to_string();
+    let received = primary.wait_for(LIMIT, |received| !received.authorizations.is_empty()).await.unwrap();
+    assert_eq!((received.authorizations[0].0.as_str(), received.authorizations[0].1.as_str()), (&*address, "rig1"));
+    assert_eq!(client.worker(), "rig1");
+
+    // What the quality failover does on finding a better pool.
+    client.set_server(backup.address());
+    let received = backup.wait_for(LIMIT, |received| !received.authorizations.is_empty()).await.unwrap();
+    assert_eq!(received.authorizations[0].0, "backup-account");
+    assert_eq!(received.authorizations[0].1, "rig1-backup");
+    assert_eq!(client.worker(), "rig1-backup");
+
+    // Back on the primary, the top-level credentials again.
+    client.set_server(primary.address());
+    let received = primary.wait_for(LIMIT, |received| received.authorizations.len() == 2).await.unwrap();
+    assert_eq!(received.authorizations[1].0, address);
+    assert_eq!(received.authorizations[1].1, "rig1");
+    assert_eq!(backup.received().authorizations.len(), 1);
+}
+
+// A dry run with the proof rate sent as 0 or not at all, proving until `shares` would have been submitted.
+async fn dry_run(zero_rate: bool, shares: u64) -> MockPool {
+    let (pool, client) = duplex_pool("dry");
+    client.set_dry_run(zero_rate);
+    pool.notify(testing::template(2), ALL_SHARES);
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    assert!(eventually(LIMIT, || client.stats().would_submit >= shares).await);
+    // Past the first proof rate reports, which need two seconds of samples.
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+    prover.stop().await;
+    let stats = client.stats();
+    assert!(stats.dry_run);
+    assert!(stats.would_submit >= shares);
+    pool
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_dry_run_submits_nothing() {
+    let received = dry_run(false, 3).await.received();
+    assert_eq!(received.authorizations.len(), 1);
+    assert!(received.shares.is_empty(), "{} shares submitted", received.shares.len());
+    assert!(!received.messages.contains(&"Submit"));
+    assert!(received.proof_rates.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_dry_run_can_report_a_zero_proof_rate() {
+    let received = dry_run(true, 1).await.received();
+    assert!(received.shares.is_empty());
+    assert!(!received.proof_rates.is_empty());
+    assert!(received.proof_rates.iter().all(|rate| *rate == 0), "{:?}", received.proof_rates);
+}
+
+// A client connecting through `DuplexConnector` to a pool the test plays itself, the connections it
+// makes and the events it sends the prover.
+fn scripted(worker: &str) -> (Arc<Client>, UnboundedReceiver<(String, DuplexStream)>, Receiver<ProverEvent>) {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let client = testing::client("mock.pool:4040", worker);
+    client.set_connector(Arc::new(connector));
+    let (sender, events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    (client, connections, events)
+}
+
+// The pool's side of the next connection, once the miner asked for authorization.
+async fn accept(connections: &mut UnboundedReceiver<(String, DuplexStream)>) -> Framed<DuplexStream, ProverCodec> {
+    let (_, stream) = timeout(LIMIT, connections.recv()).await.expect("no connection").unwrap();
+    let mut pool = Framed::new(stream, ProverCodec::default());
+    let authorize = timeout(LIMIT, pool.next()).await.expect("no Authorize").unwrap().unwrap();
+    assert!(matches!(authorize, ProverMessage::Authorize(..)), "{:?}", authorize);
+    pool
+}
+
+// Heights of the jobs handed to the prover within `wait`.
+async fn jobs(events: &mut Receiver<ProverEvent>, wait: Duration) -> Vec<u32> {
+    let deadline = Instant::now() + wait;
+    let mut heights = Vec::new();
+    while let Ok(Some(event)) = timeout_at(deadline, events.recv()).await {
+        if let ProverEvent::NewWork(_, template, _) = event {
+            heights.push(template.block_height());
+        }
+    }
+    heights
+}
+
+fn notify(height: u32) -> ProverMessage {
+    ProverMessage::Notify(testing::template(height), ALL_SHARES, false, false)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn holds_a_job_sent_before_the_authorization_until_it_succeeds() {
+    let (client, mut connections, mut events) = scripted("early");
+    let mut pool = accept(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, Vec::<u32>::new());
+    pool.send(ProverMessage::AuthorizeResult(true, None)).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![2]);
+    assert!(client.stats().authorized);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn discards_a_job_sent_before_a_rejected_authorization() {
+    let (client, mut connections, mut events) = scripted("early");
+    let mut pool = accept(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    pool.send(ProverMessage::AuthorizeResult(false, Some("unknown account".to_string()))).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, Vec::<u32>::new());
+    assert!(!client.stats().authorized);
+
+    // Nor is it handed over once authorized on the next connection.
+    let mut pool = accept(&mut connections).await;
+    pool.send(ProverMessage::AuthorizeResult(true, None)).await.unwrap();
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, Vec::<u32>::new());
+}
+
+// An authorized connection of a client mining with `scripted`.
+async fn authorized(connections: &mut UnboundedReceiver<(String, DuplexStream)>) -> Framed<DuplexStream, ProverCodec> {
+    let mut pool = accept(connections).await;
+    pool.send(ProverMessage::AuthorizeResult(true, None)).await.unwrap();
+    pool
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drops_jobs_going_back_further_than_the_reorg_tolerance() {
+    let (client, mut connections, mut events) = scripted("heights");
+    let mut pool = authorized(&mut connections).await;
+    let reorg = ProverMessage::Notify(testing::template(3), ALL_SHARES, true, false);
+    // Up, then back within the tolerance of 2, then a replay of an older job, a flagged reorg and
+    // another replay.
+    for message in [notify(5), notify(6), notify(4), notify(3), reorg, notify(2)] {
+        pool.send(message).await.unwrap();
+    }
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![5, 6, 4, 3]);
+    assert_eq!(client.stats().dropped_jobs, 2);
+
+    // The highest height is of the connection.
+    drop(pool);
+    let mut pool = authorized(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![2]);
+    assert_eq!(client.stats().dropped_jobs, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_zero_reorg_tolerance_takes_only_flagged_reorgs() {
+    let (client, mut connections, mut events) = scripted("heights");
+    client.set_reorg_tolerance(0);
+    let mut pool = authorized(&mut connections).await;
+    let reorg = ProverMessage::Notify(testing::template(4), ALL_SHARES, true, false);
+    for message in [notify(5), notify(5), notify(4), reorg] {
+        pool.send(message).await.unwrap();
+    }
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![5, 5, 4]);
+    assert_eq!(client.stats().dropped_jobs, 1);
+}
+
+// What the client reports about its connection to a mock pool once it got a job at target 1000.
+async fn connection_info(setup: impl FnOnce(&Client)) -> ConnectionInfo {
+    let (pool, client) = duplex_pool("info");
+    setup(&client);
+    pool.notify(testing::template(2), 1000);
+    let (sender, _events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    let assigned = || client.stats().connection.map_or(false, |info| info.difficulty.is_some());
+    assert!(eventually(LIMIT, assigned).await);
+    client.stats().connection.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_a_plain_connection() {
+    let info = connection_info(|_| {}).await;
+    let expected = ConnectionInfo {
+        protocol_version: 1,
+        wire_format: "json",
+        capabilities: vec![],
+        keepalive_secs: None,
+        requested_difficulty: None,
+        difficulty: Some(u64::MAX / 1000),
+        tls: None,
+        compression: None,
+        server_version: None,
+    };
+    assert_eq!(info, expected);
+    assert_eq!(info.to_string(), "protocol version 1, json frames, capabilities: none, keepalive: off");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_the_negotiated_format_keepalive_and_requested_difficulty() {
+    let info = connection_info(|client| {
+        client.set_msgpack(true);
+        client.set_keepalive(Some(Duration::from_secs(7)), Duration::from_secs(1));
+        client.set_password("x,d=64".into());
+    })
+    .await;
+    let expected = ConnectionInfo {
+        protocol_version: 1,
+        wire_format: "msgpack",
+        capabilities: vec!["msgpack"],
+        keepalive_secs: Some(7),
+        requested_difficulty: Some(64),
+        // The mock pool honors the request.
+        difficulty: Some(64),
+        tls: None,
+        compression: None,
+        server_version: None,
+    };
+    assert_eq!(info, expected);
+    assert_eq!(
+        info.to_string(),
+        "protocol version 1, msgpack frames, capabilities: msgpack, keepalive: every 7s, requested difficulty: 64"
+    );
+
+    // A configured difficulty wins over the password field.
+    let info = connection_info(|client| {
+        client.set_password("x,d=64".into());
+        client.set_difficulty(5000);
+    })
+    .await;
+    assert_eq!(info.requested_difficulty, Some(5000));
+    assert!(info.to_string().ends_with("keepalive: off, requested difficulty: 5.0 K"), "{}", info);
+}
+
+// Share results handed to the prover within `wait`.
+async fn results(events: &mut Receiver<ProverEvent>, wait: Duration) -> Vec<(Code, u32)> {
+    let deadline = Instant::now() + wait;
+    let mut results = Vec::new();
+    while let Ok(Some(event)) = timeout_at(deadline, events.recv()).await {
+        if let ProverEvent::Result { code, height, .. } = event {
+            results.push((code, height));
+        }
+    }
+    results
+}
+
+// Waits for the next `count` shares sent to `pool`.
+async fn submits(pool: &mut Framed<DuplexStream, ProverCodec>, count: usize) {
+    let mut received = 0;
+    while received < count {
+        let message = timeout(LIMIT, pool.next()).await.expect("no share").unwrap().unwrap();
+        if let ProverMessage::Submit(..) = message {
+            received += 1;
+        }
+    }
+}
+
+fn submit(height: u32) -> ProverMessage {
+    let header = testing::header();
+    ProverMessage::Submit(height, header.nonce(), header.proof().clone())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ignores_repeated_authorization_results_unless_revoked() {
+    let (client, mut connections, mut events) = scripted("repeat");
+    let mut pool = authorized(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![2]);
+    pool.send(ProverMessage::AuthorizeResult(false, Some("unknown account".to_string()))).await.unwrap();
+    pool.send(ProverMessage::AuthorizeResult(true, None)).await.unwrap();
+    client.sender().send(submit(2)).await.unwrap();
+    submits(&mut pool, 1).await;
+    assert!(client.stats().authorized);
+    assert!(connections.try_recv().is_err(), "reconnected");
+
+    pool.send(ProverMessage::AuthorizeResult(false, Some(AUTHORIZATION_REVOKED.to_string()))).await.unwrap();
+    let authorize = timeout(LIMIT, pool.next()).await.expect("not authorized again").unwrap().unwrap();
+    assert!(matches!(authorize, ProverMessage::Authorize(..)), "{:?}", authorize);
+    assert!(!client.stats().authorized);
+    pool.send(ProverMessage::AuthorizeResult(true, None)).await.unwrap();
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert!(connections.try_recv().is_err(), "reconnected");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn counts_results_no_share_is_waiting_for() {
+    let (client, mut connections, mut events) = scripted("unmatched");
+    let mut pool = authorized(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    pool.send(ProverMessage::SubmitResult(Code::Success, None)).await.unwrap();
+    assert!(eventually(LIMIT, || client.stats().unmatched_results == 1).await);
+
+    // The next share gets its own result.
+    client.sender().send(submit(2)).await.unwrap();
+    submits(&mut pool, 1).await;
+    pool.send(ProverMessage::SubmitResult(Code::Success, None)).await.unwrap();
+    assert_eq!(results(&mut events, Duration::from_millis(500)).await, vec![(Code::Success, 2)]);
+    assert_eq!(client.stats().unmatched_results, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skips_the_late_result_of_a_share_given_up_on() {
+    let (client, mut connections, mut events) = scripted("given-up");
+    let mut pool = authorized(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    // One share more than are waited for, the first one is given up on.
+    let sender = client.sender();
+    tokio::spawn(async move {
+        sender.send(submit(3)).await.unwrap();
+        for _ in 0..1024 {
+            sender.send(submit(2)).await.unwrap();
+        }
+    });
+    submits(&mut pool, 1025).await;
+    assert!(eventually(LIMIT, || client.stats().pending_submits == 1024).await);
+
+    // The pool answers in order, the first result is for the share given up on.
+    pool.send(ProverMessage::SubmitResult(Code::Stale, None)).await.unwrap();
+    pool.send(ProverMessage::SubmitResult(Code::Success, None)).await.unwrap();
+    assert_eq!(results(&mut events, Duration::from_millis(500)).await, vec![(Code::Success, 2)]);
+    let stats = client.stats();
+    assert_eq!((stats.unmatched_results, stats.pending_submits), (1, 1023));
+}
+
+// Target of the first job the mock pool sends a client set up by `setup`, asked for 1000.
+async fn assigned_target(setup: impl FnOnce(&Client)) -> u64 {
+    let (pool, client) = duplex_pool("difficulty");
+    setup(&client);
+    pool.notify(testing::template(2), 1000);
+    let (sender, mut events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    loop {
+        if let ProverEvent::NewWork(target, ..) = timeout(LIMIT, events.recv()).await.expect("no job").unwrap() {
+            return target;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mines_at_the_requested_difficulty_of_a_pool_honoring_it() {
+    assert_eq!(assigned_target(|_| {}).await, 1000);
+    assert_eq!(assigned_target(|client| client.set_password("x,d=5000".into())).await, u64::MAX / 5000);
+    let configured = assigned_target(|client| {
+        client.set_password("x,d=5000".into());
+        client.set_difficulty(64);
+    });
+    assert_eq!(configured.await, u64::MAX / 64);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_every_capability_a_pool_accepts() {
+    let tokens = session_tokens("every-capability");
+    let info = connection_info(|client| {
+        client.set_msgpack(true);
+        client.set_session_tokens(Some(tokens));
+        client.set_speculative(true);
+        client.set_job_ack(true);
+        client.set_accept_pool_info(true);
+        client.set_multiplex(true);
+    })
+    .await;
+    let expected = ConnectionInfo {
+        protocol_version: 1,
+        wire_format: "msgpack",
+        capabilities: vec!["msgpack", "session", "speculative", "job_ack", "pool_info", "multiplex"],
+        keepalive_secs: None,
+        requested_difficulty: None,
+        difficulty: Some(u64::MAX / 1000),
+        tls: None,
+        compression: None,
+        server_version: None,
+    };
+    assert_eq!(info, expected);
+    assert_eq!(
+        info.to_string(),
+        "protocol version 1, msgpack frames, capabilities: msgpack, session, speculative, job_ack, pool_info, \
+         multiplex, keepalive: off"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_the_server_version_the_pool_sends() {
+    let (pool, client) = duplex_pool("version");
+    client.set_accept_pool_info(true);
+    pool.notify(testing::template(2), 1000);
+    let (sender, _events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+
+    pool.pool_info(BTreeMap::from([("server_version".to_string(), "mockpool 1.0".to_string())]));
+    let reported = || client.stats().connection.and_then(|info| info.server_version).is_some();
+    assert!(eventually(LIMIT, reported).await);
+    let info = client.stats().connection.unwrap();
+    assert_eq!(info.capabilities, ["pool_info"]);
+    assert_eq!(info.server_version.as_deref(), Some("mockpool 1.0"));
+    assert!(info.to_string().ends_with(", keepalive: off, server version: mockpool 1.0"), "{}", info);
+}
+
+// Session tokens kept in a scratch directory of `test`.
+fn session_tokens(test: &str) -> Arc<SessionTokens> {
+    Arc::new(SessionTokens::load(scratch(test).join("stats.json")))
+}
+
+// A client resuming sessions with `tokens` mining on a mock pool, and the prover events it sends.
+fn resuming(tokens: &Arc<SessionTokens>) -> (MockPool, Arc<Client>, Receiver<ProverEvent>) {
+    let (pool, client) = duplex_pool("sessions");
+    client.set_session_tokens(Some(tokens.clone()));
+    let (sender, events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    (pool, client, events)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resumes_the_session_the_pool_gave() {
+    let tokens = session_tokens("session-resumed");
+    let (pool, client, _events) = resuming(&tokens);
+    let received = pool.wait_for(LIMIT, |received| received.session_tokens.len() == 1).await.unwrap();
+    assert_eq!(received.session_tokens, [None]);
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(tokens.get("mock.pool:4040/sessions").as_deref(), Some("session-1"));
+    assert!(client.stats().connection.unwrap().capabilities.contains(&"session"));
+
+    pool.disconnect();
+    let received = pool.wait_for(LIMIT, |received| received.session_tokens.len() == 2).await.unwrap();
+    assert_eq!(received.session_tokens[1].as_deref(), Some("session-1"));
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(tokens.get("mock.pool:4040/sessions").as_deref(), Some("session-1"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn probes_without_the_session_or_capabilities() {
+    let tokens = session_tokens("session-probed");
+    let (pool, client, _events) = resuming(&tokens);
+    client.set_msgpack(true);
+    pool.notify(testing::template(2), ALL_SHARES);
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(tokens.get("mock.pool:4040/sessions").as_deref(), Some("session-1"));
+
+    // The probe goes to the pool the session is on.
+    let probed = client.probe(&client.server()).await.expect("the probe failed");
+    assert!(probed.first_notify.is_some());
+    let received = pool.received();
+    assert_eq!(received.authorizations.len(), 2);
+    assert_eq!(received.authorizations[1].2, *ProverMessage::version());
+    assert_eq!(received.session_tokens, [None]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn starts_a_new_session_once_the_pool_rejected_the_token() {
+    let tokens = session_tokens("session-rejected");
+    let (pool, client, _events) = resuming(&tokens);
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(tokens.get("mock.pool:4040/sessions").as_deref(), Some("session-1"));
+
+    // The pool restarts without its sessions.
+    pool.forget_sessions();
+    pool.disconnect();
+    let received = pool.wait_for(LIMIT, |received| received.session_tokens.len() == 2).await.unwrap();
+    assert_eq!(received.session_tokens[1].as_deref(), Some("session-1"));
+    assert!(eventually(LIMIT, || tokens.get("mock.pool:4040/sessions").is_none()).await);
+
+    let received = pool.wait_for(LIMIT, |received| received.session_tokens.len() == 3).await.unwrap();
+    assert_eq!(received.session_tokens[2], None);
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    assert_eq!(tokens.get("mock.pool:4040/sessions").as_deref(), Some("session-3"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retries_a_share_lost_to_a_proxy_exception_once() {
+    let (client, mut connections, mut events) = scripted("proxy");
+    let mut pool = authorized(&mut connections).await;
+    pool.send(notify(2)).await.unwrap();
+    assert_eq!(jobs(&mut events, Duration::from_millis(500)).await, vec![2]);
+    client.sender().send(submit(2)).await.unwrap();
+    submits(&mut pool, 1).await;
+    pool.send(ProverMessage::SubmitResult(Code::ProxyException, None)).await.unwrap();
+
+    // The same share again, after the retry delay.
+    let retried = timeout(LIMIT, pool.next()).await.expect("not retried").unwrap().unwrap();
+    assert_eq!(retried, submit(2));
+    pool.send(ProverMessage::SubmitResult(Code::Success, None)).await.unwrap();
+    assert_eq!(results(&mut events, Duration::from_millis(500)).await, vec![(Code::Success, 2)]);
+    let stats = client.stats();
+    assert_eq!((stats.proxy_exceptions, stats.pending_submits, stats.unmatched_results), (1, 0, 0));
+    assert_eq!(stats.rejections.total(), 0);
+}
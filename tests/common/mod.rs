@@ -0,0 +1,44 @@
+// Helpers shared by the integration tests, the test doubles themselves are in `aleoxminer::testing`.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use aleoxminer::{
+    job_trace::JobTrace,
+    prover::{Prover, ProverEvent},
+    testing,
+};
+use tokio::time::sleep;
+
+/// Time anything a test waits for gets before it fails.
+pub const LIMIT: Duration = Duration::from_secs(10);
+
+/// An empty directory for the files of `test`, unique to this test binary's process.
+pub fn scratch(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aleoxminer-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Polls `condition` until it holds, false if it still doesn't after `limit`.
+pub async fn eventually(limit: Duration, condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + limit;
+    while !condition() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    true
+}
+
+/// Hands the prover work for `height` at `pool_target` the way the client does.
+pub async fn work(prover: &Prover, height: u32, pool_target: u64) {
+    let event = ProverEvent::NewWork(pool_target, testing::template(height), JobTrace::none());
+    assert!(prover.event_sender().send(event).await.is_ok(), "prover stopped");
+}
@@ -0,0 +1,104 @@
+// Failures surfacing as the typed errors an embedder matches on, and the exit codes the binary maps
+// them to.
+
+mod common;
+
+use std::{io, sync::Arc, time::Duration};
+
+use aleoxminer::{
+    client::{self, ClientError},
+    message::{ProtocolError, ProtocolLimits, ProverCodec},
+    prover::Prover,
+    testing::{self, FakeBackend, MockPool},
+    transport::{Connector, TcpConnector},
+};
+use bytes::BytesMut;
+use common::LIMIT;
+use tokio::time::timeout;
+use tokio_util::codec::Decoder;
+
+// A port nothing listens on.
+fn closed_port() -> String {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn a_refused_connection_is_connect() {
+    let server = closed_port();
+    match TcpConnector::default().connect(&server).await {
+        Err(ClientError::Connect { server: failed, source }) => {
+            assert_eq!(failed, server);
+            assert_eq!(source.kind(), io::ErrorKind::ConnectionRefused);
+        }
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("connected to {}", server),
+    }
+}
+
+#[tokio::test]
+async fn a_slow_connection_is_timeout() {
+    // Resolving the name alone takes longer than no time at all.
+    match TcpConnector::new(Duration::ZERO).connect("localhost:4040").await {
+        Err(ClientError::Timeout { server }) => assert_eq!(server, "localhost:4040"),
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("connected to localhost:4040"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_pool_rejecting_the_credentials_is_auth_rejected() {
+    let pool = MockPool::start().await.unwrap();
+    pool.set_authorize(false);
+    let client = pool.client("rejected");
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    client::start(prover.event_sender(), client.clone());
+    // 5 seconds between the attempts.
+    let error = timeout(6 * LIMIT, client.failed()).await.expect("the client kept trying");
+    assert!(matches!(*error, ClientError::AuthRejected { attempts: 3, .. }), "{:?}", error);
+    assert_eq!(error.exit_code().code(), 3);
+    assert_eq!(pool.received().authorizations.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_pool_unreachable_past_the_limit_is_unreachable() {
+    let server = closed_port();
+    let client = testing::client(&server, "unreachable");
+    client.set_unreachable_limit(Some(Duration::from_secs(1)));
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    client::start(prover.event_sender(), client.clone());
+    let error = timeout(3 * LIMIT, client.failed()).await.expect("the client kept trying");
+    match &*error {
+        ClientError::Unreachable { server: failed, after } => {
+            assert_eq!(failed, &server);
+            assert_eq!(*after, Duration::from_secs(1));
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+    assert_eq!(error.exit_code().code(), 10);
+}
+
+#[test]
+fn an_oversized_frame_is_a_protocol_mismatch() {
+    let mut codec = ProverCodec::new(ProtocolLimits::default());
+    let limit = ProtocolLimits::default().max_frame_size;
+    let mut frame = BytesMut::from(&(limit as u32 + 1).to_le_bytes()[..]);
+    let error = ClientError::from(codec.decode(&mut frame).unwrap_err());
+    match &error {
+        ClientError::ProtocolMismatch(ProtocolError::FrameTooLarge { length, limit: refused }) => {
+            assert_eq!((*length, *refused), (limit + 1, limit));
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+    assert_eq!(error.exit_code().code(), 1);
+    assert!(error.to_string().starts_with("Unreadable message from the pool: Frame of"), "{}", error);
+}
+
+#[test]
+fn other_io_failures_are_io() {
+    let error = ClientError::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer"));
+    assert!(matches!(&error, ClientError::Io(e) if e.kind() == io::ErrorKind::ConnectionReset), "{:?}", error);
+    assert_eq!(error.to_string(), "reset by peer");
+    assert_eq!(error.exit_code().code(), 1);
+}
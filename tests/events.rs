@@ -0,0 +1,98 @@
+// The miner events published during a scripted session against the mock pool, as a subscriber sees them.
+
+mod common;
+
+use std::time::Duration;
+
+use aleoxminer::{
+    events::MinerEvent,
+    message::Code,
+    session::MiningSession,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::LIMIT;
+use tokio::{
+    sync::broadcast::{error::TryRecvError, Receiver},
+    time::{sleep, Instant},
+};
+
+// The event without what changes from run to run, e.g. `job 2` or `rejected 2 Stale`.
+fn label(event: &MinerEvent) -> Option<String> {
+    Some(match event {
+        MinerEvent::Connected { worker, .. } => format!("connected {}", worker),
+        MinerEvent::Disconnected { worker, .. } => format!("disconnected {}", worker),
+        MinerEvent::Authorized { worker } => format!("authorized {}", worker),
+        MinerEvent::NewJob { height, .. } => format!("job {}", height),
+        MinerEvent::ShareSubmitted { height, .. } => format!("submitted {}", height),
+        MinerEvent::ShareAccepted(result) => format!("accepted {}", result.height),
+        MinerEvent::ShareRejected(result) => format!("rejected {} {:?}", result.height, result.code),
+        _ => return None,
+    })
+}
+
+// Receives events until `condition` holds for the labels received so far.
+async fn until(events: &mut Receiver<MinerEvent>, labels: &mut Vec<String>, condition: impl Fn(&[String]) -> bool) {
+    let deadline = Instant::now() + LIMIT;
+    while !condition(labels) {
+        assert!(Instant::now() < deadline, "events so far: {:?}", labels);
+        match events.try_recv() {
+            Ok(event) => labels.extend(label(&event)),
+            Err(TryRecvError::Empty) => sleep(Duration::from_millis(5)).await,
+            Err(e) => panic!("subscriber failed: {}", e),
+        }
+    }
+}
+
+fn count(labels: &[String], label: &str) -> usize {
+    labels.iter().filter(|received| *received == label).count()
+}
+
+fn position(labels: &[String], label: &str) -> usize {
+    labels.iter().position(|received| received == label).unwrap_or_else(|| panic!("no {} in {:?}", label, labels))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn publishes_the_session_events_in_order() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let session = MiningSession::builder()
+        .pool(pool.address())
+        .address(Some(testing::address()))
+        .worker(Some("events".to_string()))
+        .prover(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))))
+        .build()
+        .unwrap();
+    let mut events = session.events().subscribe();
+    // Never read, a subscriber falling behind must not hold up mining.
+    let _lagging = session.events().subscribe();
+    session.start().await.unwrap();
+
+    let mut labels = Vec::new();
+    until(&mut events, &mut labels, |labels| count(labels, "accepted 2") >= 3).await;
+    assert_eq!(labels[0], "connected events");
+    assert!(position(&labels, "authorized events") < position(&labels, "job 2"));
+    assert!(position(&labels, "job 2") < position(&labels, "submitted 2"));
+    // Every result answers a share submitted before it.
+    for (index, received) in labels.iter().enumerate() {
+        if received.starts_with("accepted") {
+            assert!(count(&labels[..index], "submitted 2") > count(&labels[..index], "accepted 2"), "{:?}", labels);
+        }
+    }
+
+    pool.notify(testing::template(3), ALL_SHARES);
+    let mut labels = Vec::new();
+    until(&mut events, &mut labels, |labels| labels.iter().any(|label| label == "accepted 3")).await;
+    assert!(position(&labels, "job 3") < position(&labels, "submitted 3"));
+
+    // Last, as enough rejects pause proving.
+    pool.set_result(Code::Stale, Some("too late".to_string()));
+    let mut labels = Vec::new();
+    until(&mut events, &mut labels, |labels| labels.iter().any(|label| label == "rejected 3 Stale")).await;
+
+    pool.disconnect();
+    let mut labels = Vec::new();
+    until(&mut events, &mut labels, |labels| labels.iter().any(|label| label == "connected events")).await;
+    assert!(position(&labels, "disconnected events") < position(&labels, "connected events"));
+
+    session.shutdown().await;
+}
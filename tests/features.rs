@@ -0,0 +1,44 @@
+// Every cargo feature on its own and none at all still compile. Builds the crate once per feature, run
+// with `cargo test --test features -- --ignored`.
+
+use std::{fs, path::Path, process::Command};
+
+// The features of the manifest, `default` aside.
+fn features() -> Vec<String> {
+    let manifest = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")).unwrap();
+    let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+    let mut features: Vec<String> =
+        manifest["features"].as_table().unwrap().keys().filter(|name| *name != "default").cloned().collect();
+    features.sort();
+    features
+}
+
+// Whether the CUDA toolkit the `cuda` feature and the features enabling it build against is installed.
+fn cuda_toolkit() -> bool {
+    Command::new("nvcc").arg("--version").output().map_or(false, |output| output.status.success())
+}
+
+#[test]
+#[ignore]
+fn every_feature_builds_on_its_own() {
+    let features = features();
+    for expected in ["tls", "metrics", "tui", "mqtt", "chaos", "cuda", "nvml"] {
+        assert!(features.iter().any(|feature| feature == expected), "no {} feature in {:?}", expected, features);
+    }
+    let cuda = cuda_toolkit();
+    let mut sets = vec![String::new()];
+    sets.extend(features.into_iter().filter(|feature| cuda || !["cuda", "nvml"].contains(&feature.as_str())));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    // Apart from the target directory of the running tests, which cargo may still hold locked.
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("features");
+    for set in sets {
+        let status = Command::new(&cargo)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["check", "--lib", "--bins", "--no-default-features", "--features", &set])
+            .arg("--target-dir")
+            .arg(&target)
+            .status()
+            .unwrap();
+        assert!(status.success(), "the build with only {:?} failed", set);
+    }
+}
@@ -0,0 +1,90 @@
+// Replays the fuzz corpus and the inputs that crashed the fuzz targets through the same steps as
+// fuzz/fuzz_targets, so the fixed panics stay fixed without cargo-fuzz.
+
+use std::{fs, io::Cursor, path::PathBuf};
+
+use aleoxminer::message::{ProtocolLimits, ProverCodec, ProverMessage};
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+fn inputs(dir: &str, target: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz").join(dir).join(target);
+    let mut inputs: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read(&path).unwrap())
+        })
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "nothing in {}", dir.display());
+    inputs
+}
+
+// fuzz_targets/decode.rs: the first byte picks the chunk size. Returns the frames decoded and whether
+// decoding ended in an error.
+fn decode(data: &[u8]) -> (usize, bool) {
+    let (chunk, data) = match data.split_first() {
+        Some((chunk, data)) => (*chunk as usize + 1, data),
+        None => return (0, false),
+    };
+    let mut codec = ProverCodec::new(ProtocolLimits {
+        max_frame_size: 64 * 1024,
+        max_string_length: 256,
+    });
+    let mut buffer = BytesMut::new();
+    let mut frames = 0;
+    for bytes in data.chunks(chunk) {
+        buffer.extend_from_slice(bytes);
+        loop {
+            match codec.decode(&mut buffer) {
+                Ok(Some(_)) => frames += 1,
+                Ok(None) => break,
+                Err(_) => return (frames, true),
+            }
+        }
+    }
+    (frames, false)
+}
+
+// fuzz_targets/deserialize.rs, returns the deserializers that failed.
+fn deserialize(data: &[u8]) -> usize {
+    [
+        ProverMessage::deserialize(&mut Cursor::new(data)).is_err(),
+        ProverMessage::deserialize_json(&mut Cursor::new(data)).is_err(),
+        ProverMessage::deserialize_msgpack(&mut Cursor::new(data)).is_err(),
+    ]
+    .iter()
+    .filter(|failed| **failed)
+    .count()
+}
+
+#[test]
+fn decode_corpus() {
+    for (name, data) in inputs("corpus", "decode") {
+        let (frames, failed) = decode(&data);
+        assert!(frames > 0 && !failed, "{}: {} frames, failed {}", name, frames, failed);
+    }
+}
+
+#[test]
+fn decode_crashers_fail_cleanly() {
+    for (name, data) in inputs("regressions", "decode") {
+        let (_, failed) = decode(&data);
+        assert!(failed, "{} decoded", name);
+    }
+}
+
+#[test]
+fn deserialize_corpus() {
+    for (name, data) in inputs("corpus", "deserialize") {
+        assert!(deserialize(&data) < 3, "{}: no deserializer accepts it", name);
+    }
+}
+
+#[test]
+fn deserialize_crashers_fail_cleanly() {
+    for (name, data) in inputs("regressions", "deserialize") {
+        assert_eq!(deserialize(&data), 3, "{} deserialized", name);
+    }
+}
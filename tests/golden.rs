@@ -0,0 +1,96 @@
+// The wire encodings of the fixture messages, byte for byte. A change here breaks every pool and
+// miner on the other side, update tests/fixtures/golden.txt only along with a protocol version.
+
+use std::{collections::HashMap, fs, io::Cursor, path::PathBuf};
+
+use aleoxminer::{
+    message::{ProverMessage, MSGPACK_FLAG},
+    testing,
+};
+
+const FORMATS: [&str; 3] = ["binary", "json", "msgpack"];
+
+// (name, format) -> id and fields
+fn vectors() -> HashMap<(String, String), Vec<u8>> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden.txt");
+    fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split(' ').collect::<Vec<_>>()[..] {
+            [name, format, hex] => ((name.to_string(), format.to_string()), unhex(hex)),
+            _ => panic!("invalid line in {}: {}", path.display(), line),
+        })
+        .collect()
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+        .collect()
+}
+
+fn encode(message: &ProverMessage, format: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match format {
+        "binary" => {
+            bytes.push(message.id());
+            message.serialize_into(&mut bytes).unwrap();
+        }
+        "json" => {
+            bytes.push(message.id());
+            message.serialize_into_json(&mut bytes).unwrap();
+        }
+        _ => {
+            bytes.push(message.id() | MSGPACK_FLAG);
+            message.serialize_into_msgpack(&mut bytes).unwrap();
+        }
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8], format: &str) -> ProverMessage {
+    let mut reader = Cursor::new(bytes);
+    match format {
+        "binary" => ProverMessage::deserialize(&mut reader),
+        "json" => ProverMessage::deserialize_json(&mut reader),
+        _ => ProverMessage::deserialize_msgpack(&mut reader),
+    }
+    .unwrap()
+}
+
+fn holds_template_or_proof(message: &ProverMessage) -> bool {
+    matches!(message, ProverMessage::Notify(..) | ProverMessage::Submit(..))
+}
+
+#[test]
+fn encodings_match() {
+    let vectors = vectors();
+    for (name, message) in testing::messages() {
+        for format in FORMATS {
+            match vectors.get(&(name.to_string(), format.to_string())) {
+                Some(expected) => assert_eq!(&encode(&message, format), expected, "{} {}", name, format),
+                None => assert!(holds_template_or_proof(&message), "no {} vector for {}", format, name),
+            }
+        }
+    }
+}
+
+#[test]
+fn vectors_decode_to_the_fixtures() {
+    let messages: HashMap<_, _> = testing::messages().into_iter().collect();
+    for ((name, format), bytes) in vectors() {
+        let message = messages.get(name.as_str()).unwrap_or_else(|| panic!("no fixture {}", name));
+        assert_eq!(&decode(&bytes, &format), message, "{} {}", name, format);
+    }
+}
+
+#[test]
+fn fixtures_with_templates_and_proofs_round_trip() {
+    for (name, message) in testing::messages().into_iter().filter(|(_, message)| holds_template_or_proof(message)) {
+        for format in FORMATS {
+            assert_eq!(decode(&encode(&message, format), format), message, "{} {}", name, format);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// GPUs in worker groups submitting under their group's worker name, on fake devices against the mock pool.
+
+mod common;
+
+use std::time::Duration;
+
+use aleoxminer::{
+    session::MiningSession,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+
+fn shares_of(forwarded: &[(String, u32)], worker: &str) -> usize {
+    forwarded.iter().filter(|(share_worker, _)| share_worker == worker).count()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn groups_submit_under_their_worker_names() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let mut config = testing::prover_config(16, FakeBackend::new(Duration::from_millis(5)));
+    config.cuda = Some(vec![0, 1, 2]);
+    let session = MiningSession::builder()
+        .pool(pool.address())
+        .address(Some(testing::address()))
+        .worker(Some("farm".to_string()))
+        .groups(vec!["rig_a=0".parse().unwrap(), "rig_b=1".parse().unwrap()])
+        .prover(config)
+        .build()
+        .unwrap();
+    session.start().await.unwrap();
+
+    let received = pool
+        .wait_for(LIMIT, |received| {
+            ["rig_a", "rig_b", "farm"].iter().all(|worker| shares_of(&received.forwarded, worker) >= 3)
+        })
+        .await
+        .unwrap();
+    // One connection per group, the ungrouped GPU submits as the main worker.
+    assert_eq!(received.connections, 3);
+    let mut workers: Vec<&str> = received.authorizations.iter().map(|(_, worker, _)| worker.as_str()).collect();
+    workers.sort_unstable();
+    assert_eq!(workers, vec!["farm", "rig_a", "rig_b"]);
+
+    assert!(
+        eventually(LIMIT, || {
+            let stats = session.prover().stats();
+            stats.group_shares.len() == 2 && stats.group_shares.iter().all(|(_, valid, _)| *valid >= 3)
+        })
+        .await
+    );
+    let stats = session.prover().stats();
+    let names: Vec<&str> = stats.group_shares.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["rig_a", "rig_b"]);
+    let grouped: u32 = stats.group_shares.iter().map(|(_, valid, _)| valid).sum();
+    assert!(stats.valid_shares > grouped);
+    session.shutdown().await;
+}
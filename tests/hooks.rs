@@ -0,0 +1,103 @@
+// The `MinerHooks` callbacks made during a scripted session against the mock pool.
+
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aleoxminer::{
+    hooks::MinerHooks,
+    message::Code,
+    session::MiningSession,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+
+// Records every call but `on_rate`, e.g. `job 2` or `rejected Stale too late`.
+#[derive(Default)]
+struct Recorder {
+    calls: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn count(&self, call: &str) -> usize {
+        self.calls().iter().filter(|recorded| *recorded == call).count()
+    }
+}
+
+impl MinerHooks for Recorder {
+    fn on_connected(&self, server: &str) {
+        self.record(format!("connected {}", server));
+    }
+
+    fn on_disconnected(&self, server: &str, _reason: &str) {
+        self.record(format!("disconnected {}", server));
+    }
+
+    fn on_new_job(&self, height: u32, target: u64) {
+        self.record(format!("job {} {}", height, target));
+    }
+
+    fn on_share_accepted(&self, latency: Option<Duration>) {
+        // Every share is matched to its result here.
+        self.record(if latency.is_some() { "accepted" } else { "accepted unmatched" }.to_string());
+    }
+
+    fn on_share_rejected(&self, code: &Code, message: Option<&str>) {
+        self.record(format!("rejected {:?} {}", code, message.unwrap_or("-")));
+    }
+}
+
+fn position(calls: &[String], call: &str) -> usize {
+    calls.iter().position(|recorded| recorded == call).unwrap_or_else(|| panic!("no {} in {:?}", call, calls))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn calls_the_hooks_in_order() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let recorder = Arc::new(Recorder::default());
+    let session = MiningSession::builder()
+        .pool(pool.address())
+        .address(Some(testing::address()))
+        .worker(Some("hooks".to_string()))
+        .prover(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))))
+        .hooks(recorder.clone())
+        .build()
+        .unwrap();
+    session.start().await.unwrap();
+    let connected = format!("connected {}", pool.address());
+    let disconnected = format!("disconnected {}", pool.address());
+
+    assert!(eventually(LIMIT, || recorder.count("accepted") >= 2).await, "{:?}", recorder.calls());
+    // Last, as enough rejects pause proving.
+    pool.set_result(Code::Stale, Some("too late".to_string()));
+    assert!(eventually(LIMIT, || recorder.count("rejected Stale too late") >= 1).await, "{:?}", recorder.calls());
+    pool.disconnect();
+    assert!(eventually(LIMIT, || recorder.count(&connected) >= 2).await, "{:?}", recorder.calls());
+    session.shutdown().await;
+
+    let calls = recorder.calls();
+    assert_eq!(calls[0], connected);
+    assert!(!calls.contains(&"accepted unmatched".to_string()), "{:?}", calls);
+    let job = calls.iter().position(|call| call.starts_with("job 2 ")).unwrap();
+    assert!(job < position(&calls, "accepted"), "{:?}", calls);
+    assert!(position(&calls, "accepted") < position(&calls, "rejected Stale too late"), "{:?}", calls);
+    // Nothing is accepted once the pool rejects every share.
+    let rejected = position(&calls, "rejected Stale too late");
+    assert!(!calls[rejected..].contains(&"accepted".to_string()), "{:?}", calls);
+    assert!(rejected < position(&calls, &disconnected), "{:?}", calls);
+    assert_eq!(calls.iter().filter(|call| **call == disconnected).count(), 1, "{:?}", calls);
+    let reconnected = calls.iter().rposition(|call| *call == connected).unwrap();
+    assert!(position(&calls, &disconnected) < reconnected, "{:?}", calls);
+}
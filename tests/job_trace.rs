@@ -0,0 +1,131 @@
+// The spans following a job from the Notify to the first attempts, captured by a subscriber.
+
+mod common;
+
+use std::{
+    fmt::{self, Write as _},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aleoxminer::{
+    job_trace::JobTrace,
+    prover::{Prover, ProverEvent},
+    testing::{self, FakeBackend, NO_SHARES},
+};
+use common::{eventually, LIMIT};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Event,
+    Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer,
+};
+
+// Spans opened and closed and events, one line each with the name of the parent span.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<String>>>);
+
+impl Captured {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn push(&self, line: String) {
+        self.0.lock().unwrap().push(line);
+    }
+}
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = write!(self.0, " {}={:?}", field.name(), value);
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Captured {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields(String::new());
+        attributes.record(&mut fields);
+        let span = ctx.span(id).unwrap();
+        let parent = span.parent().map_or("-", |parent| parent.name());
+        self.push(format!("new {} {}{}", span.name(), parent, fields.0));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.push(format!("close {}", ctx.span(&id).unwrap().name()));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        let parent = ctx.event_span(event).map_or("-", |parent| parent.name());
+        self.push(format!("event {}{}", parent, fields.0));
+    }
+}
+
+#[test]
+fn nests_the_stages_of_a_job_in_its_span() {
+    let captured = Captured::default();
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(captured.clone()), || {
+        let mut trace = JobTrace::new(2, 7, "pool.example.com:4040");
+        trace.received();
+        drop(trace.activation());
+        drop(trace.first_attempt("CPU 0"));
+        trace.cancelled("new work");
+        drop(trace);
+
+        let mut none = JobTrace::none();
+        none.received();
+        drop(none.activation());
+        drop(none.first_attempt("CPU 0"));
+        none.cancelled("new work");
+    });
+    assert_eq!(
+        captured.lines(),
+        [
+            r#"new job - height=2 job_epoch=7 pool="pool.example.com:4040""#,
+            "new channel_wait job",
+            "close channel_wait",
+            "new prover_activation job",
+            "close prover_activation",
+            r#"new first_attempt job device="CPU 0""#,
+            "close first_attempt",
+            r#"event job reason="new work" message=Job cancelled"#,
+            "close job",
+        ]
+    );
+}
+
+// The only test of this file setting the global subscriber, the prover's workers run on other threads.
+#[tokio::test(flavor = "multi_thread")]
+async fn traces_a_job_through_the_prover() {
+    let captured = Captured::default();
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(captured.clone())).unwrap();
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(16, backend.clone()), testing::client("127.0.0.1:1", "traced"))
+        .unwrap();
+    prover.start().await.unwrap();
+    let work = |height: u32, job_epoch: u64| {
+        ProverEvent::NewWork(NO_SHARES, testing::template(height), JobTrace::new(height, job_epoch, "mock.pool:4040"))
+    };
+    assert!(prover.event_sender().send(work(3, 1)).await.is_ok());
+    assert!(eventually(LIMIT, || backend.attempts() > 0).await);
+    assert!(prover.event_sender().send(work(4, 2)).await.is_ok());
+    assert!(eventually(LIMIT, || prover.stats().current_block == 4).await);
+    prover.stop().await;
+
+    let lines = captured.lines();
+    let position = |line: &str| lines.iter().position(|captured| captured == line);
+    let job = position(r#"new job - height=3 job_epoch=1 pool="mock.pool:4040""#).expect("no job span");
+    let activation = position("new prover_activation job").expect("no activation span");
+    assert!(job < activation, "{:#?}", lines);
+    assert!(lines[job..activation].iter().any(|line| line == "close channel_wait"), "{:#?}", lines);
+    assert!(lines.iter().any(|line| line.starts_with("new first_attempt job device=")), "{:#?}", lines);
+    assert!(position(r#"event job reason="new work" message=Job cancelled"#).is_some(), "{:#?}", lines);
+}
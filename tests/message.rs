@@ -0,0 +1,266 @@
+// Decoding inputs that used to panic or decode silently wrong, and the wire format negotiation.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor, Write},
+    sync::{Arc, Mutex},
+};
+
+use aleoxminer::{
+    message::{
+        difficulty_hint,
+        password_field,
+        CodecStats,
+        MessageTraffic,
+        ProtocolError,
+        ProtocolLimits,
+        ProverCodec,
+        ProverMessage,
+        Secret,
+        MSGPACK_FLAG,
+    },
+    testing,
+};
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+fn binary(message: &ProverMessage) -> Vec<u8> {
+    let mut bytes = vec![message.id()];
+    message.serialize_into(&mut bytes).unwrap();
+    bytes
+}
+
+fn deserialize(bytes: &[u8]) -> Result<ProverMessage, ProtocolError> {
+    ProverMessage::deserialize(&mut Cursor::new(bytes))
+}
+
+#[test]
+fn notify_flags_are_optional() {
+    let plain = ProverMessage::Notify(testing::fixed_template(), 42, false, false);
+    assert_eq!(deserialize(&binary(&plain)).unwrap(), plain);
+    let flagged = ProverMessage::Notify(testing::fixed_template(), 42, true, true);
+    assert_eq!(deserialize(&binary(&flagged)).unwrap(), flagged);
+}
+
+#[test]
+fn truncated_notify_fails() {
+    let bytes = binary(&ProverMessage::Notify(testing::fixed_template(), 42, true, false));
+    // Cut in the pool target, then in the template.
+    for length in [bytes.len() - 2, bytes.len() - 12, bytes.len() / 2, 1] {
+        assert!(deserialize(&bytes[..length]).is_err(), "{} of {} bytes decoded", length, bytes.len());
+    }
+}
+
+#[test]
+fn notify_with_trailing_bytes_fails() {
+    let mut bytes = binary(&ProverMessage::Notify(testing::fixed_template(), 42, false, true));
+    bytes.extend_from_slice(&[0, 0]);
+    match deserialize(&bytes) {
+        Err(ProtocolError::TrailingBytes { message: "Notify", length: 2 }) => {}
+        other => panic!("expected trailing bytes, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_versions_fail() {
+    for version in ["", "x", "65536", "-1", "1.0"] {
+        let mut bytes = vec![0];
+        let message = ("account", "worker", "x", version);
+        serde_json::to_writer(&mut bytes, &message).unwrap();
+        match ProverMessage::deserialize_json(&mut Cursor::new(&bytes)) {
+            Err(ProtocolError::InvalidVersion(invalid)) => assert_eq!(invalid, version),
+            other => panic!("version {:?}: {:?}", version, other),
+        }
+    }
+}
+
+fn frame(codec: &mut ProverCodec, message: ProverMessage) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    codec.encode(message, &mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn job_ack_round_trips_in_every_format() {
+    for (height, pool_target) in [(0, 0), (7, 1 << 40), (u32::MAX, u64::MAX)] {
+        let ack = ProverMessage::JobAck(height, pool_target);
+        assert_eq!(deserialize(&binary(&ack)).unwrap(), ack);
+        for msgpack in [false, true] {
+            let mut codec = ProverCodec::default();
+            if msgpack {
+                codec.use_msgpack();
+            }
+            let mut bytes = frame(&mut codec, ProverMessage::JobAck(height, pool_target));
+            assert_eq!(bytes[4] & MSGPACK_FLAG != 0, msgpack);
+            let decoded = ProverCodec::default().decode(&mut bytes).unwrap();
+            assert_eq!(decoded, Some(ProverMessage::JobAck(height, pool_target)));
+            assert!(bytes.is_empty());
+        }
+    }
+}
+
+// Whether `codec` sends MessagePack, by the id of an encoded frame.
+fn sends_msgpack(codec: &mut ProverCodec) -> bool {
+    frame(codec, ProverMessage::Canary)[4] & MSGPACK_FLAG != 0
+}
+
+#[test]
+fn msgpack_frames_switch_only_after_an_offer() {
+    let mut pool = ProverCodec::default();
+    pool.use_msgpack();
+    let mut result = frame(&mut pool, ProverMessage::AuthorizeResult(true, None));
+
+    let mut silent = ProverCodec::default();
+    assert!(silent.decode(&mut result.clone()).unwrap().is_some());
+    assert!(!silent.msgpack());
+    assert!(!sends_msgpack(&mut silent));
+
+    let mut offering = ProverCodec::default();
+    offering.offer_msgpack();
+    assert!(!sends_msgpack(&mut offering));
+    assert!(offering.decode(&mut result).unwrap().is_some());
+    assert!(offering.msgpack());
+    assert!(sends_msgpack(&mut offering));
+}
+
+#[test]
+fn json_answers_keep_the_offer_pending() {
+    let mut offering = ProverCodec::default();
+    offering.offer_msgpack();
+    let mut result = frame(&mut ProverCodec::default(), ProverMessage::AuthorizeResult(true, None));
+    assert!(offering.decode(&mut result).unwrap().is_some());
+    assert!(!offering.msgpack());
+}
+
+#[test]
+fn authorize_is_always_json() {
+    let mut codec = ProverCodec::default();
+    codec.use_msgpack();
+    let bytes = frame(&mut codec, ProverMessage::Authorize("a".into(), "w".into(), "x".into(), 1));
+    assert_eq!(bytes[4], 0);
+}
+
+// What a subscriber logging at every level wrote.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn the_password_is_only_in_the_wire_bytes() {
+    const PASSWORD: &str = "s3cret-7f9q2";
+    let secret = Secret::from(PASSWORD);
+    assert_eq!((format!("{:?}", secret), secret.to_string()), ("***".to_string(), "***".to_string()));
+    let authorize = ProverMessage::Authorize("account".into(), "rig1".into(), secret, 1);
+    let debug = format!("{:?}", authorize);
+    assert!(debug.contains("***") && !debug.contains(PASSWORD), "{}", debug);
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let (mut bytes, decoded) = tracing::subscriber::with_default(subscriber, || {
+        let mut codec = ProverCodec::default();
+        let bytes = frame(&mut codec, authorize);
+        let decoded = codec.decode(&mut bytes.clone()).unwrap().unwrap();
+        (bytes, decoded)
+    });
+    let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(log.contains("Encoded Authorize"), "nothing captured: {}", log);
+    assert!(!log.contains(PASSWORD), "{}", log);
+    match decoded {
+        ProverMessage::Authorize(_, _, password, _) => assert_eq!(password.expose(), PASSWORD),
+        message => panic!("unexpected {:?}", message),
+    }
+    assert!(bytes.split_off(4).windows(PASSWORD.len()).any(|window| window == PASSWORD.as_bytes()));
+}
+
+#[test]
+fn reads_the_difficulty_from_the_password() {
+    let cases = [
+        ("d=5000", Some(5000)),
+        ("x,d=5000", Some(5000)),
+        (" x , d=5000 ", Some(5000)),
+        ("session=abc,d=64,x", Some(64)),
+        ("x", None),
+        ("", None),
+        ("d=0", None),
+        ("d=-1", None),
+        ("d=5k", None),
+        ("dd=5000", None),
+        ("x;d=5000", None),
+    ];
+    for (password, difficulty) in cases {
+        assert_eq!(difficulty_hint(password), difficulty, "{:?}", password);
+    }
+    assert_eq!(password_field("x,session=abc", "session"), Some("abc"));
+    assert_eq!(password_field("x,session=", "session"), Some(""));
+}
+
+#[test]
+fn sets_the_difficulty_keeping_the_other_fields() {
+    let cases = [("", "d=64"), ("x", "x,d=64"), ("x,d=5000,session=abc", "x,session=abc,d=64"), ("d=1", "d=64")];
+    for (password, expected) in cases {
+        assert_eq!(Secret::from(password).with_difficulty(64).expose(), expected, "{:?}", password);
+    }
+    let with_session = Secret::from("x,d=64").with_field("session", "abc");
+    assert_eq!(with_session.expose(), "x,d=64,session=abc");
+}
+
+#[test]
+fn counts_frames_and_bytes_per_message_type() {
+    let stats = Arc::new(CodecStats::default());
+    let mut prover = ProverCodec::with_stats(ProtocolLimits::default(), stats.clone());
+    let mut pool = ProverCodec::with_stats(ProtocolLimits::default(), stats.clone());
+    let authorize = ProverMessage::Authorize("account".into(), "rig1".into(), "x".into(), 1);
+    let mut expected: BTreeMap<&str, MessageTraffic> = BTreeMap::new();
+
+    // The prover's frames, read by the pool.
+    for message in [authorize, ProverMessage::Canary, ProverMessage::Canary, ProverMessage::ProofRate(7)] {
+        let name = message.name();
+        let mut bytes = frame(&mut prover, message);
+        let traffic = expected.entry(name).or_insert(MessageTraffic { message: name, ..Default::default() });
+        traffic.encoded_frames += 1;
+        traffic.encoded_bytes += bytes.len() as u64;
+        traffic.decoded_frames += 1;
+        traffic.decoded_bytes += bytes.len() as u64;
+        assert!(pool.decode(&mut bytes).unwrap().is_some());
+    }
+    // MessagePack frames count as their message type.
+    pool.use_msgpack();
+    for message in [testing::notify(), ProverMessage::Canary] {
+        let name = message.name();
+        let bytes = frame(&mut pool, message);
+        assert_ne!(bytes[4] & MSGPACK_FLAG, 0);
+        let traffic = expected.entry(name).or_insert(MessageTraffic { message: name, ..Default::default() });
+        traffic.encoded_frames += 1;
+        traffic.encoded_bytes += bytes.len() as u64;
+    }
+    // So do frames of unknown types, even failing ones.
+    let mut unknown = BytesMut::from(&[1, 0, 0, 0, 0x7f][..]);
+    assert!(prover.decode(&mut unknown).is_err());
+    let traffic = MessageTraffic {
+        message: "unknown",
+        decoded_frames: 1,
+        decoded_bytes: 5,
+        ..Default::default()
+    };
+    expected.insert("unknown", traffic);
+
+    let snapshot: BTreeMap<&str, MessageTraffic> =
+        stats.snapshot().into_iter().map(|traffic| (traffic.message, traffic)).collect();
+    assert_eq!(snapshot, expected);
+    assert_eq!(expected["Canary"].encoded_frames, 3);
+}
@@ -0,0 +1,73 @@
+// The Prometheus endpoint of a mining session, scraped while it mines against the mock pool.
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use aleoxminer::{
+    session::MiningSession,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+use tokio::time::sleep;
+
+// A port of 127.0.0.1 nothing listens on.
+fn free_address() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+// Value of the sample of `series`, labels included, none if it isn't exported.
+fn sample(metrics: &str, series: &str) -> Option<f64> {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+        .and_then(|value| value.parse().ok())
+}
+
+async fn scrape(address: SocketAddr) -> Option<String> {
+    let response = reqwest::get(format!("http://{}/metrics", address)).await.ok()?;
+    assert!(response.status().is_success());
+    response.text().await.ok()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exports_the_series_while_mining() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let address = free_address();
+    let session = MiningSession::builder()
+        .pool(pool.address())
+        .address(Some(testing::address()))
+        .worker(Some("metrics".to_string()))
+        .prover(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))))
+        .metrics_bind(address)
+        .build()
+        .unwrap();
+    session.start().await.unwrap();
+    assert!(eventually(LIMIT, || session.prover().stats().valid_shares >= 3).await);
+
+    let mut metrics = None;
+    for _ in 0..100 {
+        metrics = scrape(address).await;
+        if metrics.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    let metrics = metrics.expect("nothing served the metrics");
+    assert!(metrics.lines().any(|line| line.starts_with("aleoxminer_build_info{")));
+    assert!(sample(&metrics, "aleoxminer_shares_total{result=\"accepted\"}").unwrap() >= 3.0);
+    assert_eq!(sample(&metrics, "aleoxminer_shares_total{result=\"rejected\"}"), Some(0.0));
+    assert_eq!(sample(&metrics, "aleoxminer_connected"), Some(1.0));
+    assert!(sample(&metrics, "aleoxminer_proofs_total").unwrap() >= 3.0);
+    assert!(sample(&metrics, "aleoxminer_uptime_seconds").is_some());
+    assert!(sample(&metrics, "aleoxminer_reconnects_total").is_some());
+    assert!(sample(&metrics, "aleoxminer_submit_latency_seconds_count").unwrap() >= 3.0);
+    assert!(sample(&metrics, "aleoxminer_submit_latency_seconds_bucket{le=\"+Inf\"}").unwrap() >= 3.0);
+    // Every series is documented and prefixed.
+    for line in metrics.lines().filter(|line| !line.is_empty()) {
+        assert!(line.starts_with("aleoxminer_") || line.starts_with("# "), "{}", line);
+    }
+    session.shutdown().await;
+}
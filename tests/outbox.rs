@@ -0,0 +1,122 @@
+// Shares waiting for a result kept across a kill of the process, and submitted after the restart.
+
+mod common;
+
+use std::{fs::OpenOptions, io::Write, path::Path, sync::Arc, time::Duration};
+
+use aleoxminer::{
+    channel,
+    client::{self, Client},
+    outbox::{Outbox, OutboxEntry},
+    prover::ProverEvent,
+    testing::{self, MockPool, ALL_SHARES},
+    transport::DuplexConnector,
+};
+use common::{eventually, scratch, LIMIT};
+use snarkvm::{
+    dpc::testnet2::Testnet2,
+    traits::Network,
+    utilities::UniformRand,
+};
+use tokio::{
+    sync::mpsc::Receiver,
+    time::{sleep, timeout},
+};
+
+fn nonce() -> <Testnet2 as Network>::PoSWNonce {
+    <Testnet2 as Network>::PoSWNonce::rand(&mut rand::thread_rng())
+}
+
+fn entry(key: &str, height: u32) -> OutboxEntry {
+    OutboxEntry {
+        key: key.to_string(),
+        height,
+        nonce: nonce(),
+        proof: testing::header().proof().clone(),
+    }
+}
+
+// A run that found a share for `height` and was killed before the pool answered it.
+fn killed(stats_file: &Path, height: u32) -> <Testnet2 as Network>::PoSWNonce {
+    let client = testing::client("mock.pool:4040", "rig");
+    client.set_outbox(Some(Arc::new(Outbox::load(stats_file))));
+    let nonce = nonce();
+    client.keep_submit(height, nonce, testing::header().proof());
+    // Killed in the middle of writing the next record.
+    let mut outbox = stats_file.to_path_buf().into_os_string();
+    outbox.push(".outbox");
+    let mut file = OpenOptions::new().append(true).open(outbox).unwrap();
+    file.write_all(br#"{"add":{"key":"mock.pool:4040/rig","hei"#).unwrap();
+    nonce
+}
+
+// The run after the restart, mining on a pool at `height`.
+async fn restarted(stats_file: &Path, height: u32) -> (MockPool, Arc<Client>, Receiver<ProverEvent>) {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let pool = MockPool::duplex(connections);
+    pool.notify(testing::template(height), ALL_SHARES);
+    let client = pool.client("rig");
+    client.set_connector(Arc::new(connector));
+    client.set_outbox(Some(Arc::new(Outbox::load(stats_file))));
+    let (sender, events) = channel::channel("prover", 64);
+    client::start(sender, client.clone());
+    (pool, client, events)
+}
+
+#[test]
+fn takes_each_restored_share_once_and_drops_the_stale_ones() {
+    let stats_file = scratch("take").join("stats.json");
+    let outbox = Outbox::load(&stats_file);
+    let fresh = entry("a:4040/rig", 5);
+    let stale = entry("b:4040/rig", 4);
+    let answered = entry("a:4040/rig", 5);
+    for entry in [&fresh, &stale, &answered] {
+        outbox.add(entry.clone()).unwrap();
+    }
+    outbox.remove(&answered.nonce).unwrap();
+    drop(outbox);
+
+    let outbox = Outbox::load(&stats_file);
+    let taken = outbox.take_restored("a:4040/rig", 5);
+    assert_eq!(taken.iter().map(|entry| entry.nonce).collect::<Vec<_>>(), vec![fresh.nonce]);
+    assert!(outbox.take_restored("a:4040/rig", 5).is_empty());
+    assert!(outbox.take_restored("b:4040/rig", 5).is_empty());
+    drop(outbox);
+
+    // Still waiting for its result, the fresh share is restored again. The stale one is gone.
+    let outbox = Outbox::load(&stats_file);
+    assert!(outbox.take_restored("b:4040/rig", 0).is_empty());
+    assert_eq!(outbox.take_restored("a:4040/rig", 5).len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submits_a_fresh_share_of_the_killed_run() {
+    let stats_file = scratch("fresh").join("stats.json");
+    let nonce = killed(&stats_file, 5);
+    let (pool, _client, mut events) = restarted(&stats_file, 5).await;
+    let received = pool.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+    assert_eq!(received.shares, vec![(5, nonce)]);
+
+    // Answered, it isn't submitted by the next run.
+    let answered = timeout(LIMIT, async {
+        while let Some(event) = events.recv().await {
+            if let ProverEvent::Result { nonce, .. } = event {
+                return nonce;
+            }
+        }
+        None
+    });
+    assert_eq!(answered.await.expect("no result"), Some(nonce));
+    assert!(Outbox::load(&stats_file).take_restored("mock.pool:4040/rig", 5).is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drops_a_share_of_the_killed_run_the_pool_moved_past() {
+    let stats_file = scratch("stale").join("stats.json");
+    killed(&stats_file, 5);
+    let (pool, client, _events) = restarted(&stats_file, 6).await;
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    sleep(Duration::from_millis(500)).await;
+    assert!(pool.received().shares.is_empty());
+    assert!(Outbox::load(&stats_file).take_restored("mock.pool:4040/rig", 0).is_empty());
+}
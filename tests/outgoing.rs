@@ -0,0 +1,81 @@
+// Which queued messages are too old to send to the pool.
+
+use std::{
+    sync::{atomic::AtomicU64, Arc},
+    time::{Duration, Instant},
+};
+
+use aleoxminer::{
+    channel,
+    message::ProverMessage,
+    outgoing::{MaxAges, Outgoing, OutgoingSender},
+    testing,
+};
+
+// `message` queued `age` ago for job `epoch`.
+fn queued(message: ProverMessage, age: Duration, epoch: u64) -> Outgoing {
+    Outgoing {
+        message,
+        enqueued_at: Instant::now().checked_sub(age).unwrap(),
+        job_epoch: epoch,
+    }
+}
+
+fn submit() -> ProverMessage {
+    let header = testing::header();
+    ProverMessage::Submit(2, header.nonce(), header.proof().clone())
+}
+
+const MAX_AGES: MaxAges = MaxAges {
+    submit: Some(Duration::from_secs(60)),
+    proof_rate: Some(Duration::from_secs(30)),
+};
+
+#[test]
+fn drops_shares_of_earlier_jobs_and_proof_rates_past_their_age() {
+    let cases = [
+        (submit(), 61, 1, true),
+        (submit(), 59, 1, false),
+        // The share is for the job the pool is on.
+        (submit(), 61, 2, false),
+        (ProverMessage::ProofRate(1000), 31, 2, true),
+        (ProverMessage::ProofRate(1000), 29, 2, false),
+    ];
+    for (message, age, epoch, expired) in cases {
+        let name = message.name();
+        let outgoing = queued(message, Duration::from_secs(age), epoch);
+        assert_eq!(MAX_AGES.expired(&outgoing, 2), expired, "{} queued {}s ago for job {}", name, age, epoch);
+    }
+
+    let keep = MaxAges { submit: None, proof_rate: None };
+    assert!(!keep.expired(&queued(submit(), Duration::from_secs(120), 0), 2));
+    assert!(!keep.expired(&queued(ProverMessage::ProofRate(1000), Duration::from_secs(120), 0), 2));
+}
+
+#[test]
+fn never_drops_authorize_and_canary() {
+    let strict = MaxAges { submit: Some(Duration::ZERO), proof_rate: Some(Duration::ZERO) };
+    let authorize = ProverMessage::Authorize("account".into(), "rig1".into(), "x".into(), 1);
+    for message in [authorize, ProverMessage::Canary] {
+        let name = message.name();
+        let outgoing = queued(message, Duration::from_secs(120), 0);
+        assert!(!strict.expired(&outgoing, 5), "{} dropped", name);
+        assert!(!MAX_AGES.expired(&outgoing, 5), "{} dropped", name);
+    }
+}
+
+#[tokio::test]
+async fn stamps_messages_with_the_job_they_were_queued_for() {
+    let epoch = Arc::new(AtomicU64::new(3));
+    let (sender, mut receiver) = channel::channel("outgoing", 4);
+    let sender = OutgoingSender::with_epoch(sender, epoch.clone());
+    sender.send(ProverMessage::Canary).await.unwrap();
+    epoch.store(4, std::sync::atomic::Ordering::SeqCst);
+    sender.send(ProverMessage::ProofRate(1)).await.unwrap();
+
+    let first = receiver.recv().await.unwrap();
+    assert_eq!((first.message, first.job_epoch), (ProverMessage::Canary, 3));
+    let second = receiver.recv().await.unwrap();
+    assert_eq!((second.message, second.job_epoch), (ProverMessage::ProofRate(1), 4));
+    assert!(second.age() < Duration::from_secs(5));
+}
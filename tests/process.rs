@@ -0,0 +1,159 @@
+// The miner binary run as a process: what it prints and writes, and the status it exits with.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    time::Duration,
+};
+
+use aleoxminer::testing::{self, MockPool, NO_SHARES};
+use common::scratch;
+use tokio::time::timeout;
+
+const PASSWORD: &str = "s3cret-7f9q2";
+
+// Runs the miner with `args` until it exits.
+async fn miner(args: Vec<String>) -> Output {
+    miner_with(args, &[]).await
+}
+
+// Runs the miner with `args` and the environment variables `vars` until it exits.
+async fn miner_with(args: Vec<String>, vars: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_AleoXMiner"));
+    command.args(args).env_remove("RUST_LOG").envs(vars.iter().copied());
+    tokio::task::spawn_blocking(move || command.output().unwrap()).await.unwrap()
+}
+
+// Arguments mining to the fixture address on `pool` without proving.
+fn mining(pool: &str, extra: &[&str]) -> Vec<String> {
+    let address = testing::address().to_string();
+    let mut args = args(&["--address", &address, "--pool", pool, "--no-prover"]);
+    args.extend(extra.iter().map(|arg| arg.to_string()));
+    args
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+// Every file below `dir`.
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(self::files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn contains(output: &[u8], text: &str) -> bool {
+    output.windows(text.len()).any(|window| window == text.as_bytes())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn never_outputs_the_password() {
+    let config = scratch("password-config").join("miner.toml");
+    fs::write(&config, format!("password = \"{}\"\n", PASSWORD)).unwrap();
+    let printed = miner(args(&["--config", config.to_str().unwrap(), "--print-config"])).await;
+    assert!(printed.status.success(), "{}", String::from_utf8_lossy(&printed.stderr));
+    assert!(contains(&printed.stdout, "password = \"<redacted>\""));
+    assert!(!contains(&printed.stdout, PASSWORD) && !contains(&printed.stderr, PASSWORD));
+
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), NO_SHARES);
+    let dir = scratch("password-run");
+    fs::create_dir(dir.join("log")).unwrap();
+    let output = miner(args(&[
+        "--address",
+        &testing::address().to_string(),
+        "--worker",
+        "secret",
+        "--password",
+        PASSWORD,
+        "--pool",
+        &pool.address(),
+        "--no-prover",
+        "--max-runtime",
+        "3s",
+        "--console-level",
+        "trace",
+        "--file-level",
+        "trace",
+        "--log-file",
+        dir.join("log").join("miner.log").to_str().unwrap(),
+        "--run-report",
+        dir.join("report.json").to_str().unwrap(),
+        "--record-traffic",
+        dir.join("traffic").to_str().unwrap(),
+    ]))
+    .await;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(pool.received().authorizations.len(), 1);
+
+    assert!(!contains(&output.stdout, PASSWORD), "password on stdout");
+    assert!(!contains(&output.stderr, PASSWORD), "password on stderr");
+    let files = files(&dir);
+    for produced in ["log", "report.json", "traffic"] {
+        assert!(files.iter().any(|file| file.starts_with(dir.join(produced))), "no {} written", produced);
+    }
+    for file in files {
+        assert!(!contains(&fs::read(&file).unwrap(), PASSWORD), "password in {}", file.display());
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exits_with_2_on_configuration_errors() {
+    let config = scratch("config-error").join("miner.toml");
+    fs::write(&config, "[prover]\nthreads = 0\n").unwrap();
+    let cases = [
+        (args(&["--config", config.to_str().unwrap()]), Vec::new()),
+        (args(&["--config", "/nonexistent/miner.toml"]), Vec::new()),
+        (mining("127.0.0.1:4040", &["--worker", "rig.1"]), Vec::new()),
+        (mining("127.0.0.1:4040", &[]), vec![("ALEOXMINER_PROVER__NICE", "maybe")]),
+        (args(&["--pool", "127.0.0.1:4040", "--no-prover"]), Vec::new()),
+    ];
+    for (args, vars) in cases {
+        let output = miner_with(args.clone(), &vars).await;
+        assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+// MQTT is not a default feature, the setting is refused by default builds.
+#[cfg(not(feature = "mqtt"))]
+#[tokio::test(flavor = "multi_thread")]
+async fn names_the_feature_a_configured_setting_needs() {
+    let config = scratch("feature").join("miner.toml");
+    fs::write(&config, "[telemetry]\nmqtt_broker = \"localhost:1883\"\n").unwrap();
+    let output = miner(args(&["--config", config.to_str().unwrap(), "--print-config"])).await;
+    assert_eq!(output.status.code(), Some(2), "{}", String::from_utf8_lossy(&output.stderr));
+    let needs = "telemetry.mqtt_broker: needs the `mqtt` feature, build with `--features mqtt`";
+    assert!(contains(&output.stderr, needs), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exits_with_3_when_the_pool_keeps_rejecting_the_credentials() {
+    let pool = MockPool::start().await.unwrap();
+    pool.set_authorize(false);
+    let output = timeout(Duration::from_secs(120), miner(mining(&pool.address(), &[])))
+        .await
+        .expect("the miner kept running");
+    assert_eq!(output.status.code(), Some(3), "{}", String::from_utf8_lossy(&output.stdout));
+    assert_eq!(pool.received().authorizations.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exits_with_10_once_the_pool_is_unreachable_for_too_long() {
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let output = timeout(Duration::from_secs(120), miner(mining(&closed, &["--pool-unreachable-exit", "1s"])))
+        .await
+        .expect("the miner kept running");
+    assert_eq!(output.status.code(), Some(10), "{}", String::from_utf8_lossy(&output.stdout));
+}
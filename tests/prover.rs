@@ -0,0 +1,554 @@
+// The prover as a library type: start, pause, resume and stop, proving on a fake backend.
+
+mod common;
+
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+use aleoxminer::{
+    client,
+    job_trace::JobTrace,
+    message::Code,
+    prover::{PauseReason, Prover, ProverConfig, ProverEvent},
+    testing::{self, FakeBackend, MockPool, PanickingSampler, ALL_SHARES, NO_SHARES},
+};
+use common::{eventually, work, LIMIT};
+use tokio::{
+    sync::oneshot,
+    time::{sleep, timeout},
+};
+
+// Two CPU pools of 8 threads.
+const THREADS: u16 = 16;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lifecycle() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let client = testing::client("127.0.0.1:1", "lifecycle");
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), client).unwrap();
+    prover.start().await.unwrap();
+    assert!(prover.start().await.is_err(), "started twice");
+
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.workers() == BTreeSet::from([0, 1])).await);
+    assert!(prover.stats().running);
+
+    // Pausing waits for the workers, nothing is proving once it returns.
+    timeout(Duration::from_secs(1), prover.pause(PauseReason::Manual))
+        .await
+        .expect("pausing took over a second");
+    assert_eq!(backend.proving(), 0);
+    assert!(prover.stats().paused);
+    let attempts = backend.attempts();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(backend.attempts(), attempts);
+
+    // Work arriving while paused is held for the resume.
+    work(&prover, 3, NO_SHARES).await;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(backend.attempts(), attempts);
+    assert!(eventually(LIMIT, || prover.stats().current_block == 3).await);
+
+    prover.resume(PauseReason::Manual).await;
+    assert!(!prover.stats().paused);
+    assert!(eventually(LIMIT, || backend.attempts() > attempts).await);
+
+    prover.stop().await;
+    assert_eq!(backend.proving(), 0);
+    assert!(!prover.stats().running);
+    let attempts = backend.attempts();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(backend.attempts(), attempts);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_without_work() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "idle"))
+        .unwrap();
+    prover.start().await.unwrap();
+    timeout(LIMIT, prover.stop()).await.expect("stopping hung");
+    assert_eq!(backend.attempts(), 0);
+    assert!(!prover.stats().running);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mines_only_without_any_pause_reason() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "pauses"))
+        .unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.attempts() > 0).await);
+
+    // The schedule closes, then the desktop gets busy and quiet again: still outside the schedule.
+    prover.pause(PauseReason::Schedule).await;
+    prover.pause(PauseReason::Idle).await;
+    assert_eq!(prover.stats().pause_reasons, vec![PauseReason::Schedule, PauseReason::Idle]);
+    prover.resume(PauseReason::Idle).await;
+    assert_eq!(prover.stats().pause_reasons, vec![PauseReason::Schedule]);
+    let attempts = backend.attempts();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(backend.attempts(), attempts);
+
+    // Resuming for a reason that isn't set changes nothing.
+    prover.resume(PauseReason::Manual).await;
+    assert!(prover.stats().paused);
+
+    prover.resume(PauseReason::Schedule).await;
+    assert!(prover.stats().pause_reasons.is_empty());
+    assert!(eventually(LIMIT, || backend.attempts() > attempts).await);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pauses_on_rejects_until_cleared() {
+    let pool = MockPool::start().await.unwrap();
+    pool.set_result(Code::InvalidProof, Some("invalid proof".to_string()));
+    pool.notify(testing::template(2), ALL_SHARES);
+    let client = pool.client("rejects");
+    let backend = FakeBackend::new(Duration::from_millis(20));
+    let mut config = testing::prover_config(THREADS, backend.clone());
+    config.reject_window = 10;
+    let prover = Prover::new(config, client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client);
+
+    // Every device rejects, each is paused on its own or all at once for the results that couldn't
+    // be attributed.
+    let stopped = || {
+        let stats = prover.stats();
+        stats.pause_reasons == vec![PauseReason::Rejects]
+            || stats.devices.iter().all(|device| device.state.to_string() == "paused")
+    };
+    assert!(eventually(LIMIT, stopped).await);
+    assert!(prover.stats().invalid_shares >= 10);
+    assert!(eventually(LIMIT, || backend.proving() == 0).await);
+
+    // Results of the shares sent before the pause arrive while tripped and are ignored.
+    pool.set_result(Code::Success, None);
+    sleep(Duration::from_millis(200)).await;
+    prover.clear_rejects().await;
+    assert!(!prover.stats().paused);
+    assert!(eventually(LIMIT, || prover.stats().valid_shares > 0).await);
+    assert!(!prover.stats().paused);
+    assert!(prover.stats().devices.iter().all(|device| device.state.to_string() != "paused"));
+    prover.stop().await;
+}
+
+// Reports `count` results with `code` for shares of `worker`, or of no known worker.
+async fn results(prover: &Prover, worker: Option<usize>, code: Code, count: usize) {
+    for _ in 0..count {
+        let event = ProverEvent::Result {
+            code: code.clone(),
+            message: None,
+            height: 2,
+            nonce: None,
+            device: worker,
+        };
+        assert!(prover.event_sender().send(event).await.is_ok(), "prover stopped");
+    }
+}
+
+// A prover mining block 2 with a reject window of 4 shares.
+async fn guarded(backend: Arc<FakeBackend>) -> Arc<Prover> {
+    let mut config = testing::prover_config(THREADS, backend.clone());
+    config.reject_window = 4;
+    let prover = Prover::new(config, testing::client("127.0.0.1:1", "guarded")).unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.workers() == BTreeSet::from([0, 1])).await);
+    prover
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pauses_only_the_device_the_rejects_come_from() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = guarded(backend.clone()).await;
+    results(&prover, Some(0), Code::Success, 4).await;
+    results(&prover, Some(1), Code::InvalidProof, 4).await;
+    assert!(eventually(LIMIT, || prover.stats().devices[1].state.to_string() == "paused").await);
+    assert!(!prover.stats().paused);
+
+    // Worker 0 keeps proving while worker 1 stays idle.
+    sleep(Duration::from_millis(100)).await;
+    let (first, second) = (backend.draws(0).len(), backend.draws(1).len());
+    sleep(Duration::from_millis(200)).await;
+    assert!(backend.draws(0).len() > first, "worker 0 stalled");
+    assert_eq!(backend.draws(1).len(), second);
+    assert_eq!(prover.stats().devices[0].state.to_string(), "mining");
+
+    prover.clear_rejects().await;
+    assert!(eventually(LIMIT, || backend.draws(1).len() > second).await);
+    assert_eq!(prover.stats().devices[1].state.to_string(), "mining");
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pauses_every_device_on_rejects_it_cannot_attribute() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = guarded(backend.clone()).await;
+    results(&prover, None, Code::InvalidProof, 4).await;
+    assert!(eventually(LIMIT, || prover.stats().pause_reasons == vec![PauseReason::Rejects]).await);
+    assert_eq!(backend.proving(), 0);
+    assert!(prover.stats().devices.iter().all(|device| device.state.to_string() != "paused"));
+
+    // Neither the operator's resume nor another controller lifts it.
+    prover.resume(PauseReason::Manual).await;
+    prover.resume(PauseReason::Schedule).await;
+    assert_eq!(prover.stats().pause_reasons, vec![PauseReason::Rejects]);
+
+    let attempts = backend.attempts();
+    prover.clear_rejects().await;
+    assert!(!prover.stats().paused);
+    assert!(eventually(LIMIT, || backend.attempts() > attempts).await);
+    prover.stop().await;
+}
+
+// Attempts of each worker until both made `attempts`, with the nonce generator seeded from `seed`.
+async fn draws(seed: Option<u64>, attempts: usize) -> (Vec<u64>, Vec<u64>) {
+    let backend = FakeBackend::new(Duration::from_millis(1));
+    let mut config = testing::prover_config(THREADS, backend.clone());
+    config.deterministic_seed = seed;
+    let prover = Prover::new(config, testing::client("127.0.0.1:1", "seeded")).unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    let done = || backend.draws(0).len() >= attempts && backend.draws(1).len() >= attempts;
+    assert!(eventually(LIMIT, done).await);
+    prover.stop().await;
+    let mut first = backend.draws(0);
+    let mut second = backend.draws(1);
+    first.truncate(attempts);
+    second.truncate(attempts);
+    (first, second)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn seeded_nonce_sequence_repeats() {
+    let (first, second) = draws(Some(7), 20).await;
+    assert_eq!(draws(Some(7), 20).await, (first.clone(), second.clone()));
+    // Every worker has a stream of its own.
+    assert_ne!(first, second);
+    assert_ne!(draws(Some(8), 20).await.0, first);
+    assert_ne!(draws(None, 20).await.0, first);
+}
+
+// Busy workers never exceed `workers` over a few attempts.
+async fn settled(backend: &FakeBackend, workers: usize) -> bool {
+    for _ in 0..20 {
+        if backend.proving() > workers {
+            return false;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    true
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scales_threads_while_mining() {
+    // Three CPU pools of 6 threads, scaling stays within them whatever the host.
+    let backend = FakeBackend::new(Duration::from_millis(20));
+    let prover = Prover::new(testing::prover_config(18, backend.clone()), testing::client("127.0.0.1:1", "scales"))
+        .unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.proving() == 3).await);
+
+    for (threads, workers) in [(6, 1), (12, 2), (18, 3), (7, 1), (0, 1), (18, 3)] {
+        assert_eq!(prover.set_threads(threads).await.unwrap(), workers * 6);
+        assert_eq!(prover.stats().threads, workers * 6);
+        assert!(eventually(LIMIT, || backend.proving() == workers).await, "never reached {} workers", workers);
+        assert!(settled(&backend, workers).await, "more than {} workers after settling", workers);
+    }
+    prover.stop().await;
+    assert_eq!(backend.proving(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn limits_concurrent_proofs() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let mut config = testing::prover_config(THREADS, backend.clone());
+    config.max_concurrent_proofs = Some(1);
+    let prover = Prover::new(config, testing::client("127.0.0.1:1", "limited")).unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.workers() == BTreeSet::from([0, 1])).await);
+    assert!(settled(&backend, 1).await, "more than one proof at once");
+    // Workers waiting for the limit don't hold up stopping.
+    timeout(LIMIT, prover.stop()).await.expect("stopping hung");
+    assert_eq!(backend.proving(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failing_worker_restarts_alone() {
+    let backend = FakeBackend::new(Duration::from_millis(2));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "failing"))
+        .unwrap();
+    backend.fail(1);
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+
+    let restarting = || {
+        let devices = prover.stats().devices;
+        devices[1].failures > 0 && devices[1].state.to_string() == "restarting"
+    };
+    assert!(eventually(LIMIT, restarting).await);
+    // The healthy worker keeps producing attempts while the other one backs off.
+    for _ in 0..5 {
+        let attempts = backend.draws(0).len();
+        sleep(Duration::from_millis(100)).await;
+        assert!(backend.draws(0).len() > attempts, "worker 0 stalled");
+        assert_eq!(prover.stats().devices[0].state.to_string(), "mining");
+    }
+    assert!(backend.draws(1).len() >= 5);
+    prover.stop().await;
+}
+
+// On a paused clock, the minutes between two telemetry readings pass while the test polls.
+#[tokio::test(start_paused = true)]
+async fn keeps_mining_when_a_background_task_panics() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let sampler = PanickingSampler::new();
+    let mut config = testing::prover_config(THREADS, backend.clone());
+    config.cuda = Some(vec![0]);
+    config.telemetry = Some(sampler.clone());
+    let prover = Prover::new(config, testing::client("127.0.0.1:1", "telemetry")).unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.attempts() > 0).await);
+
+    // The statistics task reads the telemetry once a minute and is restarted after every panic.
+    assert!(eventually(LIMIT, || sampler.samples() >= 2).await);
+    let attempts = backend.attempts();
+    assert!(eventually(LIMIT, || backend.attempts() > attempts).await);
+    assert!(prover.stats().running);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn attributes_results_to_the_worker_of_the_share() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let client = pool.client("attribution");
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client);
+
+    assert!(eventually(LIMIT, || prover.stats().valid_shares >= 4).await);
+    pool.set_result(Code::InvalidProof, Some("invalid proof".to_string()));
+    assert!(eventually(LIMIT, || prover.stats().invalid_shares >= 2).await);
+    prover.stop().await;
+
+    // Every result went back to the worker whose nonce it was, none is left unattributed.
+    let stats = prover.stats();
+    let valid: u32 = stats.worker_shares.iter().map(|(valid, _)| valid).sum();
+    let invalid: u32 = stats.worker_shares.iter().map(|(_, invalid)| invalid).sum();
+    assert_eq!((valid, invalid), (stats.valid_shares, stats.invalid_shares));
+}
+
+// A prover mining for `pool` through a client.
+async fn mining(pool: &MockPool, worker: &str) -> (Arc<Prover>, Arc<FakeBackend>) {
+    let client = pool.client(worker);
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client);
+    (prover, backend)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submits_proofs_meeting_the_pool_target() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let (prover, _) = mining(&pool, "above").await;
+
+    pool.wait_for(LIMIT, |received| received.shares.len() >= 3).await.unwrap();
+    assert!(eventually(LIMIT, || prover.stats().valid_shares >= 3).await);
+    assert_eq!(prover.stats().below_target, 0);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keeps_proofs_below_the_pool_target() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), NO_SHARES);
+    let (prover, _) = mining(&pool, "below").await;
+
+    assert!(eventually(LIMIT, || prover.stats().below_target >= 10).await);
+    prover.stop().await;
+    let stats = prover.stats();
+    assert!(pool.received().shares.is_empty());
+    assert_eq!((stats.valid_shares, stats.invalid_shares), (0, 0));
+    assert_eq!(stats.below_target, stats.total_proofs);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn applies_a_target_changed_mid_job_to_the_next_checks() {
+    let pool = MockPool::start().await.unwrap();
+    let template = testing::template(2);
+    pool.notify(template.clone(), NO_SHARES);
+    let (prover, _) = mining(&pool, "retarget").await;
+    assert!(eventually(LIMIT, || prover.stats().below_target >= 10).await);
+    assert!(pool.received().shares.is_empty());
+
+    // Same template, only the target changes: the job goes on and its next proofs are shares.
+    pool.notify(template.clone(), ALL_SHARES);
+    pool.wait_for(LIMIT, |received| received.shares.len() >= 3).await.unwrap();
+    let below_target = prover.stats().below_target;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(prover.stats().below_target, below_target);
+    assert_eq!(prover.stats().current_block, 2);
+
+    pool.notify(template, NO_SHARES);
+    assert!(eventually(LIMIT, || prover.stats().below_target > below_target).await);
+    sleep(Duration::from_millis(200)).await;
+    let shares = pool.received().shares.len();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(pool.received().shares.len(), shares);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_the_proof_rate_with_fewer_threads_than_a_pool() {
+    // Too few threads for one CPU pool: below 6 logical CPUs there are no workers at all.
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), NO_SHARES);
+    let client = pool.client("few-threads");
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(4, backend), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client);
+
+    // Reports every second, the second one comes from a reporter that survived the first.
+    pool.wait_for(LIMIT, |received| received.proof_rates.len() >= 2).await.unwrap();
+    assert!(prover.stats().running);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn counts_an_accepted_share_at_the_target_it_was_found_at() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend), testing::client("127.0.0.1:1", "share-target"))
+        .unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, ALL_SHARES).await;
+    assert!(eventually(LIMIT, || prover.stats().total_proofs > 0).await);
+
+    // The pool raises the difficulty before the result arrives. Every proof is the genesis header.
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || prover.stats().below_target > 0).await);
+    let result = ProverEvent::Result {
+        code: Code::Success,
+        message: None,
+        height: 2,
+        nonce: Some(testing::header().nonce()),
+        device: None,
+    };
+    assert!(prover.event_sender().send(result).await.is_ok());
+    assert!(eventually(LIMIT, || prover.stats().valid_shares == 1).await);
+    // Difficulty 1 of the target the share met, not the new one's.
+    assert_eq!(prover.stats().accepted_difficulty, 1);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exit_joins_the_workers_and_stops_attempts() {
+    // Attempts far longer than the exit timeout, the workers have to be interrupted.
+    let backend = FakeBackend::new(Duration::from_secs(60));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "exit"))
+        .unwrap();
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.proving() > 0).await);
+
+    let (done, exited) = oneshot::channel();
+    assert!(prover.event_sender().send(ProverEvent::Exit(done)).await.is_ok());
+    timeout(LIMIT, exited).await.expect("exit hung").unwrap();
+    assert_eq!(backend.proving(), 0);
+    assert!(!prover.stats().running);
+
+    // Work arriving after the exit starts nothing.
+    let _ = prover.event_sender().send(ProverEvent::NewWork(NO_SHARES, testing::template(3), JobTrace::none())).await;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!((backend.proving(), backend.attempts()), (0, 0));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn records_the_best_share_and_the_network_target() {
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "best"))
+        .unwrap();
+    assert_eq!(prover.stats().best_difficulty, 0);
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || prover.stats().below_target > 0).await);
+    prover.stop().await;
+
+    // Every fake attempt returns the genesis header, its proof is the best one.
+    let proof_target = testing::header().proof().to_proof_difficulty().unwrap();
+    let stats = prover.stats();
+    assert_eq!(stats.best_difficulty, u64::MAX / proof_target);
+    assert_eq!(stats.network_target, testing::template(2).difficulty_target());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn loads_the_parameters_once_for_every_waiter() {
+    let backend = FakeBackend::with_parameters(Duration::from_millis(5), Duration::from_millis(200));
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "warm"))
+        .unwrap();
+    let (first, second, third) = tokio::join!(prover.warmup(), prover.warmup(), prover.warmup());
+    let took = first.unwrap();
+    assert!(took >= Duration::from_millis(200), "{:?}", took);
+    assert_eq!((second.unwrap(), third.unwrap()), (took, took));
+    assert_eq!(backend.loads(), 1);
+
+    // The first work finds them loaded.
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.attempts() > 0).await);
+    assert_eq!(prover.warmup().await.unwrap(), took);
+    assert_eq!(backend.loads(), 1);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn proves_nothing_until_the_parameters_load() {
+    let backend = FakeBackend::with_parameters(Duration::from_millis(5), Duration::ZERO);
+    let prover = Prover::new(testing::prover_config(THREADS, backend.clone()), testing::client("127.0.0.1:1", "cold"))
+        .unwrap();
+    backend.fail_loading(Some(false));
+    assert!(prover.warmup().await.is_err());
+    backend.fail_loading(Some(true));
+    assert!(prover.warmup().await.is_err());
+    assert_eq!(backend.loads(), 2);
+
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.loads() == 3).await);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(backend.attempts(), 0);
+
+    // The next work loads them again.
+    backend.fail_loading(None);
+    work(&prover, 3, NO_SHARES).await;
+    assert!(eventually(LIMIT, || backend.attempts() > 0).await);
+    assert_eq!(backend.loads(), 4);
+    prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn never_loads_the_parameters_without_proving() {
+    let backend = FakeBackend::with_parameters(Duration::from_millis(5), Duration::ZERO);
+    let config = ProverConfig {
+        proving: false,
+        ..testing::prover_config(THREADS, backend.clone())
+    };
+    let prover = Prover::new(config, testing::client("127.0.0.1:1", "external")).unwrap();
+    assert_eq!(prover.warmup().await.unwrap(), Duration::ZERO);
+    prover.start().await.unwrap();
+    work(&prover, 2, NO_SHARES).await;
+    assert!(eventually(LIMIT, || prover.stats().current_block == 2).await);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!((backend.loads(), backend.attempts()), (0, 0));
+    prover.stop().await;
+}
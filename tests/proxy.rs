@@ -0,0 +1,129 @@
+// Rigs mining through the proxy to the mock pool, over loopback sockets.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use aleoxminer::{
+    client::{self, Client},
+    events::MinerEvent,
+    message::{Code, ProtocolLimits, MULTIPLEX_CAPABILITY},
+    prover::Prover,
+    proxy::{self, ProxyOptions},
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+use tokio::{net::TcpListener, sync::broadcast, time::timeout};
+
+// A proxy to `pool` on a free port of 127.0.0.1, connected upstream as worker "farm".
+async fn proxy(pool: &MockPool, multiplex: bool, rig_password: Option<&str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let options = ProxyOptions {
+        listen: address,
+        rig_password: rig_password.map(Into::into),
+        multiplex,
+        stats_interval: 0,
+    };
+    tokio::spawn(proxy::run_on(listener, options, pool.client("farm"), ProtocolLimits::default()));
+    address.to_string()
+}
+
+struct Rig {
+    client: Arc<Client>,
+    prover: Arc<Prover>,
+    events: broadcast::Receiver<MinerEvent>,
+}
+
+// A rig mining through `proxy` as `worker`.
+async fn rig(proxy: &str, worker: &str, password: &str) -> Rig {
+    let client = testing::client(proxy, worker);
+    client.set_password(password.into());
+    let events = client.events().subscribe();
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())
+        .unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    Rig { client, prover, events }
+}
+
+impl Rig {
+    // Codes of the next `count` share results the rig got.
+    async fn results(&mut self, count: usize) -> Vec<Code> {
+        let mut codes = Vec::new();
+        while codes.len() < count {
+            match timeout(LIMIT, self.events.recv()).await.expect("no share result") {
+                Ok(MinerEvent::ShareAccepted(result) | MinerEvent::ShareRejected(result)) => codes.push(result.code),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        codes
+    }
+}
+
+fn shares_of(forwarded: &[(String, u32)], worker: &str) -> usize {
+    forwarded.iter().filter(|(share_worker, _)| share_worker == worker).count()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rigs_mine_under_their_workers_through_one_connection() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    pool.set_worker_result("rig_a", Code::Stale, Some("share is stale".to_string()));
+    let proxy = proxy(&pool, true, None).await;
+    let mut rig_a = rig(&proxy, "rig_a", "x").await;
+    let mut rig_b = rig(&proxy, "rig_b", "x").await;
+
+    let received = pool
+        .wait_for(LIMIT, |received| {
+            shares_of(&received.forwarded, "rig_a") >= 3 && shares_of(&received.forwarded, "rig_b") >= 3
+        })
+        .await
+        .unwrap();
+    assert_eq!(received.connections, 1);
+    assert_eq!(received.authorizations.len(), 1);
+    let (_, worker, version) = &received.authorizations[0];
+    assert_eq!(worker, "farm");
+    assert_ne!(version & MULTIPLEX_CAPABILITY, 0);
+    assert_eq!(shares_of(&received.forwarded, "farm"), 0);
+
+    // Each result goes back to the rig that found the share, with the pool's code.
+    assert!(rig_a.results(3).await.iter().all(|code| *code == Code::Stale));
+    assert!(rig_b.results(3).await.iter().all(|code| *code == Code::Success));
+    assert!(eventually(LIMIT, || rig_b.prover.stats().valid_shares >= 3).await);
+    assert_eq!(rig_a.prover.stats().valid_shares, 0);
+    rig_a.prover.stop().await;
+    rig_b.prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rigs_mine_under_the_proxy_worker_without_multiplexing() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let proxy = proxy(&pool, false, None).await;
+    let mut rig_a = rig(&proxy, "rig_a", "x").await;
+
+    let received = pool.wait_for(LIMIT, |received| received.forwarded.len() >= 3).await.unwrap();
+    assert_eq!(received.authorizations[0].2 & MULTIPLEX_CAPABILITY, 0);
+    assert_eq!(shares_of(&received.forwarded, "farm"), received.forwarded.len());
+    assert!(received.messages.iter().all(|message| *message != "Worker"));
+    assert!(rig_a.results(3).await.iter().all(|code| *code == Code::Success));
+    rig_a.prover.stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rigs_without_the_password_are_rejected() {
+    let pool = MockPool::start().await.unwrap();
+    pool.notify(testing::template(2), ALL_SHARES);
+    let proxy = proxy(&pool, true, Some("s3cret")).await;
+    let intruder = rig(&proxy, "intruder", "x").await;
+    let member = rig(&proxy, "member", "x,s3cret").await;
+
+    let received = pool.wait_for(LIMIT, |received| shares_of(&received.forwarded, "member") >= 3).await.unwrap();
+    assert_eq!(shares_of(&received.forwarded, "intruder"), 0);
+    assert!(member.client.stats().authorized);
+    assert!(!intruder.client.stats().authorized);
+    intruder.prover.stop().await;
+    member.prover.stop().await;
+}
@@ -0,0 +1,142 @@
+// Pool quality scores, the failover hysteresis and a switch between two mock pools.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use aleoxminer::{
+    client,
+    message::Code,
+    prover::Prover,
+    quality::{self, Failover, FailoverPolicy, QualityInputs, QualityTracker},
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+
+fn millis(millis: u64) -> Option<Duration> {
+    Some(Duration::from_millis(millis))
+}
+
+#[test]
+fn takes_off_each_input_up_to_its_cap() {
+    // Round trip and first job times in milliseconds.
+    let cases = [
+        (None, 0.0, 0, None, 100.0),
+        (Some(150), 0.0, 0, None, 90.0),
+        (Some(5000), 0.0, 0, None, 60.0),
+        (None, 0.05, 0, None, 80.0),
+        (None, 1.0, 0, None, 60.0),
+        (None, 0.0, 2, None, 90.0),
+        (None, 0.0, 50, None, 80.0),
+        (None, 0.0, 0, Some(3000), 97.0),
+        (None, 0.0, 0, Some(60_000), 90.0),
+        (Some(300), 0.025, 1, Some(5000), 60.0),
+    ];
+    for (rtt, stale_ratio, disconnects, first_notify, expected) in cases {
+        let inputs = QualityInputs {
+            rtt: rtt.map(Duration::from_millis),
+            stale_ratio,
+            disconnects,
+            first_notify: first_notify.map(Duration::from_millis),
+        };
+        let score = quality::score(&inputs);
+        assert!((score - expected).abs() < 1e-9, "{:?} scored {}, not {}", inputs, score, expected);
+    }
+}
+
+#[test]
+fn never_scores_below_zero() {
+    let worst = QualityInputs {
+        rtt: Some(Duration::from_secs(60)),
+        stale_ratio: 1.0,
+        disconnects: 100,
+        first_notify: Some(Duration::from_secs(60)),
+    };
+    assert_eq!(quality::score(&worst), 0.0);
+}
+
+#[test]
+fn averages_the_results_from_the_first_sample_on() {
+    let mut tracker = QualityTracker::default();
+    assert_eq!(tracker.inputs(), QualityInputs::default());
+
+    tracker.record_result(Duration::from_millis(100), true);
+    tracker.record_first_notify(Duration::from_secs(2));
+    let inputs = tracker.inputs();
+    assert_eq!(inputs.rtt, millis(100));
+    assert_eq!(inputs.stale_ratio, 1.0);
+    assert_eq!(inputs.first_notify, millis(2000));
+
+    // Later samples weigh a tenth.
+    tracker.record_result(Duration::from_millis(200), false);
+    let inputs = tracker.inputs();
+    assert!((inputs.rtt.unwrap().as_secs_f64() - 0.11).abs() < 1e-6, "{:?}", inputs.rtt);
+    assert!((inputs.stale_ratio - 0.9).abs() < 1e-9, "{}", inputs.stale_ratio);
+
+    tracker.record_disconnect();
+    tracker.record_disconnect();
+    assert_eq!(tracker.inputs().disconnects, 2);
+}
+
+const POLICY: FailoverPolicy = FailoverPolicy {
+    margin: 10.0,
+    sustain: Duration::from_secs(60),
+    probe_interval: Duration::from_secs(10),
+};
+
+#[test]
+fn switches_once_the_active_pool_stayed_below_for_the_sustain_period() {
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+    let mut failover = Failover::default();
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(0)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(59)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(60)), Some("backup".to_string()));
+    // The period starts over after a switch.
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("other", 90.0)), at(61)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("other", 90.0)), at(120)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("other", 90.0)), at(121)), Some("other".to_string()));
+}
+
+#[test]
+fn starts_over_when_the_active_pool_recovers() {
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+    let mut failover = Failover::default();
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(0)), None);
+    // Within the margin.
+    assert_eq!(failover.decide(&POLICY, 80.0, Some(("backup", 90.0)), at(30)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(40)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(90)), None);
+    // No alternative answered the probes.
+    assert_eq!(failover.decide(&POLICY, 50.0, None, at(95)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(100)), None);
+    assert_eq!(failover.decide(&POLICY, 50.0, Some(("backup", 90.0)), at(160)), Some("backup".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn switches_to_the_better_of_two_pools() {
+    let primary = MockPool::start().await.unwrap();
+    let backup = MockPool::start().await.unwrap();
+    primary.notify(testing::template(2), ALL_SHARES);
+    backup.notify(testing::template(2), ALL_SHARES);
+    // Every share on the primary comes back stale, which costs it 40 points.
+    primary.set_result(Code::Stale, Some("too late".to_string()));
+
+    let client = primary.client("failover");
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(16, backend), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+    primary.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+
+    let policy = FailoverPolicy {
+        margin: 10.0,
+        sustain: Duration::from_millis(300),
+        probe_interval: Duration::from_millis(100),
+    };
+    quality::spawn(client.clone(), vec![primary.address(), backup.address()], policy);
+    assert!(eventually(LIMIT, || client.server() == backup.address()).await, "stayed on {}", client.server());
+    backup.wait_for(LIMIT, |received| !received.shares.is_empty()).await.unwrap();
+    prover.stop().await;
+}
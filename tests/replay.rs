@@ -0,0 +1,50 @@
+// Recorded pool traffic replayed to a live client, which has to answer as it did when recorded.
+
+mod common;
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use aleoxminer::{
+    client::{self, Client},
+    prover::Prover,
+    testing::{self, FakeBackend},
+    transport::DuplexConnector,
+};
+use common::{eventually, LIMIT};
+use tokio::time::timeout;
+
+const SERVER: &str = "recorded.pool:4040";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+// Connects `client` through a duplex pipe and replays `recording` to it.
+async fn replay(client: &Arc<Client>, recording: &str) -> anyhow::Result<()> {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    client.set_connector(Arc::new(connector));
+    // Keepalives cross the recorded frames, the replay has to skip them.
+    client.set_keepalive(Some(Duration::from_millis(20)), Duration::from_secs(1));
+    let prover = Prover::new(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))), client.clone())?;
+    client::start(prover.event_sender(), client.clone());
+    timeout(LIMIT, testing::replay(&fixture(recording), connections)).await?
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn revoked_session_replays() {
+    let client = testing::client(SERVER, "replay");
+    // Recorded with both offered.
+    client.set_speculative(true);
+    client.set_accept_pool_info(true);
+    replay(&client, "revoked_session.axrec").await.unwrap();
+    assert!(eventually(LIMIT, || client.stats().authorized).await);
+    let pool_info = client.pool_info().expect("the recorded PoolInfo");
+    assert_eq!(pool_info.get("balance").map(String::as_str), Some("12.5"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn diverging_client_fails_the_replay() {
+    let client = testing::client(SERVER, "another-rig");
+    let error = replay(&client, "revoked_session.axrec").await.unwrap_err();
+    assert!(error.to_string().starts_with("record 0:"), "{}", error);
+}
@@ -0,0 +1,126 @@
+// Generated messages go through the Encoder and back through the Decoder unchanged, in both wire
+// formats and whatever the boundaries the frames arrive in. Templates and proofs come from the
+// fixtures, generating valid ones isn't practical.
+
+use std::collections::BTreeMap;
+
+use aleoxminer::{
+    message::{Code, ProtocolLimits, ProverCodec, ProverMessage},
+    testing,
+};
+use bytes::BytesMut;
+use proptest::{collection::vec, prelude::*, sample::Index};
+use tokio_util::codec::{Decoder, Encoder};
+
+// Any characters, control and multi-byte ones included, within the string limit.
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![Just(String::new()), "(?s).{0,64}"]
+}
+
+fn code() -> impl Strategy<Value = Code> {
+    prop_oneof![
+        Just(Code::Success),
+        Just(Code::InvalidProof),
+        Just(Code::Stale),
+        Just(Code::ProxyException),
+        Just(Code::Other),
+    ]
+}
+
+fn message() -> impl Strategy<Value = ProverMessage> {
+    prop_oneof![
+        (text(), text(), text(), any::<u16>())
+            .prop_map(|(account, worker, password, version)| {
+                ProverMessage::Authorize(account, worker, password.into(), version)
+            }),
+        (any::<bool>(), proptest::option::of(text()))
+            .prop_map(|(result, message)| ProverMessage::AuthorizeResult(result, message)),
+        (any::<u32>(), any::<i64>(), any::<u64>(), any::<bool>(), any::<bool>()).prop_map(
+            |(height, timestamp, target, reorg, speculative)| {
+                ProverMessage::Notify(testing::template_at(height, timestamp), target, reorg, speculative)
+            }
+        ),
+        any::<u32>().prop_map(|height| {
+            let header = testing::header();
+            ProverMessage::Submit(height, header.nonce(), header.proof().clone())
+        }),
+        (code(), proptest::option::of(text())).prop_map(|(code, message)| ProverMessage::SubmitResult(code, message)),
+        Just(()).prop_map(|_| ProverMessage::Canary),
+        any::<u64>().prop_map(ProverMessage::ProofRate),
+        any::<u32>().prop_map(ProverMessage::ActivateJob),
+        (any::<u32>(), any::<u64>()).prop_map(|(height, target)| ProverMessage::JobAck(height, target)),
+        vec((text(), text()), 0..4)
+            .prop_map(|fields| ProverMessage::PoolInfo(fields.into_iter().collect::<BTreeMap<_, _>>())),
+        text().prop_map(ProverMessage::Worker),
+    ]
+}
+
+// Encodes copies, `ProverMessage` isn't Clone.
+fn encode(messages: &[ProverMessage], msgpack: bool) -> BytesMut {
+    let mut codec = ProverCodec::new(ProtocolLimits::default());
+    if msgpack {
+        codec.use_msgpack();
+    }
+    let mut bytes = BytesMut::new();
+    for message in messages {
+        codec.encode(copy(message), &mut bytes).unwrap();
+    }
+    bytes
+}
+
+// Decodes `bytes` handed over in pieces split at `splits`.
+fn decode(bytes: &[u8], splits: &[Index]) -> Result<Vec<ProverMessage>, TestCaseError> {
+    let mut splits: Vec<usize> = splits.iter().map(|split| split.index(bytes.len() + 1)).collect();
+    splits.push(bytes.len());
+    splits.sort_unstable();
+
+    let mut codec = ProverCodec::new(ProtocolLimits::default());
+    let mut buffer = BytesMut::new();
+    let mut decoded = Vec::new();
+    let mut start = 0;
+    for end in splits {
+        buffer.extend_from_slice(&bytes[start..end]);
+        start = end;
+        while let Some(message) = codec.decode(&mut buffer).map_err(|e| TestCaseError::fail(e.to_string()))? {
+            decoded.push(message);
+        }
+    }
+    prop_assert!(buffer.is_empty(), "{} bytes left over", buffer.len());
+    Ok(decoded)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn binary_and_json_round_trip(messages in vec(message(), 1..4), splits in vec(any::<Index>(), 0..6)) {
+        let bytes = encode(&messages, false);
+        prop_assert_eq!(decode(&bytes, &splits)?, messages);
+    }
+
+    #[test]
+    fn msgpack_round_trips(messages in vec(message(), 1..4), splits in vec(any::<Index>(), 0..6)) {
+        let bytes = encode(&messages, true);
+        prop_assert_eq!(decode(&bytes, &splits)?, messages);
+    }
+}
+
+fn copy(message: &ProverMessage) -> ProverMessage {
+    match message {
+        ProverMessage::Authorize(account, worker, password, version) => {
+            ProverMessage::Authorize(account.clone(), worker.clone(), password.clone(), *version)
+        }
+        ProverMessage::AuthorizeResult(result, message) => ProverMessage::AuthorizeResult(*result, message.clone()),
+        ProverMessage::Notify(template, target, reorg, speculative) => {
+            ProverMessage::Notify(template.clone(), *target, *reorg, *speculative)
+        }
+        ProverMessage::Submit(height, nonce, proof) => ProverMessage::Submit(*height, *nonce, proof.clone()),
+        ProverMessage::SubmitResult(code, message) => ProverMessage::SubmitResult(code.clone(), message.clone()),
+        ProverMessage::Canary => ProverMessage::Canary,
+        ProverMessage::ProofRate(rate) => ProverMessage::ProofRate(*rate),
+        ProverMessage::ActivateJob(height) => ProverMessage::ActivateJob(*height),
+        ProverMessage::JobAck(height, target) => ProverMessage::JobAck(*height, *target),
+        ProverMessage::PoolInfo(fields) => ProverMessage::PoolInfo(fields.clone()),
+        ProverMessage::Worker(worker) => ProverMessage::Worker(worker.clone()),
+    }
+}
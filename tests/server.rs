@@ -0,0 +1,223 @@
+// The pool side session state machine driven through valid and violating message sequences.
+
+use std::collections::BTreeMap;
+
+use aleoxminer::{
+    message::{
+        Code,
+        ProverMessage,
+        JOB_ACK_CAPABILITY,
+        MSGPACK_CAPABILITY,
+        MULTIPLEX_CAPABILITY,
+        POOL_INFO_CAPABILITY,
+        SESSION_CAPABILITY,
+        SPECULATIVE_CAPABILITY,
+    },
+    server::{Action, PoolSession, SessionState, SessionStats},
+    testing,
+};
+
+// The protocol version without any capability.
+const VERSION: u16 = 1;
+
+fn authorize(version: u16, password: &str) -> ProverMessage {
+    ProverMessage::Authorize("account".to_string(), "rig1".to_string(), password.into(), version)
+}
+
+fn submit(height: u32) -> ProverMessage {
+    let header = testing::header();
+    ProverMessage::Submit(height, header.nonce(), header.proof().clone())
+}
+
+// A session authorized with `version`, mining block 2.
+fn authorized(version: u16) -> PoolSession {
+    let mut session = PoolSession::new();
+    assert!(matches!(session.receive(authorize(version, "x")), Action::Authorize { .. }));
+    session.authorized(true, None);
+    assert!(session.notify(testing::template(2), 1000).is_some());
+    session
+}
+
+#[test]
+fn runs_a_session() {
+    let mut session = PoolSession::new();
+    assert_eq!(session.state(), SessionState::Connected);
+    assert!(session.notify(testing::template(2), 1000).is_none());
+
+    match session.receive(authorize(VERSION, "x")) {
+        Action::Authorize { account, worker, password, version } => {
+            assert_eq!((account.as_str(), worker.as_str(), version), ("account", "rig1", VERSION));
+            assert_eq!(password.expose(), "x");
+        }
+        action => panic!("unexpected {:?}", action),
+    }
+    assert_eq!(session.state(), SessionState::Authorizing);
+    assert_eq!(session.identity(), Some(("account", "rig1")));
+    assert_eq!(session.authorized(true, None), ProverMessage::AuthorizeResult(true, None));
+    assert_eq!(session.state(), SessionState::Authorized);
+
+    let notify = session.notify(testing::template(2), 1000).unwrap();
+    assert!(matches!(notify, ProverMessage::Notify(_, 1000, false, false)));
+    match session.receive(submit(2)) {
+        Action::ForwardShare { worker: None, height: 2, nonce, .. } => assert_eq!(nonce, testing::header().nonce()),
+        action => panic!("unexpected {:?}", action),
+    }
+    assert_eq!(session.share_result(Code::Success, None), ProverMessage::SubmitResult(Code::Success, None));
+    match session.receive(submit(1)) {
+        Action::Reply(reply) => assert_eq!(reply, ProverMessage::SubmitResult(Code::Stale, None)),
+        action => panic!("unexpected {:?}", action),
+    }
+    assert!(matches!(session.receive(ProverMessage::ProofRate(1234)), Action::ProofRate(1234)));
+    assert!(matches!(session.receive(ProverMessage::Canary), Action::Reply(ProverMessage::Canary)));
+    session.receive(submit(2));
+    session.share_result(Code::InvalidProof, Some("bad proof".to_string()));
+    assert_eq!(session.state(), SessionState::Authorized);
+
+    let expected = SessionStats {
+        messages: 6,
+        notifies: 1,
+        shares: 3,
+        accepted: 1,
+        rejected: 1,
+        stale: 1,
+    };
+    assert_eq!(session.stats(), &expected);
+}
+
+#[test]
+fn closes_on_violating_sequences() {
+    let cases: Vec<(Vec<ProverMessage>, &str)> = vec![
+        (vec![submit(2)], "Submit before Authorize"),
+        (vec![ProverMessage::Canary], "Canary before Authorize"),
+        (vec![authorize(VERSION, "x"), authorize(VERSION, "x")], "Authorize sent twice"),
+        (vec![authorize(VERSION, "x"), submit(2)], "Submit before the authorization result"),
+    ];
+    for (messages, reason) in cases {
+        let mut session = PoolSession::new();
+        let last = messages.len() - 1;
+        for (index, message) in messages.into_iter().enumerate() {
+            match session.receive(message) {
+                Action::ProtocolViolation(violation) => {
+                    assert_eq!(index, last, "{}", violation);
+                    assert_eq!(violation, reason);
+                }
+                action => assert!(index < last, "{}: {:?}", reason, action),
+            }
+        }
+        assert_eq!(session.state(), SessionState::Closed, "{}", reason);
+        // Nothing is accepted after that.
+        match session.receive(ProverMessage::Canary) {
+            Action::ProtocolViolation(violation) => assert_eq!(violation, "Canary after the session was closed"),
+            action => panic!("unexpected {:?}", action),
+        }
+    }
+
+    let pool_messages = vec![
+        (ProverMessage::AuthorizeResult(true, None), "AuthorizeResult is not sent by miners"),
+        (testing::notify(), "Notify is not sent by miners"),
+        (ProverMessage::SubmitResult(Code::Success, None), "SubmitResult is not sent by miners"),
+        (ProverMessage::ActivateJob(3), "ActivateJob is not sent by miners"),
+        (ProverMessage::PoolInfo(BTreeMap::new()), "PoolInfo is not sent by miners"),
+        (ProverMessage::Worker("rig2".to_string()), "Worker is not sent by miners"),
+        (authorize(VERSION, "x"), "Authorize sent twice"),
+    ];
+    for (message, reason) in pool_messages {
+        let mut session = authorized(VERSION);
+        match session.receive(message) {
+            Action::ProtocolViolation(violation) => assert_eq!(violation, reason),
+            action => panic!("{}: unexpected {:?}", reason, action),
+        }
+        assert_eq!(session.state(), SessionState::Closed);
+        assert!(session.notify(testing::template(3), 1000).is_none());
+    }
+}
+
+#[test]
+fn a_rejected_session_is_closed() {
+    let mut session = PoolSession::new();
+    session.receive(authorize(VERSION, "x"));
+    let reply = session.authorized(false, Some("unknown account".to_string()));
+    assert_eq!(reply, ProverMessage::AuthorizeResult(false, Some("unknown account".to_string())));
+    assert_eq!(session.state(), SessionState::Closed);
+    assert!(session.notify(testing::template(2), 1000).is_none());
+    assert!(matches!(session.receive(ProverMessage::ProofRate(1)), Action::ProtocolViolation(_)));
+}
+
+#[test]
+fn reads_the_capabilities_and_password_fields() {
+    let mut session = PoolSession::new();
+    session.receive(authorize(VERSION, "x"));
+    assert!(!session.msgpack() && !session.resumes_sessions() && !session.takes_speculative());
+    assert!(!session.sends_job_acks() && !session.takes_pool_info() && !session.multiplexes());
+    assert_eq!((session.difficulty_hint(), session.session_token()), (None, None));
+
+    let all = MSGPACK_CAPABILITY
+        | SESSION_CAPABILITY
+        | SPECULATIVE_CAPABILITY
+        | JOB_ACK_CAPABILITY
+        | POOL_INFO_CAPABILITY
+        | MULTIPLEX_CAPABILITY;
+    let mut session = PoolSession::new();
+    session.receive(authorize(VERSION | all, "x,d=64,session=abc"));
+    assert!(session.msgpack() && session.resumes_sessions() && session.takes_speculative());
+    assert!(session.sends_job_acks() && session.takes_pool_info() && session.multiplexes());
+    assert_eq!((session.difficulty_hint(), session.session_token()), (Some(64), Some("abc")));
+    let resumed = ProverMessage::AuthorizeResult(true, Some("session=abc".to_string()));
+    assert_eq!(session.authorized_session("abc"), resumed);
+
+    // Miners not resuming sessions get no token.
+    let mut session = PoolSession::new();
+    session.receive(authorize(VERSION, "x"));
+    assert_eq!(session.authorized_session("abc"), ProverMessage::AuthorizeResult(true, None));
+}
+
+#[test]
+fn sends_the_optional_messages_only_to_miners_taking_them() {
+    let fields = BTreeMap::from([("balance".to_string(), "1.5".to_string())]);
+    let mut session = authorized(VERSION);
+    assert!(session.speculative_notify(testing::template(3), 1000).is_none());
+    assert!(session.activate(3).is_none());
+    assert!(session.pool_info(fields.clone()).is_none());
+
+    let mut session = authorized(VERSION | SPECULATIVE_CAPABILITY | POOL_INFO_CAPABILITY);
+    let speculative = session.speculative_notify(testing::template(3), 1000).unwrap();
+    assert!(matches!(speculative, ProverMessage::Notify(_, 1000, false, true)));
+    // Still on block 2 until activated.
+    assert!(matches!(session.receive(submit(2)), Action::ForwardShare { height: 2, .. }));
+    assert_eq!(session.activate(3), Some(ProverMessage::ActivateJob(3)));
+    assert!(matches!(session.receive(submit(2)), Action::Reply(ProverMessage::SubmitResult(Code::Stale, None))));
+    assert_eq!(session.pool_info(fields.clone()), Some(ProverMessage::PoolInfo(fields)));
+}
+
+#[test]
+fn flags_a_lower_height_as_a_reorg() {
+    let mut session = authorized(VERSION);
+    let notify = session.notify(testing::template(5), 1000).unwrap();
+    assert!(matches!(notify, ProverMessage::Notify(_, _, false, false)));
+    let notify = session.notify(testing::template(4), 1000).unwrap();
+    assert!(matches!(notify, ProverMessage::Notify(_, _, true, false)));
+}
+
+#[test]
+fn tracks_the_lag_of_the_acknowledged_job() {
+    let mut session = authorized(VERSION | JOB_ACK_CAPABILITY);
+    assert_eq!(session.lag(), None);
+    session.notify(testing::template(5), 1000);
+    match session.receive(ProverMessage::JobAck(3, 1000)) {
+        Action::JobAck { height, pool_target, lag } => assert_eq!((height, pool_target, lag), (3, 1000, 2)),
+        action => panic!("unexpected {:?}", action),
+    }
+    assert_eq!((session.acked(), session.lag()), (Some((3, 1000)), Some(2)));
+}
+
+#[test]
+fn attributes_a_share_to_the_worker_named_before_it() {
+    let mut session = authorized(VERSION | MULTIPLEX_CAPABILITY);
+    assert!(matches!(session.receive(ProverMessage::Worker("rig2".to_string())), Action::None));
+    match session.receive(submit(2)) {
+        Action::ForwardShare { worker, .. } => assert_eq!(worker.as_deref(), Some("rig2")),
+        action => panic!("unexpected {:?}", action),
+    }
+    // Only for the next share.
+    assert!(matches!(session.receive(submit(2)), Action::ForwardShare { worker: None, .. }));
+}
@@ -0,0 +1,81 @@
+// A `MiningSession` built the way embedders build it, run against the mock pool.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use aleoxminer::{
+    events::MinerEvent,
+    session::{MiningSession, MiningSessionBuilder},
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+    transport::DuplexConnector,
+};
+use common::{eventually, LIMIT};
+use tokio::time::{sleep, timeout};
+
+fn builder(pool: &str) -> MiningSessionBuilder {
+    MiningSession::builder()
+        .pool(pool.to_string())
+        .address(Some(testing::address()))
+        .worker(Some("session".to_string()))
+        .prover(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mines_until_shut_down() {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let pool = MockPool::duplex(connections);
+    pool.notify(testing::template(2), ALL_SHARES);
+    let session = builder(&pool.address()).connector(Arc::new(connector)).build().unwrap();
+    let mut events = session.events().subscribe();
+    session.start().await.unwrap();
+
+    let accepted = timeout(LIMIT, async {
+        loop {
+            // Falling behind only skips events, later shares are accepted too.
+            if let Ok(MinerEvent::ShareAccepted(result)) = events.recv().await {
+                return result;
+            }
+        }
+    })
+    .await
+    .expect("no share accepted");
+    assert_eq!(accepted.height, 2);
+    let received = pool.received();
+    assert_eq!(received.authorizations.len(), 1);
+    assert_eq!(received.authorizations[0].1, "session");
+
+    let status = session.status();
+    assert_eq!((status.pool.as_str(), status.worker.as_str()), (pool.address().as_str(), "session"));
+    assert!(status.connected && status.authorized);
+    assert!(eventually(LIMIT, || session.status().shares.accepted >= 1).await);
+
+    timeout(LIMIT, session.shutdown()).await.expect("the session did not shut down");
+    assert!(!session.prover().stats().running);
+    // Nothing is proven or submitted after that.
+    let shares = pool.received().shares.len();
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(pool.received().shares.len(), shares);
+}
+
+#[test]
+fn refuses_incomplete_settings() {
+    let cases: Vec<(MiningSessionBuilder, &str)> = vec![
+        (MiningSession::builder().address(Some(testing::address())), "no pool given"),
+        (builder("pool.example.com:4040").address(None), "no account or address given"),
+        (
+            MiningSession::builder().pool("pool.example.com:4040".to_string()).address(Some(testing::address())),
+            "no prover settings given",
+        ),
+        (
+            builder("pool.example.com:4040").split(vec![("backup.example.com:4040".to_string(), 1)]),
+            "the split has to start with the main pool",
+        ),
+    ];
+    for (builder, expected) in cases {
+        match builder.build() {
+            Ok(_) => panic!("built despite {}", expected),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+}
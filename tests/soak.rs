@@ -0,0 +1,81 @@
+// Thousands of reconnects against a mock pool dropping the connection every few hundred milliseconds,
+// catching whatever leaks per connection. Slow, run with `cargo test --test soak -- --ignored`.
+
+mod common;
+
+use std::{fs, sync::Arc, time::Duration};
+
+use aleoxminer::{
+    client,
+    prover::Prover,
+    testing::{self, FakeBackend, MockPool, NO_SHARES},
+    transport::DuplexConnector,
+};
+use common::{eventually, LIMIT};
+use tokio::{runtime::Handle, time::sleep};
+
+const CYCLES: u32 = 3000;
+// Connections made before the baseline is taken, buffers and pools have grown to size by then.
+const WARMUP: u32 = 20;
+const CONNECTED: Duration = Duration::from_millis(300);
+// Slack over the baseline, a connection's tasks may still be winding down when counted.
+const MAX_EXTRA_TASKS: usize = 4;
+const MAX_RSS_GROWTH: u64 = 32 * 1024 * 1024;
+
+// Resident set size in bytes, where the platform tells.
+fn rss() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+#[tokio::test(start_paused = true)]
+#[ignore]
+async fn reconnects_without_leaking() {
+    let (connector, connections) = DuplexConnector::new(64 * 1024);
+    let pool = MockPool::duplex(connections);
+    pool.notify(testing::template(2), NO_SHARES);
+    let client = pool.client("soak");
+    client.set_connector(Arc::new(connector));
+    let backend = FakeBackend::new(Duration::from_millis(5));
+    let prover = Prover::new(testing::prover_config(16, backend), client.clone()).unwrap();
+    prover.start().await.unwrap();
+    client::start(prover.event_sender(), client.clone());
+
+    let mut baseline = None;
+    for cycle in 0..CYCLES {
+        let connected = || pool.connected() == 1 && client.stats().authorized;
+        assert!(eventually(LIMIT, connected).await, "cycle {}: not connected", cycle);
+        sleep(CONNECTED).await;
+        let usage = (Handle::current().metrics().num_alive_tasks(), rss());
+        match baseline {
+            None if cycle == WARMUP => baseline = Some(usage),
+            None => {}
+            Some((tasks, rss)) => {
+                assert!(
+                    usage.0 <= tasks + MAX_EXTRA_TASKS,
+                    "cycle {}: {} tasks, {} at the baseline",
+                    cycle,
+                    usage.0,
+                    tasks
+                );
+                if let (Some(baseline), Some(now)) = (rss, usage.1) {
+                    assert!(
+                        now <= baseline + MAX_RSS_GROWTH,
+                        "cycle {}: RSS grew from {} to {} bytes",
+                        cycle,
+                        baseline,
+                        now
+                    );
+                }
+            }
+        }
+        pool.disconnect();
+        assert!(eventually(LIMIT, || pool.connected() == 0).await, "cycle {}: still connected", cycle);
+    }
+
+    assert!(eventually(LIMIT, || pool.connected() == 1).await);
+    assert_eq!(pool.received().connections, u64::from(CYCLES) + 1);
+    assert_eq!(client.stats().reconnects, CYCLES);
+    prover.stop().await;
+}
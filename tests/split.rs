@@ -0,0 +1,102 @@
+// Proving time split between pools by weight, and the shares of each pool's work going back to it.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use aleoxminer::{
+    session::MiningSession,
+    split::Split,
+    testing::{self, FakeBackend, MockPool, ALL_SHARES},
+};
+use common::{eventually, LIMIT};
+use tokio::time::timeout;
+
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+// Mines `ticks` one second ticks from `start` on the pool `split` picks of the `available` ones.
+fn mine(split: &mut Split, available: &[bool], start: Instant, ticks: u64) -> Vec<usize> {
+    (0..ticks)
+        .map(|tick| {
+            let pool = split.next(available).unwrap();
+            split.record(pool, start + Duration::from_secs(tick));
+            pool
+        })
+        .collect()
+}
+
+fn close(realized: &[f64], expected: &[f64]) -> bool {
+    realized.iter().zip(expected).all(|(realized, expected)| (realized - expected).abs() < 0.01)
+}
+
+#[test]
+fn hands_out_the_time_by_weight() {
+    let mut split = Split::new(&[3, 1], WINDOW);
+    assert_eq!(split.targets(), &[0.75, 0.25]);
+    assert_eq!(split.realized(), vec![0.0, 0.0]);
+    mine(&mut split, &[true, true], Instant::now(), 400);
+    assert!(close(&split.realized(), &[0.75, 0.25]), "{:?}", split.realized());
+}
+
+#[test]
+fn makes_up_for_the_time_of_a_pool_that_was_down() {
+    let start = Instant::now();
+    let mut split = Split::new(&[1, 1], WINDOW);
+    assert_eq!(mine(&mut split, &[true, false], start, 100), vec![0; 100]);
+    assert_eq!(split.next(&[false, false]), None);
+
+    // The pool back gets every tick until it caught up.
+    let caught_up = mine(&mut split, &[true, true], start + Duration::from_secs(100), 100);
+    assert_eq!(caught_up, vec![1; 100]);
+    assert_eq!(split.realized(), vec![0.5, 0.5]);
+}
+
+#[test]
+fn measures_over_the_window_only() {
+    let start = Instant::now();
+    let mut split = Split::new(&[1, 1], Duration::from_secs(100));
+    mine(&mut split, &[true, false], start, 100);
+    // Once the ticks of pool 0 left the window, pool 1 has all of it.
+    mine(&mut split, &[false, true], start + Duration::from_secs(100), 101);
+    assert_eq!(split.realized(), vec![0.0, 1.0]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submits_the_shares_to_the_pool_the_work_came_from() {
+    let main = MockPool::start().await.unwrap();
+    let backup = MockPool::start().await.unwrap();
+    main.notify(testing::template(2), ALL_SHARES);
+    let session = MiningSession::builder()
+        .pool(main.address())
+        .address(Some(testing::address()))
+        .worker(Some("split".to_string()))
+        .prover(testing::prover_config(16, FakeBackend::new(Duration::from_millis(5))))
+        .split(vec![(main.address(), 1), (backup.address(), 1)])
+        .build()
+        .unwrap();
+    session.start().await.unwrap();
+    main.wait_for(LIMIT, |received| received.shares.len() >= 3).await.unwrap();
+    assert!(backup.received().shares.is_empty());
+
+    // With the main pool down the backup gets the time.
+    backup.notify(testing::template(3), ALL_SHARES);
+    main.disconnect();
+    backup.wait_for(LIMIT, |received| received.shares.len() >= 3).await.unwrap();
+    let backup_active = || {
+        let split = session.prover().stats().split;
+        split.iter().any(|share| share.active && share.server == backup.address())
+    };
+    assert!(eventually(LIMIT, backup_active).await);
+
+    let split = session.prover().stats().split;
+    assert_eq!(split.len(), 2);
+    assert_eq!((split[0].server.as_str(), split[0].target), (main.address().as_str(), 0.5));
+    assert_eq!((split[1].server.as_str(), split[1].target), (backup.address().as_str(), 0.5));
+    assert!(split.iter().all(|share| share.realized > 0.0), "{:?}", split);
+    assert!((split[0].realized + split[1].realized - 1.0).abs() < 1e-9, "{:?}", split);
+
+    // Each pool got only the shares of its own work.
+    assert!(main.received().shares.iter().all(|(height, _)| *height == 2));
+    assert!(backup.received().shares.iter().all(|(height, _)| *height == 3));
+    timeout(LIMIT, session.shutdown()).await.expect("the session did not shut down");
+}
@@ -0,0 +1,110 @@
+// The lifetime stats and session tokens kept on disk, in a scratch directory per test.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use aleoxminer::{
+    prover::Prover,
+    stats::{LifetimeStats, PoolStats, SessionTokens, StatsFile},
+    testing::{self, FakeBackend},
+};
+use common::scratch;
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.to_path_buf().into_os_string();
+    path.push(suffix);
+    path.into()
+}
+
+fn stats(valid_shares: u64, invalid_shares: u64) -> LifetimeStats {
+    LifetimeStats {
+        valid_shares,
+        invalid_shares,
+        total_proofs: 100,
+        uptime_secs: 3600,
+        pools: [("pool".to_string(), PoolStats { valid_shares, invalid_shares })].into_iter().collect(),
+        ..LifetimeStats::default()
+    }
+}
+
+#[test]
+fn starts_from_zero_without_a_file() {
+    let path = scratch("missing").join("stats.json");
+    assert_eq!(StatsFile::load(&path).previous(), &LifetimeStats::default());
+    assert!(!path.exists());
+}
+
+#[test]
+fn saves_and_loads_the_stats() {
+    let path = scratch("saved").join("stats.json");
+    StatsFile::load(&path).save(&stats(7, 2)).unwrap();
+
+    assert_eq!(StatsFile::load(&path).previous(), &stats(7, 2));
+    assert!(!with_suffix(&path, ".tmp").exists());
+}
+
+#[test]
+fn moves_a_corrupted_file_aside() {
+    let path = scratch("corrupted").join("stats.json");
+    fs::write(&path, b"{\"version\": 1, \"valid_sh").unwrap();
+
+    assert_eq!(StatsFile::load(&path).previous(), &LifetimeStats::default());
+    assert!(!path.exists());
+    assert_eq!(fs::read(with_suffix(&path, ".bak")).unwrap(), b"{\"version\": 1, \"valid_sh");
+}
+
+#[test]
+fn moves_a_file_of_another_version_aside() {
+    let path = scratch("version").join("stats.json");
+    let future = LifetimeStats { version: 2, ..stats(7, 2) };
+    fs::write(&path, serde_json::to_vec(&future).unwrap()).unwrap();
+
+    assert_eq!(StatsFile::load(&path).previous(), &LifetimeStats::default());
+    assert!(with_suffix(&path, ".bak").exists());
+}
+
+#[tokio::test]
+async fn merges_the_session_into_the_previous_runs() {
+    let client = testing::client("127.0.0.1:1", "worker");
+    let prover = Prover::new(testing::prover_config(1, FakeBackend::new(Duration::ZERO)), client).unwrap();
+    let mut session = prover.stats();
+    session.valid_shares = 3;
+    session.invalid_shares = 1;
+    session.total_proofs = 50;
+
+    let merged = stats(7, 2).merge(&session, Duration::from_secs(60), "pool");
+    assert_eq!((merged.valid_shares, merged.invalid_shares), (10, 3));
+    assert_eq!((merged.total_proofs, merged.uptime_secs), (150, 3660));
+    assert_eq!(merged.pools["pool"], PoolStats { valid_shares: 10, invalid_shares: 3 });
+
+    let merged = merged.merge(&session, Duration::ZERO, "other");
+    assert_eq!(merged.valid_shares, 13);
+    assert_eq!(merged.pools["pool"].valid_shares, 10);
+    assert_eq!(merged.pools["other"], PoolStats { valid_shares: 3, invalid_shares: 1 });
+}
+
+#[test]
+fn keeps_the_session_tokens() {
+    let path = scratch("sessions").join("stats.json");
+    let tokens = SessionTokens::load(&path);
+    tokens.set("pool/worker", Some("abc")).unwrap();
+    tokens.set("pool/other", Some("def")).unwrap();
+    tokens.set("pool/other", None).unwrap();
+
+    let tokens = SessionTokens::load(&path);
+    assert_eq!(tokens.get("pool/worker").as_deref(), Some("abc"));
+    assert_eq!(tokens.get("pool/other"), None);
+    assert!(!with_suffix(&path, ".sessions.tmp").exists());
+}
+
+#[test]
+fn starts_new_sessions_when_the_token_file_is_corrupted() {
+    let path = scratch("sessions-corrupted").join("stats.json");
+    fs::write(with_suffix(&path, ".sessions"), b"not json").unwrap();
+    assert_eq!(SessionTokens::load(&path).get("pool/worker"), None);
+}
@@ -0,0 +1,68 @@
+// Templates the client refuses to prove, one fixture per reason.
+
+use std::time::Duration;
+
+use aleoxminer::{
+    template::{validate, TemplateError},
+    testing,
+};
+use snarkvm::{dpc::testnet2::Testnet2, traits::Network};
+
+const NOW: i64 = 1_650_000_000;
+const MAX_SKEW: Duration = Duration::from_secs(60);
+
+fn target() -> u64 {
+    Testnet2::genesis_block().difficulty_target()
+}
+
+#[test]
+fn takes_a_plausible_template() {
+    let template = testing::template_at(10, NOW + 30);
+    assert_eq!(validate(&template, None, NOW, MAX_SKEW), Ok(()));
+    // A target change of up to twice per block.
+    assert_eq!(validate(&template, Some((8, target() / 3)), NOW, MAX_SKEW), Ok(()));
+    assert_eq!(validate(&template, Some((12, target() / 3)), NOW, MAX_SKEW), Ok(()));
+    // The same height again, or too long ago to tell.
+    assert_eq!(validate(&template, Some((10, target() / 1000)), NOW, MAX_SKEW), Ok(()));
+    assert_eq!(validate(&testing::template_at(50, NOW), Some((17, target() / 1000)), NOW, MAX_SKEW), Ok(()));
+}
+
+#[test]
+fn refuses_a_zero_target() {
+    let template = testing::template_with_target(10, NOW, 0);
+    assert_eq!(validate(&template, None, NOW, MAX_SKEW), Err(TemplateError::ZeroTarget));
+}
+
+#[test]
+fn refuses_a_target_built_for_another_height() {
+    let template = testing::template_at(10, NOW);
+    let previous = target() / 8;
+    let error = validate(&template, Some((8, previous)), NOW, MAX_SKEW).unwrap_err();
+    assert_eq!(
+        error,
+        TemplateError::ImplausibleTarget {
+            target: target(),
+            previous,
+            previous_height: 8,
+            blocks: 2,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        format!("difficulty target {} is implausible 2 blocks after {} at block 8", target(), previous)
+    );
+}
+
+#[test]
+fn refuses_a_timestamp_off_the_local_clock() {
+    for (offset, timestamp) in [(61, NOW + 61), (-90, NOW - 90)] {
+        let template = testing::template_at(10, timestamp);
+        let expected = TemplateError::Timestamp {
+            timestamp,
+            skew: offset.abs(),
+            max_skew: 60,
+        };
+        assert_eq!(validate(&template, None, NOW, MAX_SKEW), Err(expected));
+    }
+    assert_eq!(validate(&testing::template_at(10, NOW - 60), None, NOW, MAX_SKEW), Ok(()));
+}
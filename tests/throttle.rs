@@ -0,0 +1,68 @@
+// Bandwidth caps of a pool connection, timed on tokio's paused clock.
+
+use std::{sync::Arc, time::Duration};
+
+use aleoxminer::{
+    message::ProverCodec,
+    testing,
+    throttle::{Bandwidth, BandwidthLimits},
+};
+use bytes::BytesMut;
+use futures_util::SinkExt;
+use tokio::{
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    time::Instant,
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Encoder, Framed};
+
+#[tokio::test(start_paused = true)]
+async fn writes_at_the_capped_rate_after_a_burst() {
+    let bandwidth = Arc::new(Bandwidth::new(BandwidthLimits {
+        read: None,
+        write: Some(2048),
+    }));
+    let (miner, mut pool) = io::duplex(64 * 1024);
+    let mut miner = bandwidth.wrap(miner);
+    assert!(!bandwidth.write_exhausted());
+
+    // One second of traffic goes at once, the rest at 2 KiB per second.
+    let started = Instant::now();
+    miner.write_all(&[7; 2048]).await.unwrap();
+    assert_eq!(started.elapsed(), Duration::ZERO);
+    assert!(bandwidth.write_exhausted());
+    miner.write_all(&[7; 8 * 1024]).await.unwrap();
+    let took = started.elapsed();
+    assert!(took >= Duration::from_millis(3990) && took <= Duration::from_millis(4100), "{:?}", took);
+
+    let mut received = vec![0; 10 * 1024];
+    pool.read_exact(&mut received).await.unwrap();
+    assert!(received.iter().all(|byte| *byte == 7));
+    let stats = bandwidth.stats();
+    assert_eq!((stats.read_limit, stats.write_limit), (None, Some(2048)));
+    assert!((stats.write_rate - 2048.0).abs() < 100.0, "{}", stats.write_rate);
+    assert_eq!(stats.read_rate, 0.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn reads_a_notify_through_a_tiny_cap() {
+    let bandwidth = Arc::new(Bandwidth::new(BandwidthLimits {
+        read: Some(100),
+        write: None,
+    }));
+    let (miner, pool) = io::duplex(64 * 1024);
+    let mut frame = BytesMut::new();
+    ProverCodec::default().encode(testing::notify(), &mut frame).unwrap();
+    let mut pool = Framed::new(pool, ProverCodec::default());
+    pool.send(testing::notify()).await.unwrap();
+
+    // The smallest burst passes at once, the rest at 100 bytes per second in chunks of up to 512.
+    let started = Instant::now();
+    let mut miner = Framed::new(bandwidth.wrap(miner), ProverCodec::default());
+    let notify = miner.next().await.unwrap().unwrap();
+    let took = started.elapsed();
+    assert_eq!(notify, testing::notify());
+    let least = Duration::from_secs_f64(frame.len().saturating_sub(1024) as f64 / 100.0);
+    assert!(took + Duration::from_millis(10) >= least, "{:?} for {} bytes", took, frame.len());
+    assert!(took <= least + Duration::from_secs(6), "{:?} for {} bytes", took, frame.len());
+}